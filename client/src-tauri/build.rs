@@ -1,3 +1,35 @@
+use std::path::Path;
+
+use sha2::{Digest, Sha256};
+
+/// Bakes the SHA-256 of the server sidecar into the shell as
+/// `NYX_SERVER_SHA256`, so it can refuse to run a binary that was swapped
+/// after install. CI can pass the hash in directly instead.
+fn embed_server_hash() {
+  println!("cargo:rerun-if-env-changed=NYX_SERVER_SHA256");
+  if std::env::var("NYX_SERVER_SHA256").is_ok_and(|hash| !hash.is_empty()) {
+    return;
+  }
+
+  let target = std::env::var("TARGET").unwrap_or_default();
+  let suffix = if target.contains("windows") { ".exe" } else { "" };
+  let sidecar = format!("binaries/nyx-server-{}{}", target, suffix);
+  println!("cargo:rerun-if-changed={}", sidecar);
+  match std::fs::read(Path::new(&sidecar)) {
+    Ok(bytes) => {
+      let hash: String = Sha256::digest(&bytes)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+      println!("cargo:rustc-env=NYX_SERVER_SHA256={}", hash);
+    }
+    Err(e) => println!("cargo:warning=Not embedding a server hash, {}: {}", sidecar, e),
+  }
+}
+
 fn main() {
+  embed_server_hash();
+  // The key server update manifests are signed with; see `server/update.rs`
+  println!("cargo:rerun-if-env-changed=NYX_SERVER_UPDATE_PUBKEY");
   tauri_build::build()
 }
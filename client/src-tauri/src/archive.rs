@@ -0,0 +1,501 @@
+//! Native extraction of zip, tar.gz and 7z archives, so installs and imports
+//! do not depend on `unzip`, `tar` or `7z` being on PATH.
+//!
+//! Entries that would land outside the destination, through `..`, absolute
+//! paths or symlinks, are refused rather than skipped. Nothing is written
+//! through a symlink: symlinks in zips are only created once every file is
+//! out, and their targets may only climb up with leading `..`, so no chain
+//! of them leads outside. Progress of the `extract_archive` command is
+//! emitted as `archive://progress`.
+
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::path::{Component, Path, PathBuf};
+use std::time::Instant;
+
+use flate2::read::GzDecoder;
+use serde::Serialize;
+use sevenz_rust::{Password, SevenZReader};
+use tauri::{AppHandle, Emitter};
+use zip::ZipArchive;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ArchiveKind {
+    Zip,
+    TarGz,
+    SevenZip,
+}
+
+impl ArchiveKind {
+    /// Recognises an archive by its file name.
+    pub fn detect(path: &Path) -> Option<Self> {
+        let name = path.file_name()?.to_string_lossy().to_lowercase();
+        if name.ends_with(".zip") {
+            Some(Self::Zip)
+        } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Some(Self::TarGz)
+        } else if name.ends_with(".7z") {
+            Some(Self::SevenZip)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExtractProgress {
+    pub files: u64,
+    /// Uncompressed bytes written so far.
+    pub bytes: u64,
+    /// Uncompressed size of the whole archive, where the format records it.
+    pub total_bytes: Option<u64>,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ProgressPayload {
+    path: String,
+    dest: String,
+    #[serde(flatten)]
+    progress: ExtractProgress,
+}
+
+/// Counts what has been written and reports it every so often.
+struct Tracker<F> {
+    progress: ExtractProgress,
+    last_report: Instant,
+    report: F,
+}
+
+impl<F: FnMut(&ExtractProgress)> Tracker<F> {
+    fn new(total_bytes: Option<u64>, report: F) -> Self {
+        Self {
+            progress: ExtractProgress {
+                total_bytes,
+                ..Default::default()
+            },
+            last_report: Instant::now(),
+            report,
+        }
+    }
+
+    fn file_done(&mut self, bytes: u64) {
+        self.progress.files += 1;
+        self.progress.bytes += bytes;
+        if self.last_report.elapsed() >= crate::PROGRESS_INTERVAL {
+            self.last_report = Instant::now();
+            (self.report)(&self.progress);
+        }
+    }
+
+    fn finish(mut self) -> ExtractProgress {
+        (self.report)(&self.progress);
+        self.progress
+    }
+}
+
+/// `name` relative to `dest`, if it stays inside it.
+pub(crate) fn contained(dest: &Path, name: &Path) -> Option<PathBuf> {
+    let mut path = dest.to_path_buf();
+    for component in name.components() {
+        match component {
+            Component::Normal(part) => path.push(part),
+            Component::CurDir => {}
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+    Some(path)
+}
+
+/// Where a symlink at `link` pointing to `target` resolves, if that is
+/// still inside `dest`. Relative targets may climb back up with `..`, but
+/// only before their first name: after one, `..` would climb out of
+/// wherever that name links to rather than out of the name.
+#[cfg(any(unix, test))]
+fn link_target(dest: &Path, link: &Path, target: &Path) -> Option<PathBuf> {
+    if target.is_absolute() {
+        return None;
+    }
+    let mut path = link.parent()?.to_path_buf();
+    let mut descended = false;
+    for component in target.components() {
+        match component {
+            Component::Normal(part) => {
+                path.push(part);
+                descended = true;
+            }
+            Component::CurDir => {}
+            Component::ParentDir if !descended => {
+                if !path.pop() {
+                    return None;
+                }
+            }
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+    path.starts_with(dest).then_some(path)
+}
+
+/// Refuses `target` if it, or any directory between `dest` and it, is a
+/// symlink, so writing there cannot be redirected outside `dest`.
+fn check_unlinked(dest: &Path, target: &Path) -> io::Result<()> {
+    let relative = target
+        .strip_prefix(dest)
+        .map_err(|_| traversal(target.display()))?;
+    let mut path = dest.to_path_buf();
+    for component in relative.components() {
+        path.push(component);
+        match std::fs::symlink_metadata(&path) {
+            Ok(metadata) if metadata.file_type().is_symlink() => {
+                return Err(traversal(target.display()))
+            }
+            Ok(_) => {}
+            Err(e) if e.kind() == io::ErrorKind::NotFound => break,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+fn traversal(name: impl std::fmt::Display) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("Archive entry {} points outside the destination", name),
+    )
+}
+
+/// Creates the directory `target` inside `dest`.
+fn create_dir(dest: &Path, target: &Path) -> io::Result<()> {
+    check_unlinked(dest, target)?;
+    std::fs::create_dir_all(target)
+}
+
+/// Writes `reader` to `target` inside `dest`, creating its parent
+/// directories.
+fn write_file(dest: &Path, target: &Path, reader: &mut dyn Read) -> io::Result<u64> {
+    check_unlinked(dest, target)?;
+    if let Some(parent) = target.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut out = File::create(target)?;
+    let written = io::copy(reader, &mut out)?;
+    out.flush()?;
+    Ok(written)
+}
+
+fn extract_zip(
+    path: &Path,
+    dest: &Path,
+    on_progress: impl FnMut(&ExtractProgress),
+) -> io::Result<ExtractProgress> {
+    let mut archive = ZipArchive::new(File::open(path)?).map_err(io::Error::other)?;
+    let total = (0..archive.len())
+        .filter_map(|index| archive.by_index_raw(index).ok().map(|entry| entry.size()))
+        .sum();
+    let mut tracker = Tracker::new(Some(total), on_progress);
+    #[cfg(unix)]
+    let mut links = Vec::new();
+    for index in 0..archive.len() {
+        let mut entry = archive.by_index(index).map_err(io::Error::other)?;
+        let target = entry
+            .enclosed_name()
+            .and_then(|name| contained(dest, &name))
+            .ok_or_else(|| traversal(entry.name()))?;
+        if entry.is_dir() {
+            create_dir(dest, &target)?;
+            continue;
+        }
+        // App bundles on macOS rely on the symlinks in their frameworks
+        #[cfg(unix)]
+        if entry
+            .unix_mode()
+            .is_some_and(|mode| mode & 0o170000 == 0o120000)
+        {
+            let mut link = String::new();
+            entry.read_to_string(&mut link)?;
+            if link_target(dest, &target, Path::new(&link)).is_none() {
+                return Err(traversal(entry.name()));
+            }
+            links.push((target, link));
+            continue;
+        }
+        let written = write_file(dest, &target, &mut entry)?;
+        #[cfg(unix)]
+        if let Some(mode) = entry.unix_mode() {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&target, std::fs::Permissions::from_mode(mode & 0o777))?;
+        }
+        tracker.file_done(written);
+    }
+    #[cfg(unix)]
+    for (target, link) in links {
+        check_unlinked(dest, &target)?;
+        if let Some(parent) = target.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::os::unix::fs::symlink(&link, &target)?;
+    }
+    Ok(tracker.finish())
+}
+
+fn extract_tar_gz(
+    path: &Path,
+    dest: &Path,
+    on_progress: impl FnMut(&ExtractProgress),
+) -> io::Result<ExtractProgress> {
+    let mut archive = tar::Archive::new(GzDecoder::new(File::open(path)?));
+    archive.set_preserve_permissions(true);
+    let mut tracker = Tracker::new(None, on_progress);
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let name = entry.path()?.into_owned();
+        let Some(target) = contained(dest, &name) else {
+            return Err(traversal(name.display()));
+        };
+        if entry.header().entry_type().is_symlink() {
+            let link = entry.link_name()?.unwrap_or_default().into_owned();
+            if link_target(dest, &target, &link).is_none() {
+                return Err(traversal(name.display()));
+            }
+        }
+        // Also refuses links and parents that resolve outside `dest`
+        if !entry.unpack_in(dest)? {
+            return Err(traversal(name.display()));
+        }
+        if entry.header().entry_type().is_file() {
+            tracker.file_done(entry.size());
+        }
+    }
+    Ok(tracker.finish())
+}
+
+fn extract_7z(
+    path: &Path,
+    dest: &Path,
+    on_progress: impl FnMut(&ExtractProgress),
+) -> io::Result<ExtractProgress> {
+    let mut archive = SevenZReader::open(path, Password::empty()).map_err(io::Error::other)?;
+    let total = archive
+        .archive()
+        .files
+        .iter()
+        .map(|entry| entry.size())
+        .sum();
+    let mut tracker = Tracker::new(Some(total), on_progress);
+    let mut failure = None;
+    let result = archive.for_each_entries(|entry, reader| {
+        // Archives made on Windows may use backslashes
+        let name = entry.name().replace('\\', "/");
+        let Some(target) = contained(dest, Path::new(&name)) else {
+            failure = Some(traversal(&name));
+            return Ok(false);
+        };
+        let written = if entry.is_directory() {
+            create_dir(dest, &target).map(|_| None)
+        } else {
+            write_file(dest, &target, reader).map(Some)
+        };
+        match written {
+            Ok(Some(bytes)) => tracker.file_done(bytes),
+            Ok(None) => {}
+            Err(e) => {
+                failure = Some(e);
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    });
+    if let Some(e) = failure {
+        return Err(e);
+    }
+    result.map_err(io::Error::other)?;
+    Ok(tracker.finish())
+}
+
+/// Extracts the archive at `path` into `dest`, creating it. `on_progress`
+/// is called every so often and once at the end. Blocking; run it off the
+/// async runtime.
+pub fn extract(
+    path: &Path,
+    dest: &Path,
+    on_progress: impl FnMut(&ExtractProgress),
+) -> Result<ExtractProgress, String> {
+    let kind = ArchiveKind::detect(path)
+        .ok_or_else(|| format!("Unsupported archive type: {}", path.display()))?;
+    std::fs::create_dir_all(dest)
+        .map_err(|e| format!("Failed to create {}: {}", dest.display(), e))?;
+    match kind {
+        ArchiveKind::Zip => extract_zip(path, dest, on_progress),
+        ArchiveKind::TarGz => extract_tar_gz(path, dest, on_progress),
+        ArchiveKind::SevenZip => extract_7z(path, dest, on_progress),
+    }
+    .map_err(|e| format!("Failed to extract {}: {}", path.display(), e))
+}
+
+/// Extracts off the async runtime, emitting `archive://progress`.
+pub async fn extract_with_events(
+    app: &AppHandle,
+    path: PathBuf,
+    dest: PathBuf,
+) -> Result<ExtractProgress, String> {
+    let app = app.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        let (path_text, dest_text) = (path.display().to_string(), dest.display().to_string());
+        extract(&path, &dest, |progress| {
+            let _ = app.emit(
+                "archive://progress",
+                ProgressPayload {
+                    path: path_text.clone(),
+                    dest: dest_text.clone(),
+                    progress: progress.clone(),
+                },
+            );
+        })
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Extracts a zip, tar.gz or 7z archive into `dest`.
+#[tauri::command]
+pub async fn extract_archive(
+    app_handle: AppHandle,
+    path: String,
+    dest: String,
+) -> Result<ExtractProgress, String> {
+    let path = PathBuf::from(path);
+    if !path.is_file() {
+        return Err(format!("Archive not found: {}", path.display()));
+    }
+    let progress = extract_with_events(&app_handle, path.clone(), PathBuf::from(&dest)).await?;
+    log::info!(
+        "Extracted {} files from {} to {}",
+        progress.files,
+        path.display(),
+        dest
+    );
+    Ok(progress)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::scratch;
+
+    /// A fresh directory under the system temp dir.
+
+    #[test]
+    fn contained_joins_plain_names() {
+        let dest = Path::new("/dest");
+        assert_eq!(
+            contained(dest, Path::new("a/./b.txt")),
+            Some(PathBuf::from("/dest/a/b.txt"))
+        );
+    }
+
+    #[test]
+    fn contained_refuses_escapes() {
+        let dest = Path::new("/dest");
+        assert_eq!(contained(dest, Path::new("../x")), None);
+        assert_eq!(contained(dest, Path::new("a/../../x")), None);
+        assert_eq!(contained(dest, Path::new("a/../b")), None);
+        assert_eq!(contained(dest, Path::new("/etc/passwd")), None);
+    }
+
+    #[test]
+    fn link_target_allows_links_inside() {
+        let dest = Path::new("/dest");
+        assert_eq!(
+            link_target(dest, Path::new("/dest/Versions/Current"), Path::new("A")),
+            Some(PathBuf::from("/dest/Versions/A"))
+        );
+        assert_eq!(
+            link_target(dest, Path::new("/dest/a/b/link"), Path::new("../../c")),
+            Some(PathBuf::from("/dest/c"))
+        );
+        assert_eq!(
+            link_target(dest, Path::new("/dest/a"), Path::new(".")),
+            Some(PathBuf::from("/dest"))
+        );
+    }
+
+    #[test]
+    fn link_target_refuses_escapes() {
+        let dest = Path::new("/dest");
+        assert_eq!(
+            link_target(dest, Path::new("/dest/link"), Path::new("..")),
+            None
+        );
+        assert_eq!(
+            link_target(dest, Path::new("/dest/link"), Path::new("/etc")),
+            None
+        );
+        // `..` after a name climbs out of wherever the name links to
+        assert_eq!(
+            link_target(dest, Path::new("/dest/b"), Path::new("a/..")),
+            None
+        );
+    }
+
+    #[cfg(unix)]
+    fn zip_with(path: &Path, links: &[(&str, &str)], files: &[&str]) {
+        use zip::write::SimpleFileOptions;
+
+        let mut zip = zip::ZipWriter::new(File::create(path).unwrap());
+        for (name, target) in links {
+            zip.add_symlink(*name, *target, SimpleFileOptions::default())
+                .unwrap();
+        }
+        for name in files {
+            zip.start_file(*name, SimpleFileOptions::default()).unwrap();
+            zip.write_all(b"escaped").unwrap();
+        }
+        zip.finish().unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn zip_link_chains_cannot_escape() {
+        let root = scratch("archive-chain");
+        let dest = root.join("dest");
+        let archive = root.join("chain.zip");
+        zip_with(&archive, &[("a", "."), ("a/b", "..")], &["b/x"]);
+
+        assert!(extract(&archive, &dest, |_| {}).is_err());
+        assert!(!root.join("x").exists());
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn zip_writes_through_links_are_refused() {
+        let root = scratch("archive-through");
+        let dest = root.join("dest");
+        let archive = root.join("through.zip");
+        std::fs::create_dir_all(&dest).unwrap();
+        std::os::unix::fs::symlink(&root, dest.join("out")).unwrap();
+        zip_with(&archive, &[], &["out/x"]);
+
+        assert!(extract(&archive, &dest, |_| {}).is_err());
+        assert!(!root.join("x").exists());
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn zip_links_inside_are_kept() {
+        let root = scratch("archive-inside");
+        let dest = root.join("dest");
+        let archive = root.join("inside.zip");
+        zip_with(&archive, &[("Versions/Current", "A")], &["Versions/A/lib"]);
+
+        extract(&archive, &dest, |_| {}).unwrap();
+        assert_eq!(
+            std::fs::read(dest.join("Versions/Current/lib")).unwrap(),
+            b"escaped"
+        );
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,234 @@
+//! Profile avatars and screenshots for the UI, served over `nyx-asset://`
+//! by id rather than by path, so the webview can show thumbnails without
+//! being able to read anything else on disk.
+//!
+//! `nyx-asset://localhost/profiles/<id>/<kind>` (on Windows
+//! `http://nyx-asset.localhost/...`) resolves to the image of that kind
+//! stored for the profile under `assets/` in app data. Requests that do not
+//! name a known kind of a valid profile id get a 404, and only images are
+//! ever stored or served.
+
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use serde::{Deserialize, Serialize};
+use tauri::http::{Request, StatusCode};
+use tauri::{AppHandle, Manager, UriSchemeContext, UriSchemeResponder, Wry};
+
+use crate::audit::{self, AuditAction};
+use crate::exports;
+
+pub const SCHEME: &str = "nyx-asset";
+const ASSETS_DIR: &str = "assets";
+const MAX_ASSET_BYTES: u64 = 10 * 1024 * 1024;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AssetKind {
+    Avatar,
+    Screenshot,
+}
+
+impl AssetKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Avatar => "avatar",
+            Self::Screenshot => "screenshot",
+        }
+    }
+
+    fn parse(kind: &str) -> Option<Self> {
+        match kind {
+            "avatar" => Some(Self::Avatar),
+            "screenshot" => Some(Self::Screenshot),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum ImageFormat {
+    Png,
+    Jpeg,
+    Webp,
+    Gif,
+}
+
+impl ImageFormat {
+    const ALL: [Self; 4] = [Self::Png, Self::Jpeg, Self::Webp, Self::Gif];
+
+    fn extension(self) -> &'static str {
+        match self {
+            Self::Png => "png",
+            Self::Jpeg => "jpg",
+            Self::Webp => "webp",
+            Self::Gif => "gif",
+        }
+    }
+
+    /// Recognizes an image by its leading bytes, whatever its file name.
+    fn sniff(header: &[u8]) -> Option<Self> {
+        if header.starts_with(b"\x89PNG\r\n\x1a\n") {
+            Some(Self::Png)
+        } else if header.starts_with(&[0xff, 0xd8, 0xff]) {
+            Some(Self::Jpeg)
+        } else if header.len() >= 12 && &header[..4] == b"RIFF" && &header[8..12] == b"WEBP" {
+            Some(Self::Webp)
+        } else if header.starts_with(b"GIF87a") || header.starts_with(b"GIF89a") {
+            Some(Self::Gif)
+        } else {
+            None
+        }
+    }
+}
+
+fn assets_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    let dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to resolve app data directory: {}", e))?
+        .join(ASSETS_DIR);
+    std::fs::create_dir_all(&dir)
+        .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    Ok(dir)
+}
+
+fn profile_assets(app: &AppHandle, profile_id: &str) -> Result<PathBuf, String> {
+    crate::profiles::validate_id(profile_id)?;
+    Ok(assets_dir(app)?.join("profiles").join(profile_id))
+}
+
+/// The stored image of `kind`, whichever format it is in.
+fn find(dir: &Path, kind: AssetKind) -> Option<PathBuf> {
+    ImageFormat::ALL
+        .iter()
+        .map(|format| dir.join(format!("{}.{}", kind.as_str(), format.extension())))
+        .find(|path| path.is_file())
+}
+
+fn url_for(profile_id: &str, kind: AssetKind, path: &Path) -> String {
+    let base = if cfg!(windows) {
+        format!("http://{}.localhost", SCHEME)
+    } else {
+        format!("{}://localhost", SCHEME)
+    };
+    // Changes whenever the image is replaced, so stale copies are not shown
+    let version = std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |since| since.as_millis());
+    format!(
+        "{}/profiles/{}/{}?v={}",
+        base,
+        profile_id,
+        kind.as_str(),
+        version
+    )
+}
+
+/// Handler for [`SCHEME`].
+pub fn handle(
+    context: UriSchemeContext<'_, Wry>,
+    request: Request<Vec<u8>>,
+    responder: UriSchemeResponder,
+) {
+    let app = context.app_handle().clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        let segments: Vec<&str> = request.uri().path().trim_matches('/').split('/').collect();
+        let path = match segments.as_slice() {
+            ["profiles", id, kind] => {
+                AssetKind::parse(kind).and_then(|kind| find(&profile_assets(&app, id).ok()?, kind))
+            }
+            _ => None,
+        };
+        let response = match path {
+            Some(path) => exports::serve_file(&path, &request),
+            None => exports::error(StatusCode::NOT_FOUND),
+        };
+        responder.respond(exports::allow_app_origin(&app, &request, response));
+    });
+}
+
+/// The URL of a profile's image of `kind`, or `None` if it has none.
+#[tauri::command]
+pub fn get_profile_asset_url(
+    app_handle: AppHandle,
+    profile_id: String,
+    kind: AssetKind,
+) -> Result<Option<String>, String> {
+    let dir = profile_assets(&app_handle, &profile_id)?;
+    Ok(find(&dir, kind).map(|path| url_for(&profile_id, kind, &path)))
+}
+
+/// Stores the image at `source` as the profile's image of `kind`, replacing
+/// any previous one, and returns its URL. Only PNG, JPEG, WebP and GIF
+/// files of up to 10 MB are accepted.
+#[tauri::command]
+pub async fn set_profile_asset(
+    app_handle: AppHandle,
+    profile_id: String,
+    kind: AssetKind,
+    source: PathBuf,
+) -> Result<String, String> {
+    let dir = profile_assets(&app_handle, &profile_id)?;
+    let stored = tauri::async_runtime::spawn_blocking(move || -> Result<PathBuf, String> {
+        let metadata = std::fs::metadata(&source)
+            .map_err(|e| format!("Failed to read {}: {}", source.display(), e))?;
+        if !metadata.is_file() {
+            return Err(format!("{} is not a file", source.display()));
+        }
+        if metadata.len() > MAX_ASSET_BYTES {
+            return Err(format!(
+                "Images must be at most {} MB",
+                MAX_ASSET_BYTES / 1024 / 1024
+            ));
+        }
+        let mut header = [0u8; 12];
+        let read = std::fs::File::open(&source)
+            .and_then(|mut file| file.read(&mut header))
+            .map_err(|e| format!("Failed to read {}: {}", source.display(), e))?;
+        let format = ImageFormat::sniff(&header[..read])
+            .ok_or_else(|| format!("{} is not a PNG, JPEG, WebP or GIF image", source.display()))?;
+
+        std::fs::create_dir_all(&dir)
+            .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+        let target = dir.join(format!("{}.{}", kind.as_str(), format.extension()));
+        let partial = target.with_extension("part");
+        std::fs::copy(&source, &partial)
+            .map_err(|e| format!("Failed to copy {}: {}", source.display(), e))?;
+        if let Some(previous) = find(&dir, kind).filter(|previous| *previous != target) {
+            let _ = std::fs::remove_file(previous);
+        }
+        std::fs::rename(&partial, &target)
+            .map_err(|e| format!("Failed to save {}: {}", target.display(), e))?;
+        Ok(target)
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+    log::info!("Saved the {} of profile {}", kind.as_str(), profile_id);
+    Ok(url_for(&profile_id, kind, &stored))
+}
+
+/// Deletes a profile's image of `kind`. Returns `false` if it had none.
+#[tauri::command]
+pub fn remove_profile_asset(
+    app_handle: AppHandle,
+    profile_id: String,
+    kind: AssetKind,
+) -> Result<bool, String> {
+    let dir = profile_assets(&app_handle, &profile_id)?;
+    let Some(path) = find(&dir, kind) else {
+        return Ok(false);
+    };
+    let removed = std::fs::remove_file(&path);
+    audit::record(
+        &app_handle,
+        AuditAction::FileDelete,
+        path.display().to_string(),
+        &removed,
+    );
+    removed.map_err(|e| format!("Failed to delete {}: {}", path.display(), e))?;
+    Ok(true)
+}
@@ -0,0 +1,187 @@
+//! An append-only record of the sensitive things the shell does: starting
+//! and killing processes, deleting files, touching keychain secrets and
+//! serving control API requests.
+//!
+//! Entries go to [`AUDIT_FILE`] in app data, one JSON object per line, and
+//! are never rewritten. Each names its [`Initiator`]. That is whatever the
+//! running task was started under with [`scoped`]: the control API, command
+//! line launches and background schedules mark theirs, so anything unmarked
+//! came from the UI. Writing an entry never fails the action it records.
+
+use std::fmt::Display;
+use std::fs::OpenOptions;
+use std::future::Future;
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+
+use crate::server::logs::now_millis;
+
+pub const AUDIT_FILE: &str = "audit.jsonl";
+const DEFAULT_QUERY_LIMIT: usize = 200;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Initiator {
+    /// The app's own windows, and deep links opened by the user.
+    #[default]
+    Ui,
+    /// Flags given on the command line.
+    Cli,
+    /// A request to [`crate::control_api`].
+    ControlApi,
+    /// Schedules and background upkeep.
+    System,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AuditAction {
+    ProcessSpawn,
+    ProcessKill,
+    FileDelete,
+    SecretAccess,
+    /// A request from outside the app, through the control API.
+    ApiRequest,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditEntry {
+    /// Milliseconds since the Unix epoch.
+    pub timestamp: u64,
+    pub initiator: Initiator,
+    pub action: AuditAction,
+    /// What was acted on, e.g. a path, a profile or a secret's name.
+    pub target: String,
+    /// Why it failed; `None` when it succeeded.
+    pub error: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct AuditFilter {
+    pub initiator: Option<Initiator>,
+    pub action: Option<AuditAction>,
+    /// Milliseconds since the Unix epoch, inclusive.
+    pub since: Option<u64>,
+    pub until: Option<u64>,
+    /// Part of the target, ignoring case.
+    pub search: Option<String>,
+    pub failed_only: bool,
+    /// Most entries returned, newest first.
+    pub limit: Option<usize>,
+}
+
+impl AuditFilter {
+    fn matches(&self, entry: &AuditEntry) -> bool {
+        self.initiator.map_or(true, |i| i == entry.initiator)
+            && self.action.map_or(true, |a| a == entry.action)
+            && self.since.map_or(true, |since| entry.timestamp >= since)
+            && self.until.map_or(true, |until| entry.timestamp <= until)
+            && (!self.failed_only || entry.error.is_some())
+            && self.search.as_ref().map_or(true, |search| {
+                entry.target.to_lowercase().contains(&search.to_lowercase())
+            })
+    }
+}
+
+tokio::task_local! {
+    static INITIATOR: Initiator;
+}
+
+/// Managed state serialising appends, so entries written at once do not
+/// interleave.
+#[derive(Default)]
+pub struct AuditLog {
+    file: Mutex<()>,
+}
+
+/// Runs `future` with everything it records attributed to `initiator`.
+/// Tasks it spawns have to be scoped again.
+pub fn scoped<F: Future>(initiator: Initiator, future: F) -> impl Future<Output = F::Output> {
+    INITIATOR.scope(initiator, future)
+}
+
+/// Who the running task acts for.
+pub fn initiator() -> Initiator {
+    INITIATOR
+        .try_with(|initiator| *initiator)
+        .unwrap_or_default()
+}
+
+fn audit_path(app: &AppHandle) -> Result<PathBuf, String> {
+    app.path()
+        .app_data_dir()
+        .map(|dir| dir.join(AUDIT_FILE))
+        .map_err(|e| format!("Failed to resolve app data directory: {}", e))
+}
+
+fn append(app: &AppHandle, entry: &AuditEntry) -> Result<(), String> {
+    let path = audit_path(app)?;
+    let mut line = serde_json::to_string(entry).map_err(|e| e.to_string())?;
+    line.push('\n');
+    let log = app.state::<AuditLog>();
+    let _guard = log.file.lock().unwrap();
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .and_then(|mut file| file.write_all(line.as_bytes()))
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+/// Records `action` on `target` and how it went.
+pub fn record<T, E: Display>(
+    app: &AppHandle,
+    action: AuditAction,
+    target: impl Into<String>,
+    result: &Result<T, E>,
+) {
+    let entry = AuditEntry {
+        timestamp: now_millis(),
+        initiator: initiator(),
+        action,
+        target: target.into(),
+        error: result.as_ref().err().map(|e| e.to_string()),
+    };
+    if let Err(e) = append(app, &entry) {
+        log::warn!("Failed to audit {:?} of {}: {}", action, entry.target, e);
+    }
+}
+
+/// Records `action` on `target`, which went through.
+pub fn record_ok(app: &AppHandle, action: AuditAction, target: impl Into<String>) {
+    record(app, action, target, &Ok::<(), &str>(()));
+}
+
+/// The entries matching `filter`, newest first.
+#[tauri::command]
+pub async fn query_audit_log(
+    app_handle: AppHandle,
+    filter: Option<AuditFilter>,
+) -> Result<Vec<AuditEntry>, String> {
+    let filter = filter.unwrap_or_default();
+    let path = audit_path(&app_handle)?;
+    tauri::async_runtime::spawn_blocking(move || {
+        let file = match std::fs::File::open(&path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+        };
+        let mut entries: Vec<AuditEntry> = BufReader::new(file)
+            .lines()
+            .map_while(Result::ok)
+            .filter_map(|line| serde_json::from_str(&line).ok())
+            .filter(|entry| filter.matches(entry))
+            .collect();
+        entries.reverse();
+        entries.truncate(filter.limit.unwrap_or(DEFAULT_QUERY_LIMIT));
+        Ok(entries)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
@@ -0,0 +1,45 @@
+//! Starting the app on login: a registry Run key on Windows, a launch agent
+//! on macOS and an XDG autostart entry on Linux. The login launch passes
+//! `--minimized`, so the app boots to the tray and the backend and
+//! scheduled tasks run without a window.
+
+use tauri::AppHandle;
+use tauri_plugin_autostart::{MacosLauncher, ManagerExt};
+
+/// Flag passed by the login launch.
+pub const MINIMIZED_FLAG: &str = "--minimized";
+
+pub fn plugin<R: tauri::Runtime>() -> tauri::plugin::TauriPlugin<R> {
+    tauri_plugin_autostart::init(MacosLauncher::LaunchAgent, Some(vec![MINIMIZED_FLAG]))
+}
+
+#[tauri::command]
+pub fn get_autostart(app_handle: AppHandle) -> Result<bool, String> {
+    app_handle
+        .autolaunch()
+        .is_enabled()
+        .map_err(|e| format!("Failed to read autostart state: {}", e))
+}
+
+/// Turns starting on login on or off and returns the resulting state.
+#[tauri::command]
+pub fn set_autostart(app_handle: AppHandle, enabled: bool) -> Result<bool, String> {
+    let autolaunch = app_handle.autolaunch();
+    let result = if enabled {
+        autolaunch.enable()
+    } else {
+        autolaunch.disable()
+    };
+    result.map_err(|e| {
+        format!(
+            "Failed to {} autostart: {}",
+            if enabled { "enable" } else { "disable" },
+            e
+        )
+    })?;
+    log::info!(
+        "Autostart on login {}",
+        if enabled { "enabled" } else { "disabled" }
+    );
+    get_autostart(app_handle)
+}
@@ -0,0 +1,606 @@
+//! Backups of everything a user would miss on a new machine: the shell's
+//! config and settings, browser profiles, the backend's stored profiles and
+//! proxies, and the local cache.
+//!
+//! A backup is a zip whose `manifest.json` lists every file with its size
+//! and SHA-256 under a [`SCHEMA_VERSION`], which a restore checks first.
+//! With a password every file but the manifest is AES-256 encrypted, and the
+//! keychain secrets the config refers to are included too; without one they
+//! are left out. An encrypted backup's manifest only counts its files, whose
+//! list moves to the encrypted `files.json`, so their names and hashes give
+//! nothing away without the password.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tauri::{AppHandle, Manager};
+use zip::write::SimpleFileOptions;
+use zip::{AesMode, CompressionMethod, ZipArchive, ZipWriter};
+
+use pack::{Kind, MANIFEST};
+
+use crate::cache::CacheEntry;
+use crate::config::ConfigStore;
+use crate::profiles::registry::ProfileRegistry;
+use crate::server::supervisor::{ServerSupervisor, DEFAULT_SHUTDOWN_GRACE};
+use crate::settings::SettingsStore;
+
+pub(crate) mod pack;
+pub mod schedule;
+
+/// Bumped whenever the layout changes in a way older versions cannot read.
+/// Version 2 moved the file list of encrypted backups into [`FILES_ENTRY`].
+pub const SCHEMA_VERSION: u32 = 2;
+const KIND: Kind = Kind {
+    name: "backup",
+    format: "nyx-backup",
+    version: SCHEMA_VERSION,
+    verb: "restore",
+};
+const CACHE_ENTRY: &str = "cache.json";
+const SECRETS_ENTRY: &str = "secrets.json";
+/// The file list of an encrypted backup.
+const FILES_ENTRY: &str = "files.json";
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackupFile {
+    /// Path inside the archive, e.g. `config/settings.json`.
+    pub path: String,
+    pub size: u64,
+    pub sha256: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackupManifest {
+    pub format: String,
+    pub schema_version: u32,
+    pub app_version: String,
+    pub created_at: String,
+    pub encrypted: bool,
+    /// Empty in an encrypted backup; see [`FILES_ENTRY`].
+    pub files: Vec<BackupFile>,
+    #[serde(default)]
+    pub file_count: usize,
+    #[serde(default)]
+    pub total_bytes: u64,
+}
+
+impl BackupManifest {
+    /// How many files the backup holds and their size, also for backups
+    /// older than `fileCount`.
+    pub fn totals(&self) -> (usize, u64) {
+        if self.files.is_empty() {
+            return (self.file_count, self.total_bytes);
+        }
+        (
+            self.files.len(),
+            self.files.iter().map(|file| file.size).sum(),
+        )
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackupSummary {
+    pub path: String,
+    pub files: usize,
+    pub bytes: u64,
+    pub encrypted: bool,
+    /// Files that could not be read, e.g. locked by a running browser.
+    pub skipped: Vec<String>,
+    /// Keychain secrets left out because the backup is not encrypted.
+    pub secrets_left_out: usize,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RestoreReport {
+    pub dry_run: bool,
+    pub schema_version: u32,
+    pub app_version: String,
+    pub created_at: String,
+    /// Files that do not exist on this machine yet.
+    pub added: Vec<String>,
+    /// Files that exist here with different contents.
+    pub changed: Vec<String>,
+    pub unchanged: usize,
+    pub cache_entries: usize,
+    pub secrets: usize,
+}
+
+/// A part of the user's data and where it lives on this machine.
+struct Section {
+    /// Prefix of its files in the archive.
+    name: &'static str,
+    dir: PathBuf,
+    /// Only these files in `dir`, rather than everything under it.
+    only: Option<&'static [&'static str]>,
+}
+
+fn sections(app: &AppHandle) -> Result<Vec<Section>, String> {
+    let config_dir = app
+        .path()
+        .app_config_dir()
+        .map_err(|e| format!("Failed to resolve app config directory: {}", e))?;
+    let data_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to resolve app data directory: {}", e))?;
+    // The backend keeps its profiles, proxies and settings under its
+    // working directory
+    let server_dir = app
+        .state::<ConfigStore>()
+        .get()
+        .server
+        .working_dir
+        .unwrap_or_else(|| data_dir.clone());
+    Ok(vec![
+        // On macOS the config and data directories are one and the same,
+        // so only the shell's own files are picked out of it
+        Section {
+            name: "config",
+            dir: config_dir,
+            only: Some(&[crate::config::CONFIG_FILE, crate::settings::SETTINGS_FILE]),
+        },
+        Section {
+            name: "profiles",
+            dir: crate::data_dir::data_dir(app)?.join("profiles"),
+            only: None,
+        },
+        Section {
+            name: "server",
+            dir: server_dir.join("sessions"),
+            only: None,
+        },
+    ])
+}
+
+/// Every regular file under `root.join(relative)`, relative to `root`.
+/// Symlinks are skipped.
+pub(crate) fn walk(root: &Path, relative: &Path, files: &mut Vec<PathBuf>) {
+    let Ok(entries) = std::fs::read_dir(root.join(relative)) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let Ok(kind) = entry.file_type() else {
+            continue;
+        };
+        let path = relative.join(entry.file_name());
+        if kind.is_dir() {
+            walk(root, &path, files);
+        } else if kind.is_file() {
+            files.push(path);
+        }
+    }
+}
+
+fn section_files(section: &Section) -> Vec<PathBuf> {
+    match section.only {
+        Some(names) => names
+            .iter()
+            .map(PathBuf::from)
+            .filter(|name| section.dir.join(name).is_file())
+            .collect(),
+        None => {
+            let mut files = Vec::new();
+            walk(&section.dir, Path::new(""), &mut files);
+            files
+        }
+    }
+}
+
+pub(crate) fn archive_name(section: &str, relative: &Path) -> String {
+    let mut name = section.to_string();
+    for part in relative.components() {
+        name.push('/');
+        name.push_str(&part.as_os_str().to_string_lossy());
+    }
+    name
+}
+
+/// Where an archive path is restored to, if it belongs to a known section
+/// and stays inside it.
+fn local_path(sections: &[Section], name: &str) -> Option<PathBuf> {
+    let (prefix, rest) = name.split_once('/')?;
+    let section = sections.iter().find(|section| section.name == prefix)?;
+    if rest.is_empty() || section.only.is_some_and(|only| !only.contains(&rest)) {
+        return None;
+    }
+    crate::archive::contained(&section.dir, Path::new(rest))
+}
+
+/// Hashes what is read through it, so every file is read only once.
+pub(crate) struct Hashing<R> {
+    inner: R,
+    hasher: Sha256,
+    size: u64,
+}
+
+impl<R: Read> Hashing<R> {
+    pub(crate) fn new(inner: R) -> Self {
+        Self {
+            inner,
+            hasher: Sha256::new(),
+            size: 0,
+        }
+    }
+
+    pub(crate) fn finish(self, path: String) -> BackupFile {
+        BackupFile {
+            path,
+            size: self.size,
+            sha256: format!("{:x}", self.hasher.finalize()),
+        }
+    }
+}
+
+impl<R: Read> Read for Hashing<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.hasher.update(&buf[..read]);
+        self.size += read as u64;
+        Ok(read)
+    }
+}
+
+fn write_backup(
+    dest: &Path,
+    sections: &[Section],
+    extras: Vec<(&'static str, Vec<u8>)>,
+    password: Option<&str>,
+    app_version: String,
+) -> io::Result<(BackupManifest, Vec<String>)> {
+    let mut zip = ZipWriter::new(File::create(dest)?);
+    let plain = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    let options = match password {
+        Some(password) => plain.with_aes_encryption(AesMode::Aes256, password),
+        None => plain,
+    };
+
+    let mut files = Vec::new();
+    let mut skipped = Vec::new();
+    for section in sections {
+        for relative in section_files(section) {
+            let name = archive_name(section.name, &relative);
+            match pack::add_file(
+                &mut zip,
+                name.clone(),
+                &section.dir.join(&relative),
+                options,
+            )? {
+                Some(file) => files.push(file),
+                None => skipped.push(name),
+            }
+        }
+    }
+    for (name, contents) in extras {
+        files.push(pack::add_bytes(
+            &mut zip,
+            name.to_string(),
+            &contents,
+            options,
+        )?);
+    }
+
+    let manifest = BackupManifest {
+        format: KIND.format.to_string(),
+        schema_version: SCHEMA_VERSION,
+        app_version,
+        created_at: chrono::Utc::now().to_rfc3339(),
+        encrypted: password.is_some(),
+        file_count: files.len(),
+        total_bytes: files.iter().map(|file| file.size).sum(),
+        files,
+    };
+    // Left readable so a restore can check it before asking for a password,
+    // but without what is in an encrypted backup
+    let listed = if manifest.encrypted {
+        let list = serde_json::to_vec(&manifest.files)?;
+        pack::add_bytes(&mut zip, FILES_ENTRY.to_string(), &list, options)?;
+        BackupManifest {
+            files: Vec::new(),
+            ..manifest.clone()
+        }
+    } else {
+        manifest.clone()
+    };
+    pack::finish(zip, &listed, plain)?;
+    Ok((manifest, skipped))
+}
+
+/// Keychain entries referred to anywhere in `value`.
+fn collect_refs(value: &serde_json::Value, refs: &mut BTreeSet<String>) {
+    match value {
+        serde_json::Value::String(text) => {
+            if let Some(name) = text.strip_prefix(crate::secrets::SECRET_REF_PREFIX) {
+                refs.insert(name.to_string());
+            }
+        }
+        serde_json::Value::Array(items) => items.iter().for_each(|item| collect_refs(item, refs)),
+        serde_json::Value::Object(object) => {
+            object.values().for_each(|item| collect_refs(item, refs))
+        }
+        _ => {}
+    }
+}
+
+/// Keychain entries the config refers to: the server environment's and
+/// the backend tokens, or wherever else a reference is kept.
+fn secret_refs(app: &AppHandle) -> Vec<String> {
+    let mut refs = BTreeSet::new();
+    match serde_json::to_value(app.state::<ConfigStore>().get()) {
+        Ok(config) => collect_refs(&config, &mut refs),
+        Err(e) => log::warn!("Failed to look for secrets in the config: {}", e),
+    }
+    refs.into_iter().collect()
+}
+
+/// Writes a backup to `dest`, encrypted if a `password` is given.
+pub async fn create(
+    app: &AppHandle,
+    dest: PathBuf,
+    password: Option<String>,
+) -> Result<BackupSummary, String> {
+    let password = password.filter(|password| !password.is_empty());
+    if let Some(dir) = dest
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty() && !dir.is_dir())
+    {
+        return Err(format!("Folder does not exist: {}", dir.display()));
+    }
+    let sections = sections(app)?;
+
+    let mut extras = Vec::new();
+    match crate::cache::export(app).await {
+        Ok(entries) => extras.push((
+            CACHE_ENTRY,
+            serde_json::to_vec(&entries).map_err(|e| e.to_string())?,
+        )),
+        Err(e) => log::warn!("Leaving the cache out of the backup: {}", e),
+    }
+    let refs = secret_refs(app);
+    let mut secrets_left_out = 0;
+    if password.is_some() {
+        let mut secrets = BTreeMap::new();
+        for name in refs {
+            if let Some(value) = crate::secrets::get(app, &name).await? {
+                secrets.insert(name, value);
+            }
+        }
+        if !secrets.is_empty() {
+            extras.push((
+                SECRETS_ENTRY,
+                serde_json::to_vec(&secrets).map_err(|e| e.to_string())?,
+            ));
+        }
+    } else {
+        secrets_left_out = refs.len();
+    }
+
+    let app_version = app.package_info().version.to_string();
+    let target = dest.clone();
+    let encrypted = password.is_some();
+    let (manifest, skipped) = tauri::async_runtime::spawn_blocking(move || {
+        write_backup(&target, &sections, extras, password.as_deref(), app_version)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+    .map_err(|e| {
+        let _ = std::fs::remove_file(&dest);
+        format!("Failed to write backup: {}", e)
+    })?;
+
+    log::info!(
+        "Wrote backup of {} files to {}",
+        manifest.files.len(),
+        dest.display()
+    );
+    Ok(BackupSummary {
+        path: dest.display().to_string(),
+        files: manifest.files.len(),
+        bytes: manifest.files.iter().map(|file| file.size).sum(),
+        encrypted,
+        skipped,
+        secrets_left_out,
+    })
+}
+
+#[tauri::command]
+pub async fn create_backup(
+    app_handle: AppHandle,
+    dest_path: String,
+    password: Option<String>,
+) -> Result<BackupSummary, String> {
+    create(&app_handle, PathBuf::from(dest_path), password).await
+}
+
+/// The manifest of the backup at `path`, or `None` for a zip that is not a
+/// backup at all. A backup this version cannot restore is an error.
+pub fn inspect(path: &Path) -> Result<Option<BackupManifest>, String> {
+    let mut archive = pack::open_archive(path, &KIND)?;
+    if archive.by_name(MANIFEST).is_err() {
+        return Ok(None);
+    }
+    pack::read_manifest(&mut archive, &KIND).map(Some)
+}
+
+fn read_json<T: serde::de::DeserializeOwned>(
+    archive: &mut ZipArchive<File>,
+    name: &str,
+    password: Option<&str>,
+) -> Result<T, String> {
+    let entry = pack::open_entry(archive, name, password, &KIND)?;
+    serde_json::from_reader(entry).map_err(|e| format!("Unreadable {} in backup: {}", name, e))
+}
+
+/// What a restore would do, worked out without touching anything.
+struct Plan {
+    report: RestoreReport,
+    writes: Vec<(BackupFile, PathBuf)>,
+    cache: Option<Vec<CacheEntry>>,
+    secrets: BTreeMap<String, String>,
+}
+
+fn plan(
+    archive: &mut ZipArchive<File>,
+    sections: &[Section],
+    password: Option<&str>,
+    dry_run: bool,
+) -> Result<Plan, String> {
+    let mut manifest: BackupManifest = pack::read_manifest(archive, &KIND)?;
+    let password = match (manifest.encrypted, password) {
+        (true, None) => return Err("This backup is encrypted; enter its password".to_string()),
+        (true, password) => password,
+        (false, _) => None,
+    };
+    if manifest.encrypted && manifest.schema_version >= 2 {
+        manifest.files = read_json(archive, FILES_ENTRY, password)?;
+    }
+
+    let mut report = RestoreReport {
+        dry_run,
+        schema_version: manifest.schema_version,
+        app_version: manifest.app_version,
+        created_at: manifest.created_at,
+        added: Vec::new(),
+        changed: Vec::new(),
+        unchanged: 0,
+        cache_entries: 0,
+        secrets: 0,
+    };
+    let mut writes = Vec::new();
+    let mut cache = None;
+    let mut secrets = BTreeMap::new();
+    for file in manifest.files {
+        match file.path.as_str() {
+            CACHE_ENTRY => {
+                let entries: Vec<CacheEntry> = read_json(archive, CACHE_ENTRY, password)?;
+                report.cache_entries = entries.len();
+                cache = Some(entries);
+            }
+            SECRETS_ENTRY => {
+                secrets = read_json(archive, SECRETS_ENTRY, password)?;
+                report.secrets = secrets.len();
+            }
+            name => {
+                let target = local_path(sections, name).ok_or_else(|| {
+                    format!("Backup entry {} points outside the restored folders", name)
+                })?;
+                if !target.exists() {
+                    report.added.push(file.path.clone());
+                } else if crate::server::update::sha256_file(&target).ok().as_ref()
+                    != Some(&file.sha256)
+                {
+                    report.changed.push(file.path.clone());
+                } else {
+                    report.unchanged += 1;
+                    continue;
+                }
+                writes.push((file, target));
+            }
+        }
+    }
+    // Opening an entry is enough to find out whether the password is right
+    if let Some((file, _)) = writes.first() {
+        pack::open_entry(archive, &file.path, password, &KIND)?;
+    }
+    Ok(Plan {
+        report,
+        writes,
+        cache,
+        secrets,
+    })
+}
+
+/// Puts the backup's secrets back in the keychain and refills the cache.
+async fn restore_extras(
+    app: &AppHandle,
+    secrets: BTreeMap<String, String>,
+    cache: Option<Vec<CacheEntry>>,
+) -> Result<(), String> {
+    for (name, value) in secrets {
+        crate::secrets::store(app, &name, value).await?;
+    }
+    if let Some(entries) = cache {
+        // Only a cache; the next sync fills it anyway
+        if let Err(e) = crate::cache::import(app, &entries).await {
+            log::warn!("Failed to restore the cache: {}", e);
+        }
+    }
+    Ok(())
+}
+
+/// Restores the backup at `path`. With `dry_run`, only reports what would
+/// change. A real restore stops the server for the duration and needs every
+/// profile browser closed.
+#[tauri::command]
+pub async fn restore_backup(
+    app_handle: AppHandle,
+    path: String,
+    password: Option<String>,
+    dry_run: Option<bool>,
+) -> Result<RestoreReport, String> {
+    let dry_run = dry_run.unwrap_or(false);
+    let path = PathBuf::from(path);
+    let password = password.filter(|password| !password.is_empty());
+    let sections = sections(&app_handle)?;
+
+    let (plan, password, path) = tauri::async_runtime::spawn_blocking(move || {
+        let mut archive = pack::open_archive(&path, &KIND)?;
+        let plan = plan(&mut archive, &sections, password.as_deref(), dry_run)?;
+        Ok::<_, String>((plan, password, path))
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+    if dry_run {
+        return Ok(plan.report);
+    }
+
+    if !app_handle.state::<ProfileRegistry>().list().is_empty() {
+        return Err("Close all running profiles before restoring a backup".to_string());
+    }
+    // The backend's files cannot be swapped out from under it
+    app_handle
+        .state::<ServerSupervisor>()
+        .stop(DEFAULT_SHUTDOWN_GRACE)
+        .await;
+
+    let Plan {
+        report,
+        writes,
+        cache,
+        secrets,
+    } = plan;
+    let restored = tauri::async_runtime::spawn_blocking(move || {
+        let mut archive = pack::open_archive(&path, &KIND)?;
+        writes.iter().try_for_each(|(file, target)| {
+            pack::extract_file(&mut archive, file, target, password.as_deref(), &KIND)
+        })
+    })
+    .await
+    .map_err(|e| e.to_string())?;
+
+    // Whatever was restored is live now, even if a later file failed
+    app_handle.state::<ConfigStore>().reload();
+    app_handle.state::<SettingsStore>().reload(&app_handle);
+    let result = match restored {
+        Ok(()) => restore_extras(&app_handle, secrets, cache).await,
+        Err(e) => Err(e),
+    };
+    if let Err(e) = crate::server::connection::apply(&app_handle).await {
+        log::warn!("Failed to bring the server back up after a restore: {}", e);
+    }
+    result?;
+    log::info!(
+        "Restored {} files from a backup made by Nyx {}",
+        report.added.len() + report.changed.len(),
+        report.app_version
+    );
+    Ok(report)
+}
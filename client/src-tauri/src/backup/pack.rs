@@ -0,0 +1,263 @@
+//! The zip layout backups and profile archives share: files added one by
+//! one and hashed on the way in, a `manifest.json` listing them, and every
+//! file checked against it on the way out.
+
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use zip::read::ZipFile;
+use zip::result::ZipError;
+use zip::write::FileOptions;
+use zip::{ZipArchive, ZipWriter};
+
+use super::{BackupFile, Hashing};
+
+pub(crate) const MANIFEST: &str = "manifest.json";
+
+/// What kind of archive is read, for its checks and its messages.
+pub(crate) struct Kind {
+    /// As in "Not a Nyx backup".
+    pub name: &'static str,
+    /// The manifest's `format`.
+    pub format: &'static str,
+    /// The newest `schemaVersion` this version reads.
+    pub version: u32,
+    /// As in "update the app to restore it".
+    pub verb: &'static str,
+}
+
+/// The manifest fields every kind has, checked before the rest is read.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Header {
+    format: String,
+    schema_version: u32,
+    app_version: String,
+}
+
+pub(crate) fn open_archive(path: &Path, kind: &Kind) -> Result<ZipArchive<File>, String> {
+    File::open(path)
+        .and_then(|file| ZipArchive::new(file).map_err(io::Error::from))
+        .map_err(|e| format!("Failed to open {} {}: {}", kind.name, path.display(), e))
+}
+
+/// The archive's manifest, once it is of `kind` in a version this one reads.
+pub(crate) fn read_manifest<T: DeserializeOwned>(
+    archive: &mut ZipArchive<File>,
+    kind: &Kind,
+) -> Result<T, String> {
+    let mut contents = Vec::new();
+    archive
+        .by_name(MANIFEST)
+        .map_err(|_| format!("Not a Nyx {}: it has no manifest", kind.name))?
+        .read_to_end(&mut contents)
+        .map_err(|e| format!("Unreadable {} manifest: {}", kind.name, e))?;
+    let header: Header = serde_json::from_slice(&contents)
+        .map_err(|e| format!("Unreadable {} manifest: {}", kind.name, e))?;
+    if header.format != kind.format {
+        return Err(format!("Not a Nyx {}", kind.name));
+    }
+    if header.schema_version > kind.version {
+        return Err(format!(
+            "This {} was made by Nyx {} in a newer format (version {}); update the app to {} it",
+            kind.name, header.app_version, header.schema_version, kind.verb
+        ));
+    }
+    if header.schema_version == 0 {
+        return Err(format!("Unsupported {} format version 0", kind.name));
+    }
+    serde_json::from_slice(&contents)
+        .map_err(|e| format!("Unreadable {} manifest: {}", kind.name, e))
+}
+
+pub(crate) fn open_entry<'a>(
+    archive: &'a mut ZipArchive<File>,
+    name: &str,
+    password: Option<&str>,
+    kind: &Kind,
+) -> Result<ZipFile<'a>, String> {
+    match password {
+        Some(password) => archive.by_name_decrypt(name, password.as_bytes()),
+        None => archive.by_name(name),
+    }
+    .map_err(|e| match e {
+        ZipError::InvalidPassword => format!("Wrong {} password", kind.name),
+        e => format!("Failed to read {} from the {}: {}", name, kind.name, e),
+    })
+}
+
+/// Adds the file at `path` as `name`. A file that cannot be read is left
+/// out with a warning and `None`, since one locked file should not fail
+/// the whole archive.
+pub(crate) fn add_file(
+    zip: &mut ZipWriter<File>,
+    name: String,
+    path: &Path,
+    options: FileOptions<'_, ()>,
+) -> io::Result<Option<BackupFile>> {
+    let mut reader = match File::open(path) {
+        Ok(file) => Hashing::new(file),
+        Err(e) => {
+            log::warn!("Leaving {} out of the archive: {}", name, e);
+            return Ok(None);
+        }
+    };
+    zip.start_file(name.as_str(), options)?;
+    if let Err(e) = io::copy(&mut reader, zip) {
+        zip.abort_file()?;
+        log::warn!("Leaving {} out of the archive: {}", name, e);
+        return Ok(None);
+    }
+    Ok(Some(reader.finish(name)))
+}
+
+pub(crate) fn add_bytes(
+    zip: &mut ZipWriter<File>,
+    name: String,
+    contents: &[u8],
+    options: FileOptions<'_, ()>,
+) -> io::Result<BackupFile> {
+    zip.start_file(name.as_str(), options)?;
+    let mut reader = Hashing::new(contents);
+    io::copy(&mut reader, zip)?;
+    Ok(reader.finish(name))
+}
+
+/// Writes the manifest, unencrypted, and closes the archive.
+pub(crate) fn finish<T: Serialize>(
+    mut zip: ZipWriter<File>,
+    manifest: &T,
+    plain: FileOptions<'_, ()>,
+) -> io::Result<()> {
+    zip.start_file(MANIFEST, plain)?;
+    zip.write_all(&serde_json::to_vec_pretty(manifest)?)?;
+    zip.finish()?;
+    Ok(())
+}
+
+/// Writes one file next to `target` and moves it in place once its
+/// checksum matches the manifest.
+pub(crate) fn extract_file(
+    archive: &mut ZipArchive<File>,
+    file: &BackupFile,
+    target: &Path,
+    password: Option<&str>,
+    kind: &Kind,
+) -> Result<(), String> {
+    let mut reader = Hashing::new(open_entry(archive, &file.path, password, kind)?);
+    let name = target
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let partial = target.with_file_name(format!(".{}.restoring", name));
+    let written = target
+        .parent()
+        .map_or(Ok(()), std::fs::create_dir_all)
+        .and_then(|_| File::create(&partial))
+        .and_then(|mut out| {
+            io::copy(&mut reader, &mut out)?;
+            out.flush()
+        });
+    if let Err(e) = written {
+        let _ = std::fs::remove_file(&partial);
+        return Err(format!("Failed to write {}: {}", target.display(), e));
+    }
+    if reader.finish(file.path.clone()).sha256 != file.sha256 {
+        let _ = std::fs::remove_file(&partial);
+        return Err(format!(
+            "Entry {} of the {} is corrupt",
+            file.path, kind.name
+        ));
+    }
+    std::fs::rename(&partial, target)
+        .map_err(|e| format!("Failed to write {}: {}", target.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::scratch;
+
+    const TEST_KIND: Kind = Kind {
+        name: "test archive",
+        format: "nyx-test",
+        version: 1,
+        verb: "open",
+    };
+
+    #[derive(Serialize, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct TestManifest {
+        format: String,
+        schema_version: u32,
+        app_version: String,
+        files: Vec<BackupFile>,
+    }
+
+    fn write(path: &Path, schema_version: u32) -> Vec<BackupFile> {
+        let source = path.with_extension("txt");
+        std::fs::write(&source, "contents").unwrap();
+        let mut zip = ZipWriter::new(File::create(path).unwrap());
+        let options = FileOptions::<()>::default();
+        let files = vec![
+            add_file(&mut zip, "a.txt".to_string(), &source, options)
+                .unwrap()
+                .unwrap(),
+            add_bytes(&mut zip, "b.json".to_string(), b"{}", options).unwrap(),
+        ];
+        assert!(
+            add_file(&mut zip, "missing".to_string(), &path.join("x"), options)
+                .unwrap()
+                .is_none()
+        );
+        let manifest = TestManifest {
+            format: TEST_KIND.format.to_string(),
+            schema_version,
+            app_version: "1.0.0".to_string(),
+            files: files.clone(),
+        };
+        finish(zip, &manifest, options).unwrap();
+        files
+    }
+
+    #[test]
+    fn round_trips_through_the_manifest() {
+        let dir = scratch("pack-round-trip");
+        let path = dir.join("test.zip");
+        write(&path, 1);
+
+        let mut archive = open_archive(&path, &TEST_KIND).unwrap();
+        let manifest: TestManifest = read_manifest(&mut archive, &TEST_KIND).unwrap();
+        assert_eq!(manifest.files.len(), 2);
+        let target = dir.join("out").join("a.txt");
+        extract_file(&mut archive, &manifest.files[0], &target, None, &TEST_KIND).unwrap();
+        assert_eq!(std::fs::read_to_string(&target).unwrap(), "contents");
+
+        let mut corrupt = manifest.files[0].clone();
+        corrupt.sha256 = "0".repeat(64);
+        let other = dir.join("out").join("corrupt.txt");
+        assert!(extract_file(&mut archive, &corrupt, &other, None, &TEST_KIND).is_err());
+        assert!(!other.exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn refuses_newer_and_other_archives() {
+        let dir = scratch("pack-versions");
+        let path = dir.join("test.zip");
+        write(&path, 2);
+        let mut archive = open_archive(&path, &TEST_KIND).unwrap();
+        let newer = read_manifest::<TestManifest>(&mut archive, &TEST_KIND).err();
+        assert!(newer.is_some_and(|e| e.contains("newer format")));
+
+        let other = Kind {
+            format: "nyx-other",
+            ..TEST_KIND
+        };
+        assert!(read_manifest::<TestManifest>(&mut archive, &other).is_err());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,177 @@
+//! Automatic backups into a folder, daily or weekly at a set local time,
+//! pruned by count and age afterwards. Each outcome is emitted as
+//! `backup://completed` or `backup://failed`.
+//!
+//! A slot missed while the app was closed is made up at the next launch.
+//! A failed slot is not retried until the next one.
+
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use chrono::{DateTime, Datelike, Local, NaiveTime};
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager};
+
+use super::BackupSummary;
+use crate::audit::{AuditAction, Initiator};
+use crate::config::{BackupConfig, BackupFrequency, ConfigStore};
+
+/// How often the schedule is checked against the clock.
+const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+const FILE_PREFIX: &str = "nyx-backup_";
+/// Keychain entry holding the password of encrypted scheduled backups.
+pub const PASSWORD_SECRET: &str = "backup.password";
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CompletedPayload {
+    #[serde(flatten)]
+    summary: BackupSummary,
+    /// Older backups deleted afterwards.
+    pruned: Vec<String>,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct FailedPayload {
+    error: String,
+}
+
+pub fn parse_time(at: &str) -> Option<NaiveTime> {
+    NaiveTime::parse_from_str(at, "%H:%M").ok()
+}
+
+/// The latest scheduled time at or before `now`.
+fn last_slot(config: &BackupConfig, now: DateTime<Local>) -> Option<DateTime<Local>> {
+    let time = parse_time(&config.at)?;
+    let mut date = now.date_naive();
+    // A week back always covers one weekly slot
+    for _ in 0..8 {
+        let due_today =
+            config.frequency == BackupFrequency::Daily || date.weekday() == config.weekday;
+        if due_today {
+            // `earliest` also settles times repeated or skipped by DST
+            if let Some(slot) = date.and_time(time).and_local_timezone(Local).earliest() {
+                if slot <= now {
+                    return Some(slot);
+                }
+            }
+        }
+        date = date.pred_opt()?;
+    }
+    None
+}
+
+pub(crate) fn folder(app: &AppHandle, config: &BackupConfig) -> Result<PathBuf, String> {
+    match &config.folder {
+        Some(folder) => Ok(folder.clone()),
+        None => app
+            .path()
+            .app_data_dir()
+            .map(|dir| dir.join("backups"))
+            .map_err(|e| format!("Failed to resolve app data directory: {}", e)),
+    }
+}
+
+/// Scheduled backups in `folder`, newest first. Anything else in it is
+/// left alone.
+pub(crate) fn existing(folder: &Path) -> Vec<(PathBuf, SystemTime)> {
+    let Ok(entries) = std::fs::read_dir(folder) else {
+        return Vec::new();
+    };
+    let mut backups: Vec<_> = entries
+        .flatten()
+        .filter(|entry| {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            name.starts_with(FILE_PREFIX) && name.ends_with(".zip")
+        })
+        .filter_map(|entry| {
+            let modified = entry.metadata().ok()?.modified().ok()?;
+            Some((entry.path(), modified))
+        })
+        .collect();
+    backups.sort_by_key(|(_, modified)| std::cmp::Reverse(*modified));
+    backups
+}
+
+/// Deletes the backups past `keep` or older than `max_age_days`, never the
+/// newest. Returns what was deleted.
+fn prune(folder: &Path, config: &BackupConfig) -> Vec<String> {
+    let cutoff = config
+        .max_age_days
+        .and_then(|days| SystemTime::now().checked_sub(Duration::from_secs(days as u64 * 86_400)));
+    let mut pruned = Vec::new();
+    for (index, (path, modified)) in existing(folder).into_iter().enumerate().skip(1) {
+        if index < config.keep.max(1) && !cutoff.is_some_and(|cutoff| modified < cutoff) {
+            continue;
+        }
+        match std::fs::remove_file(&path) {
+            Ok(()) => pruned.push(path.display().to_string()),
+            Err(e) => log::warn!("Failed to delete old backup {}: {}", path.display(), e),
+        }
+    }
+    pruned
+}
+
+async fn run(app: &AppHandle, config: &BackupConfig) -> Result<CompletedPayload, String> {
+    let folder = folder(app, config)?;
+    std::fs::create_dir_all(&folder)
+        .map_err(|e| format!("Failed to create {}: {}", folder.display(), e))?;
+    let password = if config.encrypt {
+        let password = crate::secrets::get(app, PASSWORD_SECRET).await?;
+        Some(password.ok_or("Encrypted backups are on, but no backup password is stored")?)
+    } else {
+        None
+    };
+    let stamp = Local::now().format("%Y-%m-%d_%H-%M-%S");
+    let dest = folder.join(format!("{}{}.zip", FILE_PREFIX, stamp));
+    let summary = super::create(app, dest, password).await?;
+    let pruned = prune(&folder, config);
+    for path in &pruned {
+        crate::audit::record_ok(app, AuditAction::FileDelete, path.clone());
+    }
+    Ok(CompletedPayload { summary, pruned })
+}
+
+/// Runs scheduled backups for as long as the app runs. Config changes are
+/// picked up on the next check.
+pub fn spawn(app: &AppHandle) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(crate::audit::scoped(Initiator::System, async move {
+        let mut interval = tokio::time::interval(CHECK_INTERVAL);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        let mut attempted: Option<DateTime<Local>> = None;
+        loop {
+            interval.tick().await;
+            let config = app.state::<ConfigStore>().get().backup;
+            if !config.enabled {
+                continue;
+            }
+            let Some(slot) = last_slot(&config, Local::now()) else {
+                continue;
+            };
+            if attempted.is_some_and(|attempted| attempted >= slot) {
+                continue;
+            }
+            attempted = Some(slot);
+            // A backup made since the slot, e.g. before a restart, counts
+            let newest = folder(&app, &config)
+                .ok()
+                .and_then(|folder| existing(&folder).into_iter().next());
+            if newest.is_some_and(|(_, modified)| DateTime::<Local>::from(modified) >= slot) {
+                continue;
+            }
+
+            match run(&app, &config).await {
+                Ok(completed) => {
+                    log::info!("Scheduled backup written to {}", completed.summary.path);
+                    let _ = app.emit("backup://completed", completed);
+                }
+                Err(error) => {
+                    log::warn!("Scheduled backup failed: {}", error);
+                    let _ = app.emit("backup://failed", FailedPayload { error });
+                }
+            }
+        }
+    }));
+}
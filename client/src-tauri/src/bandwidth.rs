@@ -0,0 +1,199 @@
+//! Bytes the shell moves, per day and per feature, for users on metered
+//! connections.
+//!
+//! Counted are the payloads of backend API requests made through the shell,
+//! downloads, messages of the backend websocket bridge and traffic through
+//! the TLS proxy. Traffic to an embedded server never leaves the machine;
+//! it only costs data with an external one. Counts are kept in memory and
+//! written to `bandwidth.json` in app data every minute.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use chrono::{Local, NaiveDate};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+
+const BANDWIDTH_FILE: &str = "bandwidth.json";
+/// Days of history kept.
+const MAX_DAYS: usize = 90;
+const SAVE_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Feature {
+    /// Backend API requests, including those the webview made through the
+    /// shell.
+    Api,
+    Downloads,
+    /// The backend websocket relayed as events.
+    Websocket,
+    TlsProxy,
+    /// Traffic through [`crate::proxy::bridge`].
+    ProxyBridge,
+}
+
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Usage {
+    pub sent: u64,
+    pub received: u64,
+}
+
+impl Usage {
+    fn add(&mut self, other: Usage) {
+        self.sent += other.sent;
+        self.received += other.received;
+    }
+}
+
+type Days = BTreeMap<NaiveDate, BTreeMap<Feature, Usage>>;
+
+/// Managed state counting bytes per day and feature.
+pub struct Bandwidth {
+    path: Option<PathBuf>,
+    days: Mutex<Days>,
+    dirty: AtomicBool,
+}
+
+impl Bandwidth {
+    pub fn load(app: &AppHandle) -> Self {
+        let path = app
+            .path()
+            .app_data_dir()
+            .ok()
+            .map(|dir| dir.join(BANDWIDTH_FILE));
+        let days = path
+            .as_deref()
+            .and_then(|path| std::fs::read(path).ok())
+            .and_then(|contents| match serde_json::from_slice(&contents) {
+                Ok(days) => Some(days),
+                Err(e) => {
+                    log::warn!("Ignoring unreadable {}: {}", BANDWIDTH_FILE, e);
+                    None
+                }
+            })
+            .unwrap_or_default();
+        Self {
+            path,
+            days: Mutex::new(days),
+            dirty: AtomicBool::new(false),
+        }
+    }
+
+    fn add(&self, feature: Feature, usage: Usage) {
+        let mut days = self.days.lock().unwrap();
+        days.entry(Local::now().date_naive())
+            .or_default()
+            .entry(feature)
+            .or_default()
+            .add(usage);
+        while days.len() > MAX_DAYS {
+            days.pop_first();
+        }
+        self.dirty.store(true, Ordering::Relaxed);
+    }
+
+    /// Writes the counts if they changed since the last save.
+    pub fn save(&self) {
+        if !self.dirty.swap(false, Ordering::Relaxed) {
+            return;
+        }
+        let Some(path) = &self.path else {
+            return;
+        };
+        let result = serde_json::to_vec_pretty(&*self.days.lock().unwrap())
+            .map_err(|e| e.to_string())
+            .and_then(|json| {
+                let partial = path.with_extension("json.part");
+                std::fs::write(&partial, json)
+                    .and_then(|()| std::fs::rename(&partial, path))
+                    .map_err(|e| e.to_string())
+            });
+        if let Err(e) = result {
+            log::warn!("Failed to save {}: {}", path.display(), e);
+        }
+    }
+}
+
+/// Counts `sent` and `received` bytes against `feature` for today.
+pub fn record(app: &AppHandle, feature: Feature, sent: u64, received: u64) {
+    // Traffic before setup managed the state goes uncounted
+    if let Some(bandwidth) = app.try_state::<Bandwidth>() {
+        bandwidth.add(feature, Usage { sent, received });
+    }
+}
+
+/// Saves the counts periodically for the lifetime of the app.
+pub fn spawn(app: &AppHandle) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let mut interval = tokio::time::interval(SAVE_INTERVAL);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+            app.state::<Bandwidth>().save();
+        }
+    });
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DayUsage {
+    pub date: NaiveDate,
+    pub features: BTreeMap<Feature, Usage>,
+    pub total: Usage,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BandwidthStats {
+    /// Oldest first; days without traffic are left out.
+    pub days: Vec<DayUsage>,
+    pub features: BTreeMap<Feature, Usage>,
+    pub total: Usage,
+}
+
+/// Usage over the last `days` days, today included, or over all the kept
+/// history.
+#[tauri::command]
+pub fn get_bandwidth_stats(
+    bandwidth: tauri::State<'_, Bandwidth>,
+    days: Option<u32>,
+) -> BandwidthStats {
+    let since = days.map(|days| {
+        Local::now().date_naive() - chrono::Days::new(u64::from(days.saturating_sub(1)))
+    });
+    let mut stats = BandwidthStats {
+        days: Vec::new(),
+        features: BTreeMap::new(),
+        total: Usage::default(),
+    };
+    for (date, features) in bandwidth.days.lock().unwrap().iter() {
+        if since.is_some_and(|since| *date < since) {
+            continue;
+        }
+        let mut total = Usage::default();
+        for (feature, usage) in features {
+            total.add(*usage);
+            stats.features.entry(*feature).or_default().add(*usage);
+        }
+        stats.total.add(total);
+        stats.days.push(DayUsage {
+            date: *date,
+            features: features.clone(),
+            total,
+        });
+    }
+    stats
+}
+
+#[tauri::command]
+pub fn reset_bandwidth_stats(bandwidth: tauri::State<'_, Bandwidth>) {
+    bandwidth.days.lock().unwrap().clear();
+    bandwidth.dirty.store(true, Ordering::Relaxed);
+    bandwidth.save();
+}
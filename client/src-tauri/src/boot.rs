@@ -0,0 +1,168 @@
+//! Splash window that shows what the backend is doing while it boots. The
+//! main window stays hidden until the server is ready.
+//!
+//! Progress is emitted as `boot://progress` and also kept in managed state,
+//! so a splash page that loads after the first events still catches up via
+//! `get_boot_progress`.
+
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager, WebviewUrl, WebviewWindowBuilder};
+
+use crate::server::health;
+use crate::server::port::PortManager;
+use crate::server::status::{ServerState, ServerStatus};
+
+pub const SPLASH_LABEL: &str = "splash";
+
+/// How often the health endpoint is polled while the server is starting, to
+/// tell a server still setting up its database from one not listening yet.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum BootPhase {
+    ResolvingBinary,
+    Spawning,
+    WaitingForHealth,
+    /// Answering requests but still reporting its database as not ready.
+    MigratingDatabase,
+    Ready,
+    Failed,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BootProgress {
+    pub phase: BootPhase,
+    pub message: Option<String>,
+}
+
+/// Managed state holding the latest [`BootProgress`].
+pub struct BootState(Mutex<BootProgress>);
+
+impl Default for BootState {
+    fn default() -> Self {
+        Self(Mutex::new(BootProgress {
+            phase: BootPhase::ResolvingBinary,
+            message: None,
+        }))
+    }
+}
+
+pub fn report(app: &AppHandle, phase: BootPhase, message: Option<String>) {
+    let progress = BootProgress { phase, message };
+    let previous = {
+        let boot = app.state::<BootState>();
+        let mut current = boot.0.lock().unwrap();
+        // Nothing to report once the splash is gone, or if nothing changed
+        if current.phase == BootPhase::Ready
+            || (current.phase == progress.phase && current.message == progress.message)
+        {
+            return;
+        }
+        std::mem::replace(&mut *current, progress.clone()).phase
+    };
+    if previous != phase {
+        crate::startup::phase_changed(app, previous, phase);
+    }
+    log::debug!("Boot phase {:?}", phase);
+    let _ = app.emit("boot://progress", progress);
+}
+
+pub fn create_splash(app: &AppHandle) -> tauri::Result<()> {
+    WebviewWindowBuilder::new(app, SPLASH_LABEL, WebviewUrl::App("splash.html".into()))
+        .title("Nyx")
+        .inner_size(420.0, 260.0)
+        .resizable(false)
+        .decorations(false)
+        .center()
+        .build()?;
+    Ok(())
+}
+
+/// Follows the server status until it first becomes healthy, translating it
+/// into boot phases, then swaps the splash for the main window unless the
+/// backend turns out to be incompatible.
+pub fn watch(app: &AppHandle) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let status = app.state::<ServerStatus>();
+        let mut state = status.subscribe();
+        loop {
+            let current = *state.borrow_and_update();
+            match current {
+                ServerState::Starting => {
+                    let probe = health::check(&app, &health::health_url(&app), POLL_INTERVAL).await;
+                    let migrating = probe
+                        .detail
+                        .and_then(|detail| detail.database)
+                        .is_some_and(|db| !matches!(db.status.as_str(), "ok" | "healthy"));
+                    if migrating {
+                        report(&app, BootPhase::MigratingDatabase, None);
+                    } else {
+                        report(&app, BootPhase::WaitingForHealth, None);
+                    }
+                }
+                ServerState::Healthy | ServerState::Degraded => {
+                    match crate::server::compat::wait(&app).await {
+                        Some(compat) if !compat.compatible => {
+                            report(&app, BootPhase::Failed, compat.suggestion);
+                        }
+                        _ => {
+                            report(&app, BootPhase::Ready, None);
+                            finish(&app);
+                            return;
+                        }
+                    }
+                }
+                ServerState::Crashed => {
+                    let error = status
+                        .snapshot(app.state::<PortManager>().port())
+                        .last_error;
+                    report(&app, BootPhase::Failed, error);
+                }
+                ServerState::NotStarted | ServerState::Stopped => {}
+            }
+
+            let changed = tokio::time::timeout(POLL_INTERVAL, state.changed()).await;
+            if matches!(changed, Ok(Err(_))) {
+                return;
+            }
+        }
+    });
+}
+
+/// Closes the splash and shows the main window.
+pub fn finish(app: &AppHandle) {
+    if let Some(splash) = app.get_webview_window(SPLASH_LABEL) {
+        let _ = splash.close();
+    }
+    crate::tray::show_main_window(app);
+    crate::startup::end(app, crate::startup::Span::MainWindow);
+}
+
+/// Brings the app forward: the splash while still booting, rather than an
+/// empty main window, and the main window after.
+pub fn focus(app: &AppHandle) {
+    match app.get_webview_window(SPLASH_LABEL) {
+        Some(splash) => {
+            let _ = splash.unminimize();
+            let _ = splash.set_focus();
+        }
+        None => crate::tray::show_main_window(app),
+    }
+}
+
+#[tauri::command]
+pub fn get_boot_progress(boot: tauri::State<'_, BootState>) -> BootProgress {
+    boot.0.lock().unwrap().clone()
+}
+
+/// Lets the user continue to the main window without a healthy server.
+#[tauri::command]
+pub fn dismiss_splash(app_handle: AppHandle) {
+    finish(&app_handle);
+}
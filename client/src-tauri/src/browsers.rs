@@ -0,0 +1,478 @@
+//! Browser builds installed into the data directory, one directory per
+//! `<name>-<version>` under `browsers/`; see [`crate::data_dir`].
+//!
+//! Builds are listed in a manifest like the server's, downloaded through the
+//! download manager, verified against the manifest's checksum and extracted
+//! natively. Install steps are emitted as `browser://progress`, alongside
+//! the download's and extraction's own progress events.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::audit::{self, AuditAction};
+use crate::config::ConfigStore;
+use crate::downloads::DownloadManager;
+use crate::profiles::registry::ProfileRegistry;
+use crate::server::client;
+use crate::server::update::{is_newer, platform_key};
+
+const BROWSERS_DIR: &str = "browsers";
+/// Written into each build's directory once it is fully installed.
+const BUILD_FILE: &str = "build.json";
+/// Used when the config does not name a manifest.
+const DEFAULT_MANIFEST_URL: &str =
+    "https://github.com/kaunda-a/nyx-app/releases/latest/download/browser-manifest.json";
+pub const DEFAULT_BROWSER: &str = "camoufox";
+
+#[derive(Deserialize)]
+struct Manifest {
+    builds: Vec<ManifestBuild>,
+}
+
+#[derive(Deserialize)]
+struct ManifestBuild {
+    name: String,
+    version: String,
+    /// Keyed by `<os>-<arch>`, e.g. `windows-x86_64`.
+    platforms: HashMap<String, Artifact>,
+}
+
+#[derive(Clone, Deserialize)]
+struct Artifact {
+    url: String,
+    sha256: String,
+    /// Path of the executable inside the archive, if it is not one of the
+    /// usual names.
+    executable: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BuildRecord {
+    name: String,
+    version: String,
+    /// Relative to the build directory.
+    executable: PathBuf,
+    sha256: String,
+    installed_at: String,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BrowserBuild {
+    pub name: String,
+    pub version: String,
+    pub path: String,
+    pub executable: String,
+    pub size_bytes: u64,
+    pub installed_at: String,
+    /// Configured as the browser, or running a profile right now.
+    pub in_use: bool,
+}
+
+#[derive(Clone, Copy, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum InstallStage {
+    Downloading,
+    Extracting,
+    Installed,
+    Failed,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ProgressPayload {
+    name: String,
+    version: String,
+    stage: InstallStage,
+    /// Follow `download://progress` for this ID while downloading.
+    download_id: Option<String>,
+    error: Option<String>,
+}
+
+/// Names and versions become directory names, so only plain ones are
+/// accepted.
+fn validate_part(kind: &str, value: &str) -> Result<(), String> {
+    let valid = !value.is_empty()
+        && !value.starts_with('.')
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_' | '+'));
+    if !valid {
+        return Err(format!("Invalid browser {}: {:?}", kind, value));
+    }
+    Ok(())
+}
+
+fn browsers_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    crate::data_dir::data_dir(app).map(|dir| dir.join(BROWSERS_DIR))
+}
+
+pub(crate) fn dir_size(path: &Path) -> u64 {
+    let Ok(entries) = std::fs::read_dir(path) else {
+        return 0;
+    };
+    entries
+        .flatten()
+        .map(|entry| match entry.file_type() {
+            Ok(kind) if kind.is_dir() => dir_size(&entry.path()),
+            Ok(kind) if kind.is_file() => entry.metadata().map(|m| m.len()).unwrap_or(0),
+            _ => 0,
+        })
+        .sum()
+}
+
+/// Executables of the supported browsers, relative to their archive root.
+fn is_browser_executable(path: &Path) -> bool {
+    let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+        return false;
+    };
+    if cfg!(windows) {
+        matches!(name, "camoufox.exe" | "chrome.exe")
+    } else if cfg!(target_os = "macos") {
+        path.parent()
+            .is_some_and(|dir| dir.ends_with("Contents/MacOS"))
+            && matches!(name, "camoufox" | "Chromium" | "Google Chrome for Testing")
+    } else {
+        matches!(name, "camoufox-bin" | "camoufox" | "chrome" | "chromium")
+    }
+}
+
+fn find_executable(dir: &Path) -> Option<PathBuf> {
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(current) = pending.pop() {
+        let Ok(entries) = std::fs::read_dir(&current) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            match entry.file_type() {
+                Ok(kind) if kind.is_dir() => pending.push(path),
+                Ok(kind) if kind.is_file() && is_browser_executable(&path) => return Some(path),
+                _ => {}
+            }
+        }
+    }
+    None
+}
+
+/// Every fully installed build, with its directory.
+fn installed(app: &AppHandle) -> Vec<(PathBuf, BuildRecord)> {
+    let Ok(entries) =
+        browsers_dir(app).and_then(|dir| std::fs::read_dir(dir).map_err(|e| e.to_string()))
+    else {
+        return Vec::new();
+    };
+    entries
+        .flatten()
+        .filter(|entry| !entry.file_name().to_string_lossy().starts_with('.'))
+        .filter_map(|entry| {
+            let dir = entry.path();
+            let contents = std::fs::read(dir.join(BUILD_FILE)).ok()?;
+            let record: BuildRecord = serde_json::from_slice(&contents).ok()?;
+            Some((dir, record))
+        })
+        .collect()
+}
+
+/// Executables that must not be deleted: the configured one and those of
+/// running profiles.
+fn executables_in_use(app: &AppHandle) -> Vec<PathBuf> {
+    let mut in_use: Vec<PathBuf> = app
+        .state::<ProfileRegistry>()
+        .list()
+        .into_iter()
+        .map(|profile| PathBuf::from(profile.executable))
+        .collect();
+    in_use.extend(app.state::<ConfigStore>().get().browser.executable);
+    in_use
+}
+
+fn describe(dir: &Path, record: BuildRecord, in_use: &[PathBuf]) -> BrowserBuild {
+    BrowserBuild {
+        in_use: in_use.iter().any(|path| path.starts_with(dir)),
+        path: dir.display().to_string(),
+        executable: dir.join(&record.executable).display().to_string(),
+        size_bytes: dir_size(dir),
+        name: record.name,
+        version: record.version,
+        installed_at: record.installed_at,
+    }
+}
+
+/// Installed builds, newest version first within each browser.
+fn list(app: &AppHandle) -> Vec<BrowserBuild> {
+    let in_use = executables_in_use(app);
+    let mut builds: Vec<BrowserBuild> = installed(app)
+        .into_iter()
+        .map(|(dir, record)| describe(&dir, record, &in_use))
+        .collect();
+    builds.sort_by(|a, b| {
+        a.name
+            .cmp(&b.name)
+            .then_with(|| newest_first(&a.version, &b.version))
+    });
+    builds
+}
+
+fn newest_first(a: &str, b: &str) -> std::cmp::Ordering {
+    if is_newer(a, b) {
+        std::cmp::Ordering::Less
+    } else if is_newer(b, a) {
+        std::cmp::Ordering::Greater
+    } else {
+        std::cmp::Ordering::Equal
+    }
+}
+
+/// The newest installed build of `name`, for launching profiles when no
+/// browser is configured.
+pub fn latest_executable(app: &AppHandle, name: &str) -> Option<PathBuf> {
+    installed(app)
+        .into_iter()
+        .filter(|(_, record)| record.name == name)
+        .min_by(|(_, a), (_, b)| newest_first(&a.version, &b.version))
+        .map(|(dir, record)| dir.join(record.executable))
+}
+
+async fn fetch_manifest(app: &AppHandle) -> Result<Manifest, String> {
+    let url = app
+        .state::<ConfigStore>()
+        .get()
+        .browser
+        .manifest
+        .unwrap_or_else(|| DEFAULT_MANIFEST_URL.to_string());
+    client::send(app, Default::default(), |client| client.get(&url))
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| format!("Failed to fetch browser manifest {}: {}", url, e))?
+        .json()
+        .await
+        .map_err(|e| format!("Invalid browser manifest {}: {}", url, e))
+}
+
+fn emit(
+    app: &AppHandle,
+    name: &str,
+    version: &str,
+    stage: InstallStage,
+    download_id: Option<String>,
+    error: Option<String>,
+) {
+    let _ = app.emit(
+        "browser://progress",
+        ProgressPayload {
+            name: name.to_string(),
+            version: version.to_string(),
+            stage,
+            download_id,
+            error,
+        },
+    );
+}
+
+fn remove_dir(app: &AppHandle, dir: &Path) -> Result<(), String> {
+    let removed = std::fs::remove_dir_all(dir);
+    audit::record(
+        app,
+        AuditAction::FileDelete,
+        dir.display().to_string(),
+        &removed,
+    );
+    removed.map_err(|e| format!("Failed to delete {}: {}", dir.display(), e))
+}
+
+async fn install(app: &AppHandle, name: &str, version: &str) -> Result<BrowserBuild, String> {
+    let root = browsers_dir(app)?;
+    let dir_name = format!("{}-{}", name, version);
+    let dir = root.join(&dir_name);
+    if let Some((dir, record)) = installed(app).into_iter().find(|(d, _)| *d == dir) {
+        return Ok(describe(&dir, record, &executables_in_use(app)));
+    }
+
+    let manifest = fetch_manifest(app).await?;
+    let artifact = manifest
+        .builds
+        .into_iter()
+        .find(|build| build.name == name && build.version == version)
+        .ok_or_else(|| format!("No {} {} in the browser manifest", name, version))?
+        .platforms
+        .remove(&platform_key())
+        .ok_or_else(|| format!("{} {} has no build for {}", name, version, platform_key()))?;
+
+    let file_name = reqwest::Url::parse(&artifact.url)
+        .ok()
+        .and_then(|url| url.path_segments()?.next_back().map(str::to_string))
+        .filter(|file| !file.is_empty())
+        .ok_or_else(|| format!("Browser download URL has no file name: {}", artifact.url))?;
+    let archive = crate::data_dir::data_dir(app)?
+        .join("downloads")
+        .join(BROWSERS_DIR)
+        .join(file_name);
+
+    let downloads = app.state::<DownloadManager>();
+    let download = downloads.start(
+        app,
+        artifact.url.clone(),
+        archive,
+        Some(artifact.sha256.clone()),
+    )?;
+    emit(
+        app,
+        name,
+        version,
+        InstallStage::Downloading,
+        Some(download.id.clone()),
+        None,
+    );
+    let archive = downloads.wait(&download.id).await?;
+
+    emit(app, name, version, InstallStage::Extracting, None, None);
+    let staging = root.join(format!(".{}.partial", dir_name));
+    if staging.exists() {
+        remove_dir(app, &staging)?;
+    }
+    crate::archive::extract_with_events(app, archive.clone(), staging.clone()).await?;
+
+    let executable = match &artifact.executable {
+        Some(relative) => Some(staging.join(relative)).filter(|path| path.is_file()),
+        None => find_executable(&staging),
+    }
+    .ok_or_else(|| {
+        let _ = std::fs::remove_dir_all(&staging);
+        format!("No browser executable found in {} {}", name, version)
+    })?;
+    let record = BuildRecord {
+        name: name.to_string(),
+        version: version.to_string(),
+        executable: executable
+            .strip_prefix(&staging)
+            .map(Path::to_path_buf)
+            .unwrap_or(executable),
+        sha256: artifact.sha256,
+        installed_at: chrono::Local::now().to_rfc3339(),
+    };
+    let contents = serde_json::to_vec_pretty(&record).map_err(|e| e.to_string())?;
+    std::fs::write(staging.join(BUILD_FILE), contents)
+        .map_err(|e| format!("Failed to save {}: {}", BUILD_FILE, e))?;
+    std::fs::rename(&staging, &dir)
+        .map_err(|e| format!("Failed to move {} into place: {}", dir.display(), e))?;
+    let _ = std::fs::remove_file(&archive);
+
+    log::info!("Installed {} {} to {}", name, version, dir.display());
+    Ok(describe(&dir, record, &executables_in_use(app)))
+}
+
+/// Lists the installed browser builds with their disk usage.
+#[tauri::command]
+pub fn list_browsers(app_handle: AppHandle) -> Vec<BrowserBuild> {
+    list(&app_handle)
+}
+
+/// Downloads, verifies and unpacks a browser build from the manifest.
+/// Installing a build that is already there returns it as is.
+#[tauri::command]
+pub async fn install_browser(
+    app_handle: AppHandle,
+    version: String,
+    name: Option<String>,
+) -> Result<BrowserBuild, String> {
+    let name = name.unwrap_or_else(|| DEFAULT_BROWSER.to_string());
+    validate_part("name", &name)?;
+    validate_part("version", &version)?;
+    match install(&app_handle, &name, &version).await {
+        Ok(build) => {
+            emit(
+                &app_handle,
+                &name,
+                &version,
+                InstallStage::Installed,
+                None,
+                None,
+            );
+            Ok(build)
+        }
+        Err(e) => {
+            emit(
+                &app_handle,
+                &name,
+                &version,
+                InstallStage::Failed,
+                None,
+                Some(e.clone()),
+            );
+            Err(e)
+        }
+    }
+}
+
+/// Deletes the builds not in use that were installed before `cutoff`.
+/// Returns how many went and the bytes that freed.
+pub(crate) fn remove_installed_before(app: &AppHandle, cutoff: DateTime<Utc>) -> (usize, u64) {
+    let mut removed = (0, 0);
+    for build in list(app) {
+        let old = DateTime::parse_from_rfc3339(&build.installed_at).is_ok_and(|at| at < cutoff);
+        if build.in_use || !old {
+            continue;
+        }
+        match remove_dir(app, Path::new(&build.path)) {
+            Ok(()) => {
+                removed.0 += 1;
+                removed.1 += build.size_bytes;
+            }
+            Err(e) => log::warn!("{}", e),
+        }
+    }
+    removed
+}
+
+/// Deletes an installed build. Returns `false` if it was not installed.
+#[tauri::command]
+pub fn remove_browser(
+    app_handle: AppHandle,
+    version: String,
+    name: Option<String>,
+) -> Result<bool, String> {
+    let name = name.unwrap_or_else(|| DEFAULT_BROWSER.to_string());
+    validate_part("name", &name)?;
+    validate_part("version", &version)?;
+    let Some(build) = list(&app_handle)
+        .into_iter()
+        .find(|build| build.name == name && build.version == version)
+    else {
+        return Ok(false);
+    };
+    if build.in_use {
+        return Err(format!(
+            "{} {} is in use and cannot be removed",
+            name, version
+        ));
+    }
+    remove_dir(&app_handle, Path::new(&build.path))?;
+    log::info!("Removed {} {}", name, version);
+    Ok(true)
+}
+
+/// Deletes all but the newest `keep` builds of each browser, leaving any in
+/// use alone. Returns the removed `<name>-<version>` builds.
+#[tauri::command]
+pub fn prune_browsers(app_handle: AppHandle, keep: Option<usize>) -> Result<Vec<String>, String> {
+    let keep = keep.unwrap_or(1);
+    let mut kept: HashMap<String, usize> = HashMap::new();
+    let mut removed = Vec::new();
+    for build in list(&app_handle) {
+        let count = kept.entry(build.name.clone()).or_default();
+        if *count < keep || build.in_use {
+            *count += 1;
+            continue;
+        }
+        remove_dir(&app_handle, Path::new(&build.path))?;
+        log::info!("Pruned {} {}", build.name, build.version);
+        removed.push(format!("{}-{}", build.name, build.version));
+    }
+    Ok(removed)
+}
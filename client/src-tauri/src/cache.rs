@@ -0,0 +1,396 @@
+//! Local copy of the backend's profiles and proxies, so the UI has something
+//! to show straight away, offline and while the backend is down.
+//!
+//! The cache is a SQLCipher database in app data, encrypted with a random
+//! key kept in the OS keychain (see [`crate::secrets`]); without a keychain
+//! there is no cache. It is refreshed whenever the backend becomes healthy
+//! and every [`SYNC_INTERVAL`] while it stays up, and each refresh or clear
+//! is announced with `cache://updated`. The same database holds the
+//! offline queue of [`crate::server::queue`].
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use rand::RngCore;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::server::client::{self, RequestOptions};
+use crate::server::status::{ServerState, ServerStatus};
+
+const CACHE_FILE: &str = "cache.db";
+/// Keychain entry holding the database key.
+const KEY_SECRET: &str = "cache.key";
+const SYNC_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS entries (
+        kind TEXT NOT NULL,
+        id TEXT NOT NULL,
+        data TEXT NOT NULL,
+        PRIMARY KEY (kind, id)
+    );
+    CREATE TABLE IF NOT EXISTS syncs (
+        kind TEXT PRIMARY KEY,
+        synced_at TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS pending_operations (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        method TEXT NOT NULL,
+        path TEXT NOT NULL,
+        body TEXT,
+        headers TEXT NOT NULL,
+        queued_at TEXT NOT NULL,
+        attempts INTEGER NOT NULL DEFAULT 0,
+        last_error TEXT
+    );
+";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CacheKind {
+    Profiles,
+    Proxies,
+}
+
+impl CacheKind {
+    const ALL: [Self; 2] = [Self::Profiles, Self::Proxies];
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Profiles => "profiles",
+            Self::Proxies => "proxies",
+        }
+    }
+
+    /// The backend listing this kind is copied from.
+    fn path(self) -> &'static str {
+        match self {
+            Self::Profiles => "/api/profiles/",
+            Self::Proxies => "/api/proxies/",
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CacheStatus {
+    pub profiles: usize,
+    pub proxies: usize,
+    /// When the cache was last filled from the backend, as RFC 3339.
+    pub synced_at: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CachedItems {
+    pub items: Vec<Value>,
+    pub synced_at: Option<String>,
+}
+
+/// Managed state holding the database, opened on first use.
+#[derive(Default)]
+pub struct AppCache {
+    db: Mutex<Option<Connection>>,
+}
+
+fn cache_path(app: &AppHandle) -> Result<PathBuf, String> {
+    let dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to resolve app data directory: {}", e))?;
+    std::fs::create_dir_all(&dir)
+        .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    Ok(dir.join(CACHE_FILE))
+}
+
+fn new_key() -> String {
+    let mut bytes = [0u8; 32];
+    rand::rngs::OsRng.fill_bytes(&mut bytes);
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn open_with_key(path: &Path, key: &str) -> rusqlite::Result<Connection> {
+    let db = Connection::open(path)?;
+    // A raw key, so SQLCipher skips its passphrase derivation
+    db.pragma_update(None, "key", format!("x'{}'", key))?;
+    db.execute_batch(SCHEMA)?;
+    Ok(db)
+}
+
+async fn open(app: &AppHandle) -> Result<Connection, String> {
+    let path = cache_path(app)?;
+    let key = match crate::secrets::get(app, KEY_SECRET).await? {
+        Some(key) => key,
+        None => {
+            // Whatever is there was encrypted with a key that is gone
+            let _ = std::fs::remove_file(&path);
+            let key = new_key();
+            crate::secrets::store(app, KEY_SECRET, key.clone()).await?;
+            key
+        }
+    };
+    match open_with_key(&path, &key) {
+        Ok(db) => Ok(db),
+        Err(e) => {
+            // Only a cache, so start over rather than fail for good
+            log::warn!("Recreating unreadable {}: {}", CACHE_FILE, e);
+            let _ = std::fs::remove_file(&path);
+            open_with_key(&path, &key).map_err(|e| format!("Failed to open cache: {}", e))
+        }
+    }
+}
+
+fn status(db: &Connection) -> rusqlite::Result<CacheStatus> {
+    let count = |kind: CacheKind| {
+        db.query_row(
+            "SELECT COUNT(*) FROM entries WHERE kind = ?1",
+            params![kind.as_str()],
+            |row| row.get::<_, i64>(0),
+        )
+        .map(|count| count as usize)
+    };
+    Ok(CacheStatus {
+        profiles: count(CacheKind::Profiles)?,
+        proxies: count(CacheKind::Proxies)?,
+        synced_at: db.query_row("SELECT MIN(synced_at) FROM syncs", [], |row| row.get(0))?,
+    })
+}
+
+impl AppCache {
+    pub(crate) async fn with_db<T>(
+        &self,
+        app: &AppHandle,
+        query: impl FnOnce(&mut Connection) -> rusqlite::Result<T>,
+    ) -> Result<T, String> {
+        if self.db.lock().unwrap().is_none() {
+            let db = open(app).await?;
+            self.db.lock().unwrap().get_or_insert(db);
+        }
+        let mut db = self.db.lock().unwrap();
+        let db = db.as_mut().ok_or_else(|| "Cache is not open".to_string())?;
+        query(db).map_err(|e| format!("Cache query failed: {}", e))
+    }
+}
+
+/// The backend's listing of `kind`, keyed by id.
+async fn fetch(app: &AppHandle, kind: CacheKind) -> Result<Vec<(String, Value)>, String> {
+    let response = client::forward(
+        app,
+        "GET",
+        kind.path(),
+        None,
+        HashMap::new(),
+        RequestOptions::default(),
+    )
+    .await?;
+    if !response.ok {
+        return Err(format!(
+            "Failed to fetch {}: HTTP {}",
+            kind.as_str(),
+            response.status
+        ));
+    }
+    let Value::Array(items) = response.body else {
+        return Err(format!("Unexpected {} listing from backend", kind.as_str()));
+    };
+    Ok(items
+        .into_iter()
+        .filter_map(|item| {
+            let id = match item.get("id")? {
+                Value::String(id) => id.clone(),
+                id => id.to_string(),
+            };
+            Some((id, item))
+        })
+        .collect())
+}
+
+fn notify(app: &AppHandle, status: &CacheStatus) {
+    let _ = app.emit("cache://updated", status);
+}
+
+/// Replaces the cached profiles and proxies with the backend's. Nothing is
+/// replaced unless both listings could be fetched.
+pub async fn sync(app: &AppHandle) -> Result<CacheStatus, String> {
+    let mut fetched = Vec::new();
+    for kind in CacheKind::ALL {
+        fetched.push((kind, fetch(app, kind).await?));
+    }
+    let synced_at = chrono::Utc::now().to_rfc3339();
+    let status = app
+        .state::<AppCache>()
+        .with_db(app, |db| {
+            let tx = db.transaction()?;
+            for (kind, items) in &fetched {
+                tx.execute(
+                    "DELETE FROM entries WHERE kind = ?1",
+                    params![kind.as_str()],
+                )?;
+                let mut insert = tx.prepare(
+                    "INSERT OR REPLACE INTO entries (kind, id, data) VALUES (?1, ?2, ?3)",
+                )?;
+                for (id, item) in items {
+                    insert.execute(params![kind.as_str(), id, item.to_string()])?;
+                }
+                drop(insert);
+                tx.execute(
+                    "INSERT OR REPLACE INTO syncs (kind, synced_at) VALUES (?1, ?2)",
+                    params![kind.as_str(), synced_at],
+                )?;
+            }
+            tx.commit()?;
+            status(db)
+        })
+        .await?;
+    notify(app, &status);
+    Ok(status)
+}
+
+/// Keeps the cache fresh for as long as the app runs.
+pub fn spawn(app: &AppHandle) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let mut state = app.state::<ServerStatus>().subscribe();
+        loop {
+            while !matches!(
+                *state.borrow_and_update(),
+                ServerState::Healthy | ServerState::Degraded
+            ) {
+                if state.changed().await.is_err() {
+                    return;
+                }
+            }
+
+            match sync(&app).await {
+                Ok(status) => log::debug!(
+                    "Cached {} profiles and {} proxies",
+                    status.profiles,
+                    status.proxies
+                ),
+                Err(e) => log::warn!("Failed to sync cache: {}", e),
+            }
+            // Again after a while, or as soon as the server comes back up
+            let _ = tokio::time::timeout(SYNC_INTERVAL, state.changed()).await;
+        }
+    });
+}
+
+/// One cached item, as carried in a backup.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CacheEntry {
+    pub kind: CacheKind,
+    pub id: String,
+    pub data: Value,
+}
+
+/// Everything in the cache, decrypted. Used by backups, which move to
+/// machines where this cache's key does not exist.
+pub async fn export(app: &AppHandle) -> Result<Vec<CacheEntry>, String> {
+    app.state::<AppCache>()
+        .with_db(app, |db| {
+            let mut select = db.prepare("SELECT kind, id, data FROM entries ORDER BY kind, id")?;
+            let entries = select
+                .query_map([], |row| {
+                    Ok((
+                        row.get::<_, String>(0)?,
+                        row.get::<_, String>(1)?,
+                        row.get::<_, String>(2)?,
+                    ))
+                })?
+                .filter_map(|row| row.ok())
+                .filter_map(|(kind, id, data)| {
+                    Some(CacheEntry {
+                        kind: serde_json::from_value(Value::String(kind)).ok()?,
+                        id,
+                        data: serde_json::from_str(&data).ok()?,
+                    })
+                })
+                .collect();
+            Ok(entries)
+        })
+        .await
+}
+
+/// Replaces the cache with `entries`. They count as unsynced until the
+/// backend is next asked.
+pub async fn import(app: &AppHandle, entries: &[CacheEntry]) -> Result<CacheStatus, String> {
+    let status = app
+        .state::<AppCache>()
+        .with_db(app, |db| {
+            let tx = db.transaction()?;
+            tx.execute_batch("DELETE FROM entries; DELETE FROM syncs;")?;
+            let mut insert =
+                tx.prepare("INSERT OR REPLACE INTO entries (kind, id, data) VALUES (?1, ?2, ?3)")?;
+            for entry in entries {
+                insert.execute(params![
+                    entry.kind.as_str(),
+                    entry.id,
+                    entry.data.to_string()
+                ])?;
+            }
+            drop(insert);
+            tx.commit()?;
+            status(db)
+        })
+        .await?;
+    notify(app, &status);
+    Ok(status)
+}
+
+/// Refreshes the cache from the backend now.
+#[tauri::command]
+pub async fn sync_cache(app_handle: AppHandle) -> Result<CacheStatus, String> {
+    sync(&app_handle).await
+}
+
+/// Cached profiles or proxies, as the backend last listed them.
+#[tauri::command]
+pub async fn read_cache(app_handle: AppHandle, kind: CacheKind) -> Result<CachedItems, String> {
+    app_handle
+        .state::<AppCache>()
+        .with_db(&app_handle, |db| {
+            let mut select = db.prepare("SELECT data FROM entries WHERE kind = ?1 ORDER BY id")?;
+            let items = select
+                .query_map(params![kind.as_str()], |row| row.get::<_, String>(0))?
+                .filter_map(|data| data.ok())
+                .filter_map(|data| serde_json::from_str(&data).ok())
+                .collect();
+            let synced_at = db
+                .query_row(
+                    "SELECT synced_at FROM syncs WHERE kind = ?1",
+                    params![kind.as_str()],
+                    |row| row.get(0),
+                )
+                .optional()?;
+            Ok(CachedItems { items, synced_at })
+        })
+        .await
+}
+
+#[tauri::command]
+pub async fn get_cache_status(app_handle: AppHandle) -> Result<CacheStatus, String> {
+    app_handle
+        .state::<AppCache>()
+        .with_db(&app_handle, |db| status(db))
+        .await
+}
+
+/// Empties the cache. It fills again on the next sync.
+#[tauri::command]
+pub async fn clear_cache(app_handle: AppHandle) -> Result<CacheStatus, String> {
+    let status = app_handle
+        .state::<AppCache>()
+        .with_db(&app_handle, |db| {
+            db.execute_batch("DELETE FROM entries; DELETE FROM syncs; VACUUM;")?;
+            status(db)
+        })
+        .await?;
+    log::info!("Cleared the local cache");
+    notify(&app_handle, &status);
+    Ok(status)
+}
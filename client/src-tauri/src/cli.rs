@@ -0,0 +1,167 @@
+//! Command line flags, so scripts can drive the app without the UI:
+//!
+//! - `--start-server-only` starts the backend and shows no window
+//! - `--launch-profile <id>` launches a profile once the backend is up, and
+//!   may be repeated; add `--headless` to launch them headless
+//! - `--export-logs <path>` writes a diagnostic bundle with the logs to
+//!   `path` and exits without starting the backend
+//! - `--no-ui` keeps the windows hidden; the tray can still show them
+//! - `--minimized` boots to the tray, as the login launch does
+//! - `--safe-mode` starts in safe mode, as after a crash
+//!
+//! A second launch hands its flags to the running instance, which acts on
+//! them in the same way but keeps running after an export.
+
+use std::path::{Path, PathBuf};
+
+use tauri::AppHandle;
+
+use crate::audit::{self, Initiator};
+
+const USAGE: &str = "Usage: nyx [options]
+
+Options:
+  --start-server-only      Start the backend without showing any window
+  --launch-profile <id>    Launch a profile once the backend is up (repeatable)
+  --headless               Launch the profiles above headless
+  --export-logs <path>     Write a diagnostic bundle to <path> and exit
+  --no-ui                  Keep the windows hidden
+  --minimized              Start in the tray
+  --safe-mode              Start without the server, schedules or shortcuts
+  -h, --help               Print this help
+  -V, --version            Print the version";
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CliArgs {
+    pub start_server_only: bool,
+    pub launch_profiles: Vec<String>,
+    pub headless: bool,
+    pub export_logs: Option<PathBuf>,
+    pub no_ui: bool,
+    pub minimized: bool,
+    pub safe_mode: bool,
+}
+
+impl CliArgs {
+    /// Whether the app runs without showing a window.
+    pub fn hide_ui(&self) -> bool {
+        self.no_ui || self.minimized || self.start_server_only || self.export_logs.is_some()
+    }
+}
+
+fn value<'a>(
+    flag: &str,
+    inline: Option<&'a str>,
+    args: &mut impl Iterator<Item = &'a String>,
+) -> Result<String, String> {
+    inline
+        .map(str::to_string)
+        .or_else(|| args.next().cloned())
+        .filter(|value| !value.is_empty())
+        .ok_or_else(|| format!("{} needs a value", flag))
+}
+
+/// Parses `args`, without the program name. Relative paths are resolved
+/// against `cwd`.
+pub fn parse(args: &[String], cwd: &Path) -> Result<CliArgs, String> {
+    let mut parsed = CliArgs::default();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let (flag, inline) = match arg.split_once('=') {
+            Some((flag, value)) if flag.starts_with("--") => (flag, Some(value)),
+            _ => (arg.as_str(), None),
+        };
+        match flag {
+            "--start-server-only" => parsed.start_server_only = true,
+            "--launch-profile" => parsed.launch_profiles.push(value(flag, inline, &mut args)?),
+            "--headless" => parsed.headless = true,
+            "--export-logs" => parsed.export_logs = Some(cwd.join(value(flag, inline, &mut args)?)),
+            "--no-ui" => parsed.no_ui = true,
+            "--safe-mode" => parsed.safe_mode = true,
+            crate::autostart::MINIMIZED_FLAG => parsed.minimized = true,
+            // Links are handled by `deep_link`
+            _ if arg.starts_with(&format!("{}://", crate::deep_link::SCHEME)) => {}
+            // Added by older macOS versions to apps started from Finder
+            _ if arg.starts_with("-psn_") => {}
+            _ => return Err(format!("Unknown argument: {}", arg)),
+        }
+    }
+    Ok(parsed)
+}
+
+/// Parses this process's arguments. Help, the version and bad arguments
+/// are printed and exit right away.
+pub fn from_env() -> CliArgs {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.iter().any(|arg| arg == "-h" || arg == "--help") {
+        println!("{}", USAGE);
+        std::process::exit(0);
+    }
+    if args.iter().any(|arg| arg == "-V" || arg == "--version") {
+        println!("nyx {}", env!("CARGO_PKG_VERSION"));
+        std::process::exit(0);
+    }
+    let cwd = std::env::current_dir().unwrap_or_default();
+    match parse(&args, &cwd) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, USAGE);
+            std::process::exit(2);
+        }
+    }
+}
+
+/// Writes the diagnostic bundle for `--export-logs`.
+pub async fn export_logs(app: &AppHandle, path: PathBuf) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+    }
+    crate::diagnostics::write_bundle_to(app, path).await
+}
+
+/// Launches the profiles given with `--launch-profile` once the backend is
+/// up. Each failure is logged and the rest still launch.
+pub fn spawn_launches(app: &AppHandle, args: &CliArgs) {
+    if args.launch_profiles.is_empty() {
+        return;
+    }
+    let app = app.clone();
+    let ids = args.launch_profiles.clone();
+    let headless = args.headless;
+    tauri::async_runtime::spawn(audit::scoped(Initiator::Cli, async move {
+        if let Err(e) = crate::deep_link::wait_for_backend(&app).await {
+            log::error!("Failed to launch profiles from the command line: {}", e);
+            return;
+        }
+        for id in ids {
+            match crate::profiles::launch_profile(app.clone(), id.clone(), Some(headless)).await {
+                Ok(_) => log::info!("Launched profile {} from the command line", id),
+                Err(e) => log::error!("Failed to launch profile {}: {}", id, e),
+            }
+        }
+    }));
+}
+
+/// Acts on the flags of a second launch in the running instance.
+pub fn handle_second_instance(app: &AppHandle, args: &[String], cwd: &str) {
+    let parsed = match parse(args.get(1..).unwrap_or_default(), Path::new(cwd)) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            log::warn!("Ignoring the second instance's arguments: {}", e);
+            CliArgs::default()
+        }
+    };
+    if !parsed.hide_ui() {
+        crate::boot::focus(app);
+    }
+    if let Some(path) = parsed.export_logs.clone() {
+        let app = app.clone();
+        tauri::async_runtime::spawn(async move {
+            if let Err(e) = export_logs(&app, path).await {
+                log::error!("{}", e);
+            }
+        });
+    }
+    spawn_launches(app, &parsed);
+}
@@ -0,0 +1,237 @@
+//! Copying and pasting structured data through the OS clipboard from Rust,
+//! where the webview's clipboard permissions do not get in the way.
+//!
+//! What is copied is validated first and written as plain text other apps
+//! understand. What is pasted is recognized as a proxy list, a profile
+//! export, a token or plain text, or checked against the kind the caller
+//! expects. Copied tokens are cleared from the clipboard again after
+//! [`TOKEN_LIFETIME`], unless something else was copied meanwhile.
+
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tauri::{AppHandle, Manager};
+use tauri_plugin_clipboard_manager::ClipboardExt;
+
+use crate::proxy::import::parse_list;
+use crate::proxy::{ProxyConfig, ProxyProtocol};
+use crate::server::auth::ApiToken;
+
+const TOKEN_LIFETIME: Duration = Duration::from_secs(60);
+const MIN_TOKEN_LEN: usize = 16;
+
+/// Something to copy.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum ClipboardPayload {
+    /// One proxy per line, credentials included.
+    ProxyList {
+        proxies: Vec<ProxyConfig>,
+    },
+    /// A profile as pretty-printed JSON.
+    ProfileExport {
+        profile: Value,
+    },
+    /// A token; without one, the embedded server's token of this launch.
+    ApiToken {
+        token: Option<String>,
+    },
+    Text {
+        text: String,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ClipboardKind {
+    ProxyList,
+    ProfileExport,
+    ApiToken,
+    Text,
+}
+
+/// What the clipboard holds.
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum ClipboardContents {
+    Empty,
+    #[serde(rename_all = "camelCase")]
+    ProxyList {
+        proxies: Vec<ProxyConfig>,
+        duplicates: usize,
+        /// Lines that are not proxies.
+        invalid: Vec<String>,
+    },
+    ProfileExport {
+        profile: Value,
+    },
+    ApiToken {
+        token: String,
+    },
+    Text {
+        text: String,
+    },
+}
+
+fn render(app: &AppHandle, payload: ClipboardPayload) -> Result<(String, bool), String> {
+    match payload {
+        ClipboardPayload::ProxyList { proxies } => {
+            if proxies.is_empty() {
+                return Err("No proxies to copy".to_string());
+            }
+            if let Some(proxy) = proxies.iter().find(|proxy| proxy.host.trim().is_empty()) {
+                return Err(format!("Proxy without a host: {}", proxy));
+            }
+            let lines: Vec<String> = proxies.iter().map(ProxyConfig::to_line).collect();
+            Ok((lines.join("\n"), false))
+        }
+        ClipboardPayload::ProfileExport { profile } => profile_of(profile).and_then(|profile| {
+            serde_json::to_string_pretty(&profile)
+                .map(|json| (json, false))
+                .map_err(|e| format!("Failed to encode profile: {}", e))
+        }),
+        ClipboardPayload::ApiToken { token } => {
+            let token = match token {
+                Some(token) => token_of(&token)?,
+                None => app.state::<ApiToken>().value().to_string(),
+            };
+            Ok((token, true))
+        }
+        ClipboardPayload::Text { text } => Ok((text, false)),
+    }
+}
+
+/// A profile export is a JSON object with a name.
+fn profile_of(value: Value) -> Result<Value, String> {
+    let named = value
+        .get("name")
+        .and_then(Value::as_str)
+        .is_some_and(|name| !name.trim().is_empty());
+    if !value.is_object() || !named {
+        return Err("A profile export must be a JSON object with a name".to_string());
+    }
+    Ok(value)
+}
+
+/// A token is a single word of at least [`MIN_TOKEN_LEN`] characters.
+fn token_of(text: &str) -> Result<String, String> {
+    let token = text.trim();
+    let valid = token.len() >= MIN_TOKEN_LEN
+        && token.chars().all(|c| {
+            c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | '~' | '+' | '/' | '=')
+        });
+    if !valid {
+        return Err(format!(
+            "A token must be a single word of at least {} characters",
+            MIN_TOKEN_LEN
+        ));
+    }
+    Ok(token.to_string())
+}
+
+fn proxies_of(text: &str) -> ClipboardContents {
+    let (proxies, duplicates, invalid) = parse_list(text, ProxyProtocol::default());
+    ClipboardContents::ProxyList {
+        proxies,
+        duplicates,
+        invalid,
+    }
+}
+
+/// Reads `text` as the first kind it fits, most specific first.
+fn recognize(text: &str) -> ClipboardContents {
+    if let Ok(value) = serde_json::from_str::<Value>(text) {
+        if let Ok(profile) = profile_of(value) {
+            return ClipboardContents::ProfileExport { profile };
+        }
+    }
+    if let ClipboardContents::ProxyList {
+        proxies,
+        duplicates,
+        invalid,
+    } = proxies_of(text)
+    {
+        // Mostly proxies, so a stray header line does not hide the list
+        if !proxies.is_empty() && invalid.len() <= proxies.len() {
+            return ClipboardContents::ProxyList {
+                proxies,
+                duplicates,
+                invalid,
+            };
+        }
+    }
+    if let Ok(token) = token_of(text) {
+        return ClipboardContents::ApiToken { token };
+    }
+    ClipboardContents::Text {
+        text: text.to_string(),
+    }
+}
+
+fn parse_as(text: &str, kind: ClipboardKind) -> Result<ClipboardContents, String> {
+    match kind {
+        ClipboardKind::ProxyList => match proxies_of(text) {
+            ClipboardContents::ProxyList { proxies, .. } if proxies.is_empty() => {
+                Err("The clipboard holds no proxies".to_string())
+            }
+            contents => Ok(contents),
+        },
+        ClipboardKind::ProfileExport => {
+            let value = serde_json::from_str(text)
+                .map_err(|e| format!("The clipboard does not hold JSON: {}", e))?;
+            Ok(ClipboardContents::ProfileExport {
+                profile: profile_of(value)?,
+            })
+        }
+        ClipboardKind::ApiToken => Ok(ClipboardContents::ApiToken {
+            token: token_of(text)?,
+        }),
+        ClipboardKind::Text => Ok(ClipboardContents::Text {
+            text: text.to_string(),
+        }),
+    }
+}
+
+/// Copies `payload` after validating it.
+#[tauri::command]
+pub fn copy_to_clipboard(app_handle: AppHandle, payload: ClipboardPayload) -> Result<(), String> {
+    let (text, sensitive) = render(&app_handle, payload)?;
+    app_handle
+        .clipboard()
+        .write_text(text.clone())
+        .map_err(|e| format!("Failed to copy to the clipboard: {}", e))?;
+    if sensitive {
+        let app = app_handle.clone();
+        tauri::async_runtime::spawn(async move {
+            tokio::time::sleep(TOKEN_LIFETIME).await;
+            let clipboard = app.clipboard();
+            if clipboard.read_text().is_ok_and(|current| current == text) {
+                let _ = clipboard.clear();
+            }
+        });
+    }
+    Ok(())
+}
+
+/// The clipboard's text, as `expect` when given and otherwise as whatever
+/// it looks like. Fails when it does not hold the expected kind.
+#[tauri::command]
+pub fn read_clipboard(
+    app_handle: AppHandle,
+    expect: Option<ClipboardKind>,
+) -> Result<ClipboardContents, String> {
+    // Images and empty clipboards have no text
+    let text = app_handle.clipboard().read_text().unwrap_or_default();
+    let text = text.trim();
+    if text.is_empty() {
+        return match expect {
+            None => Ok(ClipboardContents::Empty),
+            Some(_) => Err("The clipboard is empty".to_string()),
+        };
+    }
+    match expect {
+        Some(kind) => parse_as(text, kind),
+        None => Ok(recognize(text)),
+    }
+}
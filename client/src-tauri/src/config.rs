@@ -0,0 +1,912 @@
+//! Persisted configuration for how the shell finds and talks to the backend.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+
+use crate::diagnostics::REDACTED;
+use crate::permissions::PermissionScope;
+use crate::secrets::SECRET_REF_PREFIX;
+
+pub(crate) const CONFIG_FILE: &str = "config.json";
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct AppConfig {
+    pub health: HealthConfig,
+    pub connection: ConnectionConfig,
+    pub updates: UpdateConfig,
+    pub server: ServerConfig,
+    pub http: HttpConfig,
+    pub api_limits: ApiLimitsConfig,
+    pub monitor: MonitorConfig,
+    pub browser: BrowserConfig,
+    pub backup: BackupConfig,
+    /// Global shortcuts as accelerators like `CommandOrControl+Shift+N`;
+    /// see [`crate::shortcuts`].
+    pub shortcuts: BTreeMap<ShortcutAction, String>,
+    /// Named backends to switch between; see [`crate::server::environments`].
+    pub environments: Vec<Environment>,
+    /// Name of the environment `connection` was last switched to.
+    pub active_environment: Option<String>,
+    pub tls_proxy: TlsProxyConfig,
+    pub idle: IdleConfig,
+    /// Where profiles, browser builds and downloads live instead of app
+    /// data; see [`crate::data_dir`].
+    pub data_dir: Option<PathBuf>,
+    pub gc: GcConfig,
+    pub automation: AutomationConfig,
+    pub control_api: ControlApiConfig,
+    pub permissions: PermissionsConfig,
+    pub proxy_pool: ProxyPoolConfig,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ConnectionMode {
+    /// Spawn and supervise the bundled server.
+    #[default]
+    Embedded,
+    /// Use a backend running elsewhere, e.g. another machine or Docker.
+    ExternalUrl,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ConnectionConfig {
+    pub mode: ConnectionMode,
+    /// Base URL of the external backend, e.g. `https://nyx.internal:8443`.
+    pub external_url: Option<String>,
+    /// Accept self-signed certificates from the external backend.
+    pub accept_invalid_certs: bool,
+    /// Token the external backend expects in `X-Nyx-Token`, usually a
+    /// keychain reference.
+    pub token: Option<String>,
+}
+
+impl ConnectionConfig {
+    /// The token, unless it is a keychain reference.
+    pub fn plaintext_token(&self) -> Option<&str> {
+        self.token
+            .as_deref()
+            .filter(|token| !token.is_empty() && !token.starts_with(SECRET_REF_PREFIX))
+    }
+
+    /// This connection as the webview gets to see it, without a token kept
+    /// in plaintext.
+    pub fn redacted(mut self) -> Self {
+        if self.plaintext_token().is_some() {
+            self.token = Some(REDACTED.to_string());
+        }
+        self
+    }
+
+    /// Keeps the token of `saved` when this one came back redacted.
+    pub fn keep_token(&mut self, saved: &ConnectionConfig) {
+        if self.token.as_deref() == Some(REDACTED) {
+            self.token = saved.token.clone();
+        }
+    }
+}
+
+/// A named backend, e.g. `local`, `staging` or `prod`.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Environment {
+    pub name: String,
+    #[serde(flatten)]
+    pub connection: ConnectionConfig,
+}
+
+/// HTTPS in front of the embedded server; see [`crate::server::tls`].
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct TlsProxyConfig {
+    pub enabled: bool,
+    /// Port to listen on; any free port when unset.
+    pub port: Option<u16>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct HealthConfig {
+    /// Path of the health endpoint, relative to the server base URL.
+    pub path: String,
+    /// Port to probe instead of the server port, e.g. behind a sidecar proxy.
+    pub port: Option<u16>,
+}
+
+impl Default for HealthConfig {
+    fn default() -> Self {
+        Self {
+            path: "/health".to_string(),
+            port: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct UpdateConfig {
+    /// Check for a new app version shortly after startup.
+    pub auto_check: bool,
+    /// Update manifest URL, overriding the endpoints in `tauri.conf.json`.
+    pub endpoint: Option<String>,
+    /// Release manifest listing the latest server build per platform.
+    pub server_manifest: Option<String>,
+}
+
+impl Default for UpdateConfig {
+    fn default() -> Self {
+        Self {
+            auto_check: true,
+            endpoint: None,
+            server_manifest: None,
+        }
+    }
+}
+
+/// How the shell's HTTP client talks to the backend. Individual requests can
+/// override the timeout and retry count.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct HttpConfig {
+    pub timeout_ms: u64,
+    pub connect_timeout_ms: u64,
+    /// Extra attempts for idempotent requests that fail to connect or time out.
+    pub retries: u32,
+    /// Delay before the first retry; it doubles on each further attempt.
+    pub retry_delay_ms: u64,
+    pub max_retry_delay_ms: u64,
+    /// Idle connections kept open per host.
+    pub pool_max_idle: usize,
+    pub pool_idle_timeout_ms: u64,
+}
+
+/// Protection for the backend against request floods; see
+/// [`crate::server::limits`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ApiLimitsConfig {
+    /// Frontend requests allowed per second on average; 0 for no limit.
+    pub requests_per_sec: u32,
+    /// Requests allowed in a burst above the average.
+    pub burst: u32,
+    /// Failed backend calls in a row that open the circuit; 0 to never open.
+    pub failure_threshold: u32,
+    /// How long an open circuit waits before probing the backend.
+    pub open_ms: u64,
+}
+
+impl Default for ApiLimitsConfig {
+    fn default() -> Self {
+        Self {
+            requests_per_sec: 50,
+            burst: 100,
+            failure_threshold: 5,
+            open_ms: 10_000,
+        }
+    }
+}
+
+impl Default for HttpConfig {
+    fn default() -> Self {
+        Self {
+            timeout_ms: 30_000,
+            connect_timeout_ms: 5_000,
+            retries: 2,
+            retry_delay_ms: 250,
+            max_retry_delay_ms: 5_000,
+            pool_max_idle: 8,
+            pool_idle_timeout_ms: 90_000,
+        }
+    }
+}
+
+/// Resource monitoring of the server process.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct MonitorConfig {
+    /// How often the server process is sampled.
+    pub interval_ms: u64,
+    /// Resident memory above which `server://high-memory` fires; 0 disables it.
+    pub high_memory_mb: u64,
+}
+
+impl Default for MonitorConfig {
+    fn default() -> Self {
+        Self {
+            interval_ms: 5_000,
+            high_memory_mb: 2_048,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum IdleAction {
+    /// Stop the embedded server.
+    #[default]
+    Stop,
+    /// Ask the server to cut its background work, keeping it running.
+    LowPower,
+}
+
+/// Suspension of the server while the UI is idle; see
+/// [`crate::server::idle`].
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct IdleConfig {
+    /// Minutes without UI activity before the server is suspended; 0 never
+    /// suspends it.
+    pub suspend_after_mins: u64,
+    pub action: IdleAction,
+}
+
+/// Limits for one kind of file the garbage collector prunes; the oldest go
+/// first once `max_size_mb` is exceeded.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct GcPolicy {
+    pub max_age_days: Option<u32>,
+    pub max_size_mb: Option<u64>,
+}
+
+/// Automatic garbage collection of what piles up on disk; see
+/// [`crate::gc`].
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct GcConfig {
+    pub enabled: bool,
+    /// Hours between runs.
+    pub interval_hours: u64,
+    pub webview_cache: GcPolicy,
+    /// Files in the downloads folder of the data directory.
+    pub downloads: GcPolicy,
+    /// Rotated log files; the ones being written are never touched.
+    pub logs: GcPolicy,
+    /// Move profile directories the backend has no profile for to the
+    /// trash. Off unless turned on.
+    pub orphaned_profiles: bool,
+    /// Days an orphaned profile directory is left alone after its last
+    /// change, in case its profile is still being created or restored.
+    pub orphan_grace_days: u32,
+}
+
+impl Default for GcConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            interval_hours: 24,
+            webview_cache: GcPolicy {
+                max_age_days: Some(30),
+                max_size_mb: Some(500),
+            },
+            downloads: GcPolicy {
+                max_age_days: Some(14),
+                max_size_mb: None,
+            },
+            logs: GcPolicy {
+                max_age_days: Some(30),
+                max_size_mb: Some(200),
+            },
+            orphaned_profiles: false,
+            orphan_grace_days: 7,
+        }
+    }
+}
+
+/// How profile browsers are started from the shell.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct BrowserConfig {
+    /// Browser executable; defaults to the Camoufox build in the user cache.
+    pub executable: Option<PathBuf>,
+    /// Extra command line arguments for every profile.
+    pub args: Vec<String>,
+    /// URL of the manifest listing installable browser builds.
+    pub manifest: Option<String>,
+    /// Hardware acceleration for every profile without its own setting.
+    pub gpu: GpuConfig,
+    /// Per-profile overrides of `gpu`, by profile ID.
+    pub profile_gpu: BTreeMap<String, GpuConfig>,
+}
+
+/// The command scopes granted to windows; see [`crate::permissions`].
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct PermissionsConfig {
+    /// Scopes by window label, or by label prefix ending in `*`.
+    pub grants: BTreeMap<String, Vec<PermissionScope>>,
+}
+
+impl Default for PermissionsConfig {
+    fn default() -> Self {
+        Self {
+            grants: crate::permissions::default_grants(),
+        }
+    }
+}
+
+/// Upkeep of the proxy pool; see [`crate::proxy::pool`].
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ProxyPoolConfig {
+    /// Re-check pooled proxies in the background.
+    pub recheck_enabled: bool,
+    /// Minutes between checks of a proxy.
+    pub recheck_interval_mins: u64,
+    /// Failed checks in a row that quarantine a proxy.
+    pub quarantine_after: u32,
+    /// Minutes between probes of a quarantined proxy.
+    pub recovery_interval_mins: u64,
+}
+
+impl Default for ProxyPoolConfig {
+    fn default() -> Self {
+        Self {
+            recheck_enabled: true,
+            recheck_interval_mins: 15,
+            quarantine_after: 3,
+            recovery_interval_mins: 60,
+        }
+    }
+}
+
+/// What a control API token may do.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ControlScope {
+    /// List running profiles.
+    ProfilesRead,
+    /// Launch and stop profiles.
+    ProfilesControl,
+    ProxyCheck,
+}
+
+/// A token for the control API. Only its hash is kept; the token itself is
+/// shown once, when it is created.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ControlToken {
+    pub id: String,
+    pub name: String,
+    /// SHA-256 of the token, as hex.
+    pub token_hash: String,
+    pub scopes: Vec<ControlScope>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// The local REST API for scripts; see [`crate::control_api`].
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ControlApiConfig {
+    pub enabled: bool,
+    /// Port to listen on; any free port when unset.
+    pub port: Option<u16>,
+    pub tokens: Vec<ControlToken>,
+}
+
+/// Which profiles external automation may attach to; see
+/// [`crate::profiles::automation`].
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct AutomationConfig {
+    pub enabled: bool,
+    /// IDs of the profiles launched with a remote debugging endpoint.
+    pub exposed_profiles: Vec<String>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum GpuMode {
+    /// Whatever the browser decides for the machine.
+    #[default]
+    Auto,
+    /// Used even on GPUs the browser blocklists.
+    On,
+    /// Rendered in software, for VMs and drivers that crash the browser.
+    Off,
+}
+
+/// The graphics API ANGLE translates WebGL to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AngleBackend {
+    D3d11,
+    D3d9,
+    Gl,
+    Vulkan,
+    Metal,
+    Swiftshader,
+}
+
+/// Hardware acceleration of a profile browser; see [`crate::profiles::gpu`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct GpuConfig {
+    pub mode: GpuMode,
+    pub angle_backend: Option<AngleBackend>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BackupFrequency {
+    #[default]
+    Daily,
+    Weekly,
+}
+
+/// Automatic backups; see [`crate::backup::schedule`].
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct BackupConfig {
+    pub enabled: bool,
+    pub frequency: BackupFrequency,
+    /// Local time of day to back up at, as `HH:MM`.
+    pub at: String,
+    /// Day of weekly backups, e.g. `Sun`.
+    pub weekday: chrono::Weekday,
+    /// Where backups are written; `app_data_dir()/backups` if unset.
+    pub folder: Option<PathBuf>,
+    /// Encrypt with the password kept in the keychain as `backup.password`.
+    pub encrypt: bool,
+    /// How many scheduled backups to keep, newest first.
+    pub keep: usize,
+    /// Scheduled backups older than this are deleted, except the newest.
+    pub max_age_days: Option<u32>,
+}
+
+impl Default for BackupConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            frequency: BackupFrequency::default(),
+            at: "03:00".to_string(),
+            weekday: chrono::Weekday::Sun,
+            folder: None,
+            encrypt: false,
+            keep: 7,
+            max_age_days: None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ShortcutAction {
+    /// Shows the main window, or hides it when it has focus.
+    ToggleWindow,
+    LaunchLastProfile,
+    StopAllProfiles,
+}
+
+/// Log levels the server accepts, quietest first.
+pub const SERVER_LOG_LEVELS: &[&str] = &["critical", "error", "warning", "info", "debug", "trace"];
+
+/// How the embedded server is launched, on top of what the shell passes
+/// itself.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ServerConfig {
+    /// Preferred listen port; the shell still moves off it if it is taken.
+    pub port: Option<u16>,
+    /// Extra command line arguments, e.g. `--debug`.
+    pub args: Vec<String>,
+    /// Extra environment variables, e.g. a database path or worker count.
+    /// Secrets among them are kept in the keychain; see [`crate::secrets`].
+    pub env: BTreeMap<String, String>,
+    /// Directory to run the server in instead of the app data directory.
+    pub working_dir: Option<PathBuf>,
+    /// One of [`SERVER_LOG_LEVELS`].
+    pub log_level: Option<String>,
+    /// How long a starting server gets to pass its health check.
+    pub startup_timeout_ms: Option<u64>,
+    /// Scheduling priority of the server and its workers.
+    pub priority: ServerPriority,
+    /// Cores the server may run on, counted from 0; all of them when unset.
+    pub cpu_affinity: Option<Vec<usize>>,
+}
+
+/// How much CPU time the server gets next to the UI: a priority class on
+/// Windows, a nice value on Unix.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ServerPriority {
+    #[default]
+    Normal,
+    BelowNormal,
+    /// Only runs when nothing else wants the CPU.
+    Idle,
+}
+
+fn read_config(path: Option<&Path>) -> AppConfig {
+    path.and_then(|path| std::fs::read(path).ok())
+        .and_then(|contents| match serde_json::from_slice(&contents) {
+            Ok(config) => Some(config),
+            Err(e) => {
+                log::warn!("Ignoring unreadable {}: {}", CONFIG_FILE, e);
+                None
+            }
+        })
+        .unwrap_or_default()
+}
+
+/// Managed state wrapping [`AppConfig`] and its file in `app_config_dir()`.
+pub struct ConfigStore {
+    path: Option<PathBuf>,
+    config: Mutex<AppConfig>,
+}
+
+impl ConfigStore {
+    pub fn load(app: &AppHandle) -> Self {
+        let path = app
+            .path()
+            .app_config_dir()
+            .ok()
+            .map(|dir| dir.join(CONFIG_FILE));
+        let config = read_config(path.as_deref());
+        Self {
+            path,
+            config: Mutex::new(config),
+        }
+    }
+
+    /// Rereads the file, e.g. after a backup was restored over it.
+    pub fn reload(&self) {
+        *self.config.lock().unwrap() = read_config(self.path.as_deref());
+    }
+
+    pub fn get(&self) -> AppConfig {
+        self.config.lock().unwrap().clone()
+    }
+
+    /// Applies `change` and writes the result back to disk, through a
+    /// partial file so a crash mid-write leaves the old config intact.
+    pub fn update(&self, change: impl FnOnce(&mut AppConfig)) -> Result<AppConfig, String> {
+        let mut config = self.config.lock().unwrap();
+        let mut updated = config.clone();
+        change(&mut updated);
+
+        if let Some(path) = &self.path {
+            let contents = serde_json::to_vec_pretty(&updated).map_err(|e| e.to_string())?;
+            let partial = path.with_extension("json.part");
+            path.parent()
+                .map_or(Ok(()), std::fs::create_dir_all)
+                .and_then(|_| std::fs::write(&partial, contents))
+                .and_then(|()| std::fs::rename(&partial, path))
+                .map_err(|e| format!("Failed to save {}: {}", CONFIG_FILE, e))?;
+        }
+        *config = updated.clone();
+        Ok(updated)
+    }
+}
+
+#[tauri::command]
+pub fn get_health_config(config: tauri::State<'_, ConfigStore>) -> HealthConfig {
+    config.get().health
+}
+
+#[tauri::command]
+pub fn set_health_config(
+    config: tauri::State<'_, ConfigStore>,
+    health: HealthConfig,
+) -> Result<HealthConfig, String> {
+    if !health.path.starts_with('/') {
+        return Err(format!("Health path must start with '/': {}", health.path));
+    }
+    config.update(|c| c.health = health).map(|c| c.health)
+}
+
+/// The connection settings, with a token not yet in the keychain redacted.
+#[tauri::command]
+pub fn get_connection_config(config: tauri::State<'_, ConfigStore>) -> ConnectionConfig {
+    config.get().connection.redacted()
+}
+
+/// Saves the connection settings and switches between the embedded and the
+/// external server right away. A plaintext token is stored in the keychain
+/// first, and a redacted one keeps the token saved before.
+#[tauri::command]
+pub async fn set_connection_config(
+    app_handle: AppHandle,
+    mut connection: ConnectionConfig,
+) -> Result<ConnectionConfig, String> {
+    crate::server::connection::validate(&connection)?;
+    connection.keep_token(&app_handle.state::<ConfigStore>().get().connection);
+    if let Some(token) = connection.plaintext_token().map(str::to_string) {
+        let name = crate::secrets::CONNECTION_TOKEN;
+        match crate::secrets::store(&app_handle, name, token).await {
+            Ok(()) => connection.token = Some(format!("{}{}", SECRET_REF_PREFIX, name)),
+            Err(e) => log::warn!("Leaving the server token in plaintext: {}", e),
+        }
+    }
+    let saved = app_handle
+        .state::<ConfigStore>()
+        .update(|c| c.connection = connection)?
+        .connection;
+    crate::server::connection::apply(&app_handle).await?;
+    Ok(saved.redacted())
+}
+
+#[tauri::command]
+pub fn get_tls_proxy_config(config: tauri::State<'_, ConfigStore>) -> TlsProxyConfig {
+    config.get().tls_proxy
+}
+
+/// Saves the TLS proxy settings and starts or stops the proxy right away.
+#[tauri::command]
+pub async fn set_tls_proxy_config(
+    app_handle: AppHandle,
+    tls_proxy: TlsProxyConfig,
+) -> Result<TlsProxyConfig, String> {
+    if tls_proxy.port == Some(0) {
+        return Err("TLS proxy port must be between 1 and 65535".to_string());
+    }
+    let saved = app_handle
+        .state::<ConfigStore>()
+        .update(|c| c.tls_proxy = tls_proxy)?
+        .tls_proxy;
+    crate::server::tls::apply(&app_handle).await?;
+    Ok(saved)
+}
+
+#[tauri::command]
+pub fn get_update_config(config: tauri::State<'_, ConfigStore>) -> UpdateConfig {
+    config.get().updates
+}
+
+#[tauri::command]
+pub fn set_update_config(
+    config: tauri::State<'_, ConfigStore>,
+    updates: UpdateConfig,
+) -> Result<UpdateConfig, String> {
+    for endpoint in updates.endpoint.iter().chain(&updates.server_manifest) {
+        reqwest::Url::parse(endpoint)
+            .map_err(|e| format!("Invalid update endpoint {}: {}", endpoint, e))?;
+    }
+    config.update(|c| c.updates = updates).map(|c| c.updates)
+}
+
+#[tauri::command]
+pub fn get_server_config(config: tauri::State<'_, ConfigStore>) -> ServerConfig {
+    config.get().server
+}
+
+/// Saves the server launch settings. They apply from the next server start.
+/// Secret environment values are moved to the keychain before returning.
+#[tauri::command]
+pub async fn set_server_config(
+    app_handle: AppHandle,
+    server: ServerConfig,
+) -> Result<ServerConfig, String> {
+    if server.port == Some(0) {
+        return Err("Server port must be between 1 and 65535".to_string());
+    }
+    if server.startup_timeout_ms == Some(0) {
+        return Err("Startup timeout must be greater than zero".to_string());
+    }
+    if let Some(level) = &server.log_level {
+        if !SERVER_LOG_LEVELS.contains(&level.as_str()) {
+            return Err(format!(
+                "Unknown log level {}; expected one of {}",
+                level,
+                SERVER_LOG_LEVELS.join(", ")
+            ));
+        }
+    }
+    if let Some(key) = server
+        .env
+        .keys()
+        .find(|key| key.is_empty() || key.contains(['=', '\0']))
+    {
+        return Err(format!("Invalid environment variable name: {:?}", key));
+    }
+    if let Some(cores) = &server.cpu_affinity {
+        crate::server::priority::validate_cores(cores)?;
+    }
+    if let Some(dir) = server.working_dir.as_ref().filter(|dir| !dir.is_dir()) {
+        return Err(format!(
+            "Working directory does not exist: {}",
+            dir.display()
+        ));
+    }
+
+    let config = app_handle.state::<ConfigStore>();
+    config.update(|c| c.server = server)?;
+    crate::secrets::migrate(&app_handle).await;
+    Ok(config.get().server)
+}
+
+#[tauri::command]
+pub fn get_http_config(config: tauri::State<'_, ConfigStore>) -> HttpConfig {
+    config.get().http
+}
+
+/// Saves the HTTP client settings; the pooled client is rebuilt on its next
+/// use.
+#[tauri::command]
+pub fn set_http_config(
+    config: tauri::State<'_, ConfigStore>,
+    http: HttpConfig,
+) -> Result<HttpConfig, String> {
+    if http.timeout_ms == 0 || http.connect_timeout_ms == 0 {
+        return Err("Timeouts must be greater than zero".to_string());
+    }
+    if http.retry_delay_ms > http.max_retry_delay_ms {
+        return Err("Retry delay cannot exceed the maximum retry delay".to_string());
+    }
+    config.update(|c| c.http = http).map(|c| c.http)
+}
+
+#[tauri::command]
+pub fn get_api_limits_config(config: tauri::State<'_, ConfigStore>) -> ApiLimitsConfig {
+    config.get().api_limits
+}
+
+#[tauri::command]
+pub fn set_api_limits_config(
+    config: tauri::State<'_, ConfigStore>,
+    api_limits: ApiLimitsConfig,
+) -> Result<ApiLimitsConfig, String> {
+    if api_limits.requests_per_sec > 0 && api_limits.burst == 0 {
+        return Err("Burst must be at least 1 when requests are limited".to_string());
+    }
+    if api_limits.failure_threshold > 0 && api_limits.open_ms == 0 {
+        return Err("Open time must be greater than zero".to_string());
+    }
+    config
+        .update(|c| c.api_limits = api_limits)
+        .map(|c| c.api_limits)
+}
+
+#[tauri::command]
+pub fn get_monitor_config(config: tauri::State<'_, ConfigStore>) -> MonitorConfig {
+    config.get().monitor
+}
+
+#[tauri::command]
+pub fn set_monitor_config(
+    config: tauri::State<'_, ConfigStore>,
+    monitor: MonitorConfig,
+) -> Result<MonitorConfig, String> {
+    if monitor.interval_ms < 500 {
+        return Err("Sampling interval must be at least 500 ms".to_string());
+    }
+    config.update(|c| c.monitor = monitor).map(|c| c.monitor)
+}
+
+#[tauri::command]
+pub fn get_browser_config(config: tauri::State<'_, ConfigStore>) -> BrowserConfig {
+    config.get().browser
+}
+
+/// Saves the browser launch settings. They apply from the next profile launch.
+#[tauri::command]
+pub fn set_browser_config(
+    config: tauri::State<'_, ConfigStore>,
+    browser: BrowserConfig,
+) -> Result<BrowserConfig, String> {
+    if let Some(path) = browser.executable.as_ref().filter(|path| !path.is_file()) {
+        return Err(format!("Browser executable not found: {}", path.display()));
+    }
+    if let Some(manifest) = &browser.manifest {
+        reqwest::Url::parse(manifest)
+            .map_err(|e| format!("Invalid browser manifest {}: {}", manifest, e))?;
+    }
+    for id in browser.profile_gpu.keys() {
+        crate::profiles::validate_id(id)?;
+    }
+    config.update(|c| c.browser = browser).map(|c| c.browser)
+}
+
+#[tauri::command]
+pub fn get_backup_config(config: tauri::State<'_, ConfigStore>) -> BackupConfig {
+    config.get().backup
+}
+
+/// Saves the backup schedule. The scheduler picks it up within a minute.
+#[tauri::command]
+pub fn set_backup_config(
+    config: tauri::State<'_, ConfigStore>,
+    backup: BackupConfig,
+) -> Result<BackupConfig, String> {
+    if crate::backup::schedule::parse_time(&backup.at).is_none() {
+        return Err(format!(
+            "Invalid backup time {:?}; expected HH:MM",
+            backup.at
+        ));
+    }
+    if let Some(folder) = backup.folder.as_ref().filter(|folder| !folder.is_dir()) {
+        return Err(format!(
+            "Backup folder does not exist: {}",
+            folder.display()
+        ));
+    }
+    if backup.keep == 0 {
+        return Err("Keep at least one backup".to_string());
+    }
+    if backup.max_age_days == Some(0) {
+        return Err("Maximum backup age must be at least one day".to_string());
+    }
+    config.update(|c| c.backup = backup).map(|c| c.backup)
+}
+
+#[tauri::command]
+pub fn get_idle_config(config: tauri::State<'_, ConfigStore>) -> IdleConfig {
+    config.get().idle
+}
+
+/// Saves the idle settings. The idle manager picks them up within a minute.
+#[tauri::command]
+pub fn set_idle_config(
+    config: tauri::State<'_, ConfigStore>,
+    idle: IdleConfig,
+) -> Result<IdleConfig, String> {
+    config.update(|c| c.idle = idle).map(|c| c.idle)
+}
+
+#[tauri::command]
+pub fn get_gc_config(config: tauri::State<'_, ConfigStore>) -> GcConfig {
+    config.get().gc
+}
+
+/// Saves the garbage collection policies, used from the next run on.
+#[tauri::command]
+pub fn set_gc_config(
+    config: tauri::State<'_, ConfigStore>,
+    gc: GcConfig,
+) -> Result<GcConfig, String> {
+    if gc.interval_hours == 0 {
+        return Err("The garbage collection interval must be at least an hour".to_string());
+    }
+    config.update(|c| c.gc = gc).map(|c| c.gc)
+}
+
+#[tauri::command]
+pub fn get_automation_config(config: tauri::State<'_, ConfigStore>) -> AutomationConfig {
+    config.get().automation
+}
+
+/// Saves which profiles may be automated. Running browsers keep the
+/// endpoint they were launched with until they are restarted.
+#[tauri::command]
+pub fn set_automation_config(
+    config: tauri::State<'_, ConfigStore>,
+    mut automation: AutomationConfig,
+) -> Result<AutomationConfig, String> {
+    for id in &automation.exposed_profiles {
+        crate::profiles::validate_id(id)?;
+    }
+    automation.exposed_profiles.sort();
+    automation.exposed_profiles.dedup();
+    config
+        .update(|c| c.automation = automation)
+        .map(|c| c.automation)
+}
+
+#[tauri::command]
+pub fn get_proxy_pool_config(config: tauri::State<'_, ConfigStore>) -> ProxyPoolConfig {
+    config.get().proxy_pool
+}
+
+/// Saves how the proxy pool is kept up; proxies are checked by it from
+/// their next check on.
+#[tauri::command]
+pub fn set_proxy_pool_config(
+    config: tauri::State<'_, ConfigStore>,
+    proxy_pool: ProxyPoolConfig,
+) -> Result<ProxyPoolConfig, String> {
+    if proxy_pool.recheck_interval_mins == 0 || proxy_pool.recovery_interval_mins == 0 {
+        return Err("Proxy pool check intervals must be at least a minute".to_string());
+    }
+    if proxy_pool.quarantine_after == 0 {
+        return Err("Quarantining must take at least one failed check".to_string());
+    }
+    config
+        .update(|c| c.proxy_pool = proxy_pool)
+        .map(|c| c.proxy_pool)
+}
@@ -0,0 +1,418 @@
+//! An optional REST API on localhost, for scripts and CI systems to drive
+//! the app without its UI.
+//!
+//! Off unless `controlApi.enabled` is set. Every request needs a token as
+//! `Authorization: Bearer <token>`, and each token carries the
+//! [`ControlScope`]s it may use:
+//!
+//! - `GET /v1/profiles` lists the running profiles (`profiles-read`)
+//! - `POST /v1/profiles/{id}/launch` with an optional `{"headless": true}`
+//!   and `POST /v1/profiles/{id}/stop` with an optional `{"graceMs": 5000}`
+//!   (`profiles-control`)
+//! - `POST /v1/proxies/check` with a proxy as the import and check commands
+//!   take it, plus an optional `timeoutMs` (`proxy-check`)
+//!
+//! Errors come back as `{"error": "..."}`. Tokens are only stored as their
+//! SHA-256, so one can be shown once and never again. Every request, allowed
+//! or not, goes to the [audit log](crate::audit) with the token that made
+//! it, and so does whatever it sets off.
+
+use std::net::Ipv4Addr;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use axum::extract::{Path, Request, State};
+use axum::http::{header, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use tauri::{AppHandle, Manager};
+use tokio::net::TcpListener;
+
+use crate::audit::{self, AuditAction, Initiator};
+use crate::config::{ConfigStore, ControlScope, ControlToken};
+use crate::profiles::registry::ProfileRegistry;
+use crate::profiles::ProfileProcess;
+use crate::proxy::check::{self, ProxyCheck, DEFAULT_CHECK_TIMEOUT};
+use crate::proxy::ProxyConfig;
+
+/// Tokens start with this, so they are easy to spot in scripts and logs.
+const TOKEN_PREFIX: &str = "nyx_";
+const MAX_TOKEN_NAME_LEN: usize = 100;
+
+/// A token as listed in the UI, without its hash.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ControlTokenInfo {
+    pub id: String,
+    pub name: String,
+    pub scopes: Vec<ControlScope>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ControlApiInfo {
+    pub enabled: bool,
+    pub running: bool,
+    /// `http://127.0.0.1:<port>` while running.
+    pub url: Option<String>,
+    pub tokens: Vec<ControlTokenInfo>,
+}
+
+/// A token just created, the only time it is shown.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreatedControlToken {
+    pub info: ControlTokenInfo,
+    pub token: String,
+}
+
+struct Running {
+    port: u16,
+    task: tauri::async_runtime::JoinHandle<()>,
+}
+
+/// Managed state with the listener while it runs.
+#[derive(Default)]
+pub struct ControlApi {
+    running: Mutex<Option<Running>>,
+}
+
+impl ControlApi {
+    fn url(&self) -> Option<String> {
+        self.running
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(|running| format!("http://{}:{}", Ipv4Addr::LOCALHOST, running.port))
+    }
+
+    fn stop(&self) {
+        if let Some(running) = self.running.lock().unwrap().take() {
+            running.task.abort();
+            log::info!("Stopped the control API on port {}", running.port);
+        }
+    }
+}
+
+struct ApiError(StatusCode, String);
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.0, Json(json!({ "error": self.1 }))).into_response()
+    }
+}
+
+fn hash(token: &str) -> String {
+    Sha256::digest(token.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+fn info(token: &ControlToken) -> ControlTokenInfo {
+    ControlTokenInfo {
+        id: token.id.clone(),
+        name: token.name.clone(),
+        scopes: token.scopes.clone(),
+        created_at: token.created_at,
+    }
+}
+
+/// The token the request carries, if it is a known one.
+fn caller(app: &AppHandle, request: &Request) -> Option<ControlToken> {
+    let presented = request
+        .headers()
+        .get(header::AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")?
+        .trim();
+    let presented = hash(presented);
+    app.state::<ConfigStore>()
+        .get()
+        .control_api
+        .tokens
+        .into_iter()
+        .find(|token| token.token_hash == presented)
+}
+
+/// Lets the request through if its token has `scope`, and audits it.
+async fn guard(
+    State((app, scope)): State<(AppHandle, ControlScope)>,
+    request: Request,
+    next: Next,
+) -> Response {
+    audit::scoped(Initiator::ControlApi, async move {
+        let token = caller(&app, &request);
+        let target = format!(
+            "{} {} by {}",
+            request.method(),
+            request.uri().path(),
+            token
+                .as_ref()
+                .map_or("an unknown token".to_string(), |token| format!(
+                    "token {} ({})",
+                    token.id, token.name
+                ))
+        );
+        let response = match &token {
+            None => ApiError(
+                StatusCode::UNAUTHORIZED,
+                "Missing or unknown token".to_string(),
+            )
+            .into_response(),
+            Some(token) if !token.scopes.contains(&scope) => ApiError(
+                StatusCode::FORBIDDEN,
+                format!("The token lacks the {:?} scope", scope),
+            )
+            .into_response(),
+            Some(_) => next.run(request).await,
+        };
+        let status = response.status();
+        let result = if status.is_client_error() || status.is_server_error() {
+            Err(format!("HTTP {}", status.as_u16()))
+        } else {
+            Ok(())
+        };
+        audit::record(&app, AuditAction::ApiRequest, target, &result);
+        response
+    })
+    .await
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct LaunchBody {
+    headless: Option<bool>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct StopBody {
+    grace_ms: Option<u64>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CheckBody {
+    #[serde(flatten)]
+    proxy: ProxyConfig,
+    timeout_ms: Option<u64>,
+}
+
+async fn list_profiles(State(app): State<AppHandle>) -> Json<Vec<ProfileProcess>> {
+    Json(app.state::<ProfileRegistry>().list())
+}
+
+async fn launch_profile(
+    State(app): State<AppHandle>,
+    Path(id): Path<String>,
+    body: Option<Json<LaunchBody>>,
+) -> Result<Json<ProfileProcess>, ApiError> {
+    crate::profiles::validate_id(&id).map_err(|e| ApiError(StatusCode::BAD_REQUEST, e))?;
+    if app.state::<ProfileRegistry>().contains(&id) {
+        return Err(ApiError(
+            StatusCode::CONFLICT,
+            format!("Profile {} is already running", id),
+        ));
+    }
+    let headless = body.and_then(|Json(body)| body.headless);
+    crate::profiles::launch_profile(app, id, headless)
+        .await
+        .map(Json)
+        .map_err(|e| ApiError(StatusCode::INTERNAL_SERVER_ERROR, e))
+}
+
+async fn stop_profile(
+    State(app): State<AppHandle>,
+    Path(id): Path<String>,
+    body: Option<Json<StopBody>>,
+) -> Result<StatusCode, ApiError> {
+    let grace_ms = body.and_then(|Json(body)| body.grace_ms);
+    match crate::profiles::stop_profile(app, id.clone(), grace_ms).await {
+        Ok(true) => Ok(StatusCode::NO_CONTENT),
+        Ok(false) => Err(ApiError(
+            StatusCode::NOT_FOUND,
+            format!("Profile {} is not running", id),
+        )),
+        Err(e) => Err(ApiError(StatusCode::INTERNAL_SERVER_ERROR, e)),
+    }
+}
+
+async fn check_proxy(Json(body): Json<CheckBody>) -> Json<ProxyCheck> {
+    let timeout = body
+        .timeout_ms
+        .map(Duration::from_millis)
+        .unwrap_or(DEFAULT_CHECK_TIMEOUT);
+    Json(check::check(&body.proxy, timeout).await)
+}
+
+fn router(app: &AppHandle) -> Router {
+    let scoped = |scope: ControlScope, routes: Router<AppHandle>| {
+        routes.route_layer(middleware::from_fn_with_state((app.clone(), scope), guard))
+    };
+    Router::new()
+        .merge(scoped(
+            ControlScope::ProfilesRead,
+            Router::new().route("/v1/profiles", get(list_profiles)),
+        ))
+        .merge(scoped(
+            ControlScope::ProfilesControl,
+            Router::new()
+                .route("/v1/profiles/:id/launch", post(launch_profile))
+                .route("/v1/profiles/:id/stop", post(stop_profile)),
+        ))
+        .merge(scoped(
+            ControlScope::ProxyCheck,
+            Router::new().route("/v1/proxies/check", post(check_proxy)),
+        ))
+        .with_state(app.clone())
+}
+
+/// Starts or stops the API to match the config.
+pub async fn apply(app: &AppHandle) -> Result<(), String> {
+    let config = app.state::<ConfigStore>().get().control_api;
+    let api = app.state::<ControlApi>();
+    let current = api.running.lock().unwrap().as_ref().map(|r| r.port);
+    match (config.enabled, current) {
+        (false, None) => return Ok(()),
+        (false, Some(_)) => {
+            api.stop();
+            return Ok(());
+        }
+        (true, Some(port)) if config.port.map_or(true, |wanted| wanted == port) => return Ok(()),
+        (true, _) => api.stop(),
+    }
+
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, config.port.unwrap_or(0)))
+        .await
+        .map_err(|e| format!("Failed to start the control API: {}", e))?;
+    let port = listener
+        .local_addr()
+        .map_err(|e| format!("Failed to start the control API: {}", e))?
+        .port();
+    let router = router(app);
+    let task = tauri::async_runtime::spawn(async move {
+        if let Err(e) = axum::serve(listener, router).await {
+            log::warn!("The control API stopped: {}", e);
+        }
+    });
+    *api.running.lock().unwrap() = Some(Running { port, task });
+    log::info!("Control API listening on http://127.0.0.1:{}", port);
+    Ok(())
+}
+
+/// Starts the API in the background if it is enabled.
+pub fn spawn(app: &AppHandle) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        if let Err(e) = apply(&app).await {
+            log::warn!("{}", e);
+        }
+    });
+}
+
+#[tauri::command]
+pub fn get_control_api(app_handle: AppHandle) -> ControlApiInfo {
+    let config = app_handle.state::<ConfigStore>().get().control_api;
+    let url = app_handle.state::<ControlApi>().url();
+    ControlApiInfo {
+        enabled: config.enabled,
+        running: url.is_some(),
+        url,
+        tokens: config.tokens.iter().map(info).collect(),
+    }
+}
+
+/// Turns the API on or off and picks its port, applied right away.
+#[tauri::command]
+pub async fn set_control_api(
+    app_handle: AppHandle,
+    enabled: bool,
+    port: Option<u16>,
+) -> Result<ControlApiInfo, String> {
+    if port == Some(0) {
+        return Err("Control API port must be between 1 and 65535".to_string());
+    }
+    app_handle.state::<ConfigStore>().update(|c| {
+        c.control_api.enabled = enabled;
+        c.control_api.port = port;
+    })?;
+    apply(&app_handle).await?;
+    Ok(get_control_api(app_handle))
+}
+
+/// `len` random bytes as hex.
+fn random_hex(len: usize) -> String {
+    let mut bytes = vec![0u8; len];
+    rand::rngs::OsRng.fill_bytes(&mut bytes);
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Creates a token with `scopes`. The token is returned this once only.
+#[tauri::command]
+pub fn create_control_token(
+    app_handle: AppHandle,
+    name: String,
+    scopes: Vec<ControlScope>,
+) -> Result<CreatedControlToken, String> {
+    let name = name.trim().to_string();
+    if name.is_empty() || name.len() > MAX_TOKEN_NAME_LEN {
+        return Err(format!(
+            "Token names must be 1 to {} characters",
+            MAX_TOKEN_NAME_LEN
+        ));
+    }
+    if scopes.is_empty() {
+        return Err("Give the token at least one scope".to_string());
+    }
+    let token = format!("{}{}", TOKEN_PREFIX, random_hex(32));
+    let mut unique = Vec::with_capacity(scopes.len());
+    for scope in scopes {
+        if !unique.contains(&scope) {
+            unique.push(scope);
+        }
+    }
+    let created = ControlToken {
+        // Not derived from the token, as it is shown and logged
+        id: random_hex(6),
+        name,
+        token_hash: hash(&token),
+        scopes: unique,
+        created_at: chrono::Utc::now(),
+    };
+    app_handle
+        .state::<ConfigStore>()
+        .update(|c| c.control_api.tokens.push(created.clone()))?;
+    log::info!("Created control API token {}", created.id);
+    Ok(CreatedControlToken {
+        info: info(&created),
+        token,
+    })
+}
+
+/// Revokes a token; requests with it are refused from now on. Returns
+/// whether it existed.
+#[tauri::command]
+pub fn revoke_control_token(app_handle: AppHandle, id: String) -> Result<bool, String> {
+    let before = app_handle
+        .state::<ConfigStore>()
+        .get()
+        .control_api
+        .tokens
+        .len();
+    let after = app_handle
+        .state::<ConfigStore>()
+        .update(|c| c.control_api.tokens.retain(|token| token.id != id))?
+        .control_api
+        .tokens
+        .len();
+    Ok(after < before)
+}
@@ -0,0 +1,572 @@
+//! Cookies moved between tools, read from and written to the formats they
+//! export: Netscape `cookies.txt`, EditThisCookie JSON and Playwright
+//! storage state.
+//!
+//! Every format is read into the same [`Cookie`], which can then be written
+//! out in any of them. Cookies without a name or a valid domain are
+//! reported as invalid, and expired ones are dropped, so what comes out is
+//! what a browser would still send.
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+
+const NETSCAPE_HEADER: &str = "# Netscape HTTP Cookie File";
+const HTTP_ONLY_PREFIX: &str = "#HttpOnly_";
+const MAX_FILE_BYTES: u64 = 50 * 1024 * 1024;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CookieFormat {
+    Netscape,
+    EditThisCookie,
+    Playwright,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SameSite {
+    Strict,
+    Lax,
+    None,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Cookie {
+    pub name: String,
+    pub value: String,
+    /// Without a leading dot; see [`Cookie::host_only`].
+    pub domain: String,
+    pub path: String,
+    /// Unix seconds, or `None` for a session cookie.
+    pub expires: Option<f64>,
+    pub secure: bool,
+    pub http_only: bool,
+    pub same_site: Option<SameSite>,
+    /// Sent to `domain` only, not to its subdomains.
+    pub host_only: bool,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CookieImport {
+    pub format: CookieFormat,
+    pub cookies: Vec<Cookie>,
+    /// Cookies dropped because they have expired.
+    pub expired: usize,
+    /// Why the cookies that could not be read were refused.
+    pub invalid: Vec<String>,
+}
+
+impl CookieImport {
+    /// Every domain the cookies are for, sorted.
+    pub fn domains(&self) -> Vec<String> {
+        let mut domains: Vec<String> = self
+            .cookies
+            .iter()
+            .map(|cookie| cookie.domain.clone())
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect();
+        domains.sort();
+        domains
+    }
+}
+
+fn now() -> f64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0.0, |since| since.as_secs_f64())
+}
+
+fn valid_domain(domain: &str) -> bool {
+    !domain.is_empty()
+        && domain.len() <= 253
+        && domain
+            .split('.')
+            .all(|label| !label.is_empty() && label.len() <= 63)
+        && domain
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '.' | '_' | ':' | '[' | ']'))
+}
+
+impl Cookie {
+    /// Normalizes the domain and path and checks what a browser would
+    /// refuse.
+    pub(crate) fn validate(mut self) -> Result<Self, String> {
+        if self.name.trim().is_empty() {
+            return Err(format!("Cookie for {:?} without a name", self.domain));
+        }
+        if let Some(domain) = self.domain.strip_prefix('.') {
+            self.domain = domain.to_string();
+            self.host_only = false;
+        }
+        self.domain = self.domain.to_ascii_lowercase();
+        if !valid_domain(&self.domain) {
+            return Err(format!(
+                "Cookie {} has an invalid domain {:?}",
+                self.name, self.domain
+            ));
+        }
+        if !self.path.starts_with('/') {
+            self.path = "/".to_string();
+        }
+        if self.same_site == Some(SameSite::None) && !self.secure {
+            return Err(format!(
+                "Cookie {} of {} is SameSite=None without being secure",
+                self.name, self.domain
+            ));
+        }
+        Ok(self)
+    }
+
+    fn expired(&self, now: f64) -> bool {
+        self.expires.is_some_and(|expires| expires <= now)
+    }
+
+    /// The domain as Netscape and EditThisCookie write it, with a leading
+    /// dot when subdomains are included.
+    fn cookie_domain(&self) -> String {
+        if self.host_only {
+            self.domain.clone()
+        } else {
+            format!(".{}", self.domain)
+        }
+    }
+}
+
+/// Which format `text` is in, judged by its shape.
+pub fn detect(text: &str) -> Option<CookieFormat> {
+    let text = text.trim_start();
+    if text.starts_with('[') {
+        return Some(CookieFormat::EditThisCookie);
+    }
+    if text.starts_with('{') {
+        return Some(CookieFormat::Playwright);
+    }
+    let netscape = text.starts_with(NETSCAPE_HEADER)
+        || text
+            .lines()
+            .map(|line| line.strip_prefix(HTTP_ONLY_PREFIX).unwrap_or(line))
+            .any(|line| !line.starts_with('#') && line.split('\t').count() == 7);
+    netscape.then_some(CookieFormat::Netscape)
+}
+
+fn netscape_bool(field: &str) -> bool {
+    field.eq_ignore_ascii_case("TRUE")
+}
+
+fn parse_netscape(text: &str) -> Vec<Result<Cookie, String>> {
+    text.lines()
+        .enumerate()
+        .filter_map(|(index, line)| {
+            let (line, http_only) = match line.strip_prefix(HTTP_ONLY_PREFIX) {
+                Some(line) => (line, true),
+                None => (line, false),
+            };
+            let line = line.trim_end_matches('\r');
+            if line.trim().is_empty() || line.starts_with('#') {
+                return None;
+            }
+            let fields: Vec<&str> = line.split('\t').collect();
+            let [domain, subdomains, path, secure, expires, name, value] = fields[..] else {
+                return Some(Err(format!(
+                    "Line {}: expected 7 tab-separated fields",
+                    index + 1
+                )));
+            };
+            let expires = match expires.trim().parse::<f64>() {
+                Ok(expires) => (expires > 0.0).then_some(expires),
+                Err(_) => {
+                    return Some(Err(format!(
+                        "Line {}: invalid expiry {:?}",
+                        index + 1,
+                        expires
+                    )))
+                }
+            };
+            Some(Ok(Cookie {
+                name: name.to_string(),
+                value: value.to_string(),
+                host_only: !netscape_bool(subdomains),
+                domain: domain.to_string(),
+                path: path.to_string(),
+                expires,
+                secure: netscape_bool(secure),
+                http_only,
+                same_site: None,
+            }))
+        })
+        .collect()
+}
+
+fn string_field(cookie: &Map<String, Value>, key: &str) -> Option<String> {
+    cookie.get(key).and_then(Value::as_str).map(str::to_string)
+}
+
+fn bool_field(cookie: &Map<String, Value>, key: &str) -> bool {
+    cookie.get(key).and_then(Value::as_bool).unwrap_or(false)
+}
+
+fn same_site_of(value: Option<&str>) -> Option<SameSite> {
+    match value?.to_ascii_lowercase().as_str() {
+        "strict" => Some(SameSite::Strict),
+        "lax" => Some(SameSite::Lax),
+        "none" | "no_restriction" => Some(SameSite::None),
+        _ => None,
+    }
+}
+
+/// One cookie of either JSON format; they differ only in a few fields.
+fn json_cookie(index: usize, value: &Value, format: CookieFormat) -> Result<Cookie, String> {
+    let cookie = value
+        .as_object()
+        .ok_or_else(|| format!("Cookie {}: not an object", index + 1))?;
+    let name = string_field(cookie, "name")
+        .ok_or_else(|| format!("Cookie {}: missing name", index + 1))?;
+    let domain = string_field(cookie, "domain")
+        .ok_or_else(|| format!("Cookie {}: missing domain", index + 1))?;
+    let (expires, host_only) = match format {
+        CookieFormat::Playwright => (
+            // -1 marks a session cookie
+            cookie
+                .get("expires")
+                .and_then(Value::as_f64)
+                .filter(|expires| *expires > 0.0),
+            !domain.starts_with('.'),
+        ),
+        _ => (
+            cookie
+                .get("expirationDate")
+                .and_then(Value::as_f64)
+                .filter(|_| !bool_field(cookie, "session")),
+            cookie
+                .get("hostOnly")
+                .and_then(Value::as_bool)
+                .unwrap_or(!domain.starts_with('.')),
+        ),
+    };
+    Ok(Cookie {
+        value: string_field(cookie, "value").unwrap_or_default(),
+        path: string_field(cookie, "path").unwrap_or_else(|| "/".to_string()),
+        secure: bool_field(cookie, "secure"),
+        http_only: bool_field(cookie, "httpOnly"),
+        same_site: same_site_of(cookie.get("sameSite").and_then(Value::as_str)),
+        name,
+        domain,
+        expires,
+        host_only,
+    })
+}
+
+fn parse_json(text: &str, format: CookieFormat) -> Result<Vec<Result<Cookie, String>>, String> {
+    let value: Value = serde_json::from_str(text).map_err(|e| format!("Invalid JSON: {}", e))?;
+    let cookies = match (&value, format) {
+        (Value::Array(cookies), CookieFormat::EditThisCookie) => cookies,
+        (Value::Object(state), CookieFormat::Playwright) => state
+            .get("cookies")
+            .and_then(Value::as_array)
+            .ok_or("A storage state needs a cookies array")?,
+        (_, CookieFormat::EditThisCookie) => return Err("Expected a JSON array of cookies".into()),
+        _ => return Err("Expected a JSON storage state object".into()),
+    };
+    Ok(cookies
+        .iter()
+        .enumerate()
+        .map(|(index, cookie)| json_cookie(index, cookie, format))
+        .collect())
+}
+
+/// Reads `text` as `format`, or as the format it looks like.
+pub fn parse(text: &str, format: Option<CookieFormat>) -> Result<CookieImport, String> {
+    let format = format
+        .or_else(|| detect(text))
+        .ok_or("Not a cookies.txt, EditThisCookie or Playwright file")?;
+    let parsed = match format {
+        CookieFormat::Netscape => parse_netscape(text),
+        json => parse_json(text, json)?,
+    };
+
+    let now = now();
+    let mut import = CookieImport {
+        format,
+        cookies: Vec::new(),
+        expired: 0,
+        invalid: Vec::new(),
+    };
+    for cookie in parsed {
+        match cookie.and_then(Cookie::validate) {
+            Ok(cookie) if cookie.expired(now) => import.expired += 1,
+            Ok(cookie) => import.cookies.push(cookie),
+            Err(e) => import.invalid.push(e),
+        }
+    }
+    if import.cookies.is_empty() && import.expired == 0 && import.invalid.is_empty() {
+        return Err("The file holds no cookies".to_string());
+    }
+    Ok(import)
+}
+
+fn netscape_flag(value: bool) -> &'static str {
+    if value {
+        "TRUE"
+    } else {
+        "FALSE"
+    }
+}
+
+fn write_netscape(cookies: &[Cookie]) -> String {
+    let mut text = format!("{}\n# Exported by Nyx\n\n", NETSCAPE_HEADER);
+    for cookie in cookies {
+        text.push_str(&format!(
+            "{}{}\t{}\t{}\t{}\t{}\t{}\t{}\n",
+            if cookie.http_only {
+                HTTP_ONLY_PREFIX
+            } else {
+                ""
+            },
+            cookie.cookie_domain(),
+            netscape_flag(!cookie.host_only),
+            cookie.path,
+            netscape_flag(cookie.secure),
+            cookie.expires.map_or(0, |expires| expires as i64),
+            cookie.name,
+            cookie.value
+        ));
+    }
+    text
+}
+
+fn write_edit_this_cookie(cookies: &[Cookie]) -> Value {
+    let cookies = cookies.iter().enumerate().map(|(index, cookie)| {
+        let mut value = json!({
+            "domain": cookie.cookie_domain(),
+            "hostOnly": cookie.host_only,
+            "httpOnly": cookie.http_only,
+            "name": cookie.name,
+            "path": cookie.path,
+            "sameSite": match cookie.same_site {
+                Some(SameSite::Strict) => "strict",
+                Some(SameSite::Lax) => "lax",
+                Some(SameSite::None) => "no_restriction",
+                None => "unspecified",
+            },
+            "secure": cookie.secure,
+            "session": cookie.expires.is_none(),
+            "storeId": "0",
+            "value": cookie.value,
+            "id": index + 1,
+        });
+        if let Some(expires) = cookie.expires {
+            value["expirationDate"] = json!(expires);
+        }
+        value
+    });
+    Value::Array(cookies.collect())
+}
+
+fn write_playwright(cookies: &[Cookie]) -> Value {
+    let cookies: Vec<Value> = cookies
+        .iter()
+        .map(|cookie| {
+            json!({
+                "name": cookie.name,
+                "value": cookie.value,
+                "domain": cookie.cookie_domain(),
+                "path": cookie.path,
+                "expires": cookie.expires.unwrap_or(-1.0),
+                "httpOnly": cookie.http_only,
+                "secure": cookie.secure,
+                // Playwright has no "unspecified"; browsers treat it as Lax
+                "sameSite": match cookie.same_site {
+                    Some(SameSite::Strict) => "Strict",
+                    Some(SameSite::None) => "None",
+                    Some(SameSite::Lax) | None => "Lax",
+                },
+            })
+        })
+        .collect();
+    json!({ "cookies": cookies, "origins": [] })
+}
+
+/// `cookies` written in `format`.
+pub fn write(cookies: &[Cookie], format: CookieFormat) -> Result<String, String> {
+    let json = match format {
+        CookieFormat::Netscape => return Ok(write_netscape(cookies)),
+        CookieFormat::EditThisCookie => write_edit_this_cookie(cookies),
+        CookieFormat::Playwright => write_playwright(cookies),
+    };
+    serde_json::to_string_pretty(&json).map_err(|e| format!("Failed to encode cookies: {}", e))
+}
+
+/// Reads cookies from `path`, as `format` or whichever it is in.
+pub fn read_file(path: &Path, format: Option<CookieFormat>) -> Result<CookieImport, String> {
+    let metadata =
+        std::fs::metadata(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    if metadata.len() > MAX_FILE_BYTES {
+        return Err(format!(
+            "Cookie files over {} MB cannot be imported",
+            MAX_FILE_BYTES / 1024 / 1024
+        ));
+    }
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    parse(&text, format)
+}
+
+/// Reads cookies from text, as `format` or whichever it is in.
+#[tauri::command]
+pub fn parse_cookies(text: String, format: Option<CookieFormat>) -> Result<CookieImport, String> {
+    parse(&text, format)
+}
+
+/// Reads cookies from the file at `path`.
+#[tauri::command]
+pub async fn import_cookies(
+    path: PathBuf,
+    format: Option<CookieFormat>,
+) -> Result<CookieImport, String> {
+    tauri::async_runtime::spawn_blocking(move || read_file(&path, format))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+/// `cookies` written in `format`, after the same checks as on import.
+#[tauri::command]
+pub fn convert_cookies(cookies: Vec<Cookie>, format: CookieFormat) -> Result<String, String> {
+    let cookies = cookies
+        .into_iter()
+        .map(Cookie::validate)
+        .collect::<Result<Vec<_>, _>>()?;
+    write(&cookies, format)
+}
+
+/// Writes `cookies` to `path` in `format`.
+#[tauri::command]
+pub async fn export_cookies(
+    cookies: Vec<Cookie>,
+    format: CookieFormat,
+    path: PathBuf,
+) -> Result<(), String> {
+    let text = convert_cookies(cookies, format)?;
+    tauri::async_runtime::spawn_blocking(move || {
+        std::fs::write(&path, text)
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NETSCAPE: &str = "# Netscape HTTP Cookie File\n\
+        .example.com\tTRUE\t/\tTRUE\t4102444800\tsid\tabc\n\
+        #HttpOnly_shop.example.com\tFALSE\t/cart\tFALSE\t0\tcart\t1\n\
+        example.com\tFALSE\t/\tFALSE\t1000\told\tgone\n";
+
+    #[test]
+    fn formats_are_detected_by_shape() {
+        assert_eq!(detect(NETSCAPE), Some(CookieFormat::Netscape));
+        assert_eq!(
+            detect("example.com\tFALSE\t/\tFALSE\t0\tname\tvalue"),
+            Some(CookieFormat::Netscape)
+        );
+        assert_eq!(
+            detect("  [{\"name\": \"sid\"}]"),
+            Some(CookieFormat::EditThisCookie)
+        );
+        assert_eq!(
+            detect("{\"cookies\": [], \"origins\": []}"),
+            Some(CookieFormat::Playwright)
+        );
+        assert_eq!(detect("name=value; Path=/"), None);
+    }
+
+    #[test]
+    fn netscape_files_are_read() {
+        let import = parse(NETSCAPE, None).unwrap();
+        assert_eq!(import.format, CookieFormat::Netscape);
+        assert_eq!(import.expired, 1);
+        assert!(import.invalid.is_empty());
+        assert_eq!(
+            import.cookies,
+            [
+                Cookie {
+                    name: "sid".to_string(),
+                    value: "abc".to_string(),
+                    domain: "example.com".to_string(),
+                    path: "/".to_string(),
+                    expires: Some(4102444800.0),
+                    secure: true,
+                    http_only: false,
+                    same_site: None,
+                    host_only: false,
+                },
+                Cookie {
+                    name: "cart".to_string(),
+                    value: "1".to_string(),
+                    domain: "shop.example.com".to_string(),
+                    path: "/cart".to_string(),
+                    expires: None,
+                    secure: false,
+                    http_only: true,
+                    same_site: None,
+                    host_only: true,
+                },
+            ]
+        );
+        assert_eq!(import.domains(), ["example.com", "shop.example.com"]);
+    }
+
+    #[test]
+    fn invalid_cookies_are_reported() {
+        let text = r#"[
+            {"name": "ok", "value": "1", "domain": ".example.com", "sameSite": "lax"},
+            {"name": "", "domain": "example.com"},
+            {"name": "bad", "domain": "exa mple.com"},
+            {"name": "lax", "domain": "example.com", "sameSite": "no_restriction"},
+            {"domain": "example.com"}
+        ]"#;
+        let import = parse(text, None).unwrap();
+        assert_eq!(import.cookies.len(), 1);
+        assert_eq!(import.cookies[0].same_site, Some(SameSite::Lax));
+        assert!(!import.cookies[0].host_only);
+        assert_eq!(import.invalid.len(), 4);
+
+        assert!(parse("[]", None).is_err());
+        assert!(parse("{\"origins\": []}", Some(CookieFormat::Playwright)).is_err());
+        assert!(parse("[]", Some(CookieFormat::Playwright)).is_err());
+    }
+
+    #[test]
+    fn every_format_reads_back_what_it_wrote() {
+        let cookies = parse(NETSCAPE, None).unwrap().cookies;
+        let mut strict = cookies.clone();
+        strict[0].same_site = Some(SameSite::Strict);
+        for format in [
+            CookieFormat::Netscape,
+            CookieFormat::EditThisCookie,
+            CookieFormat::Playwright,
+        ] {
+            let written = write(&strict, format).unwrap();
+            let import = parse(&written, None).unwrap();
+            assert_eq!(import.format, format);
+            let mut expected = strict.clone();
+            match format {
+                // cookies.txt has no SameSite
+                CookieFormat::Netscape => expected[0].same_site = None,
+                // Playwright writes a missing SameSite as Lax
+                CookieFormat::Playwright => expected[1].same_site = Some(SameSite::Lax),
+                CookieFormat::EditThisCookie => {}
+            }
+            assert_eq!(import.cookies, expected, "{:?}", format);
+        }
+    }
+}
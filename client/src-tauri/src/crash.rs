@@ -0,0 +1,632 @@
+//! Crash reports for the shell itself, and recovery after it died.
+//!
+//! A panic hook writes `crash-<stamp>.json` to `crashes/` in app data with
+//! the panic message, where it happened, a backtrace and the tail of the
+//! log. A panic caught by the runtime still gets a report; only a process
+//! that never got to exit cleanly counts as a crash. Native crashes are
+//! caught as well: on Unix a fatal signal notes which one it was, and on
+//! Windows an unhandled exception writes a minidump.
+//!
+//! While the app runs, `session.json` in app data says so and lists the
+//! profiles that are open. It is removed on a clean exit, so finding it at
+//! startup means the last session crashed. The UI then gets a
+//! [`CrashRecovery`] from [`get_crash_recovery`] and, through
+//! [`resolve_crash_recovery`], can send the report along with a diagnostic
+//! bundle and reopen the profiles.
+
+use std::backtrace::Backtrace;
+use std::collections::BTreeSet;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Listener, Manager};
+
+const CRASHES_DIR: &str = "crashes";
+const SESSION_FILE: &str = "session.json";
+/// Reports kept, oldest deleted first.
+const MAX_REPORTS: usize = 20;
+/// Log lines included with a report.
+const LOG_TAIL_LINES: usize = 200;
+const LOG_TAIL_BYTES: u64 = 64 * 1024;
+/// Where the native crash handler writes. Opened at startup, because
+/// nothing can safely be opened once the process is crashing.
+#[cfg(unix)]
+const NATIVE_FILE: &str = "native.txt";
+#[cfg(windows)]
+const NATIVE_FILE: &str = "native.dmp";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CrashKind {
+    Panic,
+    /// Killed by a fatal signal, such as a segfault.
+    Signal,
+    /// An unhandled Windows exception.
+    Exception,
+    /// The session ended without a clean exit and left nothing behind, as
+    /// after a power loss or a force quit.
+    Unknown,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CrashReport {
+    pub kind: CrashKind,
+    pub occurred_at: DateTime<Utc>,
+    pub version: String,
+    pub os: String,
+    pub message: String,
+    /// The source location of a panic.
+    pub location: Option<String>,
+    pub thread: Option<String>,
+    pub backtrace: Option<String>,
+    pub log_tail: Vec<String>,
+    /// The minidump written for a native crash.
+    pub minidump: Option<PathBuf>,
+}
+
+/// What the session that crashed left behind.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CrashRecovery {
+    pub session_started_at: DateTime<Utc>,
+    pub report: Option<CrashReport>,
+    pub report_path: Option<PathBuf>,
+    /// Profiles that were open, which restoring relaunches.
+    pub profiles: Vec<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Session {
+    started_at: DateTime<Utc>,
+    version: String,
+    profiles: BTreeSet<String>,
+}
+
+/// What the panic hook needs, fixed at startup so it has nothing to look up.
+struct Paths {
+    crashes: PathBuf,
+    logs: Option<PathBuf>,
+    version: String,
+}
+
+static PATHS: OnceLock<Paths> = OnceLock::new();
+
+/// Managed state holding the current session and any recovery offered.
+pub struct CrashState {
+    session_path: Option<PathBuf>,
+    session: Mutex<Session>,
+    recovery: Mutex<Option<CrashRecovery>>,
+}
+
+impl CrashState {
+    fn save_session(&self) {
+        let Some(path) = &self.session_path else {
+            return;
+        };
+        let result = serde_json::to_vec_pretty(&*self.session.lock().unwrap())
+            .map_err(|e| e.to_string())
+            .and_then(|json| {
+                let partial = path.with_extension("json.part");
+                std::fs::write(&partial, json)
+                    .and_then(|()| std::fs::rename(&partial, path))
+                    .map_err(|e| e.to_string())
+            });
+        if let Err(e) = result {
+            log::warn!("Failed to save {}: {}", path.display(), e);
+        }
+    }
+
+    /// The crash still waiting for an answer, if any.
+    pub fn recovery(&self) -> Option<CrashRecovery> {
+        self.recovery.lock().unwrap().clone()
+    }
+
+    fn track(&self, profile_id: String, open: bool) {
+        let changed = {
+            let mut session = self.session.lock().unwrap();
+            if open {
+                session.profiles.insert(profile_id)
+            } else {
+                session.profiles.remove(&profile_id)
+            }
+        };
+        if changed {
+            self.save_session();
+        }
+    }
+}
+
+fn os() -> String {
+    format!("{} {}", std::env::consts::OS, std::env::consts::ARCH)
+}
+
+fn stamp(at: DateTime<Utc>) -> String {
+    at.with_timezone(&chrono::Local)
+        .format("%Y-%m-%d_%H-%M-%S")
+        .to_string()
+}
+
+/// The last lines of the newest log file.
+fn log_tail(logs: Option<&Path>) -> Vec<String> {
+    let Some(newest) = logs.and_then(|dir| crate::diagnostics::recent_logs(dir).into_iter().next())
+    else {
+        return Vec::new();
+    };
+    let Ok(contents) = crate::diagnostics::read_tail(&newest, LOG_TAIL_BYTES) else {
+        return Vec::new();
+    };
+    let text = String::from_utf8_lossy(&contents);
+    let lines: Vec<&str> = text.lines().collect();
+    let skip = lines.len().saturating_sub(LOG_TAIL_LINES);
+    lines[skip..].iter().map(|line| line.to_string()).collect()
+}
+
+fn write_report(dir: &Path, report: &CrashReport) -> std::io::Result<PathBuf> {
+    std::fs::create_dir_all(dir)?;
+    let path = dir.join(format!("crash-{}.json", stamp(report.occurred_at)));
+    let json = serde_json::to_vec_pretty(report).map_err(std::io::Error::other)?;
+    std::fs::write(&path, json)?;
+    Ok(path)
+}
+
+/// Reports in `dir`, newest first.
+fn reports(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut reports: Vec<PathBuf> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with("crash-") && name.ends_with(".json"))
+        })
+        .collect();
+    // The stamp in the name sorts by time
+    reports.sort();
+    reports.reverse();
+    reports
+}
+
+fn read_report(path: &Path) -> Option<CrashReport> {
+    let contents = std::fs::read(path).ok()?;
+    serde_json::from_slice(&contents).ok()
+}
+
+/// Deletes all but the newest [`MAX_REPORTS`] reports and their minidumps.
+fn prune(dir: &Path) {
+    for report in reports(dir).into_iter().skip(MAX_REPORTS) {
+        let _ = std::fs::remove_file(report.with_extension("dmp"));
+        let _ = std::fs::remove_file(&report);
+    }
+}
+
+fn panic_message(payload: &(dyn std::any::Any + Send)) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|message| message.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "Box<dyn Any>".to_string())
+}
+
+fn install_panic_hook() {
+    let default = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        if let Some(paths) = PATHS.get() {
+            let report = CrashReport {
+                kind: CrashKind::Panic,
+                occurred_at: Utc::now(),
+                version: paths.version.clone(),
+                os: os(),
+                message: panic_message(info.payload()),
+                location: info.location().map(|location| location.to_string()),
+                thread: std::thread::current().name().map(str::to_string),
+                backtrace: Some(Backtrace::force_capture().to_string()),
+                log_tail: log_tail(paths.logs.as_deref()),
+                minidump: None,
+            };
+            if let Err(e) = write_report(&paths.crashes, &report) {
+                eprintln!("Failed to write crash report: {}", e);
+            }
+        }
+        default(info);
+    }));
+}
+
+#[cfg(unix)]
+mod native {
+    use std::fs::File;
+    use std::os::fd::IntoRawFd;
+    use std::sync::atomic::{AtomicI32, Ordering};
+    use std::sync::OnceLock;
+
+    const SIGNALS: &[libc::c_int] = &[
+        libc::SIGSEGV,
+        libc::SIGBUS,
+        libc::SIGILL,
+        libc::SIGFPE,
+        libc::SIGABRT,
+    ];
+
+    static FD: AtomicI32 = AtomicI32::new(-1);
+    /// The handlers ours replaced, such as the one behind Rust's stack
+    /// overflow message.
+    static PREVIOUS: OnceLock<Vec<(libc::c_int, libc::sigaction)>> = OnceLock::new();
+
+    pub fn name(signal: i32) -> &'static str {
+        match signal {
+            libc::SIGSEGV => "SIGSEGV",
+            libc::SIGBUS => "SIGBUS",
+            libc::SIGILL => "SIGILL",
+            libc::SIGFPE => "SIGFPE",
+            libc::SIGABRT => "SIGABRT",
+            _ => "unknown signal",
+        }
+    }
+
+    /// Only async-signal-safe calls from here on: no allocation, no locks.
+    extern "C" fn on_signal(signal: libc::c_int) {
+        let fd = FD.load(Ordering::Relaxed);
+        if fd >= 0 {
+            let mut line = *b"signal 000\n";
+            line[7] = b'0' + (signal / 100 % 10) as u8;
+            line[8] = b'0' + (signal / 10 % 10) as u8;
+            line[9] = b'0' + (signal % 10) as u8;
+            // SAFETY: write is async-signal-safe and `line` outlives the call
+            unsafe { libc::write(fd, line.as_ptr().cast(), line.len()) };
+        }
+        // Hand the signal to whoever had it before, once this returns
+        // SAFETY: sigaction and raise are async-signal-safe
+        unsafe {
+            match PREVIOUS
+                .get()
+                .and_then(|previous| previous.iter().find(|(sig, _)| *sig == signal))
+            {
+                Some((_, action)) => {
+                    libc::sigaction(signal, action, std::ptr::null_mut());
+                }
+                None => {
+                    libc::signal(signal, libc::SIG_DFL);
+                }
+            }
+            libc::raise(signal);
+        }
+    }
+
+    pub fn install(file: File) {
+        FD.store(file.into_raw_fd(), Ordering::Relaxed);
+        let mut previous = Vec::new();
+        for signal in SIGNALS {
+            // SAFETY: the actions are fully initialised before use, and the
+            // handler only makes async-signal-safe calls
+            unsafe {
+                let mut action: libc::sigaction = std::mem::zeroed();
+                action.sa_sigaction = on_signal as extern "C" fn(libc::c_int) as usize;
+                // On the alternate stack, so a stack overflow is caught too
+                action.sa_flags = libc::SA_ONSTACK;
+                libc::sigemptyset(&mut action.sa_mask);
+                let mut old: libc::sigaction = std::mem::zeroed();
+                if libc::sigaction(*signal, &action, &mut old) == 0 {
+                    previous.push((*signal, old));
+                }
+            }
+        }
+        let _ = PREVIOUS.set(previous);
+    }
+}
+
+#[cfg(windows)]
+mod native {
+    use std::ffi::c_void;
+    use std::fs::File;
+    use std::os::windows::io::IntoRawHandle;
+    use std::sync::atomic::{AtomicPtr, Ordering};
+    use std::sync::OnceLock;
+
+    use windows_sys::Win32::System::Diagnostics::Debug::{
+        MiniDumpNormal, MiniDumpWithThreadInfo, MiniDumpWriteDump, SetUnhandledExceptionFilter,
+        EXCEPTION_CONTINUE_SEARCH, EXCEPTION_POINTERS, LPTOP_LEVEL_EXCEPTION_FILTER,
+        MINIDUMP_EXCEPTION_INFORMATION,
+    };
+    use windows_sys::Win32::System::Threading::{
+        GetCurrentProcess, GetCurrentProcessId, GetCurrentThreadId,
+    };
+
+    static FILE: AtomicPtr<c_void> = AtomicPtr::new(std::ptr::null_mut());
+    static PREVIOUS: OnceLock<LPTOP_LEVEL_EXCEPTION_FILTER> = OnceLock::new();
+
+    unsafe extern "system" fn on_exception(info: *const EXCEPTION_POINTERS) -> i32 {
+        let file = FILE.swap(std::ptr::null_mut(), Ordering::Relaxed);
+        if !file.is_null() {
+            let exception = MINIDUMP_EXCEPTION_INFORMATION {
+                ThreadId: GetCurrentThreadId(),
+                ExceptionPointers: info as *mut EXCEPTION_POINTERS,
+                ClientPointers: 0,
+            };
+            MiniDumpWriteDump(
+                GetCurrentProcess(),
+                GetCurrentProcessId(),
+                file,
+                MiniDumpNormal | MiniDumpWithThreadInfo,
+                &exception,
+                std::ptr::null(),
+                std::ptr::null(),
+            );
+        }
+        match PREVIOUS.get().copied().flatten() {
+            Some(previous) => previous(info),
+            None => EXCEPTION_CONTINUE_SEARCH,
+        }
+    }
+
+    pub fn install(file: File) {
+        FILE.store(file.into_raw_handle(), Ordering::Relaxed);
+        // SAFETY: the filter only touches the pre-opened file
+        let previous = unsafe { SetUnhandledExceptionFilter(Some(on_exception)) };
+        let _ = PREVIOUS.set(previous);
+    }
+}
+
+/// Turns what the native handler left in `dir` into a report, and opens a
+/// fresh file for this session's handler.
+#[cfg(any(unix, windows))]
+fn collect_native(dir: &Path, logs: Option<&Path>, version: &str) -> Option<CrashReport> {
+    let path = dir.join(NATIVE_FILE);
+    let found = std::fs::metadata(&path)
+        .ok()
+        .filter(|metadata| metadata.len() > 0)
+        .map(|metadata| {
+            metadata
+                .modified()
+                .map(DateTime::<Utc>::from)
+                .unwrap_or_else(|_| Utc::now())
+        });
+    let report = found.and_then(|occurred_at| {
+        #[cfg(unix)]
+        let (kind, message, minidump) = {
+            let contents = std::fs::read_to_string(&path).ok()?;
+            let signal = contents
+                .lines()
+                .next()?
+                .trim_start_matches("signal ")
+                .parse::<i32>()
+                .ok()?;
+            let message = format!("Killed by signal {} ({})", signal, native::name(signal));
+            (CrashKind::Signal, message, None)
+        };
+        #[cfg(windows)]
+        let (kind, message, minidump) = {
+            let dump = dir.join(format!("crash-{}.dmp", stamp(occurred_at)));
+            std::fs::rename(&path, &dump).ok()?;
+            let message = "Unhandled exception; see the minidump".to_string();
+            (CrashKind::Exception, message, Some(dump))
+        };
+        let report = CrashReport {
+            kind,
+            occurred_at,
+            version: version.to_string(),
+            os: os(),
+            message,
+            location: None,
+            thread: None,
+            backtrace: None,
+            log_tail: log_tail(logs),
+            minidump,
+        };
+        if let Err(e) = write_report(dir, &report) {
+            log::warn!("Failed to write crash report: {}", e);
+        }
+        Some(report)
+    });
+
+    match std::fs::create_dir_all(dir).and_then(|()| File::create(&path)) {
+        Ok(file) => native::install(file),
+        Err(e) => log::warn!("Native crashes will go unreported: {}", e),
+    }
+    report
+}
+
+#[cfg(not(any(unix, windows)))]
+fn collect_native(_dir: &Path, _logs: Option<&Path>, _version: &str) -> Option<CrashReport> {
+    None
+}
+
+/// What to offer for a previous session that never exited cleanly.
+fn recover(
+    previous: Session,
+    dir: &Path,
+    logs: Option<&Path>,
+    native: Option<CrashReport>,
+) -> CrashRecovery {
+    let written = reports(dir).into_iter().find_map(|path| {
+        let report = read_report(&path)?;
+        (report.occurred_at >= previous.started_at).then_some((report, path))
+    });
+    let (report, report_path) = match (written, native) {
+        (Some((report, path)), _) => (report, Some(path)),
+        (None, Some(report)) => (report, None),
+        (None, None) => {
+            let report = CrashReport {
+                kind: CrashKind::Unknown,
+                occurred_at: previous.started_at,
+                version: previous.version.clone(),
+                os: os(),
+                message: "The app did not exit cleanly".to_string(),
+                location: None,
+                thread: None,
+                backtrace: None,
+                log_tail: log_tail(logs),
+                minidump: None,
+            };
+            (report, None)
+        }
+    };
+    CrashRecovery {
+        session_started_at: previous.started_at,
+        report: Some(report),
+        report_path,
+        profiles: previous.profiles.into_iter().collect(),
+    }
+}
+
+/// Installs the crash handlers and starts a new session, noting whether the
+/// last one crashed. Runs early in setup, right after logging.
+pub fn install(app: &AppHandle) -> CrashState {
+    let data_dir = app.path().app_data_dir().ok();
+    let crashes = data_dir
+        .as_deref()
+        .map(|dir| dir.join(CRASHES_DIR))
+        .unwrap_or_else(|| std::env::temp_dir().join("nyx-crashes"));
+    let logs = crate::log_files::logs_dir(app).ok();
+    let version = app.package_info().version.to_string();
+
+    let _ = PATHS.set(Paths {
+        crashes: crashes.clone(),
+        logs: logs.clone(),
+        version: version.clone(),
+    });
+    install_panic_hook();
+    let native = collect_native(&crashes, logs.as_deref(), &version);
+    prune(&crashes);
+
+    let session_path = data_dir.map(|dir| dir.join(SESSION_FILE));
+    let previous = session_path
+        .as_deref()
+        .and_then(|path| std::fs::read(path).ok())
+        .and_then(
+            |contents| match serde_json::from_slice::<Session>(&contents) {
+                Ok(session) => Some(session),
+                Err(e) => {
+                    log::warn!("Ignoring unreadable {}: {}", SESSION_FILE, e);
+                    None
+                }
+            },
+        );
+    let recovery = previous.map(|previous| {
+        let recovery = recover(previous, &crashes, logs.as_deref(), native);
+        log::warn!(
+            "The last session crashed: {}",
+            recovery
+                .report
+                .as_ref()
+                .map_or("no report", |report| report.message.as_str())
+        );
+        recovery
+    });
+
+    let state = CrashState {
+        session_path,
+        session: Mutex::new(Session {
+            started_at: Utc::now(),
+            version,
+            profiles: BTreeSet::new(),
+        }),
+        recovery: Mutex::new(recovery),
+    };
+    state.save_session();
+    state
+}
+
+#[derive(Deserialize)]
+struct ProfileEvent {
+    id: String,
+}
+
+fn on_profile_event(app: &AppHandle, event: &'static str, open: bool) {
+    let handle = app.clone();
+    app.listen(event, move |event| {
+        if let Ok(payload) = serde_json::from_str::<ProfileEvent>(event.payload()) {
+            handle.state::<CrashState>().track(payload.id, open);
+        }
+    });
+}
+
+/// Keeps the open profiles in the session file, for restoring after a crash.
+pub fn track_profiles(app: &AppHandle) {
+    on_profile_event(app, "profile://launched", true);
+    on_profile_event(app, "profile://stopped", false);
+    on_profile_event(app, "profile://exited", false);
+}
+
+/// Marks the session as ended cleanly.
+pub fn end_session(app: &AppHandle) {
+    if let Some(path) = app
+        .try_state::<CrashState>()
+        .and_then(|state| state.session_path.clone())
+    {
+        let _ = std::fs::remove_file(path);
+    }
+}
+
+/// The newest crash report and its minidump, for the diagnostic bundle.
+pub fn latest_report(app: &AppHandle) -> Option<(PathBuf, Option<PathBuf>)> {
+    let dir = app.path().app_data_dir().ok()?.join(CRASHES_DIR);
+    let path = reports(&dir).into_iter().next()?;
+    let minidump = read_report(&path).and_then(|report| report.minidump);
+    Some((path, minidump))
+}
+
+#[tauri::command]
+pub fn get_crash_recovery(state: tauri::State<'_, CrashState>) -> Option<CrashRecovery> {
+    state.recovery()
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CrashResolution {
+    /// The diagnostic bundle holding the report, if one was asked for.
+    pub bundle: Option<String>,
+    pub restored: Vec<String>,
+    /// Profiles that failed to relaunch, with why.
+    pub failed: Vec<(String, String)>,
+}
+
+/// Answers the crash prompt: optionally writes a diagnostic bundle with the
+/// report for the user to send, and optionally reopens the profiles that
+/// were open. Either way the prompt is not offered again.
+#[tauri::command]
+pub async fn resolve_crash_recovery(
+    app_handle: AppHandle,
+    send_report: bool,
+    restore_session: bool,
+) -> Result<CrashResolution, String> {
+    let recovery = app_handle
+        .state::<CrashState>()
+        .recovery
+        .lock()
+        .unwrap()
+        .take();
+    let Some(recovery) = recovery else {
+        return Err("There is no crash to recover from".to_string());
+    };
+    let bundle = if send_report {
+        Some(crate::diagnostics::generate_diagnostic_bundle(app_handle.clone()).await?)
+    } else {
+        None
+    };
+    let mut resolution = CrashResolution {
+        bundle,
+        restored: Vec::new(),
+        failed: Vec::new(),
+    };
+    if restore_session {
+        for profile_id in recovery.profiles {
+            match crate::profiles::launch_profile(app_handle.clone(), profile_id.clone(), None)
+                .await
+            {
+                Ok(_) => resolution.restored.push(profile_id),
+                Err(e) => {
+                    log::warn!("Failed to restore profile {}: {}", profile_id, e);
+                    resolution.failed.push((profile_id, e));
+                }
+            }
+        }
+    }
+    Ok(resolution)
+}
@@ -0,0 +1,361 @@
+//! Where the bulky data lives: profiles, browser builds and downloads, which
+//! can run to tens of gigabytes. It is app data unless `dataDir` in the
+//! config points elsewhere, e.g. another drive.
+//!
+//! [`set_data_directory`] moves that data over with the server stopped,
+//! emitting progress as `data-dir://progress`. Each folder is renamed when
+//! it stays on the same volume and copied otherwise; any failure removes
+//! what reached the target and leaves the old location as it was. The old
+//! copies are only deleted once everything arrived. Everything else,
+//! settings and small state files, stays in app data.
+//!
+//! The server keeps its profile records in [`RECORDS`] there, apart from
+//! the browser profiles, once it is told the location in [`DATA_DIR_ENV`];
+//! see [`server_data_dir`]. Until then it keeps them where it always has.
+
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::audit::{self, AuditAction};
+use crate::config::ConfigStore;
+use crate::downloads::DownloadManager;
+use crate::profiles::registry::ProfileRegistry;
+use crate::server::supervisor::{ServerSupervisor, DEFAULT_SHUTDOWN_GRACE};
+
+/// Environment variable the server reads the data directory from.
+pub const DATA_DIR_ENV: &str = "NYX_DATA_DIR";
+/// The server's folder of profile records.
+pub const RECORDS: &str = "records";
+/// Where profile directories go instead of being deleted.
+pub const TRASH: &str = "trash";
+/// The folders that move.
+const MOVED: &[&str] = &["profiles", RECORDS, "browsers", "downloads", TRASH];
+/// Room left on the target beyond what is moved.
+const SPARE_SPACE: u64 = 512 * 1024 * 1024;
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MoveProgress {
+    pub copied_bytes: u64,
+    pub total_bytes: u64,
+    /// The file being copied.
+    pub current: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DataDirectory {
+    pub path: String,
+    /// Whether it is app data rather than a configured location.
+    pub is_default: bool,
+    pub size_bytes: u64,
+}
+
+/// The data directory in use.
+pub fn data_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    match app.state::<ConfigStore>().get().data_dir {
+        Some(dir) => Ok(dir),
+        None => default_dir(app),
+    }
+}
+
+/// The data directory to pass the server: the configured one, or app data
+/// once the server's records are there, after a move back to it. Without
+/// one the server keeps its records in its own storage, so upgrading moves
+/// nothing; it moves them over itself the first time it gets one.
+pub fn server_data_dir(app: &AppHandle) -> Option<PathBuf> {
+    match app.state::<ConfigStore>().get().data_dir {
+        Some(dir) => Some(dir),
+        None => default_dir(app)
+            .ok()
+            .filter(|dir| dir.join(RECORDS).is_dir()),
+    }
+}
+
+fn default_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    app.path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to resolve app data directory: {}", e))
+}
+
+fn moved_size(dir: &Path) -> u64 {
+    MOVED
+        .iter()
+        .map(|name| crate::browsers::dir_size(&dir.join(name)))
+        .sum()
+}
+
+/// Whether anything but an empty folder, as every launch creates, is at
+/// `path`.
+fn is_occupied(path: &Path) -> bool {
+    match std::fs::read_dir(path) {
+        Ok(mut entries) => entries.next().is_some(),
+        Err(_) => path.exists(),
+    }
+}
+
+/// Checks that `target` can take the data in `current`.
+fn validate(current: &Path, target: &Path) -> Result<(), String> {
+    if !target.is_absolute() {
+        return Err(format!("{} is not an absolute path", target.display()));
+    }
+    std::fs::create_dir_all(target)
+        .map_err(|e| format!("Failed to create {}: {}", target.display(), e))?;
+    let target = target
+        .canonicalize()
+        .map_err(|e| format!("Failed to resolve {}: {}", target.display(), e))?;
+    let current = current
+        .canonicalize()
+        .unwrap_or_else(|_| current.to_path_buf());
+    if target.starts_with(&current) || current.starts_with(&target) {
+        return Err(
+            "The new data directory cannot be inside the current one or contain it".to_string(),
+        );
+    }
+    if let Some(name) = MOVED.iter().find(|name| is_occupied(&target.join(name))) {
+        return Err(format!(
+            "{} already has a {} folder with files in it",
+            target.display(),
+            name
+        ));
+    }
+
+    let probe = target.join(".nyx-write-test");
+    std::fs::write(&probe, b"")
+        .and_then(|()| std::fs::remove_file(&probe))
+        .map_err(|e| format!("{} is not writable: {}", target.display(), e))?;
+
+    let needed = moved_size(&current);
+    if let Some((free, _)) = crate::system_info::disk_space(&target) {
+        if free < needed + SPARE_SPACE {
+            return Err(format!(
+                "Not enough space on {}: {} MB needed, {} MB free",
+                target.display(),
+                (needed + SPARE_SPACE) / (1024 * 1024),
+                free / (1024 * 1024)
+            ));
+        }
+    }
+    Ok(())
+}
+
+struct Copier<'a> {
+    app: &'a AppHandle,
+    progress: MoveProgress,
+    emitted: Instant,
+}
+
+impl Copier<'_> {
+    fn emit(&mut self, force: bool) {
+        if force || self.emitted.elapsed() >= crate::PROGRESS_INTERVAL {
+            self.emitted = Instant::now();
+            let _ = self.app.emit("data-dir://progress", self.progress.clone());
+        }
+    }
+
+    fn copy_dir(&mut self, from: &Path, to: &Path) -> std::io::Result<()> {
+        std::fs::create_dir_all(to)?;
+        for entry in std::fs::read_dir(from)? {
+            let entry = entry?;
+            let (source, dest) = (entry.path(), to.join(entry.file_name()));
+            let kind = entry.file_type()?;
+            if kind.is_dir() {
+                self.copy_dir(&source, &dest)?;
+            } else if kind.is_file() {
+                self.progress.current = Some(source.display().to_string());
+                self.emit(false);
+                self.progress.copied_bytes += std::fs::copy(&source, &dest)?;
+            } else if kind.is_symlink() {
+                // Kept as links; those inside profiles point into the profile
+                copy_link(&source, &dest)?;
+            } else {
+                // The source is deleted after the copy, so nothing may be left out
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::Unsupported,
+                    format!("{} is neither a file nor a folder", source.display()),
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Recreates the symlink `source` at `dest`, pointing where it points.
+fn copy_link(source: &Path, dest: &Path) -> std::io::Result<()> {
+    let target = std::fs::read_link(source)?;
+    #[cfg(unix)]
+    return std::os::unix::fs::symlink(&target, dest);
+    #[cfg(windows)]
+    {
+        let resolved = source.parent().unwrap_or(source).join(&target);
+        if resolved.is_dir() {
+            std::os::windows::fs::symlink_dir(&target, dest)
+        } else {
+            std::os::windows::fs::symlink_file(&target, dest)
+        }
+    }
+}
+
+/// Moves the data folders from `from` to `to`, undoing it all on failure.
+fn move_data(app: &AppHandle, from: &Path, to: &Path) -> Result<(), String> {
+    let mut copier = Copier {
+        app,
+        progress: MoveProgress {
+            copied_bytes: 0,
+            total_bytes: moved_size(from),
+            current: None,
+        },
+        emitted: Instant::now(),
+    };
+    copier.emit(true);
+
+    let mut renamed = Vec::new();
+    let mut copied = Vec::new();
+    let mut result = Ok(());
+    for name in MOVED {
+        let (source, dest) = (from.join(name), to.join(name));
+        if !source.exists() {
+            continue;
+        }
+        let _ = std::fs::remove_dir(&dest);
+        if std::fs::rename(&source, &dest).is_ok() {
+            copier.progress.copied_bytes += crate::browsers::dir_size(&dest);
+            copier.emit(true);
+            renamed.push(*name);
+            continue;
+        }
+        if let Err(e) = copier.copy_dir(&source, &dest) {
+            result = Err(format!(
+                "Failed to copy {} to {}: {}",
+                source.display(),
+                dest.display(),
+                e
+            ));
+            copied.push(*name);
+            break;
+        }
+        copied.push(*name);
+    }
+
+    if let Err(e) = result {
+        log::warn!("{}; rolling back", e);
+        for name in &copied {
+            let _ = std::fs::remove_dir_all(to.join(name));
+        }
+        for name in &renamed {
+            if let Err(e) = std::fs::rename(to.join(name), from.join(name)) {
+                log::error!("Failed to move {} back: {}", name, e);
+            }
+        }
+        return Err(e);
+    }
+
+    for name in &copied {
+        let source = from.join(name);
+        let removed = std::fs::remove_dir_all(&source);
+        audit::record(
+            app,
+            AuditAction::FileDelete,
+            source.display().to_string(),
+            &removed,
+        );
+        if let Err(e) = removed {
+            log::warn!(
+                "Failed to delete {} after moving it: {}",
+                source.display(),
+                e
+            );
+        }
+    }
+    copier.progress.current = None;
+    copier.emit(true);
+    Ok(())
+}
+
+async fn run_move(app: &AppHandle, from: &Path, to: &Path) -> Result<(), String> {
+    let (app, from, to) = (app.clone(), from.to_path_buf(), to.to_path_buf());
+    tauri::async_runtime::spawn_blocking(move || move_data(&app, &from, &to))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+pub fn get_data_directory(app_handle: AppHandle) -> Result<DataDirectory, String> {
+    let path = data_dir(&app_handle)?;
+    Ok(DataDirectory {
+        is_default: app_handle.state::<ConfigStore>().get().data_dir.is_none(),
+        size_bytes: moved_size(&path),
+        path: path.display().to_string(),
+    })
+}
+
+/// Moves profiles, the server's records of them, browser builds and
+/// downloads to `new_path` and keeps them there from now on. Nothing may be running from them meanwhile, so
+/// profiles and downloads have to be stopped first; the server is stopped
+/// for the move and started again after.
+#[tauri::command]
+pub async fn set_data_directory(
+    app_handle: AppHandle,
+    new_path: String,
+) -> Result<DataDirectory, String> {
+    if !app_handle.state::<ProfileRegistry>().list().is_empty() {
+        return Err("Stop all running profiles before moving the data directory".to_string());
+    }
+    let downloading = app_handle
+        .state::<DownloadManager>()
+        .list()
+        .iter()
+        .any(|download| !download.state.is_finished());
+    if downloading {
+        return Err("Finish or cancel all downloads before moving the data directory".to_string());
+    }
+
+    let current = data_dir(&app_handle)?;
+    let target = PathBuf::from(&new_path);
+    {
+        let (current, target) = (current.clone(), target.clone());
+        tauri::async_runtime::spawn_blocking(move || validate(&current, &target))
+            .await
+            .map_err(|e| e.to_string())??;
+    }
+
+    let supervisor = app_handle.state::<ServerSupervisor>();
+    let restart = supervisor.is_running();
+    if restart {
+        supervisor.stop(DEFAULT_SHUTDOWN_GRACE).await;
+    }
+
+    log::info!(
+        "Moving the data directory from {} to {}",
+        current.display(),
+        target.display()
+    );
+    let saved = match run_move(&app_handle, &current, &target).await {
+        Ok(()) => {
+            let is_default = default_dir(&app_handle).is_ok_and(|dir| dir == target);
+            let saved = app_handle
+                .state::<ConfigStore>()
+                .update(|c| c.data_dir = (!is_default).then(|| target.clone()));
+            if saved.is_err() {
+                // The config still points at the old place, so the data goes back
+                if let Err(e) = run_move(&app_handle, &target, &current).await {
+                    log::error!("Failed to move the data back: {}", e);
+                }
+            }
+            saved.map(|_| ())
+        }
+        Err(e) => Err(e),
+    };
+
+    if restart {
+        if let Err(e) = crate::launch_server(&app_handle).await {
+            log::warn!("Failed to restart the server after moving data: {}", e);
+        }
+    }
+    saved?;
+    let _ = app_handle.emit("data-dir://moved", new_path);
+    get_data_directory(app_handle)
+}
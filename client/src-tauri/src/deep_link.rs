@@ -0,0 +1,198 @@
+//! `nyx://` links, so browsers and other tools can drive the app:
+//!
+//! - `nyx://open` brings the app forward
+//! - `nyx://profile/launch/<id>`, optionally with `?headless=true`
+//! - `nyx://profile/stop/<id>`
+//! - `nyx://import-proxy?proxy=<proxy>`, with `proxy` repeated for several
+//!   and an optional `protocol` for entries without a scheme
+//!
+//! A link starts the app, or focuses the running instance, and is handled
+//! once the backend is up. Each outcome is emitted as `deep-link://handled`.
+//! Any web page can open a link, so links only ever act on what is in the
+//! URL itself and never read local files.
+
+use std::time::Duration;
+
+use reqwest::Url;
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager};
+use tauri_plugin_deep_link::DeepLinkExt;
+
+use crate::proxy::ProxyProtocol;
+use crate::server::status::{ServerState, ServerStatus};
+
+pub(crate) const SCHEME: &str = "nyx";
+/// How long a link waits for the backend before giving up.
+const READY_TIMEOUT: Duration = Duration::from_secs(60);
+/// Most proxies one link may import.
+const MAX_LINK_PROXIES: usize = 100;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Action {
+    Open,
+    LaunchProfile {
+        id: String,
+        headless: bool,
+    },
+    StopProfile {
+        id: String,
+    },
+    ImportProxies {
+        text: String,
+        protocol: Option<ProxyProtocol>,
+    },
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct HandledPayload {
+    url: String,
+    ok: bool,
+    error: Option<String>,
+}
+
+fn query(url: &Url, key: &str) -> Vec<String> {
+    url.query_pairs()
+        .filter(|(name, _)| name == key)
+        .map(|(_, value)| value.into_owned())
+        .collect()
+}
+
+pub fn parse(url: &Url) -> Result<Action, String> {
+    if url.scheme() != SCHEME {
+        return Err(format!("Not a {}:// link: {}", SCHEME, url));
+    }
+    let segments: Vec<&str> = url
+        .path_segments()
+        .map(|segments| segments.filter(|segment| !segment.is_empty()).collect())
+        .unwrap_or_default();
+    match (url.host_str().unwrap_or_default(), segments.as_slice()) {
+        ("" | "open", []) => Ok(Action::Open),
+        ("profile", ["launch", id]) => Ok(Action::LaunchProfile {
+            id: id.to_string(),
+            headless: query(url, "headless")
+                .first()
+                .is_some_and(|value| value == "true" || value == "1"),
+        }),
+        ("profile", ["stop", id]) => Ok(Action::StopProfile { id: id.to_string() }),
+        ("import-proxy", []) => {
+            let proxies = query(url, "proxy");
+            if proxies.is_empty() {
+                return Err("Link has no proxy to import".to_string());
+            }
+            if proxies.len() > MAX_LINK_PROXIES {
+                return Err(format!(
+                    "A link can import at most {} proxies",
+                    MAX_LINK_PROXIES
+                ));
+            }
+            let protocol = query(url, "protocol")
+                .first()
+                .map(|scheme| {
+                    ProxyProtocol::from_scheme(scheme)
+                        .ok_or_else(|| format!("Unknown proxy protocol: {}", scheme))
+                })
+                .transpose()?;
+            Ok(Action::ImportProxies {
+                text: proxies.join("\n"),
+                protocol,
+            })
+        }
+        (host, _) => Err(format!("Unknown link: {}://{}{}", SCHEME, host, url.path())),
+    }
+}
+
+/// Waits for the backend, which a link opened at launch usually beats.
+pub(crate) async fn wait_for_backend(app: &AppHandle) -> Result<(), String> {
+    let mut state = app.state::<ServerStatus>().subscribe();
+    let ready = async {
+        while !matches!(
+            *state.borrow_and_update(),
+            ServerState::Healthy | ServerState::Degraded
+        ) {
+            if state.changed().await.is_err() {
+                break;
+            }
+        }
+    };
+    tokio::time::timeout(READY_TIMEOUT, ready)
+        .await
+        .map_err(|_| "The backend did not come up in time".to_string())
+}
+
+async fn run(app: &AppHandle, action: Action) -> Result<(), String> {
+    match action {
+        Action::Open => Ok(()),
+        Action::LaunchProfile { id, headless } => {
+            wait_for_backend(app).await?;
+            crate::profiles::launch_profile(app.clone(), id, Some(headless))
+                .await
+                .map(|_| ())
+        }
+        Action::StopProfile { id } => crate::profiles::stop_profile(app.clone(), id, None)
+            .await
+            .map(|_| ()),
+        Action::ImportProxies { text, protocol } => {
+            wait_for_backend(app).await?;
+            crate::proxy::import::import_proxies(
+                app.clone(),
+                Some(text),
+                None,
+                protocol,
+                None,
+                None,
+            )
+            .await
+            .map(|_| ())
+        }
+    }
+}
+
+fn handle(app: &AppHandle, urls: Vec<Url>) {
+    crate::boot::focus(app);
+    for url in urls {
+        let app = app.clone();
+        tauri::async_runtime::spawn(async move {
+            // The query may carry proxy credentials, so it stays out of the log
+            log::info!(
+                "Opening link {}://{}{}",
+                SCHEME,
+                url.host_str().unwrap_or_default(),
+                url.path()
+            );
+            let result = match parse(&url) {
+                Ok(action) => run(&app, action).await,
+                Err(e) => Err(e),
+            };
+            if let Err(e) = &result {
+                log::warn!("Failed to handle link: {}", e);
+            }
+            let _ = app.emit(
+                "deep-link://handled",
+                HandledPayload {
+                    url: url.to_string(),
+                    ok: result.is_ok(),
+                    error: result.err(),
+                },
+            );
+        });
+    }
+}
+
+/// Handles links opened from now on, and the one the app was started with.
+pub fn init(app: &AppHandle) {
+    // Installers register the scheme; this covers portable builds and dev
+    #[cfg(any(windows, target_os = "linux"))]
+    if let Err(e) = app.deep_link().register_all() {
+        log::warn!("Failed to register the {}:// scheme: {}", SCHEME, e);
+    }
+
+    let handle_app = app.clone();
+    app.deep_link()
+        .on_open_url(move |event| handle(&handle_app, event.urls()));
+    match app.deep_link().get_current() {
+        Ok(Some(urls)) => handle(app, urls),
+        Ok(None) => {}
+        Err(e) => log::warn!("Failed to read the launch link: {}", e),
+    }
+}
@@ -0,0 +1,211 @@
+//! Packs everything a support request needs into one zip in Downloads:
+//! recent logs, the shell's configuration with secrets redacted, system
+//! details, the last server failure and the last crash of the shell.
+
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use serde_json::Value;
+use tauri::{AppHandle, Manager};
+use zip::write::SimpleFileOptions;
+use zip::ZipWriter;
+
+use crate::config::ConfigStore;
+use crate::server::logs::ServerLogs;
+use crate::server::port::PortManager;
+use crate::server::status::ServerStatus;
+use crate::settings::SettingsStore;
+
+/// Log files older than this are left out.
+const LOG_MAX_AGE: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+/// Only the tail of each log file is included.
+const LOG_MAX_BYTES: u64 = 2 * 1024 * 1024;
+/// Server output lines included with the last failure.
+const CRASH_LINES: usize = 300;
+/// Object keys whose values are never written to the bundle.
+pub(crate) const SECRET_KEYS: &[&str] = &[
+    "token",
+    "secret",
+    "password",
+    "passwd",
+    "apikey",
+    "api_key",
+    "credential",
+    "auth",
+];
+pub(crate) const REDACTED: &str = "[redacted]";
+
+/// Replaces secret-looking values in `value`, including credentials
+/// embedded in URLs.
+pub(crate) fn redact(value: &mut Value) {
+    match value {
+        Value::Object(object) => {
+            for (key, value) in object.iter_mut() {
+                let key = key.to_lowercase();
+                if !value.is_null() && SECRET_KEYS.iter().any(|secret| key.contains(secret)) {
+                    *value = Value::String(REDACTED.to_string());
+                } else {
+                    redact(value);
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(redact),
+        Value::String(text) => {
+            if let Ok(mut url) = reqwest::Url::parse(text) {
+                if !url.username().is_empty() || url.password().is_some() {
+                    let _ = url.set_username("");
+                    let _ = url.set_password(None);
+                    *text = url.to_string();
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// The last server failure and the output that led up to it, which is where
+/// a Python traceback ends up.
+fn crash_report(app: &AppHandle) -> String {
+    let snapshot = app
+        .state::<ServerStatus>()
+        .snapshot(app.state::<PortManager>().port());
+    let mut report = format!(
+        "State: {:?}\nLast error: {}\n\nRecent server output:\n",
+        snapshot.state,
+        snapshot.last_error.as_deref().unwrap_or("none")
+    );
+    for line in app.state::<ServerLogs>().recent(CRASH_LINES) {
+        report.push_str(&format!("[{}] {}\n", line.stream.as_str(), line.line));
+    }
+    report
+}
+
+/// Recent files in the logs directory, newest first.
+pub(crate) fn recent_logs(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let now = SystemTime::now();
+    let mut files: Vec<(SystemTime, PathBuf)> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let metadata = entry.metadata().ok().filter(|m| m.is_file())?;
+            let modified = metadata.modified().ok()?;
+            let recent = now
+                .duration_since(modified)
+                .map_or(true, |age| age <= LOG_MAX_AGE);
+            recent.then(|| (modified, entry.path()))
+        })
+        .collect();
+    files.sort_by_key(|(modified, _)| std::cmp::Reverse(*modified));
+    files.into_iter().map(|(_, path)| path).collect()
+}
+
+pub(crate) fn read_tail(path: &Path, max_bytes: u64) -> std::io::Result<Vec<u8>> {
+    let mut file = File::open(path)?;
+    let len = file.metadata()?.len();
+    file.seek(SeekFrom::Start(len.saturating_sub(max_bytes)))?;
+    let mut contents = Vec::new();
+    file.read_to_end(&mut contents)?;
+    Ok(contents)
+}
+
+fn json_bytes(mut value: Value) -> Vec<u8> {
+    redact(&mut value);
+    serde_json::to_vec_pretty(&value).unwrap_or_default()
+}
+
+/// The user's downloads directory, where files for bug reports are saved.
+pub(crate) fn downloads_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    let dir = app
+        .path()
+        .download_dir()
+        .or_else(|_| app.path().app_data_dir().map(|dir| dir.join("downloads")))
+        .map_err(|e| format!("Failed to find a downloads directory: {}", e))?;
+    std::fs::create_dir_all(&dir)
+        .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    Ok(dir)
+}
+
+fn bundle_path(app: &AppHandle) -> Result<PathBuf, String> {
+    let dir = downloads_dir(app)?;
+    let stamp = chrono::Local::now().format("%Y-%m-%d_%H-%M-%S");
+    Ok(dir.join(format!("nyx-diagnostics_{}.zip", stamp)))
+}
+
+fn write_bundle(app: &AppHandle, path: &Path, system: Value) -> zip::result::ZipResult<()> {
+    let mut zip = ZipWriter::new(File::create(path)?);
+    let options = SimpleFileOptions::default();
+
+    let mut add = |name: &str, contents: &[u8]| -> zip::result::ZipResult<()> {
+        zip.start_file(name, options)?;
+        zip.write_all(contents)?;
+        Ok(())
+    };
+
+    add("system.json", &json_bytes(system))?;
+    add("crash.txt", crash_report(app).as_bytes())?;
+    if let Some((report, minidump)) = crate::crash::latest_report(app) {
+        if let Ok(contents) = std::fs::read(&report) {
+            add("app-crash.json", &contents)?;
+        }
+        if let Some(contents) = minidump.and_then(|path| std::fs::read(path).ok()) {
+            add("app-crash.dmp", &contents)?;
+        }
+    }
+    let config = serde_json::to_value(app.state::<ConfigStore>().get()).unwrap_or_default();
+    add("config.json", &json_bytes(config))?;
+    let settings = serde_json::to_value(app.state::<SettingsStore>().get()).unwrap_or_default();
+    add("settings.json", &json_bytes(settings))?;
+
+    if let Ok(dir) = crate::log_files::logs_dir(app) {
+        for log in recent_logs(&dir) {
+            let Some(name) = log
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+            else {
+                continue;
+            };
+            match read_tail(&log, LOG_MAX_BYTES) {
+                Ok(contents) => add(&format!("logs/{}", name), &contents)?,
+                Err(e) => log::warn!("Leaving {} out of the bundle: {}", log.display(), e),
+            }
+        }
+    }
+
+    zip.finish()?;
+    Ok(())
+}
+
+/// Writes the diagnostic bundle to `path`.
+pub async fn write_bundle_to(app: &AppHandle, path: PathBuf) -> Result<(), String> {
+    let mut system =
+        serde_json::to_value(crate::system_info::collect(app).await).unwrap_or_default();
+    if let Value::Object(object) = &mut system {
+        object.insert(
+            "generatedAt".to_string(),
+            chrono::Local::now().to_rfc3339().into(),
+        );
+    }
+    let app = app.clone();
+    let target = path.clone();
+    tauri::async_runtime::spawn_blocking(move || write_bundle(&app, &target, system))
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| {
+            let _ = std::fs::remove_file(&path);
+            format!("Failed to write diagnostic bundle: {}", e)
+        })?;
+    log::info!("Wrote diagnostic bundle to {}", path.display());
+    Ok(())
+}
+
+/// Writes the diagnostic bundle and returns where it was saved.
+#[tauri::command]
+pub async fn generate_diagnostic_bundle(app_handle: AppHandle) -> Result<String, String> {
+    let path = bundle_path(&app_handle)?;
+    write_bundle_to(&app_handle, path.clone()).await?;
+    Ok(path.display().to_string())
+}
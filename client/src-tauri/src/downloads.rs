@@ -0,0 +1,510 @@
+//! Downloads of large artifacts such as browser builds and geo databases,
+//! which the webview's fetch can neither resume nor verify.
+//!
+//! Each download streams to a `.part` file next to its destination and picks
+//! up where it left off with an HTTP range request after a pause or failure.
+//! Only a few run at once; the rest wait their turn. Every change is emitted
+//! as `download://progress` with the download's current [`DownloadInfo`].
+
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use reqwest::header::{CONTENT_RANGE, RANGE};
+use reqwest::StatusCode;
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager};
+use tokio::sync::{watch, Semaphore};
+
+use crate::bandwidth::{self, Feature};
+use crate::notifications::{self, NotificationKind};
+use crate::server::client::{self, RequestOptions};
+use crate::server::update::sha256_file;
+
+/// Downloads running at the same time.
+const MAX_CONCURRENT: usize = 3;
+/// Builds are large; the client-wide timeout would cut them off.
+const DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(60 * 60);
+const PARTIAL_SUFFIX: &str = ".part";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DownloadState {
+    Queued,
+    Downloading,
+    Paused,
+    Verifying,
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+impl DownloadState {
+    pub(crate) fn is_finished(self) -> bool {
+        matches!(self, Self::Completed | Self::Failed | Self::Cancelled)
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DownloadInfo {
+    pub id: String,
+    pub url: String,
+    pub dest: String,
+    pub sha256: Option<String>,
+    pub state: DownloadState,
+    pub received: u64,
+    pub total: Option<u64>,
+    pub error: Option<String>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Control {
+    Run,
+    Pause,
+    Cancel,
+}
+
+struct Download {
+    info: watch::Sender<DownloadInfo>,
+    control: watch::Sender<Control>,
+}
+
+/// Managed state holding this session's downloads.
+pub struct DownloadManager {
+    downloads: Mutex<HashMap<String, Download>>,
+    slots: Arc<Semaphore>,
+    next_id: AtomicU64,
+}
+
+impl Default for DownloadManager {
+    fn default() -> Self {
+        Self {
+            downloads: Mutex::new(HashMap::new()),
+            slots: Arc::new(Semaphore::new(MAX_CONCURRENT)),
+            next_id: AtomicU64::new(1),
+        }
+    }
+}
+
+/// How a finished transfer ended.
+enum Outcome {
+    Done,
+    Paused,
+    Cancelled,
+}
+
+impl DownloadManager {
+    /// Whether `path` belongs to a download still in progress. Partial files
+    /// are named after their destination.
+    pub fn holds(&self, path: &Path) -> bool {
+        let path = path.to_string_lossy();
+        self.list()
+            .iter()
+            .filter(|download| !download.state.is_finished())
+            .any(|download| path.starts_with(download.dest.as_str()))
+    }
+
+    pub fn list(&self) -> Vec<DownloadInfo> {
+        let downloads = self.downloads.lock().unwrap();
+        let mut list: Vec<DownloadInfo> = downloads
+            .values()
+            .map(|d| d.info.borrow().clone())
+            .collect();
+        list.sort_by_key(|info| info.id.parse::<u64>().unwrap_or_default());
+        list
+    }
+
+    /// Queues a download of `url` to `dest` and returns right away.
+    pub fn start(
+        &self,
+        app: &AppHandle,
+        url: String,
+        dest: PathBuf,
+        sha256: Option<String>,
+    ) -> Result<DownloadInfo, String> {
+        reqwest::Url::parse(&url).map_err(|e| format!("Invalid download URL {}: {}", url, e))?;
+        if let Some(hash) = &sha256 {
+            if hash.len() != 64 || !hash.chars().all(|c| c.is_ascii_hexdigit()) {
+                return Err(format!("Invalid SHA-256 checksum: {}", hash));
+            }
+        }
+        let dest_text = dest.display().to_string();
+        if self
+            .list()
+            .iter()
+            .any(|info| info.dest == dest_text && !info.state.is_finished())
+        {
+            return Err(format!("{} is already being downloaded", dest.display()));
+        }
+
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst).to_string();
+        let info = DownloadInfo {
+            id: id.clone(),
+            url,
+            dest: dest_text,
+            sha256,
+            state: DownloadState::Queued,
+            received: 0,
+            total: None,
+            error: None,
+        };
+        let (info_tx, _) = watch::channel(info.clone());
+        let (control_tx, control_rx) = watch::channel(Control::Run);
+        self.downloads.lock().unwrap().insert(
+            id.clone(),
+            Download {
+                info: info_tx,
+                control: control_tx,
+            },
+        );
+        let _ = app.emit("download://progress", info.clone());
+        tauri::async_runtime::spawn(run(app.clone(), id, control_rx));
+        Ok(info)
+    }
+
+    pub fn pause(&self, id: &str) -> Result<(), String> {
+        self.control(id, Control::Pause)
+    }
+
+    /// Stops the download and deletes what it had received so far.
+    pub fn cancel(&self, app: &AppHandle, id: &str) -> Result<(), String> {
+        let info = self
+            .snapshot(id)
+            .ok_or_else(|| format!("No download {}", id))?;
+        // Nothing is running to see the request
+        if matches!(info.state, DownloadState::Paused | DownloadState::Failed) {
+            let _ = std::fs::remove_file(partial_path(Path::new(&info.dest)));
+            self.update(app, id, |info| info.state = DownloadState::Cancelled);
+            return Ok(());
+        }
+        self.control(id, Control::Cancel)
+    }
+
+    /// Continues a paused or failed download from where it stopped.
+    pub fn resume(&self, app: &AppHandle, id: &str) -> Result<(), String> {
+        let control = {
+            let downloads = self.downloads.lock().unwrap();
+            let download = downloads
+                .get(id)
+                .ok_or_else(|| format!("No download {}", id))?;
+            let state = download.info.borrow().state;
+            if !matches!(state, DownloadState::Paused | DownloadState::Failed) {
+                return Err(format!("Download {} is not paused", id));
+            }
+            download.control.send_replace(Control::Run);
+            download.control.subscribe()
+        };
+        self.update(app, id, |info| {
+            info.state = DownloadState::Queued;
+            info.error = None;
+        });
+        tauri::async_runtime::spawn(run(app.clone(), id.to_string(), control));
+        Ok(())
+    }
+
+    /// Waits for the download to finish and returns where it was saved.
+    pub async fn wait(&self, id: &str) -> Result<PathBuf, String> {
+        let mut info = self
+            .downloads
+            .lock()
+            .unwrap()
+            .get(id)
+            .map(|download| download.info.subscribe())
+            .ok_or_else(|| format!("No download {}", id))?;
+        let info = info
+            .wait_for(|info| info.state.is_finished() || info.state == DownloadState::Paused)
+            .await
+            .map_err(|_| format!("Download {} went away", id))?
+            .clone();
+        match info.state {
+            DownloadState::Completed => Ok(PathBuf::from(info.dest)),
+            DownloadState::Paused => Err(format!("Download {} was paused", id)),
+            DownloadState::Cancelled => Err(format!("Download {} was cancelled", id)),
+            _ => Err(info
+                .error
+                .unwrap_or_else(|| format!("Download {} failed", id))),
+        }
+    }
+
+    fn control(&self, id: &str, control: Control) -> Result<(), String> {
+        let downloads = self.downloads.lock().unwrap();
+        let download = downloads
+            .get(id)
+            .ok_or_else(|| format!("No download {}", id))?;
+        if download.info.borrow().state.is_finished() {
+            return Err(format!("Download {} has already finished", id));
+        }
+        download.control.send_replace(control);
+        Ok(())
+    }
+
+    fn snapshot(&self, id: &str) -> Option<DownloadInfo> {
+        let downloads = self.downloads.lock().unwrap();
+        downloads
+            .get(id)
+            .map(|download| download.info.borrow().clone())
+    }
+
+    fn update(&self, app: &AppHandle, id: &str, change: impl FnOnce(&mut DownloadInfo)) {
+        let downloads = self.downloads.lock().unwrap();
+        let Some(download) = downloads.get(id) else {
+            return;
+        };
+        download.info.send_modify(change);
+        let info = download.info.borrow().clone();
+        drop(downloads);
+        let _ = app.emit("download://progress", info);
+    }
+}
+
+fn partial_path(dest: &Path) -> PathBuf {
+    let mut name = dest.file_name().unwrap_or_default().to_os_string();
+    name.push(PARTIAL_SUFFIX);
+    dest.with_file_name(name)
+}
+
+/// The full size from `Content-Range: bytes 100-999/1000`.
+fn range_total(response: &reqwest::Response) -> Option<u64> {
+    response
+        .headers()
+        .get(CONTENT_RANGE)?
+        .to_str()
+        .ok()?
+        .rsplit_once('/')?
+        .1
+        .parse()
+        .ok()
+}
+
+async fn run(app: AppHandle, id: String, mut control: watch::Receiver<Control>) {
+    let manager = app.state::<DownloadManager>();
+
+    // Wait for a slot, unless the download is paused or cancelled meanwhile
+    let slot = loop {
+        if *control.borrow_and_update() != Control::Run {
+            break None;
+        }
+        tokio::select! {
+            slot = manager.slots.clone().acquire_owned() => break slot.ok(),
+            changed = control.changed() => if changed.is_err() { break None },
+        }
+    };
+
+    let outcome = match slot {
+        Some(_slot) => transfer(&app, &id, &mut control).await,
+        None if *control.borrow() == Control::Pause => Ok(Outcome::Paused),
+        None => Ok(Outcome::Cancelled),
+    };
+
+    let Some(info) = manager.snapshot(&id) else {
+        return;
+    };
+    let dest = PathBuf::from(&info.dest);
+    match outcome {
+        Ok(Outcome::Done) => {
+            log::info!("Downloaded {} to {}", info.url, dest.display());
+            manager.update(&app, &id, |info| info.state = DownloadState::Completed);
+            let name = dest.file_name().unwrap_or_default().to_string_lossy();
+            notifications::send_or_log(
+                &app,
+                NotificationKind::Downloads,
+                "Download finished",
+                &format!("{} is ready", name),
+            );
+        }
+        Ok(Outcome::Paused) => manager.update(&app, &id, |info| info.state = DownloadState::Paused),
+        Ok(Outcome::Cancelled) => {
+            let _ = std::fs::remove_file(partial_path(&dest));
+            manager.update(&app, &id, |info| info.state = DownloadState::Cancelled);
+        }
+        Err(e) => {
+            log::warn!("Download of {} failed: {}", info.url, e);
+            manager.update(&app, &id, |info| {
+                info.state = DownloadState::Failed;
+                info.error = Some(e);
+            });
+        }
+    }
+}
+
+async fn transfer(
+    app: &AppHandle,
+    id: &str,
+    control: &mut watch::Receiver<Control>,
+) -> Result<Outcome, String> {
+    let manager = app.state::<DownloadManager>();
+    let info = manager.snapshot(id).ok_or("Download went away")?;
+    let dest = PathBuf::from(&info.dest);
+    let partial = partial_path(&dest);
+    if let Some(parent) = dest.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+    }
+
+    let mut offset = std::fs::metadata(&partial).map(|m| m.len()).unwrap_or(0);
+    let options = RequestOptions {
+        timeout_ms: Some(DOWNLOAD_TIMEOUT.as_millis() as u64),
+        retries: None,
+        queue: None,
+    };
+    let mut response = client::send(app, options, |client| {
+        let request = client.get(&info.url);
+        if offset > 0 {
+            request.header(RANGE, format!("bytes={}-", offset))
+        } else {
+            request
+        }
+    })
+    .await
+    .map_err(|e| format!("Failed to download {}: {}", info.url, e))?;
+
+    let total = match response.status() {
+        // The part file already holds everything
+        StatusCode::RANGE_NOT_SATISFIABLE if offset > 0 => Some(offset),
+        StatusCode::PARTIAL_CONTENT => range_total(&response),
+        status if status.is_success() => {
+            // The server ignored the range; start over
+            offset = 0;
+            response.content_length()
+        }
+        status => return Err(format!("Failed to download {}: HTTP {}", info.url, status)),
+    };
+    let complete = response.status() == StatusCode::RANGE_NOT_SATISFIABLE;
+    manager.update(app, id, |info| {
+        info.state = DownloadState::Downloading;
+        info.received = offset;
+        info.total = total;
+    });
+
+    if !complete {
+        let mut out = std::fs::OpenOptions::new()
+            .create(true)
+            .write(true)
+            .append(offset > 0)
+            .truncate(offset == 0)
+            .open(&partial)
+            .map_err(|e| format!("Failed to open {}: {}", partial.display(), e))?;
+        let mut received = offset;
+        let mut last_emit = Instant::now();
+        loop {
+            tokio::select! {
+                chunk = response.chunk() => {
+                    let Some(chunk) = chunk.map_err(|e| format!("Failed to download {}: {}", info.url, e))? else {
+                        break;
+                    };
+                    out.write_all(&chunk)
+                        .map_err(|e| format!("Failed to write {}: {}", partial.display(), e))?;
+                    received += chunk.len() as u64;
+                    bandwidth::record(app, Feature::Downloads, 0, chunk.len() as u64);
+                    if last_emit.elapsed() >= crate::PROGRESS_INTERVAL {
+                        last_emit = Instant::now();
+                        manager.update(app, id, |info| info.received = received);
+                    }
+                }
+                changed = control.changed() => {
+                    match changed.map(|_| *control.borrow_and_update()) {
+                        Ok(Control::Run) => {}
+                        Ok(Control::Pause) => {
+                            out.flush().map_err(|e| e.to_string())?;
+                            manager.update(app, id, |info| info.received = received);
+                            return Ok(Outcome::Paused);
+                        }
+                        Ok(Control::Cancel) | Err(_) => return Ok(Outcome::Cancelled),
+                    }
+                }
+            }
+        }
+        out.flush()
+            .map_err(|e| format!("Failed to write {}: {}", partial.display(), e))?;
+        manager.update(app, id, |info| info.received = received);
+        if total.is_some_and(|total| received < total) {
+            return Err(format!(
+                "Download of {} ended early after {} bytes",
+                info.url, received
+            ));
+        }
+    }
+
+    if let Some(expected) = &info.sha256 {
+        manager.update(app, id, |info| info.state = DownloadState::Verifying);
+        let path = partial.clone();
+        let actual = tauri::async_runtime::spawn_blocking(move || sha256_file(&path))
+            .await
+            .map_err(|e| e.to_string())?
+            .map_err(|e| format!("Failed to hash {}: {}", partial.display(), e))?;
+        if !actual.eq_ignore_ascii_case(expected) {
+            let _ = std::fs::remove_file(&partial);
+            return Err(format!(
+                "Downloaded {} failed verification: expected {}, got {}",
+                info.url, expected, actual
+            ));
+        }
+    }
+
+    std::fs::rename(&partial, &dest)
+        .map_err(|e| format!("Failed to move {} into place: {}", dest.display(), e))?;
+    Ok(Outcome::Done)
+}
+
+/// Relative destinations land in the downloads folder of the data directory.
+fn resolve_dest(app: &AppHandle, dest: &str) -> Result<PathBuf, String> {
+    let dest = PathBuf::from(dest);
+    if dest.is_absolute() {
+        return Ok(dest);
+    }
+    if dest
+        .components()
+        .any(|c| !matches!(c, std::path::Component::Normal(_)))
+    {
+        return Err(format!("Invalid download destination: {}", dest.display()));
+    }
+    let dir = crate::data_dir::data_dir(app)?.join("downloads");
+    Ok(dir.join(dest))
+}
+
+#[tauri::command]
+pub fn start_download(
+    app_handle: AppHandle,
+    manager: tauri::State<'_, DownloadManager>,
+    url: String,
+    dest: String,
+    sha256: Option<String>,
+) -> Result<DownloadInfo, String> {
+    let dest = resolve_dest(&app_handle, &dest)?;
+    manager.start(&app_handle, url, dest, sha256)
+}
+
+#[tauri::command]
+pub fn pause_download(
+    manager: tauri::State<'_, DownloadManager>,
+    id: String,
+) -> Result<(), String> {
+    manager.pause(&id)
+}
+
+#[tauri::command]
+pub fn resume_download(
+    app_handle: AppHandle,
+    manager: tauri::State<'_, DownloadManager>,
+    id: String,
+) -> Result<(), String> {
+    manager.resume(&app_handle, &id)
+}
+
+#[tauri::command]
+pub fn cancel_download(
+    app_handle: AppHandle,
+    manager: tauri::State<'_, DownloadManager>,
+    id: String,
+) -> Result<(), String> {
+    manager.cancel(&app_handle, &id)
+}
+
+#[tauri::command]
+pub fn list_downloads(manager: tauri::State<'_, DownloadManager>) -> Vec<DownloadInfo> {
+    manager.list()
+}
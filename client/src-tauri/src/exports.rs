@@ -0,0 +1,356 @@
+//! Serves generated reports and screenshots from `exports/` in app data to
+//! the webview, so large files never travel through IPC as base64.
+//!
+//! The backend finds the directory in [`EXPORTS_ENV`] and writes there; the
+//! frontend loads `nyx-export://localhost/<path>` (on Windows
+//! `http://nyx-export.localhost/<path>`), as returned by
+//! [`get_export_url`]. Only files inside the directory are served, and only
+//! for `GET` and `HEAD`. Single byte ranges are honored so media can seek,
+//! and a file too large to hold in memory at once is only served in ranges.
+//! Only the app's own pages may read the responses cross-origin.
+
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Component, Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, CONTROLS};
+use serde::Serialize;
+use tauri::http::{header, HeaderValue, Method, Request, Response, StatusCode};
+use tauri::{AppHandle, Manager, UriSchemeContext, UriSchemeResponder, Wry};
+
+use crate::audit::{self, AuditAction};
+
+pub const SCHEME: &str = "nyx-export";
+/// Environment variable the server reads the exports directory from.
+pub const EXPORTS_ENV: &str = "NYX_EXPORTS_DIR";
+const EXPORTS_DIR: &str = "exports";
+/// Largest part of a file sent for one range request.
+const MAX_RANGE: u64 = 16 * 1024 * 1024;
+/// Largest file sent whole; larger ones have to be asked for in ranges, as
+/// a response is held in memory in full.
+const MAX_BODY: u64 = 64 * 1024 * 1024;
+
+/// Characters escaped in a path segment of an export URL.
+const SEGMENT: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'/')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'`')
+    .add(b'{')
+    .add(b'}');
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportFile {
+    /// Path relative to the exports directory, with `/` separators.
+    pub path: String,
+    pub size: u64,
+    pub modified_ms: Option<u64>,
+    pub url: String,
+}
+
+pub fn exports_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    let dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to resolve app data directory: {}", e))?
+        .join(EXPORTS_DIR);
+    std::fs::create_dir_all(&dir)
+        .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    Ok(dir)
+}
+
+fn base_url() -> String {
+    if cfg!(windows) {
+        format!("http://{}.localhost", SCHEME)
+    } else {
+        format!("{}://localhost", SCHEME)
+    }
+}
+
+fn url_for(relative: &str) -> String {
+    let path: Vec<String> = relative
+        .split('/')
+        .map(|segment| utf8_percent_encode(segment, SEGMENT).to_string())
+        .collect();
+    format!("{}/{}", base_url(), path.join("/"))
+}
+
+/// `relative` as a path inside `root`, or `None` if it is not a plain
+/// relative path. Symlinks leading out of `root` are refused as well.
+pub fn resolve(root: &Path, relative: &str) -> Option<PathBuf> {
+    let relative = Path::new(relative.trim_start_matches('/'));
+    let plain = relative
+        .components()
+        .all(|component| matches!(component, Component::Normal(_)));
+    if !plain || relative.as_os_str().is_empty() {
+        return None;
+    }
+    let root = root.canonicalize().ok()?;
+    let path = root.join(relative).canonicalize().ok()?;
+    path.starts_with(&root).then_some(path)
+}
+
+fn content_type(path: &Path) -> &'static str {
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(str::to_ascii_lowercase);
+    match extension.as_deref() {
+        Some("html" | "htm") => "text/html; charset=utf-8",
+        Some("json") => "application/json",
+        Some("csv") => "text/csv; charset=utf-8",
+        Some("txt" | "log") => "text/plain; charset=utf-8",
+        Some("pdf") => "application/pdf",
+        Some("png") => "image/png",
+        Some("jpg" | "jpeg") => "image/jpeg",
+        Some("webp") => "image/webp",
+        Some("gif") => "image/gif",
+        Some("svg") => "image/svg+xml",
+        Some("mp4") => "video/mp4",
+        Some("webm") => "video/webm",
+        Some("zip") => "application/zip",
+        _ => "application/octet-stream",
+    }
+}
+
+/// The first range of a `Range: bytes=...` header, clamped to the file.
+fn parse_range(header: &str, len: u64) -> Option<(u64, u64)> {
+    let spec = header.strip_prefix("bytes=")?.split(',').next()?.trim();
+    let (start, end) = spec.split_once('-')?;
+    let (start, end) = match (start.parse::<u64>().ok(), end.parse::<u64>().ok()) {
+        (Some(start), Some(end)) => (start, end.min(len.checked_sub(1)?)),
+        (Some(start), None) => (start, len.checked_sub(1)?),
+        // The last `end` bytes
+        (None, Some(suffix)) => (len.saturating_sub(suffix), len.checked_sub(1)?),
+        (None, None) => return None,
+    };
+    (start <= end).then_some((start, end.min(start + MAX_RANGE - 1)))
+}
+
+pub(crate) fn error(status: StatusCode) -> Response<Vec<u8>> {
+    Response::builder()
+        .status(status)
+        .body(Vec::new())
+        .unwrap_or_default()
+}
+
+/// Origins of the app's own pages: the bundled frontend on every platform,
+/// and in dev builds the dev server.
+fn app_origins(app: &AppHandle) -> Vec<String> {
+    let mut origins: Vec<String> = [
+        "tauri://localhost",
+        "http://tauri.localhost",
+        "https://tauri.localhost",
+    ]
+    .map(String::from)
+    .into();
+    if cfg!(debug_assertions) {
+        if let Some(url) = &app.config().build.dev_url {
+            origins.push(url.origin().ascii_serialization());
+        }
+    }
+    origins
+}
+
+/// Lets the app's own pages read `response` cross-origin, and nothing else.
+pub(crate) fn allow_app_origin(
+    app: &AppHandle,
+    request: &Request<Vec<u8>>,
+    mut response: Response<Vec<u8>>,
+) -> Response<Vec<u8>> {
+    let origin = request
+        .headers()
+        .get(header::ORIGIN)
+        .filter(|origin| {
+            origin
+                .to_str()
+                .is_ok_and(|origin| app_origins(app).iter().any(|allowed| allowed == origin))
+        })
+        .cloned();
+    let headers = response.headers_mut();
+    headers.insert(header::VARY, HeaderValue::from_static("Origin"));
+    if let Some(origin) = origin {
+        headers.insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, origin);
+    }
+    response
+}
+
+/// Answers a request for a file under `root`.
+pub fn serve(root: &Path, request: &Request<Vec<u8>>) -> Response<Vec<u8>> {
+    if !matches!(*request.method(), Method::GET | Method::HEAD) {
+        return error(StatusCode::METHOD_NOT_ALLOWED);
+    }
+    let Ok(relative) = percent_decode_str(request.uri().path()).decode_utf8() else {
+        return error(StatusCode::BAD_REQUEST);
+    };
+    let Some(path) = resolve(root, &relative).filter(|path| path.is_file()) else {
+        return error(StatusCode::NOT_FOUND);
+    };
+    serve_file(&path, request)
+}
+
+/// Answers a `GET` or `HEAD` of `path`, which the caller has checked.
+pub(crate) fn serve_file(path: &Path, request: &Request<Vec<u8>>) -> Response<Vec<u8>> {
+    match read(path, request) {
+        Ok(response) => response,
+        Err(e) => {
+            log::warn!("Failed to serve {}: {}", path.display(), e);
+            error(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+fn read(path: &Path, request: &Request<Vec<u8>>) -> std::io::Result<Response<Vec<u8>>> {
+    let mut file = std::fs::File::open(path)?;
+    let len = file.metadata()?.len();
+    let range = request
+        .headers()
+        .get(header::RANGE)
+        .and_then(|range| range.to_str().ok());
+    let mut response = Response::builder()
+        .header(header::CONTENT_TYPE, content_type(path))
+        .header(header::ACCEPT_RANGES, "bytes")
+        .header(header::CACHE_CONTROL, "no-cache")
+        .header(header::X_CONTENT_TYPE_OPTIONS, "nosniff");
+
+    let (start, end) = match range {
+        Some(range) => match parse_range(range, len) {
+            Some((start, end)) => {
+                response = response.status(StatusCode::PARTIAL_CONTENT).header(
+                    header::CONTENT_RANGE,
+                    format!("bytes {}-{}/{}", start, end, len),
+                );
+                (start, end + 1)
+            }
+            None => {
+                return Ok(Response::builder()
+                    .status(StatusCode::RANGE_NOT_SATISFIABLE)
+                    .header(header::CONTENT_RANGE, format!("bytes */{}", len))
+                    .body(Vec::new())
+                    .unwrap_or_default())
+            }
+        },
+        None if len > MAX_BODY && *request.method() == Method::GET => {
+            return Ok(Response::builder()
+                .status(StatusCode::PAYLOAD_TOO_LARGE)
+                .header(header::ACCEPT_RANGES, "bytes")
+                .body(Vec::new())
+                .unwrap_or_default());
+        }
+        None => (0, len),
+    };
+    response = response.header(header::CONTENT_LENGTH, end - start);
+
+    let mut body = Vec::new();
+    if *request.method() == Method::GET {
+        file.seek(SeekFrom::Start(start))?;
+        file.take(end - start).read_to_end(&mut body)?;
+    }
+    Ok(response.body(body).unwrap_or_default())
+}
+
+/// Handler for [`SCHEME`]. Files are read off the main thread.
+pub fn handle(
+    context: UriSchemeContext<'_, Wry>,
+    request: Request<Vec<u8>>,
+    responder: UriSchemeResponder,
+) {
+    let app = context.app_handle().clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        let response = match exports_dir(&app) {
+            Ok(root) => serve(&root, &request),
+            Err(e) => {
+                log::warn!("{}", e);
+                error(StatusCode::INTERNAL_SERVER_ERROR)
+            }
+        };
+        responder.respond(allow_app_origin(&app, &request, response));
+    });
+}
+
+fn collect(root: &Path, dir: &Path, files: &mut Vec<ExportFile>) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        let path = entry.path();
+        if file_type.is_dir() {
+            collect(root, &path, files)?;
+        } else if file_type.is_file() {
+            let Ok(relative) = path.strip_prefix(root) else {
+                continue;
+            };
+            let relative = relative
+                .components()
+                .map(|component| component.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            let metadata = entry.metadata()?;
+            files.push(ExportFile {
+                url: url_for(&relative),
+                path: relative,
+                size: metadata.len(),
+                modified_ms: metadata
+                    .modified()
+                    .ok()
+                    .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+                    .map(|since| since.as_millis() as u64),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Every exported file, newest first.
+#[tauri::command]
+pub async fn list_exports(app_handle: AppHandle) -> Result<Vec<ExportFile>, String> {
+    let root = exports_dir(&app_handle)?;
+    tauri::async_runtime::spawn_blocking(move || {
+        let mut files = Vec::new();
+        collect(&root, &root, &mut files)
+            .map_err(|e| format!("Failed to list {}: {}", root.display(), e))?;
+        files.sort_by_key(|file| std::cmp::Reverse(file.modified_ms));
+        Ok(files)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// The URL the webview loads the export at `path` from.
+#[tauri::command]
+pub fn get_export_url(app_handle: AppHandle, path: String) -> Result<String, String> {
+    let root = exports_dir(&app_handle)?;
+    let resolved = resolve(&root, &path).ok_or_else(|| format!("No export {}", path))?;
+    let relative = resolved
+        .strip_prefix(root.canonicalize().map_err(|e| e.to_string())?)
+        .map_err(|_| format!("No export {}", path))?
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/");
+    Ok(url_for(&relative))
+}
+
+/// Deletes an exported file. Returns `false` if there was none.
+#[tauri::command]
+pub fn delete_export(app_handle: AppHandle, path: String) -> Result<bool, String> {
+    let root = exports_dir(&app_handle)?;
+    let Some(resolved) = resolve(&root, &path).filter(|path| path.is_file()) else {
+        return Ok(false);
+    };
+    let removed = std::fs::remove_file(&resolved);
+    audit::record(
+        &app_handle,
+        AuditAction::FileDelete,
+        resolved.display().to_string(),
+        &removed,
+    );
+    removed.map_err(|e| format!("Failed to delete {}: {}", resolved.display(), e))?;
+    log::info!("Deleted export {}", path);
+    Ok(true)
+}
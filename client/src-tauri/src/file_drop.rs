@@ -0,0 +1,197 @@
+//! Files dropped onto a window, recognized and checked before anything is
+//! imported.
+//!
+//! Each dropped file is classified as a proxy list, a cookie file, a Nyx
+//! backup or a browser archive, and read far enough to preview what an
+//! import would bring in. The result is emitted as `import://file-ready`
+//! for the UI to confirm, after which it imports from the same path with
+//! the matching command. Files that are none of these, or broken, are
+//! emitted as `import://file-rejected`.
+
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+use tauri::{AppHandle, Emitter};
+
+use crate::archive::ArchiveKind;
+use crate::backup::{self, BackupManifest};
+use crate::cookies::{self, CookieFormat, CookieImport};
+use crate::proxy::import::parse_list;
+
+/// Text files beyond this are not read for a preview.
+const MAX_TEXT_BYTES: u64 = 50 * 1024 * 1024;
+/// How many items of a list a preview shows.
+const SAMPLE_SIZE: usize = 5;
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum DropPreview {
+    #[serde(rename_all = "camelCase")]
+    ProxyList {
+        proxies: usize,
+        duplicates: usize,
+        invalid: usize,
+        /// The first few, without passwords.
+        sample: Vec<String>,
+    },
+    #[serde(rename_all = "camelCase")]
+    Cookies {
+        format: CookieFormat,
+        cookies: usize,
+        expired: usize,
+        invalid: usize,
+        domains: Vec<String>,
+    },
+    #[serde(rename_all = "camelCase")]
+    Backup {
+        created_at: String,
+        app_version: String,
+        encrypted: bool,
+        files: usize,
+        bytes: u64,
+    },
+    #[serde(rename_all = "camelCase")]
+    BrowserArchive { format: ArchiveKind },
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ReadyPayload {
+    window: String,
+    path: String,
+    name: String,
+    size: u64,
+    preview: DropPreview,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RejectedPayload {
+    window: String,
+    path: String,
+    error: String,
+}
+
+fn read_text(path: &Path, size: u64) -> Result<String, String> {
+    if size > MAX_TEXT_BYTES {
+        return Err(format!(
+            "Files over {} MB cannot be imported",
+            MAX_TEXT_BYTES / 1024 / 1024
+        ));
+    }
+    std::fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))
+}
+
+fn cookie_preview(import: CookieImport) -> Result<DropPreview, String> {
+    if import.cookies.is_empty() {
+        return Err("The cookie file holds no cookies that can be imported".to_string());
+    }
+    Ok(DropPreview::Cookies {
+        domains: import.domains(),
+        format: import.format,
+        cookies: import.cookies.len(),
+        expired: import.expired,
+        invalid: import.invalid.len(),
+    })
+}
+
+fn classify_text(text: &str) -> Result<DropPreview, String> {
+    if cookies::detect(text) == Some(CookieFormat::Netscape) {
+        return cookie_preview(cookies::parse(text, Some(CookieFormat::Netscape))?);
+    }
+    let (proxies, duplicates, invalid) = parse_list(text, Default::default());
+    if proxies.is_empty() {
+        return Err("No proxies found in the file".to_string());
+    }
+    Ok(DropPreview::ProxyList {
+        sample: proxies
+            .iter()
+            .take(SAMPLE_SIZE)
+            .map(ToString::to_string)
+            .collect(),
+        proxies: proxies.len(),
+        duplicates,
+        invalid: invalid.len(),
+    })
+}
+
+fn backup_preview(manifest: BackupManifest) -> DropPreview {
+    let (files, bytes) = manifest.totals();
+    DropPreview::Backup {
+        bytes,
+        files,
+        created_at: manifest.created_at,
+        app_version: manifest.app_version,
+        encrypted: manifest.encrypted,
+    }
+}
+
+/// What `path` is and what importing it would bring in.
+pub fn classify(path: &Path) -> Result<DropPreview, String> {
+    let metadata =
+        std::fs::metadata(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    if !metadata.is_file() {
+        return Err("Folders cannot be imported".to_string());
+    }
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(str::to_ascii_lowercase);
+    match extension.as_deref() {
+        Some("txt" | "csv" | "list") => classify_text(&read_text(path, metadata.len())?),
+        Some("json") => cookie_preview(
+            cookies::parse(&read_text(path, metadata.len())?, None)
+                .map_err(|e| format!("Not a cookie export: {}", e))?,
+        ),
+        _ => match ArchiveKind::detect(path) {
+            Some(ArchiveKind::Zip) => match backup::inspect(path)? {
+                Some(manifest) => Ok(backup_preview(manifest)),
+                None => Ok(DropPreview::BrowserArchive {
+                    format: ArchiveKind::Zip,
+                }),
+            },
+            Some(format) => Ok(DropPreview::BrowserArchive { format }),
+            None => Err("Not a proxy list, cookie file, backup or browser archive".to_string()),
+        },
+    }
+}
+
+/// Classifies files dropped onto `window` in the background.
+pub fn handle(app: &AppHandle, window: &str, paths: Vec<PathBuf>) {
+    let app = app.clone();
+    let window = window.to_string();
+    tauri::async_runtime::spawn_blocking(move || {
+        for path in paths {
+            let display = path.to_string_lossy().to_string();
+            match classify(&path) {
+                Ok(preview) => {
+                    log::info!("Dropped file {} is ready to import", display);
+                    let _ = app.emit(
+                        "import://file-ready",
+                        ReadyPayload {
+                            window: window.clone(),
+                            name: path
+                                .file_name()
+                                .map(|name| name.to_string_lossy().to_string())
+                                .unwrap_or_default(),
+                            size: std::fs::metadata(&path).map_or(0, |m| m.len()),
+                            path: display,
+                            preview,
+                        },
+                    );
+                }
+                Err(error) => {
+                    log::info!("Rejected dropped file {}: {}", display, error);
+                    let _ = app.emit(
+                        "import://file-rejected",
+                        RejectedPayload {
+                            window: window.clone(),
+                            path: display,
+                            error,
+                        },
+                    );
+                }
+            }
+        }
+    });
+}
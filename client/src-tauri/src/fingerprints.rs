@@ -0,0 +1,222 @@
+//! Real device fingerprints bundled with the app, so profiles can be created
+//! offline and without the backend's dataset.
+//!
+//! The dataset ships gzipped as [`DATASET`] in the bundle's resources and is
+//! loaded on the first query. Each entry is one consistent device: its user
+//! agent, screen, installed fonts and WebGL strings came from the same
+//! machine, so picking one never mixes a Mac GPU with a Windows font list.
+
+use std::io::Read;
+use std::sync::{Arc, Mutex};
+
+use flate2::read::GzDecoder;
+use rand::seq::SliceRandom;
+use serde::{Deserialize, Serialize};
+use tauri::path::BaseDirectory;
+use tauri::{AppHandle, Manager, State};
+
+/// Path of the dataset among the bundle's resources.
+const DATASET: &str = "resources/fingerprints.json.gz";
+const DATASET_VERSION: u32 = 1;
+const DEFAULT_LIMIT: usize = 100;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FingerprintOs {
+    Windows,
+    Macos,
+    Linux,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FingerprintBrowser {
+    Firefox,
+    Chrome,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScreenInfo {
+    pub width: u32,
+    pub height: u32,
+    pub avail_width: u32,
+    pub avail_height: u32,
+    pub color_depth: u32,
+    pub pixel_ratio: f64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WebGlInfo {
+    pub vendor: String,
+    pub renderer: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Fingerprint {
+    pub id: String,
+    pub os: FingerprintOs,
+    pub browser: FingerprintBrowser,
+    pub browser_version: u32,
+    pub user_agent: String,
+    pub platform: String,
+    pub screen: ScreenInfo,
+    pub hardware_concurrency: u32,
+    pub fonts: Vec<String>,
+    pub webgl: WebGlInfo,
+}
+
+#[derive(Deserialize)]
+struct Dataset {
+    version: u32,
+    fingerprints: Vec<Fingerprint>,
+}
+
+/// What a fingerprint must match. Text filters match case-insensitively
+/// anywhere in the field.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct FingerprintFilters {
+    pub os: Option<FingerprintOs>,
+    pub browser: Option<FingerprintBrowser>,
+    pub min_browser_version: Option<u32>,
+    pub min_width: Option<u32>,
+    pub max_width: Option<u32>,
+    pub min_height: Option<u32>,
+    pub max_height: Option<u32>,
+    pub user_agent: Option<String>,
+    pub webgl_vendor: Option<String>,
+    pub webgl_renderer: Option<String>,
+    /// Fonts that must all be installed.
+    pub fonts: Vec<String>,
+}
+
+fn contains(field: &str, filter: &Option<String>) -> bool {
+    filter.as_deref().map_or(true, |filter| {
+        field.to_lowercase().contains(&filter.to_lowercase())
+    })
+}
+
+impl FingerprintFilters {
+    fn matches(&self, fingerprint: &Fingerprint) -> bool {
+        let screen = &fingerprint.screen;
+        self.os.map_or(true, |os| os == fingerprint.os)
+            && self
+                .browser
+                .map_or(true, |browser| browser == fingerprint.browser)
+            && self
+                .min_browser_version
+                .map_or(true, |version| fingerprint.browser_version >= version)
+            && self.min_width.map_or(true, |width| screen.width >= width)
+            && self.max_width.map_or(true, |width| screen.width <= width)
+            && self
+                .min_height
+                .map_or(true, |height| screen.height >= height)
+            && self
+                .max_height
+                .map_or(true, |height| screen.height <= height)
+            && contains(&fingerprint.user_agent, &self.user_agent)
+            && contains(&fingerprint.webgl.vendor, &self.webgl_vendor)
+            && contains(&fingerprint.webgl.renderer, &self.webgl_renderer)
+            && self.fonts.iter().all(|font| {
+                fingerprint
+                    .fonts
+                    .iter()
+                    .any(|installed| installed.eq_ignore_ascii_case(font))
+            })
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FingerprintQuery {
+    /// How many fingerprints match, returned or not.
+    pub total: usize,
+    pub fingerprints: Vec<Fingerprint>,
+}
+
+/// The bundled dataset, loaded once it is first needed.
+#[derive(Default)]
+pub struct FingerprintDataset {
+    fingerprints: Mutex<Option<Arc<Vec<Fingerprint>>>>,
+}
+
+impl FingerprintDataset {
+    fn load(app: &AppHandle) -> Result<Vec<Fingerprint>, String> {
+        let path = app
+            .path()
+            .resolve(DATASET, BaseDirectory::Resource)
+            .map_err(|e| format!("Failed to resolve the fingerprint dataset: {}", e))?;
+        let file = std::fs::File::open(&path)
+            .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+        let mut json = String::new();
+        GzDecoder::new(file)
+            .read_to_string(&mut json)
+            .map_err(|e| format!("Failed to decompress {}: {}", path.display(), e))?;
+        let dataset: Dataset = serde_json::from_str(&json)
+            .map_err(|e| format!("Invalid fingerprint dataset: {}", e))?;
+        if dataset.version != DATASET_VERSION {
+            return Err(format!(
+                "Unsupported fingerprint dataset version {}",
+                dataset.version
+            ));
+        }
+        log::info!("Loaded {} bundled fingerprints", dataset.fingerprints.len());
+        Ok(dataset.fingerprints)
+    }
+
+    pub fn get(&self, app: &AppHandle) -> Result<Arc<Vec<Fingerprint>>, String> {
+        let mut fingerprints = self.fingerprints.lock().unwrap();
+        if let Some(fingerprints) = fingerprints.as_ref() {
+            return Ok(fingerprints.clone());
+        }
+        let loaded = Arc::new(Self::load(app)?);
+        *fingerprints = Some(loaded.clone());
+        Ok(loaded)
+    }
+}
+
+/// Fingerprints matching `filters`, at most `limit` of them.
+#[tauri::command]
+pub async fn query_fingerprints(
+    app_handle: AppHandle,
+    dataset: State<'_, FingerprintDataset>,
+    filters: Option<FingerprintFilters>,
+    limit: Option<usize>,
+) -> Result<FingerprintQuery, String> {
+    let filters = filters.unwrap_or_default();
+    let fingerprints = dataset.get(&app_handle)?;
+    let matching: Vec<&Fingerprint> = fingerprints
+        .iter()
+        .filter(|fingerprint| filters.matches(fingerprint))
+        .collect();
+    Ok(FingerprintQuery {
+        total: matching.len(),
+        fingerprints: matching
+            .into_iter()
+            .take(limit.unwrap_or(DEFAULT_LIMIT))
+            .cloned()
+            .collect(),
+    })
+}
+
+/// A fingerprint picked at random among those matching `constraints`.
+#[tauri::command]
+pub async fn random_fingerprint(
+    app_handle: AppHandle,
+    dataset: State<'_, FingerprintDataset>,
+    constraints: Option<FingerprintFilters>,
+) -> Result<Fingerprint, String> {
+    let constraints = constraints.unwrap_or_default();
+    let fingerprints = dataset.get(&app_handle)?;
+    let matching: Vec<&Fingerprint> = fingerprints
+        .iter()
+        .filter(|fingerprint| constraints.matches(fingerprint))
+        .collect();
+    matching
+        .choose(&mut rand::thread_rng())
+        .map(|fingerprint| (*fingerprint).clone())
+        .ok_or_else(|| "No bundled fingerprint matches the constraints".to_string())
+}
@@ -0,0 +1,186 @@
+//! Opens the app's folders in the OS file manager, and files the app wrote,
+//! such as exports and backups, in the file manager or their default app.
+//!
+//! Files are only opened under [`allowed_roots`]: app data, the logs, the
+//! backup folder and the user's downloads, documents and desktop, where
+//! exports are usually saved. Only folders and the kinds of document in
+//! [`DOCUMENT_EXTENSIONS`] are opened with their default app, and on Unix
+//! never one marked executable, as the OS may run those rather than show
+//! them.
+
+use std::path::{Path, PathBuf};
+
+use tauri::{AppHandle, Manager};
+
+use crate::config::ConfigStore;
+
+/// Extensions of the files [`open_with_default_app`] opens: what the app
+/// writes, such as exports, backups, logs and traces, and images.
+const DOCUMENT_EXTENSIONS: &[&str] = &[
+    "7z", "csv", "gif", "gz", "har", "jpeg", "jpg", "json", "jsonl", "log", "md", "pdf", "png",
+    "tar", "txt", "webp", "zip",
+];
+
+fn open_folder(path: &Path) -> Result<(), String> {
+    std::fs::create_dir_all(path)
+        .map_err(|e| format!("Failed to create {}: {}", path.display(), e))?;
+    tauri_plugin_opener::open_path(path, None::<&str>)
+        .map_err(|e| format!("Failed to open {}: {}", path.display(), e))
+}
+
+/// The server binary the app would launch: the bundled sidecar, or in dev
+/// builds the first runnable one in a source checkout.
+fn server_binary(app: &AppHandle) -> Option<PathBuf> {
+    crate::server::supervisor::LaunchSpec::sidecar(app, crate::SERVER_SIDECAR)
+        .ok()
+        .map(|spec| spec.program)
+        .filter(|program| program.exists())
+        .or_else(|| {
+            crate::server::binary::dev_candidates()
+                .into_iter()
+                .next()
+                .map(|(path, _)| path)
+        })
+}
+
+/// Reveals the server binary in the folder it is installed in.
+#[tauri::command]
+pub fn open_server_folder(app_handle: AppHandle) -> Result<(), String> {
+    let binary = server_binary(&app_handle).ok_or("Server executable not found")?;
+    tauri_plugin_opener::reveal_item_in_dir(&binary)
+        .map_err(|e| format!("Failed to reveal {}: {}", binary.display(), e))
+}
+
+#[tauri::command]
+pub fn open_logs_folder(app_handle: AppHandle) -> Result<(), String> {
+    open_folder(&crate::log_files::logs_dir(&app_handle)?)
+}
+
+#[tauri::command]
+pub fn open_app_data_folder(app_handle: AppHandle) -> Result<(), String> {
+    let dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to get app data directory: {}", e))?;
+    open_folder(&dir)
+}
+
+/// Folders whose contents [`reveal_in_file_manager`] and
+/// [`open_with_default_app`] may open.
+fn allowed_roots(app: &AppHandle) -> Vec<PathBuf> {
+    let path = app.path();
+    let backups = app
+        .state::<ConfigStore>()
+        .get()
+        .backup
+        .folder
+        .or_else(|| path.app_data_dir().ok().map(|dir| dir.join("backups")));
+    [
+        path.app_data_dir().ok(),
+        crate::log_files::logs_dir(app).ok(),
+        backups,
+        path.download_dir().ok(),
+        path.document_dir().ok(),
+        path.desktop_dir().ok(),
+    ]
+    .into_iter()
+    .flatten()
+    .filter_map(|root| root.canonicalize().ok())
+    .collect()
+}
+
+/// `path`, if it exists under one of the allowed roots. It is returned as
+/// given, since file managers choke on the verbatim paths Windows
+/// canonicalizes to.
+fn allowed(app: &AppHandle, path: &str) -> Result<PathBuf, String> {
+    let resolved = Path::new(path)
+        .canonicalize()
+        .map_err(|e| format!("Failed to resolve {}: {}", path, e))?;
+    if !allowed_roots(app)
+        .iter()
+        .any(|root| resolved.starts_with(root))
+    {
+        return Err(format!("{} is outside the folders the app may open", path));
+    }
+    Ok(PathBuf::from(path))
+}
+
+/// Shows `path` selected in its folder.
+#[tauri::command]
+pub fn reveal_in_file_manager(app_handle: AppHandle, path: String) -> Result<(), String> {
+    let path = allowed(&app_handle, &path)?;
+    tauri_plugin_opener::reveal_item_in_dir(&path)
+        .map_err(|e| format!("Failed to reveal {}: {}", path.display(), e))
+}
+
+/// Whether `target` is a document to open rather than something to run.
+fn is_document(target: &Path) -> bool {
+    let listed = target
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| DOCUMENT_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()));
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        // Desktop environments run executable files whatever their name
+        let executable = std::fs::metadata(target)
+            .map_or(true, |metadata| metadata.permissions().mode() & 0o111 != 0);
+        listed && !executable
+    }
+    #[cfg(not(unix))]
+    listed
+}
+
+/// Opens `path` with the app the OS associates with it, or a folder in the
+/// file manager. Files other than documents are refused.
+#[tauri::command]
+pub fn open_with_default_app(app_handle: AppHandle, path: String) -> Result<(), String> {
+    let path = allowed(&app_handle, &path)?;
+    // What a link points at is what gets opened
+    let target = path.canonicalize().unwrap_or_else(|_| path.clone());
+    if !target.is_dir() && !is_document(&target) {
+        return Err(format!("Refusing to open {}", path.display()));
+    }
+    tauri_plugin_opener::open_path(&path, None::<&str>)
+        .map_err(|e| format!("Failed to open {}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::scratch;
+
+    #[test]
+    fn only_documents_are_opened() {
+        let dir = scratch("folders-documents");
+        for name in ["export.zip", "cookies.JSON", "trace.har", "app.log"] {
+            std::fs::write(dir.join(name), b"").unwrap();
+            assert!(is_document(&dir.join(name)), "{}", name);
+        }
+        for name in [
+            "setup.exe",
+            "run.sh",
+            "page.html",
+            "README",
+            "shortcut.desktop",
+        ] {
+            std::fs::write(dir.join(name), b"").unwrap();
+            assert!(!is_document(&dir.join(name)), "{}", name);
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn executable_documents_are_not_opened() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = scratch("folders-executable");
+        let file = dir.join("notes.txt");
+        std::fs::write(&file, b"#!/bin/sh\n").unwrap();
+        assert!(is_document(&file));
+        std::fs::set_permissions(&file, std::fs::Permissions::from_mode(0o755)).unwrap();
+        assert!(!is_document(&file));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,556 @@
+//! Garbage collection of what piles up on disk: the webview's cache, old
+//! downloads, rotated logs and, when turned on, profile directories the
+//! backend no longer has a profile for.
+//!
+//! Each kind has a [`GcPolicy`] in `gc` of the config; files past its age
+//! go, and then the oldest until the kind fits its size. Profile
+//! directories are never deleted, only moved to the trash folder of the
+//! data directory, and only when the backend's list looks whole, it has no
+//! record of the profile on disk and it answers 404 when asked for it by
+//! ID.
+//!
+//! A run is queued as a [`JobKind::Gc`] job every `gc.intervalHours`,
+//! though only while no profile and no other job runs, so cleaning up never
+//! competes with real work. [`preview_gc`] reports what a run would delete
+//! without deleting anything.
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use serde::Serialize;
+use tauri::{AppHandle, Manager};
+
+use crate::audit::{self, AuditAction, Initiator};
+use crate::config::{ConfigStore, GcConfig, GcPolicy};
+use crate::data_dir::{RECORDS, TRASH};
+use crate::downloads::DownloadManager;
+use crate::jobs::{JobInfo, JobKind, JobManager, JobSpec, JobState};
+use crate::log_files;
+use crate::profiles::registry::ProfileRegistry;
+use crate::server::client::{self, RequestOptions};
+use crate::server::logs::now_millis;
+
+/// How often it is checked whether a run is due.
+const CHECK_INTERVAL: Duration = Duration::from_secs(15 * 60);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum GcTarget {
+    WebviewCache,
+    Downloads,
+    Logs,
+    OrphanedProfile,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GcReason {
+    /// Older than the policy's age.
+    Age,
+    /// Among the oldest while the kind was over its size.
+    Size,
+    /// No backend profile has its ID. Moved to the trash, not deleted.
+    Orphaned,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GcItem {
+    pub target: GcTarget,
+    pub path: String,
+    pub size_bytes: u64,
+    pub reason: GcReason,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GcReport {
+    pub dry_run: bool,
+    /// What was deleted, or would be on a dry run.
+    pub items: Vec<GcItem>,
+    pub freed_bytes: u64,
+    /// What could not be looked at or deleted.
+    pub errors: Vec<String>,
+}
+
+struct File {
+    path: PathBuf,
+    size: u64,
+    modified: SystemTime,
+}
+
+fn files(dir: &Path, found: &mut Vec<File>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let Ok(kind) = entry.file_type() else {
+            continue;
+        };
+        if kind.is_dir() {
+            files(&entry.path(), found);
+        } else if kind.is_file() {
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+            found.push(File {
+                path: entry.path(),
+                size: metadata.len(),
+                modified: metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+            });
+        }
+    }
+}
+
+fn days_ago(days: u32) -> Option<SystemTime> {
+    SystemTime::now().checked_sub(Duration::from_secs(days as u64 * 86_400))
+}
+
+/// The files under `roots` that `policy` lets go, except what `keep` holds
+/// on to. Kept files still count towards the size.
+fn select(
+    target: GcTarget,
+    roots: &[PathBuf],
+    policy: GcPolicy,
+    keep: impl Fn(&Path) -> bool,
+) -> Vec<GcItem> {
+    let mut found = Vec::new();
+    for root in roots {
+        files(root, &mut found);
+    }
+    found.sort_by_key(|file| file.modified);
+
+    let cutoff = policy.max_age_days.and_then(days_ago);
+    let mut remaining: u64 = found.iter().map(|file| file.size).sum();
+    let max_size = policy.max_size_mb.map(|mb| mb * 1024 * 1024);
+    let mut items = Vec::new();
+    for file in found {
+        if keep(&file.path) {
+            continue;
+        }
+        let reason = if cutoff.is_some_and(|cutoff| file.modified < cutoff) {
+            GcReason::Age
+        } else if max_size.is_some_and(|max| remaining > max) {
+            GcReason::Size
+        } else {
+            continue;
+        };
+        remaining -= file.size;
+        items.push(GcItem {
+            target,
+            path: file.path.display().to_string(),
+            size_bytes: file.size,
+            reason,
+        });
+    }
+    items
+}
+
+/// IDs of the backend's profiles. Fails unless the backend answers with
+/// the full list, since every directory missing from it would go.
+async fn backend_profiles(app: &AppHandle) -> Result<HashSet<String>, String> {
+    let body = crate::scheduler::actions::api(app, "GET", "/api/profiles/").await?;
+    let items = body
+        .as_array()
+        .ok_or("The backend's profile list is not a plain list")?;
+    items
+        .iter()
+        .map(|item| {
+            item.get("id")
+                .and_then(|id| id.as_str())
+                .map(str::to_string)
+                .ok_or_else(|| "A backend profile has no ID".to_string())
+        })
+        .collect()
+}
+
+/// The profile directories in `dir` that neither the backend's `known`
+/// profiles nor its `records` account for. Refuses to pick any when the
+/// list cannot be the whole of it: empty, or short of half the directories,
+/// as when the backend failed to read its records.
+fn orphaned_profiles(
+    dir: &Path,
+    records: Option<&Path>,
+    known: &HashSet<String>,
+    running: &HashSet<String>,
+    grace_days: u32,
+) -> Result<Vec<GcItem>, String> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Ok(Vec::new());
+    };
+    let dirs: Vec<std::fs::DirEntry> = entries
+        .flatten()
+        .filter(|entry| entry.file_type().is_ok_and(|kind| kind.is_dir()))
+        .collect();
+    if !dirs.is_empty() && known.len() * 2 < dirs.len() {
+        return Err(format!(
+            "Skipped orphaned profiles: the backend lists {} profiles for {} profile directories",
+            known.len(),
+            dirs.len()
+        ));
+    }
+    let cutoff = days_ago(grace_days).unwrap_or(SystemTime::UNIX_EPOCH);
+    Ok(dirs
+        .into_iter()
+        .filter_map(|entry| {
+            let id = entry.file_name().to_string_lossy().into_owned();
+            let modified = entry.metadata().and_then(|m| m.modified()).ok()?;
+            let recorded = records.is_some_and(|records| records.join(&id).exists());
+            if known.contains(&id)
+                || running.contains(&id)
+                || recorded
+                || modified >= cutoff
+                || crate::profiles::validate_id(&id).is_err()
+            {
+                return None;
+            }
+            Some(GcItem {
+                target: GcTarget::OrphanedProfile,
+                size_bytes: crate::browsers::dir_size(&entry.path()),
+                path: entry.path().display().to_string(),
+                reason: GcReason::Orphaned,
+            })
+        })
+        .collect())
+}
+
+/// Keeps the orphaned profiles among `items` only if the backend answers
+/// 404 for each when asked by ID; other items pass as they are.
+async fn confirm_orphans(
+    app: &AppHandle,
+    items: Vec<GcItem>,
+    errors: &mut Vec<String>,
+) -> Vec<GcItem> {
+    let mut confirmed = Vec::with_capacity(items.len());
+    for item in items {
+        if item.target != GcTarget::OrphanedProfile {
+            confirmed.push(item);
+            continue;
+        }
+        let id = Path::new(&item.path)
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let response = client::forward(
+            app,
+            "GET",
+            &format!("/api/profiles/{}", id),
+            None,
+            HashMap::new(),
+            RequestOptions::default(),
+        )
+        .await;
+        match response {
+            Ok(response) if response.status == 404 => confirmed.push(item),
+            Ok(response) => errors.push(format!(
+                "Kept {}: the backend answered HTTP {} for profile {}",
+                item.path, response.status, id
+            )),
+            Err(e) => errors.push(format!("Kept {}: {}", item.path, e)),
+        }
+    }
+    confirmed
+}
+
+/// Everything the policies in `config` let go.
+fn plan(
+    app: &AppHandle,
+    config: &GcConfig,
+    known: Option<&HashSet<String>>,
+    errors: &mut Vec<String>,
+) -> Vec<GcItem> {
+    let data_dir = crate::data_dir::data_dir(app).ok();
+    let mut items = Vec::new();
+
+    let cache: Vec<PathBuf> = app.path().app_cache_dir().ok().into_iter().collect();
+    items.extend(select(
+        GcTarget::WebviewCache,
+        &cache,
+        config.webview_cache,
+        |_| false,
+    ));
+
+    let downloads: Vec<PathBuf> = data_dir.iter().map(|dir| dir.join("downloads")).collect();
+    let manager = app.state::<DownloadManager>();
+    items.extend(select(
+        GcTarget::Downloads,
+        &downloads,
+        config.downloads,
+        |path| manager.holds(path),
+    ));
+
+    let logs: Vec<PathBuf> = log_files::logs_dir(app).ok().into_iter().collect();
+    items.extend(select(
+        GcTarget::Logs,
+        &logs,
+        config.logs,
+        log_files::is_active,
+    ));
+
+    if let (Some(known), Some(dir)) = (known, &data_dir) {
+        let running: HashSet<String> = app
+            .state::<ProfileRegistry>()
+            .list()
+            .into_iter()
+            .map(|profile| profile.id)
+            .collect();
+        // The server only keeps its records here once it was told this place
+        let records = crate::data_dir::server_data_dir(app).map(|dir| dir.join(RECORDS));
+        match orphaned_profiles(
+            &dir.join("profiles"),
+            records.as_deref(),
+            known,
+            &running,
+            config.orphan_grace_days,
+        ) {
+            Ok(orphaned) => items.extend(orphaned),
+            Err(e) => errors.push(e),
+        }
+    }
+    items
+}
+
+/// Deletes a file, or moves a profile directory into `trash`.
+fn delete(item: &GcItem, trash: Option<&Path>) -> std::io::Result<()> {
+    let path = Path::new(&item.path);
+    if item.target != GcTarget::OrphanedProfile {
+        return std::fs::remove_file(path);
+    }
+    let (Some(trash), Some(name)) = (trash, path.file_name()) else {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            "no trash folder to move it to",
+        ));
+    };
+    std::fs::create_dir_all(trash)?;
+    let mut dest = trash.join(name);
+    dest.as_mut_os_string().push(format!("-{}", now_millis()));
+    std::fs::rename(path, dest)
+}
+
+/// Runs the garbage collector, or only reports what it would delete.
+pub async fn collect(app: &AppHandle, dry_run: bool) -> Result<GcReport, String> {
+    let config = app.state::<ConfigStore>().get().gc;
+    let mut errors = Vec::new();
+    let known = if config.orphaned_profiles {
+        match backend_profiles(app).await {
+            Ok(known) => Some(known),
+            Err(e) => {
+                errors.push(format!("Skipped orphaned profiles: {}", e));
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    let handle = app.clone();
+    let (planned, mut errors) = tauri::async_runtime::spawn_blocking(move || {
+        let planned = plan(&handle, &config, known.as_ref(), &mut errors);
+        (planned, errors)
+    })
+    .await
+    .map_err(|e| e.to_string())?;
+    let planned = confirm_orphans(app, planned, &mut errors).await;
+
+    let (items, errors) = if dry_run {
+        (planned, errors)
+    } else {
+        let trash = crate::data_dir::data_dir(app)
+            .ok()
+            .map(|dir| dir.join(TRASH));
+        tauri::async_runtime::spawn_blocking(move || {
+            let mut deleted = Vec::with_capacity(planned.len());
+            for item in planned {
+                match delete(&item, trash.as_deref()) {
+                    Ok(()) => deleted.push(item),
+                    Err(e) => errors.push(format!("Failed to delete {}: {}", item.path, e)),
+                }
+            }
+            (deleted, errors)
+        })
+        .await
+        .map_err(|e| e.to_string())?
+    };
+
+    let freed_bytes = items.iter().map(|item| item.size_bytes).sum();
+    if !dry_run {
+        for item in items
+            .iter()
+            .filter(|item| item.target == GcTarget::OrphanedProfile)
+        {
+            audit::record_ok(
+                app,
+                AuditAction::FileDelete,
+                format!("{} moved to the trash", item.path),
+            );
+        }
+        audit::record_ok(
+            app,
+            AuditAction::FileDelete,
+            format!(
+                "{} items, {} bytes, by garbage collection",
+                items.len(),
+                freed_bytes
+            ),
+        );
+        log::info!(
+            "Garbage collection deleted {} items, {} bytes",
+            items.len(),
+            freed_bytes
+        );
+    }
+    Ok(GcReport {
+        dry_run,
+        items,
+        freed_bytes,
+        errors,
+    })
+}
+
+/// Whether a scheduled run should be queued now.
+fn due(app: &AppHandle, config: &GcConfig, jobs: &[JobInfo]) -> bool {
+    let busy = jobs
+        .iter()
+        .any(|job| matches!(job.state, JobState::Queued | JobState::Running));
+    let interval = chrono::Duration::hours(config.interval_hours.max(1) as i64);
+    let recent = jobs
+        .iter()
+        .filter(|job| job.kind == JobKind::Gc)
+        .any(|job| chrono::Utc::now() - job.created_at < interval);
+    config.enabled && !busy && !recent && app.state::<ProfileRegistry>().list().is_empty()
+}
+
+/// Queues scheduled runs for the lifetime of the app.
+pub fn spawn(app: &AppHandle) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(audit::scoped(Initiator::System, async move {
+        let mut interval = tokio::time::interval(CHECK_INTERVAL);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        // Not while the app is still starting
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            let config = app.state::<ConfigStore>().get().gc;
+            let jobs = app.state::<JobManager>();
+            if due(&app, &config, &jobs.list()) {
+                if let Err(e) = jobs.enqueue(&app, JobSpec::Gc) {
+                    log::warn!("Failed to queue garbage collection: {}", e);
+                }
+            }
+        }
+    }));
+}
+
+/// Reports what garbage collection would delete now, deleting nothing.
+#[tauri::command]
+pub async fn preview_gc(app_handle: AppHandle) -> Result<GcReport, String> {
+    collect(&app_handle, true).await
+}
+
+/// Queues garbage collection right away; follow it with `job://updated`.
+#[tauri::command]
+pub fn run_gc(app_handle: AppHandle) -> Result<JobInfo, String> {
+    app_handle
+        .state::<JobManager>()
+        .enqueue(&app_handle, JobSpec::Gc)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::scratch;
+
+    fn write(path: &Path, bytes: usize, age_days: u64) {
+        std::fs::write(path, vec![0u8; bytes]).unwrap();
+        let modified = SystemTime::now() - Duration::from_secs(age_days * 86_400);
+        std::fs::File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+    }
+
+    fn names(items: &[GcItem]) -> Vec<String> {
+        items
+            .iter()
+            .map(|item| {
+                Path::new(&item.path)
+                    .file_name()
+                    .unwrap()
+                    .to_string_lossy()
+                    .into_owned()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn select_takes_old_files_then_the_oldest_until_it_fits() {
+        let dir = scratch("gc-select");
+        write(&dir.join("old"), 1024, 30);
+        write(&dir.join("older"), 1024 * 1024, 20);
+        write(&dir.join("new"), 1024 * 1024, 1);
+        write(&dir.join("newest"), 1024 * 1024, 0);
+
+        let roots = [dir.clone()];
+        let policy = GcPolicy {
+            max_age_days: Some(25),
+            max_size_mb: Some(2),
+        };
+        let items = select(GcTarget::Downloads, &roots, policy, |_| false);
+        assert_eq!(names(&items), ["old", "older"]);
+        assert_eq!(items[0].reason, GcReason::Age);
+        assert_eq!(items[1].reason, GcReason::Size);
+
+        let items = select(GcTarget::Downloads, &roots, policy, |path| {
+            path.ends_with("older")
+        });
+        assert_eq!(names(&items), ["old", "new"]);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn orphaned_profiles_skips_known_recorded_recent_and_running() {
+        let dir = scratch("gc-orphans");
+        let profiles = dir.join("profiles");
+        let records = dir.join("records");
+        for id in ["known", "recorded", "running", "recent", "orphan"] {
+            std::fs::create_dir_all(profiles.join(id)).unwrap();
+        }
+        std::fs::create_dir_all(records.join("recorded")).unwrap();
+        let known: HashSet<String> = ["known", "a", "b"].map(String::from).into();
+        let running: HashSet<String> = ["running".to_string()].into();
+
+        // Directories have no mtime setter, so a zero grace period stands in
+        let items = orphaned_profiles(&profiles, Some(&records), &known, &running, 0).unwrap();
+        let mut found = names(&items);
+        found.sort();
+        assert_eq!(found, ["orphan", "recent"]);
+        assert!(items.iter().all(|item| item.reason == GcReason::Orphaned));
+
+        let items = orphaned_profiles(&profiles, Some(&records), &known, &running, 7).unwrap();
+        assert!(items.is_empty());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn orphaned_profiles_refuses_a_short_list() {
+        let dir = scratch("gc-short-list");
+        for id in ["a", "b", "c"] {
+            std::fs::create_dir_all(dir.join(id)).unwrap();
+        }
+        assert!(orphaned_profiles(&dir, None, &HashSet::new(), &HashSet::new(), 0).is_err());
+        let known: HashSet<String> = ["a".to_string()].into();
+        assert!(orphaned_profiles(&dir, None, &known, &HashSet::new(), 0).is_err());
+        let known: HashSet<String> = ["a", "b"].map(String::from).into();
+        assert_eq!(
+            orphaned_profiles(&dir, None, &known, &HashSet::new(), 0)
+                .unwrap()
+                .len(),
+            1
+        );
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,870 @@
+//! Profiles brought over from other antidetect browsers, read from what
+//! they export.
+//!
+//! Three layouts are read: a JSON manifest listing profiles, a folder with
+//! one subfolder per profile, and a bare cookie store, which becomes a
+//! single profile. Their fields go by different names between tools, so
+//! each is looked up under the usual aliases and mapped onto what the
+//! backend's `POST /api/profiles/` takes: name, OS, locale, screen and
+//! proxy. Anything else, such as a user agent, is dropped with a warning,
+//! since the backend generates the fingerprint itself.
+//!
+//! Cookies come from `cookies.txt`, EditThisCookie or Playwright files (see
+//! [`crate::cookies`]) and Firefox `cookies.sqlite` stores, and are written
+//! into the new profile's own `cookies.sqlite`. Each profile pushed is
+//! emitted as `import://profile-result`.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rusqlite::{params, Connection, OpenFlags};
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+use tauri::{AppHandle, Emitter};
+
+use crate::cookies::{self, Cookie, SameSite};
+use crate::proxy::import::parse_line;
+use crate::proxy::{ProxyConfig, ProxyProtocol};
+use crate::server::client::{self, RequestOptions};
+
+/// Manifests larger than this are refused.
+const MAX_FILE_BYTES: u64 = 50 * 1024 * 1024;
+const MAX_PROFILES: usize = 1_000;
+/// Files in a profile folder that may describe the profile.
+const META_FILES: &[&str] = &["profile.json", "config.json", "settings.json", "meta.json"];
+/// Files in a profile folder that may hold its cookies, in order of
+/// preference.
+const COOKIE_FILES: &[&str] = &["cookies.sqlite", "cookies.json", "cookies.txt"];
+const FIREFOX_STORE: &str = "cookies.sqlite";
+/// Schema version of the Firefox cookie stores written; Firefox upgrades
+/// older ones itself.
+const FIREFOX_SCHEMA: i32 = 12;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ImportLayout {
+    Manifest,
+    Folder,
+    CookieStore,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct ScreenSize {
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportedProfile {
+    pub name: String,
+    /// `windows`, `macos` or `linux`.
+    pub os: Option<String>,
+    pub locale: Option<String>,
+    pub screen: Option<ScreenSize>,
+    pub proxy: Option<ProxyConfig>,
+    /// Not carried over; shown so the user knows what the old tool used.
+    pub user_agent: Option<String>,
+    pub cookies: Vec<Cookie>,
+    /// Where the profile was read from.
+    pub source: String,
+    pub warnings: Vec<String>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportPreview {
+    pub layout: ImportLayout,
+    pub profiles: Vec<ImportedProfile>,
+    /// Entries that could not be read at all.
+    pub invalid: Vec<String>,
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProfileImportSummary {
+    pub total: usize,
+    pub imported: usize,
+    pub cookies: usize,
+    /// Profiles the backend refused, with its reason.
+    pub failed: Vec<String>,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ResultPayload {
+    index: usize,
+    total: usize,
+    name: String,
+    ok: bool,
+    profile_id: Option<String>,
+    cookies: usize,
+    error: Option<String>,
+}
+
+/// The first of `keys` that `object` has, looking into nested objects for
+/// dotted keys.
+fn field<'a>(object: &'a Map<String, Value>, keys: &[&str]) -> Option<&'a Value> {
+    keys.iter().find_map(|key| {
+        let mut parts = key.split('.');
+        let mut value = object.get(parts.next()?)?;
+        for part in parts {
+            value = value.get(part)?;
+        }
+        Some(value).filter(|value| !value.is_null())
+    })
+}
+
+fn string<'a>(object: &'a Map<String, Value>, keys: &[&str]) -> Option<&'a str> {
+    field(object, keys)
+        .and_then(Value::as_str)
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+}
+
+fn normalize_os(os: &str) -> Option<&'static str> {
+    let os = os.to_ascii_lowercase();
+    if os.starts_with("win") {
+        Some("windows")
+    } else if os.starts_with("mac") || os.contains("darwin") || os == "osx" {
+        Some("macos")
+    } else if os.starts_with("lin") || os.contains("ubuntu") {
+        Some("linux")
+    } else {
+        None
+    }
+}
+
+fn screen_of(value: &Value) -> Option<ScreenSize> {
+    let (width, height) = match value {
+        Value::String(text) => {
+            let (width, height) = text.split_once(['x', 'X', '*', '×'])?;
+            (width.trim().parse().ok()?, height.trim().parse().ok()?)
+        }
+        Value::Object(screen) => (
+            field(screen, &["width", "w"])?.as_u64()? as u32,
+            field(screen, &["height", "h"])?.as_u64()? as u32,
+        ),
+        _ => return None,
+    };
+    (width > 0 && height > 0).then_some(ScreenSize { width, height })
+}
+
+fn proxy_of(value: &Value) -> Result<Option<ProxyConfig>, String> {
+    let proxy = match value {
+        Value::String(line) if line.trim().is_empty() => return Ok(None),
+        Value::String(line) => parse_line(line, ProxyProtocol::Http),
+        Value::Object(proxy) => {
+            let protocol = string(proxy, &["type", "protocol", "mode", "scheme"]);
+            if protocol.is_some_and(|protocol| {
+                matches!(
+                    protocol.to_ascii_lowercase().as_str(),
+                    "none" | "direct" | "noproxy"
+                )
+            }) {
+                return Ok(None);
+            }
+            let protocol = protocol
+                .and_then(ProxyProtocol::from_scheme)
+                .unwrap_or_default();
+            let host = string(proxy, &["host", "ip", "address", "server", "hostname"]);
+            let port = field(proxy, &["port"]).and_then(|port| match port {
+                Value::Number(port) => port.as_u64().and_then(|port| u16::try_from(port).ok()),
+                Value::String(port) => port.trim().parse().ok(),
+                _ => None,
+            });
+            match (host, port) {
+                (None, _) => return Ok(None),
+                // The host may be a whole proxy line of its own
+                (Some(host), None) => parse_line(host, protocol),
+                (Some(host), Some(port)) => Some(ProxyConfig {
+                    host: host.to_string(),
+                    port,
+                    protocol,
+                    username: string(proxy, &["username", "user", "login"]).map(str::to_string),
+                    password: string(proxy, &["password", "pass"]).map(str::to_string),
+                }),
+            }
+        }
+        _ => None,
+    };
+    // Not the value itself, which may hold a password
+    proxy
+        .map(Some)
+        .ok_or_else(|| "The proxy is in no known format and was left out".to_string())
+}
+
+fn cookies_of(value: &Value, warnings: &mut Vec<String>) -> Vec<Cookie> {
+    let text = match value {
+        Value::String(text) => text.clone(),
+        value => value.to_string(),
+    };
+    match cookies::parse(&text, None) {
+        Ok(import) => {
+            if !import.invalid.is_empty() {
+                warnings.push(format!("{} cookies were unreadable", import.invalid.len()));
+            }
+            import.cookies
+        }
+        Err(e) => {
+            warnings.push(format!("Cookies not imported: {}", e));
+            Vec::new()
+        }
+    }
+}
+
+/// Maps one profile as another tool describes it.
+fn profile_of(object: &Map<String, Value>, fallback_name: &str, source: &str) -> ImportedProfile {
+    let mut warnings = Vec::new();
+    let os = string(
+        object,
+        &["os", "platform", "osType", "os_type", "navigator.platform"],
+    )
+    .and_then(|os| {
+        let normalized = normalize_os(os);
+        if normalized.is_none() {
+            warnings.push(format!("Unknown OS {:?}; the backend picks one", os));
+        }
+        normalized
+    });
+    let screen = field(
+        object,
+        &[
+            "screen",
+            "resolution",
+            "screenResolution",
+            "screen_resolution",
+        ],
+    )
+    .and_then(screen_of);
+    let proxy = match field(object, &["proxy", "proxyConfig", "proxy_config"]).map(proxy_of) {
+        Some(Ok(proxy)) => proxy,
+        Some(Err(e)) => {
+            warnings.push(e);
+            None
+        }
+        None => None,
+    };
+    let user_agent = string(
+        object,
+        &["userAgent", "user_agent", "ua", "navigator.userAgent"],
+    )
+    .map(str::to_string);
+    if user_agent.is_some() {
+        warnings.push("The user agent is not carried over; Nyx generates its own".to_string());
+    }
+    let cookies = field(object, &["cookies"])
+        .map(|value| cookies_of(value, &mut warnings))
+        .unwrap_or_default();
+
+    ImportedProfile {
+        name: string(object, &["name", "profileName", "profile_name", "title"])
+            .unwrap_or(fallback_name)
+            .to_string(),
+        os: os.map(str::to_string),
+        locale: string(
+            object,
+            &["locale", "language", "lang", "navigator.language"],
+        )
+        .map(str::to_string),
+        screen,
+        proxy,
+        user_agent,
+        cookies,
+        source: source.to_string(),
+        warnings,
+    }
+}
+
+fn read_json(path: &Path) -> Result<Value, String> {
+    let metadata =
+        std::fs::metadata(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    if metadata.len() > MAX_FILE_BYTES {
+        return Err(format!(
+            "{} is over {} MB",
+            path.display(),
+            MAX_FILE_BYTES / 1024 / 1024
+        ));
+    }
+    let contents =
+        std::fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    serde_json::from_slice(&contents).map_err(|e| format!("{} is not JSON: {}", path.display(), e))
+}
+
+/// Whether a JSON document is a cookie export rather than a manifest.
+fn is_cookie_json(value: &Value) -> bool {
+    match value {
+        Value::Array(items) => items.first().is_some_and(|item| {
+            item.get("domain").is_some()
+                && item.get("name").is_some()
+                && item.get("value").is_some()
+        }),
+        Value::Object(state) => state.contains_key("cookies") && state.contains_key("origins"),
+        _ => false,
+    }
+}
+
+/// The profiles of a manifest: a list, a list under one of the usual keys,
+/// or a single profile.
+fn manifest_profiles(value: &Value, source: &str) -> Result<Vec<ImportedProfile>, String> {
+    let items = match value {
+        Value::Array(items) => items,
+        Value::Object(object) => match field(object, &["profiles", "data", "list", "items"]) {
+            Some(Value::Array(items)) => items,
+            _ => std::slice::from_ref(value),
+        },
+        _ => return Err(format!("{} lists no profiles", source)),
+    };
+    Ok(items
+        .iter()
+        .enumerate()
+        .filter_map(|(index, item)| {
+            let object = item.as_object()?;
+            Some(profile_of(
+                object,
+                &format!("Imported {}", index + 1),
+                source,
+            ))
+        })
+        .collect())
+}
+
+fn unix_now() -> f64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0.0, |since| since.as_secs_f64())
+}
+
+/// Reads a Firefox `cookies.sqlite`, leaving out expired cookies.
+fn read_firefox_store(path: &Path) -> Result<Vec<Cookie>, String> {
+    let db = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    let mut statement = db
+        .prepare(
+            "SELECT name, value, host, path, expiry, isSecure, isHttpOnly, sameSite
+             FROM moz_cookies",
+        )
+        .map_err(|e| format!("{} is not a Firefox cookie store: {}", path.display(), e))?;
+    let rows = statement
+        .query_map([], |row| {
+            let host: String = row.get(2)?;
+            let same_site: i64 = row.get(7)?;
+            Ok(Cookie {
+                name: row.get(0)?,
+                value: row.get(1)?,
+                host_only: !host.starts_with('.'),
+                domain: host,
+                path: row.get(3)?,
+                expires: Some(row.get::<_, i64>(4)? as f64),
+                secure: row.get::<_, i64>(5)? != 0,
+                http_only: row.get::<_, i64>(6)? != 0,
+                same_site: match same_site {
+                    1 => Some(SameSite::Lax),
+                    2 => Some(SameSite::Strict),
+                    _ => None,
+                },
+            })
+        })
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let now = unix_now();
+    Ok(rows
+        .flatten()
+        .filter_map(|cookie| cookie.validate().ok())
+        .filter(|cookie| cookie.expires.is_some_and(|expires| expires > now))
+        .collect())
+}
+
+fn cookie_file(path: &Path) -> Result<Vec<Cookie>, String> {
+    if path.file_name().is_some_and(|name| name == FIREFOX_STORE) {
+        read_firefox_store(path)
+    } else {
+        cookies::read_file(path, None).map(|import| import.cookies)
+    }
+}
+
+/// One subfolder of a folder export.
+fn folder_profile(dir: &Path) -> ImportedProfile {
+    let name = dir
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let source = dir.display().to_string();
+    let meta = META_FILES
+        .iter()
+        .map(|file| dir.join(file))
+        .find(|path| path.is_file());
+    let mut profile = match meta.map(|path| read_json(&path)) {
+        Some(Ok(Value::Object(object))) => profile_of(&object, &name, &source),
+        other => {
+            let mut profile = profile_of(&Map::new(), &name, &source);
+            if let Some(Err(e)) = other {
+                profile.warnings.push(e);
+            }
+            profile
+        }
+    };
+    if profile.cookies.is_empty() {
+        let file = COOKIE_FILES
+            .iter()
+            .map(|file| dir.join(file))
+            .find(|path| path.is_file());
+        match file.map(|path| cookie_file(&path)) {
+            Some(Ok(cookies)) => profile.cookies = cookies,
+            Some(Err(e)) => profile.warnings.push(e),
+            None if dir.join("Default").join("Cookies").exists() => profile.warnings.push(
+                "Chromium cookie stores are encrypted; export the cookies as JSON to bring them"
+                    .to_string(),
+            ),
+            None => {}
+        }
+    }
+    profile
+}
+
+/// Reads the export at `path` without importing anything.
+fn read(path: &Path) -> Result<ImportPreview, String> {
+    let mut invalid = Vec::new();
+    let (layout, profiles) = if path.is_dir() {
+        let entries = std::fs::read_dir(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let mut dirs: Vec<PathBuf> = entries
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.is_dir())
+            .collect();
+        dirs.sort();
+        if dirs.is_empty() {
+            // The folder of a single profile
+            (ImportLayout::Folder, vec![folder_profile(path)])
+        } else {
+            (
+                ImportLayout::Folder,
+                dirs.iter().map(|dir| folder_profile(dir)).collect(),
+            )
+        }
+    } else {
+        let name = path
+            .file_stem()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let source = path.display().to_string();
+        let is_json = path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("json"));
+        match is_json.then(|| read_json(path)).transpose()? {
+            Some(value) if !is_cookie_json(&value) => {
+                (ImportLayout::Manifest, manifest_profiles(&value, &source)?)
+            }
+            _ => {
+                let mut profile = profile_of(&Map::new(), &name, &source);
+                profile.cookies = cookie_file(path)?;
+                (ImportLayout::CookieStore, vec![profile])
+            }
+        }
+    };
+
+    let profiles: Vec<ImportedProfile> = profiles
+        .into_iter()
+        .filter(|profile| {
+            let empty = profile.name.is_empty();
+            if empty {
+                invalid.push(format!("{}: no profile name", profile.source));
+            }
+            !empty
+        })
+        .collect();
+    if profiles.is_empty() {
+        return Err(format!("Found no profiles in {}", path.display()));
+    }
+    if profiles.len() > MAX_PROFILES {
+        return Err(format!(
+            "Found {} profiles; import at most {} at a time",
+            profiles.len(),
+            MAX_PROFILES
+        ));
+    }
+    Ok(ImportPreview {
+        layout,
+        profiles,
+        invalid,
+    })
+}
+
+/// The backend's create request for `profile`.
+fn create_body(profile: &ImportedProfile) -> Value {
+    let mut config = Map::new();
+    if let Some(os) = &profile.os {
+        config.insert("os".to_string(), json!(os));
+    }
+    if let Some(locale) = &profile.locale {
+        config.insert("locale".to_string(), json!(locale));
+    }
+    if let Some(screen) = profile.screen {
+        config.insert(
+            "screen".to_string(),
+            json!({ "width": screen.width, "height": screen.height, "colorDepth": 24 }),
+        );
+    }
+    if let Some(proxy) = &profile.proxy {
+        config.insert(
+            "proxy".to_string(),
+            json!({
+                "server": proxy.to_string(),
+                "username": proxy.username,
+                "password": proxy.password,
+            }),
+        );
+    }
+    json!({ "name": profile.name, "config": config })
+}
+
+async fn create(app: &AppHandle, profile: &ImportedProfile) -> Result<String, String> {
+    let response = client::forward(
+        app,
+        "POST",
+        "/api/profiles/",
+        Some(create_body(profile)),
+        HashMap::new(),
+        RequestOptions::default(),
+    )
+    .await?;
+    if !response.ok {
+        return Err(response
+            .body
+            .get("detail")
+            .and_then(Value::as_str)
+            .map(str::to_string)
+            .unwrap_or_else(|| format!("HTTP {}", response.status)));
+    }
+    response
+        .body
+        .get("id")
+        .and_then(Value::as_str)
+        .map(str::to_string)
+        .ok_or_else(|| "The backend returned no profile ID".to_string())
+}
+
+/// Writes `cookies` into a new Firefox cookie store at `path`. Session
+/// cookies are left out, as Firefox does not keep them on disk either.
+fn write_firefox_store(path: &Path, cookies: &[Cookie]) -> Result<usize, String> {
+    if path.exists() {
+        return Err(format!("{} exists already", path.display()));
+    }
+    let mut db = Connection::open(path)
+        .map_err(|e| format!("Failed to create {}: {}", path.display(), e))?;
+    let write = |db: &mut Connection| -> rusqlite::Result<usize> {
+        db.execute_batch(
+            "CREATE TABLE moz_cookies (
+                id INTEGER PRIMARY KEY,
+                originAttributes TEXT NOT NULL DEFAULT '',
+                name TEXT,
+                value TEXT,
+                host TEXT,
+                path TEXT,
+                expiry INTEGER,
+                lastAccessed INTEGER,
+                creationTime INTEGER,
+                isSecure INTEGER,
+                isHttpOnly INTEGER,
+                inBrowserElement INTEGER DEFAULT 0,
+                sameSite INTEGER DEFAULT 0,
+                rawSameSite INTEGER DEFAULT 0,
+                schemeMap INTEGER DEFAULT 0,
+                CONSTRAINT moz_uniqueid UNIQUE (name, host, path, originAttributes)
+            );",
+        )?;
+        db.pragma_update(None, "user_version", FIREFOX_SCHEMA)?;
+        let now = (unix_now() * 1_000_000.0) as i64;
+        let tx = db.transaction()?;
+        let mut written = 0;
+        for cookie in cookies {
+            let Some(expires) = cookie.expires else {
+                continue;
+            };
+            let host = if cookie.host_only {
+                cookie.domain.clone()
+            } else {
+                format!(".{}", cookie.domain)
+            };
+            let same_site = match cookie.same_site {
+                Some(SameSite::Lax) => 1,
+                Some(SameSite::Strict) => 2,
+                _ => 0,
+            };
+            // Secure cookies come from https, everything else is taken as http
+            let scheme_map = if cookie.secure { 2 } else { 1 };
+            written += tx.execute(
+                "INSERT OR REPLACE INTO moz_cookies
+                 (name, value, host, path, expiry, lastAccessed, creationTime, isSecure,
+                  isHttpOnly, sameSite, rawSameSite, schemeMap)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?6, ?7, ?8, ?9, ?9, ?10)",
+                params![
+                    cookie.name,
+                    cookie.value,
+                    host,
+                    cookie.path,
+                    expires as i64,
+                    now,
+                    cookie.secure,
+                    cookie.http_only,
+                    same_site,
+                    scheme_map,
+                ],
+            )?;
+        }
+        tx.commit()?;
+        Ok(written)
+    };
+    write(&mut db).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+/// Reads the export at `path`, a file or a folder, and shows what an import
+/// would bring in.
+#[tauri::command]
+pub async fn preview_profile_import(path: PathBuf) -> Result<ImportPreview, String> {
+    tauri::async_runtime::spawn_blocking(move || read(&path))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+/// Imports the profiles of the export at `path` into the backend, or only
+/// those named in `names`, each with its cookies.
+#[tauri::command]
+pub async fn import_profiles(
+    app_handle: AppHandle,
+    path: PathBuf,
+    names: Option<Vec<String>>,
+) -> Result<ProfileImportSummary, String> {
+    let preview = preview_profile_import(path).await?;
+    let profiles: Vec<ImportedProfile> = preview
+        .profiles
+        .into_iter()
+        .filter(|profile| {
+            names
+                .as_ref()
+                .map_or(true, |names| names.contains(&profile.name))
+        })
+        .collect();
+    let total = profiles.len();
+    let mut summary = ProfileImportSummary {
+        total,
+        ..Default::default()
+    };
+
+    for (index, profile) in profiles.into_iter().enumerate() {
+        let outcome = match create(&app_handle, &profile).await {
+            Ok(id) if profile.cookies.is_empty() => Ok((id, 0, None)),
+            Ok(id) => {
+                let app = app_handle.clone();
+                let target = id.clone();
+                let cookies = profile.cookies;
+                // The ID comes from the backend, so it is checked like any other
+                let written = tauri::async_runtime::spawn_blocking(move || {
+                    let dir = crate::profiles::profile_dir(&app, &target)?;
+                    write_firefox_store(&dir.join(FIREFOX_STORE), &cookies)
+                })
+                .await
+                .map_err(|e| e.to_string())
+                .and_then(|result| result);
+                // The profile exists either way, so a cookie failure is not its failure
+                match written {
+                    Ok(written) => Ok((id, written, None)),
+                    Err(e) => Ok((id, 0, Some(e))),
+                }
+            }
+            Err(e) => Err(e),
+        };
+        let payload = match outcome {
+            Ok((id, cookies, error)) => {
+                summary.imported += 1;
+                summary.cookies += cookies;
+                if let Some(error) = &error {
+                    log::warn!("Imported {} without its cookies: {}", profile.name, error);
+                }
+                ResultPayload {
+                    index,
+                    total,
+                    name: profile.name,
+                    ok: true,
+                    profile_id: Some(id),
+                    cookies,
+                    error,
+                }
+            }
+            Err(e) => {
+                summary.failed.push(format!("{}: {}", profile.name, e));
+                ResultPayload {
+                    index,
+                    total,
+                    name: profile.name,
+                    ok: false,
+                    profile_id: None,
+                    cookies: 0,
+                    error: Some(e),
+                }
+            }
+        };
+        let _ = app_handle.emit("import://profile-result", payload);
+    }
+
+    log::info!(
+        "Imported {} of {} profiles with {} cookies",
+        summary.imported,
+        summary.total,
+        summary.cookies
+    );
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::scratch;
+
+    fn cookie(name: &str, expires: Option<f64>) -> Cookie {
+        Cookie {
+            name: name.to_string(),
+            value: "1".to_string(),
+            domain: "example.com".to_string(),
+            path: "/".to_string(),
+            expires,
+            secure: true,
+            http_only: true,
+            same_site: Some(SameSite::Strict),
+            host_only: false,
+        }
+    }
+
+    #[test]
+    fn manifest_fields_are_read_under_their_aliases() {
+        let manifest = json!({
+            "data": [
+                {
+                    "profileName": "Shop",
+                    "platform": "Win32",
+                    "navigator": { "language": "de-DE" },
+                    "screenResolution": "1920 x 1080",
+                    "proxy": { "type": "socks5", "ip": "10.0.0.1", "port": "1080", "user": "u", "pass": "p" },
+                    "ua": "Mozilla/5.0",
+                },
+                {
+                    "os": "BeOS",
+                    "screen": { "w": 1280, "h": 720 },
+                    "proxy": { "mode": "direct" },
+                },
+                {
+                    "name": "Mail",
+                    "proxy_config": "http://10.0.0.2:8080",
+                },
+                { "name": "Broken", "proxy": "not a proxy" },
+                "not an object",
+            ]
+        });
+        let profiles = manifest_profiles(&manifest, "manifest.json").unwrap();
+        assert_eq!(profiles.len(), 4);
+
+        let shop = &profiles[0];
+        assert_eq!(shop.name, "Shop");
+        assert_eq!(shop.os.as_deref(), Some("windows"));
+        assert_eq!(shop.locale.as_deref(), Some("de-DE"));
+        assert_eq!(
+            shop.screen,
+            Some(ScreenSize {
+                width: 1920,
+                height: 1080
+            })
+        );
+        assert_eq!(
+            shop.proxy,
+            Some(ProxyConfig {
+                host: "10.0.0.1".to_string(),
+                port: 1080,
+                protocol: ProxyProtocol::Socks5,
+                username: Some("u".to_string()),
+                password: Some("p".to_string()),
+            })
+        );
+        assert_eq!(shop.warnings.len(), 1, "{:?}", shop.warnings);
+
+        let unnamed = &profiles[1];
+        assert_eq!(unnamed.name, "Imported 2");
+        assert_eq!(unnamed.os, None);
+        assert_eq!(unnamed.proxy, None);
+        assert_eq!(unnamed.screen.map(|screen| screen.width), Some(1280));
+        assert_eq!(unnamed.warnings.len(), 1, "{:?}", unnamed.warnings);
+
+        assert_eq!(
+            profiles[2]
+                .proxy
+                .as_ref()
+                .map(ToString::to_string)
+                .as_deref(),
+            Some("http://10.0.0.2:8080")
+        );
+        // The warning must not repeat the value, which may hold a password
+        assert_eq!(profiles[3].proxy, None);
+        assert!(!profiles[3].warnings[0].contains("not a proxy"));
+    }
+
+    #[test]
+    fn cookie_exports_are_told_from_manifests() {
+        assert!(is_cookie_json(
+            &json!([{ "domain": "example.com", "name": "a", "value": "1" }])
+        ));
+        assert!(is_cookie_json(&json!({ "cookies": [], "origins": [] })));
+        assert!(!is_cookie_json(&json!([{ "name": "Shop", "os": "linux" }])));
+        assert!(!is_cookie_json(&json!({ "profiles": [] })));
+    }
+
+    #[test]
+    fn folders_are_read_one_profile_per_subfolder() {
+        let dir = scratch("importers-folders");
+        std::fs::create_dir_all(dir.join("alpha")).unwrap();
+        std::fs::write(
+            dir.join("alpha").join("profile.json"),
+            r#"{"os": "macOS", "locale": "en-GB"}"#,
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("alpha").join("cookies.txt"),
+            "example.com\tFALSE\t/\tFALSE\t4102444800\tsid\tabc\n",
+        )
+        .unwrap();
+        std::fs::create_dir_all(dir.join("beta").join("Default")).unwrap();
+        std::fs::write(dir.join("beta").join("Default").join("Cookies"), b"").unwrap();
+
+        let preview = read(&dir).unwrap();
+        assert_eq!(preview.layout, ImportLayout::Folder);
+        let [alpha, beta] = &preview.profiles[..] else {
+            panic!("expected two profiles, got {:?}", preview.profiles);
+        };
+        assert_eq!(alpha.name, "alpha");
+        assert_eq!(alpha.os.as_deref(), Some("macos"));
+        assert_eq!(alpha.cookies.len(), 1);
+        assert_eq!(beta.name, "beta");
+        assert!(beta.cookies.is_empty());
+        assert_eq!(beta.warnings.len(), 1, "{:?}", beta.warnings);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn firefox_stores_read_back_what_was_written() {
+        let dir = scratch("importers-firefox");
+        let store = dir.join(FIREFOX_STORE);
+        let cookies = [
+            cookie("kept", Some(4102444800.0)),
+            cookie("session", None),
+            cookie("expired", Some(1000.0)),
+        ];
+        assert_eq!(write_firefox_store(&store, &cookies).unwrap(), 2);
+        assert!(write_firefox_store(&store, &cookies).is_err());
+        assert_eq!(read_firefox_store(&store).unwrap(), [cookies[0].clone()]);
+
+        let preview = read(&store).unwrap();
+        assert_eq!(preview.layout, ImportLayout::CookieStore);
+        assert_eq!(preview.profiles[0].name, "cookies");
+        assert_eq!(preview.profiles[0].cookies.len(), 1);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
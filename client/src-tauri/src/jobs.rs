@@ -0,0 +1,524 @@
+//! One place to run long operations from: bulk proxy checks, downloads,
+//! backups and garbage collection are queued as jobs, run with a concurrency limit per kind, and
+//! can be cancelled while queued or running.
+//!
+//! Every change to a job, progress included, is emitted as `job://updated`
+//! with its [`JobInfo`]. Jobs are kept in `jobs.json` in app data, so
+//! finished ones are still listed after a restart; jobs the app quit in the
+//! middle of come back as failed. Jobs that need the internet wait in the
+//! queue while [`crate::network`] reports it as offline.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tauri::{AppHandle, Emitter, Manager};
+use tokio::sync::{watch, Semaphore};
+
+use crate::audit;
+use crate::downloads::{DownloadManager, DownloadState};
+use crate::network::{Connectivity, NetworkState};
+use crate::proxy::check::{self, DEFAULT_CHECK_TIMEOUT, DEFAULT_CONCURRENCY};
+use crate::proxy::pool::ProxyPool;
+use crate::proxy::ProxyConfig;
+
+const JOBS_FILE: &str = "jobs.json";
+/// Finished jobs kept, newest first.
+const MAX_FINISHED: usize = 200;
+/// How often a download job reads its download's progress.
+const DOWNLOAD_POLL: Duration = Duration::from_millis(500);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum JobKind {
+    ProxyCheck,
+    Download,
+    Backup,
+    Gc,
+}
+
+impl JobKind {
+    const ALL: [Self; 4] = [Self::ProxyCheck, Self::Download, Self::Backup, Self::Gc];
+
+    /// Jobs of this kind running at the same time.
+    fn concurrency(self) -> usize {
+        match self {
+            Self::ProxyCheck => 2,
+            Self::Download => 3,
+            Self::Backup => 1,
+            Self::Gc => 1,
+        }
+    }
+
+    fn needs_network(self) -> bool {
+        matches!(self, Self::ProxyCheck | Self::Download)
+    }
+}
+
+/// What a job does.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum JobSpec {
+    #[serde(rename_all = "camelCase")]
+    ProxyCheck {
+        configs: Vec<ProxyConfig>,
+        timeout_ms: Option<u64>,
+        concurrency: Option<usize>,
+    },
+    #[serde(rename_all = "camelCase")]
+    Download {
+        url: String,
+        dest: PathBuf,
+        sha256: Option<String>,
+    },
+    #[serde(rename_all = "camelCase")]
+    Backup {
+        dest: PathBuf,
+        password: Option<String>,
+    },
+    /// Prunes storage by the policies of [`crate::gc`].
+    Gc,
+}
+
+impl JobSpec {
+    fn kind(&self) -> JobKind {
+        match self {
+            Self::ProxyCheck { .. } => JobKind::ProxyCheck,
+            Self::Download { .. } => JobKind::Download,
+            Self::Backup { .. } => JobKind::Backup,
+            Self::Gc => JobKind::Gc,
+        }
+    }
+
+    /// What the job list shows for it.
+    fn label(&self) -> String {
+        match self {
+            Self::ProxyCheck { configs, .. } => format!("Check {} proxies", configs.len()),
+            Self::Download { dest, .. } => format!("Download {}", dest.display()),
+            Self::Backup { dest, .. } => format!("Back up to {}", dest.display()),
+            Self::Gc => "Clean up storage".to_string(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobState {
+    Queued,
+    Running,
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+impl JobState {
+    fn is_finished(self) -> bool {
+        matches!(self, Self::Completed | Self::Failed | Self::Cancelled)
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JobProgress {
+    pub done: u64,
+    /// `None` while the amount of work is not known.
+    pub total: Option<u64>,
+    pub message: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JobInfo {
+    pub id: String,
+    pub kind: JobKind,
+    pub label: String,
+    pub state: JobState,
+    pub progress: JobProgress,
+    pub created_at: DateTime<Utc>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub finished_at: Option<DateTime<Utc>>,
+    /// What a completed job produced, shaped by its kind.
+    pub result: Option<Value>,
+    pub error: Option<String>,
+}
+
+struct Job {
+    info: JobInfo,
+    cancel: watch::Sender<bool>,
+}
+
+/// Managed state holding the jobs of this and earlier sessions.
+pub struct JobManager {
+    path: Option<PathBuf>,
+    jobs: Mutex<HashMap<String, Job>>,
+    slots: HashMap<JobKind, Arc<Semaphore>>,
+}
+
+fn new_id() -> String {
+    let mut bytes = [0u8; 8];
+    rand::rngs::OsRng.fill_bytes(&mut bytes);
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+impl JobManager {
+    pub fn load(app: &AppHandle) -> Self {
+        let path = app
+            .path()
+            .app_data_dir()
+            .ok()
+            .map(|dir| dir.join(JOBS_FILE));
+        let saved: Vec<JobInfo> = path
+            .as_deref()
+            .and_then(|path| std::fs::read(path).ok())
+            .and_then(|contents| match serde_json::from_slice(&contents) {
+                Ok(jobs) => Some(jobs),
+                Err(e) => {
+                    log::warn!("Ignoring unreadable {}: {}", JOBS_FILE, e);
+                    None
+                }
+            })
+            .unwrap_or_default();
+        let jobs = saved
+            .into_iter()
+            .map(|mut info| {
+                if !info.state.is_finished() {
+                    info.state = JobState::Failed;
+                    info.error = Some("The app quit before the job finished".to_string());
+                    info.finished_at = Some(Utc::now());
+                }
+                let (cancel, _) = watch::channel(false);
+                (info.id.clone(), Job { info, cancel })
+            })
+            .collect();
+        Self {
+            path,
+            jobs: Mutex::new(jobs),
+            slots: JobKind::ALL
+                .iter()
+                .map(|kind| (*kind, Arc::new(Semaphore::new(kind.concurrency()))))
+                .collect(),
+        }
+    }
+
+    pub fn list(&self) -> Vec<JobInfo> {
+        let mut list: Vec<JobInfo> = self
+            .jobs
+            .lock()
+            .unwrap()
+            .values()
+            .map(|job| job.info.clone())
+            .collect();
+        list.sort_by_key(|info| std::cmp::Reverse(info.created_at));
+        list
+    }
+
+    /// Writes every job, dropping the oldest finished ones past
+    /// [`MAX_FINISHED`].
+    fn save(&self, jobs: &mut HashMap<String, Job>) {
+        let mut finished: Vec<(DateTime<Utc>, String)> = jobs
+            .values()
+            .filter(|job| job.info.state.is_finished())
+            .map(|job| (job.info.created_at, job.info.id.clone()))
+            .collect();
+        finished.sort_by_key(|(created_at, _)| std::cmp::Reverse(*created_at));
+        for (_, id) in finished.into_iter().skip(MAX_FINISHED) {
+            jobs.remove(&id);
+        }
+        let Some(path) = &self.path else {
+            return;
+        };
+        let infos: Vec<&JobInfo> = jobs.values().map(|job| &job.info).collect();
+        let result = serde_json::to_vec_pretty(&infos)
+            .map_err(|e| e.to_string())
+            .and_then(|json| {
+                let partial = path.with_extension("json.part");
+                std::fs::write(&partial, json)
+                    .and_then(|()| std::fs::rename(&partial, path))
+                    .map_err(|e| e.to_string())
+            });
+        if let Err(e) = result {
+            log::warn!("Failed to save {}: {}", path.display(), e);
+        }
+    }
+
+    /// Changes a job and announces it. State changes are saved; progress
+    /// alone is not.
+    fn update(&self, app: &AppHandle, id: &str, change: impl FnOnce(&mut JobInfo)) {
+        let mut jobs = self.jobs.lock().unwrap();
+        let Some(job) = jobs.get_mut(id) else {
+            return;
+        };
+        let state = job.info.state;
+        change(&mut job.info);
+        let info = job.info.clone();
+        if info.state != state {
+            self.save(&mut jobs);
+        }
+        drop(jobs);
+        let _ = app.emit("job://updated", info);
+    }
+
+    fn progress(&self, app: &AppHandle, id: &str, progress: JobProgress) {
+        self.update(app, id, |info| info.progress = progress);
+    }
+
+    fn finish(&self, app: &AppHandle, id: &str, outcome: Option<Result<Value, String>>) {
+        self.update(app, id, |info| {
+            info.finished_at = Some(Utc::now());
+            match outcome {
+                Some(Ok(result)) => {
+                    info.state = JobState::Completed;
+                    info.result = Some(result);
+                }
+                Some(Err(error)) => {
+                    info.state = JobState::Failed;
+                    info.error = Some(error);
+                }
+                None => info.state = JobState::Cancelled,
+            }
+        });
+    }
+
+    pub fn enqueue(&self, app: &AppHandle, spec: JobSpec) -> Result<JobInfo, String> {
+        validate(&spec)?;
+        let info = JobInfo {
+            id: new_id(),
+            kind: spec.kind(),
+            label: spec.label(),
+            state: JobState::Queued,
+            progress: JobProgress::default(),
+            created_at: Utc::now(),
+            started_at: None,
+            finished_at: None,
+            result: None,
+            error: None,
+        };
+        let (cancel, cancelled) = watch::channel(false);
+        {
+            let mut jobs = self.jobs.lock().unwrap();
+            jobs.insert(
+                info.id.clone(),
+                Job {
+                    info: info.clone(),
+                    cancel,
+                },
+            );
+            self.save(&mut jobs);
+        }
+        log::info!("Queued job {}: {}", info.id, info.label);
+        let _ = app.emit("job://updated", info.clone());
+        let slots = self.slots[&info.kind].clone();
+        // Runs on behalf of whoever queued it
+        tauri::async_runtime::spawn(audit::scoped(
+            audit::initiator(),
+            run(app.clone(), info.id.clone(), spec, slots, cancelled),
+        ));
+        Ok(info)
+    }
+
+    /// Asks a queued or running job to stop. Returns `false` if it had
+    /// already finished.
+    pub fn cancel(&self, id: &str) -> Result<bool, String> {
+        let jobs = self.jobs.lock().unwrap();
+        let job = jobs.get(id).ok_or_else(|| format!("No job {}", id))?;
+        if job.info.state.is_finished() {
+            return Ok(false);
+        }
+        job.cancel.send_replace(true);
+        Ok(true)
+    }
+}
+
+fn validate(spec: &JobSpec) -> Result<(), String> {
+    match spec {
+        JobSpec::ProxyCheck { configs, .. } if configs.is_empty() => {
+            Err("No proxies to check".to_string())
+        }
+        JobSpec::Download { url, .. } => reqwest::Url::parse(url)
+            .map(|_| ())
+            .map_err(|e| format!("Invalid download URL {}: {}", url, e)),
+        _ => Ok(()),
+    }
+}
+
+async fn run(
+    app: AppHandle,
+    id: String,
+    spec: JobSpec,
+    slots: Arc<Semaphore>,
+    mut cancelled: watch::Receiver<bool>,
+) {
+    let manager = app.state::<JobManager>();
+    let kind = spec.kind();
+    if kind.needs_network() {
+        let connectivity = app.state::<Connectivity>();
+        if connectivity.state() == NetworkState::Offline {
+            manager.progress(
+                &app,
+                &id,
+                JobProgress {
+                    message: Some("Waiting for the network".to_string()),
+                    ..JobProgress::default()
+                },
+            );
+        }
+        tokio::select! {
+            _ = connectivity.wait_online() => {}
+            _ = cancelled.wait_for(|cancelled| *cancelled) => {
+                manager.finish(&app, &id, None);
+                return;
+            }
+        }
+    }
+    let permit = tokio::select! {
+        permit = slots.acquire_owned() => permit.ok(),
+        _ = cancelled.wait_for(|cancelled| *cancelled) => None,
+    };
+    let Some(_permit) = permit else {
+        manager.finish(&app, &id, None);
+        return;
+    };
+    manager.update(&app, &id, |info| {
+        info.state = JobState::Running;
+        info.started_at = Some(Utc::now());
+    });
+    // Dropping the work on cancel stops it at its next await
+    let outcome = tokio::select! {
+        result = work(&app, &id, spec) => Some(result),
+        _ = cancelled.wait_for(|cancelled| *cancelled) => None,
+    };
+    match &outcome {
+        Some(Ok(_)) => log::info!("Job {} completed", id),
+        Some(Err(e)) => log::warn!("Job {} failed: {}", id, e),
+        None => log::info!("Job {} cancelled", id),
+    }
+    manager.finish(&app, &id, outcome);
+}
+
+/// Cancels the download behind a download job unless it was disarmed.
+struct CancelDownload<'a> {
+    app: &'a AppHandle,
+    id: Option<String>,
+}
+
+impl Drop for CancelDownload<'_> {
+    fn drop(&mut self) {
+        if let Some(id) = &self.id {
+            let _ = self.app.state::<DownloadManager>().cancel(self.app, id);
+        }
+    }
+}
+
+async fn download(
+    app: &AppHandle,
+    job: &str,
+    url: String,
+    dest: PathBuf,
+    sha256: Option<String>,
+) -> Result<Value, String> {
+    let downloads = app.state::<DownloadManager>();
+    let download = downloads.start(app, url, dest, sha256)?;
+    let mut guard = CancelDownload {
+        app,
+        id: Some(download.id.clone()),
+    };
+    let wait = downloads.wait(&download.id);
+    tokio::pin!(wait);
+    let mut poll = tokio::time::interval(DOWNLOAD_POLL);
+    let path = loop {
+        tokio::select! {
+            path = &mut wait => break path?,
+            _ = poll.tick() => {
+                if let Some(info) = downloads.list().into_iter().find(|info| info.id == download.id) {
+                    let message = (info.state == DownloadState::Verifying)
+                        .then(|| "Verifying".to_string());
+                    app.state::<JobManager>().progress(app, job, JobProgress {
+                        done: info.received,
+                        total: info.total,
+                        message,
+                    });
+                }
+            }
+        }
+    };
+    guard.id = None;
+    Ok(json!({ "path": path }))
+}
+
+async fn work(app: &AppHandle, id: &str, spec: JobSpec) -> Result<Value, String> {
+    let manager = app.state::<JobManager>();
+    match spec {
+        JobSpec::ProxyCheck {
+            configs,
+            timeout_ms,
+            concurrency,
+        } => {
+            let total = configs.len() as u64;
+            manager.progress(
+                app,
+                id,
+                JobProgress {
+                    done: 0,
+                    total: Some(total),
+                    message: None,
+                },
+            );
+            let mut done = 0;
+            let results = check::check_all(
+                configs.clone(),
+                timeout_ms
+                    .map(Duration::from_millis)
+                    .unwrap_or(DEFAULT_CHECK_TIMEOUT),
+                concurrency.unwrap_or(DEFAULT_CONCURRENCY),
+                |_, result| {
+                    done += 1;
+                    manager.progress(
+                        app,
+                        id,
+                        JobProgress {
+                            done,
+                            total: Some(total),
+                            message: Some(result.proxy.clone()),
+                        },
+                    );
+                },
+            )
+            .await;
+            app.state::<ProxyPool>().observe(app, &configs, &results);
+            serde_json::to_value(results).map_err(|e| e.to_string())
+        }
+        JobSpec::Download { url, dest, sha256 } => download(app, id, url, dest, sha256).await,
+        JobSpec::Backup { dest, password } => {
+            let summary = crate::backup::create(app, dest, password).await?;
+            serde_json::to_value(summary).map_err(|e| e.to_string())
+        }
+        JobSpec::Gc => {
+            let report = crate::gc::collect(app, false).await?;
+            serde_json::to_value(report).map_err(|e| e.to_string())
+        }
+    }
+}
+
+/// Queues a job and returns it right away; follow it with `job://updated`.
+#[tauri::command]
+pub fn enqueue_job(app_handle: AppHandle, spec: JobSpec) -> Result<JobInfo, String> {
+    app_handle.state::<JobManager>().enqueue(&app_handle, spec)
+}
+
+/// Cancels a queued or running job. Returns `false` if it had already
+/// finished.
+#[tauri::command]
+pub fn cancel_job(jobs: tauri::State<'_, JobManager>, job_id: String) -> Result<bool, String> {
+    jobs.cancel(&job_id)
+}
+
+/// Every job, newest first, including finished ones from earlier sessions.
+#[tauri::command]
+pub fn get_jobs(jobs: tauri::State<'_, JobManager>) -> Vec<JobInfo> {
+    jobs.list()
+}
@@ -1,54 +1,111 @@
-use std::process::{Command, Stdio};
-use std::path::PathBuf;
-use tauri::Manager;
+use std::time::Duration;
+use serde::Serialize;
+use tauri::{DragDropEvent, Emitter, Manager, RunEvent, WindowEvent};
+use tauri::webview::PageLoadEvent;
+
+mod archive;
+mod assets;
+mod audit;
+mod autostart;
+mod backup;
+mod bandwidth;
+mod boot;
+mod browsers;
+mod cache;
+mod cli;
+mod clipboard;
+mod config;
+mod control_api;
+mod cookies;
+mod crash;
+mod data_dir;
+mod deep_link;
+mod diagnostics;
+mod downloads;
+mod exports;
+mod file_drop;
+mod fingerprints;
+mod folders;
+mod gc;
+mod importers;
+mod jobs;
+mod log_files;
+mod logging;
+mod network;
+mod notifications;
+mod onboarding;
+mod permissions;
+mod power;
+mod profiles;
+mod proxy;
+mod safe_mode;
+mod scheduler;
+mod secrets;
+mod server;
+mod settings;
+mod shortcuts;
+mod startup;
+mod storage;
+mod system_info;
+mod telemetry;
+#[cfg(test)]
+mod test_support;
+mod tray;
+mod updater;
+mod widget;
+mod window_state;
+mod windows;
+
+use boot::{BootPhase, BootState};
+use config::ConfigStore;
+use downloads::DownloadManager;
+use log_files::{RotatingFile, SERVER_LOG};
+use onboarding::Onboarding;
+use profiles::registry::ProfileRegistry;
+use server::auth::ApiToken;
+use server::client::ApiClient;
+use server::logs::ServerLogs;
+use server::metrics::ServerMetrics;
+use server::port::PortManager;
+use server::status::{ServerState, ServerStatus};
+use server::supervisor::{
+    ServerSupervisor, Started, DEFAULT_SHUTDOWN_GRACE, DEFAULT_STARTUP_TIMEOUT,
+};
+use settings::SettingsStore;
+
+/// Name of the server binary in `bundle.externalBin`.
+const SERVER_SIDECAR: &str = "nyx-server";
+/// Progress of long file operations is emitted at most this often.
+const PROGRESS_INTERVAL: std::time::Duration = std::time::Duration::from_millis(250);
 
 #[tauri::command]
-async fn check_server_health() -> Result<bool, String> {
-    let client = reqwest::Client::new();
-    match client.get("http://localhost:8080/health")
-        .timeout(std::time::Duration::from_secs(5))
-        .send()
-        .await
-    {
-        Ok(response) => Ok(response.status().is_success()),
-        Err(_) => Ok(false),
-    }
+async fn check_server_health(app_handle: tauri::AppHandle) -> Result<bool, String> {
+    let url = server::health::health_url(&app_handle);
+    Ok(server::health::probe(&app_handle, &url, Duration::from_secs(5)).await)
 }
 
 #[tauri::command]
-async fn start_server() -> Result<String, String> {
-    // Try to find the server executable
-    let server_paths = vec![
-        "../server/main.py",
-        "./server/main.py",
-        "../server/dist/main.exe",
-        "./server/dist/main.exe",
-        "server.exe"
-    ];
-
-    for path in server_paths {
-        let path_buf = PathBuf::from(path);
-        if path_buf.exists() {
-            let result = if path.ends_with(".py") {
-                // Run Python script
-                Command::new("python")
-                    .arg(path)
-                    .current_dir("../server")
-                    .stdout(Stdio::null())
-                    .stderr(Stdio::null())
-                    .spawn()
-            } else {
-                // Run executable
-                Command::new(path)
-                    .stdout(Stdio::null())
-                    .stderr(Stdio::null())
-                    .spawn()
-            };
-
-            match result {
-                Ok(_) => return Ok(format!("Server started from {}", path)),
-                Err(e) => log::warn!("Failed to start server from {}: {}", path, e),
+fn get_server_base_url(app_handle: tauri::AppHandle) -> String {
+    server::connection::frontend_base_url(&app_handle)
+}
+
+#[tauri::command]
+async fn start_server(
+    app_handle: tauri::AppHandle,
+    supervisor: tauri::State<'_, ServerSupervisor>,
+) -> Result<String, String> {
+    // Look for a frozen build or the script in a source checkout
+    for (path, spec) in server::binary::dev_candidates() {
+        boot::report(&app_handle, BootPhase::Spawning, Some(path.display().to_string()));
+        match supervisor.start(&app_handle, spec, Some(startup_timeout(&app_handle))).await {
+            Ok(started) => {
+                return Ok(format!(
+                    "Server started from {} in {} ms",
+                    path.display(),
+                    started.startup_ms.unwrap_or_default()
+                ))
             }
+            Err(e) => log::warn!("Failed to start server from {}: {}", path.display(), e),
         }
     }
 
@@ -56,136 +113,610 @@ async fn start_server() -> Result<String, String> {
 }
 
 #[tauri::command]
-async fn open_server_folder() -> Result<(), String> {
-    // Shell functionality temporarily disabled for build compatibility
-    // TODO: Implement using tauri-plugin-shell in Tauri 2.x
-    log::info!("Open server folder requested (functionality disabled)");
-    Ok(())
+async fn stop_server(
+    app_handle: tauri::AppHandle,
+    supervisor: tauri::State<'_, ServerSupervisor>,
+    grace_ms: Option<u64>,
+) -> Result<bool, String> {
+    let grace = grace_ms.map(Duration::from_millis).unwrap_or(DEFAULT_SHUTDOWN_GRACE);
+    let stopped = supervisor.stop(grace).await;
+    if stopped {
+        audit::record_ok(&app_handle, audit::AuditAction::ProcessKill, "server");
+    }
+    Ok(stopped)
+}
+
+/// How long a starting server gets to answer its health check.
+fn startup_timeout(app_handle: &tauri::AppHandle) -> Duration {
+    app_handle
+        .state::<ConfigStore>()
+        .get()
+        .server
+        .startup_timeout_ms
+        .map(Duration::from_millis)
+        .unwrap_or(DEFAULT_STARTUP_TIMEOUT)
 }
 
 #[tauri::command]
-async fn start_embedded_server(app_handle: tauri::AppHandle) -> Result<(), String> {
+async fn start_embedded_server(
+    app_handle: tauri::AppHandle,
+    supervisor: tauri::State<'_, ServerSupervisor>,
+) -> Result<Started, String> {
     log::info!("Starting embedded server...");
 
-    // Get the resource path for the server executable
-    let resource_path = app_handle
-        .path()
-        .resource_dir()
-        .map_err(|e| format!("Failed to get resource directory: {}", e))?
-        .join("nyx-server.exe");
+    // The server binary is bundled as a sidecar (see `externalBin`)
+    let spec = server::binary::bundled(&app_handle, SERVER_SIDECAR)?;
+    boot::report(&app_handle, BootPhase::Spawning, Some(spec.program.display().to_string()));
 
-    if !resource_path.exists() {
-        return Err("Server executable not found in resources".to_string());
-    }
-
-    // Start the server process with better error handling
-    match Command::new(&resource_path)
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .spawn()
+    // Resolves once the server answers its health check
+    match supervisor
+        .start(&app_handle, spec, Some(startup_timeout(&app_handle)))
+        .await
     {
-        Ok(mut child) => {
-            log::info!("Server process started, waiting for startup...");
-
-            // Give the server time to start
-            tokio::time::sleep(tokio::time::Duration::from_secs(3)).await;
-
-            // Check if the process is still running
-            match child.try_wait() {
-                Ok(Some(status)) => {
-                    log::error!("Server process exited early with status: {}", status);
-                    Err(format!("Server process exited early with status: {}", status))
-                }
-                Ok(None) => {
-                    log::info!("Server started successfully and is running");
-                    Ok(())
-                }
-                Err(e) => {
-                    log::error!("Error checking server process: {}", e);
-                    Err(format!("Error checking server process: {}", e))
-                }
-            }
+        Ok(started) => {
+            log::info!(
+                "Server started successfully and is running (pid {}, ready in {} ms)",
+                started.pid,
+                started.startup_ms.unwrap_or_default()
+            );
+            Ok(started)
         }
         Err(e) => {
-            log::error!("Failed to start server: {}", e);
-            Err(format!("Failed to start server: {}", e))
+            log::error!("{}", e);
+            Err(e)
         }
     }
 }
 
 #[tauri::command]
-async fn wait_for_server_ready() -> Result<bool, String> {
+async fn wait_for_server_ready(
+    app_handle: tauri::AppHandle,
+    status: tauri::State<'_, ServerStatus>,
+) -> Result<bool, String> {
     log::info!("Waiting for server to be ready...");
 
+    // Resolves as soon as the supervisor sees the server become healthy; the
+    // probe below still covers a backend we didn't spawn ourselves
+    let mut state = status.subscribe();
+
     // Try to connect to server for up to 30 seconds
     for i in 0..30 {
-        match check_server_health().await {
+        if *state.borrow_and_update() == ServerState::Healthy {
+            log::info!("Server is ready!");
+            return Ok(true);
+        }
+
+        match check_server_health(app_handle.clone()).await {
             Ok(true) => {
                 log::info!("Server is ready!");
                 return Ok(true);
             }
-            Ok(false) => {
-                log::debug!("Server not ready yet, attempt {}/30", i + 1);
-                tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
-            }
-            Err(e) => {
-                log::debug!("Server health check error: {}", e);
-                tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
-            }
+            Ok(false) => log::debug!("Server not ready yet, attempt {}/30", i + 1),
+            Err(e) => log::debug!("Server health check error: {}", e),
         }
+
+        let _ = tokio::time::timeout(Duration::from_secs(1), state.changed()).await;
     }
 
     Err("Server failed to start within 30 seconds".to_string())
 }
 
+/// Starts the server by whichever launch method works first.
+pub(crate) async fn launch_server(app_handle: &tauri::AppHandle) -> Result<String, String> {
+    if server::connection::is_external(app_handle) {
+        return Err("Connected to an external server; nothing to start".to_string());
+    }
+    boot::report(app_handle, BootPhase::ResolvingBinary, None);
+    match server::launch::launch(app_handle, false).await? {
+        server::launch::Launched::Started(msg) => Ok(msg),
+        server::launch::Launched::Adopted => Ok("Using the server already running".to_string()),
+    }
+}
+
+/// Stops the running server, waits for its port to free up, starts it again
+/// and resolves once the new process passes its health check.
+pub(crate) async fn relaunch_server(
+    app_handle: &tauri::AppHandle,
+    grace: Duration,
+) -> Result<String, String> {
+    log::info!("Restarting server...");
+    app_handle.state::<ServerSupervisor>().stop(grace).await;
+
+    let port = app_handle.state::<PortManager>().port();
+    if !server::port::wait_until_free(port, Duration::from_secs(10)).await {
+        let diagnosis = server::port_owner::diagnose(app_handle, port).await;
+        log::warn!("Server port is still busy after stopping it: {}", diagnosis.hint);
+    }
+
+    let msg = launch_server(app_handle).await?;
+    wait_for_server_ready(app_handle.clone(), app_handle.state()).await?;
+    Ok(msg)
+}
+
+#[tauri::command]
+async fn restart_server(app_handle: tauri::AppHandle, grace_ms: Option<u64>) -> Result<String, String> {
+    let grace = grace_ms.map(Duration::from_millis).unwrap_or(DEFAULT_SHUTDOWN_GRACE);
+    relaunch_server(&app_handle, grace).await
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SecondInstancePayload {
+    args: Vec<String>,
+    cwd: String,
+}
+
+/// A second launch hands its arguments to us and exits instead of starting
+/// another backend on the same port.
+fn on_second_instance(app: &tauri::AppHandle, args: Vec<String>, cwd: String) {
+    log::info!("Second instance launched with {:?}", args);
+    cli::handle_second_instance(app, &args, &cwd);
+    let _ = app.emit("app://second-instance", SecondInstancePayload { args, cwd });
+}
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
+  startup::launched();
+  // Before anything else, so `--help` and bad flags never open a window
+  let cli_args = cli::from_env();
   tauri::Builder::default()
-    .invoke_handler(tauri::generate_handler![
+    // Must be registered first so a duplicate launch exits before doing anything
+    .plugin(tauri_plugin_single_instance::init(on_second_instance))
+    .plugin(tauri_plugin_deep_link::init())
+    .plugin(tauri_plugin_opener::init())
+    .plugin(tauri_plugin_shell::init())
+    .plugin(tauri_plugin_updater::Builder::new().build())
+    .plugin(tauri_plugin_notification::init())
+    .plugin(autostart::plugin())
+    .plugin(shortcuts::plugin())
+    .plugin(tauri_plugin_clipboard_manager::init())
+    .manage(ServerSupervisor::default())
+    .manage(ServerLogs::default())
+    .manage(network::Connectivity::default())
+    .manage(server::limits::ApiGuard::default())
+    .manage(server::trace::HttpRecorder::default())
+    .manage(server::watch::DevWatcher::default())
+    .manage(ServerStatus::default())
+    .manage(server::health::HealthHistory::default())
+    .manage(server::idle::IdleManager::default())
+    .manage(server::bridge::BackendBridge::default())
+    .manage(power::PowerMonitor::default())
+    .manage(windows::WindowRegistry::default())
+    .manage(ServerMetrics::default())
+    .manage(BootState::default())
+    .manage(startup::StartupProfiler::default())
+    .manage(ApiToken::generate())
+    .manage(server::auth::ExternalToken::default())
+    .manage(server::tls::TlsProxy::default())
+    .manage(control_api::ControlApi::default())
+    .manage(audit::AuditLog::default())
+    .manage(ApiClient::default())
+    .manage(updater::PendingUpdate::default())
+    .manage(ProfileRegistry::default())
+    .manage(profiles::automation::AutomationRegistry::default())
+    .manage(DownloadManager::default())
+    .manage(cache::AppCache::default())
+    .manage(log_files::tail::LogTails::default())
+    .manage(server::compat::ServerCompat::default())
+    .manage(fingerprints::FingerprintDataset::default())
+    .manage(proxy::locale::GeoIp::default())
+    .manage(proxy::bridge::ProxyBridges::default())
+    .invoke_handler(telemetry::counting(permissions::guarded(tauri::generate_handler![
         check_server_health,
+        get_server_base_url,
         start_server,
-        open_server_folder,
+        stop_server,
+        restart_server,
+        folders::open_server_folder,
+        folders::open_logs_folder,
+        folders::open_app_data_folder,
+        folders::reveal_in_file_manager,
+        folders::open_with_default_app,
+        data_dir::get_data_directory,
+        data_dir::set_data_directory,
+        clipboard::copy_to_clipboard,
+        clipboard::read_clipboard,
+        cookies::parse_cookies,
+        cookies::import_cookies,
+        cookies::convert_cookies,
+        crash::get_crash_recovery,
+        crash::resolve_crash_recovery,
+        safe_mode::get_safe_mode_reasons,
+        safe_mode::apply_safe_mode_fix,
+        safe_mode::leave_safe_mode,
+        telemetry::get_telemetry_status,
+        telemetry::set_telemetry_enabled,
+        telemetry::preview_telemetry_payload,
+        startup::get_startup_timings,
+        cookies::export_cookies,
+        importers::preview_profile_import,
+        importers::import_profiles,
+        fingerprints::query_fingerprints,
+        fingerprints::random_fingerprint,
+        exports::list_exports,
+        exports::get_export_url,
+        exports::delete_export,
+        assets::get_profile_asset_url,
+        assets::set_profile_asset,
+        assets::remove_profile_asset,
         start_embedded_server,
-        wait_for_server_ready
-    ])
-    .setup(|app| {
-      if cfg!(debug_assertions) {
-        app.handle().plugin(
-          tauri_plugin_log::Builder::default()
-            .level(log::LevelFilter::Info)
-            .build(),
-        )?;
+        wait_for_server_ready,
+        boot::get_boot_progress,
+        boot::dismiss_splash,
+        server::logs::get_server_logs,
+        server::status::get_server_status,
+        server::health::get_server_health_detail,
+        server::health::get_health_history,
+        server::idle::report_activity,
+        server::idle::get_idle_status,
+        power::get_power_status,
+        windows::open_window,
+        windows::close_window,
+        windows::list_windows,
+        windows::send_to_window,
+        widget::open_status_widget,
+        widget::set_status_widget_options,
+        widget::get_widget_status,
+        server::compat::get_server_compatibility,
+        server::auth::get_api_token,
+        server::auth::proxy_api_request,
+        server::client::api_request,
+        server::queue::get_pending_operations,
+        server::queue::discard_pending_operation,
+        server::python::detect_python,
+        server::python::bootstrap_python_env,
+        server::port_owner::diagnose_port,
+        server::port_owner::kill_port_owner,
+        config::get_health_config,
+        config::set_health_config,
+        config::get_connection_config,
+        config::set_connection_config,
+        config::get_tls_proxy_config,
+        config::set_tls_proxy_config,
+        server::tls::get_tls_proxy,
+        server::environments::list_environments,
+        server::environments::save_environment,
+        server::environments::remove_environment,
+        server::environments::switch_environment,
+        config::get_update_config,
+        config::set_update_config,
+        config::get_server_config,
+        config::set_server_config,
+        config::get_http_config,
+        config::set_http_config,
+        config::get_api_limits_config,
+        config::set_api_limits_config,
+        config::get_monitor_config,
+        config::set_monitor_config,
+        config::get_browser_config,
+        config::set_browser_config,
+        config::get_backup_config,
+        config::set_backup_config,
+        config::get_idle_config,
+        config::set_idle_config,
+        config::get_gc_config,
+        config::set_gc_config,
+        config::get_proxy_pool_config,
+        config::set_proxy_pool_config,
+        config::get_automation_config,
+        config::set_automation_config,
+        control_api::get_control_api,
+        control_api::set_control_api,
+        control_api::create_control_token,
+        control_api::revoke_control_token,
+        audit::query_audit_log,
+        permissions::get_window_grants,
+        permissions::set_window_grants,
+        gc::preview_gc,
+        gc::run_gc,
+        server::metrics::get_server_metrics,
+        settings::get_setting,
+        settings::set_setting,
+        settings::reset_settings,
+        shortcuts::list_shortcuts,
+        shortcuts::register_shortcut,
+        shortcuts::unregister_shortcut,
+        onboarding::is_first_run,
+        onboarding::get_onboarding_state,
+        onboarding::complete_onboarding,
+        diagnostics::generate_diagnostic_bundle,
+        notifications::notify,
+        system_info::get_system_info,
+        storage::get_storage_breakdown,
+        storage::clean_category,
+        profiles::launch_profile,
+        profiles::stop_profile,
+        profiles::list_running_profiles,
+        profiles::kill_all_profiles,
+        profiles::leak_test::run_leak_test,
+        profiles::gpu::get_profile_gpu,
+        profiles::gpu::set_profile_gpu,
+        profiles::automation::get_automation_endpoint,
+        profiles::automation::list_automation_endpoints,
+        profiles::automation::set_profile_automation,
+        profiles::export::export_profiles,
+        profiles::export::inspect_profile_archive,
+        profiles::export::import_profile_archive,
+        scheduler::create_schedule,
+        scheduler::list_schedules,
+        scheduler::set_schedule_enabled,
+        scheduler::delete_schedule,
+        scheduler::run_now,
+        scheduler::get_schedule_history,
+        jobs::enqueue_job,
+        jobs::cancel_job,
+        jobs::get_jobs,
+        network::get_network_status,
+        network::check_network,
+        bandwidth::get_bandwidth_stats,
+        bandwidth::reset_bandwidth_stats,
+        proxy::check::check_proxy,
+        proxy::check::check_proxies,
+        proxy::import::import_proxies,
+        proxy::locale::infer_locale_for_proxy,
+        proxy::locale::get_geoip_database,
+        proxy::locale::install_geoip_database,
+        proxy::pool::list_pool_proxies,
+        proxy::pool::add_pool_proxies,
+        proxy::pool::remove_pool_proxy,
+        proxy::pool::next_proxy,
+        proxy::bridge::start_proxy_bridge,
+        proxy::bridge::stop_proxy_bridge,
+        proxy::bridge::list_proxy_bridges,
+        downloads::start_download,
+        downloads::pause_download,
+        downloads::resume_download,
+        downloads::cancel_download,
+        downloads::list_downloads,
+        browsers::list_browsers,
+        browsers::install_browser,
+        browsers::remove_browser,
+        browsers::prune_browsers,
+        archive::extract_archive,
+        autostart::get_autostart,
+        autostart::set_autostart,
+        secrets::store_secret,
+        secrets::get_secret,
+        secrets::delete_secret,
+        cache::sync_cache,
+        cache::read_cache,
+        cache::get_cache_status,
+        cache::clear_cache,
+        backup::create_backup,
+        backup::restore_backup,
+        updater::check_for_updates,
+        updater::download_update,
+        updater::install_update_and_restart,
+        server::update::check_server_update,
+        server::update::download_server_update,
+        server::update::revert_server_update,
+        server::priority::get_server_priority,
+        server::priority::set_server_priority,
+        server::limits::get_api_circuit,
+        server::trace::set_http_recording,
+        server::trace::get_http_recording,
+        server::trace::get_http_trace,
+        server::trace::clear_http_trace,
+        server::trace::export_http_trace,
+        server::watch::start_dev_watcher,
+        server::watch::stop_dev_watcher,
+        server::watch::get_dev_watcher,
+        log_files::list_log_files,
+        log_files::read_log_file,
+        log_files::tail::tail_logs,
+        log_files::tail::pause_tail,
+        log_files::tail::resume_tail,
+        log_files::tail::stop_tail,
+        logging::get_log_levels,
+        logging::set_log_level
+    ])))
+    .setup(move |app| {
+      startup::end(app.handle(), startup::Span::PluginInit);
+      startup::start(app.handle(), startup::Span::Setup);
+      // Logs always go to disk so they can be attached to bug reports
+      let logs_dir = log_files::logs_dir(app.handle())?;
+      log_files::prune(&logs_dir);
+      app.manage(logging::init(&logs_dir)?);
+      app.manage(crash::install(app.handle()));
+      crash::track_profiles(app.handle());
+      app.manage(safe_mode::SafeMode::detect(app.handle(), &cli_args));
+      let safe_mode = app.state::<safe_mode::SafeMode>().is_active();
+      app.manage(telemetry::Telemetry::load(app.handle()));
+
+      match RotatingFile::open(&logs_dir, SERVER_LOG) {
+        Ok(file) => app.state::<ServerLogs>().set_file(file),
+        Err(e) => log::warn!("Failed to open server log file: {}", e),
+      }
+
+      app.state::<startup::StartupProfiler>().load(app.handle());
+      app.manage(ConfigStore::load(app.handle()));
+      secrets::spawn_migration(app.handle());
+      app.manage(SettingsStore::load(app.handle()));
+      app.manage(scheduler::Scheduler::load(app.handle()));
+      app.manage(proxy::pool::ProxyPool::load(app.handle()));
+      app.manage(jobs::JobManager::load(app.handle()));
+      app.manage(bandwidth::Bandwidth::load(app.handle()));
+      app.manage(Onboarding::init(app.handle()));
+      app.manage(PortManager::load(app.handle()));
+
+      // An export needs nothing past the stores above and exits when done
+      if let Some(path) = cli_args.export_logs.clone() {
+          let app_handle = app.handle().clone();
+          tauri::async_runtime::spawn(async move {
+              let code = match cli::export_logs(&app_handle, path).await {
+                  Ok(()) => 0,
+                  Err(e) => {
+                      log::error!("{}", e);
+                      1
+                  }
+              };
+              app_handle.exit(code);
+          });
+          return Ok(());
       }
 
+      let window_state = window_state::WindowState::load(app.handle());
+      if let Some(main) = app.get_webview_window(windows::MAIN_LABEL) {
+          window_state.restore(&main);
+          windows::register(app.handle(), main.label());
+      }
+      app.manage(window_state);
+
+      let has_tray = match tray::create(app.handle()) {
+          Ok(()) => true,
+          Err(e) => {
+              log::warn!("Failed to create tray icon: {}", e);
+              false
+          }
+      };
+
+      server::bridge::spawn(app.handle());
+      power::spawn(app.handle());
+      server::metrics::spawn(app.handle());
+      server::compat::spawn(app.handle());
+      cache::spawn(app.handle());
+      server::queue::spawn(app.handle());
+      server::tls::spawn(app.handle());
+      server::idle::spawn(app.handle());
+      network::spawn(app.handle());
+      bandwidth::spawn(app.handle());
+      telemetry::spawn(app.handle());
+      // Nothing that runs on its own in safe mode
+      if !safe_mode {
+          backup::schedule::spawn(app.handle());
+          scheduler::spawn(app.handle());
+          gc::spawn(app.handle());
+          proxy::pool::spawn(app.handle());
+          control_api::spawn(app.handle());
+          updater::spawn_auto_check(app.handle());
+          shortcuts::register_all(app.handle());
+      }
+
+      // The main window starts hidden and is shown once the server is up
+      // Without a tray there would be no way back to a hidden window
+      if cli_args.hide_ui() && has_tray {
+          log::info!("Running in the tray as asked on the command line");
+      } else if safe_mode {
+          // No server is coming up to end the splash
+          boot::finish(app.handle());
+      } else {
+          match boot::create_splash(app.handle()) {
+              Ok(()) => boot::watch(app.handle()),
+              Err(e) => {
+                  log::warn!("Failed to create splash window: {}", e);
+                  boot::finish(app.handle());
+              }
+          }
+      }
+
+      // After the splash, so a launch link focuses it rather than the main window
+      deep_link::init(app.handle());
+      cli::spawn_launches(app.handle(), &cli_args);
+
       // Auto-check server health on startup
       let app_handle = app.handle().clone();
-      tokio::spawn(async move {
-          // Wait a moment for the app to fully initialize
-          tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
-
-          // Check if server is running
-          match check_server_health().await {
-              Ok(true) => log::info!("Server is already running"),
-              Ok(false) => {
-                  log::info!("Server not running, starting embedded server...");
-                  match start_embedded_server(app_handle.clone()).await {
-                      Ok(_) => log::info!("Embedded server started successfully"),
-                      Err(e) => {
-                          log::warn!("Failed to start embedded server: {}", e);
-                          // Fallback to external server start
-                          match start_server().await {
-                              Ok(msg) => log::info!("Fallback server start: {}", msg),
-                              Err(e2) => log::error!("All server start methods failed: {}", e2),
-                          }
-                      }
-                  }
-              },
-              Err(e) => log::error!("Error checking server health: {}", e),
+      tauri::async_runtime::spawn(async move {
+          startup::start(&app_handle, startup::Span::ResolveBinary);
+
+          // Nothing to spawn or clean up when the backend lives elsewhere
+          if server::connection::is_external(&app_handle) {
+              let url = server::connection::base_url(&app_handle);
+              log::info!("Using external server at {}", url);
+              boot::report(&app_handle, BootPhase::WaitingForHealth, Some(url));
+              if let Err(e) = server::auth::load_external_token(&app_handle).await {
+                  log::warn!("{}", e);
+              }
+              server::connection::spawn_monitor(&app_handle);
+              return;
+          }
+
+          if safe_mode {
+              log::info!("Not starting the embedded server in safe mode");
+              return;
+          }
+
+          // A backend left behind by a crashed session would otherwise answer
+          // the health check below and never be supervised
+          if let Some(pid) = server::orphans::kill_orphaned_server(&app_handle).await {
+              log::info!("Cleaned up orphaned server process {}", pid);
+          }
+
+          // Looks for a server already running while the launch methods resolve
+          match server::launch::launch(&app_handle, true).await {
+              Ok(server::launch::Launched::Adopted) => {
+                  log::info!("Server is already running");
+                  app_handle.state::<ServerStatus>().external(&app_handle);
+              }
+              Ok(server::launch::Launched::Started(msg)) => log::info!("{}", msg),
+              Err(e) => {
+                  log::error!("All server start methods failed: {}", e);
+                  boot::report(&app_handle, BootPhase::Failed, Some(e));
+              }
           }
       });
 
+      startup::end(app.handle(), startup::Span::Setup);
       Ok(())
     })
-    .run(tauri::generate_context!())
-    .expect("error while running tauri application");
+    .on_page_load(|webview, payload| {
+      server::tls::trust(webview);
+      if payload.event() == PageLoadEvent::Finished {
+        startup::end(webview.app_handle(), startup::Span::FirstPaint);
+      }
+    })
+    .register_asynchronous_uri_scheme_protocol(exports::SCHEME, exports::handle)
+    .register_asynchronous_uri_scheme_protocol(assets::SCHEME, assets::handle)
+    .on_window_event(|window, event| {
+      if let Some(state) = window.try_state::<window_state::WindowState>() {
+        state.track(window, event);
+      }
+      // With a tray icon to bring it back, closing the main window only hides
+      // it and the backend keeps running; Quit lives in the tray menu
+      if let WindowEvent::CloseRequested { api, .. } = event {
+        if window.label() == "main" && window.app_handle().tray_by_id(tray::TRAY_ID).is_some() {
+          api.prevent_close();
+          let _ = window.hide();
+          if let Some(state) = window.try_state::<window_state::WindowState>() {
+            state.save();
+          }
+        }
+      }
+      if let WindowEvent::Destroyed = event {
+        windows::closed(window.app_handle(), window.label());
+      }
+      if let WindowEvent::DragDrop(DragDropEvent::Drop { paths, .. }) = event {
+        file_drop::handle(window.app_handle(), window.label(), paths.clone());
+      }
+    })
+    .build(tauri::generate_context!())
+    .expect("error while building tauri application")
+    .run(|app_handle, event| {
+      if let RunEvent::Exit = event {
+        crash::end_session(app_handle);
+        if let Some(state) = app_handle.try_state::<window_state::WindowState>() {
+          state.save();
+        }
+        if let Some(bandwidth) = app_handle.try_state::<bandwidth::Bandwidth>() {
+          bandwidth.save();
+        }
+        if let Some(telemetry) = app_handle.try_state::<telemetry::Telemetry>() {
+          telemetry.save();
+        }
+      }
+      if let RunEvent::ExitRequested { api, .. } = event {
+        // Hold the exit until the backend is down, then exit for real; the
+        // second request finds no running server and goes through.
+        if app_handle.state::<ServerSupervisor>().is_running() {
+          api.prevent_exit();
+          let app_handle = app_handle.clone();
+          tauri::async_runtime::spawn(async move {
+            app_handle
+              .state::<ServerSupervisor>()
+              .stop(DEFAULT_SHUTDOWN_GRACE)
+              .await;
+            app_handle.exit(0);
+          });
+        }
+      }
+    });
 }
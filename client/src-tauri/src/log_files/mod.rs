@@ -0,0 +1,194 @@
+//! On-disk logs for the shell and the server, with rotation and retention.
+
+pub mod tail;
+
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+use tauri::{AppHandle, Manager};
+
+/// Base name of the file the log plugin writes the shell's own logs to.
+pub const SHELL_LOG: &str = "nyx-shell";
+/// Base name of the file captured server output goes to.
+pub const SERVER_LOG: &str = "nyx-server";
+/// Size at which a log file is rotated.
+pub const MAX_FILE_SIZE: u64 = 5 * 1024 * 1024;
+
+/// Rotated files kept per log, newest first.
+const MAX_ROTATED_FILES: usize = 5;
+/// Rotated files older than this are deleted regardless of count.
+const MAX_AGE: Duration = Duration::from_secs(14 * 24 * 60 * 60);
+/// Default cap on how much of a file `read_log_file` returns.
+const DEFAULT_READ_BYTES: u64 = 1024 * 1024;
+
+pub fn logs_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    app.path()
+        .app_data_dir()
+        .map(|dir| dir.join("logs"))
+        .map_err(|e| format!("Failed to get app data directory: {}", e))
+}
+
+/// Whether `path` is a log file being written to, rather than a rotated one.
+pub fn is_active(path: &Path) -> bool {
+    path.file_name().is_some_and(|name| {
+        [SHELL_LOG, SERVER_LOG]
+            .iter()
+            .any(|base| name.to_string_lossy() == format!("{}.log", base))
+    })
+}
+
+/// An append-only log file that rolls over to `<name>_<timestamp>.log`.
+pub struct RotatingFile {
+    dir: PathBuf,
+    name: String,
+    file: File,
+    size: u64,
+}
+
+impl RotatingFile {
+    pub fn open(dir: &Path, name: &str) -> std::io::Result<Self> {
+        std::fs::create_dir_all(dir)?;
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(dir.join(format!("{}.log", name)))?;
+        let size = file.metadata()?.len();
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            name: name.to_string(),
+            file,
+            size,
+        })
+    }
+
+    pub fn write_line(&mut self, line: &str) -> std::io::Result<()> {
+        self.write_all(format!("{}\n", line).as_bytes())
+    }
+
+    fn rotate(&mut self) -> std::io::Result<()> {
+        let current = self.dir.join(format!("{}.log", self.name));
+        let stamp = chrono::Local::now().format("%Y-%m-%d_%H-%M-%S");
+        std::fs::rename(
+            &current,
+            self.dir.join(format!("{}_{}.log", self.name, stamp)),
+        )?;
+        self.file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&current)?;
+        self.size = 0;
+        prune(&self.dir);
+        Ok(())
+    }
+}
+
+/// Each write is kept whole in one file, so a write of a full line never
+/// straddles a rotation.
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if self.size + buf.len() as u64 > MAX_FILE_SIZE && self.size > 0 {
+            self.rotate()?;
+        }
+        let written = self.file.write(buf)?;
+        self.size += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.file.flush()
+    }
+}
+
+/// Applies the retention policy to rotated files of every known log.
+pub fn prune(dir: &Path) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    let mut rotated: Vec<(String, SystemTime, PathBuf)> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            let base = [SHELL_LOG, SERVER_LOG]
+                .into_iter()
+                .find(|base| name.starts_with(&format!("{}_", base)) && name.ends_with(".log"))?;
+            let modified = entry.metadata().and_then(|m| m.modified()).ok()?;
+            Some((base.to_string(), modified, entry.path()))
+        })
+        .collect();
+    rotated.sort_by_key(|(_, modified, _)| std::cmp::Reverse(*modified));
+
+    let now = SystemTime::now();
+    for base in [SHELL_LOG, SERVER_LOG] {
+        for (index, (_, modified, path)) in rotated.iter().filter(|(b, ..)| b == base).enumerate() {
+            let expired = now.duration_since(*modified).is_ok_and(|age| age > MAX_AGE);
+            if index >= MAX_ROTATED_FILES || expired {
+                if let Err(e) = std::fs::remove_file(path) {
+                    log::warn!("Failed to remove old log file {}: {}", path.display(), e);
+                }
+            }
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LogFileInfo {
+    pub name: String,
+    pub size: u64,
+    /// Milliseconds since the Unix epoch.
+    pub modified: u64,
+}
+
+#[tauri::command]
+pub fn list_log_files(app_handle: AppHandle) -> Result<Vec<LogFileInfo>, String> {
+    let dir = logs_dir(&app_handle)?;
+    let Ok(entries) = std::fs::read_dir(&dir) else {
+        return Ok(Vec::new());
+    };
+
+    let mut files: Vec<LogFileInfo> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let metadata = entry.metadata().ok().filter(|m| m.is_file())?;
+            Some(LogFileInfo {
+                name: entry.file_name().to_string_lossy().to_string(),
+                size: metadata.len(),
+                modified: metadata
+                    .modified()
+                    .ok()
+                    .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                    .map(|d| d.as_millis() as u64)
+                    .unwrap_or_default(),
+            })
+        })
+        .collect();
+    files.sort_by_key(|file| std::cmp::Reverse(file.modified));
+    Ok(files)
+}
+
+/// Returns the tail of a file in the logs directory, at most `max_bytes` long.
+#[tauri::command]
+pub fn read_log_file(
+    app_handle: AppHandle,
+    name: String,
+    max_bytes: Option<u64>,
+) -> Result<String, String> {
+    if name.is_empty() || name.contains(['/', '\\']) || name.contains("..") {
+        return Err(format!("Invalid log file name: {}", name));
+    }
+
+    let path = logs_dir(&app_handle)?.join(&name);
+    let mut file = File::open(&path).map_err(|e| format!("Failed to open {}: {}", name, e))?;
+    let len = file.metadata().map_err(|e| e.to_string())?.len();
+    let max_bytes = max_bytes.unwrap_or(DEFAULT_READ_BYTES);
+    file.seek(SeekFrom::Start(len.saturating_sub(max_bytes)))
+        .map_err(|e| format!("Failed to read {}: {}", name, e))?;
+
+    let mut contents = Vec::new();
+    file.read_to_end(&mut contents)
+        .map_err(|e| format!("Failed to read {}: {}", name, e))?;
+    Ok(String::from_utf8_lossy(&contents).into_owned())
+}
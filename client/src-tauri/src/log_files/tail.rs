@@ -0,0 +1,357 @@
+//! Streams the shell or server log to the frontend's log console, so it
+//! never has to load whole files. A tail starts with the newest matching
+//! lines and, when following, keeps reading what is appended until it is
+//! stopped, across rotations too.
+//!
+//! Matching lines are emitted in batches as `logs://lines`, and
+//! `logs://ended` once the tail is done. A paused tail reads nothing and
+//! picks up where it left off when resumed.
+
+use std::collections::BTreeMap;
+use std::io::SeekFrom;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager};
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use tokio::sync::watch;
+
+use super::{logs_dir, SERVER_LOG, SHELL_LOG};
+
+/// How often a followed file is checked for new lines.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+/// How far back from the end the opening lines are looked for.
+const BACKLOG_BYTES: u64 = 1024 * 1024;
+/// Most bytes read per poll, so a burst is spread over several batches.
+const MAX_CHUNK: u64 = 256 * 1024;
+const DEFAULT_BACKLOG_LINES: usize = 200;
+/// Tails running at once; starting another stops the oldest, e.g. one
+/// left behind by a reloaded page.
+const MAX_TAILS: usize = 8;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogSource {
+    Shell,
+    Server,
+}
+
+impl LogSource {
+    fn path(self, app: &AppHandle) -> Result<PathBuf, String> {
+        let name = match self {
+            Self::Shell => SHELL_LOG,
+            Self::Server => SERVER_LOG,
+        };
+        Ok(logs_dir(app)?.join(format!("{}.log", name)))
+    }
+}
+
+/// Severity, lowest first.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    Trace,
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+impl LogLevel {
+    /// The first level named in `line`, as in `[INFO]` from the shell or
+    /// `WARNING:` from the server. Only uppercase names count, so words in
+    /// the message itself rarely match.
+    fn detect(line: &str) -> Option<Self> {
+        line.split(|c: char| !c.is_ascii_alphabetic())
+            .find_map(|word| match word {
+                "TRACE" => Some(Self::Trace),
+                "DEBUG" => Some(Self::Debug),
+                "INFO" => Some(Self::Info),
+                "WARN" | "WARNING" => Some(Self::Warn),
+                "ERROR" | "CRITICAL" | "FATAL" => Some(Self::Error),
+                _ => None,
+            })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Control {
+    Running,
+    Paused,
+}
+
+/// Managed state with the control of every running tail.
+#[derive(Default)]
+pub struct LogTails {
+    next_id: Mutex<u64>,
+    tails: Mutex<BTreeMap<u64, watch::Sender<Control>>>,
+}
+
+impl LogTails {
+    fn set(&self, id: &str, control: Control) -> Result<(), String> {
+        let tails = self.tails.lock().unwrap();
+        id.parse::<u64>()
+            .ok()
+            .and_then(|id| tails.get(&id))
+            .ok_or_else(|| format!("No log tail {}", id))?
+            .send_replace(control);
+        Ok(())
+    }
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct TailLine {
+    line: String,
+    level: Option<LogLevel>,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct LinesPayload {
+    tail_id: String,
+    source: LogSource,
+    lines: Vec<TailLine>,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct EndedPayload {
+    tail_id: String,
+    error: Option<String>,
+}
+
+struct Filter {
+    level: Option<LogLevel>,
+    pattern: Option<Regex>,
+    /// Level of the line before, which lines without one, like traceback
+    /// frames, belong to.
+    last_level: Option<LogLevel>,
+}
+
+impl Filter {
+    fn apply(&mut self, lines: Vec<String>) -> Vec<TailLine> {
+        lines
+            .into_iter()
+            .filter_map(|line| {
+                let level = LogLevel::detect(&line).or(self.last_level);
+                self.last_level = level;
+                let level_ok = match (self.level, level) {
+                    (Some(min), Some(level)) => level >= min,
+                    _ => true,
+                };
+                let pattern_ok = self
+                    .pattern
+                    .as_ref()
+                    .map_or(true, |pattern| pattern.is_match(&line));
+                (level_ok && pattern_ok).then_some(TailLine { line, level })
+            })
+            .collect()
+    }
+}
+
+/// Reads a growing file line by line.
+struct Reader {
+    path: PathBuf,
+    offset: u64,
+    /// Start of a line not yet ended.
+    partial: Vec<u8>,
+    /// Whether the first line read starts mid-line.
+    cut_off: bool,
+}
+
+impl Reader {
+    /// Starts `BACKLOG_BYTES` before the end, at a line boundary.
+    async fn open(path: &Path) -> Self {
+        let len = file_len(path).await;
+        let offset = len.saturating_sub(BACKLOG_BYTES);
+        Self {
+            path: path.to_path_buf(),
+            offset,
+            partial: Vec::new(),
+            cut_off: offset > 0,
+        }
+    }
+
+    /// Complete lines appended since the last read.
+    async fn read(&mut self, limit: u64) -> std::io::Result<Vec<String>> {
+        let len = file_len(&self.path).await;
+        // Rotation started a new, shorter file
+        if len < self.offset {
+            self.offset = 0;
+            self.partial.clear();
+            self.cut_off = false;
+        }
+        if len == self.offset {
+            return Ok(Vec::new());
+        }
+        let mut file = tokio::fs::File::open(&self.path).await?;
+        file.seek(SeekFrom::Start(self.offset)).await?;
+        let mut chunk = Vec::new();
+        file.take((len - self.offset).min(limit))
+            .read_to_end(&mut chunk)
+            .await?;
+        self.offset += chunk.len() as u64;
+        self.partial.extend_from_slice(&chunk);
+
+        let Some(end) = self.partial.iter().rposition(|&b| b == b'\n') else {
+            return Ok(Vec::new());
+        };
+        let rest = self.partial.split_off(end + 1);
+        let complete = std::mem::replace(&mut self.partial, rest);
+        let mut lines: Vec<String> = String::from_utf8_lossy(&complete)
+            .lines()
+            .map(|line| line.trim_end().to_string())
+            .collect();
+        if std::mem::take(&mut self.cut_off) {
+            lines.remove(0);
+        }
+        Ok(lines)
+    }
+}
+
+async fn file_len(path: &Path) -> u64 {
+    tokio::fs::metadata(path)
+        .await
+        .map(|metadata| metadata.len())
+        .unwrap_or(0)
+}
+
+fn emit(app: &AppHandle, id: u64, source: LogSource, lines: Vec<TailLine>) {
+    if lines.is_empty() {
+        return;
+    }
+    let _ = app.emit(
+        "logs://lines",
+        LinesPayload {
+            tail_id: id.to_string(),
+            source,
+            lines,
+        },
+    );
+}
+
+async fn run(
+    app: &AppHandle,
+    id: u64,
+    source: LogSource,
+    mut filter: Filter,
+    follow: bool,
+    backlog: usize,
+    mut control: watch::Receiver<Control>,
+) -> Result<(), String> {
+    let path = source.path(app)?;
+    let mut reader = Reader::open(&path).await;
+    let mut opening = filter.apply(
+        reader
+            .read(BACKLOG_BYTES + 1)
+            .await
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?,
+    );
+    opening.drain(..opening.len().saturating_sub(backlog));
+    emit(app, id, source, opening);
+
+    if !follow {
+        return Ok(());
+    }
+    loop {
+        tokio::select! {
+            _ = tokio::time::sleep(POLL_INTERVAL) => {}
+            changed = control.changed() => {
+                if changed.is_err() {
+                    break;
+                }
+            }
+        }
+        if *control.borrow() == Control::Paused {
+            continue;
+        }
+        match reader.read(MAX_CHUNK).await {
+            Ok(lines) => emit(app, id, source, filter.apply(lines)),
+            // The file is briefly missing while it rotates
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+        }
+    }
+    Ok(())
+}
+
+/// Starts streaming a log and returns the tail's id. `level` is the lowest
+/// severity shown, `filter` a regular expression lines must match, and
+/// `backlog` how many past lines to start with.
+#[tauri::command]
+pub fn tail_logs(
+    app_handle: AppHandle,
+    source: LogSource,
+    level: Option<LogLevel>,
+    filter: Option<String>,
+    follow: Option<bool>,
+    backlog: Option<usize>,
+) -> Result<String, String> {
+    let pattern = filter
+        .filter(|filter| !filter.is_empty())
+        .map(|filter| Regex::new(&filter).map_err(|e| format!("Invalid filter: {}", e)))
+        .transpose()?;
+    let filter = Filter {
+        level,
+        pattern,
+        last_level: None,
+    };
+
+    let tails = app_handle.state::<LogTails>();
+    let (control_tx, control_rx) = watch::channel(Control::Running);
+    let id = {
+        let mut next_id = tails.next_id.lock().unwrap();
+        *next_id += 1;
+        *next_id
+    };
+    {
+        let mut running = tails.tails.lock().unwrap();
+        while running.len() >= MAX_TAILS {
+            // Dropping the sender ends that tail
+            running.pop_first();
+        }
+        running.insert(id, control_tx);
+    }
+
+    let app = app_handle.clone();
+    let follow = follow.unwrap_or(true);
+    let backlog = backlog.unwrap_or(DEFAULT_BACKLOG_LINES);
+    tauri::async_runtime::spawn(async move {
+        let result = run(&app, id, source, filter, follow, backlog, control_rx).await;
+        app.state::<LogTails>().tails.lock().unwrap().remove(&id);
+        if let Err(e) = &result {
+            log::warn!("{}", e);
+        }
+        let _ = app.emit(
+            "logs://ended",
+            EndedPayload {
+                tail_id: id.to_string(),
+                error: result.err(),
+            },
+        );
+    });
+    Ok(id.to_string())
+}
+
+#[tauri::command]
+pub fn pause_tail(tails: tauri::State<'_, LogTails>, tail_id: String) -> Result<(), String> {
+    tails.set(&tail_id, Control::Paused)
+}
+
+#[tauri::command]
+pub fn resume_tail(tails: tauri::State<'_, LogTails>, tail_id: String) -> Result<(), String> {
+    tails.set(&tail_id, Control::Running)
+}
+
+/// Ends a tail. Returns `false` if it had already ended.
+#[tauri::command]
+pub fn stop_tail(tails: tauri::State<'_, LogTails>, tail_id: String) -> bool {
+    let Ok(id) = tail_id.parse::<u64>() else {
+        return false;
+    };
+    tails.tails.lock().unwrap().remove(&id).is_some()
+}
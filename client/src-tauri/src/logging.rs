@@ -0,0 +1,198 @@
+//! The shell's own logs. `log` records from every module go through
+//! `tracing` to stdout and, as JSON lines, to `logs/nyx-shell.log`, which
+//! rotates like the server log.
+//!
+//! Everything starts at `info`, or at `RUST_LOG` when set, and
+//! `set_log_level` raises or lowers one target at runtime, e.g. to debug
+//! the supervisor. Overrides last until the app quits.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Mutex;
+
+use serde::Serialize;
+use tracing::Level;
+use tracing_subscriber::filter::{EnvFilter, LevelFilter};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{fmt, reload, Registry};
+
+use crate::log_files::{RotatingFile, SHELL_LOG};
+
+const DEFAULT_LEVEL: &str = "info";
+
+/// Short names for the targets most often debugged.
+const TARGET_ALIASES: &[(&str, &str)] = &[
+    ("supervisor", "app_lib::server::supervisor"),
+    ("http-proxy", "app_lib::server::auth"),
+    ("http", "app_lib::server::client"),
+];
+
+/// Managed state for changing levels after startup.
+pub struct LogLevels {
+    handle: reload::Handle<EnvFilter, Registry>,
+    /// The filter from startup, below any overrides.
+    base: String,
+    overrides: Mutex<BTreeMap<String, LevelFilter>>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LogLevelsInfo {
+    pub default: String,
+    /// Targets to their level, e.g. `app_lib::server::supervisor: debug`.
+    pub targets: BTreeMap<String, String>,
+}
+
+fn base_filter() -> String {
+    std::env::var(EnvFilter::DEFAULT_ENV)
+        .ok()
+        .filter(|filter| EnvFilter::try_new(filter).is_ok())
+        .unwrap_or_else(|| DEFAULT_LEVEL.to_string())
+}
+
+/// Installs the global logger. Without a log file, logs only go to stdout.
+pub fn init(logs_dir: &Path) -> Result<LogLevels, String> {
+    let base = base_filter();
+    let (filter, handle) = reload::Layer::new(EnvFilter::new(&base));
+    let file = match RotatingFile::open(logs_dir, SHELL_LOG) {
+        Ok(file) => Some(file),
+        Err(e) => {
+            eprintln!("Failed to open shell log file: {}", e);
+            None
+        }
+    };
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(fmt::layer())
+        .with(file.map(|file| {
+            fmt::layer()
+                .json()
+                .with_ansi(false)
+                .with_writer(Mutex::new(file))
+        }))
+        .try_init()
+        .map_err(|e| format!("Failed to set up logging: {}", e))?;
+    Ok(LogLevels {
+        handle,
+        base,
+        overrides: Mutex::new(BTreeMap::new()),
+    })
+}
+
+impl LogLevels {
+    fn info(&self, overrides: &BTreeMap<String, LevelFilter>) -> LogLevelsInfo {
+        LogLevelsInfo {
+            default: overrides
+                .get("")
+                .map(|level| level.to_string().to_lowercase())
+                .unwrap_or_else(|| self.base.clone()),
+            targets: overrides
+                .iter()
+                .filter(|(target, _)| !target.is_empty())
+                .map(|(target, level)| (target.clone(), level.to_string().to_lowercase()))
+                .collect(),
+        }
+    }
+
+    fn apply(&self, overrides: &BTreeMap<String, LevelFilter>) -> Result<(), String> {
+        let mut directives = vec![match overrides.get("") {
+            Some(level) => level.to_string(),
+            None => self.base.clone(),
+        }];
+        directives.extend(
+            overrides
+                .iter()
+                .filter(|(target, _)| !target.is_empty())
+                .map(|(target, level)| format!("{}={}", target, level)),
+        );
+        let filter = EnvFilter::try_new(directives.join(","))
+            .map_err(|e| format!("Invalid log level: {}", e))?;
+        self.handle
+            .reload(filter)
+            .map_err(|e| format!("Failed to change log level: {}", e))?;
+        sync_log_max_level();
+        Ok(())
+    }
+}
+
+/// `log` drops records above its own cap before they reach the filter, so
+/// the cap follows the most verbose level enabled anywhere.
+fn sync_log_max_level() {
+    let max = match LevelFilter::current().into_level() {
+        None => log::LevelFilter::Off,
+        Some(level) if level == Level::ERROR => log::LevelFilter::Error,
+        Some(level) if level == Level::WARN => log::LevelFilter::Warn,
+        Some(level) if level == Level::INFO => log::LevelFilter::Info,
+        Some(level) if level == Level::DEBUG => log::LevelFilter::Debug,
+        Some(_) => log::LevelFilter::Trace,
+    };
+    log::set_max_level(max);
+}
+
+fn normalize_target(target: &str) -> Result<String, String> {
+    let target = target.trim();
+    if target.is_empty() || target == "*" {
+        return Ok(String::new());
+    }
+    if let Some((_, full)) = TARGET_ALIASES.iter().find(|(alias, _)| *alias == target) {
+        return Ok(full.to_string());
+    }
+    let valid = target.split("::").all(|part| {
+        !part.is_empty()
+            && part
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    });
+    if !valid {
+        return Err(format!("Invalid log target: {}", target));
+    }
+    Ok(target.to_string())
+}
+
+#[tauri::command]
+pub fn get_log_levels(levels: tauri::State<'_, LogLevels>) -> LogLevelsInfo {
+    levels.info(&levels.overrides.lock().unwrap())
+}
+
+/// Sets the level of `target`, a module path such as
+/// `app_lib::server::supervisor` or a short name like `supervisor`; an
+/// empty target sets the default. Without a level the override is removed.
+#[tauri::command]
+pub fn set_log_level(
+    levels: tauri::State<'_, LogLevels>,
+    target: String,
+    level: Option<String>,
+) -> Result<LogLevelsInfo, String> {
+    let target = normalize_target(&target)?;
+    let level = level
+        .map(|level| {
+            LevelFilter::from_str(&level).map_err(|_| {
+                format!(
+                    "Invalid log level {:?}; expected off, error, warn, info, debug or trace",
+                    level
+                )
+            })
+        })
+        .transpose()?;
+
+    let mut overrides = levels.overrides.lock().unwrap();
+    let mut updated = overrides.clone();
+    match level {
+        Some(level) => updated.insert(target.clone(), level),
+        None => updated.remove(&target),
+    };
+    levels.apply(&updated)?;
+    *overrides = updated;
+    log::info!(
+        "Log level of {} set to {}",
+        if target.is_empty() {
+            "everything"
+        } else {
+            &target
+        },
+        level.map_or("its default".to_string(), |level| level.to_string())
+    );
+    Ok(levels.info(&overrides))
+}
@@ -0,0 +1,241 @@
+//! Whether this machine can reach the internet at all, which is a different
+//! question from whether the backend is up.
+//!
+//! Public connectivity check endpoints are probed periodically, and more
+//! often while offline. Changes are emitted as `network://online` and
+//! `network://offline` with a [`NetworkStatus`], and the state is part of
+//! the server's status snapshot so the UI can tell "no internet" from
+//! "backend down". A captive portal answering in place of an endpoint
+//! counts as offline.
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager};
+use tokio::sync::watch;
+use tokio::task::JoinSet;
+
+use crate::server::status::ServerStatus;
+
+/// Endpoints known to answer with a fixed body, and that body. An empty body
+/// means the endpoint answers `204 No Content`.
+const ENDPOINTS: &[(&str, &str)] = &[
+    ("http://connectivitycheck.gstatic.com/generate_204", ""),
+    ("http://detectportal.firefox.com/success.txt", "success"),
+    (
+        "http://www.msftconnecttest.com/connecttest.txt",
+        "Microsoft Connect Test",
+    ),
+];
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+const ONLINE_INTERVAL: Duration = Duration::from_secs(30);
+const OFFLINE_INTERVAL: Duration = Duration::from_secs(5);
+/// Failed rounds in a row before going offline, so one slow probe does not
+/// flap the state.
+const FAILURES_BEFORE_OFFLINE: u32 = 2;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum NetworkState {
+    /// Not checked yet.
+    Unknown,
+    Online,
+    Offline,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NetworkStatus {
+    pub state: NetworkState,
+    pub checked_at: Option<DateTime<Utc>>,
+    /// The endpoint that answered last.
+    pub endpoint: Option<String>,
+    pub latency_ms: Option<u64>,
+    /// Why the last round failed.
+    pub error: Option<String>,
+}
+
+struct Inner {
+    status: NetworkStatus,
+    failures: u32,
+}
+
+/// Managed state holding the latest [`NetworkStatus`].
+pub struct Connectivity {
+    inner: Mutex<Inner>,
+    state_tx: watch::Sender<NetworkState>,
+}
+
+impl Default for Connectivity {
+    fn default() -> Self {
+        Self {
+            inner: Mutex::new(Inner {
+                status: NetworkStatus {
+                    state: NetworkState::Unknown,
+                    checked_at: None,
+                    endpoint: None,
+                    latency_ms: None,
+                    error: None,
+                },
+                failures: 0,
+            }),
+            state_tx: watch::channel(NetworkState::Unknown).0,
+        }
+    }
+}
+
+/// Which endpoint answered and how fast.
+struct Reached {
+    endpoint: &'static str,
+    latency_ms: u64,
+}
+
+async fn probe_one(
+    client: reqwest::Client,
+    endpoint: &'static str,
+    expected: &'static str,
+) -> Result<Reached, String> {
+    let started = Instant::now();
+    let response = client
+        .get(endpoint)
+        .send()
+        .await
+        .map_err(|e| format!("{}: {}", endpoint, e))?;
+    let status = response.status();
+    let body = response.text().await.unwrap_or_default();
+    let genuine = if expected.is_empty() {
+        status == reqwest::StatusCode::NO_CONTENT
+    } else {
+        status.is_success() && body.trim() == expected
+    };
+    if !genuine {
+        return Err(format!(
+            "{} answered with something else (HTTP {}), likely a captive portal",
+            endpoint,
+            status.as_u16()
+        ));
+    }
+    Ok(Reached {
+        endpoint,
+        latency_ms: started.elapsed().as_millis() as u64,
+    })
+}
+
+/// The first endpoint to answer genuinely, or the last error.
+async fn probe() -> Result<Reached, String> {
+    let client = reqwest::Client::builder()
+        .timeout(PROBE_TIMEOUT)
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .map_err(|e| e.to_string())?;
+    let mut probes = JoinSet::new();
+    for (endpoint, expected) in ENDPOINTS {
+        probes.spawn(probe_one(client.clone(), endpoint, expected));
+    }
+    let mut error = "No connectivity endpoints to probe".to_string();
+    while let Some(result) = probes.join_next().await {
+        match result {
+            Ok(Ok(reached)) => return Ok(reached),
+            Ok(Err(e)) => error = e,
+            Err(e) => error = e.to_string(),
+        }
+    }
+    Err(error)
+}
+
+impl Connectivity {
+    pub fn status(&self) -> NetworkStatus {
+        self.inner.lock().unwrap().status.clone()
+    }
+
+    pub fn state(&self) -> NetworkState {
+        *self.state_tx.borrow()
+    }
+
+    /// Resolves right away unless the network is known to be down, and
+    /// otherwise once it is back.
+    pub async fn wait_online(&self) {
+        let mut state = self.state_tx.subscribe();
+        let _ = state
+            .wait_for(|state| *state != NetworkState::Offline)
+            .await;
+    }
+
+    fn record(&self, app: &AppHandle, result: Result<Reached, String>) -> NetworkStatus {
+        let (previous, status) = {
+            let mut inner = self.inner.lock().unwrap();
+            let previous = inner.status.state;
+            inner.status.checked_at = Some(Utc::now());
+            match result {
+                Ok(reached) => {
+                    inner.failures = 0;
+                    inner.status.state = NetworkState::Online;
+                    inner.status.endpoint = Some(reached.endpoint.to_string());
+                    inner.status.latency_ms = Some(reached.latency_ms);
+                    inner.status.error = None;
+                }
+                Err(e) => {
+                    inner.failures += 1;
+                    inner.status.latency_ms = None;
+                    inner.status.error = Some(e);
+                    if previous != NetworkState::Online || inner.failures >= FAILURES_BEFORE_OFFLINE
+                    {
+                        inner.status.state = NetworkState::Offline;
+                    }
+                }
+            }
+            (previous, inner.status.clone())
+        };
+        if status.state != previous {
+            self.state_tx.send_replace(status.state);
+            app.state::<ServerStatus>().set_network(app, status.state);
+            match status.state {
+                NetworkState::Online => {
+                    log::info!("Network is online");
+                    let _ = app.emit("network://online", status.clone());
+                }
+                NetworkState::Offline => {
+                    log::warn!(
+                        "Network is offline: {}",
+                        status.error.as_deref().unwrap_or_default()
+                    );
+                    let _ = app.emit("network://offline", status.clone());
+                }
+                NetworkState::Unknown => {}
+            }
+        }
+        status
+    }
+}
+
+/// Keeps [`Connectivity`] up to date for the lifetime of the app.
+pub fn spawn(app: &AppHandle) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let connectivity = app.state::<Connectivity>();
+        loop {
+            let status = connectivity.record(&app, probe().await);
+            let interval = match status.state {
+                NetworkState::Online => ONLINE_INTERVAL,
+                _ => OFFLINE_INTERVAL,
+            };
+            tokio::time::sleep(interval).await;
+        }
+    });
+}
+
+#[tauri::command]
+pub fn get_network_status(connectivity: tauri::State<'_, Connectivity>) -> NetworkStatus {
+    connectivity.status()
+}
+
+/// Probes right away instead of waiting for the next round.
+#[tauri::command]
+pub async fn check_network(
+    app_handle: AppHandle,
+    connectivity: tauri::State<'_, Connectivity>,
+) -> Result<NetworkStatus, String> {
+    Ok(connectivity.record(&app_handle, probe().await))
+}
@@ -0,0 +1,64 @@
+//! OS notifications for things that finish while the user looks elsewhere:
+//! server crashes, downloads, proxy checks and new versions. Each kind can
+//! be muted with the `mutedNotifications` setting.
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+use tauri_plugin_notification::NotificationExt;
+
+use crate::settings::SettingsStore;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum NotificationKind {
+    /// Sent by the frontend.
+    #[default]
+    General,
+    Server,
+    Downloads,
+    Proxies,
+    Updates,
+}
+
+/// Shows a notification unless its kind is muted. Returns whether it was
+/// shown.
+pub fn send(
+    app: &AppHandle,
+    kind: NotificationKind,
+    title: &str,
+    body: &str,
+) -> Result<bool, String> {
+    if app
+        .state::<SettingsStore>()
+        .get()
+        .muted_notifications
+        .contains(&kind)
+    {
+        return Ok(false);
+    }
+    app.notification()
+        .builder()
+        .title(title)
+        .body(body)
+        .show()
+        .map_err(|e| format!("Failed to show notification: {}", e))?;
+    Ok(true)
+}
+
+/// [`send`] for notifications the app raises itself, where a failure is
+/// only worth a log line.
+pub fn send_or_log(app: &AppHandle, kind: NotificationKind, title: &str, body: &str) {
+    if let Err(e) = send(app, kind, title, body) {
+        log::warn!("{}", e);
+    }
+}
+
+#[tauri::command]
+pub fn notify(
+    app_handle: AppHandle,
+    title: String,
+    body: String,
+    kind: Option<NotificationKind>,
+) -> Result<bool, String> {
+    send(&app_handle, kind.unwrap_or_default(), &title, &body)
+}
@@ -0,0 +1,118 @@
+//! First-run detection and the app data layout.
+//!
+//! Every launch makes sure the data directories exist and records the app
+//! version in `onboarding.json`. The frontend shows its setup wizard until
+//! `complete_onboarding` is called.
+
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+
+use crate::config::ConfigStore;
+
+const STATE_FILE: &str = "onboarding.json";
+/// Directories created on every launch; logs go under `app_data_dir()`, the
+/// rest under the data directory of [`crate::data_dir`].
+const DATA_DIRS: &[&str] = &["profiles", "downloads"];
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct OnboardingState {
+    /// Version the app was first launched with.
+    pub first_version: Option<String>,
+    /// Version of the previous launch, before this one was recorded.
+    pub previous_version: Option<String>,
+    pub current_version: Option<String>,
+    /// When the setup wizard was finished, as an RFC 3339 timestamp.
+    pub completed_at: Option<String>,
+}
+
+/// Managed state holding [`OnboardingState`] and its file.
+pub struct Onboarding {
+    path: Option<PathBuf>,
+    state: Mutex<OnboardingState>,
+}
+
+impl Onboarding {
+    /// Creates the data layout and records this launch's version.
+    pub fn init(app: &AppHandle) -> Self {
+        let data_dir = app.path().app_data_dir().ok();
+        let moved = crate::data_dir::data_dir(app).ok();
+        let dirs = data_dir.iter().map(|dir| dir.join("logs")).chain(
+            moved
+                .iter()
+                .flat_map(|dir| DATA_DIRS.iter().map(|name| dir.join(name))),
+        );
+        for dir in dirs {
+            if let Err(e) = std::fs::create_dir_all(&dir) {
+                log::warn!("Failed to create {}: {}", dir.display(), e);
+            }
+        }
+
+        // Write the defaults out so there is a config file to edit by hand
+        let config = app.state::<ConfigStore>();
+        if let Err(e) = config.update(|_| {}) {
+            log::warn!("{}", e);
+        }
+
+        let path = data_dir.map(|dir| dir.join(STATE_FILE));
+        let mut state: OnboardingState = path
+            .as_ref()
+            .and_then(|path| std::fs::read(path).ok())
+            .and_then(|contents| serde_json::from_slice(&contents).ok())
+            .unwrap_or_default();
+        let version = app.package_info().version.to_string();
+        if state.first_version.is_none() {
+            log::info!("First launch of {}", version);
+            state.first_version = Some(version.clone());
+        }
+        state.previous_version = state.current_version.take();
+        state.current_version = Some(version);
+
+        let onboarding = Self {
+            path,
+            state: Mutex::new(state.clone()),
+        };
+        if let Err(e) = onboarding.save(&state) {
+            log::warn!("{}", e);
+        }
+        onboarding
+    }
+
+    pub fn get(&self) -> OnboardingState {
+        self.state.lock().unwrap().clone()
+    }
+
+    fn save(&self, state: &OnboardingState) -> Result<(), String> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let contents = serde_json::to_vec_pretty(state).map_err(|e| e.to_string())?;
+        std::fs::write(path, contents).map_err(|e| format!("Failed to save {}: {}", STATE_FILE, e))
+    }
+}
+
+/// Whether the setup wizard still has to be shown.
+#[tauri::command]
+pub fn is_first_run(onboarding: tauri::State<'_, Onboarding>) -> bool {
+    onboarding.get().completed_at.is_none()
+}
+
+#[tauri::command]
+pub fn get_onboarding_state(onboarding: tauri::State<'_, Onboarding>) -> OnboardingState {
+    onboarding.get()
+}
+
+#[tauri::command]
+pub fn complete_onboarding(
+    onboarding: tauri::State<'_, Onboarding>,
+) -> Result<OnboardingState, String> {
+    let mut state = onboarding.state.lock().unwrap();
+    let mut updated = state.clone();
+    updated.completed_at = Some(chrono::Utc::now().to_rfc3339());
+    onboarding.save(&updated)?;
+    *state = updated.clone();
+    Ok(updated)
+}
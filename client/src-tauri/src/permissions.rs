@@ -0,0 +1,491 @@
+//! Which windows may call which commands.
+//!
+//! Tauri's capabilities decide which windows reach the app's commands at
+//! all. On top of that, the commands that start or kill processes, delete
+//! files, read secrets or change settings need a [`PermissionScope`], which
+//! [`guarded`] checks against the scopes granted to the calling window's
+//! label before the command runs. Commands that need none are listed in
+//! [`UNSCOPED`]; anything neither scoped nor listed there is refused, so a
+//! new command cannot be callable from every window by accident. Grants
+//! live in `permissions` of the config, keyed by label or by a label prefix
+//! ending in `*`; a window without any gets only the unscoped commands. The
+//! main window always has every scope, so it cannot lock itself out, nor
+//! lose access when a new scope is added.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use tauri::ipc::Invoke;
+use tauri::{AppHandle, Manager, Wry};
+
+use crate::config::ConfigStore;
+use crate::windows::{MAIN_LABEL, STATUS_WIDGET_LABEL};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PermissionScope {
+    /// Start, stop and update the embedded server.
+    Server,
+    /// Kill processes the app did not start itself.
+    Processes,
+    /// Launch and stop profiles, move them in and out of the app, and hand
+    /// out their proxies.
+    Profiles,
+    /// Read, open, download, delete or overwrite files at paths the caller
+    /// picks.
+    Files,
+    /// Keychain secrets and the clipboard.
+    Secrets,
+    Settings,
+    /// Call the backend's API through the shell.
+    Api,
+    /// Open windows and message other windows.
+    Windows,
+    /// Grants, control API tokens, automation, logs and the audit log.
+    Admin,
+}
+
+const ALL_SCOPES: [PermissionScope; 9] = [
+    PermissionScope::Server,
+    PermissionScope::Processes,
+    PermissionScope::Profiles,
+    PermissionScope::Files,
+    PermissionScope::Secrets,
+    PermissionScope::Settings,
+    PermissionScope::Api,
+    PermissionScope::Windows,
+    PermissionScope::Admin,
+];
+
+/// The grants windows start with.
+pub fn default_grants() -> BTreeMap<String, Vec<PermissionScope>> {
+    BTreeMap::from([
+        (MAIN_LABEL.to_string(), ALL_SCOPES.to_vec()),
+        (
+            STATUS_WIDGET_LABEL.to_string(),
+            vec![PermissionScope::Server, PermissionScope::Api],
+        ),
+        (
+            "profile-detail-*".to_string(),
+            vec![PermissionScope::Profiles, PermissionScope::Api],
+        ),
+    ])
+}
+
+/// Commands any window may call: they only read state the UI shows anyway,
+/// or act on the calling window's own things.
+pub const UNSCOPED: &[&str] = &[
+    "check_server_health",
+    "get_server_base_url",
+    "get_data_directory",
+    "parse_cookies",
+    "convert_cookies",
+    "get_crash_recovery",
+    "get_safe_mode_reasons",
+    "get_telemetry_status",
+    "preview_telemetry_payload",
+    "get_startup_timings",
+    "query_fingerprints",
+    "random_fingerprint",
+    "list_exports",
+    "get_export_url",
+    "get_profile_asset_url",
+    "wait_for_server_ready",
+    "get_boot_progress",
+    "dismiss_splash",
+    "get_server_status",
+    "get_server_health_detail",
+    "get_health_history",
+    "report_activity",
+    "get_idle_status",
+    "get_power_status",
+    "close_window",
+    "list_windows",
+    "open_status_widget",
+    "set_status_widget_options",
+    "get_widget_status",
+    "get_server_compatibility",
+    "get_pending_operations",
+    "detect_python",
+    "diagnose_port",
+    "get_health_config",
+    "get_connection_config",
+    "get_tls_proxy_config",
+    "get_tls_proxy",
+    "list_environments",
+    "get_update_config",
+    "get_server_config",
+    "get_http_config",
+    "get_api_limits_config",
+    "get_monitor_config",
+    "get_browser_config",
+    "get_backup_config",
+    "get_idle_config",
+    "get_gc_config",
+    "get_proxy_pool_config",
+    "get_automation_config",
+    "preview_gc",
+    "get_server_metrics",
+    "get_setting",
+    "list_shortcuts",
+    "is_first_run",
+    "get_onboarding_state",
+    "notify",
+    "get_system_info",
+    "get_storage_breakdown",
+    "list_running_profiles",
+    "get_profile_gpu",
+    "list_schedules",
+    "get_schedule_history",
+    "get_jobs",
+    "get_network_status",
+    "check_network",
+    "get_bandwidth_stats",
+    "check_proxy",
+    "check_proxies",
+    "infer_locale_for_proxy",
+    "get_geoip_database",
+    "list_downloads",
+    "list_browsers",
+    "get_autostart",
+    "get_cache_status",
+    "check_for_updates",
+    "check_server_update",
+    "get_server_priority",
+    "get_api_circuit",
+    "get_http_recording",
+    "get_dev_watcher",
+    "list_log_files",
+    "pause_tail",
+    "resume_tail",
+    "stop_tail",
+    "get_log_levels",
+];
+
+/// The scope `command` needs; `None` for the [`UNSCOPED`] commands and for
+/// unknown ones, which [`guarded`] tells apart.
+pub fn required_scope(command: &str) -> Option<PermissionScope> {
+    let scope = match command {
+        "start_server"
+        | "stop_server"
+        | "restart_server"
+        | "start_embedded_server"
+        | "switch_environment"
+        | "bootstrap_python_env"
+        | "download_server_update"
+        | "revert_server_update"
+        | "set_server_priority"
+        | "start_dev_watcher"
+        | "stop_dev_watcher"
+        | "install_update_and_restart"
+        | "download_update" => PermissionScope::Server,
+        "kill_port_owner" | "kill_all_profiles" => PermissionScope::Processes,
+        "launch_profile"
+        | "stop_profile"
+        | "run_leak_test"
+        | "import_profiles"
+        | "export_profiles"
+        | "import_profile_archive"
+        | "get_automation_endpoint"
+        | "import_cookies"
+        | "list_pool_proxies"
+        | "next_proxy"
+        | "start_proxy_bridge"
+        | "stop_proxy_bridge"
+        | "list_proxy_bridges" => PermissionScope::Profiles,
+        "delete_export"
+        | "remove_profile_asset"
+        | "clean_category"
+        | "run_gc"
+        | "set_data_directory"
+        | "restore_backup"
+        | "remove_browser"
+        | "prune_browsers"
+        | "extract_archive"
+        | "clear_cache"
+        | "open_server_folder"
+        | "open_logs_folder"
+        | "open_app_data_folder"
+        | "reveal_in_file_manager"
+        | "open_with_default_app"
+        | "export_cookies"
+        | "preview_profile_import"
+        | "inspect_profile_archive"
+        | "set_profile_asset"
+        | "import_proxies"
+        | "install_geoip_database"
+        | "start_download"
+        | "pause_download"
+        | "resume_download"
+        | "cancel_download"
+        | "install_browser"
+        | "create_backup"
+        | "enqueue_job"
+        | "cancel_job"
+        | "export_http_trace" => PermissionScope::Files,
+        "store_secret" | "get_secret" | "delete_secret" | "get_api_token" | "read_clipboard"
+        | "copy_to_clipboard" => PermissionScope::Secrets,
+        "set_health_config"
+        | "set_connection_config"
+        | "set_tls_proxy_config"
+        | "set_update_config"
+        | "set_server_config"
+        | "set_http_config"
+        | "set_api_limits_config"
+        | "set_monitor_config"
+        | "set_browser_config"
+        | "set_backup_config"
+        | "set_idle_config"
+        | "set_gc_config"
+        | "set_proxy_pool_config"
+        | "add_pool_proxies"
+        | "remove_pool_proxy"
+        | "save_environment"
+        | "remove_environment"
+        | "reset_settings"
+        | "set_autostart"
+        | "set_telemetry_enabled"
+        | "set_log_level"
+        | "set_profile_gpu"
+        | "register_shortcut"
+        | "unregister_shortcut"
+        | "apply_safe_mode_fix"
+        | "leave_safe_mode"
+        | "set_setting"
+        | "complete_onboarding"
+        | "resolve_crash_recovery"
+        | "create_schedule"
+        | "set_schedule_enabled"
+        | "delete_schedule"
+        | "run_now"
+        | "reset_bandwidth_stats"
+        | "set_http_recording"
+        | "clear_http_trace" => PermissionScope::Settings,
+        "api_request"
+        | "proxy_api_request"
+        | "discard_pending_operation"
+        | "sync_cache"
+        | "read_cache" => PermissionScope::Api,
+        "open_window" | "send_to_window" => PermissionScope::Windows,
+        "get_window_grants"
+        | "set_window_grants"
+        | "set_automation_config"
+        | "set_profile_automation"
+        | "set_control_api"
+        | "create_control_token"
+        | "revoke_control_token"
+        | "query_audit_log"
+        | "get_control_api"
+        | "list_automation_endpoints"
+        | "get_server_logs"
+        | "get_http_trace"
+        | "read_log_file"
+        | "tail_logs"
+        | "generate_diagnostic_bundle" => PermissionScope::Admin,
+        _ => return None,
+    };
+    Some(scope)
+}
+
+/// The scopes granted to the window `label`: its own grants, or else those
+/// of the longest matching prefix.
+pub fn granted(app: &AppHandle, label: &str) -> Vec<PermissionScope> {
+    if label == MAIN_LABEL {
+        return ALL_SCOPES.to_vec();
+    }
+    grants_for(&app.state::<ConfigStore>().get().permissions.grants, label)
+}
+
+fn grants_for(
+    grants: &BTreeMap<String, Vec<PermissionScope>>,
+    label: &str,
+) -> Vec<PermissionScope> {
+    if let Some(scopes) = grants.get(label) {
+        return scopes.clone();
+    }
+    grants
+        .iter()
+        .filter_map(|(pattern, scopes)| {
+            let prefix = pattern.strip_suffix('*')?;
+            label.starts_with(prefix).then_some((prefix.len(), scopes))
+        })
+        .max_by_key(|(len, _)| *len)
+        .map(|(_, scopes)| scopes.clone())
+        .unwrap_or_default()
+}
+
+/// Wraps the command handler so scoped commands are refused to windows
+/// without the scope.
+pub fn guarded<F>(handler: F) -> impl Fn(Invoke<Wry>) -> bool + Send + Sync + 'static
+where
+    F: Fn(Invoke<Wry>) -> bool + Send + Sync + 'static,
+{
+    move |invoke| {
+        let command = invoke.message.command();
+        let Some(scope) = required_scope(command) else {
+            if UNSCOPED.contains(&command) {
+                return handler(invoke);
+            }
+            log::warn!("Refused unknown command {}", command);
+            invoke
+                .resolver
+                .reject(format!("Unknown command {}", invoke.message.command()));
+            return true;
+        };
+        let webview = invoke.message.webview_ref();
+        let label = webview.label().to_string();
+        if granted(webview.app_handle(), &label).contains(&scope) {
+            return handler(invoke);
+        }
+        log::warn!(
+            "Refused {} to window {}, which lacks the {:?} scope",
+            invoke.message.command(),
+            label,
+            scope
+        );
+        invoke.resolver.reject(format!(
+            "The {} window may not call {}; it needs the {:?} permission",
+            label,
+            invoke.message.command(),
+            scope
+        ));
+        true
+    }
+}
+
+#[tauri::command]
+pub fn get_window_grants(app_handle: AppHandle) -> BTreeMap<String, Vec<PermissionScope>> {
+    app_handle.state::<ConfigStore>().get().permissions.grants
+}
+
+/// Grants `scopes` to the window `label`, or to every window whose label
+/// starts with it if it ends in `*`. No scopes removes its grants.
+#[tauri::command]
+pub fn set_window_grants(
+    app_handle: AppHandle,
+    label: String,
+    mut scopes: Vec<PermissionScope>,
+) -> Result<BTreeMap<String, Vec<PermissionScope>>, String> {
+    let valid = !label.is_empty()
+        && label
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '*'))
+        && label.find('*').map_or(true, |at| at == label.len() - 1);
+    if !valid {
+        return Err(format!("Invalid window label: {:?}", label));
+    }
+    if label == MAIN_LABEL {
+        return Err("The main window always has every permission".to_string());
+    }
+    scopes.sort();
+    scopes.dedup();
+    log::info!("Granting {:?} to window {}", scopes, label);
+    app_handle
+        .state::<ConfigStore>()
+        .update(|c| {
+            if scopes.is_empty() {
+                c.permissions.grants.remove(&label);
+            } else {
+                c.permissions.grants.insert(label.clone(), scopes);
+            }
+        })
+        .map(|c| c.permissions.grants)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The command names in `generate_handler!` in lib.rs.
+    fn handler_commands() -> Vec<&'static str> {
+        let lib = include_str!("lib.rs");
+        let start = lib
+            .find("generate_handler![")
+            .expect("no command handler in lib.rs");
+        let list = &lib[start + "generate_handler![".len()..];
+        let end = list.find(']').unwrap();
+        list[..end]
+            .split(',')
+            .map(str::trim)
+            .filter(|path| !path.is_empty())
+            .map(|path| path.rsplit("::").next().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn every_command_is_scoped_or_unscoped() {
+        let commands = handler_commands();
+        assert!(commands.len() > 100);
+        for command in commands {
+            let scoped = required_scope(command).is_some();
+            let unscoped = UNSCOPED.contains(&command);
+            assert!(
+                scoped != unscoped,
+                "{} must be either scoped or in UNSCOPED, not {}",
+                command,
+                if scoped { "both" } else { "neither" }
+            );
+        }
+    }
+
+    #[test]
+    fn unscoped_commands_exist() {
+        let commands = handler_commands();
+        for command in UNSCOPED {
+            assert!(commands.contains(command), "{} is not a command", command);
+        }
+    }
+
+    #[test]
+    fn unknown_commands_are_not_allowed() {
+        assert_eq!(required_scope("no_such_command"), None);
+        assert!(!UNSCOPED.contains(&"no_such_command"));
+    }
+
+    #[test]
+    fn sensitive_commands_are_scoped() {
+        for (command, scope) in [
+            ("open_with_default_app", PermissionScope::Files),
+            ("extract_archive", PermissionScope::Files),
+            ("start_download", PermissionScope::Files),
+            ("read_clipboard", PermissionScope::Secrets),
+            ("get_secret", PermissionScope::Secrets),
+            ("api_request", PermissionScope::Api),
+            ("set_setting", PermissionScope::Settings),
+            ("create_schedule", PermissionScope::Settings),
+            ("start_proxy_bridge", PermissionScope::Profiles),
+            ("list_proxy_bridges", PermissionScope::Profiles),
+            ("read_log_file", PermissionScope::Admin),
+            ("kill_port_owner", PermissionScope::Processes),
+            ("open_window", PermissionScope::Windows),
+            ("send_to_window", PermissionScope::Windows),
+        ] {
+            assert_eq!(required_scope(command), Some(scope), "{}", command);
+        }
+    }
+
+    #[test]
+    fn grants_prefer_exact_labels_then_longest_prefix() {
+        let grants = BTreeMap::from([
+            ("profile-*".to_string(), vec![PermissionScope::Api]),
+            (
+                "profile-detail-*".to_string(),
+                vec![PermissionScope::Profiles],
+            ),
+            ("profile-detail-1".to_string(), vec![PermissionScope::Files]),
+        ]);
+        assert_eq!(
+            grants_for(&grants, "profile-detail-1"),
+            vec![PermissionScope::Files]
+        );
+        assert_eq!(
+            grants_for(&grants, "profile-detail-2"),
+            vec![PermissionScope::Profiles]
+        );
+        assert_eq!(
+            grants_for(&grants, "profile-list"),
+            vec![PermissionScope::Api]
+        );
+        assert!(grants_for(&grants, "other").is_empty());
+    }
+}
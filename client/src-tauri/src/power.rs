@@ -0,0 +1,283 @@
+//! Sleep and wake of the machine, as far as the backend connection cares.
+//!
+//! After a sleep the sockets to the backend are often dead while the last
+//! health probe still says healthy. While the machine suspends the
+//! supervisor stops probing, and once it resumes the server is probed right
+//! away and the backend websocket is dialed again. Both are emitted, as
+//! `power://suspended` and `power://resumed`.
+//!
+//! Linux hears of both from logind and Windows from its power
+//! notifications. On every platform a wall clock that jumped ahead of the
+//! monotonic one, which stands still during sleep on Linux and macOS, counts
+//! as a resume as well.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::server::bridge::BackendBridge;
+use crate::server::health;
+use crate::server::status::ServerStatus;
+
+/// How often the clocks are compared.
+const CLOCK_INTERVAL: Duration = Duration::from_secs(5);
+/// How far the wall clock has to get ahead to count as a sleep; clock
+/// adjustments are smaller than this.
+const CLOCK_JUMP: Duration = Duration::from_secs(30);
+/// Further resume notices within this long belong to the same wake-up.
+const RESUME_DEBOUNCE: Duration = Duration::from_secs(30);
+const RESUME_PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Where a suspend or resume was heard of.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PowerSource {
+    #[cfg(target_os = "linux")]
+    Logind,
+    #[cfg(windows)]
+    Windows,
+    /// The wall clock jumped ahead.
+    Clock,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PowerStatus {
+    pub sleeping: bool,
+    pub last_suspend_at: Option<DateTime<Utc>>,
+    pub last_resume_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SuspendedPayload {
+    source: PowerSource,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ResumedPayload {
+    source: PowerSource,
+    slept_secs: Option<u64>,
+    /// What the probe right after waking found.
+    healthy: bool,
+}
+
+#[derive(Default)]
+struct Inner {
+    last_suspend_at: Option<DateTime<Utc>>,
+    last_resume_at: Option<DateTime<Utc>>,
+    last_resume: Option<Instant>,
+}
+
+/// Managed state tracking whether the machine is going to sleep.
+#[derive(Default)]
+pub struct PowerMonitor {
+    sleeping: AtomicBool,
+    inner: Mutex<Inner>,
+}
+
+impl PowerMonitor {
+    /// Whether the machine is suspending; nothing the server does now says
+    /// much.
+    pub fn is_sleeping(&self) -> bool {
+        self.sleeping.load(Ordering::SeqCst)
+    }
+
+    fn status(&self) -> PowerStatus {
+        let inner = self.inner.lock().unwrap();
+        PowerStatus {
+            sleeping: self.is_sleeping(),
+            last_suspend_at: inner.last_suspend_at,
+            last_resume_at: inner.last_resume_at,
+        }
+    }
+}
+
+fn suspended(app: &AppHandle, source: PowerSource) {
+    let monitor = app.state::<PowerMonitor>();
+    if monitor.sleeping.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    monitor.inner.lock().unwrap().last_suspend_at = Some(Utc::now());
+    log::info!("System is suspending ({:?}), pausing server checks", source);
+    let _ = app.emit("power://suspended", SuspendedPayload { source });
+}
+
+fn resumed(app: &AppHandle, source: PowerSource, slept: Option<Duration>) {
+    let monitor = app.state::<PowerMonitor>();
+    let slept = {
+        let mut inner = monitor.inner.lock().unwrap();
+        if inner
+            .last_resume
+            .is_some_and(|at| at.elapsed() < RESUME_DEBOUNCE)
+        {
+            return;
+        }
+        inner.last_resume = Some(Instant::now());
+        inner.last_resume_at = Some(Utc::now());
+        slept.or_else(|| {
+            let since = inner.last_suspend_at.filter(|_| monitor.is_sleeping())?;
+            (Utc::now() - since).to_std().ok()
+        })
+    };
+    monitor.sleeping.store(false, Ordering::SeqCst);
+    log::info!(
+        "System resumed ({:?}) after {:?}, checking the server",
+        source,
+        slept.unwrap_or_default()
+    );
+
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        // The websocket may be dead without knowing it
+        app.state::<BackendBridge>().reconnect();
+        let report = health::check(&app, &health::health_url(&app), RESUME_PROBE_TIMEOUT).await;
+        app.state::<ServerStatus>().probed(&app, report.healthy);
+        let _ = app.emit(
+            "power://resumed",
+            ResumedPayload {
+                source,
+                slept_secs: slept.map(|slept| slept.as_secs()),
+                healthy: report.healthy,
+            },
+        );
+    });
+}
+
+/// Compares the clocks for the lifetime of the app.
+fn watch_clock(app: AppHandle) {
+    std::thread::spawn(move || {
+        let mut wall = SystemTime::now();
+        let mut monotonic = Instant::now();
+        loop {
+            std::thread::sleep(CLOCK_INTERVAL);
+            let wall_elapsed = wall.elapsed().unwrap_or_default();
+            let monotonic_elapsed = monotonic.elapsed();
+            wall = SystemTime::now();
+            monotonic = Instant::now();
+            let jump = wall_elapsed.saturating_sub(monotonic_elapsed);
+            if jump >= CLOCK_JUMP {
+                resumed(&app, PowerSource::Clock, Some(jump));
+            }
+        }
+    });
+}
+
+/// Follows logind's `PrepareForSleep`, sent before a suspend with `true`
+/// and after the resume with `false`.
+#[cfg(target_os = "linux")]
+async fn watch_logind(app: &AppHandle) -> zbus::Result<()> {
+    use futures_util::StreamExt;
+
+    let connection = zbus::Connection::system().await?;
+    let proxy = zbus::Proxy::new(
+        &connection,
+        "org.freedesktop.login1",
+        "/org/freedesktop/login1",
+        "org.freedesktop.login1.Manager",
+    )
+    .await?;
+    let mut signals = proxy.receive_signal("PrepareForSleep").await?;
+    while let Some(signal) = signals.next().await {
+        match signal.body().deserialize::<bool>() {
+            Ok(true) => suspended(app, PowerSource::Logind),
+            Ok(false) => resumed(app, PowerSource::Logind, None),
+            Err(e) => log::debug!("Ignoring malformed PrepareForSleep: {}", e),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(windows)]
+mod windows {
+    use std::sync::OnceLock;
+
+    use tauri::AppHandle;
+    use windows_sys::Win32::System::Power::{
+        PowerRegisterSuspendResumeNotification, DEVICE_NOTIFY_SUBSCRIBE_PARAMETERS,
+    };
+    use windows_sys::Win32::UI::WindowsAndMessaging::{
+        DEVICE_NOTIFY_CALLBACK, PBT_APMRESUMEAUTOMATIC, PBT_APMRESUMESUSPEND, PBT_APMSUSPEND,
+    };
+
+    use super::PowerSource;
+
+    static APP: OnceLock<AppHandle> = OnceLock::new();
+
+    unsafe extern "system" fn callback(
+        _context: *const core::ffi::c_void,
+        kind: u32,
+        _setting: *const core::ffi::c_void,
+    ) -> u32 {
+        if let Some(app) = APP.get() {
+            match kind {
+                PBT_APMSUSPEND => super::suspended(app, PowerSource::Windows),
+                PBT_APMRESUMEAUTOMATIC | PBT_APMRESUMESUSPEND => {
+                    super::resumed(app, PowerSource::Windows, None)
+                }
+                _ => {}
+            }
+        }
+        0
+    }
+
+    /// Registers for suspend and resume notifications, which arrive on a
+    /// system thread for the lifetime of the process.
+    pub fn register(app: &AppHandle) -> Result<(), String> {
+        if APP.set(app.clone()).is_err() {
+            return Ok(());
+        }
+        // Windows keeps using the parameters, so they live as long as the
+        // registration does
+        let parameters: &'static mut DEVICE_NOTIFY_SUBSCRIBE_PARAMETERS =
+            Box::leak(Box::new(DEVICE_NOTIFY_SUBSCRIBE_PARAMETERS {
+                Callback: Some(callback),
+                Context: std::ptr::null_mut(),
+            }));
+        let mut registration = std::ptr::null_mut();
+        let error = unsafe {
+            PowerRegisterSuspendResumeNotification(
+                DEVICE_NOTIFY_CALLBACK,
+                parameters as *mut DEVICE_NOTIFY_SUBSCRIBE_PARAMETERS as _,
+                &mut registration,
+            )
+        };
+        if error != 0 {
+            return Err(format!(
+                "Failed to register for power notifications: error {}",
+                error
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Starts listening for suspend and resume.
+pub fn spawn(app: &AppHandle) {
+    watch_clock(app.clone());
+
+    #[cfg(target_os = "linux")]
+    {
+        let app = app.clone();
+        tauri::async_runtime::spawn(async move {
+            if let Err(e) = watch_logind(&app).await {
+                log::info!("Not following logind sleep signals: {}", e);
+            }
+        });
+    }
+
+    #[cfg(windows)]
+    if let Err(e) = windows::register(app) {
+        log::warn!("{}", e);
+    }
+}
+
+#[tauri::command]
+pub fn get_power_status(monitor: tauri::State<'_, PowerMonitor>) -> PowerStatus {
+    monitor.status()
+}
@@ -0,0 +1,258 @@
+//! Remote debugging endpoints for Selenium, Playwright, Puppeteer and other
+//! automation frameworks to attach to a profile's browser.
+//!
+//! Only profiles on the allow-list in `automation` of the config get one,
+//! and only while it is enabled: their browser is launched with a remote
+//! debugging port on localhost, picked free at launch. Chromium builds
+//! speak CDP on it, Firefox builds such as Camoufox WebDriver BiDi. The
+//! endpoints of running browsers are kept in [`AutomationRegistry`]; one
+//! whose browser is gone is dropped the next time it is looked at. A
+//! browser started without a port cannot get one, so it has to be
+//! relaunched for that.
+
+use std::collections::HashMap;
+use std::net::{Ipv4Addr, SocketAddr, TcpListener};
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+
+use super::registry::ProfileRegistry;
+use super::validate_id;
+use crate::config::ConfigStore;
+use crate::server::logs::now_millis;
+
+/// How long a freshly launched browser gets to open its port.
+const READY_TIMEOUT: Duration = Duration::from_secs(15);
+const READY_POLL: Duration = Duration::from_millis(250);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AutomationProtocol {
+    /// Chrome DevTools Protocol, from Chromium builds.
+    Cdp,
+    /// WebDriver BiDi, from Firefox builds.
+    WebdriverBidi,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AutomationEndpoint {
+    pub profile_id: String,
+    /// The browser the endpoint belongs to.
+    pub pid: u32,
+    pub port: u16,
+    pub protocol: AutomationProtocol,
+    /// What to hand the framework, e.g. Playwright's `connectOverCDP` or
+    /// Puppeteer's `browserWSEndpoint`; known once the browser listens.
+    pub ws_endpoint: Option<String>,
+    /// Milliseconds since the Unix epoch.
+    pub created_at: u64,
+}
+
+/// Managed state holding the endpoints of running browsers.
+#[derive(Default)]
+pub struct AutomationRegistry {
+    endpoints: Mutex<HashMap<String, AutomationEndpoint>>,
+}
+
+impl AutomationRegistry {
+    fn insert(&self, endpoint: AutomationEndpoint) {
+        self.endpoints
+            .lock()
+            .unwrap()
+            .insert(endpoint.profile_id.clone(), endpoint);
+    }
+
+    /// Drops the endpoints whose browser is no longer running.
+    fn prune(&self, app: &AppHandle) {
+        let running: HashMap<String, u32> = app
+            .state::<ProfileRegistry>()
+            .list()
+            .into_iter()
+            .map(|profile| (profile.id, profile.pid))
+            .collect();
+        self.endpoints
+            .lock()
+            .unwrap()
+            .retain(|id, endpoint| running.get(id) == Some(&endpoint.pid));
+    }
+
+    fn get(&self, app: &AppHandle, id: &str) -> Option<AutomationEndpoint> {
+        self.prune(app);
+        self.endpoints.lock().unwrap().get(id).cloned()
+    }
+
+    pub fn list(&self, app: &AppHandle) -> Vec<AutomationEndpoint> {
+        self.prune(app);
+        let mut list: Vec<AutomationEndpoint> =
+            self.endpoints.lock().unwrap().values().cloned().collect();
+        list.sort_by_key(|endpoint| endpoint.created_at);
+        list
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct VersionInfo {
+    web_socket_debugger_url: String,
+}
+
+/// Whether `profile_id` is allowed an endpoint.
+pub fn is_exposed(app: &AppHandle, profile_id: &str) -> bool {
+    let automation = app.state::<ConfigStore>().get().automation;
+    automation.enabled
+        && automation
+            .exposed_profiles
+            .iter()
+            .any(|id| id == profile_id)
+}
+
+/// A port on localhost nothing listens on right now.
+pub fn free_port() -> Result<u16, String> {
+    TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+        .and_then(|listener| listener.local_addr())
+        .map(|addr| addr.port())
+        .map_err(|e| format!("Failed to find a free port for remote debugging: {}", e))
+}
+
+/// Command line flags opening the remote debugging `port`.
+pub fn args(executable: &Path, port: u16) -> Vec<String> {
+    if super::gpu::is_chromium(executable) {
+        vec![
+            format!("--remote-debugging-port={}", port),
+            "--remote-debugging-address=127.0.0.1".to_string(),
+        ]
+    } else {
+        vec!["--remote-debugging-port".to_string(), port.to_string()]
+    }
+}
+
+/// Records the endpoint of a browser just launched with [`args`].
+pub fn register(app: &AppHandle, profile_id: &str, pid: u32, executable: &Path, port: u16) {
+    let protocol = if super::gpu::is_chromium(executable) {
+        AutomationProtocol::Cdp
+    } else {
+        AutomationProtocol::WebdriverBidi
+    };
+    log::info!(
+        "Profile {} exposes remote debugging on port {}",
+        profile_id,
+        port
+    );
+    app.state::<AutomationRegistry>()
+        .insert(AutomationEndpoint {
+            profile_id: profile_id.to_string(),
+            pid,
+            port,
+            protocol,
+            ws_endpoint: None,
+            created_at: now_millis(),
+        });
+}
+
+/// The WebSocket URL, once the browser answers on its port.
+async fn ws_endpoint(endpoint: &AutomationEndpoint) -> Option<String> {
+    let addr = SocketAddr::from((Ipv4Addr::LOCALHOST, endpoint.port));
+    match endpoint.protocol {
+        AutomationProtocol::WebdriverBidi => tokio::net::TcpStream::connect(addr)
+            .await
+            .ok()
+            .map(|_| format!("ws://{}/session", addr)),
+        // Chromium names its socket after a fresh browser ID
+        AutomationProtocol::Cdp => {
+            let url = format!("http://{}/json/version", addr);
+            let response = reqwest::Client::new()
+                .get(url)
+                .timeout(READY_POLL * 4)
+                .send()
+                .await
+                .ok()?;
+            let info: VersionInfo = response.json().await.ok()?;
+            Some(info.web_socket_debugger_url)
+        }
+    }
+}
+
+/// Waits for the browser to listen and fills in its WebSocket URL.
+async fn resolve(app: &AppHandle, mut endpoint: AutomationEndpoint) -> AutomationEndpoint {
+    if endpoint.ws_endpoint.is_some() {
+        return endpoint;
+    }
+    let started = Instant::now();
+    while started.elapsed() < READY_TIMEOUT {
+        if let Some(url) = ws_endpoint(&endpoint).await {
+            endpoint.ws_endpoint = Some(url);
+            app.state::<AutomationRegistry>().insert(endpoint.clone());
+            break;
+        }
+        tokio::time::sleep(READY_POLL).await;
+    }
+    endpoint
+}
+
+/// The endpoint to attach automation to `profile_id`, launching the
+/// profile first unless it is running or `launch` is `false`.
+#[tauri::command]
+pub async fn get_automation_endpoint(
+    app_handle: AppHandle,
+    profile_id: String,
+    launch: Option<bool>,
+    headless: Option<bool>,
+) -> Result<AutomationEndpoint, String> {
+    validate_id(&profile_id)?;
+    if !is_exposed(&app_handle, &profile_id) {
+        return Err(format!(
+            "Profile {} is not on the automation allow-list",
+            profile_id
+        ));
+    }
+    let registry = app_handle.state::<AutomationRegistry>();
+    if let Some(endpoint) = registry.get(&app_handle, &profile_id) {
+        return Ok(resolve(&app_handle, endpoint).await);
+    }
+    if app_handle.state::<ProfileRegistry>().contains(&profile_id) {
+        return Err(format!(
+            "Profile {} was launched without remote debugging; stop it and ask again",
+            profile_id
+        ));
+    }
+    if !launch.unwrap_or(true) {
+        return Err(format!("Profile {} is not running", profile_id));
+    }
+
+    super::launch_profile(app_handle.clone(), profile_id.clone(), headless).await?;
+    let endpoint = registry
+        .get(&app_handle, &profile_id)
+        .ok_or_else(|| format!("Profile {} exited right after launching", profile_id))?;
+    Ok(resolve(&app_handle, endpoint).await)
+}
+
+#[tauri::command]
+pub fn list_automation_endpoints(app_handle: AppHandle) -> Vec<AutomationEndpoint> {
+    app_handle.state::<AutomationRegistry>().list(&app_handle)
+}
+
+/// Adds `profile_id` to the allow-list or takes it off. A running browser
+/// keeps its endpoint until it is restarted.
+#[tauri::command]
+pub fn set_profile_automation(
+    app_handle: AppHandle,
+    profile_id: String,
+    exposed: bool,
+) -> Result<Vec<String>, String> {
+    validate_id(&profile_id)?;
+    app_handle
+        .state::<ConfigStore>()
+        .update(|c| {
+            let profiles = &mut c.automation.exposed_profiles;
+            profiles.retain(|id| *id != profile_id);
+            if exposed {
+                profiles.push(profile_id.clone());
+                profiles.sort();
+            }
+        })
+        .map(|c| c.automation.exposed_profiles)
+}
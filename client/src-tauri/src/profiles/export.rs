@@ -0,0 +1,553 @@
+//! Profiles packed into one portable archive, to move them to another
+//! machine or keep them aside.
+//!
+//! An archive is a zip like a backup (see [`crate::backup`]): a readable
+//! `manifest.json` under an [`ARCHIVE_VERSION`] lists every profile with
+//! its files, each with size and SHA-256. Per profile it holds the
+//! backend's record as `profiles/<id>/profile.json` and, unless left out,
+//! the browser's user-data directory under `profiles/<id>/data/`. With a
+//! password every file but the manifest is AES-256 encrypted. The backend's
+//! own files in a profile directory, such as its encrypted record, stay out
+//! both ways. Both ways report progress, as `profile-export://progress` and
+//! `profile-import://progress`.
+//!
+//! Importing creates each profile anew in the backend, which gives it a new
+//! ID and fingerprint, and unpacks its user data into the new profile's
+//! directory.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tauri::{AppHandle, Emitter, Manager};
+use zip::write::SimpleFileOptions;
+use zip::{AesMode, CompressionMethod, ZipArchive, ZipWriter};
+
+use super::registry::ProfileRegistry;
+use crate::backup::pack::{self, Kind};
+use crate::backup::{self, BackupFile};
+use crate::server::client::{self, RequestOptions};
+
+/// Bumped whenever the layout changes in a way older versions cannot read.
+pub const ARCHIVE_VERSION: u32 = 1;
+const KIND: Kind = Kind {
+    name: "profile archive",
+    format: "nyx-profiles",
+    version: ARCHIVE_VERSION,
+    verb: "import",
+};
+const RECORD_FILE: &str = "profile.json";
+/// Files the backend keeps in a profile directory, which are its business
+/// and never part of the user data.
+const BACKEND_FILES: &[&str] = &["profile.enc"];
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ExportOptions {
+    pub password: Option<String>,
+    /// Only the backend's records, without cookies, history and the rest
+    /// of the browser's data.
+    pub skip_user_data: bool,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ArchivedProfile {
+    pub id: String,
+    pub name: String,
+    pub files: Vec<BackupFile>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ArchiveManifest {
+    pub format: String,
+    pub schema_version: u32,
+    pub app_version: String,
+    pub created_at: String,
+    pub encrypted: bool,
+    pub profiles: Vec<ArchivedProfile>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportSummary {
+    pub path: String,
+    pub profiles: usize,
+    pub files: usize,
+    pub bytes: u64,
+    pub encrypted: bool,
+    /// Files that could not be read.
+    pub skipped: Vec<String>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportedArchiveProfile {
+    /// The ID in the archive.
+    pub old_id: String,
+    pub new_id: String,
+    pub name: String,
+    pub files: usize,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ArchiveImportSummary {
+    pub imported: Vec<ImportedArchiveProfile>,
+    /// Profiles that could not be imported, with the reason.
+    pub failed: Vec<String>,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ProgressPayload {
+    done_bytes: u64,
+    total_bytes: u64,
+    profiles_done: usize,
+    profiles_total: usize,
+    /// Name of the profile being worked on.
+    current: Option<String>,
+}
+
+struct Progress<'a> {
+    app: &'a AppHandle,
+    event: &'static str,
+    payload: ProgressPayload,
+    emitted: Instant,
+}
+
+impl Progress<'_> {
+    fn emit(&mut self, force: bool) {
+        if force || self.emitted.elapsed() >= crate::PROGRESS_INTERVAL {
+            self.emitted = Instant::now();
+            let _ = self.app.emit(self.event, self.payload.clone());
+        }
+    }
+}
+
+/// A profile to export: the backend's record and where its data lives.
+struct Source {
+    id: String,
+    name: String,
+    record: Value,
+    data_dir: Option<PathBuf>,
+}
+
+async fn fetch_record(app: &AppHandle, id: &str) -> Result<Value, String> {
+    let response = client::forward(
+        app,
+        "GET",
+        &format!("/api/profiles/{}", id),
+        None,
+        HashMap::new(),
+        RequestOptions::default(),
+    )
+    .await?;
+    if response.status == 404 {
+        return Err(format!("Profile {} not found", id));
+    }
+    if !response.ok {
+        return Err(format!(
+            "Failed to load profile {}: HTTP {}",
+            id, response.status
+        ));
+    }
+    Ok(response.body)
+}
+
+fn write_archive(
+    progress: &mut Progress,
+    dest: &Path,
+    sources: &[Source],
+    password: Option<&str>,
+    app_version: String,
+) -> io::Result<(ArchiveManifest, Vec<String>)> {
+    let mut zip = ZipWriter::new(File::create(dest)?);
+    let plain = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    let options = match password {
+        Some(password) => plain.with_aes_encryption(AesMode::Aes256, password),
+        None => plain,
+    };
+
+    let mut profiles = Vec::new();
+    let mut skipped = Vec::new();
+    for source in sources {
+        progress.payload.current = Some(source.name.clone());
+        progress.emit(true);
+        let prefix = format!("profiles/{}", source.id);
+        let mut files = Vec::new();
+
+        let record = serde_json::to_vec_pretty(&source.record)?;
+        let name = format!("{}/{}", prefix, RECORD_FILE);
+        files.push(pack::add_bytes(&mut zip, name, &record, options)?);
+
+        let mut relatives = Vec::new();
+        if let Some(dir) = &source.data_dir {
+            backup::walk(dir, Path::new(""), &mut relatives);
+        }
+        for relative in relatives
+            .into_iter()
+            .filter(|relative| !is_backend_file(relative))
+        {
+            let dir = source.data_dir.as_deref().unwrap_or(Path::new(""));
+            let name = backup::archive_name(&format!("{}/data", prefix), &relative);
+            let Some(file) = pack::add_file(&mut zip, name.clone(), &dir.join(&relative), options)?
+            else {
+                skipped.push(name);
+                continue;
+            };
+            progress.payload.done_bytes += file.size;
+            progress.emit(false);
+            files.push(file);
+        }
+
+        profiles.push(ArchivedProfile {
+            id: source.id.clone(),
+            name: source.name.clone(),
+            files,
+        });
+        progress.payload.profiles_done += 1;
+    }
+
+    let manifest = ArchiveManifest {
+        format: KIND.format.to_string(),
+        schema_version: ARCHIVE_VERSION,
+        app_version,
+        created_at: chrono::Utc::now().to_rfc3339(),
+        encrypted: password.is_some(),
+        profiles,
+    };
+    // Left readable so an import can list the profiles before asking for
+    // a password
+    pack::finish(zip, &manifest, plain)?;
+    progress.payload.current = None;
+    progress.emit(true);
+    Ok((manifest, skipped))
+}
+
+/// Whether `relative` in a profile directory is one of [`BACKEND_FILES`].
+fn is_backend_file(relative: &Path) -> bool {
+    BACKEND_FILES.iter().any(|name| relative == Path::new(name))
+}
+
+/// Unpacks a profile's user data into `dest`, checking every file against
+/// the manifest.
+fn unpack_data(
+    archive: &mut ZipArchive<File>,
+    profile: &ArchivedProfile,
+    dest: &Path,
+    password: Option<&str>,
+    progress: &mut Progress,
+) -> Result<usize, String> {
+    let prefix = format!("profiles/{}/data/", profile.id);
+    let mut unpacked = 0;
+    for file in &profile.files {
+        let Some(relative) = file.path.strip_prefix(&prefix) else {
+            continue;
+        };
+        if is_backend_file(Path::new(relative)) {
+            log::warn!("Leaving {} out of the import", file.path);
+            continue;
+        }
+        let target = crate::archive::contained(dest, Path::new(relative))
+            .ok_or_else(|| format!("Archive entry {} points outside its profile", file.path))?;
+        pack::extract_file(archive, file, &target, password, &KIND)?;
+        unpacked += 1;
+        progress.payload.done_bytes += file.size;
+        progress.emit(false);
+    }
+    Ok(unpacked)
+}
+
+async fn create_profile(app: &AppHandle, record: &Value, name: &str) -> Result<String, String> {
+    let body = json!({
+        "name": name,
+        "config": record.get("config").cloned().unwrap_or_else(|| json!({})),
+    });
+    let response = client::forward(
+        app,
+        "POST",
+        "/api/profiles/",
+        Some(body),
+        HashMap::new(),
+        RequestOptions::default(),
+    )
+    .await?;
+    if !response.ok {
+        return Err(response
+            .body
+            .get("detail")
+            .and_then(Value::as_str)
+            .map(str::to_string)
+            .unwrap_or_else(|| format!("HTTP {}", response.status)));
+    }
+    response
+        .body
+        .get("id")
+        .and_then(Value::as_str)
+        .map(str::to_string)
+        .ok_or_else(|| "The backend returned no profile ID".to_string())
+}
+
+/// Writes the profiles `ids` with their user data to an archive at `dest`.
+/// Their browsers have to be closed, so the data is not changing meanwhile.
+#[tauri::command]
+pub async fn export_profiles(
+    app_handle: AppHandle,
+    ids: Vec<String>,
+    dest: PathBuf,
+    options: Option<ExportOptions>,
+) -> Result<ExportSummary, String> {
+    let options = options.unwrap_or_default();
+    let password = options.password.filter(|password| !password.is_empty());
+    if ids.is_empty() {
+        return Err("No profiles to export".to_string());
+    }
+    if let Some(dir) = dest
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty() && !dir.is_dir())
+    {
+        return Err(format!("Folder does not exist: {}", dir.display()));
+    }
+    let running: Vec<String> = app_handle
+        .state::<ProfileRegistry>()
+        .list()
+        .into_iter()
+        .map(|profile| profile.id)
+        .filter(|id| ids.contains(id))
+        .collect();
+    if !running.is_empty() {
+        return Err(format!(
+            "Close the running profiles {} before exporting them",
+            running.join(", ")
+        ));
+    }
+
+    let profiles_root = crate::data_dir::data_dir(&app_handle)?.join("profiles");
+    let mut sources = Vec::with_capacity(ids.len());
+    for id in ids {
+        super::validate_id(&id)?;
+        let record = fetch_record(&app_handle, &id).await?;
+        let data_dir =
+            Some(profiles_root.join(&id)).filter(|dir| !options.skip_user_data && dir.is_dir());
+        sources.push(Source {
+            name: record
+                .get("name")
+                .and_then(Value::as_str)
+                .unwrap_or(&id)
+                .to_string(),
+            id,
+            record,
+            data_dir,
+        });
+    }
+
+    let app_version = app_handle.package_info().version.to_string();
+    let encrypted = password.is_some();
+    let app = app_handle.clone();
+    let target = dest.clone();
+    let (manifest, skipped) = tauri::async_runtime::spawn_blocking(move || {
+        let mut progress = Progress {
+            app: &app,
+            event: "profile-export://progress",
+            payload: ProgressPayload {
+                done_bytes: 0,
+                total_bytes: sources
+                    .iter()
+                    .filter_map(|source| source.data_dir.as_deref())
+                    .map(crate::browsers::dir_size)
+                    .sum(),
+                profiles_done: 0,
+                profiles_total: sources.len(),
+                current: None,
+            },
+            emitted: Instant::now(),
+        };
+        write_archive(
+            &mut progress,
+            &target,
+            &sources,
+            password.as_deref(),
+            app_version,
+        )
+    })
+    .await
+    .map_err(|e| e.to_string())?
+    .map_err(|e| {
+        let _ = std::fs::remove_file(&dest);
+        format!("Failed to write the profile archive: {}", e)
+    })?;
+
+    let files: Vec<&BackupFile> = manifest
+        .profiles
+        .iter()
+        .flat_map(|profile| &profile.files)
+        .collect();
+    log::info!(
+        "Exported {} profiles to {}",
+        manifest.profiles.len(),
+        dest.display()
+    );
+    Ok(ExportSummary {
+        path: dest.display().to_string(),
+        profiles: manifest.profiles.len(),
+        files: files.len(),
+        bytes: files.iter().map(|file| file.size).sum(),
+        encrypted,
+        skipped,
+    })
+}
+
+/// Lists the profiles in the archive at `path` without importing them.
+#[tauri::command]
+pub async fn inspect_profile_archive(path: PathBuf) -> Result<ArchiveManifest, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        pack::read_manifest(&mut pack::open_archive(&path, &KIND)?, &KIND)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Imports every profile of the archive at `path` as a new profile. A
+/// profile that fails is removed again and the rest go on.
+#[tauri::command]
+pub async fn import_profile_archive(
+    app_handle: AppHandle,
+    path: PathBuf,
+    password: Option<String>,
+) -> Result<ArchiveImportSummary, String> {
+    let password = password.filter(|password| !password.is_empty());
+    let (manifest, records) = {
+        let (path, password) = (path.clone(), password.clone());
+        tauri::async_runtime::spawn_blocking(move || {
+            let mut archive = pack::open_archive(&path, &KIND)?;
+            let manifest: ArchiveManifest = pack::read_manifest(&mut archive, &KIND)?;
+            let password = match (manifest.encrypted, password) {
+                (true, None) => {
+                    return Err("This archive is encrypted; enter its password".to_string())
+                }
+                (true, password) => password,
+                (false, _) => None,
+            };
+            let mut records = Vec::with_capacity(manifest.profiles.len());
+            for profile in &manifest.profiles {
+                let name = format!("profiles/{}/{}", profile.id, RECORD_FILE);
+                let entry = pack::open_entry(&mut archive, &name, password.as_deref(), &KIND)?;
+                let record: Value = serde_json::from_reader(entry)
+                    .map_err(|e| format!("Unreadable {} in the archive: {}", name, e))?;
+                records.push(record);
+            }
+            Ok((manifest, records))
+        })
+        .await
+        .map_err(|e| e.to_string())??
+    };
+
+    let total_bytes = manifest
+        .profiles
+        .iter()
+        .flat_map(|profile| &profile.files)
+        .map(|file| file.size)
+        .sum();
+    let mut summary = ArchiveImportSummary {
+        imported: Vec::new(),
+        failed: Vec::new(),
+    };
+    let mut done_bytes = 0;
+    let profiles_total = manifest.profiles.len();
+    for (index, (profile, record)) in manifest.profiles.into_iter().zip(records).enumerate() {
+        // The ID comes from the backend, which may be one configured elsewhere
+        let created = create_profile(&app_handle, &record, &profile.name)
+            .await
+            .and_then(|id| super::validate_id(&id).map(|()| id));
+        let new_id = match created {
+            Ok(id) => id,
+            Err(e) => {
+                summary.failed.push(format!("{}: {}", profile.name, e));
+                continue;
+            }
+        };
+        let dest = super::profile_dir(&app_handle, &new_id);
+        let (app, path, password) = (app_handle.clone(), path.clone(), password.clone());
+        let archived = profile.clone();
+        let unpacked = tauri::async_runtime::spawn_blocking(move || {
+            let dest = dest?;
+            let mut progress = Progress {
+                app: &app,
+                event: "profile-import://progress",
+                payload: ProgressPayload {
+                    done_bytes,
+                    total_bytes,
+                    profiles_done: index,
+                    profiles_total,
+                    current: Some(archived.name.clone()),
+                },
+                emitted: Instant::now(),
+            };
+            progress.emit(true);
+            let mut archive = pack::open_archive(&path, &KIND)?;
+            let unpacked = unpack_data(
+                &mut archive,
+                &archived,
+                &dest,
+                password.as_deref(),
+                &mut progress,
+            );
+            if unpacked.is_err() {
+                let _ = std::fs::remove_dir_all(&dest);
+            }
+            unpacked
+        })
+        .await
+        .map_err(|e| e.to_string())
+        .and_then(|result| result);
+        done_bytes += profile.files.iter().map(|file| file.size).sum::<u64>();
+
+        match unpacked {
+            Ok(files) => summary.imported.push(ImportedArchiveProfile {
+                old_id: profile.id,
+                new_id,
+                name: profile.name,
+                files,
+            }),
+            Err(e) => {
+                // Half a profile helps nobody
+                let removed = client::forward(
+                    &app_handle,
+                    "DELETE",
+                    &format!("/api/profiles/{}", new_id),
+                    None,
+                    HashMap::new(),
+                    RequestOptions::default(),
+                )
+                .await;
+                if let Err(delete) = removed {
+                    log::warn!("Failed to remove the half-imported {}: {}", new_id, delete);
+                }
+                summary.failed.push(format!("{}: {}", profile.name, e));
+            }
+        }
+    }
+    let _ = app_handle.emit(
+        "profile-import://progress",
+        ProgressPayload {
+            done_bytes: total_bytes,
+            total_bytes,
+            profiles_done: profiles_total,
+            profiles_total,
+            current: None,
+        },
+    );
+    log::info!(
+        "Imported {} of {} profiles from {}",
+        summary.imported.len(),
+        profiles_total,
+        path.display()
+    );
+    Ok(summary)
+}
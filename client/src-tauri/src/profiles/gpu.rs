@@ -0,0 +1,141 @@
+//! Hardware acceleration of profile browsers, for headless and VM users
+//! whose GPU driver crashes the browser.
+//!
+//! Each profile uses its own [`GpuConfig`] if it has one and the global one
+//! from [`BrowserConfig`](crate::config::BrowserConfig) otherwise. Firefox
+//! builds such as Camoufox get it as prefs in `user.js`; Chromium builds get
+//! it as command line flags.
+
+use std::path::Path;
+
+use serde::Serialize;
+use tauri::{AppHandle, Manager};
+
+use super::validate_id;
+use crate::config::{AngleBackend, ConfigStore, GpuConfig, GpuMode};
+
+/// The settings `profile_id` launches with.
+pub fn for_profile(app: &AppHandle, profile_id: &str) -> GpuConfig {
+    let browser = app.state::<ConfigStore>().get().browser;
+    browser
+        .profile_gpu
+        .get(profile_id)
+        .copied()
+        .unwrap_or(browser.gpu)
+}
+
+/// Whether `executable` is a Chromium build rather than a Firefox one.
+pub(crate) fn is_chromium(executable: &Path) -> bool {
+    executable
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .is_some_and(|stem| {
+            let stem = stem.to_ascii_lowercase();
+            stem.contains("chrom") || stem.contains("msedge") || stem.contains("brave")
+        })
+}
+
+/// Chromium flags for `gpu`; none for Firefox builds.
+pub fn args(executable: &Path, gpu: &GpuConfig) -> Vec<String> {
+    if !is_chromium(executable) {
+        return Vec::new();
+    }
+    let mut args: Vec<String> = match gpu.mode {
+        GpuMode::Auto => Vec::new(),
+        GpuMode::On => vec![
+            "--ignore-gpu-blocklist".to_string(),
+            "--enable-gpu-rasterization".to_string(),
+        ],
+        GpuMode::Off => vec!["--disable-gpu".to_string()],
+    };
+    if let Some(backend) = gpu.angle_backend {
+        let name = match backend {
+            AngleBackend::D3d11 => "d3d11",
+            AngleBackend::D3d9 => "d3d9",
+            AngleBackend::Gl => "gl",
+            AngleBackend::Vulkan => "vulkan",
+            AngleBackend::Metal => "metal",
+            AngleBackend::Swiftshader => "swiftshader",
+        };
+        args.push(format!("--use-angle={}", name));
+    }
+    args
+}
+
+/// Firefox prefs for `gpu`. Every pref is written even in `Auto`, with its
+/// default value, because Firefox keeps a pref once `user.js` has set it.
+pub fn prefs(gpu: &GpuConfig) -> Vec<(&'static str, String)> {
+    let on = gpu.mode == GpuMode::On;
+    let off = gpu.mode == GpuMode::Off;
+    let backend = gpu.angle_backend;
+    [
+        ("layers.acceleration.disabled", off),
+        ("layers.acceleration.force-enabled", on),
+        ("gfx.webrender.all", on),
+        ("gfx.webrender.software", off),
+        ("media.hardware-video-decoding.enabled", !off),
+        // ANGLE only exists on Windows, where it is D3D11 by default, D3D9
+        // as a fallback and WARP for software rendering
+        ("webgl.disable-angle", backend == Some(AngleBackend::Gl)),
+        (
+            "webgl.angle.force-d3d11",
+            backend == Some(AngleBackend::D3d11),
+        ),
+        ("webgl.angle.try-d3d11", backend != Some(AngleBackend::D3d9)),
+        (
+            "webgl.angle.force-warp",
+            backend == Some(AngleBackend::Swiftshader),
+        ),
+    ]
+    .into_iter()
+    .map(|(name, value)| (name, value.to_string()))
+    .collect()
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProfileGpu {
+    /// The profile's own setting, if it has one.
+    pub custom: Option<GpuConfig>,
+    /// What it launches with.
+    pub effective: GpuConfig,
+}
+
+fn profile_gpu(app: &AppHandle, profile_id: &str) -> ProfileGpu {
+    ProfileGpu {
+        custom: app
+            .state::<ConfigStore>()
+            .get()
+            .browser
+            .profile_gpu
+            .get(profile_id)
+            .copied(),
+        effective: for_profile(app, profile_id),
+    }
+}
+
+#[tauri::command]
+pub fn get_profile_gpu(app_handle: AppHandle, profile_id: String) -> Result<ProfileGpu, String> {
+    validate_id(&profile_id)?;
+    Ok(profile_gpu(&app_handle, &profile_id))
+}
+
+/// Gives the profile its own hardware acceleration setting, or with `None`
+/// goes back to the global one. Applies from the next launch.
+#[tauri::command]
+pub fn set_profile_gpu(
+    app_handle: AppHandle,
+    profile_id: String,
+    gpu: Option<GpuConfig>,
+) -> Result<ProfileGpu, String> {
+    validate_id(&profile_id)?;
+    app_handle.state::<ConfigStore>().update(|c| match gpu {
+        Some(gpu) => {
+            c.browser.profile_gpu.insert(profile_id.clone(), gpu);
+        }
+        None => {
+            c.browser.profile_gpu.remove(&profile_id);
+        }
+    })?;
+    Ok(profile_gpu(&app_handle, &profile_id))
+}
@@ -0,0 +1,385 @@
+//! Checks whether a profile's browser gives away this machine's real IP or
+//! DNS resolver despite its proxy.
+//!
+//! The profile's browser is started headless on a throwaway copy of its
+//! prefs and pointed at a page served from a local echo endpoint. The page
+//! asks the IP lookup service which address its HTTP traffic comes from,
+//! gathers WebRTC candidates against public STUN servers, and posts what it
+//! found back to the endpoint. Those addresses are compared with this
+//! machine's own public IP and the proxy's exit IP. The profile's own data
+//! directory is left alone, so the test also works while it is running.
+
+use std::net::{IpAddr, Ipv4Addr};
+use std::time::Duration;
+
+use percent_encoding::percent_decode_str;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use tauri::AppHandle;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+
+use super::{
+    executable, fetch_profile, gpu, proxy_url, spawn_browser, user_prefs, validate_id, USER_PREFS,
+};
+use crate::proxy::check::{self, GEO_ENDPOINT};
+use crate::proxy::{ProxyConfig, ProxyProtocol};
+
+/// How long the page gets to report back.
+const REPORT_TIMEOUT: Duration = Duration::from_secs(40);
+const LOOKUP_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long the page gathers WebRTC candidates.
+const ICE_TIMEOUT_MS: u64 = 8000;
+const STUN_SERVERS: &[&str] = &[
+    "stun:stun.l.google.com:19302",
+    "stun:stun.cloudflare.com:3478",
+];
+const MAX_REQUEST_BYTES: usize = 1024 * 1024;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LeakVerdict {
+    /// Nothing but the proxy's exit IP was seen.
+    Pass,
+    Leak,
+    /// The page did not report back, so nothing could be compared.
+    Inconclusive,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LeakTestReport {
+    pub profile_id: String,
+    pub verdict: LeakVerdict,
+    /// This machine's public IP, without the proxy.
+    pub real_ip: Option<String>,
+    /// The proxy's exit IP, asked through the proxy from here.
+    pub proxy_ip: Option<String>,
+    /// The IP the browser's HTTP traffic came from.
+    pub browser_ip: Option<String>,
+    /// Addresses in the browser's WebRTC candidates.
+    pub webrtc_ips: Vec<String>,
+    pub ip_leak: bool,
+    pub webrtc_leak: bool,
+    pub dns_leak: bool,
+    /// What leaked and why, for the UI to show.
+    pub issues: Vec<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct PageReport {
+    http_ip: Option<String>,
+    candidates: Vec<String>,
+    errors: Vec<String>,
+}
+
+fn proxy_config(url: &reqwest::Url) -> Option<ProxyConfig> {
+    let decode = |part: &str| percent_decode_str(part).decode_utf8_lossy().to_string();
+    Some(ProxyConfig {
+        host: url.host_str()?.trim_matches(['[', ']']).to_string(),
+        port: url.port_or_known_default()?,
+        protocol: ProxyProtocol::from_scheme(url.scheme())?,
+        username: Some(url.username()).filter(|u| !u.is_empty()).map(decode),
+        password: url.password().map(decode),
+    })
+}
+
+/// The test page. It reports to `/<token>/report` once both checks are done.
+fn page(token: &str) -> String {
+    let stun = serde_json::to_string(STUN_SERVERS).unwrap_or_default();
+    format!(
+        r#"<!doctype html>
+<meta charset="utf-8">
+<title>Nyx leak test</title>
+<script>
+const report = {{ httpIp: null, candidates: [], errors: [] }};
+async function http() {{
+  try {{
+    const response = await fetch("{geo}", {{ cache: "no-store" }});
+    report.httpIp = (await response.json()).ip;
+  }} catch (e) {{
+    report.errors.push("HTTP: " + e);
+  }}
+}}
+function webrtc() {{
+  return new Promise((resolve) => {{
+    try {{
+      const pc = new RTCPeerConnection({{ iceServers: [{{ urls: {stun} }}] }});
+      const done = () => {{ pc.close(); resolve(); }};
+      pc.onicecandidate = (event) => {{
+        if (!event.candidate) return done();
+        report.candidates.push(event.candidate.candidate);
+      }};
+      pc.createDataChannel("leak-test");
+      pc.createOffer().then((offer) => pc.setLocalDescription(offer));
+      setTimeout(done, {ice});
+    }} catch (e) {{
+      report.errors.push("WebRTC: " + e);
+      resolve();
+    }}
+  }});
+}}
+Promise.all([http(), webrtc()]).then(() =>
+  fetch("/{token}/report", {{ method: "POST", body: JSON.stringify(report) }}));
+</script>
+"#,
+        geo = GEO_ENDPOINT,
+        stun = stun,
+        ice = ICE_TIMEOUT_MS,
+        token = token
+    )
+}
+
+async fn respond(stream: &mut TcpStream, status: &str, content_type: &str, body: &str) {
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    );
+    let _ = stream.write_all(response.as_bytes()).await;
+}
+
+/// Reads one request, returning its request line and body.
+async fn read_request(stream: &mut TcpStream) -> Option<(String, Vec<u8>)> {
+    let mut buffer = Vec::new();
+    let mut chunk = [0u8; 8192];
+    let header_end = loop {
+        let read = stream.read(&mut chunk).await.ok()?;
+        if read == 0 || buffer.len() + read > MAX_REQUEST_BYTES {
+            return None;
+        }
+        buffer.extend_from_slice(&chunk[..read]);
+        if let Some(end) = buffer.windows(4).position(|window| window == b"\r\n\r\n") {
+            break end + 4;
+        }
+    };
+    let head = String::from_utf8_lossy(&buffer[..header_end]).to_string();
+    let length = head
+        .lines()
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("content-length"))
+        .and_then(|(_, value)| value.trim().parse::<usize>().ok())
+        .unwrap_or(0)
+        .min(MAX_REQUEST_BYTES);
+    let mut body = buffer[header_end..].to_vec();
+    while body.len() < length {
+        let read = stream.read(&mut chunk).await.ok()?;
+        if read == 0 {
+            break;
+        }
+        body.extend_from_slice(&chunk[..read]);
+    }
+    Some((head.lines().next()?.to_string(), body))
+}
+
+async fn serve(listener: TcpListener, token: String, reports: mpsc::Sender<PageReport>) {
+    let page = page(&token);
+    while let Ok((mut stream, _)) = listener.accept().await {
+        let Some((request_line, body)) = read_request(&mut stream).await else {
+            continue;
+        };
+        let mut parts = request_line.split_whitespace();
+        match (parts.next(), parts.next()) {
+            (Some("GET"), Some(path)) if path == format!("/{}", token) => {
+                respond(&mut stream, "200 OK", "text/html; charset=utf-8", &page).await;
+            }
+            (Some("POST"), Some(path)) if path == format!("/{}/report", token) => {
+                respond(&mut stream, "204 No Content", "text/plain", "").await;
+                let report = serde_json::from_slice(&body).unwrap_or_else(|e| PageReport {
+                    errors: vec![format!("Unreadable report: {}", e)],
+                    ..Default::default()
+                });
+                let _ = reports.send(report).await;
+            }
+            _ => respond(&mut stream, "404 Not Found", "text/plain", "").await,
+        }
+    }
+}
+
+/// Addresses in ICE candidate lines, e.g. `candidate:0 1 UDP 2122252543
+/// 192.0.2.1 54321 typ srflx ...`. Firefox hides host candidates behind
+/// `.local` names, which are skipped.
+fn candidate_ips(candidates: &[String]) -> Vec<IpAddr> {
+    let mut ips: Vec<IpAddr> = candidates
+        .iter()
+        .filter_map(|candidate| candidate.split_whitespace().nth(4)?.parse().ok())
+        .collect();
+    ips.sort();
+    ips.dedup();
+    ips
+}
+
+fn is_public(ip: &IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            !(ip.is_private() || ip.is_loopback() || ip.is_link_local() || ip.is_unspecified())
+        }
+        IpAddr::V6(ip) => {
+            let segments = ip.segments();
+            // Unique local and link-local addresses never leave the network
+            !(ip.is_loopback()
+                || ip.is_unspecified()
+                || (segments[0] & 0xfe00) == 0xfc00
+                || (segments[0] & 0xffc0) == 0xfe80)
+        }
+    }
+}
+
+fn judge(
+    profile_id: String,
+    proxy: Option<&ProxyConfig>,
+    real_ip: Option<String>,
+    proxy_ip: Option<String>,
+    page: Option<PageReport>,
+) -> LeakTestReport {
+    let mut issues = Vec::new();
+    let Some(page) = page else {
+        return LeakTestReport {
+            profile_id,
+            verdict: LeakVerdict::Inconclusive,
+            real_ip,
+            proxy_ip,
+            browser_ip: None,
+            webrtc_ips: Vec::new(),
+            ip_leak: false,
+            webrtc_leak: false,
+            dns_leak: false,
+            issues: vec!["The browser did not report back in time".to_string()],
+        };
+    };
+    issues.extend(page.errors);
+
+    let ip_leak = match (&page.http_ip, &real_ip) {
+        _ if proxy.is_none() => {
+            issues.push("The profile has no proxy, so every request uses the real IP".to_string());
+            true
+        }
+        (Some(browser), Some(real)) if browser == real => {
+            issues.push(format!("HTTP traffic left from the real IP {}", real));
+            true
+        }
+        _ => false,
+    };
+
+    let webrtc_ips = candidate_ips(&page.candidates);
+    let exposed: Vec<&IpAddr> = webrtc_ips
+        .iter()
+        .filter(|ip| is_public(ip) && proxy_ip.as_deref() != Some(ip.to_string().as_str()))
+        .collect();
+    let webrtc_leak = proxy.is_some() && !exposed.is_empty();
+    if webrtc_leak {
+        let exposed: Vec<String> = exposed.iter().map(ToString::to_string).collect();
+        issues.push(format!(
+            "WebRTC exposes {} outside the proxy",
+            exposed.join(", ")
+        ));
+    }
+
+    // Names are resolved by the proxy for HTTP proxies and by SOCKS5 with
+    // remote DNS, which launches always enable; SOCKS4 resolves them here.
+    // STUN servers are looked up here too whenever WebRTC goes around it.
+    let dns_leak = match proxy {
+        None => true,
+        Some(proxy) if proxy.protocol == ProxyProtocol::Socks4 => {
+            issues.push("SOCKS4 proxies resolve host names on this machine".to_string());
+            true
+        }
+        Some(_) if webrtc_leak => {
+            issues.push("WebRTC resolves STUN servers through this machine's resolver".to_string());
+            true
+        }
+        Some(_) => false,
+    };
+
+    let verdict = if ip_leak || webrtc_leak || dns_leak {
+        LeakVerdict::Leak
+    } else if page.http_ip.is_none() || real_ip.is_none() {
+        issues.push("The browser's IP could not be compared with the real one".to_string());
+        LeakVerdict::Inconclusive
+    } else {
+        LeakVerdict::Pass
+    };
+    LeakTestReport {
+        profile_id,
+        verdict,
+        real_ip,
+        proxy_ip,
+        browser_ip: page.http_ip,
+        webrtc_ips: webrtc_ips.iter().map(ToString::to_string).collect(),
+        ip_leak,
+        webrtc_leak,
+        dns_leak,
+        issues,
+    }
+}
+
+/// Runs the leak test for a profile and returns the verdict. Takes up to
+/// about a minute; failures to start the browser are errors, anything the
+/// test finds is in the report.
+#[tauri::command]
+pub async fn run_leak_test(
+    app_handle: AppHandle,
+    profile_id: String,
+) -> Result<LeakTestReport, String> {
+    validate_id(&profile_id)?;
+    let profile = fetch_profile(&app_handle, &profile_id).await?;
+    let executable = executable(&app_handle)?;
+    let proxy = proxy_url(&profile.config).and_then(|url| proxy_config(&url));
+
+    let (real_ip, proxy_ip) = tokio::join!(check::direct_ip(LOOKUP_TIMEOUT), async {
+        match &proxy {
+            Some(proxy) => check::exit_geo(proxy, LOOKUP_TIMEOUT)
+                .await
+                .map(|geo| Some(geo.ip)),
+            None => Ok(None),
+        }
+    });
+    let real_ip = real_ip.map_err(|e| log::warn!("{}", e)).ok();
+    let proxy_ip = proxy_ip.map_err(|e| log::warn!("{}", e)).ok().flatten();
+
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+        .await
+        .map_err(|e| format!("Failed to start the leak test endpoint: {}", e))?;
+    let port = listener.local_addr().map_err(|e| e.to_string())?.port();
+    let mut bytes = [0u8; 16];
+    rand::rngs::OsRng.fill_bytes(&mut bytes);
+    let token: String = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+    let (reports_tx, mut reports_rx) = mpsc::channel(1);
+    let server = tauri::async_runtime::spawn(serve(listener, token.clone(), reports_tx));
+
+    let dir = std::env::temp_dir().join(format!("nyx-leak-test-{}", token));
+    std::fs::create_dir_all(&dir)
+        .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    let result = async {
+        let gpu = gpu::for_profile(&app_handle, &profile_id);
+        let prefs = dir.join(USER_PREFS);
+        std::fs::write(&prefs, user_prefs(&profile.config, &gpu))
+            .map_err(|e| format!("Failed to write {}: {}", prefs.display(), e))?;
+        let url = format!("http://127.0.0.1:{}/{}", port, token);
+        let (mut child, tree) =
+            spawn_browser(&app_handle, &executable, &dir, true, Some(&url), &gpu, None)?;
+        log::info!("Running leak test for profile {}", profile_id);
+        let page = tokio::time::timeout(REPORT_TIMEOUT, reports_rx.recv())
+            .await
+            .ok()
+            .flatten();
+        tree.kill();
+        let _ = child.kill().await;
+        Ok::<_, String>(page)
+    }
+    .await;
+    server.abort();
+    let _ = std::fs::remove_dir_all(&dir);
+
+    let report = judge(profile_id, proxy.as_ref(), real_ip, proxy_ip, result?);
+    log::info!(
+        "Leak test for profile {}: {:?}",
+        report.profile_id,
+        report.verdict
+    );
+    Ok(report)
+}
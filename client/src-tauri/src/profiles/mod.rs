@@ -0,0 +1,406 @@
+//! Starts profile browsers directly from the shell instead of through the
+//! backend, and keeps track of the ones it started.
+//!
+//! The profile and its proxy still come from the backend API, which is told
+//! about every launch and stop so its own profile status stays in step.
+//! Lifecycle changes are emitted as `profile://launched`, `profile://stopped`
+//! and, for browsers that exit on their own, `profile://exited`. Profiles
+//! on the automation allow-list launch with a remote debugging endpoint;
+//! see [`automation`].
+
+pub mod automation;
+pub mod export;
+pub mod gpu;
+pub mod leak_test;
+pub mod registry;
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tauri::{AppHandle, Emitter, Manager};
+use tokio::process::{Child, Command};
+
+use registry::ProfileRegistry;
+
+use crate::audit::{self, AuditAction};
+use crate::config::{ConfigStore, GpuConfig};
+use crate::proxy::bridge::ProxyBridges;
+use crate::server::client::{self, RequestOptions};
+use crate::server::logs::now_millis;
+use crate::server::process::{isolate, ProcessTree};
+use crate::settings::SettingsStore;
+
+/// How long a browser gets to close its windows before it is killed.
+pub const DEFAULT_STOP_GRACE: Duration = Duration::from_secs(5);
+/// Firefox reads this from the profile directory on every start.
+const USER_PREFS: &str = "user.js";
+/// Longest profile ID accepted.
+const MAX_ID_LEN: usize = 128;
+
+/// A browser the shell started, as reported to the frontend.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProfileProcess {
+    pub id: String,
+    pub pid: u32,
+    pub executable: String,
+    pub headless: bool,
+    /// Milliseconds since the Unix epoch.
+    pub started_at: u64,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct StoppedPayload {
+    id: String,
+}
+
+/// The parts of the backend's profile the launcher needs.
+#[derive(Deserialize)]
+struct Profile {
+    id: String,
+    #[serde(default)]
+    config: serde_json::Value,
+}
+
+/// Profile IDs end up in paths and URLs, so only plain ones are accepted.
+pub(crate) fn validate_id(id: &str) -> Result<(), String> {
+    let valid = !id.is_empty()
+        && id.len() <= MAX_ID_LEN
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !valid {
+        return Err(format!("Invalid profile ID: {:?}", id));
+    }
+    Ok(())
+}
+
+async fn fetch_profile(app: &AppHandle, id: &str) -> Result<Profile, String> {
+    let response = client::forward(
+        app,
+        "GET",
+        &format!("/api/profiles/{}", id),
+        None,
+        HashMap::new(),
+        RequestOptions::default(),
+    )
+    .await?;
+    if response.status == 404 {
+        return Err(format!("Profile {} not found", id));
+    }
+    if !response.ok {
+        return Err(format!(
+            "Failed to load profile {}: HTTP {}",
+            id, response.status
+        ));
+    }
+    serde_json::from_value(response.body)
+        .map_err(|e| format!("Failed to read profile {}: {}", id, e))
+}
+
+/// Tells the backend the profile's browser is now `status`. Best effort: a
+/// backend that is down must not keep a browser from starting or stopping.
+async fn report_status(app: &AppHandle, id: &str, status: &str, pid: Option<u32>) {
+    let result = client::forward(
+        app,
+        "POST",
+        &format!("/api/profiles/{}/status", id),
+        Some(json!({ "status": status, "pid": pid })),
+        HashMap::new(),
+        RequestOptions::default(),
+    )
+    .await;
+    match result {
+        Ok(response) if response.ok => {}
+        Ok(response) => log::warn!(
+            "Backend rejected status {} for profile {}: HTTP {}",
+            status,
+            id,
+            response.status
+        ),
+        Err(e) => log::warn!("Failed to report profile {} as {}: {}", id, status, e),
+    }
+}
+
+/// Where the Camoufox Python package installs its browser.
+fn default_executable(app: &AppHandle) -> Option<PathBuf> {
+    let cache = app.path().cache_dir().ok()?.join("camoufox");
+    let path = if cfg!(windows) {
+        cache.join("camoufox").join("Cache").join("camoufox.exe")
+    } else if cfg!(target_os = "macos") {
+        cache.join("Camoufox.app/Contents/MacOS/camoufox")
+    } else {
+        cache.join("camoufox-bin")
+    };
+    Some(path)
+}
+
+fn executable(app: &AppHandle) -> Result<PathBuf, String> {
+    let path = app
+        .state::<ConfigStore>()
+        .get()
+        .browser
+        .executable
+        .or_else(|| crate::browsers::latest_executable(app, crate::browsers::DEFAULT_BROWSER))
+        .or_else(|| default_executable(app))
+        .ok_or("No browser executable configured")?;
+    if !path.is_file() {
+        return Err(format!(
+            "Browser not found at {}; install a browser build or set the browser executable",
+            path.display()
+        ));
+    }
+    Ok(path)
+}
+
+/// The data directory of profile `id`, created if need be.
+pub(crate) fn profile_dir(app: &AppHandle, id: &str) -> Result<PathBuf, String> {
+    validate_id(id)?;
+    let dir = crate::data_dir::data_dir(app)?.join("profiles").join(id);
+    std::fs::create_dir_all(&dir)
+        .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    Ok(dir)
+}
+
+/// The proxy from the profile's config, which is either `{"server": ...}` or
+/// a bare server string.
+fn proxy_url(config: &serde_json::Value) -> Option<reqwest::Url> {
+    let server = match config.get("proxy")? {
+        serde_json::Value::String(server) => server.as_str(),
+        proxy => proxy.get("server")?.as_str()?,
+    }
+    .trim();
+    if server.is_empty() {
+        return None;
+    }
+    let server = if server.contains("://") {
+        server.to_string()
+    } else {
+        format!("http://{}", server)
+    };
+    match reqwest::Url::parse(&server) {
+        Ok(url) if url.host_str().is_some() => Some(url),
+        _ => {
+            log::warn!("Ignoring invalid proxy server {:?}", server);
+            None
+        }
+    }
+}
+
+/// Prefs written to `user.js`, which Firefox applies over the profile's own
+/// on each start. Written every launch so a removed proxy does not linger.
+fn user_prefs(config: &serde_json::Value, gpu: &GpuConfig) -> String {
+    let mut prefs = vec![
+        ("browser.shell.checkDefaultBrowser", "false".to_string()),
+        ("browser.aboutwelcome.enabled", "false".to_string()),
+    ];
+    match proxy_url(config) {
+        Some(url) => {
+            let host =
+                serde_json::to_string(url.host_str().unwrap_or_default()).unwrap_or_default();
+            let socks = url.scheme().starts_with("socks");
+            let port = url
+                .port_or_known_default()
+                .unwrap_or(if socks { 1080 } else { 8080 })
+                .to_string();
+            prefs.push(("network.proxy.type", "1".to_string()));
+            if socks {
+                let version = if url.scheme() == "socks4" { "4" } else { "5" };
+                prefs.push(("network.proxy.socks", host));
+                prefs.push(("network.proxy.socks_port", port));
+                prefs.push(("network.proxy.socks_version", version.to_string()));
+                // Resolve names through the proxy so DNS does not leak
+                prefs.push(("network.proxy.socks_remote_dns", "true".to_string()));
+            } else {
+                prefs.push(("network.proxy.http", host.clone()));
+                prefs.push(("network.proxy.http_port", port.clone()));
+                prefs.push(("network.proxy.ssl", host));
+                prefs.push(("network.proxy.ssl_port", port));
+            }
+        }
+        None => prefs.push(("network.proxy.type", "0".to_string())),
+    }
+    prefs.extend(gpu::prefs(gpu));
+    prefs
+        .into_iter()
+        .map(|(name, value)| format!("user_pref(\"{}\", {});\n", name, value))
+        .collect()
+}
+
+fn spawn_browser(
+    app: &AppHandle,
+    executable: &Path,
+    dir: &Path,
+    headless: bool,
+    url: Option<&str>,
+    gpu: &GpuConfig,
+    debugging_port: Option<u16>,
+) -> Result<(Child, ProcessTree), String> {
+    let mut command = Command::new(executable);
+    command
+        .arg("-profile")
+        .arg(dir)
+        .arg("-no-remote")
+        .args(headless.then_some("-headless"))
+        .args(gpu::args(executable, gpu))
+        .args(
+            debugging_port
+                .map(|port| automation::args(executable, port))
+                .unwrap_or_default(),
+        )
+        .args(app.state::<ConfigStore>().get().browser.args)
+        .args(url)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    isolate(&mut command);
+    let child = command.spawn();
+    audit::record(
+        app,
+        AuditAction::ProcessSpawn,
+        format!("browser {} on {}", executable.display(), dir.display()),
+        &child,
+    );
+    let child = child.map_err(|e| format!("Failed to start {}: {}", executable.display(), e))?;
+    let tree = ProcessTree::contain(&child);
+    Ok((child, tree))
+}
+
+/// Starts the profile's browser with its own data directory and proxy.
+#[tauri::command]
+pub async fn launch_profile(
+    app_handle: AppHandle,
+    profile_id: String,
+    headless: Option<bool>,
+) -> Result<ProfileProcess, String> {
+    validate_id(&profile_id)?;
+    let registry = app_handle.state::<ProfileRegistry>();
+    if registry.contains(&profile_id) {
+        return Err(format!("Profile {} is already running", profile_id));
+    }
+
+    let mut profile = fetch_profile(&app_handle, &profile_id).await?;
+    profile.config =
+        crate::proxy::pool::resolve_profile_proxy(&app_handle, &profile.id, profile.config)
+            .await?;
+    let executable = executable(&app_handle)?;
+    let dir = profile_dir(&app_handle, &profile.id)?;
+    let gpu = gpu::for_profile(&app_handle, &profile.id);
+    let headless = headless.unwrap_or(false);
+    let debugging_port = automation::is_exposed(&app_handle, &profile.id)
+        .then(automation::free_port)
+        .transpose()?;
+
+    // Claimed last, so that only writing the prefs or spawning can fail
+    // while the bridge waits for the browser
+    let (config, bridge) =
+        crate::proxy::bridge::resolve_profile_proxy(&app_handle, profile.config).await?;
+    profile.config = config;
+    let prefs = dir.join(USER_PREFS);
+    let spawned = std::fs::write(&prefs, user_prefs(&profile.config, &gpu))
+        .map_err(|e| format!("Failed to write {}: {}", prefs.display(), e))
+        .and_then(|()| {
+            spawn_browser(
+                &app_handle,
+                &executable,
+                &dir,
+                headless,
+                None,
+                &gpu,
+                debugging_port,
+            )
+        });
+    let bridges = app_handle.state::<ProxyBridges>();
+    let (child, tree) = match spawned {
+        Ok(spawned) => spawned,
+        Err(e) => {
+            if let Some(bridge) = &bridge {
+                bridges.unclaim(bridge);
+            }
+            return Err(e);
+        }
+    };
+    let info = ProfileProcess {
+        id: profile.id,
+        pid: child.id().unwrap_or_default(),
+        executable: executable.display().to_string(),
+        headless,
+        started_at: now_millis(),
+    };
+    log::info!("Launched profile {} (pid {})", info.id, info.pid);
+    if let Some(bridge) = &bridge {
+        bridges.attach(bridge, &info.id, info.pid);
+    }
+    registry.register(&app_handle, info.clone(), child, tree);
+    if let Some(port) = debugging_port {
+        automation::register(&app_handle, &info.id, info.pid, &executable, port);
+    }
+
+    let _ = app_handle.emit("profile://launched", info.clone());
+    let settings = app_handle.state::<SettingsStore>();
+    if settings.get().last_profile.as_ref() != Some(&info.id) {
+        if let Err(e) = settings.update(&app_handle, "lastProfile", |s| {
+            s.last_profile = Some(info.id.clone())
+        }) {
+            log::warn!("{}", e);
+        }
+    }
+    report_status(&app_handle, &info.id, "active", Some(info.pid)).await;
+    Ok(info)
+}
+
+/// Closes the profile's browser. Returns `false` if it was not running.
+#[tauri::command]
+pub async fn stop_profile(
+    app_handle: AppHandle,
+    profile_id: String,
+    grace_ms: Option<u64>,
+) -> Result<bool, String> {
+    let grace = grace_ms
+        .map(Duration::from_millis)
+        .unwrap_or(DEFAULT_STOP_GRACE);
+    if !app_handle
+        .state::<ProfileRegistry>()
+        .stop(&profile_id, grace)
+        .await
+    {
+        return Ok(false);
+    }
+    stopped(&app_handle, &profile_id).await;
+    Ok(true)
+}
+
+async fn stopped(app: &AppHandle, id: &str) {
+    log::info!("Stopped profile {}", id);
+    audit::record_ok(
+        app,
+        AuditAction::ProcessKill,
+        format!("browser of profile {}", id),
+    );
+    let _ = app.emit("profile://stopped", StoppedPayload { id: id.to_string() });
+    report_status(app, id, "inactive", None).await;
+}
+
+/// Kills every running profile browser without waiting for it to close, and
+/// returns the profiles that were stopped.
+#[tauri::command]
+pub async fn kill_all_profiles(app_handle: AppHandle) -> Result<Vec<String>, String> {
+    let ids = app_handle
+        .state::<ProfileRegistry>()
+        .stop_all(Duration::ZERO)
+        .await;
+    for id in &ids {
+        stopped(&app_handle, id).await;
+    }
+    Ok(ids)
+}
+
+#[tauri::command]
+pub fn list_running_profiles(registry: tauri::State<'_, ProfileRegistry>) -> Vec<ProfileProcess> {
+    registry.list()
+}
@@ -0,0 +1,174 @@
+//! Tracks the profile browsers the shell started. Each one gets a task that
+//! waits on it, so a browser that crashes or is closed by the user drops out
+//! of the registry and `profile://exited` tells the UI.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager};
+use tokio::process::Child;
+use tokio::sync::oneshot;
+
+use super::ProfileProcess;
+use crate::proxy::bridge::ProxyBridges;
+use crate::server::process::ProcessTree;
+
+pub type ProfileId = String;
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ExitedPayload {
+    id: ProfileId,
+    /// `None` when the browser was killed by a signal.
+    code: Option<i32>,
+}
+
+struct StopRequest {
+    grace: Duration,
+    done: oneshot::Sender<()>,
+}
+
+struct ChildInfo {
+    info: ProfileProcess,
+    /// Dropping the sender also kills the browser.
+    stop: oneshot::Sender<StopRequest>,
+}
+
+/// Managed state holding the browsers launched from this session.
+#[derive(Default)]
+pub struct ProfileRegistry {
+    children: Mutex<HashMap<ProfileId, ChildInfo>>,
+}
+
+impl ProfileRegistry {
+    pub fn list(&self) -> Vec<ProfileProcess> {
+        let children = self.children.lock().unwrap();
+        let mut list: Vec<ProfileProcess> = children.values().map(|c| c.info.clone()).collect();
+        list.sort_by_key(|profile| profile.started_at);
+        list
+    }
+
+    pub fn contains(&self, id: &str) -> bool {
+        self.children.lock().unwrap().contains_key(id)
+    }
+
+    /// Starts tracking `child` and watching it for exit.
+    pub fn register(&self, app: &AppHandle, info: ProfileProcess, child: Child, tree: ProcessTree) {
+        let (stop_tx, stop_rx) = oneshot::channel();
+        self.children.lock().unwrap().insert(
+            info.id.clone(),
+            ChildInfo {
+                info: info.clone(),
+                stop: stop_tx,
+            },
+        );
+        tauri::async_runtime::spawn(watch(app.clone(), info, child, tree, stop_rx));
+    }
+
+    /// Closes the profile's browser, killing it if it outlives `grace`.
+    /// Returns `false` if it was not running.
+    pub async fn stop(&self, id: &str, grace: Duration) -> bool {
+        let Some(child) = self.children.lock().unwrap().remove(id) else {
+            return false;
+        };
+        let (done_tx, done_rx) = oneshot::channel();
+        if child
+            .stop
+            .send(StopRequest {
+                grace,
+                done: done_tx,
+            })
+            .is_ok()
+        {
+            let _ = done_rx.await;
+        }
+        true
+    }
+
+    /// Stops every browser and returns the profiles that were running.
+    pub async fn stop_all(&self, grace: Duration) -> Vec<ProfileId> {
+        let ids: Vec<ProfileId> = self.list().into_iter().map(|profile| profile.id).collect();
+        for id in &ids {
+            self.stop(id, grace).await;
+        }
+        ids
+    }
+
+    /// Forgets the profile if its entry still belongs to the browser `pid`;
+    /// a relaunch may have replaced it already.
+    fn forget(&self, id: &str, pid: u32) -> bool {
+        let mut children = self.children.lock().unwrap();
+        if children.get(id).is_some_and(|child| child.info.pid == pid) {
+            children.remove(id);
+            return true;
+        }
+        false
+    }
+}
+
+async fn watch(
+    app: AppHandle,
+    info: ProfileProcess,
+    mut child: Child,
+    tree: ProcessTree,
+    stop: oneshot::Receiver<StopRequest>,
+) {
+    tokio::select! {
+        status = child.wait() => {
+            // Content processes outliving the browser hold its profile lock
+            tree.kill();
+            app.state::<ProxyBridges>().release(&info.id, info.pid);
+            let code = status.as_ref().ok().and_then(|status| status.code());
+            match &status {
+                Ok(status) => log::info!("Browser for profile {} exited with {}", info.id, status),
+                Err(e) => log::warn!("Error waiting for browser of profile {}: {}", info.id, e),
+            }
+            if !app.state::<ProfileRegistry>().forget(&info.id, info.pid) {
+                return;
+            }
+            let _ = app.emit(
+                "profile://exited",
+                ExitedPayload {
+                    id: info.id.clone(),
+                    code,
+                },
+            );
+            let status = if code == Some(0) { "inactive" } else { "error" };
+            super::report_status(&app, &info.id, status, None).await;
+        }
+        stop = stop => {
+            match stop {
+                Ok(stop) => {
+                    terminate(&info, &mut child, &tree, stop.grace).await;
+                    let _ = stop.done.send(());
+                }
+                Err(_) => terminate(&info, &mut child, &tree, Duration::ZERO).await,
+            }
+            app.state::<ProxyBridges>().release(&info.id, info.pid);
+        }
+    }
+}
+
+/// Asks the browser to close, killing its process tree if it outlives
+/// `grace`.
+async fn terminate(info: &ProfileProcess, child: &mut Child, tree: &ProcessTree, grace: Duration) {
+    if !grace.is_zero() {
+        tree.interrupt();
+        match tokio::time::timeout(grace, child.wait()).await {
+            Ok(Ok(_)) => {
+                tree.kill();
+                return;
+            }
+            Ok(Err(e)) => log::warn!("Error waiting for browser of profile {}: {}", info.id, e),
+            Err(_) => log::warn!(
+                "Browser for profile {} did not exit within {:?}, killing it",
+                info.id,
+                grace
+            ),
+        }
+    }
+    tree.kill();
+    let _ = child.kill().await;
+}
@@ -0,0 +1,683 @@
+//! Local HTTP proxies in front of SOCKS5 ones, for browser setups that only
+//! take HTTP proxies, or cannot log in to a SOCKS5 proxy, as Firefox cannot.
+//!
+//! Each bridge listens on its own port on localhost and serves HTTP proxy
+//! requests, `CONNECT` tunnels as well as plain requests, by opening the
+//! connection through its SOCKS5 proxy, logging in with the proxy's
+//! credentials when it has them. Host names are passed on for the proxy to
+//! resolve, so no DNS lookup leaks from this machine. One bridge serves
+//! every profile using the same proxy; at most [`MAX_BRIDGES`] run at once.
+//!
+//! Other programs on this machine can reach localhost too, so each bridge
+//! has its own random credentials and answers `407` to clients that do not
+//! send them in `Proxy-Authorization`.
+//!
+//! A profile whose `proxy` has `"bridge": true` gets its SOCKS5 proxy
+//! bridged at launch, and the browser pointed at the bridge with its
+//! credentials instead, as for any HTTP proxy that needs a login. The
+//! bridge stops once the last browser using it exits, unless it was
+//! started with [`start_proxy_bridge`], in which case it runs until it is
+//! stopped or the app exits.
+
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use base64::Engine;
+use rand::RngCore;
+use serde::Serialize;
+use serde_json::{json, Value};
+use tauri::{AppHandle, Manager};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+use super::import::parse_line;
+use super::pool::proxy_id;
+use super::{ProxyConfig, ProxyProtocol};
+use crate::bandwidth::{self, Feature};
+use crate::server::logs::now_millis;
+
+/// Bridges running at the same time.
+pub const MAX_BRIDGES: usize = 16;
+/// Longest request head accepted from the browser.
+const MAX_HEAD: usize = 16 * 1024;
+/// Time the SOCKS5 proxy gets to open a connection.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(20);
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BridgeInfo {
+    pub id: String,
+    /// The SOCKS5 proxy, without its credentials.
+    pub upstream: String,
+    pub local_port: u16,
+    /// What to put in the profile's proxy settings, `http://127.0.0.1:<port>`.
+    pub url: String,
+    /// The login the bridge asks its clients for.
+    pub username: String,
+    pub password: String,
+    /// Milliseconds since the Unix epoch.
+    pub created_at: u64,
+}
+
+struct Bridge {
+    info: BridgeInfo,
+    task: tauri::async_runtime::JoinHandle<()>,
+    /// Started with [`start_proxy_bridge`], so kept until it is stopped.
+    pinned: bool,
+    /// Launches that claimed the bridge but have no browser yet.
+    pending: usize,
+    /// Browser PID by profile, for the browsers using the bridge.
+    users: HashMap<String, u32>,
+}
+
+impl Bridge {
+    fn idle(&self) -> bool {
+        !self.pinned && self.pending == 0 && self.users.is_empty()
+    }
+
+    fn reuse(&mut self, pinned: bool) -> BridgeInfo {
+        if pinned {
+            self.pinned = true;
+        } else {
+            self.pending += 1;
+        }
+        self.info.clone()
+    }
+}
+
+/// Managed state holding the running bridges, by ID.
+#[derive(Default)]
+pub struct ProxyBridges {
+    bridges: Mutex<HashMap<String, Bridge>>,
+}
+
+impl ProxyBridges {
+    pub fn list(&self) -> Vec<BridgeInfo> {
+        let mut list: Vec<BridgeInfo> = self
+            .bridges
+            .lock()
+            .unwrap()
+            .values()
+            .map(|bridge| bridge.info.clone())
+            .collect();
+        list.sort_by_key(|info| info.created_at);
+        list
+    }
+
+    /// The bridge for `upstream`, started unless it runs already, and kept
+    /// until it is stopped.
+    pub async fn start(
+        &self,
+        app: &AppHandle,
+        upstream: ProxyConfig,
+    ) -> Result<BridgeInfo, String> {
+        self.open(app, upstream, true).await
+    }
+
+    /// The bridge for `upstream`, for a launch to [`attach`](Self::attach)
+    /// its browser to once it runs, or to [`unclaim`](Self::unclaim) if it
+    /// fails.
+    pub async fn claim(
+        &self,
+        app: &AppHandle,
+        upstream: ProxyConfig,
+    ) -> Result<BridgeInfo, String> {
+        self.open(app, upstream, false).await
+    }
+
+    async fn open(
+        &self,
+        app: &AppHandle,
+        upstream: ProxyConfig,
+        pinned: bool,
+    ) -> Result<BridgeInfo, String> {
+        if upstream.protocol != ProxyProtocol::Socks5 {
+            return Err(format!(
+                "Only SOCKS5 proxies can be bridged, not {}",
+                upstream
+            ));
+        }
+        let id = proxy_id(&upstream);
+        if let Some(bridge) = self.bridges.lock().unwrap().get_mut(&id) {
+            return Ok(bridge.reuse(pinned));
+        }
+
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+            .await
+            .map_err(|e| format!("Failed to start a bridge for {}: {}", upstream, e))?;
+        let local_port = listener
+            .local_addr()
+            .map_err(|e| format!("Failed to start a bridge for {}: {}", upstream, e))?
+            .port();
+        let info = BridgeInfo {
+            id: id.clone(),
+            upstream: upstream.to_string(),
+            local_port,
+            url: format!("http://{}:{}", Ipv4Addr::LOCALHOST, local_port),
+            username: random_hex(8),
+            password: random_hex(16),
+            created_at: now_millis(),
+        };
+        let credentials = format!(
+            "Basic {}",
+            base64::engine::general_purpose::STANDARD
+                .encode(format!("{}:{}", info.username, info.password))
+        );
+
+        let mut bridges = self.bridges.lock().unwrap();
+        // Another call may have started one meanwhile
+        if let Some(bridge) = bridges.get_mut(&id) {
+            return Ok(bridge.reuse(pinned));
+        }
+        if bridges.len() >= MAX_BRIDGES {
+            return Err(format!(
+                "{} proxy bridges are running already; stop one first",
+                MAX_BRIDGES
+            ));
+        }
+        let handle = app.clone();
+        let task = tauri::async_runtime::spawn(async move {
+            loop {
+                match listener.accept().await {
+                    Ok((client, _)) => {
+                        let app = handle.clone();
+                        let upstream = upstream.clone();
+                        let credentials = credentials.clone();
+                        tauri::async_runtime::spawn(async move {
+                            if let Err(e) = serve(&app, &upstream, &credentials, client).await {
+                                log::debug!(
+                                    "Bridged connection through {} failed: {}",
+                                    upstream,
+                                    e
+                                );
+                            }
+                        });
+                    }
+                    Err(e) => log::warn!("Proxy bridge failed to accept a connection: {}", e),
+                }
+            }
+        });
+        log::info!("Bridging {} on {}", info.upstream, info.url);
+        bridges.insert(
+            id,
+            Bridge {
+                info: info.clone(),
+                task,
+                pinned,
+                pending: usize::from(!pinned),
+                users: HashMap::new(),
+            },
+        );
+        Ok(info)
+    }
+
+    /// Records that the browser `pid` of `profile_id` uses the claimed
+    /// bridge `id`.
+    pub fn attach(&self, id: &str, profile_id: &str, pid: u32) {
+        if let Some(bridge) = self.bridges.lock().unwrap().get_mut(id) {
+            bridge.pending = bridge.pending.saturating_sub(1);
+            bridge.users.insert(profile_id.to_string(), pid);
+        }
+    }
+
+    /// Gives up a claim on `id` whose launch failed.
+    pub fn unclaim(&self, id: &str) {
+        let mut bridges = self.bridges.lock().unwrap();
+        if let Some(bridge) = bridges.get_mut(id) {
+            bridge.pending = bridge.pending.saturating_sub(1);
+        }
+        stop_idle(&mut bridges);
+    }
+
+    /// Called when the browser `pid` of `profile_id` has exited: stops the
+    /// bridges no other browser uses. A relaunch has another PID, so a
+    /// late call for the old browser leaves the new one its bridge.
+    pub fn release(&self, profile_id: &str, pid: u32) {
+        let mut bridges = self.bridges.lock().unwrap();
+        for bridge in bridges.values_mut() {
+            if bridge.users.get(profile_id) == Some(&pid) {
+                bridge.users.remove(profile_id);
+            }
+        }
+        stop_idle(&mut bridges);
+    }
+
+    pub fn stop(&self, id: &str) -> bool {
+        let Some(bridge) = self.bridges.lock().unwrap().remove(id) else {
+            return false;
+        };
+        bridge.task.abort();
+        log::info!("Stopped the bridge for {}", bridge.info.upstream);
+        true
+    }
+}
+
+fn stop_idle(bridges: &mut HashMap<String, Bridge>) {
+    bridges.retain(|_, bridge| {
+        if !bridge.idle() {
+            return true;
+        }
+        bridge.task.abort();
+        log::info!("Stopped the unused bridge for {}", bridge.info.upstream);
+        false
+    });
+}
+
+fn random_hex(len: usize) -> String {
+    let mut bytes = vec![0u8; len];
+    rand::rngs::OsRng.fill_bytes(&mut bytes);
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Reads the request head, up to and including the blank line. Returns it
+/// and whatever the browser sent after it.
+async fn read_head(client: &mut TcpStream) -> Result<(String, Vec<u8>), String> {
+    let mut buffer = Vec::new();
+    let mut chunk = [0u8; 4096];
+    loop {
+        let read = client.read(&mut chunk).await.map_err(|e| e.to_string())?;
+        if read == 0 {
+            return Err("Connection closed before the request was read".to_string());
+        }
+        buffer.extend_from_slice(&chunk[..read]);
+        if let Some(end) = buffer.windows(4).position(|w| w == b"\r\n\r\n") {
+            let rest = buffer.split_off(end + 4);
+            let head = String::from_utf8(buffer).map_err(|_| "Request head is not UTF-8")?;
+            return Ok((head, rest));
+        }
+        if buffer.len() > MAX_HEAD {
+            return Err("Request head too long".to_string());
+        }
+    }
+}
+
+/// Splits `host:port`, with IPv6 hosts in brackets.
+fn split_target(target: &str) -> Option<(String, u16)> {
+    let (host, port) = target.rsplit_once(':')?;
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    Some((host.to_string(), port.parse().ok()?))
+}
+
+/// The head to send on for a plain request: origin-form, without the
+/// headers meant for the proxy, and closing after one response, as the next
+/// request may be for another host.
+fn forwarded_head(head: &str, path: &str) -> String {
+    let mut lines = head.split("\r\n");
+    let request = lines.next().unwrap_or_default();
+    let mut parts = request.split(' ');
+    let method = parts.next().unwrap_or_default();
+    let version = parts.nth(1).unwrap_or("HTTP/1.1");
+    let mut forwarded = format!("{} {} {}\r\n", method, path, version);
+    for line in lines.filter(|line| !line.is_empty()) {
+        let name = line
+            .split(':')
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        if !name.starts_with("proxy-") && name != "connection" && name != "keep-alive" {
+            forwarded.push_str(line);
+            forwarded.push_str("\r\n");
+        }
+    }
+    forwarded.push_str("Connection: close\r\n\r\n");
+    forwarded
+}
+
+/// Whether `head` carries the bridge's `credentials`, compared in constant
+/// time.
+fn authorized(head: &str, credentials: &str) -> bool {
+    let Some(presented) = head.split("\r\n").skip(1).find_map(|line| {
+        let (name, value) = line.split_once(':')?;
+        name.trim()
+            .eq_ignore_ascii_case("proxy-authorization")
+            .then(|| value.trim())
+    }) else {
+        return false;
+    };
+    presented.len() == credentials.len()
+        && presented
+            .bytes()
+            .zip(credentials.bytes())
+            .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+async fn respond(client: &mut TcpStream, status: &str) {
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+        status
+    );
+    let _ = client.write_all(response.as_bytes()).await;
+}
+
+/// Serves one connection from the browser.
+async fn serve(
+    app: &AppHandle,
+    upstream: &ProxyConfig,
+    credentials: &str,
+    mut client: TcpStream,
+) -> Result<(), String> {
+    let (head, rest) = read_head(&mut client).await?;
+    if !authorized(&head, credentials) {
+        let _ = client
+            .write_all(
+                b"HTTP/1.1 407 Proxy Authentication Required\r\n\
+                  Proxy-Authenticate: Basic realm=\"nyx bridge\"\r\n\
+                  Content-Length: 0\r\nConnection: close\r\n\r\n",
+            )
+            .await;
+        return Err("Client did not log in to the bridge".to_string());
+    }
+    let request = head.lines().next().unwrap_or_default();
+    let mut parts = request.split(' ');
+    let (method, target) = (
+        parts.next().unwrap_or_default(),
+        parts.next().unwrap_or_default(),
+    );
+
+    let tunnel = method.eq_ignore_ascii_case("CONNECT");
+    let (host, port, forwarded) = if tunnel {
+        let Some((host, port)) = split_target(target) else {
+            respond(&mut client, "400 Bad Request").await;
+            return Err(format!("Invalid CONNECT target {:?}", target));
+        };
+        (host, port, None)
+    } else {
+        let url = match reqwest::Url::parse(target) {
+            Ok(url) if url.scheme() == "http" && url.host_str().is_some() => url,
+            _ => {
+                respond(&mut client, "400 Bad Request").await;
+                return Err(format!("Not a proxy request: {:?}", target));
+            }
+        };
+        let path = match url.query() {
+            Some(query) => format!("{}?{}", url.path(), query),
+            None => url.path().to_string(),
+        };
+        let host = url
+            .host_str()
+            .unwrap_or_default()
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .to_string();
+        (
+            host,
+            url.port_or_known_default().unwrap_or(80),
+            Some(forwarded_head(&head, &path)),
+        )
+    };
+
+    let mut remote =
+        match tokio::time::timeout(CONNECT_TIMEOUT, connect(upstream, &host, port)).await {
+            Ok(Ok(remote)) => remote,
+            Ok(Err(e)) => {
+                respond(&mut client, "502 Bad Gateway").await;
+                return Err(e);
+            }
+            Err(_) => {
+                respond(&mut client, "504 Gateway Timeout").await;
+                return Err(format!(
+                    "{} did not connect to {}:{} in time",
+                    upstream, host, port
+                ));
+            }
+        };
+
+    let (mut sent, mut received) = (0, 0);
+    let relayed = relay(
+        &mut client,
+        &mut remote,
+        forwarded,
+        &rest,
+        &mut sent,
+        &mut received,
+    )
+    .await;
+    // Counted however the connection ended, as the proxy saw the bytes
+    bandwidth::record(app, Feature::ProxyBridge, sent, received);
+    relayed
+}
+
+/// Relays between the browser and the proxy until both are done, adding
+/// the bytes passed on to `sent` and `received` as they go.
+async fn relay(
+    client: &mut TcpStream,
+    remote: &mut TcpStream,
+    forwarded: Option<String>,
+    rest: &[u8],
+    sent: &mut u64,
+    received: &mut u64,
+) -> Result<(), String> {
+    let io = |e: std::io::Error| e.to_string();
+    match forwarded {
+        None => client
+            .write_all(b"HTTP/1.1 200 Connection Established\r\n\r\n")
+            .await
+            .map_err(io)?,
+        Some(head) => {
+            remote.write_all(head.as_bytes()).await.map_err(io)?;
+            *sent += head.len() as u64;
+        }
+    }
+    remote.write_all(rest).await.map_err(io)?;
+    *sent += rest.len() as u64;
+
+    let (mut client_read, mut client_write) = client.split();
+    let (mut remote_read, mut remote_write) = remote.split();
+    tokio::try_join!(
+        pump(&mut client_read, &mut remote_write, sent),
+        pump(&mut remote_read, &mut client_write, received),
+    )
+    .map_err(io)?;
+    Ok(())
+}
+
+/// Copies `from` to `to` until `from` ends, then shuts `to` down.
+async fn pump<R, W>(from: &mut R, to: &mut W, count: &mut u64) -> std::io::Result<()>
+where
+    R: tokio::io::AsyncRead + Unpin,
+    W: tokio::io::AsyncWrite + Unpin,
+{
+    let mut buffer = [0u8; 16 * 1024];
+    loop {
+        let read = from.read(&mut buffer).await?;
+        if read == 0 {
+            return to.shutdown().await;
+        }
+        to.write_all(&buffer[..read]).await?;
+        *count += read as u64;
+    }
+}
+
+/// Opens a connection to `host:port` through the SOCKS5 proxy `upstream`.
+async fn connect(upstream: &ProxyConfig, host: &str, port: u16) -> Result<TcpStream, String> {
+    let io = |e: std::io::Error| format!("SOCKS5 proxy {} failed: {}", upstream, e);
+    let mut stream = TcpStream::connect((upstream.host.as_str(), upstream.port))
+        .await
+        .map_err(|e| format!("Failed to reach {}: {}", upstream, e))?;
+
+    let credentials = upstream
+        .username
+        .as_deref()
+        .filter(|username| !username.is_empty())
+        .map(|username| (username, upstream.password.as_deref().unwrap_or_default()));
+    let greeting: &[u8] = if credentials.is_some() {
+        &[5, 2, 0, 2]
+    } else {
+        &[5, 1, 0]
+    };
+    stream.write_all(greeting).await.map_err(io)?;
+    let mut choice = [0u8; 2];
+    stream.read_exact(&mut choice).await.map_err(io)?;
+    match (choice, credentials) {
+        ([5, 0], _) => {}
+        ([5, 2], Some((username, password))) => {
+            if username.len() > 255 || password.len() > 255 {
+                return Err("SOCKS5 credentials are limited to 255 bytes each".to_string());
+            }
+            let mut login = vec![1, username.len() as u8];
+            login.extend_from_slice(username.as_bytes());
+            login.push(password.len() as u8);
+            login.extend_from_slice(password.as_bytes());
+            stream.write_all(&login).await.map_err(io)?;
+            let mut status = [0u8; 2];
+            stream.read_exact(&mut status).await.map_err(io)?;
+            if status[1] != 0 {
+                return Err(format!("{} rejected the credentials", upstream));
+            }
+        }
+        _ => {
+            return Err(format!(
+                "{} accepts none of the login methods offered",
+                upstream
+            ))
+        }
+    }
+
+    let mut request = vec![5, 1, 0];
+    match host.parse::<IpAddr>() {
+        Ok(IpAddr::V4(ip)) => {
+            request.push(1);
+            request.extend_from_slice(&ip.octets());
+        }
+        Ok(IpAddr::V6(ip)) => {
+            request.push(4);
+            request.extend_from_slice(&ip.octets());
+        }
+        // Resolved by the proxy
+        Err(_) if host.len() <= 255 => {
+            request.push(3);
+            request.push(host.len() as u8);
+            request.extend_from_slice(host.as_bytes());
+        }
+        Err(_) => return Err(format!("Host name too long: {}", host)),
+    }
+    request.extend_from_slice(&port.to_be_bytes());
+    stream.write_all(&request).await.map_err(io)?;
+
+    let mut reply = [0u8; 4];
+    stream.read_exact(&mut reply).await.map_err(io)?;
+    if reply[1] != 0 {
+        let reason = match reply[1] {
+            2 => "not allowed by its rules",
+            3 => "network unreachable",
+            4 => "host unreachable",
+            5 => "connection refused",
+            6 => "TTL expired",
+            _ => "general failure",
+        };
+        return Err(format!(
+            "{} could not connect to {}:{}: {}",
+            upstream, host, port, reason
+        ));
+    }
+    // The address the proxy bound, which is of no use here
+    let bound = match reply[3] {
+        1 => 4,
+        4 => 16,
+        3 => {
+            let mut len = [0u8; 1];
+            stream.read_exact(&mut len).await.map_err(io)?;
+            len[0] as usize
+        }
+        kind => return Err(format!("{} replied with address type {}", upstream, kind)),
+    };
+    let mut skipped = vec![0u8; bound + 2];
+    stream.read_exact(&mut skipped).await.map_err(io)?;
+    Ok(stream)
+}
+
+/// Points a profile whose `proxy` asks for a bridge at one, and returns the
+/// bridge's ID with the config, for the launch to attach its browser to.
+/// Configs without `"bridge": true` are left as they are.
+pub async fn resolve_profile_proxy(
+    app: &AppHandle,
+    mut config: Value,
+) -> Result<(Value, Option<String>), String> {
+    let Some(proxy) = config
+        .get("proxy")
+        .filter(|proxy| proxy.get("bridge").and_then(Value::as_bool) == Some(true))
+    else {
+        return Ok((config, None));
+    };
+    let server = proxy
+        .get("server")
+        .and_then(Value::as_str)
+        .unwrap_or_default();
+    let mut upstream = parse_line(server, ProxyProtocol::Socks5)
+        .ok_or_else(|| format!("Invalid proxy server to bridge: {:?}", server))?;
+    if let Some(username) = proxy.get("username").and_then(Value::as_str) {
+        upstream.username = Some(username.to_string());
+        upstream.password = proxy
+            .get("password")
+            .and_then(Value::as_str)
+            .map(str::to_string);
+    }
+    let bridge = app.state::<ProxyBridges>().claim(app, upstream).await?;
+    config["proxy"] = json!({
+        "server": bridge.url,
+        "username": bridge.username,
+        "password": bridge.password,
+    });
+    Ok((config, Some(bridge.id)))
+}
+
+/// Bridges the SOCKS5 proxy `config`, or finds the bridge already running
+/// for it. Its `url` is the HTTP proxy to configure instead, logging in
+/// with its `username` and `password`.
+#[tauri::command]
+pub async fn start_proxy_bridge(
+    app_handle: AppHandle,
+    config: ProxyConfig,
+) -> Result<BridgeInfo, String> {
+    app_handle
+        .state::<ProxyBridges>()
+        .start(&app_handle, config)
+        .await
+}
+
+/// Stops a bridge. Returns `false` if none ran with that ID.
+#[tauri::command]
+pub fn stop_proxy_bridge(bridges: tauri::State<'_, ProxyBridges>, id: String) -> bool {
+    bridges.stop(&id)
+}
+
+#[tauri::command]
+pub fn list_proxy_bridges(bridges: tauri::State<'_, ProxyBridges>) -> Vec<BridgeInfo> {
+    bridges.list()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clients_must_send_the_credentials() {
+        let credentials = "Basic dXNlcjpwYXNz";
+        let head = |auth: &str| {
+            format!(
+                "CONNECT example.com:443 HTTP/1.1\r\nHost: example.com:443\r\n{}\r\n",
+                auth
+            )
+        };
+        assert!(authorized(
+            &head("Proxy-Authorization: Basic dXNlcjpwYXNz\r\n"),
+            credentials
+        ));
+        assert!(authorized(
+            &head("proxy-authorization:Basic dXNlcjpwYXNz\r\n"),
+            credentials
+        ));
+        assert!(!authorized(&head(""), credentials));
+        assert!(!authorized(
+            &head("Proxy-Authorization: Basic dXNlcjpwYXNt\r\n"),
+            credentials
+        ));
+        assert!(!authorized(
+            &head("Authorization: Basic dXNlcjpwYXNz\r\n"),
+            credentials
+        ));
+    }
+}
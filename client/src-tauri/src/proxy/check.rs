@@ -0,0 +1,263 @@
+//! Tests proxies from the shell: how fast the proxy accepts a connection,
+//! which IP and country traffic leaves from, and whether plain HTTP and
+//! HTTPS tunnelling both work.
+//!
+//! Requests go straight through the proxy, with SOCKS5 resolving names on
+//! the proxy side, so checking never leaks DNS lookups from this machine.
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+use tokio::net::TcpStream;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+
+use super::pool::ProxyPool;
+use super::{ProxyConfig, ProxyProtocol};
+use crate::notifications::{self, NotificationKind};
+
+/// Answers with the caller's IP and its location, over HTTPS.
+pub const GEO_ENDPOINT: &str = "https://ipinfo.io/json";
+/// A plain HTTP page, for proxies that cannot tunnel.
+const HTTP_ENDPOINT: &str = "http://ipinfo.io/ip";
+pub const DEFAULT_CHECK_TIMEOUT: Duration = Duration::from_secs(10);
+/// Proxies checked at once by `check_proxies`.
+pub const DEFAULT_CONCURRENCY: usize = 16;
+
+#[derive(Clone, Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProxyCheck {
+    /// The proxy without its credentials.
+    pub proxy: String,
+    /// Whether traffic got through over either protocol.
+    pub ok: bool,
+    /// Time to open a TCP connection to the proxy.
+    pub connect_ms: Option<u64>,
+    /// Round trip of the geo lookup through the proxy.
+    pub latency_ms: Option<u64>,
+    pub exit_ip: Option<String>,
+    /// ISO 3166 country code of the exit IP.
+    pub country: Option<String>,
+    pub city: Option<String>,
+    pub supports_http: bool,
+    pub supports_https: bool,
+    pub error: Option<String>,
+}
+
+/// Where the lookup service places the caller.
+#[derive(Deserialize)]
+pub struct GeoInfo {
+    pub ip: String,
+    pub country: Option<String>,
+    pub city: Option<String>,
+    pub timezone: Option<String>,
+    /// `latitude,longitude`.
+    pub loc: Option<String>,
+}
+
+fn millis(since: Instant) -> u64 {
+    since.elapsed().as_millis() as u64
+}
+
+fn client(proxy: &ProxyConfig, timeout: Duration) -> Result<reqwest::Client, String> {
+    if proxy.protocol == ProxyProtocol::Socks4 {
+        return Err("SOCKS4 proxies are not supported".to_string());
+    }
+    let proxy = reqwest::Proxy::all(proxy.url()?).map_err(|e| e.to_string())?;
+    reqwest::Client::builder()
+        .proxy(proxy)
+        .timeout(timeout)
+        .connect_timeout(timeout)
+        .build()
+        .map_err(|e| format!("Failed to create HTTP client: {}", e))
+}
+
+async fn geo_lookup(client: &reqwest::Client) -> Result<GeoInfo, reqwest::Error> {
+    client
+        .get(GEO_ENDPOINT)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await
+}
+
+/// The exit IP of `proxy` and where the lookup service places it, asked
+/// through the proxy itself.
+pub async fn exit_geo(proxy: &ProxyConfig, timeout: Duration) -> Result<GeoInfo, String> {
+    let client = client(proxy, timeout)?;
+    geo_lookup(&client)
+        .await
+        .map_err(|e| format!("Failed to look up the exit IP of {}: {}", proxy, e))
+}
+
+/// This machine's own public IP, asked without any proxy.
+pub async fn direct_ip(timeout: Duration) -> Result<String, String> {
+    let client = reqwest::Client::builder()
+        .no_proxy()
+        .timeout(timeout)
+        .build()
+        .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
+    geo_lookup(&client)
+        .await
+        .map(|geo| geo.ip)
+        .map_err(|e| format!("Failed to look up this machine's IP: {}", e))
+}
+
+async fn http_lookup(client: &reqwest::Client) -> Result<String, reqwest::Error> {
+    let text = client
+        .get(HTTP_ENDPOINT)
+        .send()
+        .await?
+        .error_for_status()?
+        .text()
+        .await?;
+    Ok(text.trim().to_string())
+}
+
+/// Checks one proxy. Failures are reported in the result, not as an error.
+pub async fn check(proxy: &ProxyConfig, timeout: Duration) -> ProxyCheck {
+    let mut result = ProxyCheck {
+        proxy: proxy.to_string(),
+        ..Default::default()
+    };
+
+    let started = Instant::now();
+    match tokio::time::timeout(
+        timeout,
+        TcpStream::connect((proxy.host.as_str(), proxy.port)),
+    )
+    .await
+    {
+        Ok(Ok(_)) => result.connect_ms = Some(millis(started)),
+        Ok(Err(e)) => {
+            result.error = Some(format!("Failed to connect to {}: {}", result.proxy, e));
+            return result;
+        }
+        Err(_) => {
+            result.error = Some(format!("Timed out connecting to {}", result.proxy));
+            return result;
+        }
+    }
+
+    let client = match client(proxy, timeout) {
+        Ok(client) => client,
+        Err(e) => {
+            result.error = Some(e);
+            return result;
+        }
+    };
+
+    let started = Instant::now();
+    let (geo, http) = tokio::join!(geo_lookup(&client), http_lookup(&client));
+    let mut errors = Vec::new();
+    match geo {
+        Ok(geo) => {
+            result.latency_ms = Some(millis(started));
+            result.supports_https = true;
+            result.exit_ip = Some(geo.ip);
+            result.country = geo.country;
+            result.city = geo.city;
+        }
+        Err(e) => errors.push(format!("HTTPS: {}", e)),
+    }
+    match http {
+        Ok(ip) => {
+            result.supports_http = true;
+            if result.exit_ip.is_none() && !ip.is_empty() {
+                result.exit_ip = Some(ip);
+            }
+        }
+        Err(e) => errors.push(format!("HTTP: {}", e)),
+    }
+
+    result.ok = result.supports_http || result.supports_https;
+    if !errors.is_empty() {
+        result.error = Some(errors.join("; "));
+    }
+    result
+}
+
+/// Checks `proxies` with at most `concurrency` in flight, returning results
+/// in the same order. `on_result` sees each result as it comes in, along with
+/// the proxy's index.
+pub async fn check_all(
+    proxies: Vec<ProxyConfig>,
+    timeout: Duration,
+    concurrency: usize,
+    mut on_result: impl FnMut(usize, &ProxyCheck),
+) -> Vec<ProxyCheck> {
+    let permits = Arc::new(Semaphore::new(concurrency.max(1)));
+    let mut tasks = JoinSet::new();
+    for (index, proxy) in proxies.into_iter().enumerate() {
+        let permits = permits.clone();
+        tasks.spawn(async move {
+            let _permit = permits.acquire_owned().await;
+            (index, check(&proxy, timeout).await)
+        });
+    }
+
+    let mut results = Vec::with_capacity(tasks.len());
+    while let Some(joined) = tasks.join_next().await {
+        match joined {
+            Ok((index, result)) => {
+                on_result(index, &result);
+                results.push((index, result));
+            }
+            Err(e) => log::warn!("Proxy check task failed: {}", e),
+        }
+    }
+    results.sort_by_key(|(index, _)| *index);
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
+#[tauri::command]
+pub async fn check_proxy(
+    app_handle: AppHandle,
+    config: ProxyConfig,
+    timeout_ms: Option<u64>,
+) -> Result<ProxyCheck, String> {
+    let timeout = timeout_ms
+        .map(Duration::from_millis)
+        .unwrap_or(DEFAULT_CHECK_TIMEOUT);
+    let result = check(&config, timeout).await;
+    app_handle.state::<ProxyPool>().observe(
+        &app_handle,
+        std::slice::from_ref(&config),
+        std::slice::from_ref(&result),
+    );
+    Ok(result)
+}
+
+/// Checks a list of proxies concurrently.
+#[tauri::command]
+pub async fn check_proxies(
+    app_handle: AppHandle,
+    configs: Vec<ProxyConfig>,
+    timeout_ms: Option<u64>,
+    concurrency: Option<usize>,
+) -> Result<Vec<ProxyCheck>, String> {
+    let timeout = timeout_ms
+        .map(Duration::from_millis)
+        .unwrap_or(DEFAULT_CHECK_TIMEOUT);
+    let concurrency = concurrency.unwrap_or(DEFAULT_CONCURRENCY);
+    let results = check_all(configs.clone(), timeout, concurrency, |_, _| {}).await;
+    app_handle
+        .state::<ProxyPool>()
+        .observe(&app_handle, &configs, &results);
+    if !results.is_empty() {
+        notifications::send_or_log(
+            &app_handle,
+            NotificationKind::Proxies,
+            "Proxy check finished",
+            &format!(
+                "{} of {} proxies are working",
+                results.iter().filter(|result| result.ok).count(),
+                results.len()
+            ),
+        );
+    }
+    Ok(results)
+}
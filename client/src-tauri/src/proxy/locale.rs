@@ -0,0 +1,290 @@
+//! The timezone, locale and coordinates a profile should claim for the
+//! country its proxy exits in, worked out before the backend is up.
+//!
+//! The exit IP is asked through the proxy, never over this machine's own
+//! connection. It is then looked up in a MaxMind-format database (GeoLite2
+//! or DB-IP, City or Country), installed with [`install_geoip_database`]
+//! into `geoip/` in app data. Country databases carry no timezone or
+//! location, so those come from the country table bundled with the app.
+//! Without any database, the answer of the IP lookup service is used,
+//! which was also fetched through the proxy.
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use maxminddb::{geoip2, Reader};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager, State};
+
+use super::check::{self, DEFAULT_CHECK_TIMEOUT};
+use super::ProxyConfig;
+
+const GEOIP_DIR: &str = "geoip";
+const COUNTRIES: &str = include_str!("../../resources/countries.json");
+
+#[derive(Deserialize)]
+struct CountryInfo {
+    timezone: String,
+    locale: String,
+    languages: Vec<String>,
+    latitude: f64,
+    longitude: f64,
+}
+
+#[derive(Clone, Copy, Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LocaleSource {
+    /// The installed GeoIP database.
+    Database,
+    /// The IP lookup service, asked through the proxy.
+    Lookup,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LocaleInference {
+    pub exit_ip: String,
+    /// ISO 3166 country code.
+    pub country: Option<String>,
+    pub city: Option<String>,
+    /// IANA timezone, e.g. `Europe/Berlin`.
+    pub timezone: Option<String>,
+    /// BCP 47 locale, e.g. `de-DE`.
+    pub locale: Option<String>,
+    /// For `navigator.languages` and `Accept-Language`, preferred first.
+    pub languages: Vec<String>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub source: LocaleSource,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GeoIpDatabase {
+    pub path: String,
+    /// E.g. `GeoLite2-City` or `DBIP-Country-Lite`.
+    pub database_type: String,
+    /// Unix seconds the database was built at.
+    pub build_epoch: u64,
+}
+
+type Database = Arc<Reader<Vec<u8>>>;
+
+/// The installed GeoIP database, opened on first use.
+#[derive(Default)]
+pub struct GeoIp {
+    reader: Mutex<Option<(PathBuf, Database)>>,
+}
+
+fn geoip_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    let dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to resolve app data directory: {}", e))?
+        .join(GEOIP_DIR);
+    std::fs::create_dir_all(&dir)
+        .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    Ok(dir)
+}
+
+/// The newest `.mmdb` file in the GeoIP directory, if any.
+fn installed(dir: &Path) -> Option<PathBuf> {
+    std::fs::read_dir(dir)
+        .ok()?
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|extension| extension == "mmdb")
+        })
+        .max_by_key(|path| std::fs::metadata(path).and_then(|m| m.modified()).ok())
+}
+
+impl GeoIp {
+    fn reader(&self, app: &AppHandle) -> Result<Option<Database>, String> {
+        let Some(path) = installed(&geoip_dir(app)?) else {
+            return Ok(None);
+        };
+        let mut reader = self.reader.lock().unwrap();
+        if let Some((opened, reader)) = reader.as_ref() {
+            if *opened == path {
+                return Ok(Some(reader.clone()));
+            }
+        }
+        let opened = Arc::new(
+            Reader::open_readfile(&path)
+                .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?,
+        );
+        log::info!("Opened GeoIP database {}", path.display());
+        *reader = Some((path, opened.clone()));
+        Ok(Some(opened))
+    }
+
+    fn forget(&self) {
+        *self.reader.lock().unwrap() = None;
+    }
+}
+
+fn countries() -> HashMap<String, CountryInfo> {
+    serde_json::from_str(COUNTRIES).unwrap_or_default()
+}
+
+/// Fills in what the country table knows and the lookup did not.
+fn complete(mut inference: LocaleInference) -> LocaleInference {
+    let countries = countries();
+    let Some(country) = inference
+        .country
+        .as_deref()
+        .and_then(|code| countries.get(&code.to_ascii_uppercase()))
+    else {
+        return inference;
+    };
+    inference
+        .timezone
+        .get_or_insert_with(|| country.timezone.clone());
+    inference.locale = Some(country.locale.clone());
+    let mut languages = vec![country.locale.clone()];
+    languages.extend(country.languages.iter().cloned());
+    languages.dedup();
+    inference.languages = languages;
+    if inference.latitude.is_none() || inference.longitude.is_none() {
+        inference.latitude = Some(country.latitude);
+        inference.longitude = Some(country.longitude);
+    }
+    inference
+}
+
+fn from_database(reader: &Reader<Vec<u8>>, ip: IpAddr) -> Option<LocaleInference> {
+    let city: geoip2::City = reader.lookup(ip).ok()?;
+    let location = city.location.as_ref();
+    Some(LocaleInference {
+        exit_ip: ip.to_string(),
+        country: city
+            .country
+            .as_ref()
+            .and_then(|country| country.iso_code)
+            .map(str::to_string),
+        city: city
+            .city
+            .as_ref()
+            .and_then(|city| city.names.as_ref()?.get("en").copied())
+            .map(str::to_string),
+        timezone: location
+            .and_then(|location| location.time_zone)
+            .map(str::to_string),
+        locale: None,
+        languages: Vec::new(),
+        latitude: location.and_then(|location| location.latitude),
+        longitude: location.and_then(|location| location.longitude),
+        source: LocaleSource::Database,
+    })
+}
+
+fn from_lookup(geo: check::GeoInfo) -> LocaleInference {
+    let coordinates = geo.loc.as_deref().and_then(|loc| {
+        let (latitude, longitude) = loc.split_once(',')?;
+        Some((
+            latitude.trim().parse().ok()?,
+            longitude.trim().parse().ok()?,
+        ))
+    });
+    LocaleInference {
+        exit_ip: geo.ip,
+        country: geo.country,
+        city: geo.city,
+        timezone: geo.timezone,
+        locale: None,
+        languages: Vec::new(),
+        latitude: coordinates.map(|(latitude, _)| latitude),
+        longitude: coordinates.map(|(_, longitude)| longitude),
+        source: LocaleSource::Lookup,
+    }
+}
+
+/// The timezone, locale and coordinates matching where `proxy` exits.
+#[tauri::command]
+pub async fn infer_locale_for_proxy(
+    app_handle: AppHandle,
+    geoip: State<'_, GeoIp>,
+    proxy: ProxyConfig,
+    timeout_ms: Option<u64>,
+) -> Result<LocaleInference, String> {
+    let timeout = timeout_ms
+        .map(Duration::from_millis)
+        .unwrap_or(DEFAULT_CHECK_TIMEOUT);
+    let geo = check::exit_geo(&proxy, timeout).await?;
+    let from_database = match (geoip.reader(&app_handle)?, geo.ip.parse::<IpAddr>()) {
+        (Some(reader), Ok(ip)) => {
+            tauri::async_runtime::spawn_blocking(move || from_database(&reader, ip))
+                .await
+                .map_err(|e| e.to_string())?
+        }
+        _ => None,
+    };
+    let inference = from_database.unwrap_or_else(|| from_lookup(geo));
+    if inference.country.is_none() {
+        return Err(format!(
+            "Could not tell which country {} is in",
+            inference.exit_ip
+        ));
+    }
+    Ok(complete(inference))
+}
+
+/// The GeoIP database in use, if one is installed.
+#[tauri::command]
+pub fn get_geoip_database(
+    app_handle: AppHandle,
+    geoip: State<'_, GeoIp>,
+) -> Result<Option<GeoIpDatabase>, String> {
+    let Some(reader) = geoip.reader(&app_handle)? else {
+        return Ok(None);
+    };
+    Ok(
+        installed(&geoip_dir(&app_handle)?).map(|path| GeoIpDatabase {
+            path: path.to_string_lossy().to_string(),
+            database_type: reader.metadata.database_type.clone(),
+            build_epoch: reader.metadata.build_epoch,
+        }),
+    )
+}
+
+/// Installs the `.mmdb` database at `source`, replacing any previous one.
+#[tauri::command]
+pub async fn install_geoip_database(
+    app_handle: AppHandle,
+    geoip: State<'_, GeoIp>,
+    source: PathBuf,
+) -> Result<GeoIpDatabase, String> {
+    let dir = geoip_dir(&app_handle)?;
+    let database = tauri::async_runtime::spawn_blocking(move || {
+        // Refuse anything that is not a database before replacing the old one
+        let reader = Reader::open_readfile(&source)
+            .map_err(|e| format!("{} is not a GeoIP database: {}", source.display(), e))?;
+        let target = dir.join(format!(
+            "{}.mmdb",
+            reader.metadata.database_type.replace(['/', '\\'], "_")
+        ));
+        let partial = target.with_extension("part");
+        std::fs::copy(&source, &partial)
+            .map_err(|e| format!("Failed to copy {}: {}", source.display(), e))?;
+        if let Some(previous) = installed(&dir) {
+            let _ = std::fs::remove_file(previous);
+        }
+        std::fs::rename(&partial, &target)
+            .map_err(|e| format!("Failed to save {}: {}", target.display(), e))?;
+        Ok::<_, String>(GeoIpDatabase {
+            path: target.to_string_lossy().to_string(),
+            database_type: reader.metadata.database_type.clone(),
+            build_epoch: reader.metadata.build_epoch,
+        })
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+    geoip.forget();
+    log::info!("Installed GeoIP database {}", database.database_type);
+    Ok(database)
+}
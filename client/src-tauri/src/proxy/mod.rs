@@ -0,0 +1,111 @@
+//! Proxies as the shell handles them, in the same shape the backend's
+//! proxy API uses.
+
+pub mod bridge;
+pub mod check;
+pub mod import;
+pub mod locale;
+pub mod pool;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProxyProtocol {
+    #[default]
+    Http,
+    Https,
+    Socks4,
+    Socks5,
+}
+
+impl ProxyProtocol {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Http => "http",
+            Self::Https => "https",
+            Self::Socks4 => "socks4",
+            Self::Socks5 => "socks5",
+        }
+    }
+
+    /// Reads a URL scheme, treating `socks5h` as SOCKS5.
+    pub fn from_scheme(scheme: &str) -> Option<Self> {
+        match scheme.to_ascii_lowercase().as_str() {
+            "http" => Some(Self::Http),
+            "https" => Some(Self::Https),
+            "socks4" | "socks4a" => Some(Self::Socks4),
+            "socks5" | "socks5h" | "socks" => Some(Self::Socks5),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ProxyConfig {
+    pub host: String,
+    pub port: u16,
+    #[serde(default)]
+    pub protocol: ProxyProtocol,
+    pub username: Option<String>,
+    pub password: Option<String>,
+}
+
+impl ProxyConfig {
+    /// IPv6 hosts need brackets in URLs.
+    fn url_host(&self) -> String {
+        if self.host.contains(':') && !self.host.starts_with('[') {
+            format!("[{}]", self.host)
+        } else {
+            self.host.clone()
+        }
+    }
+
+    /// The proxy URL for reqwest, credentials included. SOCKS5 uses the
+    /// `socks5h` scheme so host names are resolved by the proxy and DNS
+    /// lookups do not leak from this machine.
+    pub fn url(&self) -> Result<reqwest::Url, String> {
+        let scheme = match self.protocol {
+            ProxyProtocol::Socks5 => "socks5h",
+            protocol => protocol.as_str(),
+        };
+        let mut url =
+            reqwest::Url::parse(&format!("{}://{}:{}", scheme, self.url_host(), self.port))
+                .map_err(|e| format!("Invalid proxy {}: {}", self, e))?;
+        if let Some(username) = self.username.as_deref().filter(|u| !u.is_empty()) {
+            let _ = url.set_username(username);
+            let _ = url.set_password(self.password.as_deref());
+        }
+        Ok(url)
+    }
+
+    /// `protocol://[user:pass@]host:port`, as [`import::parse_line`] reads it
+    /// back, credentials included.
+    pub fn to_line(&self) -> String {
+        let credentials = match (self.username.as_deref(), self.password.as_deref()) {
+            (Some(username), Some(password)) => format!("{}:{}@", username, password),
+            (Some(username), None) => format!("{}@", username),
+            _ => String::new(),
+        };
+        format!(
+            "{}://{}{}:{}",
+            self.protocol.as_str(),
+            credentials,
+            self.url_host(),
+            self.port
+        )
+    }
+}
+
+/// `protocol://host:port`, never with the password.
+impl std::fmt::Display for ProxyConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}://{}:{}",
+            self.protocol.as_str(),
+            self.url_host(),
+            self.port
+        )
+    }
+}
@@ -0,0 +1,657 @@
+//! A managed pool of proxies that profiles draw from instead of each having
+//! one of its own.
+//!
+//! Proxies only join the pool once a check passed. From then on every check
+//! of them, whether from the proxy tester, a check job or the pool itself,
+//! updates their record: successes, failures and a smoothed latency. Only
+//! proxies whose latest check passed are handed out by [`next_proxy`], by
+//! the [`PoolStrategy`] asked for.
+//!
+//! [`spawn`] re-checks every proxy in the pool on the interval in
+//! `proxyPool` of the config. One failing that many checks in a row is
+//! quarantined: it is no longer handed out, and only probed now and then
+//! until a check passes again. Each change to the pool is announced as
+//! `proxy://pool-changed`, carrying the whole pool.
+//!
+//! A profile whose `proxy` is `"pool"` or `{"pool": "<strategy>"}`,
+//! optionally with a `country`, gets a proxy from the pool at each launch;
+//! `sticky`, the default, keeps giving it the same one while it stays
+//! healthy. The pool is kept in [`POOL_FILE`] in app data, with each
+//! proxy's password in the OS keychain and only a `keychain:` reference to
+//! it in the file; see [`crate::secrets`].
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use tauri::{AppHandle, Emitter, Manager};
+
+use super::check::{self, ProxyCheck, DEFAULT_CHECK_TIMEOUT, DEFAULT_CONCURRENCY};
+use super::ProxyConfig;
+use crate::audit::{self, Initiator};
+use crate::config::ConfigStore;
+use crate::secrets::{self, SECRET_REF_PREFIX};
+
+pub const POOL_FILE: &str = "proxy-pool.json";
+/// Weight of the newest latency in the smoothed one.
+const LATENCY_WEIGHT: f64 = 0.3;
+/// How often [`spawn`] looks for proxies due a check.
+const RECHECK_POLL: Duration = Duration::from_secs(60);
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PoolProxy {
+    /// Derived from the proxy itself, so adding it twice finds the same one.
+    pub id: String,
+    pub proxy: ProxyConfig,
+    pub exit_ip: Option<String>,
+    /// ISO 3166 country code of the exit IP.
+    pub country: Option<String>,
+    pub city: Option<String>,
+    pub successes: u32,
+    pub failures: u32,
+    /// Failed checks since the last one that passed.
+    pub consecutive_failures: u32,
+    /// Smoothed over recent checks.
+    pub latency_ms: Option<u64>,
+    pub last_checked_at: Option<DateTime<Utc>>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    /// Times it was handed out.
+    pub uses: u64,
+    /// Why the latest check failed; `None` once one passed.
+    #[serde(default)]
+    pub last_error: Option<String>,
+    /// Set while it fails too often to be handed out.
+    #[serde(default)]
+    pub quarantined_at: Option<DateTime<Utc>>,
+}
+
+impl PoolProxy {
+    fn healthy(&self) -> bool {
+        self.consecutive_failures == 0 && self.quarantined_at.is_none()
+    }
+
+    /// Whether a check is due, `recheck` after the last one, or `recovery`
+    /// after it while quarantined.
+    fn due(&self, now: DateTime<Utc>, recheck: Duration, recovery: Duration) -> bool {
+        let wait = if self.quarantined_at.is_some() {
+            recovery
+        } else {
+            recheck
+        };
+        self.last_checked_at.map_or(true, |checked| {
+            (now - checked).to_std().unwrap_or_default() >= wait
+        })
+    }
+
+    /// Counts `result`, quarantining after `quarantine_after` failures in a
+    /// row.
+    fn observe(&mut self, result: &ProxyCheck, quarantine_after: u32) {
+        let now = Utc::now();
+        self.last_checked_at = Some(now);
+        if !result.ok {
+            self.failures += 1;
+            self.consecutive_failures += 1;
+            self.last_error = Some(
+                result
+                    .error
+                    .clone()
+                    .unwrap_or_else(|| "check failed".to_string()),
+            );
+            if self.quarantined_at.is_none() && self.consecutive_failures >= quarantine_after {
+                log::warn!(
+                    "Quarantining pool proxy {} after {} failed checks",
+                    self.proxy,
+                    self.consecutive_failures
+                );
+                self.quarantined_at = Some(now);
+            }
+            return;
+        }
+        self.successes += 1;
+        self.consecutive_failures = 0;
+        self.last_error = None;
+        if self.quarantined_at.take().is_some() {
+            log::info!("Pool proxy {} recovered", self.proxy);
+        }
+        if let Some(latency) = result.latency_ms.or(result.connect_ms) {
+            self.latency_ms = Some(match self.latency_ms {
+                Some(smoothed) => (smoothed as f64 * (1.0 - LATENCY_WEIGHT)
+                    + latency as f64 * LATENCY_WEIGHT)
+                    .round() as u64,
+                None => latency,
+            });
+        }
+        if result.exit_ip.is_some() {
+            self.exit_ip = result.exit_ip.clone();
+            self.country = result.country.clone();
+            self.city = result.city.clone();
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum PoolStrategy {
+    RoundRobin,
+    /// The one handed out longest ago, or never.
+    LeastRecentlyUsed,
+    /// The same one for a profile as long as it stays healthy.
+    #[serde(rename_all = "camelCase")]
+    Sticky {
+        profile_id: String,
+    },
+    /// The least recently used of those exiting in `country`.
+    Geo {
+        country: String,
+    },
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PoolAddSummary {
+    pub added: Vec<PoolProxy>,
+    /// Proxies that failed their check, with the reason.
+    pub rejected: Vec<String>,
+}
+
+#[derive(Clone, Serialize)]
+struct PoolChangedPayload {
+    proxies: Vec<PoolProxy>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct PoolFile {
+    proxies: Vec<PoolProxy>,
+    /// The proxy each sticky profile has, by profile ID.
+    sticky: BTreeMap<String, String>,
+    /// Where round robin goes on from.
+    cursor: usize,
+}
+
+/// Managed state holding the pool.
+pub struct ProxyPool {
+    path: Option<PathBuf>,
+    file: Mutex<PoolFile>,
+}
+
+/// The keychain entry holding the password of the pool proxy `id`.
+fn secret_name(id: &str) -> String {
+    format!("proxy-pool.{}", id)
+}
+
+/// `proxy` with its password read from the keychain, if it refers to one.
+async fn resolve_password(app: &AppHandle, mut proxy: ProxyConfig) -> Result<ProxyConfig, String> {
+    let Some(name) = proxy
+        .password
+        .as_deref()
+        .and_then(|password| password.strip_prefix(SECRET_REF_PREFIX))
+    else {
+        return Ok(proxy);
+    };
+    let password = secrets::get(app, name).await?.ok_or_else(|| {
+        format!(
+            "The password of pool proxy {} is not in the keychain",
+            proxy
+        )
+    })?;
+    proxy.password = Some(password);
+    Ok(proxy)
+}
+
+/// Stores the password of the pool proxy `id` in the keychain and puts a
+/// reference to it in its place.
+async fn store_password(
+    app: &AppHandle,
+    id: &str,
+    mut proxy: ProxyConfig,
+) -> Result<ProxyConfig, String> {
+    let Some(password) = proxy
+        .password
+        .take()
+        .filter(|password| !password.starts_with(SECRET_REF_PREFIX))
+    else {
+        return Ok(proxy);
+    };
+    let name = secret_name(id);
+    secrets::store(app, &name, password).await?;
+    proxy.password = Some(format!("{}{}", SECRET_REF_PREFIX, name));
+    Ok(proxy)
+}
+
+pub(crate) fn proxy_id(proxy: &ProxyConfig) -> String {
+    Sha256::digest(proxy.to_line().as_bytes())[..8]
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+impl ProxyPool {
+    pub fn load(app: &AppHandle) -> Self {
+        let path = app
+            .path()
+            .app_data_dir()
+            .ok()
+            .map(|dir| dir.join(POOL_FILE));
+        let file = path
+            .as_deref()
+            .and_then(|path| std::fs::read(path).ok())
+            .and_then(|contents| match serde_json::from_slice(&contents) {
+                Ok(file) => Some(file),
+                Err(e) => {
+                    log::warn!("Ignoring unreadable {}: {}", POOL_FILE, e);
+                    None
+                }
+            })
+            .unwrap_or_default();
+        Self {
+            path,
+            file: Mutex::new(file),
+        }
+    }
+
+    fn save(&self, file: &PoolFile) -> Result<(), String> {
+        let Some(path) = &self.path else {
+            return Err("No app data directory to keep the proxy pool in".to_string());
+        };
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+        }
+        let json = serde_json::to_vec_pretty(file)
+            .map_err(|e| format!("Failed to encode the proxy pool: {}", e))?;
+        let partial = path.with_extension("json.part");
+        std::fs::write(&partial, json)
+            .and_then(|()| std::fs::rename(&partial, path))
+            .map_err(|e| format!("Failed to save {}: {}", path.display(), e))
+    }
+
+    /// Saves the pool and tells the UI.
+    fn changed(&self, app: &AppHandle, file: &PoolFile) {
+        if let Err(e) = self.save(file) {
+            log::warn!("{}", e);
+        }
+        let _ = app.emit(
+            "proxy://pool-changed",
+            PoolChangedPayload {
+                proxies: file.proxies.clone(),
+            },
+        );
+    }
+
+    pub fn list(&self) -> Vec<PoolProxy> {
+        self.file.lock().unwrap().proxies.clone()
+    }
+
+    /// Counts `results` of checks of `proxies` against those in the pool.
+    pub fn observe(&self, app: &AppHandle, proxies: &[ProxyConfig], results: &[ProxyCheck]) {
+        let quarantine_after = app.state::<ConfigStore>().get().proxy_pool.quarantine_after;
+        let mut file = self.file.lock().unwrap();
+        let mut changed = false;
+        for (proxy, result) in proxies.iter().zip(results) {
+            let id = proxy_id(proxy);
+            if let Some(entry) = file.proxies.iter_mut().find(|entry| entry.id == id) {
+                entry.observe(result, quarantine_after);
+                changed = true;
+            }
+        }
+        // A quarantined proxy no longer holds on to its sticky profiles
+        let PoolFile {
+            proxies, sticky, ..
+        } = &mut *file;
+        sticky.retain(|_, id| {
+            proxies
+                .iter()
+                .any(|entry| entry.id == *id && entry.quarantined_at.is_none())
+        });
+        if changed {
+            self.changed(app, &file);
+        }
+    }
+
+    /// The proxies due a check at `now`.
+    fn due(&self, app: &AppHandle, now: DateTime<Utc>) -> Vec<ProxyConfig> {
+        let config = app.state::<ConfigStore>().get().proxy_pool;
+        let recheck = Duration::from_secs(config.recheck_interval_mins * 60);
+        let recovery = Duration::from_secs(config.recovery_interval_mins * 60);
+        self.file
+            .lock()
+            .unwrap()
+            .proxies
+            .iter()
+            .filter(|entry| entry.due(now, recheck, recovery))
+            .map(|entry| entry.proxy.clone())
+            .collect()
+    }
+
+    /// Adds `proxy`, known by `id` and with its password stored already.
+    fn add(
+        &self,
+        app: &AppHandle,
+        id: String,
+        proxy: ProxyConfig,
+        result: &ProxyCheck,
+    ) -> PoolProxy {
+        let quarantine_after = app.state::<ConfigStore>().get().proxy_pool.quarantine_after;
+        let mut file = self.file.lock().unwrap();
+        let index = match file.proxies.iter().position(|entry| entry.id == id) {
+            Some(index) => index,
+            None => {
+                file.proxies.push(PoolProxy {
+                    id,
+                    proxy,
+                    exit_ip: None,
+                    country: None,
+                    city: None,
+                    successes: 0,
+                    failures: 0,
+                    consecutive_failures: 0,
+                    latency_ms: None,
+                    last_checked_at: None,
+                    last_used_at: None,
+                    uses: 0,
+                    last_error: None,
+                    quarantined_at: None,
+                });
+                file.proxies.len() - 1
+            }
+        };
+        file.proxies[index].observe(result, quarantine_after);
+        let added = file.proxies[index].clone();
+        self.changed(app, &file);
+        added
+    }
+
+    fn remove(&self, app: &AppHandle, id: &str) -> bool {
+        let mut file = self.file.lock().unwrap();
+        let before = file.proxies.len();
+        file.proxies.retain(|entry| entry.id != id);
+        file.sticky.retain(|_, proxy| proxy != id);
+        let removed = file.proxies.len() < before;
+        if removed {
+            self.changed(app, &file);
+        }
+        removed
+    }
+
+    /// Moves the passwords of pool files from before they were kept in the
+    /// keychain there. As with [`secrets::migrate`], a password is only
+    /// replaced once it is stored, so without a usable keychain the pool
+    /// stays as it was.
+    async fn migrate(&self, app: &AppHandle) {
+        let plaintext: Vec<PoolProxy> = self
+            .list()
+            .into_iter()
+            .filter(|entry| {
+                entry
+                    .proxy
+                    .password
+                    .as_deref()
+                    .is_some_and(|password| !password.starts_with(SECRET_REF_PREFIX))
+            })
+            .collect();
+        let mut moved = BTreeMap::new();
+        for entry in plaintext {
+            match store_password(app, &entry.id, entry.proxy.clone()).await {
+                Ok(stored) => {
+                    moved.insert(entry.id, (entry.proxy.password, stored.password));
+                }
+                Err(e) => {
+                    log::warn!("Leaving pool proxy passwords in plaintext: {}", e);
+                    break;
+                }
+            }
+        }
+        if moved.is_empty() {
+            return;
+        }
+
+        let mut file = self.file.lock().unwrap();
+        for entry in file.proxies.iter_mut() {
+            if let Some((password, reference)) = moved.get(&entry.id) {
+                if entry.proxy.password == *password {
+                    entry.proxy.password = reference.clone();
+                }
+            }
+        }
+        match self.save(&file) {
+            Ok(()) => log::info!("Moved {} pool proxy passwords to the keychain", moved.len()),
+            Err(e) => log::warn!("Failed to save migrated pool passwords: {}", e),
+        }
+    }
+
+    /// Picks a healthy proxy by `strategy` and marks it used.
+    pub fn next(&self, app: &AppHandle, strategy: &PoolStrategy) -> Result<PoolProxy, String> {
+        let mut file = self.file.lock().unwrap();
+        let healthy: Vec<usize> = (0..file.proxies.len())
+            .filter(|&index| file.proxies[index].healthy())
+            .collect();
+        if healthy.is_empty() {
+            return Err("No healthy proxy in the pool".to_string());
+        }
+        let least_recent = |candidates: &[usize], proxies: &[PoolProxy]| {
+            candidates
+                .iter()
+                .copied()
+                .min_by_key(|&index| (proxies[index].last_used_at, proxies[index].uses))
+        };
+
+        let index = match strategy {
+            PoolStrategy::RoundRobin => {
+                let next = healthy
+                    .iter()
+                    .copied()
+                    .find(|&index| index >= file.cursor)
+                    .unwrap_or(healthy[0]);
+                file.cursor = next + 1;
+                next
+            }
+            PoolStrategy::LeastRecentlyUsed => least_recent(&healthy, &file.proxies).unwrap(),
+            PoolStrategy::Sticky { profile_id } => {
+                let assigned = file.sticky.get(profile_id).and_then(|id| {
+                    healthy
+                        .iter()
+                        .copied()
+                        .find(|&index| file.proxies[index].id == *id)
+                });
+                match assigned {
+                    Some(index) => index,
+                    None => {
+                        let index = least_recent(&healthy, &file.proxies).unwrap();
+                        let id = file.proxies[index].id.clone();
+                        file.sticky.insert(profile_id.clone(), id);
+                        index
+                    }
+                }
+            }
+            PoolStrategy::Geo { country } => {
+                let in_country: Vec<usize> = healthy
+                    .iter()
+                    .copied()
+                    .filter(|&index| {
+                        file.proxies[index]
+                            .country
+                            .as_deref()
+                            .is_some_and(|c| c.eq_ignore_ascii_case(country))
+                    })
+                    .collect();
+                least_recent(&in_country, &file.proxies)
+                    .ok_or_else(|| format!("No healthy proxy in the pool exits in {}", country))?
+            }
+        };
+
+        let entry = &mut file.proxies[index];
+        entry.last_used_at = Some(Utc::now());
+        entry.uses += 1;
+        let picked = entry.clone();
+        self.changed(app, &file);
+        Ok(picked)
+    }
+}
+
+/// The strategy a profile's `proxy` asks for, if it draws from the pool.
+/// A bare `"pool"` is sticky; a `country` makes any but round robin and
+/// least recently used pick from that country.
+fn profile_strategy(profile_id: &str, proxy: &Value) -> Option<PoolStrategy> {
+    let pool = match proxy {
+        Value::String(server) if server == "pool" => None,
+        proxy => Some(proxy.get("pool")?.as_str()),
+    }
+    .flatten();
+    let country = proxy.get("country").and_then(Value::as_str);
+    let strategy = match (pool, country) {
+        (Some("round-robin"), _) => PoolStrategy::RoundRobin,
+        (Some("least-recently-used"), _) => PoolStrategy::LeastRecentlyUsed,
+        (_, Some(country)) => PoolStrategy::Geo {
+            country: country.to_string(),
+        },
+        _ => PoolStrategy::Sticky {
+            profile_id: profile_id.to_string(),
+        },
+    };
+    Some(strategy)
+}
+
+/// Replaces a pool reference in the profile's `config` with a proxy from the
+/// pool. Configs with a proxy of their own, or none, are left as they are.
+pub async fn resolve_profile_proxy(
+    app: &AppHandle,
+    profile_id: &str,
+    mut config: Value,
+) -> Result<Value, String> {
+    let Some(strategy) = config
+        .get("proxy")
+        .and_then(|proxy| profile_strategy(profile_id, proxy))
+    else {
+        return Ok(config);
+    };
+    let picked = app.state::<ProxyPool>().next(app, &strategy)?;
+    log::info!(
+        "Profile {} uses pool proxy {} ({})",
+        profile_id,
+        picked.proxy,
+        picked.id
+    );
+    let proxy = resolve_password(app, picked.proxy).await?;
+    let bridge = config["proxy"].get("bridge").cloned();
+    config["proxy"] = json!({
+        "server": proxy.to_string(),
+        "username": proxy.username,
+        "password": proxy.password,
+    });
+    if let Some(bridge) = bridge {
+        config["proxy"]["bridge"] = bridge;
+    }
+    Ok(config)
+}
+
+/// Re-checks the proxies in the pool as they come due, after moving any
+/// plaintext passwords into the keychain.
+pub fn spawn(app: &AppHandle) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(audit::scoped(Initiator::System, async move {
+        app.state::<ProxyPool>().migrate(&app).await;
+        let mut interval = tokio::time::interval(RECHECK_POLL);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+            if !app.state::<ConfigStore>().get().proxy_pool.recheck_enabled {
+                continue;
+            }
+            let pool = app.state::<ProxyPool>();
+            let mut due = Vec::new();
+            for proxy in pool.due(&app, Utc::now()) {
+                match resolve_password(&app, proxy).await {
+                    Ok(proxy) => due.push(proxy),
+                    Err(e) => log::warn!("Not re-checking a pool proxy: {}", e),
+                }
+            }
+            if due.is_empty() {
+                continue;
+            }
+            log::debug!("Re-checking {} pool proxies", due.len());
+            let results = check::check_all(
+                due.clone(),
+                DEFAULT_CHECK_TIMEOUT,
+                DEFAULT_CONCURRENCY,
+                |_, _| {},
+            )
+            .await;
+            pool.observe(&app, &due, &results);
+        }
+    }));
+}
+
+#[tauri::command]
+pub fn list_pool_proxies(pool: tauri::State<'_, ProxyPool>) -> Vec<PoolProxy> {
+    pool.list()
+}
+
+/// Checks `configs` and adds the ones that pass to the pool.
+#[tauri::command]
+pub async fn add_pool_proxies(
+    app_handle: AppHandle,
+    configs: Vec<ProxyConfig>,
+    timeout_ms: Option<u64>,
+) -> Result<PoolAddSummary, String> {
+    let timeout = timeout_ms
+        .map(Duration::from_millis)
+        .unwrap_or(DEFAULT_CHECK_TIMEOUT);
+    let results = check::check_all(configs.clone(), timeout, DEFAULT_CONCURRENCY, |_, _| {}).await;
+    let pool = app_handle.state::<ProxyPool>();
+    let mut summary = PoolAddSummary {
+        added: Vec::new(),
+        rejected: Vec::new(),
+    };
+    for (proxy, result) in configs.into_iter().zip(results) {
+        if result.ok {
+            // Known by the proxy with its password, as checks report it
+            let id = proxy_id(&proxy);
+            match store_password(&app_handle, &id, proxy).await {
+                Ok(proxy) => summary
+                    .added
+                    .push(pool.add(&app_handle, id, proxy, &result)),
+                Err(e) => summary.rejected.push(format!("{}: {}", result.proxy, e)),
+            }
+        } else {
+            summary.rejected.push(format!(
+                "{}: {}",
+                result.proxy,
+                result.error.as_deref().unwrap_or("check failed")
+            ));
+        }
+    }
+    Ok(summary)
+}
+
+/// Takes a proxy out of the pool, and its password out of the keychain.
+/// Returns `false` if it was not in the pool.
+#[tauri::command]
+pub async fn remove_pool_proxy(app_handle: AppHandle, id: String) -> bool {
+    let removed = app_handle.state::<ProxyPool>().remove(&app_handle, &id);
+    if removed {
+        if let Err(e) = secrets::delete(&app_handle, &secret_name(&id)).await {
+            log::warn!("{}", e);
+        }
+    }
+    removed
+}
+
+/// Picks a proxy from the pool, with its password read from the keychain.
+#[tauri::command]
+pub async fn next_proxy(
+    app_handle: AppHandle,
+    strategy: PoolStrategy,
+) -> Result<PoolProxy, String> {
+    let mut picked = app_handle
+        .state::<ProxyPool>()
+        .next(&app_handle, &strategy)?;
+    picked.proxy = resolve_password(&app_handle, picked.proxy).await?;
+    Ok(picked)
+}
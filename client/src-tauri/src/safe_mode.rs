@@ -0,0 +1,154 @@
+//! A stripped-down boot for when the last session crashed, so whatever
+//! crashed it does not get to run again before the user has had a look.
+//!
+//! Safe mode does not auto-start the embedded server, runs no schedules or
+//! automatic backups, and holds back the extras that hook into the system,
+//! global shortcuts and the automatic update check. The shell has no plugins
+//! of its own beyond these. The main window opens right away instead of the
+//! splash. [`get_safe_mode_reasons`] says why the app is in safe mode, and
+//! [`apply_safe_mode_fix`] offers the usual fixes. [`leave_safe_mode`]
+//! restarts the app normally.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+
+use crate::cli::CliArgs;
+use crate::config::{AppConfig, ConfigStore, CONFIG_FILE};
+use crate::crash::CrashState;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SafeModeReasonKind {
+    LastSessionCrashed,
+    /// Started with `--safe-mode`.
+    Requested,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SafeModeReason {
+    pub kind: SafeModeReasonKind,
+    pub message: String,
+    pub occurred_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SafeModeFix {
+    /// Empties the local cache of backend data.
+    ClearCache,
+    /// Puts the shell's configuration back to defaults, keeping a copy of
+    /// the old one.
+    ResetConfig,
+    /// Drops a downloaded server build and checks the bundled one.
+    ReinstallServer,
+}
+
+/// Managed state saying whether this session runs in safe mode, and why.
+pub struct SafeMode {
+    reasons: Vec<SafeModeReason>,
+}
+
+impl SafeMode {
+    /// Decides on safe mode at startup, after [`crate::crash::install`].
+    pub fn detect(app: &AppHandle, cli_args: &CliArgs) -> Self {
+        let mut reasons = Vec::new();
+        if cli_args.safe_mode {
+            reasons.push(SafeModeReason {
+                kind: SafeModeReasonKind::Requested,
+                message: "Started with --safe-mode".to_string(),
+                occurred_at: None,
+            });
+        }
+        let recovery = app
+            .try_state::<CrashState>()
+            .and_then(|state| state.recovery());
+        if let Some(recovery) = recovery {
+            let report = recovery.report.as_ref();
+            reasons.push(SafeModeReason {
+                kind: SafeModeReasonKind::LastSessionCrashed,
+                message: report.map_or_else(
+                    || "The last session crashed".to_string(),
+                    |report| format!("The last session crashed: {}", report.message),
+                ),
+                occurred_at: Some(
+                    report.map_or(recovery.session_started_at, |report| report.occurred_at),
+                ),
+            });
+        }
+        if !reasons.is_empty() {
+            log::warn!("Starting in safe mode");
+        }
+        Self { reasons }
+    }
+
+    pub fn is_active(&self) -> bool {
+        !self.reasons.is_empty()
+    }
+}
+
+/// Why the app is in safe mode; empty when it is not.
+#[tauri::command]
+pub fn get_safe_mode_reasons(safe_mode: tauri::State<'_, SafeMode>) -> Vec<SafeModeReason> {
+    safe_mode.reasons.clone()
+}
+
+fn reset_config(app: &AppHandle) -> Result<String, String> {
+    let config = app.state::<ConfigStore>();
+    let backup = app.path().app_config_dir().ok().and_then(|dir| {
+        let path = dir.join(CONFIG_FILE);
+        let backup = path.with_extension("json.bak");
+        std::fs::copy(&path, &backup).ok().map(|_| backup)
+    });
+    config.update(|c| *c = AppConfig::default())?;
+    log::info!("Reset the configuration to defaults");
+    Ok(match backup {
+        Some(backup) => format!(
+            "Reset the configuration; the old one is at {}",
+            backup.display()
+        ),
+        None => "Reset the configuration".to_string(),
+    })
+}
+
+fn reinstall_server(app: &AppHandle) -> Result<String, String> {
+    let staged = crate::server::update::staged_binary(app).is_some();
+    crate::server::update::revert_server_update(app.clone())?;
+    crate::server::binary::bundled(app, crate::SERVER_SIDECAR).map_err(|e| {
+        format!(
+            "The bundled server is damaged; reinstall the app to repair it: {}",
+            e
+        )
+    })?;
+    Ok(if staged {
+        "Removed the downloaded server; the bundled one is intact".to_string()
+    } else {
+        "The bundled server is intact".to_string()
+    })
+}
+
+/// Applies one of the fixes and says what it did.
+#[tauri::command]
+pub async fn apply_safe_mode_fix(
+    app_handle: AppHandle,
+    fix: SafeModeFix,
+) -> Result<String, String> {
+    log::info!("Applying safe mode fix {:?}", fix);
+    match fix {
+        SafeModeFix::ClearCache => {
+            crate::cache::clear_cache(app_handle).await?;
+            Ok("Cleared the local cache".to_string())
+        }
+        SafeModeFix::ResetConfig => reset_config(&app_handle),
+        SafeModeFix::ReinstallServer => reinstall_server(&app_handle),
+    }
+}
+
+/// Restarts the app out of safe mode.
+#[tauri::command]
+pub fn leave_safe_mode(app_handle: AppHandle) {
+    log::info!("Leaving safe mode");
+    // The restart goes through a clean exit, which ends the session
+    app_handle.request_restart();
+}
@@ -0,0 +1,167 @@
+//! What a schedule can do when it fires.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tauri::AppHandle;
+
+use crate::profiles;
+use crate::server::client::{self, RequestOptions};
+
+/// Warm-ups browse for this long unless the schedule says otherwise.
+const DEFAULT_WARM_UP_SECS: u64 = 120;
+/// Longest a warm-up may keep a browser open.
+const MAX_WARM_UP_SECS: u64 = 60 * 60;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum ScheduleAction {
+    /// Opens the profile headless for a while so its session stays fresh.
+    #[serde(rename_all = "camelCase")]
+    WarmUpProfile {
+        profile_id: String,
+        duration_secs: Option<u64>,
+    },
+    /// Has the backend check the health of every proxy in the pool.
+    RecheckProxies,
+    /// Has the backend read back the actual fingerprint of these profiles,
+    /// or of every profile when none are given.
+    #[serde(rename_all = "camelCase")]
+    RefreshFingerprints {
+        #[serde(default)]
+        profile_ids: Vec<String>,
+    },
+}
+
+impl ScheduleAction {
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            Self::WarmUpProfile {
+                profile_id,
+                duration_secs,
+            } => {
+                profiles::validate_id(profile_id)?;
+                if duration_secs.is_some_and(|secs| secs == 0 || secs > MAX_WARM_UP_SECS) {
+                    return Err(format!(
+                        "A warm-up lasts between 1 and {} seconds",
+                        MAX_WARM_UP_SECS
+                    ));
+                }
+                Ok(())
+            }
+            Self::RecheckProxies => Ok(()),
+            Self::RefreshFingerprints { profile_ids } => profile_ids
+                .iter()
+                .try_for_each(|id| profiles::validate_id(id)),
+        }
+    }
+}
+
+pub(crate) async fn api(app: &AppHandle, method: &str, path: &str) -> Result<Value, String> {
+    let response = client::forward(
+        app,
+        method,
+        path,
+        None,
+        HashMap::new(),
+        RequestOptions::default(),
+    )
+    .await?;
+    if !response.ok {
+        return Err(format!(
+            "{} {} failed: HTTP {}",
+            method, path, response.status
+        ));
+    }
+    Ok(response.body)
+}
+
+/// IDs of the items of a backend listing.
+async fn list_ids(app: &AppHandle, path: &str) -> Result<Vec<String>, String> {
+    let body = api(app, "GET", path).await?;
+    Ok(body
+        .as_array()
+        .map(|items| {
+            items
+                .iter()
+                .filter_map(|item| item.get("id")?.as_str().map(str::to_string))
+                .collect()
+        })
+        .unwrap_or_default())
+}
+
+async fn warm_up(app: &AppHandle, profile_id: &str, duration: Duration) -> Result<String, String> {
+    profiles::launch_profile(app.clone(), profile_id.to_string(), Some(true)).await?;
+    tokio::time::sleep(duration).await;
+    // The user may have closed it already, which is fine
+    profiles::stop_profile(app.clone(), profile_id.to_string(), None).await?;
+    Ok(format!(
+        "Warmed up profile {} for {} seconds",
+        profile_id,
+        duration.as_secs()
+    ))
+}
+
+async fn recheck_proxies(app: &AppHandle) -> Result<String, String> {
+    let ids = list_ids(app, "/api/proxies/").await?;
+    let mut healthy = 0;
+    let mut failed = 0;
+    for id in &ids {
+        match api(app, "POST", &format!("/api/proxies/{}/check", id)).await {
+            Ok(body) if body.get("is_healthy").and_then(Value::as_bool) == Some(true) => {
+                healthy += 1
+            }
+            Ok(_) => {}
+            Err(e) => {
+                log::warn!("{}", e);
+                failed += 1;
+            }
+        }
+    }
+    let mut message = format!("{} of {} proxies are healthy", healthy, ids.len());
+    if failed > 0 {
+        message.push_str(&format!(", {} could not be checked", failed));
+    }
+    Ok(message)
+}
+
+async fn refresh_fingerprints(app: &AppHandle, profile_ids: &[String]) -> Result<String, String> {
+    let ids = if profile_ids.is_empty() {
+        list_ids(app, "/api/profiles/").await?
+    } else {
+        profile_ids.to_vec()
+    };
+    let mut errors = Vec::new();
+    for id in &ids {
+        if let Err(e) = api(app, "GET", &format!("/api/profiles/{}/fingerprint", id)).await {
+            errors.push(e);
+        }
+    }
+    if !errors.is_empty() && errors.len() == ids.len() {
+        return Err(errors.join("; "));
+    }
+    Ok(format!(
+        "Refreshed the fingerprints of {} of {} profiles",
+        ids.len() - errors.len(),
+        ids.len()
+    ))
+}
+
+/// Runs `action`, returning what it did.
+pub async fn run(app: &AppHandle, action: &ScheduleAction) -> Result<String, String> {
+    match action {
+        ScheduleAction::WarmUpProfile {
+            profile_id,
+            duration_secs,
+        } => {
+            let duration = Duration::from_secs(duration_secs.unwrap_or(DEFAULT_WARM_UP_SECS));
+            warm_up(app, profile_id, duration).await
+        }
+        ScheduleAction::RecheckProxies => recheck_proxies(app).await,
+        ScheduleAction::RefreshFingerprints { profile_ids } => {
+            refresh_fingerprints(app, profile_ids).await
+        }
+    }
+}
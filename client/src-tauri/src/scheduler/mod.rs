@@ -0,0 +1,483 @@
+//! Automation that runs on a cron schedule for as long as the app does,
+//! including while its window is closed to the tray.
+//!
+//! Schedules and the record of their runs are kept in `schedules.json` in
+//! app data. Expressions are standard five-field cron (minute, hour, day of
+//! month, month, day of week, with 0 or 7 for Sunday) in local time; a
+//! sixth leading field for seconds is accepted too. A run missed while the
+//! app was closed is made up once at the next launch. Each run is announced
+//! with `scheduler://run-started` and `scheduler://run-finished`.
+
+pub mod actions;
+
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use chrono::{DateTime, Local, Utc};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use actions::ScheduleAction;
+
+use crate::audit::{self, Initiator};
+
+const SCHEDULES_FILE: &str = "schedules.json";
+/// How often schedules are checked against the clock.
+const CHECK_INTERVAL: Duration = Duration::from_secs(30);
+/// Runs kept in the history, across all schedules.
+const MAX_HISTORY: usize = 500;
+const MAX_NAME_LEN: usize = 100;
+/// Day names by standard cron number, as the `cron` crate numbers days from
+/// Sunday = 1 instead.
+const DAY_NAMES: [&str; 7] = ["SUN", "MON", "TUE", "WED", "THU", "FRI", "SAT"];
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Schedule {
+    pub id: String,
+    pub name: String,
+    pub cron: String,
+    pub action: ScheduleAction,
+    pub enabled: bool,
+    pub created_at: DateTime<Utc>,
+    pub last_run_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScheduleInfo {
+    #[serde(flatten)]
+    pub schedule: Schedule,
+    /// `None` while disabled.
+    pub next_run_at: Option<DateTime<Utc>>,
+    pub running: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RunTrigger {
+    Schedule,
+    Manual,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScheduleRun {
+    pub schedule_id: String,
+    pub name: String,
+    pub trigger: RunTrigger,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+    pub ok: bool,
+    /// What the action did, or why it failed.
+    pub message: String,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct StartedPayload {
+    schedule_id: String,
+    trigger: RunTrigger,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct SchedulesFile {
+    schedules: Vec<Schedule>,
+    history: Vec<ScheduleRun>,
+}
+
+/// A standard day-of-week field with its numbers, single or in ranges,
+/// written as day names. `*`, its steps and names stay as they are.
+fn day_names(field: &str) -> Result<String, String> {
+    let items = field.split(',').map(|item| {
+        let (range, step) = match item.split_once('/') {
+            Some((range, step)) => (range, Some(step)),
+            None => (item, None),
+        };
+        let (start, end) = range.split_once('-').unwrap_or((range, range));
+        let (Ok(start), Ok(end)) = (start.parse::<usize>(), end.parse::<usize>()) else {
+            return Ok(item.to_string());
+        };
+        let step = match step.map(str::parse::<usize>) {
+            None => 1,
+            Some(Ok(step)) if step > 0 => step,
+            Some(_) => return Err(format!("invalid step in {:?}", item)),
+        };
+        if start > end || end > 7 {
+            return Err(format!("invalid day of week {:?}", item));
+        }
+        let days: Vec<&str> = (start..=end)
+            .step_by(step)
+            .map(|day| DAY_NAMES[day % 7])
+            .collect();
+        Ok(days.join(","))
+    });
+    Ok(items.collect::<Result<Vec<_>, String>>()?.join(","))
+}
+
+/// Parses a cron expression, with or without the seconds field.
+fn parse_cron(expression: &str) -> Result<cron::Schedule, String> {
+    let expression = expression.trim();
+    let invalid = |e: String| format!("Invalid cron expression {:?}: {}", expression, e);
+    let mut fields: Vec<String> = expression.split_whitespace().map(str::to_string).collect();
+    if fields.len() == 5 {
+        fields.insert(0, "0".to_string());
+    }
+    if let Some(days) = fields.get_mut(5) {
+        *days = day_names(days).map_err(invalid)?;
+    }
+    cron::Schedule::from_str(&fields.join(" ")).map_err(|e| invalid(e.to_string()))
+}
+
+/// The first time the schedule fires after `after`.
+fn next_after(schedule: &Schedule, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    let cron = parse_cron(&schedule.cron).ok()?;
+    let next = cron.after(&after.with_timezone(&Local)).next()?;
+    Some(next.with_timezone(&Utc))
+}
+
+fn new_id() -> String {
+    let mut bytes = [0u8; 8];
+    rand::rngs::OsRng.fill_bytes(&mut bytes);
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Managed state holding the schedules and their history.
+pub struct Scheduler {
+    path: Option<PathBuf>,
+    file: Mutex<SchedulesFile>,
+    running: Mutex<HashSet<String>>,
+}
+
+impl Scheduler {
+    pub fn load(app: &AppHandle) -> Self {
+        let path = app
+            .path()
+            .app_data_dir()
+            .ok()
+            .map(|dir| dir.join(SCHEDULES_FILE));
+        let file = path
+            .as_deref()
+            .and_then(|path| std::fs::read(path).ok())
+            .and_then(|contents| match serde_json::from_slice(&contents) {
+                Ok(file) => Some(file),
+                Err(e) => {
+                    log::warn!("Ignoring unreadable {}: {}", SCHEDULES_FILE, e);
+                    None
+                }
+            })
+            .unwrap_or_default();
+        Self {
+            path,
+            file: Mutex::new(file),
+            running: Mutex::new(HashSet::new()),
+        }
+    }
+
+    fn save(&self, file: &SchedulesFile) -> Result<(), String> {
+        let Some(path) = &self.path else {
+            return Err("No app data directory to keep schedules in".to_string());
+        };
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+        }
+        let json = serde_json::to_vec_pretty(file)
+            .map_err(|e| format!("Failed to encode schedules: {}", e))?;
+        let partial = path.with_extension("json.part");
+        std::fs::write(&partial, json)
+            .and_then(|()| std::fs::rename(&partial, path))
+            .map_err(|e| format!("Failed to save {}: {}", path.display(), e))
+    }
+
+    /// Changes the schedules and saves them.
+    fn update<T>(&self, change: impl FnOnce(&mut SchedulesFile) -> T) -> Result<T, String> {
+        let mut file = self.file.lock().unwrap();
+        let result = change(&mut file);
+        self.save(&file)?;
+        Ok(result)
+    }
+
+    fn info(&self, schedule: &Schedule) -> ScheduleInfo {
+        let now = Utc::now();
+        ScheduleInfo {
+            next_run_at: if schedule.enabled {
+                next_after(schedule, now)
+            } else {
+                None
+            },
+            running: self.running.lock().unwrap().contains(&schedule.id),
+            schedule: schedule.clone(),
+        }
+    }
+
+    fn get(&self, id: &str) -> Result<Schedule, String> {
+        self.file
+            .lock()
+            .unwrap()
+            .schedules
+            .iter()
+            .find(|schedule| schedule.id == id)
+            .cloned()
+            .ok_or_else(|| format!("No schedule {}", id))
+    }
+
+    /// Schedules whose next run since they last ran has come.
+    fn due(&self, now: DateTime<Utc>) -> Vec<Schedule> {
+        self.file
+            .lock()
+            .unwrap()
+            .schedules
+            .iter()
+            .filter(|schedule| schedule.enabled)
+            .filter(|schedule| {
+                let since = schedule.last_run_at.unwrap_or(schedule.created_at);
+                next_after(schedule, since).is_some_and(|next| next <= now)
+            })
+            .cloned()
+            .collect()
+    }
+}
+
+/// Runs `schedule`'s action and records how it went.
+async fn execute(
+    app: &AppHandle,
+    schedule: Schedule,
+    trigger: RunTrigger,
+) -> Result<ScheduleRun, String> {
+    let scheduler = app.state::<Scheduler>();
+    if !scheduler
+        .running
+        .lock()
+        .unwrap()
+        .insert(schedule.id.clone())
+    {
+        return Err(format!("Schedule {} is already running", schedule.name));
+    }
+    let started_at = Utc::now();
+    let _ = app.emit(
+        "scheduler://run-started",
+        StartedPayload {
+            schedule_id: schedule.id.clone(),
+            trigger,
+        },
+    );
+    let result = actions::run(app, &schedule.action).await;
+    scheduler.running.lock().unwrap().remove(&schedule.id);
+
+    let run = ScheduleRun {
+        schedule_id: schedule.id.clone(),
+        name: schedule.name.clone(),
+        trigger,
+        started_at,
+        finished_at: Utc::now(),
+        ok: result.is_ok(),
+        message: result.unwrap_or_else(|e| e),
+    };
+    if run.ok {
+        log::info!("Schedule {} ran: {}", schedule.name, run.message);
+    } else {
+        log::warn!("Schedule {} failed: {}", schedule.name, run.message);
+    }
+    let saved = scheduler.update(|file| {
+        if let Some(stored) = file.schedules.iter_mut().find(|s| s.id == schedule.id) {
+            stored.last_run_at = Some(started_at);
+        }
+        file.history.push(run.clone());
+        let excess = file.history.len().saturating_sub(MAX_HISTORY);
+        file.history.drain(..excess);
+    });
+    if let Err(e) = saved {
+        log::warn!("{}", e);
+    }
+    let _ = app.emit("scheduler://run-finished", run.clone());
+    Ok(run)
+}
+
+/// Fires due schedules for as long as the app runs.
+pub fn spawn(app: &AppHandle) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let mut interval = tokio::time::interval(CHECK_INTERVAL);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+            for schedule in app.state::<Scheduler>().due(Utc::now()) {
+                let app = app.clone();
+                // Each in its own task, so a long warm-up holds up nothing else
+                tauri::async_runtime::spawn(audit::scoped(Initiator::System, async move {
+                    let _ = execute(&app, schedule, RunTrigger::Schedule).await;
+                }));
+            }
+        }
+    });
+}
+
+fn validate(name: &str, cron: &str, action: &ScheduleAction) -> Result<(), String> {
+    if name.trim().is_empty() || name.len() > MAX_NAME_LEN {
+        return Err(format!(
+            "A schedule name is 1 to {} characters long",
+            MAX_NAME_LEN
+        ));
+    }
+    parse_cron(cron)?;
+    action.validate()
+}
+
+#[tauri::command]
+pub fn create_schedule(
+    scheduler: State<'_, Scheduler>,
+    name: String,
+    cron: String,
+    action: ScheduleAction,
+    enabled: Option<bool>,
+) -> Result<ScheduleInfo, String> {
+    validate(&name, &cron, &action)?;
+    let schedule = Schedule {
+        id: new_id(),
+        name: name.trim().to_string(),
+        cron: cron.trim().to_string(),
+        action,
+        enabled: enabled.unwrap_or(true),
+        created_at: Utc::now(),
+        last_run_at: None,
+    };
+    scheduler.update(|file| file.schedules.push(schedule.clone()))?;
+    log::info!("Created schedule {} ({})", schedule.name, schedule.cron);
+    Ok(scheduler.info(&schedule))
+}
+
+#[tauri::command]
+pub fn list_schedules(scheduler: State<'_, Scheduler>) -> Vec<ScheduleInfo> {
+    let schedules = scheduler.file.lock().unwrap().schedules.clone();
+    schedules
+        .iter()
+        .map(|schedule| scheduler.info(schedule))
+        .collect()
+}
+
+#[tauri::command]
+pub fn set_schedule_enabled(
+    scheduler: State<'_, Scheduler>,
+    schedule_id: String,
+    enabled: bool,
+) -> Result<ScheduleInfo, String> {
+    let schedule = scheduler.update(|file| {
+        file.schedules
+            .iter_mut()
+            .find(|schedule| schedule.id == schedule_id)
+            .map(|schedule| {
+                // Re-enabling does not make up for runs skipped meanwhile
+                if enabled && !schedule.enabled {
+                    schedule.last_run_at = Some(Utc::now());
+                }
+                schedule.enabled = enabled;
+                schedule.clone()
+            })
+    })?;
+    let schedule = schedule.ok_or_else(|| format!("No schedule {}", schedule_id))?;
+    Ok(scheduler.info(&schedule))
+}
+
+/// Deletes a schedule. Returns `false` if there was none. Its history is
+/// kept.
+#[tauri::command]
+pub fn delete_schedule(
+    scheduler: State<'_, Scheduler>,
+    schedule_id: String,
+) -> Result<bool, String> {
+    scheduler.update(|file| {
+        let before = file.schedules.len();
+        file.schedules.retain(|schedule| schedule.id != schedule_id);
+        file.schedules.len() != before
+    })
+}
+
+/// Runs a schedule's action straight away, whether or not it is enabled.
+#[tauri::command]
+pub async fn run_now(app_handle: AppHandle, schedule_id: String) -> Result<ScheduleRun, String> {
+    let schedule = app_handle.state::<Scheduler>().get(&schedule_id)?;
+    execute(&app_handle, schedule, RunTrigger::Manual).await
+}
+
+/// Past runs, newest first, of one schedule or of all of them.
+#[tauri::command]
+pub fn get_schedule_history(
+    scheduler: State<'_, Scheduler>,
+    schedule_id: Option<String>,
+    limit: Option<usize>,
+) -> Vec<ScheduleRun> {
+    scheduler
+        .file
+        .lock()
+        .unwrap()
+        .history
+        .iter()
+        .rev()
+        .filter(|run| {
+            schedule_id
+                .as_ref()
+                .map_or(true, |id| run.schedule_id == *id)
+        })
+        .take(limit.unwrap_or(MAX_HISTORY))
+        .cloned()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    fn next(expression: &str, after: DateTime<Utc>) -> DateTime<Utc> {
+        parse_cron(expression)
+            .unwrap()
+            .after(&after)
+            .next()
+            .unwrap()
+    }
+
+    #[test]
+    fn five_and_six_fields_are_accepted() {
+        let saturday = Utc.with_ymd_and_hms(2026, 10, 10, 12, 0, 20).unwrap();
+        assert_eq!(
+            next("*/15 * * * *", saturday),
+            Utc.with_ymd_and_hms(2026, 10, 10, 12, 15, 0).unwrap()
+        );
+        assert_eq!(
+            next("30 */15 * * * *", saturday),
+            Utc.with_ymd_and_hms(2026, 10, 10, 12, 0, 30).unwrap()
+        );
+        for invalid in [
+            "",
+            "* * * *",
+            "61 * * * *",
+            "every day",
+            "0 9 * * 8",
+            "0 9 * * 5-1",
+        ] {
+            assert!(parse_cron(invalid).is_err(), "{:?}", invalid);
+        }
+    }
+
+    #[test]
+    fn days_of_week_count_from_sunday_as_zero() {
+        let saturday = Utc.with_ymd_and_hms(2026, 10, 10, 12, 0, 0).unwrap();
+        let sunday = Utc.with_ymd_and_hms(2026, 10, 11, 9, 0, 0).unwrap();
+        let monday = Utc.with_ymd_and_hms(2026, 10, 12, 9, 0, 0).unwrap();
+        let friday = Utc.with_ymd_and_hms(2026, 10, 16, 9, 0, 0).unwrap();
+        assert_eq!(next("0 9 * * 1-5", saturday), monday);
+        assert_eq!(next("0 9 * * MON-FRI", saturday), monday);
+        assert_eq!(next("0 9 * * 0", saturday), sunday);
+        assert_eq!(next("0 9 * * 7", saturday), sunday);
+        assert_eq!(next("0 9 * * 5,0", sunday), friday);
+        assert_eq!(day_names("1-5/2").unwrap(), "MON,WED,FRI");
+        assert_eq!(day_names("*/2").unwrap(), "*/2");
+    }
+}
@@ -0,0 +1,227 @@
+//! Credentials kept in the OS keychain rather than in plaintext files: the
+//! Credential Manager on Windows, the Keychain on macOS and the Secret
+//! Service (GNOME Keyring, KWallet) on Linux.
+//!
+//! Config values point at a stored secret with a reference such as
+//! `keychain:server.env.OPENAI_API_KEY`, resolved only when it is used.
+//! Plaintext secrets found in the config, in the server environment and as
+//! backend tokens, are moved into the keychain and replaced with such a
+//! reference.
+
+use std::collections::BTreeMap;
+
+use keyring::Entry;
+use tauri::{AppHandle, Manager};
+
+use crate::audit::{self, AuditAction};
+use crate::config::ConfigStore;
+use crate::server::environments::token_secret;
+
+/// Marks a config value as a reference to a keychain entry.
+pub const SECRET_REF_PREFIX: &str = "keychain:";
+/// Where the token of the active connection is kept.
+pub const CONNECTION_TOKEN: &str = "connection.token";
+const MAX_NAME_LEN: usize = 256;
+
+fn validate_name(name: &str) -> Result<(), String> {
+    if name.trim().is_empty() {
+        return Err("Secret name must not be empty".to_string());
+    }
+    if name.len() > MAX_NAME_LEN || name.chars().any(char::is_control) {
+        return Err(format!("Invalid secret name: {:?}", name));
+    }
+    Ok(())
+}
+
+/// Entries are filed under the app's identifier, so they are easy to find
+/// and remove in the OS keychain manager.
+fn entry(app: &AppHandle, name: &str) -> Result<Entry, String> {
+    validate_name(name)?;
+    Entry::new(&app.config().identifier, name)
+        .map_err(|e| format!("Failed to open keychain entry {}: {}", name, e))
+}
+
+/// Keychain calls block, on Linux on a D-Bus round trip, so they run off
+/// the async runtime.
+async fn blocking<T: Send + 'static>(
+    call: impl FnOnce() -> Result<T, String> + Send + 'static,
+) -> Result<T, String> {
+    tauri::async_runtime::spawn_blocking(call)
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+pub async fn store(app: &AppHandle, name: &str, value: String) -> Result<(), String> {
+    let entry = entry(app, name)?;
+    let owned = name.to_string();
+    let stored = blocking(move || {
+        entry
+            .set_password(&value)
+            .map_err(|e| format!("Failed to store secret {}: {}", owned, e))
+    })
+    .await;
+    audit::record(
+        app,
+        AuditAction::SecretAccess,
+        format!("store {}", name),
+        &stored,
+    );
+    stored
+}
+
+/// The secret stored under `name`, or `None` if there is none.
+pub async fn get(app: &AppHandle, name: &str) -> Result<Option<String>, String> {
+    let entry = entry(app, name)?;
+    let owned = name.to_string();
+    let read = blocking(move || match entry.get_password() {
+        Ok(value) => Ok(Some(value)),
+        Err(keyring::Error::NoEntry) => Ok(None),
+        Err(e) => Err(format!("Failed to read secret {}: {}", owned, e)),
+    })
+    .await;
+    audit::record(
+        app,
+        AuditAction::SecretAccess,
+        format!("read {}", name),
+        &read,
+    );
+    read
+}
+
+/// Deletes the secret under `name`. Returns whether there was one.
+pub async fn delete(app: &AppHandle, name: &str) -> Result<bool, String> {
+    let entry = entry(app, name)?;
+    let owned = name.to_string();
+    let deleted = blocking(move || match entry.delete_credential() {
+        Ok(()) => Ok(true),
+        Err(keyring::Error::NoEntry) => Ok(false),
+        Err(e) => Err(format!("Failed to delete secret {}: {}", owned, e)),
+    })
+    .await;
+    audit::record(
+        app,
+        AuditAction::SecretAccess,
+        format!("delete {}", name),
+        &deleted,
+    );
+    deleted
+}
+
+/// Replaces every keychain reference in `env` with the secret it points at.
+/// A missing secret is an error rather than an empty variable.
+pub async fn resolve_env(
+    app: &AppHandle,
+    env: &mut BTreeMap<String, String>,
+) -> Result<(), String> {
+    for (key, value) in env.iter_mut() {
+        let Some(name) = value.strip_prefix(SECRET_REF_PREFIX) else {
+            continue;
+        };
+        let secret = get(app, name)
+            .await?
+            .ok_or_else(|| format!("Secret {} for {} is not in the keychain", name, key))?;
+        *value = secret;
+    }
+    Ok(())
+}
+
+/// Whether a server environment variable holds a secret: its name says so,
+/// or its value is a URL with a password, such as an upstream proxy.
+fn is_secret_env(key: &str, value: &str) -> bool {
+    if value.is_empty() || value.starts_with(SECRET_REF_PREFIX) {
+        return false;
+    }
+    let key = key.to_lowercase();
+    crate::diagnostics::SECRET_KEYS
+        .iter()
+        .any(|secret| key.contains(secret))
+        || reqwest::Url::parse(value).is_ok_and(|url| url.password().is_some())
+}
+
+/// Replaces `current` with a reference to `name` if that is where it was
+/// just stored.
+fn replace_moved(current: &mut String, name: &str, moved: &BTreeMap<String, String>) {
+    if moved.get(name) == Some(current) {
+        *current = format!("{}{}", SECRET_REF_PREFIX, name);
+    }
+}
+
+/// Moves plaintext secrets in the server environment and backend tokens
+/// into the keychain. A value is only replaced once it is stored, and only
+/// if it was not changed meanwhile, so without a usable keychain the config
+/// stays as it was.
+pub async fn migrate(app: &AppHandle) {
+    let config = app.state::<ConfigStore>().get();
+    let mut plaintext: Vec<(String, String)> = config
+        .server
+        .env
+        .iter()
+        .filter(|(key, value)| is_secret_env(key, value))
+        .map(|(key, value)| (format!("server.env.{}", key), value.clone()))
+        .collect();
+    if let Some(token) = config.connection.plaintext_token() {
+        plaintext.push((CONNECTION_TOKEN.to_string(), token.to_string()));
+    }
+    for environment in &config.environments {
+        if let Some(token) = environment.connection.plaintext_token() {
+            plaintext.push((token_secret(&environment.name), token.to_string()));
+        }
+    }
+
+    let mut moved = BTreeMap::new();
+    for (name, value) in plaintext {
+        if let Err(e) = store(app, &name, value.clone()).await {
+            // Most likely no keychain at all, which the rest would hit too
+            log::warn!("Leaving secrets in plaintext: {}", e);
+            break;
+        }
+        moved.insert(name, value);
+    }
+    if moved.is_empty() {
+        return;
+    }
+
+    let result = app.state::<ConfigStore>().update(|c| {
+        for (key, current) in c.server.env.iter_mut() {
+            replace_moved(current, &format!("server.env.{}", key), &moved);
+        }
+        if let Some(token) = c.connection.token.as_mut() {
+            replace_moved(token, CONNECTION_TOKEN, &moved);
+        }
+        for environment in &mut c.environments {
+            if let Some(token) = environment.connection.token.as_mut() {
+                replace_moved(token, &token_secret(&environment.name), &moved);
+            }
+        }
+    });
+    match result {
+        Ok(_) => log::info!("Moved {} secrets to the keychain", moved.len()),
+        Err(e) => log::warn!("Failed to save migrated secrets: {}", e),
+    }
+}
+
+pub fn spawn_migration(app: &AppHandle) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move { migrate(&app).await });
+}
+
+/// Stores `value` under `name`, replacing any previous value.
+#[tauri::command]
+pub async fn store_secret(
+    app_handle: AppHandle,
+    name: String,
+    value: String,
+) -> Result<(), String> {
+    store(&app_handle, &name, value).await
+}
+
+#[tauri::command]
+pub async fn get_secret(app_handle: AppHandle, name: String) -> Result<Option<String>, String> {
+    get(&app_handle, &name).await
+}
+
+/// Returns whether there was a secret to delete.
+#[tauri::command]
+pub async fn delete_secret(app_handle: AppHandle, name: String) -> Result<bool, String> {
+    delete(&app_handle, &name).await
+}
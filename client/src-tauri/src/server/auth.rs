@@ -0,0 +1,105 @@
+//! Shared secret between the shell and the embedded server, so nothing else
+//! on localhost can use the backend API.
+//!
+//! A fresh token is generated every launch and handed to the server in
+//! [`TOKEN_ENV`]; every request to it carries the token in [`TOKEN_HEADER`].
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use rand::RngCore;
+use tauri::{AppHandle, Manager};
+
+use super::client::ApiResponse;
+use super::connection;
+use crate::config::ConfigStore;
+use crate::secrets;
+
+pub const TOKEN_ENV: &str = "NYX_API_TOKEN";
+pub const TOKEN_HEADER: &str = "X-Nyx-Token";
+
+/// Managed state holding this launch's token.
+pub struct ApiToken(String);
+
+impl ApiToken {
+    pub fn generate() -> Self {
+        let mut bytes = [0u8; 32];
+        rand::rngs::OsRng.fill_bytes(&mut bytes);
+        Self(bytes.iter().map(|b| format!("{:02x}", b)).collect())
+    }
+
+    pub fn value(&self) -> &str {
+        &self.0
+    }
+}
+
+/// Managed state holding the token of the external backend, resolved from
+/// the keychain whenever the connection changes.
+#[derive(Default)]
+pub struct ExternalToken(Mutex<Option<String>>);
+
+/// Resolves the configured external token, so requests need not wait on
+/// the keychain.
+pub async fn load_external_token(app: &AppHandle) -> Result<(), String> {
+    let connection = app.state::<ConfigStore>().get().connection;
+    let token = match connection.token.filter(|_| connection::is_external(app)) {
+        Some(token) => match token.strip_prefix(secrets::SECRET_REF_PREFIX) {
+            Some(name) => Some(secrets::get(app, name).await?.ok_or_else(|| {
+                format!(
+                    "Secret {} for the server token is not in the keychain",
+                    name
+                )
+            })?),
+            None => Some(token),
+        },
+        None => None,
+    };
+    *app.state::<ExternalToken>().0.lock().unwrap() = token;
+    Ok(())
+}
+
+/// The token the backend expects. The embedded server gets this launch's
+/// token; an external one only the token configured for it, never ours, as
+/// it was not started with it and it must not leak.
+pub fn backend_token(app: &AppHandle) -> Option<String> {
+    if connection::is_external(app) {
+        return app.state::<ExternalToken>().0.lock().unwrap().clone();
+    }
+    Some(app.state::<ApiToken>().value().to_string())
+}
+
+/// Adds [`backend_token`] to a request.
+pub fn authorize(app: &AppHandle, request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+    match backend_token(app) {
+        Some(token) => request.header(TOKEN_HEADER, token),
+        None => request,
+    }
+}
+
+#[tauri::command]
+pub fn get_api_token(token: tauri::State<'_, ApiToken>) -> String {
+    token.value().to_string()
+}
+
+/// Forwards a request to the backend with the token attached. See
+/// [`super::client::api_request`] for the full set of options.
+#[tauri::command]
+pub async fn proxy_api_request(
+    app_handle: AppHandle,
+    method: String,
+    path: String,
+    body: Option<serde_json::Value>,
+) -> Result<ApiResponse, String> {
+    if let Some(throttled) = super::limits::throttle(&app_handle) {
+        return Ok(throttled);
+    }
+    super::client::forward(
+        &app_handle,
+        &method,
+        &path,
+        body,
+        HashMap::new(),
+        Default::default(),
+    )
+    .await
+}
@@ -0,0 +1,126 @@
+//! Finds something runnable that starts the server on the current platform.
+
+use std::env::consts::EXE_SUFFIX;
+use std::path::{Path, PathBuf};
+
+use tauri::{AppHandle, Manager};
+
+use super::integrity;
+use super::python;
+use super::supervisor::LaunchSpec;
+
+/// Source checkouts the dev build looks in, relative to its working directory.
+const DEV_ROOTS: &[&str] = &["../server", "./server", "../../server"];
+
+/// The bundled sidecar, or a newer downloaded build of it, run from the app
+/// data directory: the install location is read-only on Windows and macOS
+/// starts apps in `/`.
+pub fn bundled(app: &AppHandle, sidecar: &str) -> Result<LaunchSpec, String> {
+    let (spec, bundled) = match super::update::staged_binary(app) {
+        Some(path) => (LaunchSpec::new(path), false),
+        None => (LaunchSpec::sidecar(app, sidecar)?, true),
+    };
+    if !is_executable(&spec.program) {
+        return Err(format!(
+            "Server sidecar not found or not executable at {}",
+            spec.program.display()
+        ));
+    }
+    // Downloaded builds were just checked against their signed manifest
+    if bundled {
+        integrity::verify_bundled(app, &spec.program)?;
+    }
+
+    let data_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to get app data directory: {}", e))?;
+    if let Err(e) = std::fs::create_dir_all(&data_dir) {
+        log::warn!("Failed to create {}: {}", data_dir.display(), e);
+        return Ok(spec);
+    }
+    Ok(spec.current_dir(data_dir))
+}
+
+/// The first source checkout with a `main.py`, for setting up its Python.
+/// Always `None` in release builds, like [`dev_candidates`].
+pub fn dev_root() -> Option<PathBuf> {
+    if !cfg!(debug_assertions) {
+        return None;
+    }
+    DEV_ROOTS
+        .iter()
+        .map(PathBuf::from)
+        .find(|root| root.join("main.py").is_file())
+        .map(|root| absolute(&root))
+}
+
+/// Ways to run the server from a source checkout, best first: a frozen
+/// build in `dist/`, then `main.py` through the checkout's virtualenv or a
+/// new enough Python on PATH (see [`python::locate`]). Each runs
+/// from the checkout root so the server finds its `.env` and packages.
+///
+/// Release builds only ever run the verified sidecar, never whatever happens
+/// to sit next to the working directory.
+pub fn dev_candidates() -> Vec<(PathBuf, LaunchSpec)> {
+    let mut candidates = Vec::new();
+    if !cfg!(debug_assertions) {
+        return candidates;
+    }
+    for root in DEV_ROOTS.iter().map(PathBuf::from) {
+        for name in ["nyx-server", "main"] {
+            let exe = root.join("dist").join(format!("{}{}", name, EXE_SUFFIX));
+            if is_executable(&exe) {
+                let spec = LaunchSpec::new(absolute(&exe)).current_dir(absolute(&root));
+                candidates.push((exe, spec));
+            }
+        }
+
+        let script = root.join("main.py");
+        if !script.is_file() {
+            continue;
+        }
+        let Some(python) = python::locate(&absolute(&root)) else {
+            log::warn!("Found {} but no usable Python to run it", script.display());
+            continue;
+        };
+        let spec = python
+            .args
+            .iter()
+            .fold(LaunchSpec::new(&python.program), |spec, arg| spec.arg(arg))
+            .arg(absolute(&script).display().to_string())
+            .current_dir(absolute(&root));
+        candidates.push((script, spec));
+    }
+    candidates
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    path.metadata()
+        .is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+/// Relative program paths are resolved against `current_dir` on some
+/// platforms and the parent's directory on others, so avoid relying on either.
+fn absolute(path: &Path) -> PathBuf {
+    std::fs::canonicalize(path)
+        .map(strip_verbatim)
+        .unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// `canonicalize` returns `\\?\` paths on Windows, which Python rejects as
+/// a working directory.
+fn strip_verbatim(path: PathBuf) -> PathBuf {
+    match path.to_str().and_then(|p| p.strip_prefix(r"\\?\")) {
+        Some(stripped) => PathBuf::from(stripped),
+        None => path,
+    }
+}
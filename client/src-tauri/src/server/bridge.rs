@@ -0,0 +1,156 @@
+//! Relays the backend's websocket pushes to the frontend as Tauri events, so
+//! the webview gets live updates without reaching the socket itself.
+//!
+//! A JSON message with a string `type` (or `event`) field is emitted as
+//! `backend://<type>`; anything else goes out as `backend://message`. The
+//! bridge announces itself with `backend://connected` and
+//! `backend://disconnected`. It sends the same token as every other request
+//! to the backend, which checks it before accepting the socket. [`BackendBridge::reconnect`] drops the socket
+//! and dials again, for when it may have died without noticing, e.g.
+//! across a sleep of the machine.
+
+use std::time::Duration;
+
+use futures_util::StreamExt;
+use tauri::{AppHandle, Emitter, Manager};
+use tokio::sync::Notify;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::http::HeaderValue;
+use tokio_tungstenite::tungstenite::Message;
+
+use super::auth::{self, TOKEN_HEADER};
+use super::connection;
+use super::status::{ServerState, ServerStatus};
+use crate::bandwidth::{self, Feature};
+
+const WS_PATH: &str = "/ws";
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// Managed state for poking the bridge from elsewhere.
+#[derive(Default)]
+pub struct BackendBridge {
+    reconnect: Notify,
+}
+
+impl BackendBridge {
+    /// Closes the current connection, if any; the bridge dials again a
+    /// second later.
+    pub fn reconnect(&self) {
+        self.reconnect.notify_waiters();
+    }
+}
+
+/// Keeps a websocket to the backend open for as long as the app runs,
+/// reconnecting with backoff whenever it drops.
+pub fn spawn(app: &AppHandle) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let mut state = app.state::<ServerStatus>().subscribe();
+        let mut backoff = INITIAL_BACKOFF;
+        loop {
+            // No point dialing a server that is not up
+            while !matches!(
+                *state.borrow_and_update(),
+                ServerState::Healthy | ServerState::Degraded
+            ) {
+                if state.changed().await.is_err() {
+                    return;
+                }
+            }
+
+            match connect(&app).await {
+                Ok(()) => backoff = INITIAL_BACKOFF,
+                Err(e) => log::debug!("Backend websocket unavailable: {}", e),
+            }
+            tokio::time::sleep(backoff).await;
+            backoff = (backoff * 2).min(MAX_BACKOFF);
+        }
+    });
+}
+
+/// Runs one connection until it closes. Returns an error only if it could
+/// not be established.
+async fn connect(app: &AppHandle) -> Result<(), String> {
+    let url = ws_url(app);
+    let mut request = url
+        .as_str()
+        .into_client_request()
+        .map_err(|e| format!("Invalid websocket URL {}: {}", url, e))?;
+    if let Some(token) = auth::backend_token(app) {
+        let token = HeaderValue::from_str(&token)
+            .map_err(|_| "The backend token cannot be sent as a header".to_string())?;
+        request.headers_mut().insert(TOKEN_HEADER, token);
+    }
+
+    let (mut socket, _) = tokio_tungstenite::connect_async(request)
+        .await
+        .map_err(|e| e.to_string())?;
+    log::info!("Connected to backend websocket at {}", url);
+    let _ = app.emit("backend://connected", ());
+
+    let bridge = app.state::<BackendBridge>();
+    loop {
+        let message = tokio::select! {
+            message = socket.next() => message,
+            _ = bridge.reconnect.notified() => {
+                log::info!("Reconnecting backend websocket");
+                break;
+            }
+        };
+        let Some(message) = message else {
+            break;
+        };
+        if let Ok(message) = &message {
+            bandwidth::record(app, Feature::Websocket, 0, message.len() as u64);
+        }
+        match message {
+            Ok(Message::Text(text)) => dispatch(app, text.as_str()),
+            Ok(Message::Binary(bytes)) => dispatch(app, &String::from_utf8_lossy(&bytes)),
+            Ok(Message::Close(_)) => break,
+            Ok(_) => {}
+            Err(e) => {
+                log::warn!("Backend websocket error: {}", e);
+                break;
+            }
+        }
+    }
+
+    log::info!("Backend websocket disconnected");
+    let _ = app.emit("backend://disconnected", ());
+    Ok(())
+}
+
+fn dispatch(app: &AppHandle, text: &str) {
+    let payload =
+        serde_json::from_str(text).unwrap_or_else(|_| serde_json::Value::String(text.to_string()));
+    let event = payload
+        .get("type")
+        .or_else(|| payload.get("event"))
+        .and_then(|kind| kind.as_str())
+        .map(event_name)
+        .unwrap_or_else(|| "backend://message".to_string());
+    let _ = app.emit(&event, payload);
+}
+
+/// Tauri only accepts alphanumerics and `-/:_` in event names.
+fn event_name(kind: &str) -> String {
+    let kind: String = kind
+        .chars()
+        .map(|c| match c {
+            c if c.is_ascii_alphanumeric() || matches!(c, '-' | '/' | ':' | '_') => c,
+            _ => '_',
+        })
+        .collect();
+    format!("backend://{}", kind)
+}
+
+fn ws_url(app: &AppHandle) -> String {
+    let base = connection::base_url(app);
+    let base = match base.split_once("://") {
+        Some(("https", rest)) => format!("wss://{}", rest),
+        Some((_, rest)) => format!("ws://{}", rest),
+        None => format!("ws://{}", base),
+    };
+    format!("{}{}", base, WS_PATH)
+}
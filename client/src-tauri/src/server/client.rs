@@ -0,0 +1,274 @@
+//! The shell's HTTP client for the backend, and the `api_request` command
+//! that lets the frontend go through it instead of talking to localhost.
+//!
+//! One pooled client, built from [`HttpConfig`], serves every request the
+//! shell makes. [`send`] wraps it with exponential-backoff retries.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+
+use super::limits::{self, ApiGuard};
+use super::trace::Pending;
+use super::{auth, connection, idle, queue};
+use crate::bandwidth::{self, Feature};
+use crate::config::{ConfigStore, HttpConfig};
+
+/// Managed state holding a pooled client, rebuilt when the HTTP settings or
+/// the TLS settings of the connection change.
+#[derive(Default)]
+pub struct ApiClient {
+    client: Mutex<Option<(bool, HttpConfig, reqwest::Client)>>,
+}
+
+impl ApiClient {
+    pub fn get(&self, accept_invalid_certs: bool, config: &HttpConfig) -> reqwest::Client {
+        let mut client = self.client.lock().unwrap();
+        match &*client {
+            Some((accepts, built_with, existing))
+                if *accepts == accept_invalid_certs && built_with == config =>
+            {
+                existing.clone()
+            }
+            _ => {
+                let built = reqwest::Client::builder()
+                    .danger_accept_invalid_certs(accept_invalid_certs)
+                    .timeout(Duration::from_millis(config.timeout_ms))
+                    .connect_timeout(Duration::from_millis(config.connect_timeout_ms))
+                    .pool_max_idle_per_host(config.pool_max_idle)
+                    .pool_idle_timeout(Duration::from_millis(config.pool_idle_timeout_ms))
+                    .build()
+                    .unwrap_or_default();
+                *client = Some((accept_invalid_certs, config.clone(), built.clone()));
+                built
+            }
+        }
+    }
+}
+
+/// Per-request overrides of [`HttpConfig`].
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RequestOptions {
+    pub timeout_ms: Option<u64>,
+    /// Extra attempts. Unlike the configured default, an explicit count
+    /// applies to non-idempotent methods too.
+    pub retries: Option<u32>,
+    /// Whether a mutation that is safe to repeat is queued while the
+    /// backend is down, which it is by default.
+    pub queue: Option<bool>,
+}
+
+impl RequestOptions {
+    pub fn timeout(timeout: Duration) -> Self {
+        Self {
+            timeout_ms: Some(timeout.as_millis() as u64),
+            retries: None,
+            queue: None,
+        }
+    }
+
+    pub fn no_retry(mut self) -> Self {
+        self.retries = Some(0);
+        self
+    }
+}
+
+/// Sends the request `build` produces, building it again for each retry of
+/// a connect error or timeout. The delay doubles from `retry_delay_ms` up to
+/// `max_retry_delay_ms`.
+pub async fn send(
+    app: &AppHandle,
+    options: RequestOptions,
+    build: impl Fn(&reqwest::Client) -> reqwest::RequestBuilder,
+) -> Result<reqwest::Response, reqwest::Error> {
+    let config = app.state::<ConfigStore>().get().http;
+    let client = connection::http_client(app);
+    let mut retries = None;
+    let mut attempt = 0;
+    loop {
+        let mut request = build(&client);
+        if let Some(timeout) = options.timeout_ms {
+            request = request.timeout(Duration::from_millis(timeout));
+        }
+        let retries = *retries.get_or_insert_with(|| {
+            options
+                .retries
+                .unwrap_or_else(|| default_retries(&config, &request))
+        });
+
+        match request.send().await {
+            Err(e) if attempt < retries && (e.is_connect() || e.is_timeout()) => {
+                attempt += 1;
+                let delay = backoff(&config, attempt);
+                log::debug!(
+                    "Retrying {} ({}/{}) in {:?}: {}",
+                    e.url().map(|url| url.as_str()).unwrap_or("request"),
+                    attempt,
+                    retries,
+                    delay,
+                    e
+                );
+                tokio::time::sleep(delay).await;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Only requests that are safe to repeat are retried unless asked to.
+fn default_retries(config: &HttpConfig, request: &reqwest::RequestBuilder) -> u32 {
+    let idempotent = request
+        .try_clone()
+        .and_then(|request| request.build().ok())
+        .is_some_and(|request| request.method().is_idempotent());
+    if idempotent {
+        config.retries
+    } else {
+        0
+    }
+}
+
+fn backoff(config: &HttpConfig, attempt: u32) -> Duration {
+    let delay = config
+        .retry_delay_ms
+        .saturating_mul(1 << (attempt - 1).min(16));
+    Duration::from_millis(delay.min(config.max_retry_delay_ms))
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiResponse {
+    pub status: u16,
+    pub ok: bool,
+    pub headers: HashMap<String, String>,
+    /// Parsed JSON, or the raw text when the body is not JSON.
+    pub body: serde_json::Value,
+}
+
+/// Sends `path` to the backend with the auth token attached, retrying
+/// requests that never got an answer.
+pub async fn forward(
+    app: &AppHandle,
+    method: &str,
+    path: &str,
+    body: Option<serde_json::Value>,
+    headers: HashMap<String, String>,
+    options: RequestOptions,
+) -> Result<ApiResponse, String> {
+    if !path.starts_with('/') {
+        return Err(format!("API path must start with '/': {}", path));
+    }
+    let method = reqwest::Method::from_bytes(method.to_uppercase().as_bytes())
+        .map_err(|_| format!("Invalid HTTP method: {}", method))?;
+    idle::wake(app).await?;
+    let url = format!("{}{}", connection::base_url(app), path);
+    let guard = app.state::<ApiGuard>();
+    guard.admit()?;
+    let mut pending = Pending::start(app, method.as_str(), &url, &headers, body.as_ref());
+
+    let response = send(app, options, |client| {
+        let mut request = client.request(method.clone(), &url);
+        for (name, value) in &headers {
+            request = request.header(name, value);
+        }
+        if let Some(body) = &body {
+            request = request.json(body);
+        }
+        auth::authorize(app, request)
+    })
+    .await
+    .map_err(|e| {
+        let error = format!("Failed to reach {}: {}", url, e);
+        guard.failed(app, error.clone());
+        if let Some(pending) = pending.take() {
+            pending.failed(app, &error);
+        }
+        error
+    })?;
+
+    let status = response.status();
+    if status.is_server_error() {
+        guard.failed(app, format!("{} answered HTTP {}", url, status.as_u16()));
+    } else {
+        guard.succeeded();
+    }
+    let headers = response
+        .headers()
+        .iter()
+        .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+        .collect();
+    let text = response.text().await.map_err(|e| {
+        let error = format!("Failed to read response from {}: {}", url, e);
+        if let Some(pending) = pending.take() {
+            pending.failed(app, &error);
+        }
+        error
+    })?;
+    if let Some(pending) = pending {
+        pending.answered(app, status.as_u16(), &headers, &text);
+    }
+    let sent = body.as_ref().map_or(0, |body| body.to_string().len());
+    bandwidth::record(app, Feature::Api, sent as u64, text.len() as u64);
+    let body = serde_json::from_str(&text).unwrap_or(serde_json::Value::String(text));
+
+    Ok(ApiResponse {
+        status: status.as_u16(),
+        ok: status.is_success(),
+        headers,
+        body,
+    })
+}
+
+/// Forwards a request to the backend through the shell, so the webview
+/// never deals with CORS, mixed content or the auth token itself.
+///
+/// A server suspended while the UI was idle is woken first; see [`idle`].
+///
+/// Mutations that are safe to repeat are queued while the backend is down
+/// and answered with `202 Accepted`; see [`queue`]. Requests over the rate
+/// limit are answered with `429 Too Many Requests`; see [`limits`].
+#[tauri::command]
+pub async fn api_request(
+    app_handle: AppHandle,
+    method: String,
+    path: String,
+    body: Option<serde_json::Value>,
+    headers: Option<HashMap<String, String>>,
+    options: Option<RequestOptions>,
+) -> Result<ApiResponse, String> {
+    if let Some(throttled) = limits::throttle(&app_handle) {
+        return Ok(throttled);
+    }
+    let headers = headers.unwrap_or_default();
+    let options = options.unwrap_or_default();
+    let queueable = options.queue != Some(false)
+        && path.starts_with('/')
+        && queue::is_queueable(&method, &headers);
+    // A server suspended for idling is woken rather than queued for
+    idle::wake(&app_handle).await?;
+    if queueable && !queue::server_is_up(&app_handle) {
+        return queue::enqueue(&app_handle, &method, &path, body, headers).await;
+    }
+    let outcome = forward(
+        &app_handle,
+        &method,
+        &path,
+        body.clone(),
+        headers.clone(),
+        options,
+    )
+    .await;
+    match outcome {
+        Err(e) if queueable => {
+            log::debug!("Queueing after a failed request: {}", e);
+            queue::enqueue(&app_handle, &method, &path, body, headers)
+                .await
+                .map_err(|_| e)
+        }
+        outcome => outcome,
+    }
+}
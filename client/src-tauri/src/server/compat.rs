@@ -0,0 +1,194 @@
+//! Checks that the backend speaks an API version this shell supports.
+//!
+//! Every time the backend comes up its `/version` is read, falling back to
+//! `info.version` of the OpenAPI document for backends from before that
+//! route, and matched against the range from [`MIN_API`] up to [`MAX_API`].
+//! A backend outside it is announced as `server://incompatible` with a
+//! suggested fix, and the splash stops at the error instead of opening the
+//! main window. A version that cannot be read is logged and let through.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use semver::{Version, VersionReq};
+use serde::Serialize;
+use serde_json::Value;
+use tauri::{AppHandle, Emitter, Manager};
+use tokio::sync::watch;
+
+use super::client::{self, RequestOptions};
+use super::status::{ServerState, ServerStatus};
+
+/// Oldest backend API version this shell works with.
+const MIN_API: (u64, u64, u64) = (1, 0, 0);
+/// First backend API version it no longer works with.
+const MAX_API: (u64, u64, u64) = (2, 0, 0);
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CompatReport {
+    pub compatible: bool,
+    /// `None` when the backend did not say.
+    pub backend_version: Option<String>,
+    pub supported: String,
+    pub shell_version: String,
+    /// What to do about an incompatible backend.
+    pub suggestion: Option<String>,
+}
+
+/// Managed state with the check of the backend currently up, `None` while
+/// it is starting or being checked.
+pub struct ServerCompat(watch::Sender<Option<CompatReport>>);
+
+impl Default for ServerCompat {
+    fn default() -> Self {
+        Self(watch::channel(None).0)
+    }
+}
+
+fn version_field(body: &Value) -> Option<String> {
+    body.get("apiVersion")
+        .or_else(|| body.get("version"))
+        .or_else(|| body.get("info").and_then(|info| info.get("version")))
+        .and_then(Value::as_str)
+        .map(str::to_string)
+}
+
+async fn backend_version(app: &AppHandle) -> Result<String, String> {
+    let mut last_error = String::new();
+    for path in ["/version", "/openapi.json"] {
+        let response = client::forward(
+            app,
+            "GET",
+            path,
+            None,
+            HashMap::new(),
+            RequestOptions::timeout(PROBE_TIMEOUT),
+        )
+        .await?;
+        if !response.ok {
+            last_error = format!("{} answered HTTP {}", path, response.status);
+            continue;
+        }
+        match version_field(&response.body) {
+            Some(version) => return Ok(version),
+            None => last_error = format!("{} has no version", path),
+        }
+    }
+    Err(last_error)
+}
+
+/// Semver, tolerating the `v` prefix and missing parts of `1.2`.
+fn parse_version(version: &str) -> Option<Version> {
+    let version = version.trim().trim_start_matches('v');
+    Version::parse(version).ok().or_else(|| {
+        let mut parts: Vec<&str> = version.split('.').collect();
+        parts.resize(3, "0");
+        Version::parse(&parts.join(".")).ok()
+    })
+}
+
+fn suggestion(app: &AppHandle, version: &Version) -> String {
+    let newer = *version >= Version::new(MAX_API.0, MAX_API.1, MAX_API.2);
+    let advice = match (newer, super::connection::is_external(app)) {
+        (true, true) => {
+            "is newer than this app supports. Update Nyx, or connect to a matching server"
+        }
+        (true, false) => "is newer than this app supports. Update Nyx, or revert the server update",
+        (false, true) => "is older than this app supports. Update the server you connect to",
+        (false, false) => {
+            "is older than this app supports. Check for server updates, or reinstall Nyx"
+        }
+    };
+    format!("The server ({}) {}.", version, advice)
+}
+
+fn supported() -> VersionReq {
+    VersionReq::parse(&supported_range()).expect("valid supported range")
+}
+
+fn supported_range() -> String {
+    format!(
+        ">={}.{}.{}, <{}.{}.{}",
+        MIN_API.0, MIN_API.1, MIN_API.2, MAX_API.0, MAX_API.1, MAX_API.2
+    )
+}
+
+pub async fn check(app: &AppHandle) -> CompatReport {
+    let mut report = CompatReport {
+        compatible: true,
+        backend_version: None,
+        supported: supported_range(),
+        shell_version: app.package_info().version.to_string(),
+        suggestion: None,
+    };
+    let version = match backend_version(app).await {
+        Ok(version) => version,
+        Err(e) => {
+            log::warn!(
+                "Could not read the backend version, assuming it is compatible: {}",
+                e
+            );
+            return report;
+        }
+    };
+    report.backend_version = Some(version.clone());
+    let Some(parsed) = parse_version(&version) else {
+        log::warn!("Backend reported an unreadable version {:?}", version);
+        return report;
+    };
+    if !supported().matches(&parsed) {
+        report.compatible = false;
+        report.suggestion = Some(suggestion(app, &parsed));
+    }
+    report
+}
+
+/// The check of the backend now up, waiting for it if it is still running.
+pub async fn wait(app: &AppHandle) -> Option<CompatReport> {
+    let mut reports = app.state::<ServerCompat>().0.subscribe();
+    let ready = tokio::time::timeout(PROBE_TIMEOUT * 3, reports.wait_for(Option::is_some)).await;
+    match ready {
+        Ok(Ok(report)) => report.clone(),
+        _ => None,
+    }
+}
+
+/// Checks the backend each time it comes up, for as long as the app runs.
+pub fn spawn(app: &AppHandle) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let mut state = app.state::<ServerStatus>().subscribe();
+        let mut was_up = false;
+        loop {
+            let up = matches!(
+                *state.borrow_and_update(),
+                ServerState::Healthy | ServerState::Degraded
+            );
+            if up && !was_up {
+                let report = check(&app).await;
+                if !report.compatible {
+                    log::error!(
+                        "Backend {} is outside the supported range {}",
+                        report.backend_version.as_deref().unwrap_or_default(),
+                        report.supported
+                    );
+                    let _ = app.emit("server://incompatible", report.clone());
+                }
+                app.state::<ServerCompat>().0.send_replace(Some(report));
+            } else if !up && was_up {
+                app.state::<ServerCompat>().0.send_replace(None);
+            }
+            was_up = up;
+            if state.changed().await.is_err() {
+                return;
+            }
+        }
+    });
+}
+
+#[tauri::command]
+pub fn get_server_compatibility(compat: tauri::State<'_, ServerCompat>) -> Option<CompatReport> {
+    compat.0.borrow().clone()
+}
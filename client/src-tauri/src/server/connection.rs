@@ -0,0 +1,125 @@
+//! Where the backend lives: the embedded server we supervise, or an
+//! external URL the user points us at.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use tauri::{AppHandle, Manager};
+
+use super::client::ApiClient;
+use super::health;
+use super::port::PortManager;
+use super::status::{ServerState, ServerStatus};
+use super::supervisor::{ServerSupervisor, DEFAULT_SHUTDOWN_GRACE};
+use crate::config::{ConfigStore, ConnectionConfig, ConnectionMode};
+use crate::power::PowerMonitor;
+
+/// How often an external backend's health endpoint is polled.
+const MONITOR_INTERVAL: Duration = Duration::from_secs(5);
+const MONITOR_TIMEOUT: Duration = Duration::from_secs(5);
+
+static MONITOR_RUNNING: AtomicBool = AtomicBool::new(false);
+
+pub fn is_external(app: &AppHandle) -> bool {
+    app.state::<ConfigStore>().get().connection.mode == ConnectionMode::ExternalUrl
+}
+
+/// Base URL the frontend and the shell should talk to, without a trailing slash.
+pub fn base_url(app: &AppHandle) -> String {
+    let connection = app.state::<ConfigStore>().get().connection;
+    match (connection.mode, connection.external_url) {
+        (ConnectionMode::ExternalUrl, Some(url)) => url.trim_end_matches('/').to_string(),
+        _ => app.state::<PortManager>().base_url(),
+    }
+}
+
+/// Base URL for the frontend: the TLS proxy's when it runs, otherwise the
+/// same as [`base_url`].
+pub fn frontend_base_url(app: &AppHandle) -> String {
+    app.state::<super::tls::TlsProxy>()
+        .url()
+        .unwrap_or_else(|| base_url(app))
+}
+
+/// The pooled HTTP client for talking to the backend, honoring the TLS
+/// settings of an external connection.
+pub fn http_client(app: &AppHandle) -> reqwest::Client {
+    let config = app.state::<ConfigStore>().get();
+    let accept_invalid_certs = config.connection.mode == ConnectionMode::ExternalUrl
+        && config.connection.accept_invalid_certs;
+    app.state::<ApiClient>()
+        .get(accept_invalid_certs, &config.http)
+}
+
+/// Rejects an external connection without a usable http(s) URL.
+pub fn validate(connection: &ConnectionConfig) -> Result<(), String> {
+    if connection.mode != ConnectionMode::ExternalUrl {
+        return Ok(());
+    }
+    let url = connection
+        .external_url
+        .as_deref()
+        .ok_or("External connection mode requires a URL")?;
+    let parsed = reqwest::Url::parse(url).map_err(|e| format!("Invalid URL {}: {}", url, e))?;
+    match parsed.scheme() {
+        "http" | "https" if parsed.host().is_some() => Ok(()),
+        _ => Err(format!("External URL must be http(s) with a host: {}", url)),
+    }
+}
+
+/// Brings the running app in line with the current connection mode: stops
+/// the embedded server and watches the external one, or the other way round.
+pub async fn apply(app: &AppHandle) -> Result<(), String> {
+    super::auth::load_external_token(app).await?;
+    if let Err(e) = super::tls::apply(app).await {
+        log::warn!("{}", e);
+    }
+    if is_external(app) {
+        app.state::<ServerSupervisor>()
+            .stop(DEFAULT_SHUTDOWN_GRACE)
+            .await;
+        spawn_monitor(app);
+        Ok(())
+    } else if app.state::<ServerSupervisor>().is_running() {
+        Ok(())
+    } else {
+        crate::launch_server(app).await.map(|_| ())
+    }
+}
+
+/// Polls the external backend so the status and tray reflect it the same way
+/// they do for the embedded one. Exits once the mode is switched back.
+pub fn spawn_monitor(app: &AppHandle) {
+    if MONITOR_RUNNING.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let status = app.state::<ServerStatus>();
+        while is_external(&app) {
+            if app.state::<PowerMonitor>().is_sleeping() {
+                tokio::time::sleep(MONITOR_INTERVAL).await;
+                continue;
+            }
+            let report = health::check(&app, &health::health_url(&app), MONITOR_TIMEOUT).await;
+            let state = *status.subscribe().borrow();
+            match (report.healthy, state) {
+                (true, ServerState::Healthy | ServerState::Degraded) => status.probed(&app, true),
+                (true, _) => {
+                    log::info!("External server at {} is reachable", report.url);
+                    status.external(&app);
+                }
+                (false, ServerState::Healthy | ServerState::Degraded) => status.probed(&app, false),
+                (false, ServerState::NotStarted | ServerState::Stopped) => status.crashed(
+                    &app,
+                    report
+                        .error
+                        .unwrap_or_else(|| format!("{} is not healthy", report.url)),
+                ),
+                (false, _) => {}
+            }
+            tokio::time::sleep(MONITOR_INTERVAL).await;
+        }
+        MONITOR_RUNNING.store(false, Ordering::SeqCst);
+    });
+}
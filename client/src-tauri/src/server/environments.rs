@@ -0,0 +1,210 @@
+//! Named backends to switch between, such as a local embedded server, one
+//! in a container and a remote staging or production server.
+//!
+//! Each environment is a complete [`ConnectionConfig`]. Switching copies it
+//! into the active connection, stops or starts the embedded server to
+//! match, and announces `environment://switched`; `server://ready` follows
+//! once the new backend answers. Tokens are moved into the keychain when an
+//! environment is saved.
+
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager};
+
+use super::connection;
+use super::status::ServerStatus;
+use crate::config::{ConfigStore, ConnectionMode, Environment};
+use crate::secrets::{self, SECRET_REF_PREFIX};
+
+const MAX_NAME_LEN: usize = 64;
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EnvironmentList {
+    pub active: Option<String>,
+    pub environments: Vec<Environment>,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SwitchedPayload {
+    name: String,
+    mode: ConnectionMode,
+    base_url: String,
+}
+
+/// The keychain entry of the token of environment `name`.
+pub(crate) fn token_secret(name: &str) -> String {
+    format!("environments.{}.token", name)
+}
+
+fn validate_name(name: &str) -> Result<(), String> {
+    let valid = !name.is_empty()
+        && name.len() <= MAX_NAME_LEN
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    if !valid {
+        return Err(format!(
+            "Invalid environment name {:?}; use up to {} letters, digits, '-', '_' or '.'",
+            name, MAX_NAME_LEN
+        ));
+    }
+    Ok(())
+}
+
+fn list(app: &AppHandle) -> EnvironmentList {
+    let config = app.state::<ConfigStore>().get();
+    EnvironmentList {
+        active: config.active_environment,
+        environments: config
+            .environments
+            .into_iter()
+            .map(|environment| Environment {
+                connection: environment.connection.redacted(),
+                ..environment
+            })
+            .collect(),
+    }
+}
+
+#[tauri::command]
+pub fn list_environments(app_handle: AppHandle) -> EnvironmentList {
+    list(&app_handle)
+}
+
+/// Adds an environment, or replaces the one with the same name. A plaintext
+/// token is stored in the keychain first, and kept as is if there is none;
+/// a redacted one keeps the token saved before.
+#[tauri::command]
+pub async fn save_environment(
+    app_handle: AppHandle,
+    mut environment: Environment,
+) -> Result<EnvironmentList, String> {
+    environment.name = environment.name.trim().to_string();
+    validate_name(&environment.name)?;
+    connection::validate(&environment.connection)?;
+    environment.connection.token = environment
+        .connection
+        .token
+        .filter(|token| !token.is_empty());
+    if let Some(saved) = app_handle
+        .state::<ConfigStore>()
+        .get()
+        .environments
+        .iter()
+        .find(|existing| existing.name == environment.name)
+    {
+        environment.connection.keep_token(&saved.connection);
+    }
+    if let Some(token) = environment
+        .connection
+        .token
+        .as_mut()
+        .filter(|token| !token.starts_with(SECRET_REF_PREFIX))
+    {
+        let name = token_secret(&environment.name);
+        match secrets::store(&app_handle, &name, token.clone()).await {
+            Ok(()) => *token = format!("{}{}", SECRET_REF_PREFIX, name),
+            Err(e) => log::warn!(
+                "Leaving the token of {} in plaintext: {}",
+                environment.name,
+                e
+            ),
+        }
+    }
+
+    app_handle.state::<ConfigStore>().update(|c| {
+        match c
+            .environments
+            .iter_mut()
+            .find(|existing| existing.name == environment.name)
+        {
+            Some(existing) => *existing = environment,
+            None => c.environments.push(environment),
+        }
+    })?;
+    Ok(list(&app_handle))
+}
+
+/// Removes an environment and its stored token. The active connection
+/// stays as it is. Returns `false` if there was no such environment.
+#[tauri::command]
+pub async fn remove_environment(app_handle: AppHandle, name: String) -> Result<bool, String> {
+    let config = app_handle.state::<ConfigStore>();
+    let Some(removed) = config
+        .get()
+        .environments
+        .into_iter()
+        .find(|environment| environment.name == name)
+    else {
+        return Ok(false);
+    };
+    config.update(|c| {
+        c.environments
+            .retain(|environment| environment.name != name);
+        if c.active_environment.as_deref() == Some(name.as_str()) {
+            c.active_environment = None;
+        }
+    })?;
+    if removed.connection.token.as_deref()
+        == Some(&format!("{}{}", SECRET_REF_PREFIX, token_secret(&name)))
+    {
+        if let Err(e) = secrets::delete(&app_handle, &token_secret(&name)).await {
+            log::warn!("Failed to delete the token of {}: {}", name, e);
+        }
+    }
+    Ok(true)
+}
+
+/// Connects to the environment `name`. When that is a different backend,
+/// operations queued while offline are dropped, as they were meant for the
+/// one being left.
+#[tauri::command]
+pub async fn switch_environment(
+    app_handle: AppHandle,
+    name: String,
+) -> Result<EnvironmentList, String> {
+    let config = app_handle.state::<ConfigStore>();
+    let environment = config
+        .get()
+        .environments
+        .into_iter()
+        .find(|environment| environment.name == name)
+        .ok_or_else(|| format!("No environment named {}", name))?;
+    connection::validate(&environment.connection)?;
+
+    let was_external = connection::is_external(&app_handle);
+    let previous_url = connection::base_url(&app_handle);
+    config.update(|c| {
+        c.connection = environment.connection.clone();
+        c.active_environment = Some(name.clone());
+    })?;
+    log::info!("Switching to environment {}", name);
+
+    if connection::base_url(&app_handle) != previous_url {
+        match super::queue::clear(&app_handle).await {
+            Ok(0) => {}
+            Ok(dropped) => log::warn!(
+                "Dropped {} operation(s) queued for the previous backend",
+                dropped
+            ),
+            Err(e) => log::warn!("Failed to clear queued operations: {}", e),
+        }
+    }
+    // The monitor of an external backend keeps running across the switch,
+    // so it is told to start over and announce the new one as ready
+    if was_external && connection::is_external(&app_handle) {
+        app_handle.state::<ServerStatus>().stopped(&app_handle);
+    }
+    connection::apply(&app_handle).await?;
+
+    let _ = app_handle.emit(
+        "environment://switched",
+        SwitchedPayload {
+            name,
+            mode: environment.connection.mode,
+            base_url: connection::base_url(&app_handle),
+        },
+    );
+    Ok(list(&app_handle))
+}
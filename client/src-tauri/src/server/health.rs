@@ -0,0 +1,290 @@
+//! Probes the server's health endpoint.
+//!
+//! The supervisor's periodic probes are kept in a [`HealthHistory`] of the
+//! last [`HISTORY_LEN`] results. A server that keeps going between healthy
+//! and unhealthy is flapping: rather than leave it limping along, it is
+//! restarted and the user is told.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager};
+
+use super::client::{self, RequestOptions};
+use super::connection;
+use super::supervisor::DEFAULT_SHUTDOWN_GRACE;
+use crate::config::ConfigStore;
+use crate::notifications::{self, NotificationKind};
+
+/// Probe results kept, about ten minutes at the supervisor's pace.
+const HISTORY_LEN: usize = 120;
+/// Changes between healthy and unhealthy within [`FLAP_WINDOW`] that count
+/// as flapping.
+const FLAP_TRANSITIONS: usize = 6;
+const FLAP_WINDOW: Duration = Duration::from_secs(2 * 60);
+/// A restart for flapping is not repeated sooner than this.
+const FLAP_COOLDOWN: Duration = Duration::from_secs(10 * 60);
+
+/// Body of the server's health endpoint. Only `status` is required so older
+/// or third-party backends still parse.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HealthDetail {
+    pub status: String,
+    pub version: Option<String>,
+    pub environment: Option<String>,
+    pub timestamp: Option<String>,
+    pub uptime: Option<f64>,
+    pub database: Option<SubsystemHealth>,
+    pub system: Option<SystemHealth>,
+    pub error: Option<String>,
+    /// Any further subsystems the backend reports.
+    #[serde(flatten)]
+    pub subsystems: BTreeMap<String, serde_json::Value>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SubsystemHealth {
+    pub status: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SystemHealth {
+    pub status: Option<String>,
+    pub cpu_percent: Option<f64>,
+    pub memory_percent: Option<f64>,
+    pub disk_percent: Option<f64>,
+}
+
+impl HealthDetail {
+    pub fn is_ok(&self) -> bool {
+        matches!(self.status.as_str(), "ok" | "healthy")
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HealthReport {
+    pub url: String,
+    pub reachable: bool,
+    pub healthy: bool,
+    pub http_status: Option<u16>,
+    pub latency_ms: u64,
+    pub detail: Option<HealthDetail>,
+    pub error: Option<String>,
+}
+
+/// One probe of the health endpoint.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HealthSample {
+    pub at: DateTime<Utc>,
+    pub healthy: bool,
+    pub latency_ms: u64,
+    pub http_status: Option<u16>,
+    pub error: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HealthHistorySnapshot {
+    /// Oldest first.
+    pub samples: Vec<HealthSample>,
+    /// Changes between healthy and unhealthy in the flap window so far.
+    pub recent_transitions: usize,
+    pub last_flap_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct FlappingPayload {
+    transitions: usize,
+    window_secs: u64,
+}
+
+#[derive(Default)]
+struct History {
+    samples: VecDeque<HealthSample>,
+    transitions: VecDeque<Instant>,
+    last_flap: Option<(Instant, DateTime<Utc>)>,
+}
+
+/// Managed state holding the supervisor's recent probes.
+#[derive(Default)]
+pub struct HealthHistory {
+    inner: Mutex<History>,
+}
+
+impl HealthHistory {
+    /// Adds the result of a probe. Returns the number of transitions once
+    /// they amount to flapping and no restart for it happened recently.
+    fn push(&self, report: &HealthReport) -> Option<usize> {
+        let mut history = self.inner.lock().unwrap();
+        let now = Instant::now();
+        let changed = history
+            .samples
+            .back()
+            .is_some_and(|last| last.healthy != report.healthy);
+        if history.samples.len() >= HISTORY_LEN {
+            history.samples.pop_front();
+        }
+        history.samples.push_back(HealthSample {
+            at: Utc::now(),
+            healthy: report.healthy,
+            latency_ms: report.latency_ms,
+            http_status: report.http_status,
+            error: report.error.clone(),
+        });
+        if changed {
+            history.transitions.push_back(now);
+        }
+        while history
+            .transitions
+            .front()
+            .is_some_and(|at| now.duration_since(*at) > FLAP_WINDOW)
+        {
+            history.transitions.pop_front();
+        }
+
+        let transitions = history.transitions.len();
+        let cooling_down = history
+            .last_flap
+            .is_some_and(|(at, _)| now.duration_since(at) < FLAP_COOLDOWN);
+        if transitions < FLAP_TRANSITIONS || cooling_down {
+            return None;
+        }
+        history.transitions.clear();
+        history.last_flap = Some((now, Utc::now()));
+        Some(transitions)
+    }
+
+    fn snapshot(&self) -> HealthHistorySnapshot {
+        let history = self.inner.lock().unwrap();
+        HealthHistorySnapshot {
+            samples: history.samples.iter().cloned().collect(),
+            recent_transitions: history.transitions.len(),
+            last_flap_at: history.last_flap.map(|(_, at)| at),
+        }
+    }
+}
+
+/// Records a probe of the supervised server and, when the server is
+/// flapping, warns the user and restarts it in the background.
+pub fn record(app: &AppHandle, report: &HealthReport) {
+    let Some(transitions) = app.state::<HealthHistory>().push(report) else {
+        return;
+    };
+    log::warn!(
+        "Server health changed {} times within {:?}, restarting it",
+        transitions,
+        FLAP_WINDOW
+    );
+    let _ = app.emit(
+        "server://flapping",
+        FlappingPayload {
+            transitions,
+            window_secs: FLAP_WINDOW.as_secs(),
+        },
+    );
+    notifications::send_or_log(
+        app,
+        NotificationKind::Server,
+        "Server is unstable",
+        "The backend keeps failing its health check and is being restarted",
+    );
+    // The restart stops the supervision that called us, so it cannot wait
+    // for it here
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        if let Err(e) = crate::relaunch_server(&app, DEFAULT_SHUTDOWN_GRACE).await {
+            log::warn!("Failed to restart the flapping server: {}", e);
+        }
+    });
+}
+
+/// The health endpoint URL, honoring the connection mode and the configured
+/// path and port override.
+pub fn health_url(app: &AppHandle) -> String {
+    let health = app.state::<ConfigStore>().get().health;
+    let url = format!("{}{}", connection::base_url(app), health.path);
+    let Some(port) = health.port else {
+        return url;
+    };
+    // A port override only makes sense for a URL that can carry one
+    match reqwest::Url::parse(&url) {
+        Ok(mut parsed) => match parsed.set_port(Some(port)) {
+            Ok(()) => parsed.to_string(),
+            Err(()) => url,
+        },
+        Err(_) => url,
+    }
+}
+
+/// Queries `url` and parses whatever health information it returns.
+pub async fn check(app: &AppHandle, url: &str, timeout: Duration) -> HealthReport {
+    let started = Instant::now();
+    let mut report = HealthReport {
+        url: url.to_string(),
+        reachable: false,
+        healthy: false,
+        http_status: None,
+        latency_ms: 0,
+        detail: None,
+        error: None,
+    };
+
+    // Callers poll, so a failed probe is reported rather than retried
+    let options = RequestOptions::timeout(timeout).no_retry();
+    let result = client::send(app, options, |client| {
+        super::auth::authorize(app, client.get(url))
+    })
+    .await;
+    match result {
+        Ok(response) => {
+            report.reachable = true;
+            report.http_status = Some(response.status().as_u16());
+            let success = response.status().is_success();
+            match response.json::<HealthDetail>().await {
+                Ok(detail) => {
+                    // The backend answers 200 with `"status": "error"` when
+                    // its own checks fail
+                    report.healthy = success && detail.is_ok();
+                    report.error = detail.error.clone();
+                    report.detail = Some(detail);
+                }
+                Err(e) => {
+                    report.healthy = success;
+                    report.error = Some(format!("Unparseable health response: {}", e));
+                }
+            }
+        }
+        Err(e) => report.error = Some(e.to_string()),
+    }
+
+    report.latency_ms = started.elapsed().as_millis() as u64;
+    report
+}
+
+/// Returns whether the health endpoint reports the server as healthy.
+pub async fn probe(app: &AppHandle, url: &str, timeout: Duration) -> bool {
+    check(app, url, timeout).await.healthy
+}
+
+#[tauri::command]
+pub async fn get_server_health_detail(app_handle: AppHandle) -> Result<HealthReport, String> {
+    Ok(check(
+        &app_handle,
+        &health_url(&app_handle),
+        Duration::from_secs(5),
+    )
+    .await)
+}
+
+/// The supervisor's recent health probes, oldest first.
+#[tauri::command]
+pub fn get_health_history(history: tauri::State<'_, HealthHistory>) -> HealthHistorySnapshot {
+    history.snapshot()
+}
@@ -0,0 +1,192 @@
+//! Suspends the embedded server while nobody is using the app.
+//!
+//! The UI sends [`report_activity`] as a heartbeat while the user interacts
+//! with it. Once it has been quiet for `idle.suspendAfterMins`, the server is
+//! stopped, or asked through `POST /power` to cut its background work,
+//! depending on `idle.action`. The next request through the shell wakes it
+//! before it is sent, so the frontend only sees a slower answer. External
+//! servers are never suspended, nor one serving running profiles.
+
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::json;
+use tauri::{AppHandle, Emitter, Manager};
+
+use super::client::{self, RequestOptions};
+use super::supervisor::{ServerSupervisor, DEFAULT_SHUTDOWN_GRACE};
+use super::{auth, connection};
+use crate::config::{ConfigStore, IdleAction};
+use crate::profiles::registry::ProfileRegistry;
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(30);
+const POWER_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IdleStatus {
+    pub idle_secs: u64,
+    /// How the server was suspended, while it is.
+    pub suspended: Option<IdleAction>,
+    pub suspended_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SuspendedPayload {
+    action: IdleAction,
+    idle_secs: u64,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ResumedPayload {
+    action: IdleAction,
+    suspended_secs: u64,
+}
+
+/// Managed state tracking the last UI activity and whether the server is
+/// suspended.
+pub struct IdleManager {
+    last_activity: std::sync::Mutex<Instant>,
+    /// Held across suspending and waking, so requests arriving meanwhile
+    /// wait for the server instead of racing it.
+    suspended: tokio::sync::Mutex<Option<(IdleAction, Instant, DateTime<Utc>)>>,
+}
+
+impl Default for IdleManager {
+    fn default() -> Self {
+        Self {
+            last_activity: std::sync::Mutex::new(Instant::now()),
+            suspended: tokio::sync::Mutex::new(None),
+        }
+    }
+}
+
+impl IdleManager {
+    fn touch(&self) {
+        *self.last_activity.lock().unwrap() = Instant::now();
+    }
+
+    fn idle_for(&self) -> Duration {
+        self.last_activity.lock().unwrap().elapsed()
+    }
+}
+
+async fn set_power(app: &AppHandle, mode: &str) -> Result<(), String> {
+    let url = format!("{}/power", connection::base_url(app));
+    let response = client::send(app, RequestOptions::timeout(POWER_TIMEOUT), |client| {
+        auth::authorize(app, client.post(&url).json(&json!({ "mode": mode })))
+    })
+    .await
+    .map_err(|e| format!("Failed to switch the server to {} power: {}", mode, e))?;
+    if !response.status().is_success() {
+        return Err(format!(
+            "Server refused {} power mode: HTTP {}",
+            mode,
+            response.status().as_u16()
+        ));
+    }
+    Ok(())
+}
+
+async fn suspend(app: &AppHandle, action: IdleAction) {
+    let idle = app.state::<IdleManager>();
+    let mut suspended = idle.suspended.lock().await;
+    if suspended.is_some() {
+        return;
+    }
+    let idle_secs = idle.idle_for().as_secs();
+    log::info!(
+        "UI idle for {} s, suspending the server ({:?})",
+        idle_secs,
+        action
+    );
+    match action {
+        IdleAction::Stop => {
+            app.state::<ServerSupervisor>()
+                .stop(DEFAULT_SHUTDOWN_GRACE)
+                .await;
+        }
+        IdleAction::LowPower => {
+            if let Err(e) = set_power(app, "low").await {
+                log::warn!("{}", e);
+                return;
+            }
+        }
+    }
+    *suspended = Some((action, Instant::now(), Utc::now()));
+    let _ = app.emit("server://suspended", SuspendedPayload { action, idle_secs });
+}
+
+/// Brings a suspended server back, waiting until it answers. Called before
+/// every request the shell forwards; does nothing unless it is suspended.
+pub async fn wake(app: &AppHandle) -> Result<(), String> {
+    let idle = app.state::<IdleManager>();
+    let mut suspended = idle.suspended.lock().await;
+    let Some((action, since, _)) = *suspended else {
+        return Ok(());
+    };
+    log::info!("Waking the server from idle suspension");
+    match action {
+        // Unless someone started it again meanwhile
+        IdleAction::Stop if app.state::<ServerSupervisor>().is_running() => {}
+        IdleAction::Stop => {
+            crate::launch_server(app).await?;
+        }
+        IdleAction::LowPower => set_power(app, "normal").await?,
+    }
+    *suspended = None;
+    // The request that woke it counts, or it would be suspended right away
+    idle.touch();
+    let _ = app.emit(
+        "server://resumed",
+        ResumedPayload {
+            action,
+            suspended_secs: since.elapsed().as_secs(),
+        },
+    );
+    Ok(())
+}
+
+/// Suspends the server whenever the UI has been idle long enough, for the
+/// lifetime of the app.
+pub fn spawn(app: &AppHandle) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let mut interval = tokio::time::interval(CHECK_INTERVAL);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+            let config = app.state::<ConfigStore>().get().idle;
+            if config.suspend_after_mins == 0 {
+                continue;
+            }
+            let threshold = Duration::from_secs(config.suspend_after_mins * 60);
+            let due = app.state::<IdleManager>().idle_for() >= threshold
+                && !connection::is_external(&app)
+                && app.state::<ServerSupervisor>().is_running()
+                && app.state::<ProfileRegistry>().list().is_empty();
+            if due {
+                suspend(&app, config.action).await;
+            }
+        }
+    });
+}
+
+/// Heartbeat from the UI: the user did something.
+#[tauri::command]
+pub fn report_activity(idle: tauri::State<'_, IdleManager>) {
+    idle.touch();
+}
+
+#[tauri::command]
+pub async fn get_idle_status(idle: tauri::State<'_, IdleManager>) -> Result<IdleStatus, String> {
+    let suspended = *idle.suspended.lock().await;
+    Ok(IdleStatus {
+        idle_secs: idle.idle_for().as_secs(),
+        suspended: suspended.map(|(action, _, _)| action),
+        suspended_at: suspended.map(|(_, _, at)| at),
+    })
+}
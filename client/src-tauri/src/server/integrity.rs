@@ -0,0 +1,72 @@
+//! Checks a server binary against a known SHA-256 before it is run, so a
+//! binary swapped after install is refused rather than executed.
+//!
+//! The bundled sidecar's hash is baked in at build time by `build.rs` (or
+//! passed in as `NYX_SERVER_SHA256`); downloaded builds are checked against
+//! the hash in their signed manifest. A mismatch is announced with
+//! `server://integrity-failure`.
+
+use std::path::Path;
+
+use serde::Serialize;
+use tauri::{AppHandle, Emitter};
+
+use super::update::sha256_file;
+
+/// Hash of the sidecar this shell was built with, if known.
+pub const BUNDLED_SHA256: Option<&str> = option_env!("NYX_SERVER_SHA256");
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct IntegrityFailurePayload {
+    path: String,
+    expected: String,
+    actual: Option<String>,
+    reason: String,
+}
+
+/// Fails unless `path` hashes to `expected`, emitting
+/// `server://integrity-failure` when it does not.
+pub fn verify(app: &AppHandle, path: &Path, expected: &str) -> Result<(), String> {
+    let (actual, reason) = match sha256_file(path) {
+        Ok(actual) if actual.eq_ignore_ascii_case(expected) => return Ok(()),
+        Ok(actual) => (Some(actual), "hash mismatch".to_string()),
+        Err(e) => (None, format!("failed to read: {}", e)),
+    };
+    log::error!(
+        "Refusing to run {}: {} (expected {}, got {})",
+        path.display(),
+        reason,
+        expected,
+        actual.as_deref().unwrap_or("nothing")
+    );
+    let _ = app.emit(
+        "server://integrity-failure",
+        IntegrityFailurePayload {
+            path: path.display().to_string(),
+            expected: expected.to_string(),
+            actual,
+            reason: reason.clone(),
+        },
+    );
+    Err(format!(
+        "Server binary {} failed its integrity check: {}",
+        path.display(),
+        reason
+    ))
+}
+
+/// Verifies the bundled sidecar against [`BUNDLED_SHA256`]. Builds without
+/// an embedded hash only get a warning, so local builds keep working.
+pub fn verify_bundled(app: &AppHandle, path: &Path) -> Result<(), String> {
+    match BUNDLED_SHA256.filter(|hash| !hash.is_empty()) {
+        Some(expected) => verify(app, path, expected),
+        None => {
+            log::warn!(
+                "No server hash was embedded in this build; not verifying {}",
+                path.display()
+            );
+            Ok(())
+        }
+    }
+}
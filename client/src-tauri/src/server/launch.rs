@@ -0,0 +1,367 @@
+//! Picks how to start the server, without trying one way after another.
+//!
+//! Every launch method is resolved at once in the background: the bundled
+//! sidecar (or a downloaded build of it), whose hash check takes a while,
+//! and in dev builds the frozen builds and scripts of source checkouts,
+//! whose Python has to be located. At startup a server that is already
+//! listening is probed for at the same time. Candidates are then started in
+//! order of preference as soon as each has resolved, and the first to pass
+//! its health check wins; resolution still running is cancelled. Only one
+//! is ever spawned at a time, since they all listen on the same port.
+//!
+//! The method that won is kept in `server-launch.json` in app data and is
+//! started next time on its own, before anything else is probed. The hint
+//! is dropped once the size, modification time or hash of the binary or
+//! script it ran changes, or when starting it fails. The file is only a
+//! hint: what it names is resolved again, and a release build never runs
+//! anything but the sidecar whatever it says.
+
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+use tokio::task::JoinSet;
+
+use super::binary;
+use super::health;
+use super::supervisor::{LaunchSpec, ServerSupervisor};
+use super::update::sha256_file;
+use crate::boot::{self, BootPhase};
+
+const LAUNCH_FILE: &str = "server-launch.json";
+/// How long a server already listening at startup gets to answer.
+const ADOPT_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LaunchMethod {
+    /// The bundled sidecar, or a downloaded build that replaces it.
+    Bundled,
+    /// A frozen build in a source checkout; dev builds only.
+    DevBinary,
+    /// `main.py` in a source checkout; dev builds only.
+    DevScript,
+}
+
+/// What a launch method's file looked like, to tell when it changed.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Fingerprint {
+    pub modified: Option<DateTime<Utc>>,
+    pub size: u64,
+    pub sha256: String,
+}
+
+impl Fingerprint {
+    fn of(path: &Path) -> std::io::Result<Self> {
+        let metadata = std::fs::metadata(path)?;
+        Ok(Self {
+            modified: metadata.modified().ok().map(DateTime::from),
+            size: metadata.len(),
+            sha256: sha256_file(path)?,
+        })
+    }
+}
+
+/// The method that started the server last and the file it ran, to try
+/// that first next time.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LastLaunch {
+    pub method: LaunchMethod,
+    /// The binary or script that was run.
+    pub path: PathBuf,
+    /// `path` as it was; the hint is dropped once it differs.
+    #[serde(default)]
+    pub fingerprint: Option<Fingerprint>,
+    pub started_at: DateTime<Utc>,
+    pub startup_ms: Option<u64>,
+}
+
+struct Candidate {
+    method: LaunchMethod,
+    path: PathBuf,
+    spec: LaunchSpec,
+}
+
+/// Candidates come from one of these; each is resolved as its own task.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Source {
+    Bundled,
+    Dev,
+}
+
+impl Source {
+    fn of(method: LaunchMethod) -> Self {
+        match method {
+            LaunchMethod::Bundled => Source::Bundled,
+            LaunchMethod::DevBinary | LaunchMethod::DevScript => Source::Dev,
+        }
+    }
+
+    fn resolve(self, app: &AppHandle) -> Result<Vec<Candidate>, String> {
+        match self {
+            Source::Bundled => {
+                let spec = binary::bundled(app, crate::SERVER_SIDECAR)?;
+                Ok(vec![Candidate {
+                    method: LaunchMethod::Bundled,
+                    path: spec.program.clone(),
+                    spec,
+                }])
+            }
+            Source::Dev => Ok(binary::dev_candidates()
+                .into_iter()
+                .map(|(path, spec)| {
+                    let method = if path.extension().is_some_and(|ext| ext == "py") {
+                        LaunchMethod::DevScript
+                    } else {
+                        LaunchMethod::DevBinary
+                    };
+                    Candidate { method, path, spec }
+                })
+                .collect()),
+        }
+    }
+}
+
+fn launch_file(app: &AppHandle) -> Option<PathBuf> {
+    app.path()
+        .app_data_dir()
+        .ok()
+        .map(|dir| dir.join(LAUNCH_FILE))
+}
+
+pub fn last_launch(app: &AppHandle) -> Option<LastLaunch> {
+    let contents = std::fs::read(launch_file(app)?).ok()?;
+    match serde_json::from_slice(&contents) {
+        Ok(last) => Some(last),
+        Err(e) => {
+            log::warn!("Ignoring unreadable {}: {}", LAUNCH_FILE, e);
+            None
+        }
+    }
+}
+
+fn forget(app: &AppHandle) {
+    if let Some(path) = launch_file(app) {
+        let _ = std::fs::remove_file(path);
+    }
+}
+
+fn remember(app: &AppHandle, last: &LastLaunch) {
+    let Some(path) = launch_file(app) else {
+        return;
+    };
+    let result = serde_json::to_vec_pretty(last)
+        .map_err(|e| e.to_string())
+        .and_then(|json| {
+            let partial = path.with_extension("json.part");
+            std::fs::write(&partial, json)
+                .and_then(|()| std::fs::rename(&partial, &path))
+                .map_err(|e| e.to_string())
+        });
+    if let Err(e) = result {
+        log::warn!("Failed to save {}: {}", path.display(), e);
+    }
+}
+
+/// How the server came to be running.
+pub enum Launched {
+    /// A server was already listening and was taken over as it is.
+    Adopted,
+    Started(String),
+}
+
+/// The last winner ready to start again, or `None` once its file changed.
+/// It is resolved again as usual and only the candidate at its path is
+/// taken, so the sidecar's integrity check is never skipped and a hint
+/// edited to run something else is ignored.
+fn hinted(app: &AppHandle, last: &LastLaunch) -> Option<Candidate> {
+    let fingerprint = Fingerprint::of(&last.path).ok();
+    if fingerprint.is_none() || fingerprint != last.fingerprint {
+        log::info!(
+            "{} changed since it last started the server; probing again",
+            last.path.display()
+        );
+        return None;
+    }
+    match last.method {
+        LaunchMethod::Bundled => Source::Bundled
+            .resolve(app)
+            .map_err(|e| log::warn!("{}", e))
+            .ok()?
+            .into_iter()
+            .next(),
+        LaunchMethod::DevBinary | LaunchMethod::DevScript => {
+            if !cfg!(debug_assertions) {
+                return None;
+            }
+            let candidate = Source::Dev
+                .resolve(app)
+                .ok()?
+                .into_iter()
+                .find(|candidate| candidate.path == last.path);
+            if candidate.is_none() {
+                log::info!(
+                    "{} is no longer a dev launch candidate; probing again",
+                    last.path.display()
+                );
+            }
+            candidate
+        }
+    }
+}
+
+/// Starts `candidate` and remembers it if it comes up.
+async fn start(app: &AppHandle, candidate: Candidate) -> Result<String, String> {
+    boot::report(
+        app,
+        BootPhase::Spawning,
+        Some(candidate.path.display().to_string()),
+    );
+    let hint = LastLaunch {
+        method: candidate.method,
+        path: candidate.path.clone(),
+        fingerprint: None,
+        started_at: Utc::now(),
+        startup_ms: None,
+    };
+    let started = app
+        .state::<ServerSupervisor>()
+        .start(app, candidate.spec, Some(crate::startup_timeout(app)))
+        .await
+        .map_err(|e| {
+            format!(
+                "Failed to start server from {}: {}",
+                candidate.path.display(),
+                e
+            )
+        })?;
+    log::info!(
+        "Server started from {} ({:?}) in {} ms",
+        candidate.path.display(),
+        candidate.method,
+        started.startup_ms.unwrap_or_default()
+    );
+    let path = candidate.path.clone();
+    let app_handle = app.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        remember(
+            &app_handle,
+            &LastLaunch {
+                fingerprint: Fingerprint::of(&hint.path).ok(),
+                startup_ms: started.startup_ms,
+                ..hint
+            },
+        )
+    });
+    Ok(format!(
+        "Server started from {} in {} ms",
+        path.display(),
+        started.startup_ms.unwrap_or_default()
+    ))
+}
+
+fn resolve_all(
+    app: &AppHandle,
+    order: &[Source],
+) -> JoinSet<(Source, Result<Vec<Candidate>, String>)> {
+    let mut resolving = JoinSet::new();
+    for source in order.iter().copied() {
+        let app = app.clone();
+        resolving.spawn_blocking(move || (source, source.resolve(&app)));
+    }
+    resolving
+}
+
+/// Starts the server by the first method that works. With `adopt`, a
+/// server that is already listening is used instead.
+pub async fn launch(app: &AppHandle, adopt: bool) -> Result<Launched, String> {
+    let last = last_launch(app);
+    // The last winner's source goes first; the rest keep their usual order
+    let mut order = vec![Source::Bundled, Source::Dev];
+    if let Some(last) = &last {
+        order.sort_by_key(|source| *source != Source::of(last.method));
+    }
+
+    // Only the hint is looked at while it might do, and everything at once
+    // otherwise
+    let (hinted, mut resolving) = match last.clone() {
+        Some(last) => {
+            let app = app.clone();
+            let hinted = tauri::async_runtime::spawn_blocking(move || hinted(&app, &last));
+            (Some(hinted), None)
+        }
+        None => (None, Some(resolve_all(app, &order))),
+    };
+
+    if adopt && health::probe(app, &health::health_url(app), ADOPT_TIMEOUT).await {
+        // Dropping the set cancels whatever is still resolving
+        return Ok(Launched::Adopted);
+    }
+
+    let mut errors = Vec::new();
+    let mut tried = None;
+    if let Some(hinted) = hinted {
+        if let Some(candidate) = hinted.await.ok().flatten() {
+            tried = Some(candidate.path.clone());
+            match start(app, candidate).await {
+                Ok(message) => return Ok(Launched::Started(message)),
+                Err(e) => {
+                    log::warn!("{}", e);
+                    errors.push(e);
+                }
+            }
+        }
+        forget(app);
+    }
+
+    let mut resolving = resolving.take().unwrap_or_else(|| resolve_all(app, &order));
+    let mut resolved: Vec<(Source, Result<Vec<Candidate>, String>)> = Vec::new();
+    for source in order {
+        let candidates = loop {
+            if let Some(index) = resolved.iter().position(|(s, _)| *s == source) {
+                break resolved.swap_remove(index).1;
+            }
+            match resolving.join_next().await {
+                Some(Ok(result)) => resolved.push(result),
+                Some(Err(e)) => log::warn!("Resolving a server launch method failed: {}", e),
+                None => break Err(format!("{:?} launch method never resolved", source)),
+            }
+        };
+        let candidates = match candidates {
+            Ok(candidates) => candidates,
+            Err(e) => {
+                log::warn!("{}", e);
+                errors.push(e);
+                continue;
+            }
+        };
+        // The hint already had its go
+        for candidate in candidates
+            .into_iter()
+            .filter(|candidate| tried.as_ref() != Some(&candidate.path))
+        {
+            match start(app, candidate).await {
+                Ok(message) => {
+                    resolving.abort_all();
+                    return Ok(Launched::Started(message));
+                }
+                Err(e) => {
+                    log::warn!("{}", e);
+                    errors.push(e);
+                }
+            }
+        }
+    }
+    Err(match errors.last() {
+        Some(last) => format!(
+            "All {} server start methods failed; the last: {}",
+            errors.len(),
+            last
+        ),
+        None => "Could not find or start server executable".to_string(),
+    })
+}
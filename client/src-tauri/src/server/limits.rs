@@ -0,0 +1,207 @@
+//! Keeps a misbehaving frontend loop from hammering the backend into the
+//! ground.
+//!
+//! Requests from the webview draw from a token bucket first. Past the limit
+//! they are answered `429 Too Many Requests` with a `Retry-After`, without
+//! reaching the backend. Every call through [`forward`](super::client::forward)
+//! also goes through a circuit breaker: after `failureThreshold` failures in
+//! a row, meaning no answer or a 5xx, calls fail right away and
+//! `proxy://circuit-open` is emitted. After `openMs` the circuit goes
+//! half-open and the health endpoint is probed; it closes again with
+//! `proxy://circuit-closed` once the backend answers, and stays open for
+//! another round otherwise. Thresholds come from [`ApiLimitsConfig`].
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use serde_json::json;
+use tauri::{AppHandle, Emitter, Manager};
+
+use super::client::ApiResponse;
+use super::health;
+use crate::config::{ApiLimitsConfig, ConfigStore};
+
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CircuitState {
+    Closed,
+    /// Failing fast until the next probe.
+    Open,
+    /// The health endpoint is being probed.
+    HalfOpen,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CircuitStatus {
+    pub state: CircuitState,
+    /// Failed calls in a row.
+    pub failures: u32,
+    pub last_error: Option<String>,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CircuitOpenPayload {
+    failures: u32,
+    last_error: Option<String>,
+    /// When the backend is probed next.
+    retry_in_ms: u64,
+}
+
+struct Bucket {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+/// Managed state holding the token bucket and the circuit breaker.
+pub struct ApiGuard {
+    bucket: Mutex<Option<Bucket>>,
+    circuit: Mutex<CircuitStatus>,
+}
+
+impl Default for ApiGuard {
+    fn default() -> Self {
+        Self {
+            bucket: Mutex::new(None),
+            circuit: Mutex::new(CircuitStatus {
+                state: CircuitState::Closed,
+                failures: 0,
+                last_error: None,
+            }),
+        }
+    }
+}
+
+impl ApiGuard {
+    /// Takes a token, or says how long until the next one.
+    fn take_token(&self, config: &ApiLimitsConfig) -> Result<(), Duration> {
+        if config.requests_per_sec == 0 {
+            return Ok(());
+        }
+        let rate = f64::from(config.requests_per_sec);
+        let burst = f64::from(config.burst.max(1));
+        let now = Instant::now();
+        let mut bucket = self.bucket.lock().unwrap();
+        let bucket = bucket.get_or_insert(Bucket {
+            tokens: burst,
+            refilled_at: now,
+        });
+        let elapsed = now.duration_since(bucket.refilled_at).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * rate).min(burst);
+        bucket.refilled_at = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            return Ok(());
+        }
+        Err(Duration::from_secs_f64((1.0 - bucket.tokens) / rate))
+    }
+
+    pub fn status(&self) -> CircuitStatus {
+        self.circuit.lock().unwrap().clone()
+    }
+
+    /// Fails while the circuit is not closed.
+    pub fn admit(&self) -> Result<(), String> {
+        let circuit = self.circuit.lock().unwrap();
+        if circuit.state == CircuitState::Closed {
+            return Ok(());
+        }
+        Err(format!(
+            "Backend calls are paused after {} failures in a row: {}",
+            circuit.failures,
+            circuit.last_error.as_deref().unwrap_or("no answer")
+        ))
+    }
+
+    pub fn succeeded(&self) {
+        let mut circuit = self.circuit.lock().unwrap();
+        if circuit.state == CircuitState::Closed {
+            circuit.failures = 0;
+            circuit.last_error = None;
+        }
+    }
+
+    /// Counts a failed call, opening the circuit past the threshold.
+    pub fn failed(&self, app: &AppHandle, error: String) {
+        let config = app.state::<ConfigStore>().get().api_limits;
+        let payload = {
+            let mut circuit = self.circuit.lock().unwrap();
+            if circuit.state != CircuitState::Closed {
+                return;
+            }
+            circuit.failures += 1;
+            circuit.last_error = Some(error);
+            if config.failure_threshold == 0 || circuit.failures < config.failure_threshold {
+                return;
+            }
+            circuit.state = CircuitState::Open;
+            CircuitOpenPayload {
+                failures: circuit.failures,
+                last_error: circuit.last_error.clone(),
+                retry_in_ms: config.open_ms,
+            }
+        };
+        log::warn!(
+            "Opened the circuit to the backend after {} failures: {}",
+            payload.failures,
+            payload.last_error.as_deref().unwrap_or_default()
+        );
+        let _ = app.emit("proxy://circuit-open", payload);
+        recover(app);
+    }
+
+    fn set_state(&self, state: CircuitState) {
+        self.circuit.lock().unwrap().state = state;
+    }
+}
+
+/// Probes the backend every `openMs` until it answers, then closes the
+/// circuit.
+fn recover(app: &AppHandle) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let guard = app.state::<ApiGuard>();
+        loop {
+            let open = app.state::<ConfigStore>().get().api_limits.open_ms;
+            tokio::time::sleep(Duration::from_millis(open.max(1))).await;
+            guard.set_state(CircuitState::HalfOpen);
+            if health::probe(&app, &health::health_url(&app), PROBE_TIMEOUT).await {
+                break;
+            }
+            log::debug!("Backend still failing; keeping the circuit open");
+            guard.set_state(CircuitState::Open);
+        }
+        {
+            let mut circuit = guard.circuit.lock().unwrap();
+            circuit.state = CircuitState::Closed;
+            circuit.failures = 0;
+            circuit.last_error = None;
+        }
+        log::info!("Closed the circuit to the backend");
+        let _ = app.emit("proxy://circuit-closed", ());
+    });
+}
+
+/// Answers `429` instead of forwarding when the frontend is over its rate.
+pub fn throttle(app: &AppHandle) -> Option<ApiResponse> {
+    let config = app.state::<ConfigStore>().get().api_limits;
+    let wait = app.state::<ApiGuard>().take_token(&config).err()?;
+    log::debug!("Throttling a frontend request for {:?}", wait);
+    let retry_after = wait.as_secs_f64().ceil().max(1.0) as u64;
+    Some(ApiResponse {
+        status: 429,
+        ok: false,
+        headers: HashMap::from([("retry-after".to_string(), retry_after.to_string())]),
+        body: json!({ "detail": "Too many requests; slow down" }),
+    })
+}
+
+#[tauri::command]
+pub fn get_api_circuit(guard: tauri::State<'_, ApiGuard>) -> CircuitStatus {
+    guard.status()
+}
@@ -0,0 +1,155 @@
+//! Captures the server's stdout/stderr and forwards it to the frontend.
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+use tauri::async_runtime::JoinHandle;
+use tauri::{AppHandle, Emitter, Manager};
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
+use tokio::process::Child;
+
+use crate::log_files::RotatingFile;
+
+/// Number of recent lines kept for `get_server_logs`.
+const BUFFER_LINES: usize = 1000;
+
+#[derive(Clone, Copy, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogStream {
+    Stdout,
+    Stderr,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LogLine {
+    pub stream: LogStream,
+    pub line: String,
+    /// Milliseconds since the Unix epoch.
+    pub timestamp: u64,
+}
+
+impl LogStream {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LogStream::Stdout => "stdout",
+            LogStream::Stderr => "stderr",
+        }
+    }
+}
+
+/// Managed ring buffer of the most recent server output, optionally
+/// mirrored to a rotating file on disk.
+#[derive(Default)]
+pub struct ServerLogs {
+    lines: Mutex<VecDeque<LogLine>>,
+    file: Mutex<Option<RotatingFile>>,
+}
+
+impl ServerLogs {
+    pub fn set_file(&self, file: RotatingFile) {
+        *self.file.lock().unwrap() = Some(file);
+    }
+
+    fn push(&self, line: LogLine) {
+        if let Some(file) = self.file.lock().unwrap().as_mut() {
+            let stamp = chrono::Local::now().format("%Y-%m-%d %H:%M:%S%.3f");
+            let entry = format!("{} [{}] {}", stamp, line.stream.as_str(), line.line);
+            if let Err(e) = file.write_line(&entry) {
+                log::debug!("Failed to write server log file: {}", e);
+            }
+        }
+
+        let mut lines = self.lines.lock().unwrap();
+        if lines.len() == BUFFER_LINES {
+            lines.pop_front();
+        }
+        lines.push_back(line);
+    }
+
+    /// The newest `limit` stderr lines logged at or after `since` (in
+    /// milliseconds since the Unix epoch), oldest first.
+    pub fn stderr_since(&self, since: u64, limit: usize) -> Vec<String> {
+        let lines = self.lines.lock().unwrap();
+        let mut tail: Vec<String> = lines
+            .iter()
+            .rev()
+            .take_while(|line| line.timestamp >= since)
+            .filter(|line| matches!(line.stream, LogStream::Stderr))
+            .take(limit)
+            .map(|line| line.line.clone())
+            .collect();
+        tail.reverse();
+        tail
+    }
+
+    /// Returns up to `limit` of the newest lines, oldest first.
+    pub fn recent(&self, limit: usize) -> Vec<LogLine> {
+        let lines = self.lines.lock().unwrap();
+        let skip = lines.len().saturating_sub(limit);
+        lines.iter().skip(skip).cloned().collect()
+    }
+}
+
+/// Takes the child's piped output and starts forwarding it line by line.
+/// The returned tasks finish once the child closes its pipes.
+pub fn attach(app: &AppHandle, child: &mut Child) -> Vec<JoinHandle<()>> {
+    let mut pumps = Vec::new();
+    if let Some(stdout) = child.stdout.take() {
+        pumps.push(tauri::async_runtime::spawn(pump(
+            app.clone(),
+            stdout,
+            LogStream::Stdout,
+        )));
+    }
+    if let Some(stderr) = child.stderr.take() {
+        pumps.push(tauri::async_runtime::spawn(pump(
+            app.clone(),
+            stderr,
+            LogStream::Stderr,
+        )));
+    }
+    pumps
+}
+
+pub fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+async fn pump(app: AppHandle, reader: impl AsyncRead + Unpin, stream: LogStream) {
+    let logs = app.state::<ServerLogs>();
+    let mut reader = BufReader::new(reader);
+    let mut buf = Vec::new();
+
+    loop {
+        buf.clear();
+        match reader.read_until(b'\n', &mut buf).await {
+            Ok(0) => break,
+            Ok(_) => {}
+            Err(e) => {
+                log::debug!("Stopped reading server output: {}", e);
+                break;
+            }
+        }
+
+        // The server's console encoding isn't guaranteed to be UTF-8
+        let line = String::from_utf8_lossy(&buf).trim_end().to_string();
+        let line = LogLine {
+            stream,
+            line,
+            timestamp: now_millis(),
+        };
+        logs.push(line.clone());
+        let _ = app.emit("server-log", line);
+    }
+}
+
+#[tauri::command]
+pub fn get_server_logs(logs: tauri::State<'_, ServerLogs>, limit: Option<usize>) -> Vec<LogLine> {
+    logs.recent(limit.unwrap_or(BUFFER_LINES))
+}
@@ -0,0 +1,115 @@
+//! Samples the supervised server's CPU, memory and handle usage.
+//!
+//! A background task refreshes the numbers every few seconds and emits
+//! `server://high-memory` when resident memory crosses the configured
+//! threshold, once per crossing.
+
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serde::Serialize;
+use sysinfo::{Pid, ProcessRefreshKind, ProcessesToUpdate, System};
+use tauri::{AppHandle, Emitter, Manager};
+
+use super::logs::now_millis;
+use super::supervisor::ServerSupervisor;
+use crate::config::ConfigStore;
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MetricsSample {
+    pub pid: u32,
+    /// Share of one core, so a busy multi-threaded server can exceed 100.
+    pub cpu_percent: f32,
+    pub rss_bytes: u64,
+    pub virtual_bytes: u64,
+    /// Open file descriptors, or handles on Windows, where available.
+    pub handles: Option<usize>,
+    /// Milliseconds since the Unix epoch.
+    pub timestamp: u64,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct HighMemoryPayload {
+    pid: u32,
+    rss_bytes: u64,
+    threshold_bytes: u64,
+}
+
+/// Managed state holding the latest sample, if the server is running.
+#[derive(Default)]
+pub struct ServerMetrics {
+    latest: Mutex<Option<MetricsSample>>,
+}
+
+impl ServerMetrics {
+    pub fn latest(&self) -> Option<MetricsSample> {
+        self.latest.lock().unwrap().clone()
+    }
+}
+
+fn sample(system: &mut System, pid: u32) -> Option<MetricsSample> {
+    let id = Pid::from_u32(pid);
+    system.refresh_processes_specifics(
+        ProcessesToUpdate::Some(&[id]),
+        true,
+        ProcessRefreshKind::nothing().with_cpu().with_memory(),
+    );
+    let process = system.process(id)?;
+    Some(MetricsSample {
+        pid,
+        cpu_percent: process.cpu_usage(),
+        rss_bytes: process.memory(),
+        virtual_bytes: process.virtual_memory(),
+        handles: process.open_files(),
+        timestamp: now_millis(),
+    })
+}
+
+/// Keeps [`ServerMetrics`] up to date for as long as the app runs.
+pub fn spawn(app: &AppHandle) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let mut system = System::new();
+        let mut over_threshold = false;
+        loop {
+            let config = app.state::<ConfigStore>().get().monitor;
+            tokio::time::sleep(Duration::from_millis(config.interval_ms)).await;
+
+            let current = app
+                .state::<ServerSupervisor>()
+                .pid()
+                .and_then(|pid| sample(&mut system, pid));
+            *app.state::<ServerMetrics>().latest.lock().unwrap() = current.clone();
+            let Some(current) = current else {
+                over_threshold = false;
+                continue;
+            };
+
+            let threshold = config.high_memory_mb.saturating_mul(1024 * 1024);
+            let over = threshold > 0 && current.rss_bytes > threshold;
+            if over && !over_threshold {
+                log::warn!(
+                    "Server {} is using {} MB of memory",
+                    current.pid,
+                    current.rss_bytes / (1024 * 1024)
+                );
+                let _ = app.emit(
+                    "server://high-memory",
+                    HighMemoryPayload {
+                        pid: current.pid,
+                        rss_bytes: current.rss_bytes,
+                        threshold_bytes: threshold,
+                    },
+                );
+            }
+            over_threshold = over;
+        }
+    });
+}
+
+#[tauri::command]
+pub fn get_server_metrics(metrics: tauri::State<'_, ServerMetrics>) -> Option<MetricsSample> {
+    metrics.latest()
+}
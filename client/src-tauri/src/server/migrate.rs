@@ -0,0 +1,191 @@
+//! Runs the backend's storage migrations before the server itself starts,
+//! so an upgrade shows its progress instead of a server that seems to hang.
+//!
+//! The backend is started once with `--migrate` and reports on stdout in
+//! `NYX-MIGRATE <json>` lines. Each step is emitted as `server://migration`
+//! and shown on the splash; a failure is emitted as
+//! `server://migration-failed` and stops the start. A backend that does not
+//! announce migrations in time predates `--migrate` and is serving instead,
+//! so it is stopped and the step skipped.
+
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter};
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader, Lines};
+
+use super::supervisor::LaunchSpec;
+use crate::boot::{self, BootPhase};
+
+pub const MIGRATE_FLAG: &str = "--migrate";
+const PROGRESS_PREFIX: &str = "NYX-MIGRATE ";
+/// How long the backend gets to say how many migrations are pending.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(20);
+/// How long all migrations together may take.
+const MIGRATE_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+/// How long the process gets to exit after reporting it is done.
+const EXIT_TIMEOUT: Duration = Duration::from_secs(10);
+/// Stderr lines kept for the error of a failed run.
+const STDERR_LINES: usize = 20;
+/// How long to wait for the output of a failed run.
+const OUTPUT_DRAIN_TIMEOUT: Duration = Duration::from_secs(1);
+const SUGGESTION: &str =
+    "Restore a backup from before the update to get back to the last working state.";
+
+#[derive(Debug, Deserialize)]
+struct Progress {
+    total: Option<u32>,
+    step: Option<u32>,
+    name: Option<String>,
+    #[serde(default)]
+    done: bool,
+    error: Option<String>,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct MigrationPayload {
+    step: u32,
+    total: u32,
+    name: String,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct FailedPayload {
+    error: String,
+    suggestion: &'static str,
+}
+
+/// The next progress line, or `None` once stdout closes.
+async fn next_progress(lines: &mut Lines<BufReader<impl AsyncRead + Unpin>>) -> Option<Progress> {
+    while let Ok(Some(line)) = lines.next_line().await {
+        let Some(json) = line.trim().strip_prefix(PROGRESS_PREFIX) else {
+            continue;
+        };
+        match serde_json::from_str(json) {
+            Ok(progress) => return Some(progress),
+            Err(e) => log::debug!("Ignoring unreadable migration progress {:?}: {}", json, e),
+        }
+    }
+    None
+}
+
+async fn follow(
+    app: &AppHandle,
+    lines: &mut Lines<BufReader<impl AsyncRead + Unpin>>,
+    total: u32,
+) -> Result<(), String> {
+    while let Some(progress) = next_progress(lines).await {
+        if let Some(error) = progress.error {
+            return Err(error);
+        }
+        if progress.done {
+            return Ok(());
+        }
+        if let Some(step) = progress.step {
+            let name = progress.name.unwrap_or_default();
+            log::info!("Running migration {}/{}: {}", step, total, name);
+            boot::report(
+                app,
+                BootPhase::MigratingDatabase,
+                Some(format!("Migrating {} of {}: {}", step, total, name)),
+            );
+            let _ = app.emit("server://migration", MigrationPayload { step, total, name });
+        }
+    }
+    Err("The migration ended without finishing".to_string())
+}
+
+async fn migrate(app: &AppHandle, spec: &LaunchSpec) -> Result<(), String> {
+    let (mut child, tree) = spec
+        .clone()
+        .arg(MIGRATE_FLAG)
+        .spawn()
+        .map_err(|e| format!("Failed to start the migration: {}", e))?;
+    let stdout = child.stdout.take().ok_or("Migration output is not piped")?;
+    let stderr = child.stderr.take();
+    let stderr_tail = tauri::async_runtime::spawn(async move {
+        let mut tail = VecDeque::new();
+        if let Some(stderr) = stderr {
+            let mut lines = BufReader::new(stderr).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                if tail.len() == STDERR_LINES {
+                    tail.pop_front();
+                }
+                tail.push_back(line);
+            }
+        }
+        tail
+    });
+    let mut lines = BufReader::new(stdout).lines();
+
+    let total = match tokio::time::timeout(HANDSHAKE_TIMEOUT, next_progress(&mut lines)).await {
+        Ok(Some(Progress {
+            total: Some(total), ..
+        })) => total,
+        Ok(Some(Progress {
+            error: Some(error), ..
+        })) => {
+            tree.kill();
+            let _ = child.kill().await;
+            return Err(error);
+        }
+        _ => {
+            log::info!(
+                "The server does not support {}; skipping migrations",
+                MIGRATE_FLAG
+            );
+            tree.kill();
+            let _ = child.kill().await;
+            return Ok(());
+        }
+    };
+    if total > 0 {
+        log::info!("{} storage migration(s) pending", total);
+    }
+
+    let outcome = match tokio::time::timeout(MIGRATE_TIMEOUT, follow(app, &mut lines, total)).await
+    {
+        Ok(Ok(())) => match tokio::time::timeout(EXIT_TIMEOUT, child.wait()).await {
+            Ok(Ok(status)) if !status.success() => {
+                Err(format!("The migration exited with status {}", status))
+            }
+            _ => Ok(()),
+        },
+        Ok(Err(error)) => Err(error),
+        Err(_) => Err("The migration did not finish in time".to_string()),
+    };
+    tree.kill();
+    let _ = child.kill().await;
+    let Err(error) = outcome else {
+        return Ok(());
+    };
+    // The pipe closes with the process, which ends the stderr reader
+    let stderr = tokio::time::timeout(OUTPUT_DRAIN_TIMEOUT, stderr_tail)
+        .await
+        .ok()
+        .and_then(Result::ok)
+        .unwrap_or_default();
+    match stderr.back() {
+        Some(last) if !last.trim().is_empty() => Err(format!("{}: {}", error, last.trim())),
+        _ => Err(error),
+    }
+}
+
+/// Runs pending migrations with the server's own launch settings.
+pub async fn run(app: &AppHandle, spec: &LaunchSpec) -> Result<(), String> {
+    migrate(app, spec).await.map_err(|e| {
+        let error = format!("Database migration failed: {}", e);
+        log::error!("{}", error);
+        let _ = app.emit(
+            "server://migration-failed",
+            FailedPayload {
+                error: error.clone(),
+                suggestion: SUGGESTION,
+            },
+        );
+        format!("{}. {}", error, SUGGESTION)
+    })
+}
@@ -0,0 +1,30 @@
+//! Everything related to the backend process the desktop shell runs.
+
+pub mod auth;
+pub mod binary;
+pub mod bridge;
+pub mod client;
+pub mod compat;
+pub mod connection;
+pub mod environments;
+pub mod health;
+pub mod idle;
+pub mod integrity;
+pub mod launch;
+pub mod limits;
+pub mod logs;
+pub mod metrics;
+pub mod migrate;
+pub mod orphans;
+pub mod port;
+pub mod port_owner;
+pub mod priority;
+pub mod process;
+pub mod python;
+pub mod queue;
+pub mod status;
+pub mod supervisor;
+pub mod tls;
+pub mod trace;
+pub mod update;
+pub mod watch;
@@ -0,0 +1,100 @@
+//! Tracks the server PID on disk so a crashed session's backend can be reaped.
+
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use sysinfo::{Pid, ProcessesToUpdate, System};
+use tauri::{AppHandle, Manager};
+
+use super::process::kill_tree;
+use crate::audit::AuditAction;
+
+const PID_FILE: &str = "server.pid";
+const EXIT_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Serialize, Deserialize)]
+struct PidRecord {
+    pid: u32,
+    program: PathBuf,
+}
+
+fn pid_file_path(app: &AppHandle) -> Option<PathBuf> {
+    app.path().app_data_dir().ok().map(|dir| dir.join(PID_FILE))
+}
+
+/// Records the live server process so the next launch can find it.
+pub fn write_pid_file(app: &AppHandle, pid: u32, program: &Path) {
+    let Some(path) = pid_file_path(app) else {
+        return;
+    };
+    let record = PidRecord {
+        pid,
+        program: program.to_path_buf(),
+    };
+    let result = path
+        .parent()
+        .map_or(Ok(()), std::fs::create_dir_all)
+        .and_then(|_| std::fs::write(&path, serde_json::to_vec(&record).unwrap_or_default()));
+    if let Err(e) = result {
+        log::warn!("Failed to write server PID file: {}", e);
+    }
+}
+
+pub fn remove_pid_file(app: &AppHandle) {
+    if let Some(path) = pid_file_path(app) {
+        let _ = std::fs::remove_file(path);
+    }
+}
+
+/// Terminates a server left running by a previous session, if there is one.
+///
+/// Only the process recorded in the PID file is touched, and only when its
+/// executable still matches what we launched, so a recycled PID belonging to
+/// something else is left alone. Returns the PID that was killed.
+pub async fn kill_orphaned_server(app: &AppHandle) -> Option<u32> {
+    let path = pid_file_path(app)?;
+    let record: PidRecord = serde_json::from_slice(&std::fs::read(&path).ok()?).ok()?;
+    let _ = std::fs::remove_file(&path);
+
+    let pid = Pid::from_u32(record.pid);
+    let mut system = System::new();
+    system.refresh_processes(ProcessesToUpdate::Some(&[pid]), true);
+    let process = system.process(pid)?;
+
+    let expected = record.program.file_stem()?;
+    let matches = process.exe().and_then(Path::file_stem).map_or_else(
+        || Path::new(process.name()).file_stem() == Some(expected),
+        |stem| stem == expected,
+    );
+    if !matches {
+        log::debug!(
+            "PID {} from previous session is no longer the server",
+            record.pid
+        );
+        return None;
+    }
+
+    log::warn!(
+        "Killing orphaned server process {} from a previous session",
+        record.pid
+    );
+    kill_tree(record.pid).await;
+    process.kill();
+    crate::audit::record_ok(
+        app,
+        AuditAction::ProcessKill,
+        format!("orphaned server {}", record.pid),
+    );
+
+    let deadline = tokio::time::Instant::now() + EXIT_TIMEOUT;
+    while tokio::time::Instant::now() < deadline {
+        system.refresh_processes(ProcessesToUpdate::Some(&[pid]), true);
+        if system.process(pid).is_none() {
+            return Some(record.pid);
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    log::error!("Orphaned server process {} did not exit", record.pid);
+    Some(record.pid)
+}
@@ -0,0 +1,122 @@
+//! Chooses and remembers the port the local server listens on.
+
+use std::net::TcpListener;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU16, Ordering};
+
+use tauri::{AppHandle, Manager};
+
+use crate::config::ConfigStore;
+
+pub const DEFAULT_PORT: u16 = 8080;
+/// Environment variable the server reads its listen port from.
+pub const PORT_ENV: &str = "NYX_SERVER_PORT";
+
+const PORT_FILE: &str = "server-port";
+/// How many ports above the preferred one are tried before asking the OS.
+const PROBE_RANGE: u16 = 100;
+
+/// Managed state holding the port the server is (or will be) bound to.
+pub struct PortManager {
+    port: AtomicU16,
+    path: Option<PathBuf>,
+}
+
+impl PortManager {
+    /// Starts from the port configured in the server settings, else the one
+    /// used last time, falling back to [`DEFAULT_PORT`].
+    pub fn load(app: &AppHandle) -> Self {
+        let path = app
+            .path()
+            .app_data_dir()
+            .ok()
+            .map(|dir| dir.join(PORT_FILE));
+        let configured = app.state::<ConfigStore>().get().server.port;
+        let port = configured
+            .or_else(|| {
+                path.as_ref()
+                    .and_then(|path| std::fs::read_to_string(path).ok())
+                    .and_then(|contents| contents.trim().parse().ok())
+            })
+            .unwrap_or(DEFAULT_PORT);
+        Self {
+            port: AtomicU16::new(port),
+            path,
+        }
+    }
+
+    pub fn port(&self) -> u16 {
+        self.port.load(Ordering::SeqCst)
+    }
+
+    pub fn base_url(&self) -> String {
+        format!("http://localhost:{}", self.port())
+    }
+
+    /// Switches to `port` for the next server start.
+    pub fn prefer(&self, port: u16) {
+        if self.port.swap(port, Ordering::SeqCst) != port {
+            self.persist(port);
+        }
+    }
+
+    /// Makes sure the current port can be bound before spawning the server,
+    /// moving to (and persisting) a free one if something else holds it.
+    pub fn reserve(&self) -> u16 {
+        let current = self.port();
+        if is_port_free(current) {
+            return current;
+        }
+
+        let port = find_free_port(current);
+        log::warn!(
+            "Port {} is in use, switching server to port {}",
+            current,
+            port
+        );
+        self.port.store(port, Ordering::SeqCst);
+        self.persist(port);
+        port
+    }
+
+    fn persist(&self, port: u16) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        let result = path
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|_| std::fs::write(path, port.to_string()));
+        if let Err(e) = result {
+            log::warn!("Failed to persist server port: {}", e);
+        }
+    }
+}
+
+pub fn is_port_free(port: u16) -> bool {
+    TcpListener::bind(("0.0.0.0", port)).is_ok()
+}
+
+/// Polls until `port` can be bound again, e.g. after stopping the server.
+pub async fn wait_until_free(port: u16, timeout: std::time::Duration) -> bool {
+    let deadline = tokio::time::Instant::now() + timeout;
+    while !is_port_free(port) {
+        if tokio::time::Instant::now() >= deadline {
+            return false;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    true
+}
+
+fn find_free_port(preferred: u16) -> u16 {
+    (preferred..preferred.saturating_add(PROBE_RANGE))
+        .find(|&port| is_port_free(port))
+        .or_else(|| {
+            TcpListener::bind(("127.0.0.1", 0))
+                .and_then(|listener| listener.local_addr())
+                .map(|addr| addr.port())
+                .ok()
+        })
+        .unwrap_or(preferred)
+}
@@ -0,0 +1,241 @@
+//! Explains why the server port is busy: which process is listening on it,
+//! and whether it is a stale server the shell can safely kill.
+//!
+//! Listeners are found with `lsof` (falling back to `ss` on Linux) or
+//! `netstat` on Windows, then looked up with sysinfo.
+
+use std::path::Path;
+use std::process::Stdio;
+use std::time::Duration;
+
+use serde::Serialize;
+use sysinfo::{Pid, ProcessesToUpdate, System};
+use tauri::Manager;
+use tokio::process::Command;
+
+use super::port::{is_port_free, PortManager};
+use super::process::kill_tree;
+use super::supervisor::ServerSupervisor;
+use crate::audit::AuditAction;
+
+const EXIT_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PortOwner {
+    pub pid: u32,
+    pub name: Option<String>,
+    pub exe: Option<String>,
+    /// A nyx-server process.
+    pub is_server: bool,
+    /// The server this shell is currently supervising.
+    pub is_supervised: bool,
+}
+
+impl PortOwner {
+    /// A server no session is looking after any more, which is safe to kill.
+    fn is_stale(&self) -> bool {
+        self.is_server && !self.is_supervised
+    }
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PortDiagnosis {
+    pub port: u16,
+    pub free: bool,
+    pub owners: Vec<PortOwner>,
+    /// Whether `kill_port_owner` would do anything.
+    pub can_kill: bool,
+    /// What the user can do about it, in plain words.
+    pub hint: String,
+}
+
+async fn output(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .await
+        .ok()?;
+    Some(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// PIDs listening on `port`, as far as the OS tools can tell.
+#[cfg(windows)]
+async fn listening_pids(port: u16) -> Vec<u32> {
+    let Some(text) = output("netstat", &["-ano", "-p", "TCP"]).await else {
+        return Vec::new();
+    };
+    let suffix = format!(":{}", port);
+    let mut pids: Vec<u32> = text
+        .lines()
+        .filter_map(|line| {
+            // Proto  Local Address  Foreign Address  State  PID
+            let columns: Vec<&str> = line.split_whitespace().collect();
+            match columns.as_slice() {
+                [_, local, _, "LISTENING", pid] if local.ends_with(&suffix) => pid.parse().ok(),
+                _ => None,
+            }
+        })
+        .collect();
+    pids.sort_unstable();
+    pids.dedup();
+    pids
+}
+
+#[cfg(not(windows))]
+async fn listening_pids(port: u16) -> Vec<u32> {
+    let filter = format!("-iTCP:{}", port);
+    if let Some(text) = output("lsof", &["-nP", &filter, "-sTCP:LISTEN", "-t"]).await {
+        let mut pids: Vec<u32> = text.lines().filter_map(|l| l.trim().parse().ok()).collect();
+        if !pids.is_empty() {
+            pids.sort_unstable();
+            pids.dedup();
+            return pids;
+        }
+    }
+
+    // Minimal Linux installs ship `ss` but not `lsof`
+    let filter = format!("sport = :{}", port);
+    let Some(text) = output("ss", &["-Hltnp", &filter]).await else {
+        return Vec::new();
+    };
+    let mut pids: Vec<u32> = text
+        .split("pid=")
+        .skip(1)
+        .filter_map(|rest| {
+            let end = rest.find(|c: char| !c.is_ascii_digit())?;
+            rest[..end].parse().ok()
+        })
+        .collect();
+    pids.sort_unstable();
+    pids.dedup();
+    pids
+}
+
+fn describe(system: &System, pid: u32, supervised: Option<u32>) -> PortOwner {
+    let process = system.process(Pid::from_u32(pid));
+    let name = process.map(|p| p.name().to_string_lossy().into_owned());
+    let exe = process.and_then(|p| p.exe()).map(Path::to_path_buf);
+    // Sidecars keep their target-triple suffix in dev builds
+    let stem = exe
+        .as_deref()
+        .and_then(Path::file_stem)
+        .or_else(|| name.as_deref().map(Path::new).and_then(Path::file_stem))
+        .map(|stem| stem.to_string_lossy().into_owned());
+    PortOwner {
+        pid,
+        name,
+        exe: exe.map(|exe| exe.display().to_string()),
+        is_server: stem.is_some_and(|stem| stem.starts_with(crate::SERVER_SIDECAR)),
+        is_supervised: supervised == Some(pid),
+    }
+}
+
+pub async fn diagnose(app: &tauri::AppHandle, port: u16) -> PortDiagnosis {
+    if is_port_free(port) {
+        return PortDiagnosis {
+            port,
+            free: true,
+            owners: Vec::new(),
+            can_kill: false,
+            hint: format!("Port {} is free", port),
+        };
+    }
+
+    let pids = listening_pids(port).await;
+    let mut system = System::new();
+    let lookup: Vec<Pid> = pids.iter().copied().map(Pid::from_u32).collect();
+    system.refresh_processes(ProcessesToUpdate::Some(&lookup), true);
+    let supervised = app.state::<ServerSupervisor>().pid();
+    let owners: Vec<PortOwner> = pids
+        .into_iter()
+        .map(|pid| describe(&system, pid, supervised))
+        .collect();
+
+    let can_kill = owners.iter().any(PortOwner::is_stale);
+    let hint = if can_kill {
+        format!(
+            "Port {} is held by a server left over from an earlier session; it can be stopped",
+            port
+        )
+    } else if owners.iter().any(|owner| owner.is_supervised) {
+        format!("Port {} is in use by the running server", port)
+    } else if let Some(owner) = owners.first() {
+        format!(
+            "Port {} is in use by {} (PID {}); close it or choose another port",
+            port,
+            owner.name.as_deref().unwrap_or("another program"),
+            owner.pid
+        )
+    } else {
+        format!(
+            "Port {} is in use, but the owning process could not be identified; \
+             it may belong to another user or be reserved by the OS",
+            port
+        )
+    };
+
+    PortDiagnosis {
+        port,
+        free: false,
+        owners,
+        can_kill,
+        hint,
+    }
+}
+
+/// Reports who is listening on `port`, defaulting to the server port.
+#[tauri::command]
+pub async fn diagnose_port(
+    app_handle: tauri::AppHandle,
+    port: Option<u16>,
+) -> Result<PortDiagnosis, String> {
+    let port = port.unwrap_or_else(|| app_handle.state::<PortManager>().port());
+    Ok(diagnose(&app_handle, port).await)
+}
+
+/// Kills the processes holding `port`, but only stale nyx-server ones; any
+/// other owner is left for the user to deal with. Returns the killed PIDs.
+#[tauri::command]
+pub async fn kill_port_owner(
+    app_handle: tauri::AppHandle,
+    port: Option<u16>,
+) -> Result<Vec<u32>, String> {
+    let port = port.unwrap_or_else(|| app_handle.state::<PortManager>().port());
+    let diagnosis = diagnose(&app_handle, port).await;
+    let stale: Vec<u32> = diagnosis
+        .owners
+        .iter()
+        .filter(|owner| owner.is_stale())
+        .map(|owner| owner.pid)
+        .collect();
+    if stale.is_empty() {
+        return Err(diagnosis.hint);
+    }
+
+    let mut system = System::new();
+    for &pid in &stale {
+        log::warn!("Killing stale server {} holding port {}", pid, port);
+        kill_tree(pid).await;
+        system.refresh_processes(ProcessesToUpdate::Some(&[Pid::from_u32(pid)]), true);
+        if let Some(process) = system.process(Pid::from_u32(pid)) {
+            process.kill();
+        }
+        crate::audit::record_ok(
+            &app_handle,
+            AuditAction::ProcessKill,
+            format!("stale server {} on port {}", pid, port),
+        );
+    }
+
+    if !super::port::wait_until_free(port, EXIT_TIMEOUT).await {
+        return Err(format!(
+            "Port {} is still in use after stopping PID {:?}",
+            port, stale
+        ));
+    }
+    Ok(stale)
+}
@@ -0,0 +1,233 @@
+//! Keeps the server from starving the UI on low-end machines by lowering
+//! its scheduling priority and, optionally, pinning it to some of the cores.
+//!
+//! Both are applied right after the server is spawned, so the workers it
+//! starts inherit them, and can be changed while it runs. On Unix the change
+//! covers the server's whole process group; on Windows it covers the server
+//! process, and workers it starts afterwards. Pinning is supported on Linux
+//! and Windows only.
+
+use std::io;
+
+use serde::Serialize;
+use tauri::{AppHandle, Manager};
+
+use super::supervisor::ServerSupervisor;
+use crate::config::{ConfigStore, ServerPriority};
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServerPrioritySettings {
+    pub priority: ServerPriority,
+    pub cpu_affinity: Option<Vec<usize>>,
+    /// Cores available to pin to.
+    pub cores: usize,
+}
+
+fn core_count() -> usize {
+    std::thread::available_parallelism()
+        .map(|count| count.get())
+        .unwrap_or(1)
+}
+
+pub fn validate_cores(cores: &[usize]) -> Result<(), String> {
+    let count = core_count();
+    if cores.is_empty() {
+        return Err("Pin the server to at least one core".to_string());
+    }
+    if let Some(core) = cores.iter().find(|core| **core >= count) {
+        return Err(format!(
+            "Core {} does not exist; this machine has cores 0 to {}",
+            core,
+            count - 1
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn set_priority(pid: u32, priority: ServerPriority) -> io::Result<()> {
+    let nice = match priority {
+        ServerPriority::Normal => 0,
+        ServerPriority::BelowNormal => 10,
+        ServerPriority::Idle => 19,
+    };
+    // Servers we spawn lead their own process group, named after their pid
+    // SAFETY: setpriority has no memory-safety preconditions
+    if unsafe { libc::setpriority(libc::PRIO_PGRP, pid as libc::id_t, nice) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Every thread of every process in the process group `pgid`.
+#[cfg(target_os = "linux")]
+fn group_threads(pgid: u32) -> Vec<libc::pid_t> {
+    let Ok(processes) = std::fs::read_dir("/proc") else {
+        return vec![pgid as libc::pid_t];
+    };
+    processes
+        .filter_map(Result::ok)
+        .filter_map(|entry| entry.file_name().to_str()?.parse::<libc::pid_t>().ok())
+        // SAFETY: getpgid has no memory-safety preconditions
+        .filter(|pid| unsafe { libc::getpgid(*pid) } == pgid as libc::pid_t)
+        .flat_map(|pid| {
+            std::fs::read_dir(format!("/proc/{}/task", pid))
+                .map(|tasks| {
+                    tasks
+                        .filter_map(Result::ok)
+                        .filter_map(|entry| entry.file_name().to_str()?.parse().ok())
+                        .collect()
+                })
+                .unwrap_or_else(|_| vec![pid])
+        })
+        .collect()
+}
+
+#[cfg(target_os = "linux")]
+fn set_affinity(pid: u32, cores: Option<&[usize]>) -> io::Result<()> {
+    let all: Vec<usize> = (0..core_count()).collect();
+    // SAFETY: an all-zero cpu_set_t is the empty set
+    let mut set: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+    for core in cores.unwrap_or(&all) {
+        // SAFETY: CPU_SET ignores cores past the end of the set
+        unsafe { libc::CPU_SET(*core, &mut set) };
+    }
+    // Affinity belongs to threads, and existing ones do not pick up a change
+    for thread in group_threads(pid) {
+        // SAFETY: `set` is a valid cpu_set_t of the size passed
+        let result = unsafe {
+            libc::sched_setaffinity(thread, std::mem::size_of::<libc::cpu_set_t>(), &set)
+        };
+        // Threads that exited in the meantime are fine to miss
+        if result != 0 && io::Error::last_os_error().raw_os_error() != Some(libc::ESRCH) {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+fn set_affinity(_pid: u32, cores: Option<&[usize]>) -> io::Result<()> {
+    match cores {
+        Some(_) => Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "pinning to cores is not supported on this platform",
+        )),
+        None => Ok(()),
+    }
+}
+
+#[cfg(unix)]
+fn set(pid: u32, priority: ServerPriority, cores: Option<&[usize]>) -> io::Result<()> {
+    set_priority(pid, priority)?;
+    set_affinity(pid, cores)
+}
+
+#[cfg(windows)]
+fn set(pid: u32, priority: ServerPriority, cores: Option<&[usize]>) -> io::Result<()> {
+    use windows_sys::Win32::Foundation::CloseHandle;
+    use windows_sys::Win32::System::Threading::{
+        GetProcessAffinityMask, OpenProcess, SetPriorityClass, SetProcessAffinityMask,
+        BELOW_NORMAL_PRIORITY_CLASS, IDLE_PRIORITY_CLASS, NORMAL_PRIORITY_CLASS,
+        PROCESS_QUERY_LIMITED_INFORMATION, PROCESS_SET_INFORMATION,
+    };
+
+    let class = match priority {
+        ServerPriority::Normal => NORMAL_PRIORITY_CLASS,
+        ServerPriority::BelowNormal => BELOW_NORMAL_PRIORITY_CLASS,
+        ServerPriority::Idle => IDLE_PRIORITY_CLASS,
+    };
+    // SAFETY: the handle is checked before use and closed before returning
+    unsafe {
+        let process = OpenProcess(
+            PROCESS_SET_INFORMATION | PROCESS_QUERY_LIMITED_INFORMATION,
+            0,
+            pid,
+        );
+        if process.is_null() {
+            return Err(io::Error::last_os_error());
+        }
+        let result = (|| {
+            if SetPriorityClass(process, class) == 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let mask = match cores {
+                Some(cores) => cores
+                    .iter()
+                    .filter(|core| **core < usize::BITS as usize)
+                    .fold(0usize, |mask, core| mask | 1 << core),
+                None => {
+                    let (mut process_mask, mut system_mask) = (0, 0);
+                    if GetProcessAffinityMask(process, &mut process_mask, &mut system_mask) == 0 {
+                        return Err(io::Error::last_os_error());
+                    }
+                    system_mask
+                }
+            };
+            if SetProcessAffinityMask(process, mask) == 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(())
+        })();
+        CloseHandle(process);
+        result
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+fn set(_pid: u32, _priority: ServerPriority, _cores: Option<&[usize]>) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "not supported on this platform",
+    ))
+}
+
+/// Gives the server `pid` and the processes it started `priority`, pinned
+/// to `cores` or to all of them.
+pub fn apply(pid: u32, priority: ServerPriority, cores: Option<&[usize]>) -> Result<(), String> {
+    set(pid, priority, cores).map_err(|e| format!("Failed to set server priority: {}", e))
+}
+
+fn current(app: &AppHandle) -> ServerPrioritySettings {
+    let server = app.state::<ConfigStore>().get().server;
+    ServerPrioritySettings {
+        priority: server.priority,
+        cpu_affinity: server.cpu_affinity,
+        cores: core_count(),
+    }
+}
+
+#[tauri::command]
+pub fn get_server_priority(app_handle: AppHandle) -> ServerPrioritySettings {
+    current(&app_handle)
+}
+
+/// Saves the server's priority and cores, and applies them to the running
+/// server if there is one.
+#[tauri::command]
+pub fn set_server_priority(
+    app_handle: AppHandle,
+    supervisor: tauri::State<'_, ServerSupervisor>,
+    priority: ServerPriority,
+    cpu_affinity: Option<Vec<usize>>,
+) -> Result<ServerPrioritySettings, String> {
+    if let Some(cores) = &cpu_affinity {
+        validate_cores(cores)?;
+    }
+    app_handle.state::<ConfigStore>().update(|c| {
+        c.server.priority = priority;
+        c.server.cpu_affinity = cpu_affinity.clone();
+    })?;
+    if let Some(pid) = supervisor.pid() {
+        // Unix only lets unprivileged users lower a priority, not raise it
+        apply(pid, priority, cpu_affinity.as_deref()).map_err(|e| {
+            format!(
+                "{}; the saved setting applies from the next server start",
+                e
+            )
+        })?;
+        log::info!("Set server priority to {:?}", priority);
+    }
+    Ok(current(&app_handle))
+}
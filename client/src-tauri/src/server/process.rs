@@ -0,0 +1,176 @@
+//! Platform helpers for tearing down the server and everything it spawned.
+//!
+//! Killing the server's pid alone leaves its Python workers and browsers
+//! behind, so the child is started as the root of its own unit: a process
+//! group on Unix and a Job Object on Windows.
+
+use tokio::process::{Child, Command};
+
+/// Prepares `command` so the process it spawns can be contained.
+pub fn isolate(command: &mut Command) {
+    #[cfg(unix)]
+    command.process_group(0);
+    #[cfg(not(unix))]
+    let _ = command;
+}
+
+/// The spawned server together with every process it starts.
+pub struct ProcessTree {
+    #[cfg(unix)]
+    pgid: Option<i32>,
+    #[cfg(windows)]
+    job: Option<windows::Job>,
+}
+
+impl ProcessTree {
+    /// Takes ownership of the tree rooted at `child`, which must have been
+    /// spawned from a command passed through [`isolate`].
+    pub fn contain(child: &Child) -> Self {
+        #[cfg(unix)]
+        {
+            // With `process_group(0)` the child leads a group named after it
+            Self {
+                pgid: child.id().map(|pid| pid as i32),
+            }
+        }
+        #[cfg(windows)]
+        {
+            let job = windows::Job::new().and_then(|job| {
+                job.assign(child)?;
+                Ok(job)
+            });
+            Self {
+                job: job
+                    .map_err(|e| log::warn!("Failed to put server in a job object: {}", e))
+                    .ok(),
+            }
+        }
+        #[cfg(not(any(unix, windows)))]
+        {
+            let _ = child;
+            Self {}
+        }
+    }
+
+    /// Asks every process in the tree to exit. A no-op on Windows, which has
+    /// no signal to send; the server is asked over HTTP instead.
+    pub fn interrupt(&self) {
+        #[cfg(unix)]
+        if let Some(pgid) = self.pgid {
+            // SAFETY: killpg has no memory-safety preconditions
+            unsafe { libc::killpg(pgid, libc::SIGTERM) };
+        }
+    }
+
+    /// Kills every process still in the tree.
+    pub fn kill(&self) {
+        #[cfg(unix)]
+        if let Some(pgid) = self.pgid {
+            // SAFETY: killpg has no memory-safety preconditions
+            unsafe { libc::killpg(pgid, libc::SIGKILL) };
+        }
+        #[cfg(windows)]
+        if let Some(job) = &self.job {
+            job.terminate();
+        }
+    }
+}
+
+/// Kills `pid` along with its descendants where the platform supports it.
+/// Used for servers left behind by an earlier session, which no longer have
+/// a [`ProcessTree`].
+#[cfg(windows)]
+pub async fn kill_tree(pid: u32) {
+    use std::process::Stdio;
+
+    // `/T` takes the worker processes the server spawned down with it.
+    let _ = Command::new("taskkill")
+        .args(["/PID", &pid.to_string(), "/T", "/F"])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .await;
+}
+
+#[cfg(unix)]
+pub async fn kill_tree(pid: u32) {
+    // Servers we spawned lead their own process group
+    // SAFETY: killpg has no memory-safety preconditions
+    unsafe { libc::killpg(pid as i32, libc::SIGKILL) };
+}
+
+#[cfg(not(any(unix, windows)))]
+pub async fn kill_tree(_pid: u32) {}
+
+#[cfg(windows)]
+mod windows {
+    use std::io;
+    use std::mem::{size_of, zeroed};
+
+    use tokio::process::Child;
+    use windows_sys::Win32::Foundation::{CloseHandle, HANDLE};
+    use windows_sys::Win32::System::JobObjects::{
+        AssignProcessToJobObject, CreateJobObjectW, JobObjectExtendedLimitInformation,
+        SetInformationJobObject, TerminateJobObject, JOBOBJECT_EXTENDED_LIMIT_INFORMATION,
+        JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE,
+    };
+
+    /// A Job Object that kills its processes when the last handle closes, so
+    /// the backend goes down with the app even if the app crashes.
+    pub struct Job(HANDLE);
+
+    // SAFETY: job handles may be used and closed from any thread
+    unsafe impl Send for Job {}
+    unsafe impl Sync for Job {}
+
+    impl Job {
+        pub fn new() -> io::Result<Self> {
+            // SAFETY: null attributes and name create an anonymous job; the
+            // handle is owned by the returned value and closed on drop
+            unsafe {
+                let handle = CreateJobObjectW(std::ptr::null(), std::ptr::null());
+                if handle.is_null() {
+                    return Err(io::Error::last_os_error());
+                }
+                let job = Self(handle);
+
+                let mut info: JOBOBJECT_EXTENDED_LIMIT_INFORMATION = zeroed();
+                info.BasicLimitInformation.LimitFlags = JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE;
+                if SetInformationJobObject(
+                    job.0,
+                    JobObjectExtendedLimitInformation,
+                    &info as *const _ as *const _,
+                    size_of::<JOBOBJECT_EXTENDED_LIMIT_INFORMATION>() as u32,
+                ) == 0
+                {
+                    return Err(io::Error::last_os_error());
+                }
+                Ok(job)
+            }
+        }
+
+        /// Adds `child` to the job; processes it starts from then on join too.
+        pub fn assign(&self, child: &Child) -> io::Result<()> {
+            let process = child
+                .raw_handle()
+                .ok_or_else(|| io::Error::other("process already exited"))?;
+            // SAFETY: both handles are valid for the duration of the call
+            if unsafe { AssignProcessToJobObject(self.0, process as HANDLE) } == 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(())
+        }
+
+        pub fn terminate(&self) {
+            // SAFETY: the handle is valid until drop
+            unsafe { TerminateJobObject(self.0, 1) };
+        }
+    }
+
+    impl Drop for Job {
+        fn drop(&mut self) {
+            // SAFETY: the handle is owned and not used after this
+            unsafe { CloseHandle(self.0) };
+        }
+    }
+}
@@ -0,0 +1,236 @@
+//! Finds a usable Python for running the server from a source checkout, and
+//! sets up its virtualenv and dependencies on request.
+//!
+//! A `.venv` in the checkout wins over Python on PATH. The setup commands'
+//! output is streamed as `python://output` events.
+
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+
+use serde::Serialize;
+use tauri::{AppHandle, Emitter};
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
+use tokio::process::Command;
+
+use super::binary;
+use super::logs::LogStream;
+
+/// Oldest Python the server supports.
+const MIN_VERSION: (u32, u32) = (3, 11);
+const VENV_DIR: &str = ".venv";
+const REQUIREMENTS: &str = "requirements.txt";
+
+/// Interpreters tried on PATH, best first. The `py` launcher picks the
+/// newest install on Windows, where `python` may be the Store stub.
+#[cfg(windows)]
+const SYSTEM_PYTHONS: &[(&str, &[&str])] = &[("py", &["-3"]), ("python", &[]), ("python3", &[])];
+#[cfg(not(windows))]
+const SYSTEM_PYTHONS: &[(&str, &[&str])] = &[("python3", &[]), ("python", &[])];
+
+#[derive(Clone, Debug)]
+pub struct Interpreter {
+    pub program: PathBuf,
+    /// Arguments that go before the script, e.g. `-3` for the `py` launcher.
+    pub args: Vec<String>,
+    pub version: (u32, u32, u32),
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PythonInfo {
+    pub program: String,
+    pub version: String,
+    pub in_venv: bool,
+    /// The checkout the server would be run from.
+    pub server_root: String,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct OutputPayload {
+    stream: LogStream,
+    line: String,
+}
+
+impl Interpreter {
+    fn probe(program: impl Into<PathBuf>, args: &[&str]) -> Option<Self> {
+        let program = program.into();
+        let output = std::process::Command::new(&program)
+            .args(args)
+            .arg("--version")
+            .stdin(Stdio::null())
+            .output()
+            .ok()?;
+        // Python 2 printed its version to stderr
+        let text = [output.stdout, output.stderr].concat();
+        let version = parse_version(&String::from_utf8_lossy(&text))?;
+        Some(Self {
+            program,
+            args: args.iter().map(|arg| arg.to_string()).collect(),
+            version,
+        })
+    }
+
+    fn is_supported(&self) -> bool {
+        (self.version.0, self.version.1) >= MIN_VERSION
+    }
+
+    fn version_string(&self) -> String {
+        let (major, minor, patch) = self.version;
+        format!("{}.{}.{}", major, minor, patch)
+    }
+
+    fn command(&self) -> Command {
+        let mut command = Command::new(&self.program);
+        command.args(&self.args);
+        command
+    }
+}
+
+/// Parses `Python 3.11.4` into its numeric parts.
+fn parse_version(text: &str) -> Option<(u32, u32, u32)> {
+    let version = text.trim().strip_prefix("Python ")?;
+    let mut parts = version
+        .split(|c: char| !c.is_ascii_digit())
+        .map(|part| part.parse().ok());
+    let major = parts.next()??;
+    let minor = parts.next()??;
+    let patch = parts.next().flatten().unwrap_or(0);
+    Some((major, minor, patch))
+}
+
+fn venv_python(root: &Path) -> PathBuf {
+    if cfg!(windows) {
+        root.join(VENV_DIR).join("Scripts").join("python.exe")
+    } else {
+        root.join(VENV_DIR).join("bin").join("python")
+    }
+}
+
+fn venv_interpreter(root: &Path) -> Option<Interpreter> {
+    let path = venv_python(root);
+    if !path.is_file() {
+        return None;
+    }
+    Interpreter::probe(path, &[]).filter(Interpreter::is_supported)
+}
+
+fn system_interpreter() -> Option<Interpreter> {
+    SYSTEM_PYTHONS.iter().find_map(|(program, args)| {
+        let interpreter = Interpreter::probe(program, args)?;
+        if !interpreter.is_supported() {
+            log::info!(
+                "Skipping {} {}: the server needs Python {}.{} or newer",
+                program,
+                interpreter.version_string(),
+                MIN_VERSION.0,
+                MIN_VERSION.1
+            );
+            return None;
+        }
+        Some(interpreter)
+    })
+}
+
+/// The Python to run the checkout at `root` with: its virtualenv if it has a
+/// working one, else a new enough Python on PATH.
+pub fn locate(root: &Path) -> Option<Interpreter> {
+    venv_interpreter(root).or_else(system_interpreter)
+}
+
+fn require(root: &Path) -> Result<Interpreter, String> {
+    locate(root).ok_or_else(|| {
+        format!(
+            "Python {}.{} or newer was not found on PATH",
+            MIN_VERSION.0, MIN_VERSION.1
+        )
+    })
+}
+
+fn info(root: &Path, interpreter: &Interpreter) -> PythonInfo {
+    PythonInfo {
+        program: interpreter.program.display().to_string(),
+        version: interpreter.version_string(),
+        in_venv: interpreter.program.starts_with(root.join(VENV_DIR)),
+        server_root: root.display().to_string(),
+    }
+}
+
+/// Runs `command` in `root`, streaming its output, and fails on a non-zero
+/// exit.
+async fn run_streamed(app: &AppHandle, mut command: Command, root: &Path) -> Result<(), String> {
+    let mut child = command
+        .current_dir(root)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| format!("Failed to run {:?}: {}", command.as_std().get_program(), e))?;
+
+    let (stdout, stderr) = (child.stdout.take(), child.stderr.take());
+    let (status, _, _) = tokio::join!(
+        child.wait(),
+        async {
+            if let Some(stdout) = stdout {
+                pump(app, stdout, LogStream::Stdout).await;
+            }
+        },
+        async {
+            if let Some(stderr) = stderr {
+                pump(app, stderr, LogStream::Stderr).await;
+            }
+        }
+    );
+
+    let status = status.map_err(|e| format!("Failed to wait for Python: {}", e))?;
+    if !status.success() {
+        return Err(format!("Python exited with {}", status));
+    }
+    Ok(())
+}
+
+async fn pump(app: &AppHandle, reader: impl AsyncRead + Unpin, stream: LogStream) {
+    let mut lines = BufReader::new(reader).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        let _ = app.emit("python://output", OutputPayload { stream, line });
+    }
+}
+
+#[tauri::command]
+pub fn detect_python() -> Result<PythonInfo, String> {
+    let root = binary::dev_root().ok_or("No server source checkout found")?;
+    let interpreter = require(&root)?;
+    Ok(info(&root, &interpreter))
+}
+
+/// Prepares the source checkout to run: creates its `.venv` if asked to and
+/// missing, then installs `requirements.txt` into whichever Python it uses.
+#[tauri::command]
+pub async fn bootstrap_python_env(
+    app_handle: AppHandle,
+    create_venv: Option<bool>,
+) -> Result<PythonInfo, String> {
+    let root = binary::dev_root().ok_or("No server source checkout found")?;
+    let mut interpreter = require(&root)?;
+
+    if create_venv.unwrap_or(true) && venv_interpreter(&root).is_none() {
+        log::info!("Creating virtualenv in {}", root.join(VENV_DIR).display());
+        let mut command = interpreter.command();
+        command.args(["-m", "venv", VENV_DIR]);
+        run_streamed(&app_handle, command, &root).await?;
+        interpreter = venv_interpreter(&root).ok_or("Created virtualenv has no usable Python")?;
+    }
+
+    if root.join(REQUIREMENTS).is_file() {
+        log::info!(
+            "Installing server requirements with {}",
+            interpreter.program.display()
+        );
+        let mut command = interpreter.command();
+        command.args(["-m", "pip", "install", "-r", REQUIREMENTS]);
+        run_streamed(&app_handle, command, &root).await?;
+    }
+
+    Ok(info(&root, &interpreter))
+}
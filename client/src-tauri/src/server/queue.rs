@@ -0,0 +1,328 @@
+//! Mutations the frontend made while the backend was down, kept until it is
+//! back up.
+//!
+//! `api_request` queues a request instead of failing it when the backend is
+//! not up, or cannot be reached, and the request is safe to send twice: a
+//! `PUT` or `DELETE`, or a `POST` or `PATCH` carrying an `Idempotency-Key`.
+//! The queue lives in the local cache database, so it survives a restart.
+//! Each time the backend becomes healthy the queue is replayed in order,
+//! every replayed operation is announced as `queue://replayed`, and every
+//! change to the queue as `queue://updated`.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use rusqlite::params;
+use serde::Serialize;
+use serde_json::Value;
+use tauri::{AppHandle, Emitter, Manager};
+
+use super::client::{self, ApiResponse, RequestOptions};
+use super::status::{ServerState, ServerStatus};
+use crate::cache::AppCache;
+
+/// Header that makes a `POST` or `PATCH` safe to queue.
+const IDEMPOTENCY_HEADER: &str = "idempotency-key";
+/// Header on the response to a queued request.
+pub const QUEUED_HEADER: &str = "x-nyx-queued";
+/// How long a replayed request may take.
+const REPLAY_TIMEOUT: Duration = Duration::from_secs(30);
+/// How often the queue is replayed while the backend stays up.
+const RETRY_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PendingOperation {
+    pub id: i64,
+    pub method: String,
+    pub path: String,
+    /// When it was queued, as RFC 3339.
+    pub queued_at: String,
+    /// Replays that did not get through.
+    pub attempts: u32,
+    pub last_error: Option<String>,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct UpdatedPayload {
+    pending: usize,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ReplayedPayload {
+    operation_id: i64,
+    method: String,
+    path: String,
+    status: u16,
+    ok: bool,
+    body: Value,
+}
+
+struct Stored {
+    operation: PendingOperation,
+    body: Option<Value>,
+    headers: HashMap<String, String>,
+}
+
+/// Whether a request may be queued and sent later.
+pub fn is_queueable(method: &str, headers: &HashMap<String, String>) -> bool {
+    match method.to_uppercase().as_str() {
+        "PUT" | "DELETE" => true,
+        "POST" | "PATCH" => headers
+            .keys()
+            .any(|name| name.eq_ignore_ascii_case(IDEMPOTENCY_HEADER)),
+        _ => false,
+    }
+}
+
+pub fn server_is_up(app: &AppHandle) -> bool {
+    matches!(
+        *app.state::<ServerStatus>().subscribe().borrow(),
+        ServerState::Healthy | ServerState::Degraded
+    )
+}
+
+async fn notify(app: &AppHandle) {
+    match count(app).await {
+        Ok(pending) => {
+            let _ = app.emit("queue://updated", UpdatedPayload { pending });
+        }
+        Err(e) => log::warn!("Failed to count pending operations: {}", e),
+    }
+}
+
+async fn count(app: &AppHandle) -> Result<usize, String> {
+    app.state::<AppCache>()
+        .with_db(app, |db| {
+            db.query_row("SELECT COUNT(*) FROM pending_operations", [], |row| {
+                row.get::<_, i64>(0)
+            })
+            .map(|count| count as usize)
+        })
+        .await
+}
+
+/// Stores a request for later and answers it with `202 Accepted`.
+pub async fn enqueue(
+    app: &AppHandle,
+    method: &str,
+    path: &str,
+    body: Option<Value>,
+    headers: HashMap<String, String>,
+) -> Result<ApiResponse, String> {
+    let method = method.to_uppercase();
+    let encoded_headers = serde_json::to_string(&headers)
+        .map_err(|e| format!("Failed to queue {} {}: {}", method, path, e))?;
+    let queued_at = chrono::Utc::now().to_rfc3339();
+    let id = app
+        .state::<AppCache>()
+        .with_db(app, |db| {
+            db.execute(
+                "INSERT INTO pending_operations (method, path, body, headers, queued_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![
+                    method,
+                    path,
+                    body.as_ref().map(Value::to_string),
+                    encoded_headers,
+                    queued_at
+                ],
+            )?;
+            Ok(db.last_insert_rowid())
+        })
+        .await?;
+    log::info!("Queued {} {} until the server is back", method, path);
+    notify(app).await;
+    Ok(ApiResponse {
+        status: 202,
+        ok: true,
+        headers: HashMap::from([(QUEUED_HEADER.to_string(), "true".to_string())]),
+        body: serde_json::json!({
+            "queued": true,
+            "operationId": id,
+            "queuedAt": queued_at,
+        }),
+    })
+}
+
+async fn load(app: &AppHandle) -> Result<Vec<Stored>, String> {
+    app.state::<AppCache>()
+        .with_db(app, |db| {
+            let mut select = db.prepare(
+                "SELECT id, method, path, body, headers, queued_at, attempts, last_error
+                 FROM pending_operations ORDER BY id",
+            )?;
+            let rows = select
+                .query_map([], |row| {
+                    let body: Option<String> = row.get(3)?;
+                    let headers: String = row.get(4)?;
+                    Ok(Stored {
+                        operation: PendingOperation {
+                            id: row.get(0)?,
+                            method: row.get(1)?,
+                            path: row.get(2)?,
+                            queued_at: row.get(5)?,
+                            attempts: row.get(6)?,
+                            last_error: row.get(7)?,
+                        },
+                        body: body.and_then(|body| serde_json::from_str(&body).ok()),
+                        headers: serde_json::from_str(&headers).unwrap_or_default(),
+                    })
+                })?
+                .filter_map(|row| row.ok())
+                .collect();
+            Ok(rows)
+        })
+        .await
+}
+
+async fn remove(app: &AppHandle, id: i64) -> Result<bool, String> {
+    app.state::<AppCache>()
+        .with_db(app, |db| {
+            db.execute("DELETE FROM pending_operations WHERE id = ?1", params![id])
+                .map(|removed| removed > 0)
+        })
+        .await
+}
+
+async fn record_failure(app: &AppHandle, id: i64, error: &str) -> Result<(), String> {
+    app.state::<AppCache>()
+        .with_db(app, |db| {
+            db.execute(
+                "UPDATE pending_operations SET attempts = attempts + 1, last_error = ?2
+                 WHERE id = ?1",
+                params![id, error],
+            )
+            .map(|_| ())
+        })
+        .await
+}
+
+/// Drops every queued operation, e.g. when they were meant for a backend
+/// no longer connected to. Returns how many there were.
+pub async fn clear(app: &AppHandle) -> Result<usize, String> {
+    let removed = app
+        .state::<AppCache>()
+        .with_db(app, |db| db.execute("DELETE FROM pending_operations", []))
+        .await?;
+    if removed > 0 {
+        notify(app).await;
+    }
+    Ok(removed)
+}
+
+/// Sends the queued operations in order. Stops at the first one the
+/// backend does not take, keeping it and everything after for the next
+/// replay, and returns whether the queue is empty.
+pub async fn replay(app: &AppHandle) -> Result<bool, String> {
+    let operations = load(app).await?;
+    if operations.is_empty() {
+        return Ok(true);
+    }
+    log::info!("Replaying {} queued operation(s)", operations.len());
+    for stored in operations {
+        let PendingOperation {
+            id, method, path, ..
+        } = stored.operation;
+        let outcome = client::forward(
+            app,
+            &method,
+            &path,
+            stored.body,
+            stored.headers,
+            RequestOptions::timeout(REPLAY_TIMEOUT),
+        )
+        .await;
+        let response = match outcome {
+            // Not the request's fault, so it is tried again later
+            Ok(response) if matches!(response.status, 502..=504) => {
+                Err(format!("HTTP {}", response.status))
+            }
+            outcome => outcome,
+        };
+        match response {
+            Ok(response) => {
+                if !response.ok {
+                    log::warn!(
+                        "Queued {} {} was rejected with HTTP {}",
+                        method,
+                        path,
+                        response.status
+                    );
+                }
+                remove(app, id).await?;
+                let _ = app.emit(
+                    "queue://replayed",
+                    ReplayedPayload {
+                        operation_id: id,
+                        method,
+                        path,
+                        status: response.status,
+                        ok: response.ok,
+                        body: response.body,
+                    },
+                );
+            }
+            Err(e) => {
+                log::warn!("Failed to replay {} {}: {}", method, path, e);
+                record_failure(app, id, &e).await?;
+                notify(app).await;
+                return Ok(false);
+            }
+        }
+    }
+    notify(app).await;
+    Ok(true)
+}
+
+/// Replays the queue each time the backend comes up, for as long as the
+/// app runs.
+pub fn spawn(app: &AppHandle) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let mut state = app.state::<ServerStatus>().subscribe();
+        loop {
+            while !matches!(
+                *state.borrow_and_update(),
+                ServerState::Healthy | ServerState::Degraded
+            ) {
+                if state.changed().await.is_err() {
+                    return;
+                }
+            }
+
+            if let Err(e) = replay(&app).await {
+                log::warn!("Failed to replay queued operations: {}", e);
+            }
+            // Again after a while, for what was queued or kept meanwhile, or
+            // as soon as the server comes back up
+            let _ = tokio::time::timeout(RETRY_INTERVAL, state.changed()).await;
+        }
+    });
+}
+
+/// Operations waiting for the backend, oldest first.
+#[tauri::command]
+pub async fn get_pending_operations(
+    app_handle: AppHandle,
+) -> Result<Vec<PendingOperation>, String> {
+    Ok(load(&app_handle)
+        .await?
+        .into_iter()
+        .map(|stored| stored.operation)
+        .collect())
+}
+
+/// Drops a queued operation without sending it. Returns `false` if it was
+/// no longer queued.
+#[tauri::command]
+pub async fn discard_pending_operation(app_handle: AppHandle, id: i64) -> Result<bool, String> {
+    let removed = remove(&app_handle, id).await?;
+    if removed {
+        log::info!("Discarded queued operation {}", id);
+        notify(&app_handle).await;
+    }
+    Ok(removed)
+}
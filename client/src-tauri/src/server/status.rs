@@ -0,0 +1,262 @@
+//! The server lifecycle as seen by the shell.
+//!
+//! Every transition is pushed to the frontend so it can subscribe once
+//! instead of polling: `server://starting`, `server://ready` and
+//! `server://failed` for the milestones it usually cares about, plus
+//! `server://state-changed` carrying a full [`StatusSnapshot`]. The
+//! snapshot also says whether the internet is reachable, so a backend that
+//! is down can be told apart from a machine that is offline.
+
+use std::sync::Mutex;
+use std::time::Instant;
+
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager};
+use tokio::sync::watch;
+
+use super::port::PortManager;
+use crate::network::NetworkState;
+use crate::notifications::{self, NotificationKind};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ServerState {
+    NotStarted,
+    /// Spawned but not yet answering health checks.
+    Starting,
+    Healthy,
+    /// Running but failing health checks after having been healthy.
+    Degraded,
+    /// Exited unexpectedly; the supervisor may be about to restart it.
+    Crashed,
+    Stopped,
+}
+
+struct Inner {
+    state: ServerState,
+    pid: Option<u32>,
+    started_at: Option<Instant>,
+    last_error: Option<String>,
+    last_stderr: Vec<String>,
+    network: NetworkState,
+}
+
+/// Managed state holding the current [`ServerState`] and its details.
+pub struct ServerStatus {
+    inner: Mutex<Inner>,
+    state_tx: watch::Sender<ServerState>,
+}
+
+impl Default for ServerStatus {
+    fn default() -> Self {
+        Self {
+            inner: Mutex::new(Inner {
+                state: ServerState::NotStarted,
+                pid: None,
+                started_at: None,
+                last_error: None,
+                last_stderr: Vec::new(),
+                network: NetworkState::Unknown,
+            }),
+            state_tx: watch::channel(ServerState::NotStarted).0,
+        }
+    }
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StatusSnapshot {
+    pub state: ServerState,
+    pub pid: Option<u32>,
+    pub uptime_secs: Option<u64>,
+    pub port: u16,
+    pub last_error: Option<String>,
+    /// Whether the internet is reachable; see [`crate::network`].
+    pub network: NetworkState,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct StartingPayload {
+    pid: Option<u32>,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ReadyPayload {
+    pid: Option<u32>,
+    base_url: String,
+    /// Time from spawn to the first healthy probe.
+    startup_ms: Option<u64>,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct FailedPayload {
+    error: String,
+    /// The last stderr lines of a server that exited during startup.
+    stderr: Vec<String>,
+}
+
+impl ServerStatus {
+    /// Resolves whenever the state changes.
+    pub fn subscribe(&self) -> watch::Receiver<ServerState> {
+        self.state_tx.subscribe()
+    }
+
+    /// A process was just spawned and is waiting to become healthy.
+    pub fn starting(&self, app: &AppHandle, pid: Option<u32>) {
+        self.update(app, |inner| {
+            inner.state = ServerState::Starting;
+            inner.pid = pid;
+            inner.started_at = Some(Instant::now());
+        });
+    }
+
+    /// Records a health probe result for the running process.
+    pub fn probed(&self, app: &AppHandle, healthy: bool) {
+        self.update(app, |inner| {
+            inner.state = match (inner.state, healthy) {
+                (ServerState::Starting | ServerState::Degraded | ServerState::Healthy, true) => {
+                    ServerState::Healthy
+                }
+                (ServerState::Healthy, false) => ServerState::Degraded,
+                (state, _) => state,
+            };
+        });
+    }
+
+    /// A server we did not spawn is already answering on our port.
+    pub fn external(&self, app: &AppHandle) {
+        self.update(app, |inner| {
+            inner.state = ServerState::Healthy;
+            inner.pid = None;
+            inner.started_at = Some(Instant::now());
+        });
+    }
+
+    pub fn crashed(&self, app: &AppHandle, error: impl Into<String>) {
+        self.crashed_with_output(app, error, Vec::new());
+    }
+
+    /// Like [`crashed`](Self::crashed), with what the process printed to
+    /// stderr before it died.
+    pub fn crashed_with_output(
+        &self,
+        app: &AppHandle,
+        error: impl Into<String>,
+        stderr: Vec<String>,
+    ) {
+        let error = error.into();
+        self.update(app, |inner| {
+            inner.state = ServerState::Crashed;
+            inner.pid = None;
+            inner.started_at = None;
+            inner.last_error = Some(error);
+            inner.last_stderr = stderr;
+        });
+    }
+
+    pub fn stopped(&self, app: &AppHandle) {
+        self.update(app, |inner| {
+            inner.state = ServerState::Stopped;
+            inner.pid = None;
+            inner.started_at = None;
+        });
+    }
+
+    /// The internet became reachable or unreachable.
+    pub fn set_network(&self, app: &AppHandle, network: NetworkState) {
+        let snapshot = {
+            let mut inner = self.inner.lock().unwrap();
+            if inner.network == network {
+                return;
+            }
+            inner.network = network;
+            Self::snapshot_of(&inner, app.state::<PortManager>().port())
+        };
+        let _ = app.emit("server://state-changed", snapshot);
+    }
+
+    pub fn snapshot(&self, port: u16) -> StatusSnapshot {
+        let inner = self.inner.lock().unwrap();
+        Self::snapshot_of(&inner, port)
+    }
+
+    fn snapshot_of(inner: &Inner, port: u16) -> StatusSnapshot {
+        StatusSnapshot {
+            state: inner.state,
+            pid: inner.pid,
+            uptime_secs: inner.started_at.map(|t| t.elapsed().as_secs()),
+            port,
+            last_error: inner.last_error.clone(),
+            network: inner.network,
+        }
+    }
+
+    fn update(&self, app: &AppHandle, apply: impl FnOnce(&mut Inner)) {
+        let ports = app.state::<PortManager>();
+        let (previous, snapshot, startup_ms, stderr) = {
+            let mut inner = self.inner.lock().unwrap();
+            let previous = inner.state;
+            let startup_ms = inner.started_at.map(|t| t.elapsed().as_millis() as u64);
+            apply(&mut inner);
+            (
+                previous,
+                Self::snapshot_of(&inner, ports.port()),
+                startup_ms,
+                inner.last_stderr.clone(),
+            )
+        };
+        // Crashes always carry news (a different error), other states only
+        // when they actually change.
+        if previous == snapshot.state && snapshot.state != ServerState::Crashed {
+            return;
+        }
+
+        log::debug!("Server state {:?} -> {:?}", previous, snapshot.state);
+        self.state_tx.send_replace(snapshot.state);
+
+        let _ = match snapshot.state {
+            ServerState::Starting => {
+                app.emit("server://starting", StartingPayload { pid: snapshot.pid })
+            }
+            ServerState::Healthy if previous != ServerState::Degraded => app.emit(
+                "server://ready",
+                ReadyPayload {
+                    pid: snapshot.pid,
+                    base_url: super::connection::frontend_base_url(app),
+                    startup_ms: startup_ms.filter(|_| previous == ServerState::Starting),
+                },
+            ),
+            ServerState::Crashed => {
+                notifications::send_or_log(
+                    app,
+                    NotificationKind::Server,
+                    "Server stopped unexpectedly",
+                    snapshot
+                        .last_error
+                        .as_deref()
+                        .unwrap_or("The backend crashed"),
+                );
+                app.emit(
+                    "server://failed",
+                    FailedPayload {
+                        error: snapshot.last_error.clone().unwrap_or_default(),
+                        stderr,
+                    },
+                )
+            }
+            _ => Ok(()),
+        };
+        let _ = app.emit("server://state-changed", snapshot);
+    }
+}
+
+#[tauri::command]
+pub fn get_server_status(
+    status: tauri::State<'_, ServerStatus>,
+    ports: tauri::State<'_, PortManager>,
+) -> StatusSnapshot {
+    status.snapshot(ports.port())
+}
@@ -0,0 +1,635 @@
+//! Owns the backend child process and restarts it when it dies unexpectedly.
+
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager};
+use tauri_plugin_shell::ShellExt;
+use tokio::process::{Child, Command};
+use tokio::sync::oneshot;
+
+use super::auth::{ApiToken, TOKEN_ENV, TOKEN_HEADER};
+use super::health;
+use super::logs;
+use super::orphans::{remove_pid_file, write_pid_file};
+use super::port::{PortManager, PORT_ENV};
+use super::priority;
+use super::process::{self, ProcessTree};
+use super::status::ServerStatus;
+use crate::audit::AuditAction;
+use crate::config::{ConfigStore, ServerConfig, ServerPriority};
+use crate::data_dir::DATA_DIR_ENV;
+use crate::exports::EXPORTS_ENV;
+use crate::power::PowerMonitor;
+
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+const MAX_RESTARTS: u32 = 5;
+/// A child that stays up at least this long resets the backoff sequence.
+const STABLE_UPTIME: Duration = Duration::from_secs(60);
+/// How long a stopping server gets to exit on its own before it is
+/// interrupted.
+pub const DEFAULT_SHUTDOWN_GRACE: Duration = Duration::from_secs(5);
+/// How long an interrupted server gets before its process tree is killed.
+const INTERRUPT_GRACE: Duration = Duration::from_secs(2);
+/// How often a running server's health endpoint is polled.
+const HEALTH_INTERVAL: Duration = Duration::from_secs(5);
+const HEALTH_TIMEOUT: Duration = Duration::from_secs(2);
+/// How often a starting server's health endpoint is polled.
+const READY_POLL_INTERVAL: Duration = Duration::from_millis(250);
+const READY_PROBE_TIMEOUT: Duration = Duration::from_secs(1);
+/// How long a starting server gets to answer unless configured otherwise.
+pub const DEFAULT_STARTUP_TIMEOUT: Duration = Duration::from_secs(60);
+/// Stderr lines kept from a server that exits during startup.
+const STARTUP_STDERR_LINES: usize = 100;
+/// How long to wait for the output of a process that exited early.
+const OUTPUT_DRAIN_TIMEOUT: Duration = Duration::from_secs(1);
+const STARTUP_FAILURE_LOG: &str = "startup-failure.log";
+/// Environment variable the server reads its log level from.
+const LOG_LEVEL_ENV: &str = "NYX_LOG_LEVEL";
+
+/// How to (re)launch the server process.
+#[derive(Clone, Debug)]
+pub struct LaunchSpec {
+    pub program: PathBuf,
+    pub args: Vec<String>,
+    pub env: Vec<(String, String)>,
+    pub current_dir: Option<PathBuf>,
+    pub priority: ServerPriority,
+    pub cpu_affinity: Option<Vec<usize>>,
+}
+
+impl LaunchSpec {
+    pub fn new(program: impl Into<PathBuf>) -> Self {
+        Self {
+            program: program.into(),
+            args: Vec::new(),
+            env: Vec::new(),
+            current_dir: None,
+            priority: ServerPriority::Normal,
+            cpu_affinity: None,
+        }
+    }
+
+    /// Launches the `externalBin` sidecar `name` from `tauri.conf.json`.
+    ///
+    /// The bundler strips the target-triple suffix and installs sidecars next
+    /// to the app executable, which is where this resolves them; the
+    /// platform's executable extension is added automatically.
+    pub fn sidecar(app: &AppHandle, name: &str) -> Result<Self, String> {
+        let command = app
+            .shell()
+            .sidecar(name)
+            .map_err(|e| format!("Failed to resolve sidecar {}: {}", name, e))?;
+        let command = std::process::Command::from(command);
+        let mut spec = Self::new(command.get_program());
+        if let Some(dir) = command.get_current_dir() {
+            spec = spec.current_dir(dir);
+        }
+        Ok(spec)
+    }
+
+    pub fn arg(mut self, arg: impl Into<String>) -> Self {
+        self.args.push(arg.into());
+        self
+    }
+
+    pub fn env(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.env.push((key.into(), value.into()));
+        self
+    }
+
+    pub fn current_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.current_dir = Some(dir.into());
+        self
+    }
+
+    /// Layers the user's server settings on top. The port and token the
+    /// shell adds afterwards always win.
+    fn configure(mut self, config: ServerConfig) -> Self {
+        self.args.extend(config.args);
+        self.env.extend(config.env);
+        if let Some(level) = config.log_level {
+            self.env.push((LOG_LEVEL_ENV.to_string(), level));
+        }
+        if let Some(dir) = config.working_dir {
+            self.current_dir = Some(dir);
+        }
+        self.priority = config.priority;
+        self.cpu_affinity = config.cpu_affinity;
+        self
+    }
+
+    pub(super) fn spawn(&self) -> std::io::Result<(Child, ProcessTree)> {
+        let mut command = Command::new(&self.program);
+        process::isolate(&mut command);
+        command
+            .args(&self.args)
+            .envs(self.env.iter().map(|(k, v)| (k, v)))
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+        if let Some(dir) = &self.current_dir {
+            command.current_dir(dir);
+        }
+        let child = command.spawn()?;
+        let tree = ProcessTree::contain(&child);
+        let adjusted = self.priority != ServerPriority::Normal || self.cpu_affinity.is_some();
+        if let (true, Some(pid)) = (adjusted, child.id()) {
+            if let Err(e) = priority::apply(pid, self.priority, self.cpu_affinity.as_deref()) {
+                log::warn!("{}", e);
+            }
+        }
+        Ok((child, tree))
+    }
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RestartedPayload {
+    attempt: u32,
+    pid: Option<u32>,
+    exit_code: Option<i32>,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct GaveUpPayload {
+    attempts: u32,
+    last_error: String,
+}
+
+struct StopRequest {
+    grace: Duration,
+    done: oneshot::Sender<()>,
+}
+
+struct Running {
+    generation: u64,
+    pid: Option<u32>,
+    base_url: String,
+    token: String,
+    /// The pooled client, for asking the server to shut down.
+    client: reqwest::Client,
+    /// Dropping the sender also ends supervision and kills the child.
+    shutdown: oneshot::Sender<StopRequest>,
+}
+
+/// Managed state tracking the supervised server, if any.
+#[derive(Default)]
+pub struct ServerSupervisor {
+    running: Mutex<Option<Running>>,
+    generation: Mutex<u64>,
+    /// Set while a `start` is waiting for its server to come up.
+    starting: AtomicBool,
+}
+
+struct StartingGuard<'a>(&'a AtomicBool);
+
+impl Drop for StartingGuard<'_> {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::SeqCst);
+    }
+}
+
+/// A server that was started and, if asked to wait, answered its health
+/// check.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Started {
+    pub pid: u32,
+    /// Time from spawn to the first healthy probe.
+    pub startup_ms: Option<u64>,
+}
+
+impl ServerSupervisor {
+    pub fn is_running(&self) -> bool {
+        self.running.lock().unwrap().is_some()
+    }
+
+    pub fn pid(&self) -> Option<u32> {
+        self.running
+            .lock()
+            .unwrap()
+            .as_ref()
+            .and_then(|running| running.pid)
+    }
+
+    /// Spawns the server and hands it to a background supervision task.
+    ///
+    /// Pending storage migrations run first; when they fail the server is
+    /// not started.
+    ///
+    /// With a `ready_timeout`, the health endpoint is polled right after the
+    /// spawn and this resolves as soon as it answers. An exit before then, or
+    /// no answer within the timeout, fails the start and is not retried.
+    pub async fn start(
+        &self,
+        app: &AppHandle,
+        spec: LaunchSpec,
+        ready_timeout: Option<Duration>,
+    ) -> Result<Started, String> {
+        if self.is_running() {
+            return Err("Server is already running".to_string());
+        }
+        if super::connection::is_external(app) {
+            return Err("Connected to an external server; not spawning a local one".to_string());
+        }
+        if self.starting.swap(true, Ordering::SeqCst) {
+            return Err("Server is already starting".to_string());
+        }
+        let _starting = StartingGuard(&self.starting);
+
+        let ports = app.state::<PortManager>();
+        let status = app.state::<ServerStatus>();
+        let mut config = app.state::<ConfigStore>().get().server;
+        if let Err(e) = crate::secrets::resolve_env(app, &mut config.env).await {
+            status.crashed(app, &e);
+            return Err(e);
+        }
+        if let Some(port) = config.port {
+            ports.prefer(port);
+        }
+        let port = ports.reserve().to_string();
+        let token = app.state::<ApiToken>().value().to_string();
+        let mut spec = spec
+            .configure(config)
+            .arg("--port")
+            .arg(&port)
+            .env(PORT_ENV, port)
+            .env(TOKEN_ENV, &token);
+        match crate::exports::exports_dir(app) {
+            Ok(dir) => spec = spec.env(EXPORTS_ENV, dir.to_string_lossy()),
+            Err(e) => log::warn!("Server starts without an exports directory: {}", e),
+        }
+        if let Some(dir) = crate::data_dir::server_data_dir(app) {
+            spec = spec.env(DATA_DIR_ENV, dir.to_string_lossy());
+        }
+
+        if let Err(e) = super::migrate::run(app, &spec).await {
+            status.crashed(app, &e);
+            return Err(e);
+        }
+        let spawned = spec.spawn();
+        crate::audit::record(
+            app,
+            AuditAction::ProcessSpawn,
+            format!("server {}", spec.program.display()),
+            &spawned,
+        );
+        let (mut child, tree) = spawned.map_err(|e| {
+            let error = format!("Failed to start server: {}", e);
+            status.crashed(app, &error);
+            error
+        })?;
+        let spawned_at = logs::now_millis();
+        let pumps = logs::attach(app, &mut child);
+        let pid = child.id();
+        status.starting(app, pid);
+
+        let started_at = Instant::now();
+        let outcome = match ready_timeout {
+            Some(timeout) => wait_until_ready(app, &mut child, timeout).await,
+            None => Ok(()),
+        };
+        if let Err(mut error) = outcome {
+            tree.kill();
+
+            // Let the pumps catch up on what the process printed before dying
+            let deadline = tokio::time::Instant::now() + OUTPUT_DRAIN_TIMEOUT;
+            for pump in pumps {
+                let _ = tokio::time::timeout_at(deadline, pump).await;
+            }
+            let stderr = app
+                .state::<logs::ServerLogs>()
+                .stderr_since(spawned_at, STARTUP_STDERR_LINES);
+            write_startup_failure(app, &spec, &error, &stderr);
+            if let Some(last) = stderr.last() {
+                error = format!("{}: {}", error, last);
+            }
+            status.crashed_with_output(app, &error, stderr);
+            return Err(error);
+        }
+        let startup_ms = ready_timeout.map(|_| started_at.elapsed().as_millis() as u64);
+
+        let generation = {
+            let mut generation = self.generation.lock().unwrap();
+            *generation += 1;
+            *generation
+        };
+        let (shutdown_tx, shutdown_rx) = oneshot::channel();
+        *self.running.lock().unwrap() = Some(Running {
+            generation,
+            pid,
+            base_url: ports.base_url(),
+            token,
+            client: super::connection::http_client(app),
+            shutdown: shutdown_tx,
+        });
+
+        tauri::async_runtime::spawn(supervise(
+            app.clone(),
+            spec,
+            child,
+            tree,
+            generation,
+            shutdown_rx,
+        ));
+        Ok(Started {
+            pid: pid.unwrap_or_default(),
+            startup_ms,
+        })
+    }
+
+    /// Stops supervising the server and shuts it down.
+    ///
+    /// The backend is asked to exit via `/shutdown` first; if it is still
+    /// alive after `grace` it is interrupted, and its whole process tree is
+    /// killed if that does not stop it either. Returns `false` when no
+    /// server was running.
+    pub async fn stop(&self, grace: Duration) -> bool {
+        let Some(running) = self.running.lock().unwrap().take() else {
+            return false;
+        };
+
+        let (done_tx, done_rx) = oneshot::channel();
+        if running
+            .shutdown
+            .send(StopRequest {
+                grace,
+                done: done_tx,
+            })
+            .is_err()
+        {
+            return true;
+        }
+
+        log::info!("Requesting server shutdown...");
+        if let Err(e) =
+            request_shutdown(&running.client, &running.base_url, &running.token, grace).await
+        {
+            log::debug!("Shutdown request failed: {}", e);
+        }
+
+        // The supervision task drops `done` without sending if it was between
+        // restarts, which also means there is nothing left to wait for.
+        let _ = done_rx.await;
+        log::info!("Server stopped");
+        true
+    }
+
+    fn set_pid(&self, generation: u64, pid: Option<u32>) {
+        if let Some(running) = self.running.lock().unwrap().as_mut() {
+            if running.generation == generation {
+                running.pid = pid;
+            }
+        }
+    }
+
+    fn clear(&self, generation: u64) {
+        let mut running = self.running.lock().unwrap();
+        if running.as_ref().is_some_and(|r| r.generation == generation) {
+            *running = None;
+        }
+    }
+}
+
+async fn supervise(
+    app: AppHandle,
+    spec: LaunchSpec,
+    mut child: Child,
+    mut tree: ProcessTree,
+    generation: u64,
+    mut shutdown: oneshot::Receiver<StopRequest>,
+) {
+    let supervisor = app.state::<ServerSupervisor>();
+    let server_status = app.state::<ServerStatus>();
+    let mut attempt = 0;
+    let mut backoff = INITIAL_BACKOFF;
+
+    loop {
+        if let Some(pid) = child.id() {
+            write_pid_file(&app, pid, &spec.program);
+        }
+
+        let started = Instant::now();
+        let mut health_check = tokio::time::interval(HEALTH_INTERVAL);
+        let status = loop {
+            tokio::select! {
+                status = child.wait() => break status,
+                _ = health_check.tick() => {
+                    // A probe across a sleep fails for reasons of its own;
+                    // the resume probes again
+                    if app.state::<PowerMonitor>().is_sleeping() {
+                        continue;
+                    }
+                    let report = health::check(&app, &health::health_url(&app), HEALTH_TIMEOUT).await;
+                    server_status.probed(&app, report.healthy);
+                    health::record(&app, &report);
+                }
+                stop = &mut shutdown => {
+                    match stop {
+                        Ok(stop) => {
+                            terminate(&mut child, &tree, stop.grace).await;
+                            let _ = stop.done.send(());
+                        }
+                        Err(_) => terminate(&mut child, &tree, Duration::ZERO).await,
+                    }
+                    server_status.stopped(&app);
+                    remove_pid_file(&app);
+                    return;
+                }
+            }
+        };
+
+        // Workers outliving the server would hold its port and block the restart
+        tree.kill();
+        let exit_code = status.as_ref().ok().and_then(|s| s.code());
+        match &status {
+            Ok(status) => log::warn!("Server process exited unexpectedly with status: {}", status),
+            Err(e) => log::warn!("Error waiting for server process: {}", e),
+        }
+
+        if started.elapsed() >= STABLE_UPTIME {
+            attempt = 0;
+            backoff = INITIAL_BACKOFF;
+        }
+
+        let mut last_error = match &status {
+            Ok(status) => format!("Server process exited with status: {}", status),
+            Err(e) => format!("Error waiting for server process: {}", e),
+        };
+        server_status.crashed(&app, &last_error);
+
+        loop {
+            if attempt >= MAX_RESTARTS {
+                log::error!(
+                    "Server restart limit reached after {} attempts, giving up",
+                    attempt
+                );
+                supervisor.clear(generation);
+                remove_pid_file(&app);
+                let _ = app.emit(
+                    "server://gave-up",
+                    GaveUpPayload {
+                        attempts: attempt,
+                        last_error,
+                    },
+                );
+                return;
+            }
+            attempt += 1;
+
+            log::info!(
+                "Restarting server in {:?} (attempt {}/{})",
+                backoff,
+                attempt,
+                MAX_RESTARTS
+            );
+            tokio::select! {
+                _ = tokio::time::sleep(backoff) => {}
+                _ = &mut shutdown => {
+                    server_status.stopped(&app);
+                    remove_pid_file(&app);
+                    return;
+                }
+            }
+            backoff = (backoff * 2).min(MAX_BACKOFF);
+
+            match spec.spawn() {
+                Ok((new_child, new_tree)) => {
+                    child = new_child;
+                    tree = new_tree;
+                    logs::attach(&app, &mut child);
+                    supervisor.set_pid(generation, child.id());
+                    server_status.starting(&app, child.id());
+                    log::info!("Server restarted with pid {:?}", child.id());
+                    let _ = app.emit(
+                        "server://restarted",
+                        RestartedPayload {
+                            attempt,
+                            pid: child.id(),
+                            exit_code,
+                        },
+                    );
+                    break;
+                }
+                Err(e) => {
+                    log::warn!("Failed to restart server: {}", e);
+                    last_error = format!("Failed to restart server: {}", e);
+                    server_status.crashed(&app, &last_error);
+                }
+            }
+        }
+    }
+}
+
+/// Polls the health endpoint until it answers, the child exits or `timeout`
+/// passes.
+async fn wait_until_ready(
+    app: &AppHandle,
+    child: &mut Child,
+    timeout: Duration,
+) -> Result<(), String> {
+    let url = health::health_url(app);
+    let deadline = tokio::time::Instant::now() + timeout;
+    let mut poll = tokio::time::interval(READY_POLL_INTERVAL);
+    poll.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        tokio::select! {
+            exit = child.wait() => {
+                return Err(match exit {
+                    Ok(exit) => format!("Server process exited early with status: {}", exit),
+                    Err(e) => format!("Error checking server process: {}", e),
+                });
+            }
+            _ = poll.tick() => {
+                if health::probe(app, &url, READY_PROBE_TIMEOUT).await {
+                    app.state::<ServerStatus>().probed(app, true);
+                    return Ok(());
+                }
+                if tokio::time::Instant::now() >= deadline {
+                    return Err(format!(
+                        "Server did not become healthy within {} seconds",
+                        timeout.as_secs()
+                    ));
+                }
+            }
+        }
+    }
+}
+
+/// Saves what is known about a failed start to [`STARTUP_FAILURE_LOG`] in
+/// the logs directory, replacing the previous one.
+fn write_startup_failure(app: &AppHandle, spec: &LaunchSpec, error: &str, stderr: &[String]) {
+    let Ok(dir) = crate::log_files::logs_dir(app) else {
+        return;
+    };
+    let mut report = format!(
+        "{}\n{}\nCommand: {} {}\n",
+        chrono::Local::now().to_rfc3339(),
+        error,
+        spec.program.display(),
+        spec.args.join(" ")
+    );
+    if let Some(dir) = &spec.current_dir {
+        report.push_str(&format!("Working directory: {}\n", dir.display()));
+    }
+    report.push_str("\nstderr:\n");
+    for line in stderr {
+        report.push_str(line);
+        report.push('\n');
+    }
+    let path = dir.join(STARTUP_FAILURE_LOG);
+    let result = std::fs::create_dir_all(&dir).and_then(|_| std::fs::write(&path, report));
+    if let Err(e) = result {
+        log::warn!("Failed to write {}: {}", path.display(), e);
+    }
+}
+
+async fn request_shutdown(
+    client: &reqwest::Client,
+    base_url: &str,
+    token: &str,
+    timeout: Duration,
+) -> Result<(), reqwest::Error> {
+    client
+        .post(format!("{}/shutdown", base_url))
+        .header(TOKEN_HEADER, token)
+        .timeout(timeout)
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(())
+}
+
+/// Waits up to `grace` for the child to exit on its own after `/shutdown`,
+/// then interrupts it, and kills its process tree if it is still running
+/// [`INTERRUPT_GRACE`] later.
+async fn terminate(child: &mut Child, tree: &ProcessTree, grace: Duration) {
+    let mut exited = tokio::time::timeout(grace, child.wait()).await;
+    if exited.is_err() {
+        log::info!("Server did not exit within {:?}, interrupting it", grace);
+        tree.interrupt();
+        exited = tokio::time::timeout(INTERRUPT_GRACE, child.wait()).await;
+    }
+    match exited {
+        Ok(Ok(status)) => {
+            log::info!("Server process exited with status: {}", status);
+            // Take down any workers that did not follow it
+            tree.kill();
+            return;
+        }
+        Ok(Err(e)) => log::warn!("Error waiting for server process: {}", e),
+        Err(_) => log::warn!("Server did not exit when interrupted, killing it"),
+    }
+
+    tree.kill();
+    if let Err(e) = child.kill().await {
+        log::warn!("Failed to kill server process: {}", e);
+    }
+}
@@ -0,0 +1,250 @@
+//! Optional HTTPS in front of the embedded server, for webviews whose
+//! policies refuse plain `http://localhost`.
+//!
+//! When enabled, a self-signed certificate for `localhost` is generated at
+//! every launch and kept in memory only. Connections to
+//! `https://localhost:<port>` are decrypted and passed on, bytes as they
+//! are, to the backend's own port, so websockets work too. The frontend is
+//! handed the HTTPS URL, while the shell keeps talking to the backend
+//! directly.
+//!
+//! With webkit on Linux the certificate is trusted by this app's webviews
+//! alone. Other platforms have no such hook, and their webviews only accept
+//! it once it is trusted some other way; see [`get_tls_proxy`].
+
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::{Arc, Mutex};
+
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use tauri::{AppHandle, Manager, Runtime, Webview};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer};
+use tokio_rustls::rustls::{self, ServerConfig};
+use tokio_rustls::TlsAcceptor;
+
+use super::connection;
+use super::port::PortManager;
+use crate::bandwidth::{self, Feature};
+use crate::config::ConfigStore;
+
+/// The only name the certificate is valid for.
+const HOST: &str = "localhost";
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TlsProxyInfo {
+    pub running: bool,
+    /// `https://localhost:<port>` while running.
+    pub url: Option<String>,
+    /// This launch's certificate, for trusting it outside the app.
+    pub certificate_pem: Option<String>,
+    /// SHA-256 of the certificate, as hex.
+    pub fingerprint: Option<String>,
+}
+
+struct Certificate {
+    pem: String,
+    fingerprint: String,
+    acceptor: TlsAcceptor,
+}
+
+struct Running {
+    port: u16,
+    task: tauri::async_runtime::JoinHandle<()>,
+}
+
+/// Managed state with this launch's certificate, made on first use, and the
+/// listener while it runs.
+#[derive(Default)]
+pub struct TlsProxy {
+    certificate: Mutex<Option<Arc<Certificate>>>,
+    running: Mutex<Option<Running>>,
+}
+
+fn generate() -> Result<Certificate, String> {
+    let generated =
+        rcgen::generate_simple_self_signed(vec![HOST.to_string(), Ipv4Addr::LOCALHOST.to_string()])
+            .map_err(|e| format!("Failed to generate a certificate: {}", e))?;
+    let der: CertificateDer<'static> = generated.cert.der().clone();
+    let key = PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(generated.key_pair.serialize_der()));
+    let config =
+        ServerConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+            .with_safe_default_protocol_versions()
+            .and_then(|builder| {
+                builder
+                    .with_no_client_auth()
+                    .with_single_cert(vec![der.clone()], key)
+            })
+            .map_err(|e| format!("Failed to set up TLS: {}", e))?;
+    Ok(Certificate {
+        pem: generated.cert.pem(),
+        fingerprint: Sha256::digest(&der)
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect(),
+        acceptor: TlsAcceptor::from(Arc::new(config)),
+    })
+}
+
+impl TlsProxy {
+    fn certificate(&self) -> Result<Arc<Certificate>, String> {
+        let mut certificate = self.certificate.lock().unwrap();
+        if let Some(existing) = &*certificate {
+            return Ok(existing.clone());
+        }
+        let generated = Arc::new(generate()?);
+        *certificate = Some(generated.clone());
+        Ok(generated)
+    }
+
+    /// `https://localhost:<port>` while the proxy runs.
+    pub fn url(&self) -> Option<String> {
+        self.running
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(|running| format!("https://{}:{}", HOST, running.port))
+    }
+
+    fn info(&self) -> TlsProxyInfo {
+        let certificate = self.certificate.lock().unwrap().clone();
+        let url = self.url();
+        TlsProxyInfo {
+            running: url.is_some(),
+            url,
+            certificate_pem: certificate.as_ref().map(|c| c.pem.clone()),
+            fingerprint: certificate.map(|c| c.fingerprint.clone()),
+        }
+    }
+
+    fn stop(&self) {
+        if let Some(running) = self.running.lock().unwrap().take() {
+            running.task.abort();
+            log::info!("Stopped the TLS proxy on port {}", running.port);
+        }
+    }
+}
+
+async fn relay(app: AppHandle, acceptor: TlsAcceptor, client: TcpStream, peer: SocketAddr) {
+    let mut tls = match acceptor.accept(client).await {
+        Ok(tls) => tls,
+        Err(e) => {
+            log::debug!("TLS handshake with {} failed: {}", peer, e);
+            return;
+        }
+    };
+    let port = app.state::<PortManager>().port();
+    let mut backend = match TcpStream::connect((Ipv4Addr::LOCALHOST, port)).await {
+        Ok(backend) => backend,
+        Err(e) => {
+            log::debug!("Failed to reach the server on port {}: {}", port, e);
+            return;
+        }
+    };
+    match tokio::io::copy_bidirectional(&mut tls, &mut backend).await {
+        Ok((sent, received)) => bandwidth::record(&app, Feature::TlsProxy, sent, received),
+        Err(e) => log::trace!("TLS connection from {} ended: {}", peer, e),
+    }
+}
+
+/// Starts or stops the proxy to match the config. Only the embedded server
+/// is proxied; an external one brings its own TLS.
+pub async fn apply(app: &AppHandle) -> Result<(), String> {
+    let config = app.state::<ConfigStore>().get().tls_proxy;
+    let proxy = app.state::<TlsProxy>();
+    let wanted = config.enabled && !connection::is_external(app);
+    let current = proxy.running.lock().unwrap().as_ref().map(|r| r.port);
+    match (wanted, current) {
+        (false, None) => return Ok(()),
+        (false, Some(_)) => {
+            proxy.stop();
+            return Ok(());
+        }
+        (true, Some(port)) if config.port.map_or(true, |wanted| wanted == port) => return Ok(()),
+        (true, _) => proxy.stop(),
+    }
+
+    let certificate = proxy.certificate()?;
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, config.port.unwrap_or(0)))
+        .await
+        .map_err(|e| format!("Failed to start the TLS proxy: {}", e))?;
+    let port = listener
+        .local_addr()
+        .map_err(|e| format!("Failed to start the TLS proxy: {}", e))?
+        .port();
+    let acceptor = certificate.acceptor.clone();
+    let handle = app.clone();
+    let task = tauri::async_runtime::spawn(async move {
+        loop {
+            match listener.accept().await {
+                Ok((client, peer)) => {
+                    tauri::async_runtime::spawn(relay(
+                        handle.clone(),
+                        acceptor.clone(),
+                        client,
+                        peer,
+                    ));
+                }
+                Err(e) => log::warn!("TLS proxy failed to accept a connection: {}", e),
+            }
+        }
+    });
+    *proxy.running.lock().unwrap() = Some(Running { port, task });
+    log::info!("TLS proxy listening on https://{}:{}", HOST, port);
+
+    for window in app.webview_windows().values() {
+        trust(window.as_ref());
+    }
+    Ok(())
+}
+
+/// Starts the proxy in the background if it is enabled.
+pub fn spawn(app: &AppHandle) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        if let Err(e) = apply(&app).await {
+            log::warn!("{}", e);
+        }
+    });
+}
+
+/// Lets `webview` accept this launch's certificate, where the platform
+/// allows it. Called for every webview as it loads.
+pub fn trust<R: Runtime>(webview: &Webview<R>) {
+    let app = webview.app_handle();
+    let Some(proxy) = app.try_state::<TlsProxy>() else {
+        return;
+    };
+    let Some(certificate) = proxy.certificate.lock().unwrap().clone() else {
+        return;
+    };
+    trust_certificate(webview, &certificate.pem);
+}
+
+#[cfg(target_os = "linux")]
+fn trust_certificate<R: Runtime>(webview: &Webview<R>, pem: &str) {
+    let pem = pem.to_string();
+    let result = webview.with_webview(move |platform| {
+        use webkit2gtk::{WebContextExt, WebViewExt};
+        match webkit2gtk::gio::TlsCertificate::from_pem(&pem) {
+            Ok(certificate) => {
+                if let Some(context) = platform.inner().context() {
+                    context.allow_tls_certificate_for_host(&certificate, HOST);
+                }
+            }
+            Err(e) => log::warn!("Webview rejected the TLS proxy certificate: {}", e),
+        }
+    });
+    if let Err(e) = result {
+        log::warn!("Failed to trust the TLS proxy certificate: {}", e);
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn trust_certificate<R: Runtime>(_webview: &Webview<R>, _pem: &str) {}
+
+#[tauri::command]
+pub fn get_tls_proxy(proxy: tauri::State<'_, TlsProxy>) -> TlsProxyInfo {
+    proxy.info()
+}
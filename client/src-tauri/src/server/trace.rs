@@ -0,0 +1,343 @@
+//! An opt-in recorder of the backend calls made through
+//! [`forward`](super::client::forward), for attaching to bug reports.
+//!
+//! While recording, the method, URL, status and latency of each call are
+//! kept in a ring buffer of the last [`MAX_ENTRIES`], with headers and, if
+//! asked for, bodies. Secret-looking headers and JSON fields are redacted
+//! before they are stored. [`export_http_trace`] writes the buffer as a HAR
+//! file that browser devtools and most HTTP tools can open. Nothing is
+//! written to disk otherwise.
+
+use std::collections::{HashMap, VecDeque};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::Instant;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::{json, Value};
+use tauri::{AppHandle, Manager};
+
+use crate::diagnostics::{self, REDACTED, SECRET_KEYS};
+
+/// Calls kept, oldest dropped first.
+const MAX_ENTRIES: usize = 500;
+/// Bodies are cut off past this many bytes.
+const MAX_BODY: usize = 64 * 1024;
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TraceEntry {
+    pub started_at: DateTime<Utc>,
+    pub method: String,
+    pub url: String,
+    pub request_headers: Vec<(String, String)>,
+    pub request_body: Option<String>,
+    /// `None` when the backend never answered.
+    pub status: Option<u16>,
+    pub response_headers: Vec<(String, String)>,
+    pub response_body: Option<String>,
+    pub latency_ms: u64,
+    pub error: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecorderStatus {
+    pub recording: bool,
+    pub capture_bodies: bool,
+    pub entries: usize,
+}
+
+#[derive(Default)]
+struct Inner {
+    recording: bool,
+    capture_bodies: bool,
+    entries: VecDeque<TraceEntry>,
+}
+
+/// Managed state holding the recorded calls.
+#[derive(Default)]
+pub struct HttpRecorder {
+    inner: Mutex<Inner>,
+}
+
+impl HttpRecorder {
+    fn status(&self) -> RecorderStatus {
+        let inner = self.inner.lock().unwrap();
+        RecorderStatus {
+            recording: inner.recording,
+            capture_bodies: inner.capture_bodies,
+            entries: inner.entries.len(),
+        }
+    }
+
+    fn push(&self, entry: TraceEntry) {
+        let mut inner = self.inner.lock().unwrap();
+        if !inner.recording {
+            return;
+        }
+        if inner.entries.len() >= MAX_ENTRIES {
+            inner.entries.pop_front();
+        }
+        inner.entries.push_back(entry);
+    }
+}
+
+fn is_secret(name: &str) -> bool {
+    let name = name.to_lowercase();
+    name.contains("cookie") || SECRET_KEYS.iter().any(|secret| name.contains(secret))
+}
+
+fn redact_headers<'a>(
+    headers: impl Iterator<Item = (&'a String, &'a String)>,
+) -> Vec<(String, String)> {
+    let mut headers: Vec<(String, String)> = headers
+        .map(|(name, value)| {
+            let value = if is_secret(name) {
+                REDACTED.to_string()
+            } else {
+                value.clone()
+            };
+            (name.clone(), value)
+        })
+        .collect();
+    headers.sort();
+    headers
+}
+
+/// The body as stored: secrets redacted if it is JSON, and cut short.
+fn redact_body(text: &str) -> String {
+    let mut text = match serde_json::from_str::<Value>(text) {
+        Ok(mut value) => {
+            diagnostics::redact(&mut value);
+            value.to_string()
+        }
+        Err(_) => text.to_string(),
+    };
+    if text.len() > MAX_BODY {
+        let mut end = MAX_BODY;
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        text.truncate(end);
+        text.push('…');
+    }
+    text
+}
+
+/// A call being recorded, from before it is sent.
+pub struct Pending {
+    started: Instant,
+    entry: TraceEntry,
+    capture_bodies: bool,
+}
+
+impl Pending {
+    /// Starts recording a call, or `None` when the recorder is off.
+    pub fn start(
+        app: &AppHandle,
+        method: &str,
+        url: &str,
+        headers: &HashMap<String, String>,
+        body: Option<&Value>,
+    ) -> Option<Self> {
+        let capture_bodies = {
+            let recorder = app.state::<HttpRecorder>();
+            let inner = recorder.inner.lock().unwrap();
+            if !inner.recording {
+                return None;
+            }
+            inner.capture_bodies
+        };
+        Some(Self {
+            started: Instant::now(),
+            capture_bodies,
+            entry: TraceEntry {
+                started_at: Utc::now(),
+                method: method.to_string(),
+                url: url.to_string(),
+                request_headers: redact_headers(headers.iter()),
+                request_body: body
+                    .filter(|_| capture_bodies)
+                    .map(|body| redact_body(&body.to_string())),
+                status: None,
+                response_headers: Vec::new(),
+                response_body: None,
+                latency_ms: 0,
+                error: None,
+            },
+        })
+    }
+
+    fn finish(mut self, app: &AppHandle) {
+        self.entry.latency_ms = self.started.elapsed().as_millis() as u64;
+        app.state::<HttpRecorder>().push(self.entry);
+    }
+
+    pub fn failed(mut self, app: &AppHandle, error: &str) {
+        self.entry.error = Some(error.to_string());
+        self.finish(app);
+    }
+
+    pub fn answered(
+        mut self,
+        app: &AppHandle,
+        status: u16,
+        headers: &HashMap<String, String>,
+        body: &str,
+    ) {
+        self.entry.status = Some(status);
+        self.entry.response_headers = redact_headers(headers.iter());
+        if self.capture_bodies {
+            self.entry.response_body = Some(redact_body(body));
+        }
+        self.finish(app);
+    }
+}
+
+fn har_headers(headers: &[(String, String)]) -> Vec<Value> {
+    headers
+        .iter()
+        .map(|(name, value)| json!({ "name": name, "value": value }))
+        .collect()
+}
+
+fn mime_type(headers: &[(String, String)]) -> &str {
+    headers
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case("content-type"))
+        .map_or("application/json", |(_, value)| value.as_str())
+}
+
+fn har_entry(entry: &TraceEntry) -> Value {
+    let mut request = json!({
+        "method": entry.method,
+        "url": entry.url,
+        "httpVersion": "HTTP/1.1",
+        "headers": har_headers(&entry.request_headers),
+        "queryString": [],
+        "cookies": [],
+        "headersSize": -1,
+        "bodySize": entry.request_body.as_ref().map_or(0, |body| body.len() as i64),
+    });
+    if let Some(body) = &entry.request_body {
+        request["postData"] = json!({
+            "mimeType": mime_type(&entry.request_headers),
+            "text": body,
+        });
+    }
+    let mut content = json!({
+        "size": entry.response_body.as_ref().map_or(0, |body| body.len()),
+        "mimeType": mime_type(&entry.response_headers),
+    });
+    if let Some(body) = &entry.response_body {
+        content["text"] = json!(body);
+    }
+    let mut har = json!({
+        "startedDateTime": entry.started_at.to_rfc3339(),
+        "time": entry.latency_ms,
+        "request": request,
+        "response": {
+            // HAR has no way to say "no answer" other than status 0
+            "status": entry.status.unwrap_or(0),
+            "statusText": "",
+            "httpVersion": "HTTP/1.1",
+            "headers": har_headers(&entry.response_headers),
+            "cookies": [],
+            "content": content,
+            "redirectURL": "",
+            "headersSize": -1,
+            "bodySize": -1,
+        },
+        "cache": {},
+        "timings": { "send": 0, "wait": entry.latency_ms, "receive": 0 },
+    });
+    if let Some(error) = &entry.error {
+        har["_error"] = json!(error);
+    }
+    har
+}
+
+/// Starts or stops recording. Stopping keeps what was recorded.
+#[tauri::command]
+pub fn set_http_recording(
+    recorder: tauri::State<'_, HttpRecorder>,
+    recording: bool,
+    capture_bodies: Option<bool>,
+) -> RecorderStatus {
+    {
+        let mut inner = recorder.inner.lock().unwrap();
+        inner.recording = recording;
+        if let Some(capture_bodies) = capture_bodies {
+            inner.capture_bodies = capture_bodies;
+        }
+    }
+    log::info!(
+        "{} recording backend calls",
+        if recording { "Started" } else { "Stopped" }
+    );
+    recorder.status()
+}
+
+#[tauri::command]
+pub fn get_http_recording(recorder: tauri::State<'_, HttpRecorder>) -> RecorderStatus {
+    recorder.status()
+}
+
+/// The recorded calls, newest last, optionally only the last `limit`.
+#[tauri::command]
+pub fn get_http_trace(
+    recorder: tauri::State<'_, HttpRecorder>,
+    limit: Option<usize>,
+) -> Vec<TraceEntry> {
+    let inner = recorder.inner.lock().unwrap();
+    let skip = limit.map_or(0, |limit| inner.entries.len().saturating_sub(limit));
+    inner.entries.iter().skip(skip).cloned().collect()
+}
+
+#[tauri::command]
+pub fn clear_http_trace(recorder: tauri::State<'_, HttpRecorder>) {
+    recorder.inner.lock().unwrap().entries.clear();
+}
+
+/// Writes the recorded calls as a HAR file, to `dest` or the downloads
+/// directory, and returns where it was saved.
+#[tauri::command]
+pub fn export_http_trace(
+    app_handle: AppHandle,
+    recorder: tauri::State<'_, HttpRecorder>,
+    dest: Option<PathBuf>,
+) -> Result<String, String> {
+    let path = match dest {
+        Some(dest) => dest,
+        None => {
+            let stamp = chrono::Local::now().format("%Y-%m-%d_%H-%M-%S");
+            diagnostics::downloads_dir(&app_handle)?.join(format!("nyx-http-trace_{}.har", stamp))
+        }
+    };
+    let entries: Vec<Value> = recorder
+        .inner
+        .lock()
+        .unwrap()
+        .entries
+        .iter()
+        .map(har_entry)
+        .collect();
+    let count = entries.len();
+    let har = json!({
+        "log": {
+            "version": "1.2",
+            "creator": {
+                "name": app_handle.package_info().name,
+                "version": app_handle.package_info().version.to_string(),
+            },
+            "entries": entries,
+        }
+    });
+    let json = serde_json::to_vec_pretty(&har).map_err(|e| e.to_string())?;
+    std::fs::write(&path, json)
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    log::info!("Exported {} backend calls to {}", count, path.display());
+    Ok(path.display().to_string())
+}
@@ -0,0 +1,419 @@
+//! Updates the server binary independently of app releases.
+//!
+//! A release manifest lists the latest server build per platform along with
+//! its SHA-256, and is signed with minisign next to it as
+//! `<manifest>.minisig`. The signature is checked against the public key baked
+//! in at build time as `NYX_SERVER_UPDATE_PUBKEY`; builds without one never
+//! update the server. A newer build is downloaded into `app_data_dir()/server`,
+//! verified, and recorded as staged along with the signed manifest; the next
+//! server launch checks the signature and the hash again and picks it up in
+//! place of the bundled sidecar. A staged binary that fails either is deleted
+//! and the bundled copy is used instead.
+
+use std::collections::HashMap;
+use std::env::consts::{ARCH, EXE_SUFFIX, OS};
+use std::ffi::OsStr;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use minisign_verify::{PublicKey, Signature};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tauri::{AppHandle, Emitter, Manager};
+
+use super::client::{self, RequestOptions};
+use super::health;
+use super::integrity;
+use crate::config::ConfigStore;
+
+const SERVER_DIR: &str = "server";
+const STAGED_FILE: &str = "staged.json";
+const DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+/// Used when the config does not name a manifest.
+const DEFAULT_MANIFEST_URL: &str =
+    "https://github.com/kaunda-a/nyx-app/releases/latest/download/server-manifest.json";
+/// The minisign public key server manifests are signed with, if this build
+/// has one.
+const UPDATE_PUBKEY: Option<&str> = option_env!("NYX_SERVER_UPDATE_PUBKEY");
+
+#[derive(Deserialize)]
+struct Manifest {
+    version: String,
+    notes: Option<String>,
+    /// Keyed by `<os>-<arch>`, e.g. `windows-x86_64`.
+    platforms: HashMap<String, Artifact>,
+}
+
+#[derive(Clone, Deserialize)]
+struct Artifact {
+    url: String,
+    sha256: String,
+}
+
+/// A manifest as it was fetched, with its signature.
+#[derive(Clone, Default, Serialize, Deserialize)]
+struct SignedManifest {
+    manifest: String,
+    signature: String,
+}
+
+impl SignedManifest {
+    /// The manifest, once its signature checks out against [`UPDATE_PUBKEY`].
+    fn verify(&self) -> Result<Manifest, String> {
+        let key = UPDATE_PUBKEY
+            .filter(|key| !key.trim().is_empty())
+            .ok_or("This build has no key to verify server updates with")?;
+        let key = PublicKey::from_base64(key.trim())
+            .map_err(|e| format!("Invalid server update key: {}", e))?;
+        let signature = Signature::decode(&self.signature)
+            .map_err(|e| format!("Invalid server manifest signature: {}", e))?;
+        key.verify(self.manifest.as_bytes(), &signature, false)
+            .map_err(|e| format!("Server manifest failed its signature check: {}", e))?;
+        serde_json::from_str(&self.manifest).map_err(|e| format!("Invalid server manifest: {}", e))
+    }
+}
+
+/// A verified download waiting to be used, persisted in [`STAGED_FILE`].
+#[derive(Serialize, Deserialize)]
+struct Staged {
+    version: String,
+    /// A bare file name in [`SERVER_DIR`].
+    file: String,
+    /// What the download was verified against; checked again before it runs.
+    #[serde(default)]
+    signed: SignedManifest,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServerUpdateInfo {
+    pub version: String,
+    pub current_version: Option<String>,
+    pub notes: Option<String>,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ProgressPayload {
+    downloaded: u64,
+    content_length: Option<u64>,
+}
+
+fn server_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    app.path()
+        .app_data_dir()
+        .map(|dir| dir.join(SERVER_DIR))
+        .map_err(|e| format!("Failed to get app data directory: {}", e))
+}
+
+pub fn platform_key() -> String {
+    format!("{}-{}", OS, ARCH)
+}
+
+pub fn sha256_file(path: &Path) -> std::io::Result<String> {
+    let mut file = std::fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = [0u8; 64 * 1024];
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(hex(&hasher.finalize()))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Whether dotted version `candidate` is newer than `current`.
+pub fn is_newer(candidate: &str, current: &str) -> bool {
+    let parse = |version: &str| -> Vec<u64> {
+        version
+            .trim_start_matches('v')
+            .split(['.', '-', '+'])
+            .map_while(|part| part.parse().ok())
+            .collect()
+    };
+    parse(candidate) > parse(current)
+}
+
+/// Whether `name` names a file right in its directory, not a path.
+fn is_file_name(name: &str) -> bool {
+    Path::new(name).file_name() == Some(OsStr::new(name))
+}
+
+fn read_staged(app: &AppHandle) -> Option<(Staged, PathBuf)> {
+    let dir = server_dir(app).ok()?;
+    let contents = std::fs::read(dir.join(STAGED_FILE)).ok()?;
+    let staged: Staged = serde_json::from_slice(&contents).ok()?;
+    if !is_file_name(&staged.file) {
+        log::warn!(
+            "Ignoring staged server at {:?}, not a file name",
+            staged.file
+        );
+        let _ = std::fs::remove_file(dir.join(STAGED_FILE));
+        return None;
+    }
+    let path = dir.join(&staged.file);
+    Some((staged, path))
+}
+
+fn clear_staged(app: &AppHandle, path: &Path) {
+    let _ = std::fs::remove_file(path);
+    if let Ok(dir) = server_dir(app) {
+        let _ = std::fs::remove_file(dir.join(STAGED_FILE));
+    }
+}
+
+/// The hash the signed manifest gives for `staged`'s build on this
+/// platform.
+fn signed_sha256(staged: &Staged) -> Result<String, String> {
+    let manifest = staged.signed.verify()?;
+    if manifest.version != staged.version {
+        return Err(format!(
+            "Staged server {} does not match its manifest's {}",
+            staged.version, manifest.version
+        ));
+    }
+    manifest
+        .platforms
+        .get(&platform_key())
+        .map(|artifact| artifact.sha256.clone())
+        .ok_or_else(|| format!("Server manifest has no build for {}", platform_key()))
+}
+
+/// The downloaded server binary to run instead of the bundled one, if there
+/// is one, its manifest is signed and it matches the hash in it.
+pub fn staged_binary(app: &AppHandle) -> Option<PathBuf> {
+    let (staged, path) = read_staged(app)?;
+    match signed_sha256(&staged).and_then(|sha256| integrity::verify(app, &path, &sha256)) {
+        Ok(()) => {
+            log::info!("Using downloaded server {}", staged.version);
+            Some(path)
+        }
+        Err(e) => {
+            log::warn!("{}; falling back to the bundled copy", e);
+            clear_staged(app, &path);
+            None
+        }
+    }
+}
+
+/// The version of the server that is running, or failing that, the one
+/// staged for the next launch.
+async fn current_version(app: &AppHandle) -> Option<String> {
+    let report = health::check(app, &health::health_url(app), Duration::from_secs(5)).await;
+    report
+        .detail
+        .and_then(|detail| detail.version)
+        .or_else(|| read_staged(app).map(|(staged, _)| staged.version))
+}
+
+async fn fetch_text(app: &AppHandle, url: &str) -> Result<String, String> {
+    client::send(app, Default::default(), |client| client.get(url))
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| format!("Failed to fetch {}: {}", url, e))?
+        .text()
+        .await
+        .map_err(|e| format!("Failed to read {}: {}", url, e))
+}
+
+/// The manifest and its signature, once the signature checks out.
+async fn fetch_manifest(app: &AppHandle) -> Result<(Manifest, SignedManifest), String> {
+    let url = app
+        .state::<ConfigStore>()
+        .get()
+        .updates
+        .server_manifest
+        .unwrap_or_else(|| DEFAULT_MANIFEST_URL.to_string());
+    let signed = SignedManifest {
+        manifest: fetch_text(app, &url).await?,
+        signature: fetch_text(app, &format!("{}.minisig", url)).await?,
+    };
+    let manifest = signed.verify()?;
+    Ok((manifest, signed))
+}
+
+/// A newer server build for this platform and the manifest that lists it.
+struct Update {
+    info: ServerUpdateInfo,
+    artifact: Artifact,
+    signed: SignedManifest,
+}
+
+/// Returns the newer server build for this platform, if there is one.
+async fn check(app: &AppHandle) -> Result<Option<Update>, String> {
+    let (manifest, signed) = fetch_manifest(app).await?;
+    let Some(artifact) = manifest.platforms.get(&platform_key()).cloned() else {
+        log::info!("Server manifest has no build for {}", platform_key());
+        return Ok(None);
+    };
+    let current = current_version(app).await;
+    if current
+        .as_deref()
+        .is_some_and(|current| !is_newer(&manifest.version, current))
+    {
+        return Ok(None);
+    }
+    Ok(Some(Update {
+        info: ServerUpdateInfo {
+            version: manifest.version,
+            current_version: current,
+            notes: manifest.notes,
+        },
+        artifact,
+        signed,
+    }))
+}
+
+/// Streams the update's build to disk, hashing as it goes, and only moves
+/// it into place once the hash matches.
+async fn download(app: &AppHandle, update: Update) -> Result<PathBuf, String> {
+    let Update {
+        info,
+        artifact,
+        signed,
+    } = update;
+    let version = &info.version;
+    let dir = server_dir(app)?;
+    std::fs::create_dir_all(&dir)
+        .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    let file = format!("nyx-server-{}{}", version, EXE_SUFFIX);
+    let path = dir.join(&file);
+    let partial = dir.join(format!("{}.part", file));
+
+    // Builds are large; the client-wide timeout would cut the download off
+    let options = RequestOptions {
+        timeout_ms: Some(DOWNLOAD_TIMEOUT.as_millis() as u64),
+        retries: None,
+        queue: None,
+    };
+    let mut response = client::send(app, options, |client| client.get(&artifact.url))
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| format!("Failed to download {}: {}", artifact.url, e))?;
+    let content_length = response.content_length();
+
+    let mut out = std::fs::File::create(&partial)
+        .map_err(|e| format!("Failed to create {}: {}", partial.display(), e))?;
+    let mut hasher = Sha256::new();
+    let mut downloaded = 0u64;
+    while let Some(chunk) = response
+        .chunk()
+        .await
+        .map_err(|e| format!("Failed to download {}: {}", artifact.url, e))?
+    {
+        hasher.update(&chunk);
+        out.write_all(&chunk)
+            .map_err(|e| format!("Failed to write {}: {}", partial.display(), e))?;
+        downloaded += chunk.len() as u64;
+        let _ = app.emit(
+            "server-update://progress",
+            ProgressPayload {
+                downloaded,
+                content_length,
+            },
+        );
+    }
+    drop(out);
+
+    let hash = hex(&hasher.finalize());
+    if !hash.eq_ignore_ascii_case(&artifact.sha256) {
+        let _ = std::fs::remove_file(&partial);
+        return Err(format!(
+            "Downloaded server {} failed verification: expected {}, got {}",
+            version, artifact.sha256, hash
+        ));
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&partial, std::fs::Permissions::from_mode(0o755))
+            .map_err(|e| format!("Failed to make {} executable: {}", partial.display(), e))?;
+    }
+    std::fs::rename(&partial, &path)
+        .map_err(|e| format!("Failed to move {} into place: {}", path.display(), e))?;
+
+    // Replace the previous download, unless it is the one being installed again
+    if let Some((_, previous)) = read_staged(app).filter(|(_, previous)| *previous != path) {
+        let _ = std::fs::remove_file(previous);
+    }
+    let staged = Staged {
+        version: version.to_string(),
+        file,
+        signed,
+    };
+    let contents = serde_json::to_vec_pretty(&staged).map_err(|e| e.to_string())?;
+    std::fs::write(dir.join(STAGED_FILE), contents)
+        .map_err(|e| format!("Failed to save {}: {}", STAGED_FILE, e))?;
+    Ok(path)
+}
+
+#[tauri::command]
+pub async fn check_server_update(
+    app_handle: AppHandle,
+) -> Result<Option<ServerUpdateInfo>, String> {
+    Ok(check(&app_handle).await?.map(|update| update.info))
+}
+
+/// Downloads and stages the newer server build, which takes over on the
+/// next server restart.
+#[tauri::command]
+pub async fn download_server_update(
+    app_handle: AppHandle,
+) -> Result<Option<ServerUpdateInfo>, String> {
+    let Some(update) = check(&app_handle).await? else {
+        return Ok(None);
+    };
+    let info = update.info.clone();
+    let path = download(&app_handle, update).await?;
+    log::info!("Staged server {} at {}", info.version, path.display());
+    let _ = app_handle.emit("server-update://staged", info.clone());
+    Ok(Some(info))
+}
+
+/// Drops the downloaded server so the next launch uses the bundled copy.
+#[tauri::command]
+pub fn revert_server_update(app_handle: AppHandle) -> Result<(), String> {
+    if let Some((_, path)) = read_staged(&app_handle) {
+        clear_staged(&app_handle, &path);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn staged_files_are_bare_file_names() {
+        assert!(is_file_name("nyx-server-1.2.0"));
+        assert!(is_file_name("nyx-server-1.2.0.exe"));
+        for name in [
+            "",
+            ".",
+            "..",
+            "../nyx-server",
+            "/usr/bin/nyx-server",
+            "bin/nyx",
+            "nyx/",
+        ] {
+            assert!(!is_file_name(name), "{:?}", name);
+        }
+    }
+
+    #[test]
+    fn unsigned_manifests_are_refused() {
+        let signed = SignedManifest {
+            manifest: r#"{"version":"9.9.9","platforms":{}}"#.to_string(),
+            signature: String::new(),
+        };
+        assert!(signed.verify().is_err());
+    }
+}
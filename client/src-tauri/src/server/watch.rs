@@ -0,0 +1,228 @@
+//! Development-build watcher for the server's Python sources and config.
+//!
+//! Edits to the source checkout (`*.py`, `.env`) or to the app's
+//! `config.json` are collected until things go quiet for [`DEBOUNCE`], then
+//! announced as `dev://server-changed`. When started with `restart`, a
+//! server this app runs is also restarted to pick them up.
+
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use serde::Serialize;
+use tauri::async_runtime::JoinHandle;
+use tauri::{AppHandle, Emitter, Manager};
+use tokio::sync::mpsc;
+
+use super::binary;
+use super::supervisor::{ServerSupervisor, DEFAULT_SHUTDOWN_GRACE};
+use crate::config::{ConfigStore, CONFIG_FILE};
+
+/// Quiet time after the last change before it is acted on.
+const DEBOUNCE: Duration = Duration::from_millis(500);
+/// Directories in the checkout whose changes never matter to the server.
+const IGNORED_DIRS: &[&str] = &[
+    "__pycache__",
+    ".git",
+    ".venv",
+    "venv",
+    "dist",
+    "build",
+    "node_modules",
+    ".pytest_cache",
+    ".mypy_cache",
+];
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DevWatchStatus {
+    pub paths: Vec<String>,
+    pub restart: bool,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ServerChanged {
+    /// Files changed since the last announcement.
+    paths: Vec<String>,
+    /// Whether the server is being restarted over them.
+    restarting: bool,
+}
+
+struct Running {
+    // Dropping it stops the notifications
+    _watcher: RecommendedWatcher,
+    task: JoinHandle<()>,
+    status: DevWatchStatus,
+}
+
+/// Managed state holding the watcher while it runs.
+#[derive(Default)]
+pub struct DevWatcher {
+    running: Mutex<Option<Running>>,
+}
+
+impl DevWatcher {
+    fn stop(&self) -> bool {
+        let Some(running) = self.running.lock().unwrap().take() else {
+            return false;
+        };
+        running.task.abort();
+        log::info!("Stopped watching the server sources");
+        true
+    }
+}
+
+/// Whether a change to `path` under the checkout `root` concerns the server.
+fn is_source(root: &Path, path: &Path) -> bool {
+    let Ok(relative) = path.strip_prefix(root) else {
+        return false;
+    };
+    if relative
+        .components()
+        .any(|part| IGNORED_DIRS.iter().any(|dir| part.as_os_str() == *dir))
+    {
+        return false;
+    }
+    path.extension().is_some_and(|extension| extension == "py")
+        || path.file_name().is_some_and(|name| name == ".env")
+}
+
+fn is_relevant(event: &Event) -> bool {
+    matches!(
+        event.kind,
+        EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)
+    )
+}
+
+async fn debounce(app: AppHandle, mut changes: mpsc::UnboundedReceiver<PathBuf>, restart: bool) {
+    while let Some(first) = changes.recv().await {
+        let mut paths = BTreeSet::from([first]);
+        loop {
+            match tokio::time::timeout(DEBOUNCE, changes.recv()).await {
+                Ok(Some(path)) => {
+                    paths.insert(path);
+                }
+                Ok(None) => return,
+                Err(_) => break,
+            }
+        }
+
+        if paths
+            .iter()
+            .any(|path| path.file_name().is_some_and(|name| name == CONFIG_FILE))
+        {
+            app.state::<ConfigStore>().reload();
+        }
+        let restarting = restart
+            && !super::connection::is_external(&app)
+            && app.state::<ServerSupervisor>().is_running();
+        log::info!(
+            "{} server file(s) changed{}",
+            paths.len(),
+            if restarting { ", restarting" } else { "" }
+        );
+        let _ = app.emit(
+            "dev://server-changed",
+            ServerChanged {
+                paths: paths
+                    .iter()
+                    .map(|path| path.display().to_string())
+                    .collect(),
+                restarting,
+            },
+        );
+        if restarting {
+            // Edits made while restarting are picked up by the next round
+            if let Err(e) = crate::relaunch_server(&app, DEFAULT_SHUTDOWN_GRACE).await {
+                log::warn!("Failed to restart the server after a change: {}", e);
+            }
+        }
+    }
+}
+
+/// Starts watching the source checkout and `config.json`, replacing any
+/// earlier watcher. With `restart`, each change also restarts the server.
+#[tauri::command]
+pub fn start_dev_watcher(
+    app_handle: AppHandle,
+    watcher: tauri::State<'_, DevWatcher>,
+    restart: Option<bool>,
+) -> Result<DevWatchStatus, String> {
+    if !cfg!(debug_assertions) {
+        return Err("The server watcher is only available in development builds".to_string());
+    }
+    let root = binary::dev_root();
+    let config = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to resolve app data directory: {}", e))?;
+
+    let (tx, rx) = mpsc::unbounded_channel();
+    let handler = {
+        let root = root.clone();
+        let config = config.join(CONFIG_FILE);
+        move |result: notify::Result<Event>| match result {
+            Ok(event) if is_relevant(&event) => {
+                for path in event.paths {
+                    let wanted = path == config
+                        || root.as_deref().is_some_and(|root| is_source(root, &path));
+                    if wanted {
+                        let _ = tx.send(path);
+                    }
+                }
+            }
+            Ok(_) => {}
+            Err(e) => log::warn!("Server watcher error: {}", e),
+        }
+    };
+    let mut notifier = notify::recommended_watcher(handler)
+        .map_err(|e| format!("Failed to start file watcher: {}", e))?;
+
+    let mut paths = Vec::new();
+    if let Some(root) = &root {
+        notifier
+            .watch(root, RecursiveMode::Recursive)
+            .map_err(|e| format!("Failed to watch {}: {}", root.display(), e))?;
+        paths.push(root.display().to_string());
+    }
+    // The directory rather than the file, which is replaced on every save
+    if config.is_dir() {
+        notifier
+            .watch(&config, RecursiveMode::NonRecursive)
+            .map_err(|e| format!("Failed to watch {}: {}", config.display(), e))?;
+        paths.push(config.join(CONFIG_FILE).display().to_string());
+    }
+    if paths.is_empty() {
+        return Err("Found no server sources or config to watch".to_string());
+    }
+
+    let restart = restart.unwrap_or(false);
+    let status = DevWatchStatus { paths, restart };
+    watcher.stop();
+    *watcher.running.lock().unwrap() = Some(Running {
+        _watcher: notifier,
+        task: tauri::async_runtime::spawn(debounce(app_handle, rx, restart)),
+        status: status.clone(),
+    });
+    log::info!("Watching {:?} for server changes", status.paths);
+    Ok(status)
+}
+
+/// Stops the watcher. Returns `false` if none was running.
+#[tauri::command]
+pub fn stop_dev_watcher(watcher: tauri::State<'_, DevWatcher>) -> bool {
+    watcher.stop()
+}
+
+#[tauri::command]
+pub fn get_dev_watcher(watcher: tauri::State<'_, DevWatcher>) -> Option<DevWatchStatus> {
+    watcher
+        .running
+        .lock()
+        .unwrap()
+        .as_ref()
+        .map(|running| running.status.clone())
+}
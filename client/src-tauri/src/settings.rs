@@ -0,0 +1,227 @@
+//! User preferences kept by the shell in `app_config_dir()`, so they survive
+//! the webview cache being cleared.
+//!
+//! Keys are the camelCase field names of [`Settings`]. A value is only
+//! accepted if the whole settings object still deserializes with it, which
+//! checks both the key and the value's type. Every change is announced with
+//! `settings://changed`.
+
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::notifications::NotificationKind;
+
+pub(crate) const SETTINGS_FILE: &str = "settings.json";
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Theme {
+    #[default]
+    Light,
+    Dark,
+    System,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Font {
+    #[default]
+    Inter,
+    Manrope,
+    System,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "camelCase")]
+pub struct Settings {
+    pub theme: Theme,
+    pub font: Font,
+    /// BCP 47 tag of the interface language, e.g. `en` or `pt-BR`.
+    pub language: String,
+    /// When the user last checked for updates, as an RFC 3339 timestamp.
+    pub last_update_check: Option<String>,
+    /// Kinds of OS notification not to show.
+    pub muted_notifications: Vec<NotificationKind>,
+    /// The profile launched last, for the launch-last-profile shortcut.
+    pub last_profile: Option<String>,
+    pub status_widget: WidgetSettings,
+}
+
+/// How the status widget window behaves; see [`crate::widget`].
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "camelCase")]
+pub struct WidgetSettings {
+    /// Kept above other windows.
+    pub pinned: bool,
+    /// Clicks go through to the window underneath.
+    pub click_through: bool,
+}
+
+impl Default for WidgetSettings {
+    fn default() -> Self {
+        Self {
+            pinned: true,
+            click_through: false,
+        }
+    }
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            theme: Theme::default(),
+            font: Font::default(),
+            language: "en".to_string(),
+            last_update_check: None,
+            muted_notifications: Vec::new(),
+            last_profile: None,
+            status_widget: WidgetSettings::default(),
+        }
+    }
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ChangedPayload {
+    /// The key that changed, or `None` when everything was reset or reloaded.
+    key: Option<String>,
+    settings: Settings,
+}
+
+fn read_settings(path: Option<&Path>) -> Settings {
+    path.and_then(|path| std::fs::read(path).ok())
+        .and_then(|contents| match serde_json::from_slice(&contents) {
+            Ok(settings) => Some(settings),
+            Err(e) => {
+                log::warn!("Ignoring unreadable {}: {}", SETTINGS_FILE, e);
+                None
+            }
+        })
+        .unwrap_or_default()
+}
+
+/// Managed state wrapping [`Settings`] and its file.
+pub struct SettingsStore {
+    path: Option<PathBuf>,
+    settings: Mutex<Settings>,
+}
+
+impl SettingsStore {
+    pub fn load(app: &AppHandle) -> Self {
+        let path = app
+            .path()
+            .app_config_dir()
+            .ok()
+            .map(|dir| dir.join(SETTINGS_FILE));
+        let settings = read_settings(path.as_deref());
+        Self {
+            path,
+            settings: Mutex::new(settings),
+        }
+    }
+
+    /// Rereads the file, e.g. after a backup was restored over it.
+    pub fn reload(&self, app: &AppHandle) {
+        let settings = read_settings(self.path.as_deref());
+        *self.settings.lock().unwrap() = settings.clone();
+        notify(app, None, settings);
+    }
+
+    pub fn get(&self) -> Settings {
+        self.settings.lock().unwrap().clone()
+    }
+
+    /// Changes `key` from the shell's side and announces it.
+    pub fn update(
+        &self,
+        app: &AppHandle,
+        key: &str,
+        change: impl FnOnce(&mut Settings),
+    ) -> Result<Settings, String> {
+        let mut settings = self.get();
+        change(&mut settings);
+        self.replace(settings.clone())?;
+        notify(app, Some(key.to_string()), settings.clone());
+        Ok(settings)
+    }
+
+    fn replace(&self, settings: Settings) -> Result<(), String> {
+        let mut current = self.settings.lock().unwrap();
+        if let Some(path) = &self.path {
+            let contents = serde_json::to_vec_pretty(&settings).map_err(|e| e.to_string())?;
+            path.parent()
+                .map_or(Ok(()), std::fs::create_dir_all)
+                .and_then(|_| std::fs::write(path, contents))
+                .map_err(|e| format!("Failed to save {}: {}", SETTINGS_FILE, e))?;
+        }
+        *current = settings;
+        Ok(())
+    }
+}
+
+fn to_object(settings: &Settings) -> serde_json::Map<String, Value> {
+    match serde_json::to_value(settings) {
+        Ok(Value::Object(object)) => object,
+        _ => serde_json::Map::new(),
+    }
+}
+
+fn notify(app: &AppHandle, key: Option<String>, settings: Settings) {
+    let _ = app.emit("settings://changed", ChangedPayload { key, settings });
+}
+
+/// Returns one setting, or all of them as an object when `key` is omitted.
+#[tauri::command]
+pub fn get_setting(
+    store: tauri::State<'_, SettingsStore>,
+    key: Option<String>,
+) -> Result<Value, String> {
+    let mut object = to_object(&store.get());
+    match key {
+        Some(key) => object
+            .remove(&key)
+            .ok_or_else(|| format!("Unknown setting: {}", key)),
+        None => Ok(Value::Object(object)),
+    }
+}
+
+#[tauri::command]
+pub fn set_setting(app_handle: AppHandle, key: String, value: Value) -> Result<Settings, String> {
+    let store = app_handle.state::<SettingsStore>();
+    let mut object = to_object(&store.get());
+    if !object.contains_key(&key) {
+        return Err(format!("Unknown setting: {}", key));
+    }
+    object.insert(key.clone(), value);
+    let settings: Settings = serde_json::from_value(Value::Object(object))
+        .map_err(|e| format!("Invalid value for {}: {}", key, e))?;
+
+    store.replace(settings.clone())?;
+    notify(&app_handle, Some(key), settings.clone());
+    Ok(settings)
+}
+
+/// Restores one setting, or all of them when `key` is omitted, to its default.
+#[tauri::command]
+pub fn reset_settings(app_handle: AppHandle, key: Option<String>) -> Result<Settings, String> {
+    let store = app_handle.state::<SettingsStore>();
+    let settings = match &key {
+        Some(key) => {
+            let mut object = to_object(&store.get());
+            let default = to_object(&Settings::default())
+                .remove(key)
+                .ok_or_else(|| format!("Unknown setting: {}", key))?;
+            object.insert(key.clone(), default);
+            serde_json::from_value(Value::Object(object)).map_err(|e| e.to_string())?
+        }
+        None => Settings::default(),
+    };
+
+    store.replace(settings.clone())?;
+    notify(&app_handle, key, settings.clone());
+    Ok(settings)
+}
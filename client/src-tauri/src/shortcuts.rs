@@ -0,0 +1,198 @@
+//! Global keyboard shortcuts for core actions, which work while the app is
+//! in the background. Bindings are kept in the config's `shortcuts` and
+//! registered again at launch.
+//!
+//! A combo can be bound to one action only, and one another app holds
+//! cannot be registered at all.
+
+use std::collections::BTreeMap;
+
+use tauri::plugin::TauriPlugin;
+use tauri::{AppHandle, Manager, Wry};
+use tauri_plugin_global_shortcut::{GlobalShortcutExt, Shortcut, ShortcutEvent, ShortcutState};
+
+use crate::config::{ConfigStore, ShortcutAction};
+use crate::settings::SettingsStore;
+
+impl ShortcutAction {
+    fn label(self) -> &'static str {
+        match self {
+            Self::ToggleWindow => "show/hide window",
+            Self::LaunchLastProfile => "launch last profile",
+            Self::StopAllProfiles => "stop all profiles",
+        }
+    }
+}
+
+fn parse(accelerator: &str) -> Result<Shortcut, String> {
+    accelerator
+        .parse()
+        .map_err(|e| format!("Invalid shortcut {:?}: {}", accelerator, e))
+}
+
+pub fn plugin() -> TauriPlugin<Wry> {
+    tauri_plugin_global_shortcut::Builder::new()
+        .with_handler(handle)
+        .build()
+}
+
+fn handle(app: &AppHandle, shortcut: &Shortcut, event: ShortcutEvent) {
+    if event.state() != ShortcutState::Pressed {
+        return;
+    }
+    let bindings = app.state::<ConfigStore>().get().shortcuts;
+    let action = bindings
+        .iter()
+        .find(|(_, accelerator)| parse(accelerator).is_ok_and(|bound| bound == *shortcut))
+        .map(|(action, _)| *action);
+    if let Some(action) = action {
+        log::info!("Shortcut pressed: {}", action.label());
+        run(app, action);
+    }
+}
+
+fn toggle_window(app: &AppHandle) -> bool {
+    let Some(window) = app.get_webview_window("main") else {
+        return false;
+    };
+    let shown = window.is_visible().unwrap_or(false) && window.is_focused().unwrap_or(false);
+    if shown {
+        let _ = window.hide();
+    }
+    shown
+}
+
+fn run(app: &AppHandle, action: ShortcutAction) {
+    match action {
+        ShortcutAction::ToggleWindow => {
+            if !toggle_window(app) {
+                crate::boot::focus(app);
+            }
+        }
+        ShortcutAction::LaunchLastProfile => {
+            let Some(id) = app.state::<SettingsStore>().get().last_profile else {
+                log::info!("No profile has been launched yet");
+                return;
+            };
+            let app = app.clone();
+            tauri::async_runtime::spawn(async move {
+                if let Err(e) = crate::profiles::launch_profile(app, id, None).await {
+                    log::warn!("{}", e);
+                }
+            });
+        }
+        ShortcutAction::StopAllProfiles => {
+            let app = app.clone();
+            tauri::async_runtime::spawn(async move {
+                if let Err(e) = crate::profiles::kill_all_profiles(app).await {
+                    log::warn!("{}", e);
+                }
+            });
+        }
+    }
+}
+
+/// Registers the configured shortcuts. One that cannot be registered is
+/// logged and the rest still are.
+pub fn register_all(app: &AppHandle) {
+    for (action, accelerator) in app.state::<ConfigStore>().get().shortcuts {
+        let result = parse(&accelerator).and_then(|shortcut| {
+            app.global_shortcut()
+                .register(shortcut)
+                .map_err(|e| e.to_string())
+        });
+        if let Err(e) = result {
+            log::warn!(
+                "Failed to register shortcut {} for {}: {}",
+                accelerator,
+                action.label(),
+                e
+            );
+        }
+    }
+}
+
+#[tauri::command]
+pub fn list_shortcuts(config: tauri::State<'_, ConfigStore>) -> BTreeMap<ShortcutAction, String> {
+    config.get().shortcuts
+}
+
+/// Binds `accelerator` to `action`, replacing the action's previous combo,
+/// and returns all bindings.
+#[tauri::command]
+pub fn register_shortcut(
+    app_handle: AppHandle,
+    action: ShortcutAction,
+    accelerator: String,
+) -> Result<BTreeMap<ShortcutAction, String>, String> {
+    let shortcut = parse(&accelerator)?;
+    let config = app_handle.state::<ConfigStore>();
+    let bindings = config.get().shortcuts;
+    for (other, bound) in &bindings {
+        if *other != action && parse(bound).is_ok_and(|bound| bound == shortcut) {
+            return Err(format!(
+                "{} is already bound to {}",
+                accelerator,
+                other.label()
+            ));
+        }
+    }
+    let previous = bindings.get(&action).and_then(|bound| parse(bound).ok());
+    if previous == Some(shortcut) {
+        return config
+            .update(|c| {
+                c.shortcuts.insert(action, accelerator);
+            })
+            .map(|c| c.shortcuts);
+    }
+
+    let global = app_handle.global_shortcut();
+    if global.is_registered(shortcut) {
+        return Err(format!("{} is already registered", accelerator));
+    }
+    global.register(shortcut).map_err(|e| {
+        format!(
+            "Failed to register {}, another app may be using it: {}",
+            accelerator, e
+        )
+    })?;
+    let saved = config.update(|c| {
+        c.shortcuts.insert(action, accelerator);
+    });
+    match saved {
+        Ok(c) => {
+            if let Some(previous) = previous {
+                let _ = global.unregister(previous);
+            }
+            Ok(c.shortcuts)
+        }
+        Err(e) => {
+            let _ = global.unregister(shortcut);
+            Err(e)
+        }
+    }
+}
+
+/// Removes the action's combo and returns the remaining bindings.
+#[tauri::command]
+pub fn unregister_shortcut(
+    app_handle: AppHandle,
+    action: ShortcutAction,
+) -> Result<BTreeMap<ShortcutAction, String>, String> {
+    let config = app_handle.state::<ConfigStore>();
+    let Some(accelerator) = config.get().shortcuts.get(&action).cloned() else {
+        return Ok(config.get().shortcuts);
+    };
+    let bindings = config
+        .update(|c| {
+            c.shortcuts.remove(&action);
+        })?
+        .shortcuts;
+    if let Ok(shortcut) = parse(&accelerator) {
+        app_handle
+            .global_shortcut()
+            .unregister(shortcut)
+            .map_err(|e| format!("Failed to unregister {}: {}", accelerator, e))?;
+    }
+    Ok(bindings)
+}
@@ -0,0 +1,256 @@
+//! Where the time goes between launching the app and using it.
+//!
+//! Each stretch of the boot sequence is timed as a [`Span`] from the moment
+//! the process started: plugins, setup, finding the server's binary,
+//! spawning it, waiting for its health check and any database migration,
+//! the first page painted in any window and the main window shown. The spans of the last [`MAX_RUNS`] launches are kept
+//! in `startup.json` in app data and returned by [`get_startup_timings`], so
+//! a slow start can be compared against earlier ones.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Mutex, OnceLock};
+use std::time::Instant;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+
+use crate::boot::BootPhase;
+
+const STARTUP_FILE: &str = "startup.json";
+/// Launches kept, this one included.
+const MAX_RUNS: usize = 20;
+
+static LAUNCHED: OnceLock<Instant> = OnceLock::new();
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Span {
+    /// From launch until setup starts, mostly plugin initialisation.
+    PluginInit,
+    Setup,
+    ResolveBinary,
+    Spawn,
+    HealthWait,
+    MigrateDatabase,
+    /// From launch until a window first finished loading its page.
+    FirstPaint,
+    /// From launch until the main window was shown.
+    MainWindow,
+}
+
+impl Span {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Span::PluginInit => "plugin-init",
+            Span::Setup => "setup",
+            Span::ResolveBinary => "resolve-binary",
+            Span::Spawn => "spawn",
+            Span::HealthWait => "health-wait",
+            Span::MigrateDatabase => "migrate-database",
+            Span::FirstPaint => "first-paint",
+            Span::MainWindow => "main-window",
+        }
+    }
+
+    /// The span a boot phase is timed as, if any.
+    fn for_phase(phase: BootPhase) -> Option<Span> {
+        match phase {
+            BootPhase::ResolvingBinary => Some(Span::ResolveBinary),
+            BootPhase::Spawning => Some(Span::Spawn),
+            BootPhase::WaitingForHealth => Some(Span::HealthWait),
+            BootPhase::MigratingDatabase => Some(Span::MigrateDatabase),
+            BootPhase::Ready | BootPhase::Failed => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpanTiming {
+    pub span: Span,
+    /// Milliseconds after launch the span started.
+    pub start_ms: u64,
+    pub duration_ms: u64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StartupRun {
+    pub launched_at: DateTime<Utc>,
+    pub version: String,
+    /// In the order they ended.
+    pub spans: Vec<SpanTiming>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StartupTimings {
+    pub current: StartupRun,
+    /// Earlier launches, oldest first.
+    pub previous: Vec<StartupRun>,
+}
+
+struct Inner {
+    previous: Vec<StartupRun>,
+    current: StartupRun,
+    open: HashMap<Span, Instant>,
+}
+
+/// Managed state timing this launch.
+pub struct StartupProfiler {
+    path: Mutex<Option<PathBuf>>,
+    inner: Mutex<Inner>,
+}
+
+impl Default for StartupProfiler {
+    fn default() -> Self {
+        Self {
+            path: Mutex::new(None),
+            inner: Mutex::new(Inner {
+                previous: Vec::new(),
+                current: StartupRun {
+                    launched_at: Utc::now(),
+                    version: env!("CARGO_PKG_VERSION").to_string(),
+                    spans: Vec::new(),
+                },
+                open: HashMap::new(),
+            }),
+        }
+    }
+}
+
+impl StartupProfiler {
+    /// Reads the earlier launches. Runs in setup, once app data is known;
+    /// spans that end before are saved with the next one.
+    pub fn load(&self, app: &AppHandle) {
+        let path = app
+            .path()
+            .app_data_dir()
+            .ok()
+            .map(|dir| dir.join(STARTUP_FILE));
+        let previous: Vec<StartupRun> = path
+            .as_deref()
+            .and_then(|path| std::fs::read(path).ok())
+            .and_then(|contents| match serde_json::from_slice(&contents) {
+                Ok(runs) => Some(runs),
+                Err(e) => {
+                    log::warn!("Ignoring unreadable {}: {}", STARTUP_FILE, e);
+                    None
+                }
+            })
+            .unwrap_or_default();
+        {
+            let mut inner = self.inner.lock().unwrap();
+            let skip = previous.len().saturating_sub(MAX_RUNS - 1);
+            inner.previous = previous.into_iter().skip(skip).collect();
+        }
+        *self.path.lock().unwrap() = path;
+        self.save();
+    }
+
+    fn save(&self) {
+        let Some(path) = self.path.lock().unwrap().clone() else {
+            return;
+        };
+        let runs = {
+            let inner = self.inner.lock().unwrap();
+            let mut runs = inner.previous.clone();
+            runs.push(inner.current.clone());
+            runs
+        };
+        let result = serde_json::to_vec_pretty(&runs)
+            .map_err(|e| e.to_string())
+            .and_then(|json| {
+                let partial = path.with_extension("json.part");
+                std::fs::write(&partial, json)
+                    .and_then(|()| std::fs::rename(&partial, &path))
+                    .map_err(|e| e.to_string())
+            });
+        if let Err(e) = result {
+            log::warn!("Failed to save {}: {}", path.display(), e);
+        }
+    }
+
+    fn start(&self, span: Span) {
+        self.inner
+            .lock()
+            .unwrap()
+            .open
+            .entry(span)
+            .or_insert_with(Instant::now);
+    }
+
+    /// Ends `span`, timed from its start or else from launch. Only the first
+    /// end of a span counts.
+    fn end(&self, span: Span) -> Option<SpanTiming> {
+        let launched = *LAUNCHED.get_or_init(Instant::now);
+        let timing = {
+            let mut inner = self.inner.lock().unwrap();
+            if inner.current.spans.iter().any(|timing| timing.span == span) {
+                return None;
+            }
+            let started = inner.open.remove(&span).unwrap_or(launched);
+            let timing = SpanTiming {
+                span,
+                start_ms: started.saturating_duration_since(launched).as_millis() as u64,
+                duration_ms: started.elapsed().as_millis() as u64,
+            };
+            inner.current.spans.push(timing.clone());
+            timing
+        };
+        self.save();
+        Some(timing)
+    }
+}
+
+/// Notes the moment the process started. Called first thing in `run`.
+pub fn launched() {
+    LAUNCHED.get_or_init(Instant::now);
+}
+
+/// Starts timing `span` now.
+pub fn start(app: &AppHandle, span: Span) {
+    if let Some(profiler) = app.try_state::<StartupProfiler>() {
+        profiler.start(span);
+    }
+}
+
+/// Ends `span` and passes its time on to telemetry.
+pub fn end(app: &AppHandle, span: Span) {
+    let Some(profiler) = app.try_state::<StartupProfiler>() else {
+        return;
+    };
+    let Some(timing) = profiler.end(span) else {
+        return;
+    };
+    log::debug!(
+        "Startup span {} took {} ms",
+        span.as_str(),
+        timing.duration_ms
+    );
+    if span == Span::MainWindow {
+        log::info!("Main window shown {} ms after launch", timing.duration_ms);
+    }
+    crate::telemetry::record_startup(app, span.as_str(), timing.duration_ms);
+}
+
+/// Ends the span of the boot phase left and starts the one entered.
+pub fn phase_changed(app: &AppHandle, from: BootPhase, to: BootPhase) {
+    if let Some(span) = Span::for_phase(from) {
+        end(app, span);
+    }
+    if let Some(span) = Span::for_phase(to) {
+        start(app, span);
+    }
+}
+
+#[tauri::command]
+pub fn get_startup_timings(profiler: tauri::State<'_, StartupProfiler>) -> StartupTimings {
+    let inner = profiler.inner.lock().unwrap();
+    StartupTimings {
+        current: inner.current.clone(),
+        previous: inner.previous.clone(),
+    }
+}
@@ -0,0 +1,283 @@
+//! How much disk the app takes, by category, and reclaiming some of it.
+//!
+//! [`get_storage_breakdown`] walks the folders of each category at once,
+//! each on its own blocking thread. [`clean_category`] deletes what is older
+//! than a number of days, or everything it can: rotated logs but not the
+//! ones being written, cached files but not unfinished downloads, scheduled
+//! backups but never the newest, and browser builds not in use. Profiles are
+//! only ever deleted along with their profile.
+
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+
+use crate::audit::{self, AuditAction};
+use crate::config::ConfigStore;
+use crate::downloads::DownloadManager;
+use crate::log_files;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageCategory {
+    Profiles,
+    Browsers,
+    Logs,
+    /// The webview's cache and the downloads folder.
+    Cache,
+    /// Scheduled backups only; other files in the backup folder are not ours.
+    Backups,
+}
+
+const CATEGORIES: [StorageCategory; 5] = [
+    StorageCategory::Profiles,
+    StorageCategory::Browsers,
+    StorageCategory::Logs,
+    StorageCategory::Cache,
+    StorageCategory::Backups,
+];
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CategoryUsage {
+    pub category: StorageCategory,
+    pub paths: Vec<String>,
+    pub size_bytes: u64,
+    pub files: u64,
+    /// Whether [`clean_category`] takes it.
+    pub cleanable: bool,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StorageBreakdown {
+    pub data_dir: String,
+    pub categories: Vec<CategoryUsage>,
+    pub total_bytes: u64,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CleanResult {
+    pub category: StorageCategory,
+    pub removed: usize,
+    pub freed_bytes: u64,
+}
+
+/// The folders a category lives in.
+fn roots(app: &AppHandle, category: StorageCategory) -> Vec<PathBuf> {
+    let data_dir = crate::data_dir::data_dir(app).ok();
+    match category {
+        StorageCategory::Profiles => data_dir
+            .map(|dir| dir.join("profiles"))
+            .into_iter()
+            .collect(),
+        StorageCategory::Browsers => data_dir
+            .map(|dir| dir.join("browsers"))
+            .into_iter()
+            .collect(),
+        StorageCategory::Logs => log_files::logs_dir(app).ok().into_iter().collect(),
+        StorageCategory::Cache => app
+            .path()
+            .app_cache_dir()
+            .ok()
+            .into_iter()
+            .chain(data_dir.map(|dir| dir.join("downloads")))
+            .collect(),
+        StorageCategory::Backups => {
+            crate::backup::schedule::folder(app, &app.state::<ConfigStore>().get().backup)
+                .ok()
+                .into_iter()
+                .collect()
+        }
+    }
+}
+
+/// Adds up the files under `path` into `size` and `files`.
+fn walk(path: &Path, size: &mut u64, files: &mut u64) {
+    let Ok(entries) = std::fs::read_dir(path) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        match entry.file_type() {
+            Ok(kind) if kind.is_dir() => walk(&entry.path(), size, files),
+            Ok(kind) if kind.is_file() => {
+                *size += entry.metadata().map(|m| m.len()).unwrap_or(0);
+                *files += 1;
+            }
+            _ => {}
+        }
+    }
+}
+
+fn usage(category: StorageCategory, roots: Vec<PathBuf>) -> CategoryUsage {
+    let (mut size_bytes, mut files) = (0, 0);
+    for root in &roots {
+        if category == StorageCategory::Backups {
+            for (path, _) in crate::backup::schedule::existing(root) {
+                size_bytes += std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+                files += 1;
+            }
+        } else {
+            walk(root, &mut size_bytes, &mut files);
+        }
+    }
+    CategoryUsage {
+        category,
+        paths: roots
+            .iter()
+            .map(|root| root.display().to_string())
+            .collect(),
+        size_bytes,
+        files,
+        cleanable: category != StorageCategory::Profiles,
+    }
+}
+
+/// Deletes the files under `path` last modified before `cutoff`, unless
+/// `keep` says otherwise, and the folders that leaves empty.
+fn remove_older(
+    path: &Path,
+    cutoff: SystemTime,
+    keep: &dyn Fn(&Path) -> bool,
+    result: &mut CleanResult,
+) {
+    let Ok(entries) = std::fs::read_dir(path) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let Ok(kind) = entry.file_type() else {
+            continue;
+        };
+        if kind.is_dir() {
+            remove_older(&path, cutoff, keep, result);
+            // Only goes if nothing is left in it
+            let _ = std::fs::remove_dir(&path);
+            continue;
+        }
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+        let old = metadata.modified().is_ok_and(|modified| modified < cutoff);
+        if !kind.is_file() || !old || keep(&path) {
+            continue;
+        }
+        match std::fs::remove_file(&path) {
+            Ok(()) => {
+                result.removed += 1;
+                result.freed_bytes += metadata.len();
+            }
+            Err(e) => log::warn!("Failed to delete {}: {}", path.display(), e),
+        }
+    }
+}
+
+fn clean(app: &AppHandle, category: StorageCategory, cutoff: SystemTime) -> CleanResult {
+    let mut result = CleanResult {
+        category,
+        removed: 0,
+        freed_bytes: 0,
+    };
+    match category {
+        StorageCategory::Profiles => {}
+        StorageCategory::Browsers => {
+            let (removed, freed_bytes) =
+                crate::browsers::remove_installed_before(app, DateTime::<Utc>::from(cutoff));
+            result.removed = removed;
+            result.freed_bytes = freed_bytes;
+        }
+        StorageCategory::Logs => {
+            for root in roots(app, category) {
+                remove_older(&root, cutoff, &log_files::is_active, &mut result);
+            }
+        }
+        StorageCategory::Cache => {
+            let manager = app.state::<DownloadManager>();
+            let keep = |path: &Path| manager.holds(path);
+            for root in roots(app, category) {
+                remove_older(&root, cutoff, &keep, &mut result);
+            }
+        }
+        StorageCategory::Backups => {
+            for root in roots(app, category) {
+                let backups = crate::backup::schedule::existing(&root);
+                for (path, modified) in backups.into_iter().skip(1) {
+                    if modified >= cutoff {
+                        continue;
+                    }
+                    let size = std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+                    match std::fs::remove_file(&path) {
+                        Ok(()) => {
+                            result.removed += 1;
+                            result.freed_bytes += size;
+                        }
+                        Err(e) => log::warn!("Failed to delete {}: {}", path.display(), e),
+                    }
+                }
+            }
+        }
+    }
+    result
+}
+
+#[tauri::command]
+pub async fn get_storage_breakdown(app_handle: AppHandle) -> Result<StorageBreakdown, String> {
+    let data_dir = crate::data_dir::data_dir(&app_handle)?;
+    let walks: Vec<_> = CATEGORIES
+        .into_iter()
+        .map(|category| {
+            let roots = roots(&app_handle, category);
+            tauri::async_runtime::spawn_blocking(move || usage(category, roots))
+        })
+        .collect();
+    let mut categories = Vec::with_capacity(walks.len());
+    for walk in walks {
+        categories.push(walk.await.map_err(|e| e.to_string())?);
+    }
+    Ok(StorageBreakdown {
+        data_dir: data_dir.display().to_string(),
+        total_bytes: categories.iter().map(|usage| usage.size_bytes).sum(),
+        categories,
+    })
+}
+
+/// Deletes what `category` holds that is older than `older_than` days, or
+/// all of it that can go if no age is given.
+#[tauri::command]
+pub async fn clean_category(
+    app_handle: AppHandle,
+    category: StorageCategory,
+    older_than: Option<u64>,
+) -> Result<CleanResult, String> {
+    if category == StorageCategory::Profiles {
+        return Err("Profile data is deleted along with its profile".to_string());
+    }
+    let cutoff = match older_than {
+        Some(days) => SystemTime::now()
+            .checked_sub(Duration::from_secs(days * 86_400))
+            .ok_or_else(|| format!("Invalid age: {} days", days))?,
+        None => SystemTime::now(),
+    };
+    let app = app_handle.clone();
+    let result = tauri::async_runtime::spawn_blocking(move || clean(&app, category, cutoff))
+        .await
+        .map_err(|e| e.to_string())?;
+    audit::record_ok(
+        &app_handle,
+        AuditAction::FileDelete,
+        format!(
+            "{} files, {} bytes, cleaned from {:?}",
+            result.removed, result.freed_bytes, category
+        ),
+    );
+    log::info!(
+        "Cleaned {:?}: {} removed, {} bytes freed",
+        category,
+        result.removed,
+        result.freed_bytes
+    );
+    Ok(result)
+}
@@ -0,0 +1,150 @@
+//! Describes the machine the app runs on, for the About screen, diagnostic
+//! bundles and the backend's profile fingerprinting.
+
+use std::path::Path;
+use std::process::Stdio;
+
+use serde::Serialize;
+use sysinfo::{CpuRefreshKind, Disks, RefreshKind, System};
+use tauri::{AppHandle, Manager};
+use tokio::process::Command;
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SystemInfo {
+    pub os: Option<String>,
+    pub os_version: Option<String>,
+    pub kernel_version: Option<String>,
+    pub arch: String,
+    pub hostname: Option<String>,
+    pub cpu_model: Option<String>,
+    pub cpu_cores: Option<usize>,
+    pub cpu_threads: usize,
+    pub total_memory: u64,
+    pub available_memory: u64,
+    /// Free space on the disk holding the app data directory.
+    pub data_dir_free_space: Option<u64>,
+    pub data_dir_total_space: Option<u64>,
+    pub gpus: Vec<String>,
+    pub app_version: String,
+    pub tauri_version: String,
+    pub webview_version: Option<String>,
+}
+
+/// Free and total space of the disk `path` lives on: the one with the
+/// longest mount point that contains it.
+pub(crate) fn disk_space(path: &Path) -> Option<(u64, u64)> {
+    let path = std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    Disks::new_with_refreshed_list()
+        .iter()
+        .filter(|disk| path.starts_with(disk.mount_point()))
+        .max_by_key(|disk| disk.mount_point().as_os_str().len())
+        .map(|disk| (disk.available_space(), disk.total_space()))
+}
+
+async fn output(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .await
+        .ok()
+        .filter(|output| output.status.success())?;
+    Some(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Display adapter names, as far as the OS tools report them.
+#[cfg(windows)]
+async fn gpus() -> Vec<String> {
+    let script = "Get-CimInstance Win32_VideoController | ForEach-Object { $_.Name }";
+    output(
+        "powershell",
+        &["-NoProfile", "-NonInteractive", "-Command", script],
+    )
+    .await
+    .map(|text| non_empty_lines(&text))
+    .unwrap_or_default()
+}
+
+#[cfg(target_os = "macos")]
+async fn gpus() -> Vec<String> {
+    output("system_profiler", &["SPDisplaysDataType"])
+        .await
+        .map(|text| {
+            text.lines()
+                .filter_map(|line| line.trim().strip_prefix("Chipset Model:"))
+                .map(|name| name.trim().to_string())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(not(any(windows, target_os = "macos")))]
+async fn gpus() -> Vec<String> {
+    output("lspci", &[])
+        .await
+        .map(|text| {
+            text.lines()
+                .filter(|line| {
+                    line.contains("VGA compatible controller")
+                        || line.contains("3D controller")
+                        || line.contains("Display controller")
+                })
+                // `00:02.0 VGA compatible controller: Intel Corporation ...`
+                .filter_map(|line| line.split_once(": ").map(|(_, name)| name))
+                .map(|name| name.trim().to_string())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(windows)]
+fn non_empty_lines(text: &str) -> Vec<String> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+pub async fn collect(app: &AppHandle) -> SystemInfo {
+    let system = System::new_with_specifics(
+        RefreshKind::nothing()
+            .with_memory(Default::default())
+            .with_cpu(CpuRefreshKind::nothing()),
+    );
+    let space = app
+        .path()
+        .app_data_dir()
+        .ok()
+        .and_then(|dir| disk_space(&dir));
+
+    SystemInfo {
+        os: System::name(),
+        os_version: System::long_os_version(),
+        kernel_version: System::kernel_version(),
+        arch: std::env::consts::ARCH.to_string(),
+        hostname: System::host_name(),
+        cpu_model: system
+            .cpus()
+            .first()
+            .map(|cpu| cpu.brand().trim().to_string())
+            .filter(|brand| !brand.is_empty()),
+        cpu_cores: System::physical_core_count(),
+        cpu_threads: system.cpus().len(),
+        total_memory: system.total_memory(),
+        available_memory: system.available_memory(),
+        data_dir_free_space: space.map(|(free, _)| free),
+        data_dir_total_space: space.map(|(_, total)| total),
+        gpus: gpus().await,
+        app_version: app.package_info().version.to_string(),
+        tauri_version: tauri::VERSION.to_string(),
+        webview_version: tauri::webview_version().ok(),
+    }
+}
+
+#[tauri::command]
+pub async fn get_system_info(app_handle: AppHandle) -> Result<SystemInfo, String> {
+    Ok(collect(&app_handle).await)
+}
@@ -0,0 +1,370 @@
+//! Anonymous usage statistics, off until the user turns them on.
+//!
+//! While enabled, the shell counts which of its commands the UI uses, how
+//! often the app crashed and how long startup took. Nothing else is
+//! collected: no profile ids, paths, URLs, arguments or anything typed in.
+//! Reports carry a random install id that is thrown away, along with
+//! everything not yet sent, when telemetry is turned off.
+//!
+//! Counts are batched hourly into `telemetry.json` in app data and sent
+//! while the network is up to the endpoint baked in at build time as
+//! `NYX_TELEMETRY_URL`; builds without one never send anything. Batches
+//! wait in the file while offline, up to [`MAX_QUEUED`].
+//! [`preview_telemetry_payload`] returns exactly what would be sent next.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tauri::ipc::Invoke;
+use tauri::{AppHandle, Manager, Wry};
+
+use crate::crash::CrashState;
+use crate::network::{Connectivity, NetworkState};
+
+const TELEMETRY_FILE: &str = "telemetry.json";
+/// Where reports go, if this build sends any.
+const ENDPOINT: Option<&str> = option_env!("NYX_TELEMETRY_URL");
+const FLUSH_INTERVAL: Duration = Duration::from_secs(60 * 60);
+const SEND_TIMEOUT: Duration = Duration::from_secs(10);
+/// Batches kept while they cannot be sent, oldest dropped first.
+const MAX_QUEUED: usize = 7 * 24;
+/// Bumped when the payload changes shape.
+const SCHEMA: u32 = 1;
+
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Timing {
+    pub count: u64,
+    pub total_ms: u64,
+    pub max_ms: u64,
+}
+
+/// Counts over one period.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Batch {
+    pub period_start: DateTime<Utc>,
+    pub period_end: Option<DateTime<Utc>>,
+    /// Invocations per command name.
+    pub commands: BTreeMap<String, u64>,
+    /// Crashes per kind of crash.
+    pub crashes: BTreeMap<String, u64>,
+    /// Startup timings per phase.
+    pub startup: BTreeMap<String, Timing>,
+}
+
+impl Batch {
+    fn new() -> Self {
+        Self {
+            period_start: Utc::now(),
+            period_end: None,
+            commands: BTreeMap::new(),
+            crashes: BTreeMap::new(),
+            startup: BTreeMap::new(),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.commands.is_empty() && self.crashes.is_empty() && self.startup.is_empty()
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct Stored {
+    enabled: bool,
+    install_id: Option<String>,
+    current: Batch,
+    queue: VecDeque<Batch>,
+    last_sent_at: Option<DateTime<Utc>>,
+}
+
+impl Default for Stored {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            install_id: None,
+            current: Batch::new(),
+            queue: VecDeque::new(),
+            last_sent_at: None,
+        }
+    }
+}
+
+impl Stored {
+    /// Closes the current batch and queues it, if anything was counted.
+    fn rotate(&mut self) {
+        if self.current.is_empty() {
+            return;
+        }
+        let mut batch = std::mem::replace(&mut self.current, Batch::new());
+        batch.period_end = Some(Utc::now());
+        self.queue.push_back(batch);
+        while self.queue.len() > MAX_QUEUED {
+            self.queue.pop_front();
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TelemetryStatus {
+    pub enabled: bool,
+    /// Whether this build has anywhere to send reports.
+    pub can_send: bool,
+    pub queued_batches: usize,
+    pub last_sent_at: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+}
+
+/// Managed state holding the counts and the queue of unsent batches.
+pub struct Telemetry {
+    path: Option<PathBuf>,
+    stored: Mutex<Stored>,
+    last_error: Mutex<Option<String>>,
+}
+
+fn new_install_id() -> String {
+    let mut bytes = [0u8; 16];
+    rand::rngs::OsRng.fill_bytes(&mut bytes);
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+impl Telemetry {
+    /// Loads the stored counts and counts a crash of the last session.
+    /// Runs after [`crate::crash::install`].
+    pub fn load(app: &AppHandle) -> Self {
+        let path = app
+            .path()
+            .app_data_dir()
+            .ok()
+            .map(|dir| dir.join(TELEMETRY_FILE));
+        let stored = path
+            .as_deref()
+            .and_then(|path| std::fs::read(path).ok())
+            .and_then(|contents| match serde_json::from_slice(&contents) {
+                Ok(stored) => Some(stored),
+                Err(e) => {
+                    log::warn!("Ignoring unreadable {}: {}", TELEMETRY_FILE, e);
+                    None
+                }
+            })
+            .unwrap_or_default();
+        let telemetry = Self {
+            path,
+            stored: Mutex::new(stored),
+            last_error: Mutex::new(None),
+        };
+        let crash = app
+            .try_state::<CrashState>()
+            .and_then(|state| state.recovery())
+            .and_then(|recovery| recovery.report)
+            .and_then(|report| serde_json::to_value(report.kind).ok())
+            .and_then(|kind| kind.as_str().map(str::to_string));
+        if let Some(kind) = crash {
+            telemetry.count(|batch| *batch.crashes.entry(kind).or_default() += 1);
+        }
+        telemetry
+    }
+
+    /// Applies `change` to the current batch, if telemetry is on.
+    fn count(&self, change: impl FnOnce(&mut Batch)) {
+        let mut stored = self.stored.lock().unwrap();
+        if stored.enabled {
+            change(&mut stored.current);
+        }
+    }
+
+    pub fn save(&self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        let result = serde_json::to_vec_pretty(&*self.stored.lock().unwrap())
+            .map_err(|e| e.to_string())
+            .and_then(|json| {
+                let partial = path.with_extension("json.part");
+                std::fs::write(&partial, json)
+                    .and_then(|()| std::fs::rename(&partial, path))
+                    .map_err(|e| e.to_string())
+            });
+        if let Err(e) = result {
+            log::warn!("Failed to save {}: {}", path.display(), e);
+        }
+    }
+
+    fn status(&self) -> TelemetryStatus {
+        let stored = self.stored.lock().unwrap();
+        TelemetryStatus {
+            enabled: stored.enabled,
+            can_send: ENDPOINT.is_some_and(|endpoint| !endpoint.is_empty()),
+            queued_batches: stored.queue.len(),
+            last_sent_at: stored.last_sent_at,
+            last_error: self.last_error.lock().unwrap().clone(),
+        }
+    }
+}
+
+/// The report for `batches`, or `None` while telemetry is off.
+fn payload(stored: &Stored, batches: &[&Batch]) -> Option<Value> {
+    if !stored.enabled {
+        return None;
+    }
+    Some(json!({
+        "schema": SCHEMA,
+        "installId": stored.install_id,
+        "appVersion": env!("CARGO_PKG_VERSION"),
+        "os": std::env::consts::OS,
+        "arch": std::env::consts::ARCH,
+        "batches": batches,
+    }))
+}
+
+/// Wraps the command handler so each command the UI invokes is counted.
+pub fn counting<F>(handler: F) -> impl Fn(Invoke<Wry>) -> bool + Send + Sync + 'static
+where
+    F: Fn(Invoke<Wry>) -> bool + Send + Sync + 'static,
+{
+    move |invoke| {
+        let command = invoke.message.command();
+        if let Some(telemetry) = invoke.message.webview_ref().try_state::<Telemetry>() {
+            telemetry.count(|batch| *batch.commands.entry(command.to_string()).or_default() += 1);
+        }
+        handler(invoke)
+    }
+}
+
+/// Counts `ms` against the startup `phase`.
+pub fn record_startup(app: &AppHandle, phase: &str, ms: u64) {
+    if let Some(telemetry) = app.try_state::<Telemetry>() {
+        telemetry.count(|batch| {
+            let timing = batch.startup.entry(phase.to_string()).or_default();
+            timing.count += 1;
+            timing.total_ms += ms;
+            timing.max_ms = timing.max_ms.max(ms);
+        });
+    }
+}
+
+/// Sends the queued batches and drops them once the endpoint took them.
+async fn send(app: &AppHandle) -> Result<(), String> {
+    let Some(endpoint) = ENDPOINT.filter(|endpoint| !endpoint.is_empty()) else {
+        return Ok(());
+    };
+    let telemetry = app.state::<Telemetry>();
+    let (body, sent) = {
+        let stored = telemetry.stored.lock().unwrap();
+        if stored.queue.is_empty() {
+            return Ok(());
+        }
+        let batches: Vec<&Batch> = stored.queue.iter().collect();
+        match payload(&stored, &batches) {
+            Some(body) => (body, stored.queue.len()),
+            None => return Ok(()),
+        }
+    };
+    let client = reqwest::Client::builder()
+        .timeout(SEND_TIMEOUT)
+        .build()
+        .map_err(|e| e.to_string())?;
+    let response = client
+        .post(endpoint)
+        .json(&body)
+        .send()
+        .await
+        .map_err(|e| format!("Failed to send telemetry: {}", e))?;
+    if !response.status().is_success() {
+        return Err(format!(
+            "Telemetry endpoint answered HTTP {}",
+            response.status().as_u16()
+        ));
+    }
+    {
+        let mut stored = telemetry.stored.lock().unwrap();
+        // Batches queued while sending stay for the next round
+        let sent = sent.min(stored.queue.len());
+        stored.queue.drain(..sent);
+        stored.last_sent_at = Some(Utc::now());
+    }
+    log::debug!("Sent {} telemetry batches", sent);
+    Ok(())
+}
+
+async fn flush(app: &AppHandle) {
+    let telemetry = app.state::<Telemetry>();
+    let enabled = {
+        let mut stored = telemetry.stored.lock().unwrap();
+        stored.rotate();
+        stored.enabled
+    };
+    let online = app.state::<Connectivity>().state() != NetworkState::Offline;
+    if enabled && online {
+        let result = send(app).await;
+        if let Err(e) = &result {
+            log::debug!("{}", e);
+        }
+        *telemetry.last_error.lock().unwrap() = result.err();
+    }
+    telemetry.save();
+}
+
+/// Batches and sends the counts every hour for the lifetime of the app.
+pub fn spawn(app: &AppHandle) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let mut interval = tokio::time::interval(FLUSH_INTERVAL);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        // The first tick is immediate; there is nothing to batch yet
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            flush(&app).await;
+        }
+    });
+}
+
+#[tauri::command]
+pub fn get_telemetry_status(telemetry: tauri::State<'_, Telemetry>) -> TelemetryStatus {
+    telemetry.status()
+}
+
+/// Turns telemetry on or off. Turning it off deletes the install id and
+/// everything not yet sent.
+#[tauri::command]
+pub fn set_telemetry_enabled(
+    telemetry: tauri::State<'_, Telemetry>,
+    enabled: bool,
+) -> TelemetryStatus {
+    {
+        let mut stored = telemetry.stored.lock().unwrap();
+        if enabled {
+            stored.enabled = true;
+            stored.install_id.get_or_insert_with(new_install_id);
+        } else {
+            *stored = Stored::default();
+        }
+    }
+    *telemetry.last_error.lock().unwrap() = None;
+    telemetry.save();
+    log::info!("Telemetry {}", if enabled { "enabled" } else { "disabled" });
+    telemetry.status()
+}
+
+/// What the next report would contain if it were sent now, including the
+/// batch still being counted; `null` while telemetry is off.
+#[tauri::command]
+pub fn preview_telemetry_payload(telemetry: tauri::State<'_, Telemetry>) -> Option<Value> {
+    let stored = telemetry.stored.lock().unwrap();
+    let mut current = stored.current.clone();
+    current.period_end = Some(Utc::now());
+    let mut batches: Vec<&Batch> = stored.queue.iter().collect();
+    if !current.is_empty() {
+        batches.push(&current);
+    }
+    payload(&stored, &batches)
+}
@@ -0,0 +1,12 @@
+//! Helpers shared by the unit tests.
+
+use std::path::PathBuf;
+
+/// An empty folder in the system's temp directory, named after `name` and
+/// this test run, so tests running in parallel each get their own.
+pub fn scratch(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("nyx-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
@@ -0,0 +1,170 @@
+//! Tray icon that mirrors the server state and offers quick controls, so the
+//! app can keep running with its window hidden.
+
+use tauri::image::Image;
+use tauri::menu::{Menu, MenuEvent, MenuItem, PredefinedMenuItem};
+use tauri::tray::{MouseButton, MouseButtonState, TrayIcon, TrayIconBuilder, TrayIconEvent};
+use tauri::{AppHandle, Manager};
+
+use crate::server::status::{ServerState, ServerStatus};
+use crate::server::supervisor::{ServerSupervisor, DEFAULT_SHUTDOWN_GRACE};
+
+pub const TRAY_ID: &str = "main";
+
+struct Controls {
+    start: MenuItem<tauri::Wry>,
+    stop: MenuItem<tauri::Wry>,
+    restart: MenuItem<tauri::Wry>,
+}
+
+pub fn create(app: &AppHandle) -> tauri::Result<()> {
+    let controls = Controls {
+        start: MenuItem::with_id(app, "start", "Start server", true, None::<&str>)?,
+        stop: MenuItem::with_id(app, "stop", "Stop server", false, None::<&str>)?,
+        restart: MenuItem::with_id(app, "restart", "Restart server", false, None::<&str>)?,
+    };
+    let menu = Menu::with_items(
+        app,
+        &[
+            &MenuItem::with_id(app, "show", "Show Nyx", true, None::<&str>)?,
+            &PredefinedMenuItem::separator(app)?,
+            &controls.start,
+            &controls.stop,
+            &controls.restart,
+            &PredefinedMenuItem::separator(app)?,
+            &MenuItem::with_id(app, "open_logs", "Open logs", true, None::<&str>)?,
+            &MenuItem::with_id(app, "quit", "Quit", true, None::<&str>)?,
+        ],
+    )?;
+
+    let mut builder = TrayIconBuilder::with_id(TRAY_ID)
+        .tooltip("Nyx")
+        .menu(&menu)
+        .show_menu_on_left_click(false)
+        .on_menu_event(on_menu_event)
+        .on_tray_icon_event(|tray, event| {
+            if let TrayIconEvent::Click {
+                button: MouseButton::Left,
+                button_state: MouseButtonState::Up,
+                ..
+            } = event
+            {
+                show_main_window(tray.app_handle());
+            }
+        });
+    if let Some(icon) = app.default_window_icon() {
+        builder = builder.icon(icon.clone());
+    }
+    let tray = builder.build(app)?;
+
+    // Keep the icon and menu in sync with the supervisor
+    let mut state = app.state::<ServerStatus>().subscribe();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            let current = *state.borrow_and_update();
+            refresh(&tray, &controls, current);
+            if state.changed().await.is_err() {
+                break;
+            }
+        }
+    });
+
+    Ok(())
+}
+
+fn on_menu_event(app: &AppHandle, event: MenuEvent) {
+    let app = app.clone();
+    match event.id.as_ref() {
+        "show" => show_main_window(&app),
+        "start" => {
+            tauri::async_runtime::spawn(async move {
+                if let Err(e) = crate::launch_server(&app).await {
+                    log::error!("Failed to start server from tray: {}", e);
+                }
+            });
+        }
+        "stop" => {
+            tauri::async_runtime::spawn(async move {
+                app.state::<ServerSupervisor>()
+                    .stop(DEFAULT_SHUTDOWN_GRACE)
+                    .await;
+            });
+        }
+        "restart" => {
+            tauri::async_runtime::spawn(async move {
+                if let Err(e) = crate::relaunch_server(&app, DEFAULT_SHUTDOWN_GRACE).await {
+                    log::error!("Failed to restart server from tray: {}", e);
+                }
+            });
+        }
+        "open_logs" => {
+            if let Err(e) = crate::folders::open_logs_folder(app) {
+                log::warn!("{}", e);
+            }
+        }
+        "quit" => app.exit(0),
+        _ => {}
+    }
+}
+
+pub fn show_main_window(app: &AppHandle) {
+    if let Some(window) = app.get_webview_window("main") {
+        let _ = window.unminimize();
+        let _ = window.show();
+        if let Some(state) = app.try_state::<crate::window_state::WindowState>() {
+            state.on_shown(&window);
+        }
+        let _ = window.set_focus();
+    }
+}
+
+fn refresh(tray: &TrayIcon, controls: &Controls, state: ServerState) {
+    let (label, color) = match state {
+        ServerState::NotStarted => ("Not started", GRAY),
+        ServerState::Starting => ("Starting", AMBER),
+        ServerState::Healthy => ("Healthy", GREEN),
+        ServerState::Degraded => ("Degraded", AMBER),
+        ServerState::Crashed => ("Crashed", RED),
+        ServerState::Stopped => ("Stopped", GRAY),
+    };
+    let _ = tray.set_tooltip(Some(format!("Nyx — server {}", label.to_lowercase())));
+    if let Some(icon) = tray.app_handle().default_window_icon() {
+        let _ = tray.set_icon(Some(with_status_dot(icon, color)));
+    }
+
+    // An external server is not ours to start or stop
+    let local = !crate::server::connection::is_external(tray.app_handle());
+    let running = tray.app_handle().state::<ServerSupervisor>().is_running()
+        || matches!(
+            state,
+            ServerState::Starting | ServerState::Healthy | ServerState::Degraded
+        );
+    let _ = controls.start.set_enabled(local && !running);
+    let _ = controls.stop.set_enabled(local && running);
+    let _ = controls.restart.set_enabled(local && running);
+}
+
+const GREEN: [u8; 3] = [34, 197, 94];
+const AMBER: [u8; 3] = [245, 158, 11];
+const RED: [u8; 3] = [239, 68, 68];
+const GRAY: [u8; 3] = [156, 163, 175];
+
+/// Paints a status dot over the bottom-right corner of the app icon.
+fn with_status_dot(icon: &Image<'_>, color: [u8; 3]) -> Image<'static> {
+    let (width, height) = (icon.width() as i64, icon.height() as i64);
+    let mut rgba = icon.rgba().to_vec();
+    let radius = width.min(height) / 4;
+    let (cx, cy) = (width - radius - 1, height - radius - 1);
+
+    for y in (cy - radius).max(0)..=(cy + radius).min(height - 1) {
+        for x in (cx - radius).max(0)..=(cx + radius).min(width - 1) {
+            let (dx, dy) = (x - cx, y - cy);
+            if dx * dx + dy * dy <= radius * radius {
+                let offset = ((y * width + x) * 4) as usize;
+                rgba[offset..offset + 4].copy_from_slice(&[color[0], color[1], color[2], 255]);
+            }
+        }
+    }
+
+    Image::new_owned(rgba, width as u32, height as u32)
+}
@@ -0,0 +1,191 @@
+//! Updates for the desktop shell itself, through tauri-plugin-updater.
+//!
+//! `check_for_updates` remembers the update it found, `download_update`
+//! fetches it while emitting `updater://progress`, and
+//! `install_update_and_restart` shuts the backend down before installing.
+
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager};
+use tauri_plugin_updater::{Update, UpdaterExt};
+
+use crate::config::ConfigStore;
+use crate::notifications::{self, NotificationKind};
+use crate::server::supervisor::{ServerSupervisor, DEFAULT_SHUTDOWN_GRACE};
+
+/// Delay before the automatic check, so it does not compete with boot.
+const AUTO_CHECK_DELAY: Duration = Duration::from_secs(30);
+
+/// Managed state holding the update found by the last check and, once
+/// downloaded, its installer bytes.
+#[derive(Default)]
+pub struct PendingUpdate {
+    update: Mutex<Option<Update>>,
+    bytes: Mutex<Option<Vec<u8>>>,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateInfo {
+    pub version: String,
+    pub current_version: String,
+    pub date: Option<String>,
+    pub notes: Option<String>,
+}
+
+impl From<&Update> for UpdateInfo {
+    fn from(update: &Update) -> Self {
+        Self {
+            version: update.version.clone(),
+            current_version: update.current_version.clone(),
+            date: update.date.map(|date| date.to_string()),
+            notes: update.body.clone(),
+        }
+    }
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ProgressPayload {
+    downloaded: u64,
+    content_length: Option<u64>,
+}
+
+/// Public key the update signatures are checked against: the one in
+/// `tauri.conf.json`, or one baked in at build time.
+fn pubkey(app: &AppHandle) -> Option<String> {
+    let configured = app
+        .config()
+        .plugins
+        .0
+        .get("updater")
+        .and_then(|updater| updater.get("pubkey"))
+        .and_then(|key| key.as_str())
+        .filter(|key| !key.is_empty())
+        .map(str::to_string);
+    configured.or_else(|| option_env!("NYX_UPDATER_PUBKEY").map(str::to_string))
+}
+
+async fn check(app: &AppHandle) -> Result<Option<UpdateInfo>, String> {
+    let pubkey = pubkey(app).ok_or("No updater public key is configured for this build")?;
+    let mut builder = app.updater_builder().pubkey(pubkey);
+    if let Some(endpoint) = app.state::<ConfigStore>().get().updates.endpoint {
+        let url = endpoint
+            .parse()
+            .map_err(|e| format!("Invalid update endpoint {}: {}", endpoint, e))?;
+        builder = builder.endpoints(vec![url]).map_err(|e| e.to_string())?;
+    }
+    let update = builder
+        .build()
+        .map_err(|e| format!("Failed to set up updater: {}", e))?
+        .check()
+        .await
+        .map_err(|e| format!("Failed to check for updates: {}", e))?;
+
+    let pending = app.state::<PendingUpdate>();
+    let info = update.as_ref().map(UpdateInfo::from);
+    *pending.bytes.lock().unwrap() = None;
+    *pending.update.lock().unwrap() = update;
+    Ok(info)
+}
+
+/// Checks once after startup unless disabled in the config, announcing a
+/// new version with `updater://available`.
+pub fn spawn_auto_check(app: &AppHandle) {
+    if !app.state::<ConfigStore>().get().updates.auto_check {
+        return;
+    }
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        tokio::time::sleep(AUTO_CHECK_DELAY).await;
+        match check(&app).await {
+            Ok(Some(info)) => {
+                log::info!("Update {} is available", info.version);
+                notifications::send_or_log(
+                    &app,
+                    NotificationKind::Updates,
+                    "Update available",
+                    &format!("Nyx {} is ready to download", info.version),
+                );
+                let _ = app.emit("updater://available", info);
+            }
+            Ok(None) => log::info!("App is up to date"),
+            Err(e) => log::warn!("{}", e),
+        }
+    });
+}
+
+#[tauri::command]
+pub async fn check_for_updates(app_handle: AppHandle) -> Result<Option<UpdateInfo>, String> {
+    check(&app_handle).await
+}
+
+#[tauri::command]
+pub async fn download_update(
+    app_handle: AppHandle,
+    pending: tauri::State<'_, PendingUpdate>,
+) -> Result<(), String> {
+    let update = pending
+        .update
+        .lock()
+        .unwrap()
+        .clone()
+        .ok_or("No update available; check for updates first")?;
+
+    let mut downloaded = 0u64;
+    let bytes = update
+        .download(
+            |chunk, content_length| {
+                downloaded += chunk as u64;
+                let _ = app_handle.emit(
+                    "updater://progress",
+                    ProgressPayload {
+                        downloaded,
+                        content_length,
+                    },
+                );
+            },
+            || {
+                let _ = app_handle.emit("updater://downloaded", ());
+            },
+        )
+        .await
+        .map_err(|e| format!("Failed to download update: {}", e))?;
+
+    *pending.bytes.lock().unwrap() = Some(bytes);
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn install_update_and_restart(
+    app_handle: AppHandle,
+    pending: tauri::State<'_, PendingUpdate>,
+    supervisor: tauri::State<'_, ServerSupervisor>,
+) -> Result<(), String> {
+    let update = pending
+        .update
+        .lock()
+        .unwrap()
+        .clone()
+        .ok_or("No update available; check for updates first")?;
+    let bytes = pending
+        .bytes
+        .lock()
+        .unwrap()
+        .take()
+        .ok_or("Update has not been downloaded yet")?;
+
+    // The installer replaces files the backend may hold open
+    supervisor.stop(DEFAULT_SHUTDOWN_GRACE).await;
+    log::info!("Installing update {}", update.version);
+    if let Err(e) = update.install(bytes) {
+        // Stay usable on the current version
+        if let Err(e) = crate::launch_server(&app_handle).await {
+            log::error!("Failed to restart server after a failed update: {}", e);
+        }
+        return Err(format!("Failed to install update: {}", e));
+    }
+    app_handle.restart()
+}
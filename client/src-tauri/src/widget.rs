@@ -0,0 +1,142 @@
+//! A small status window to keep in a corner while working in other apps
+//! during long automation runs.
+//!
+//! The widget shows the server state, how many profiles run and the
+//! progress of active jobs. While it is open, a [`WidgetStatus`] is pushed
+//! to it as `widget://status` every [`PUSH_INTERVAL`]. It can be pinned
+//! above other windows and made click-through, both kept in the
+//! `statusWidget` setting; a click-through widget takes no clicks, so that
+//! is turned off again from the main window.
+
+use std::time::Duration;
+
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager, WebviewWindow};
+
+use crate::jobs::{JobKind, JobManager, JobProgress, JobState};
+use crate::profiles::registry::ProfileRegistry;
+use crate::server::port::PortManager;
+use crate::server::status::{ServerState, ServerStatus};
+use crate::settings::{SettingsStore, WidgetSettings};
+use crate::windows::{self, WindowInfo, WindowRole, STATUS_WIDGET_LABEL};
+
+const PUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WidgetJob {
+    pub id: String,
+    pub kind: JobKind,
+    pub label: String,
+    pub state: JobState,
+    pub progress: JobProgress,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WidgetStatus {
+    pub server: ServerState,
+    pub running_profiles: usize,
+    /// Queued and running jobs, newest first.
+    pub jobs: Vec<WidgetJob>,
+}
+
+fn status(app: &AppHandle) -> WidgetStatus {
+    let server = app
+        .state::<ServerStatus>()
+        .snapshot(app.state::<PortManager>().port())
+        .state;
+    let jobs = app
+        .state::<JobManager>()
+        .list()
+        .into_iter()
+        .filter(|job| matches!(job.state, JobState::Queued | JobState::Running))
+        .map(|job| WidgetJob {
+            id: job.id,
+            kind: job.kind,
+            label: job.label,
+            state: job.state,
+            progress: job.progress,
+        })
+        .collect();
+    WidgetStatus {
+        server,
+        running_profiles: app.state::<ProfileRegistry>().list().len(),
+        jobs,
+    }
+}
+
+fn apply(window: &WebviewWindow, options: WidgetSettings) -> Result<(), String> {
+    window
+        .set_always_on_top(options.pinned)
+        .and_then(|()| window.set_ignore_cursor_events(options.click_through))
+        .map_err(|e| format!("Failed to apply status widget options: {}", e))
+}
+
+/// Pushes the status to the widget until it is closed.
+fn push(app: &AppHandle) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let mut interval = tokio::time::interval(PUSH_INTERVAL);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        while app.get_webview_window(STATUS_WIDGET_LABEL).is_some() {
+            interval.tick().await;
+            let _ = app.emit_to(STATUS_WIDGET_LABEL, "widget://status", status(&app));
+        }
+    });
+}
+
+/// Saves whichever of the options are given and returns them all.
+fn save_options(
+    app: &AppHandle,
+    pinned: Option<bool>,
+    click_through: Option<bool>,
+) -> Result<WidgetSettings, String> {
+    let store = app.state::<SettingsStore>();
+    if pinned.is_none() && click_through.is_none() {
+        return Ok(store.get().status_widget);
+    }
+    store
+        .update(app, "statusWidget", |settings| {
+            let widget = &mut settings.status_widget;
+            widget.pinned = pinned.unwrap_or(widget.pinned);
+            widget.click_through = click_through.unwrap_or(widget.click_through);
+        })
+        .map(|settings| settings.status_widget)
+}
+
+/// Opens the status widget, or focuses it, with the saved options unless
+/// others are given.
+#[tauri::command]
+pub async fn open_status_widget(
+    app_handle: AppHandle,
+    pinned: Option<bool>,
+    click_through: Option<bool>,
+) -> Result<WindowInfo, String> {
+    let options = save_options(&app_handle, pinned, click_through)?;
+    let (window, info, opened) = windows::open(&app_handle, WindowRole::StatusWidget, None)?;
+    apply(&window, options)?;
+    if opened {
+        push(&app_handle);
+    }
+    Ok(info)
+}
+
+/// Changes the widget's options, applying them right away if it is open.
+#[tauri::command]
+pub fn set_status_widget_options(
+    app_handle: AppHandle,
+    pinned: Option<bool>,
+    click_through: Option<bool>,
+) -> Result<WidgetSettings, String> {
+    let options = save_options(&app_handle, pinned, click_through)?;
+    if let Some(window) = app_handle.get_webview_window(STATUS_WIDGET_LABEL) {
+        apply(&window, options)?;
+    }
+    Ok(options)
+}
+
+#[tauri::command]
+pub fn get_widget_status(app_handle: AppHandle) -> WidgetStatus {
+    status(&app_handle)
+}
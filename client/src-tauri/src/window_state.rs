@@ -0,0 +1,213 @@
+//! Window size, position and maximized state, remembered per monitor
+//! layout in `window-state.json` in `app_config_dir()`, so a laptop docked
+//! and undocked gets the right place for each.
+//!
+//! On a layout not seen before, the bounds saved last are used if they are
+//! still on screen; otherwise the window opens at its default size and
+//! position. Bounds are in physical pixels, which stay right on monitors
+//! with different scale factors.
+
+use std::collections::{BTreeMap, HashSet};
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use tauri::{
+    AppHandle, Manager, Monitor, PhysicalPosition, PhysicalSize, Runtime, Window, WindowEvent,
+};
+
+const STATE_FILE: &str = "window-state.json";
+/// How much of a window must be on some monitor to count as visible.
+const MIN_VISIBLE: (u32, u32) = (200, 100);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Bounds {
+    x: i32,
+    y: i32,
+    width: u32,
+    height: u32,
+    maximized: bool,
+}
+
+/// Window labels to their bounds.
+type Layout = BTreeMap<String, Bounds>;
+
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct StateFile {
+    /// Keyed by [`layout_key`].
+    layouts: BTreeMap<String, Layout>,
+    /// The layout saved last, for monitor setups not seen before.
+    last: Option<String>,
+}
+
+/// Managed state holding the bounds tracked since launch.
+#[derive(Default)]
+pub struct WindowState {
+    path: Option<PathBuf>,
+    file: Mutex<StateFile>,
+    /// Windows to maximize once shown, since doing it while hidden shows
+    /// them on some platforms.
+    pending_maximize: Mutex<HashSet<String>>,
+}
+
+/// Identifies the connected monitors by size and arrangement.
+fn layout_key(monitors: &[Monitor]) -> String {
+    let mut monitors: Vec<String> = monitors
+        .iter()
+        .map(|monitor| {
+            let (size, position) = (monitor.size(), monitor.position());
+            format!(
+                "{}x{}@{},{}",
+                size.width, size.height, position.x, position.y
+            )
+        })
+        .collect();
+    monitors.sort();
+    monitors.join("+")
+}
+
+fn overlap(start: i32, len: u32, other_start: i32, other_len: u32) -> u32 {
+    let end = (start as i64 + len as i64).min(other_start as i64 + other_len as i64);
+    (end - (start as i64).max(other_start as i64)).max(0) as u32
+}
+
+/// Whether enough of `bounds`, including its top edge where the title bar
+/// is, is on one of `monitors`.
+fn is_visible(bounds: &Bounds, monitors: &[Monitor]) -> bool {
+    monitors.iter().any(|monitor| {
+        let (size, position) = (monitor.size(), monitor.position());
+        let top_on_screen =
+            bounds.y >= position.y && (bounds.y as i64) < position.y as i64 + size.height as i64;
+        top_on_screen
+            && overlap(bounds.x, bounds.width, position.x, size.width) >= MIN_VISIBLE.0
+            && overlap(bounds.y, bounds.height, position.y, size.height) >= MIN_VISIBLE.1
+    })
+}
+
+impl WindowState {
+    pub fn load(app: &AppHandle) -> Self {
+        let path = app
+            .path()
+            .app_config_dir()
+            .ok()
+            .map(|dir| dir.join(STATE_FILE));
+        let file = path
+            .as_ref()
+            .and_then(|path| std::fs::read(path).ok())
+            .and_then(|contents| match serde_json::from_slice(&contents) {
+                Ok(file) => Some(file),
+                Err(e) => {
+                    log::warn!("Ignoring unreadable {}: {}", STATE_FILE, e);
+                    None
+                }
+            })
+            .unwrap_or_default();
+        Self {
+            path,
+            file: Mutex::new(file),
+            pending_maximize: Mutex::default(),
+        }
+    }
+
+    /// Moves a window that is not shown yet to where it was last time.
+    pub fn restore<R: Runtime>(&self, window: &tauri::WebviewWindow<R>) {
+        let monitors = window.available_monitors().unwrap_or_default();
+        let file = self.file.lock().unwrap();
+        let saved = file
+            .layouts
+            .get(&layout_key(&monitors))
+            .or_else(|| file.last.as_ref().and_then(|key| file.layouts.get(key)))
+            .and_then(|layout| layout.get(window.label()))
+            .copied();
+        drop(file);
+        let Some(bounds) = saved else {
+            return;
+        };
+        if !is_visible(&bounds, &monitors) {
+            log::info!(
+                "Saved position of window {} is off screen, using the default",
+                window.label()
+            );
+            return;
+        }
+        let _ = window.set_size(PhysicalSize::new(bounds.width, bounds.height));
+        let _ = window.set_position(PhysicalPosition::new(bounds.x, bounds.y));
+        if bounds.maximized {
+            self.pending_maximize
+                .lock()
+                .unwrap()
+                .insert(window.label().to_string());
+        }
+    }
+
+    /// Maximizes a window that was maximized last time, once it is shown.
+    pub fn on_shown<R: Runtime>(&self, window: &tauri::WebviewWindow<R>) {
+        if self.pending_maximize.lock().unwrap().remove(window.label()) {
+            let _ = window.maximize();
+        }
+    }
+
+    /// Records where a window is after it moved or was resized.
+    pub fn track<R: Runtime>(&self, window: &Window<R>, event: &WindowEvent) {
+        if !matches!(event, WindowEvent::Moved(_) | WindowEvent::Resized(_)) {
+            return;
+        }
+        // A minimized window reports a meaningless position
+        if window.is_minimized().unwrap_or(false) || !window.is_visible().unwrap_or(false) {
+            return;
+        }
+        let monitors = window.available_monitors().unwrap_or_default();
+        if monitors.is_empty() {
+            return;
+        }
+        let key = layout_key(&monitors);
+        let maximized = window.is_maximized().unwrap_or(false);
+        let mut file = self.file.lock().unwrap();
+        let layout = file.layouts.entry(key.clone()).or_default();
+        let label = window.label().to_string();
+        // Keep the restored bounds of a maximized window to go back to
+        match (maximized, layout.get_mut(&label)) {
+            (true, Some(bounds)) => bounds.maximized = true,
+            _ => {
+                let (Ok(position), Ok(size)) = (window.outer_position(), window.inner_size())
+                else {
+                    return;
+                };
+                layout.insert(
+                    label,
+                    Bounds {
+                        x: position.x,
+                        y: position.y,
+                        width: size.width,
+                        height: size.height,
+                        maximized,
+                    },
+                );
+            }
+        }
+        file.last = Some(key);
+    }
+
+    pub fn save(&self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        let file = self.file.lock().unwrap();
+        if file.layouts.is_empty() {
+            return;
+        }
+        let result = serde_json::to_vec_pretty(&*file)
+            .map_err(|e| e.to_string())
+            .and_then(|contents| {
+                path.parent()
+                    .map_or(Ok(()), std::fs::create_dir_all)
+                    .and_then(|_| std::fs::write(path, contents))
+                    .map_err(|e| e.to_string())
+            });
+        if let Err(e) = result {
+            log::warn!("Failed to save {}: {}", STATE_FILE, e);
+        }
+    }
+}
@@ -0,0 +1,303 @@
+//! Auxiliary windows next to the main one, each with a role: the log
+//! console, a profile's detail view, the proxy tester and the status widget
+//! of [`crate::widget`].
+//!
+//! A role fixes a window's label, the route its webview loads, its title
+//! and its default size; the log console and the proxy tester are single
+//! windows, and there is one detail window per profile. Opening a window
+//! that is already open focuses it. Open windows are kept in a
+//! [`WindowRegistry`], announced with `window://opened` and `window://closed`,
+//! and can message each other through [`send_to_window`], which arrives as
+//! `window://message`.
+
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager, WebviewUrl, WebviewWindow, WebviewWindowBuilder};
+
+use crate::window_state::WindowState;
+
+pub const MAIN_LABEL: &str = "main";
+pub const STATUS_WIDGET_LABEL: &str = "status-widget";
+const PROFILE_DETAIL_PREFIX: &str = "profile-detail-";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum WindowRole {
+    Main,
+    LogConsole,
+    /// One per profile; opened with the profile's ID.
+    ProfileDetail,
+    ProxyTester,
+    /// Small, frameless and kept above other windows.
+    StatusWidget,
+}
+
+impl WindowRole {
+    fn of_label(label: &str) -> Option<(WindowRole, Option<String>)> {
+        match label {
+            MAIN_LABEL => Some((WindowRole::Main, None)),
+            "log-console" => Some((WindowRole::LogConsole, None)),
+            "proxy-tester" => Some((WindowRole::ProxyTester, None)),
+            STATUS_WIDGET_LABEL => Some((WindowRole::StatusWidget, None)),
+            _ => label
+                .strip_prefix(PROFILE_DETAIL_PREFIX)
+                .map(|id| (WindowRole::ProfileDetail, Some(id.to_string()))),
+        }
+    }
+
+    /// The label of the window for `context`, which only profile details
+    /// need.
+    fn label(self, context: Option<&str>) -> Result<String, String> {
+        match (self, context) {
+            (WindowRole::Main, _) => Ok(MAIN_LABEL.to_string()),
+            (WindowRole::LogConsole, _) => Ok("log-console".to_string()),
+            (WindowRole::ProxyTester, _) => Ok("proxy-tester".to_string()),
+            (WindowRole::StatusWidget, _) => Ok(STATUS_WIDGET_LABEL.to_string()),
+            (WindowRole::ProfileDetail, Some(id)) => {
+                crate::profiles::validate_id(id)?;
+                Ok(format!("{}{}", PROFILE_DETAIL_PREFIX, id))
+            }
+            (WindowRole::ProfileDetail, None) => {
+                Err("A profile detail window needs a profile ID".to_string())
+            }
+        }
+    }
+
+    fn route(self, context: Option<&str>) -> String {
+        match self {
+            WindowRole::Main => "index.html".to_string(),
+            WindowRole::LogConsole => "windows/logs".to_string(),
+            WindowRole::ProfileDetail => {
+                format!("windows/profiles/{}", context.unwrap_or_default())
+            }
+            WindowRole::ProxyTester => "windows/proxy-tester".to_string(),
+            WindowRole::StatusWidget => "windows/widget".to_string(),
+        }
+    }
+
+    fn title(self, context: Option<&str>) -> String {
+        match self {
+            WindowRole::Main => "Nyx Admin".to_string(),
+            WindowRole::LogConsole => "Nyx - Logs".to_string(),
+            WindowRole::ProfileDetail => format!("Nyx - Profile {}", context.unwrap_or_default()),
+            WindowRole::ProxyTester => "Nyx - Proxy tester".to_string(),
+            WindowRole::StatusWidget => "Nyx".to_string(),
+        }
+    }
+
+    /// Default and minimum inner size, in logical pixels.
+    fn size(self) -> ((f64, f64), (f64, f64)) {
+        match self {
+            WindowRole::Main => ((1200.0, 800.0), (800.0, 600.0)),
+            WindowRole::LogConsole => ((900.0, 600.0), (500.0, 300.0)),
+            WindowRole::ProfileDetail => ((720.0, 640.0), (480.0, 400.0)),
+            WindowRole::ProxyTester => ((640.0, 520.0), (420.0, 360.0)),
+            WindowRole::StatusWidget => ((280.0, 120.0), (200.0, 80.0)),
+        }
+    }
+
+    /// Whether the window goes without frame and taskbar entry.
+    fn is_frameless(self) -> bool {
+        self == WindowRole::StatusWidget
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WindowInfo {
+    pub label: String,
+    pub role: WindowRole,
+    /// The profile ID of a profile detail window.
+    pub context: Option<String>,
+    pub opened_at: DateTime<Utc>,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ClosedPayload {
+    label: String,
+    role: WindowRole,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct MessagePayload {
+    from: String,
+    from_role: Option<WindowRole>,
+    event: String,
+    payload: serde_json::Value,
+}
+
+/// Managed state listing the open windows by label.
+#[derive(Default)]
+pub struct WindowRegistry {
+    windows: Mutex<BTreeMap<String, WindowInfo>>,
+}
+
+impl WindowRegistry {
+    fn insert(&self, label: &str) -> Option<WindowInfo> {
+        let (role, context) = WindowRole::of_label(label)?;
+        let info = WindowInfo {
+            label: label.to_string(),
+            role,
+            context,
+            opened_at: Utc::now(),
+        };
+        self.windows
+            .lock()
+            .unwrap()
+            .insert(label.to_string(), info.clone());
+        Some(info)
+    }
+
+    fn get(&self, label: &str) -> Option<WindowInfo> {
+        self.windows.lock().unwrap().get(label).cloned()
+    }
+}
+
+/// Adds a window created elsewhere, i.e. the main window from
+/// `tauri.conf.json`.
+pub fn register(app: &AppHandle, label: &str) {
+    app.state::<WindowRegistry>().insert(label);
+}
+
+/// Drops a destroyed window from the registry and says so.
+pub fn closed(app: &AppHandle, label: &str) {
+    let Some(registry) = app.try_state::<WindowRegistry>() else {
+        return;
+    };
+    let Some(info) = registry.windows.lock().unwrap().remove(label) else {
+        return;
+    };
+    let _ = app.emit(
+        "window://closed",
+        ClosedPayload {
+            label: info.label,
+            role: info.role,
+        },
+    );
+}
+
+/// Opens the window for `role`, or focuses it if it is open already.
+/// Returns whether it was opened just now along with it.
+pub(crate) fn open(
+    app: &AppHandle,
+    role: WindowRole,
+    context: Option<&str>,
+) -> Result<(WebviewWindow, WindowInfo, bool), String> {
+    let label = role.label(context)?;
+    let registry = app.state::<WindowRegistry>();
+    if let Some(window) = app.get_webview_window(&label) {
+        let _ = window.unminimize();
+        let _ = window.show();
+        let _ = window.set_focus();
+        let info = registry
+            .get(&label)
+            .or_else(|| registry.insert(&label))
+            .ok_or_else(|| format!("Unknown window {}", label))?;
+        return Ok((window, info, false));
+    }
+
+    let ((width, height), (min_width, min_height)) = role.size();
+    let frameless = role.is_frameless();
+    let window =
+        WebviewWindowBuilder::new(app, &label, WebviewUrl::App(role.route(context).into()))
+            .title(role.title(context))
+            .inner_size(width, height)
+            .min_inner_size(min_width, min_height)
+            .decorations(!frameless)
+            .skip_taskbar(frameless)
+            .visible(false)
+            .build()
+            .map_err(|e| format!("Failed to open window {}: {}", label, e))?;
+    let window_state = app.try_state::<WindowState>();
+    if let Some(state) = &window_state {
+        state.restore(&window);
+    }
+    let _ = window.show();
+    if let Some(state) = &window_state {
+        state.on_shown(&window);
+    }
+    let _ = window.set_focus();
+
+    let info = registry
+        .insert(&label)
+        .ok_or_else(|| format!("Unknown window {}", label))?;
+    log::info!("Opened window {}", label);
+    let _ = app.emit("window://opened", info.clone());
+    Ok((window, info, true))
+}
+
+/// Opens the window for `role`, or focuses it if it is open already.
+#[tauri::command]
+pub async fn open_window(
+    app_handle: AppHandle,
+    role: WindowRole,
+    context: Option<String>,
+) -> Result<WindowInfo, String> {
+    if role == WindowRole::StatusWidget {
+        return crate::widget::open_status_widget(app_handle, None, None).await;
+    }
+    open(&app_handle, role, context.as_deref()).map(|(_, info, _)| info)
+}
+
+/// Closes an auxiliary window; the main window stays.
+#[tauri::command]
+pub fn close_window(app_handle: AppHandle, label: String) -> Result<(), String> {
+    if label == MAIN_LABEL {
+        return Err("The main window cannot be closed this way".to_string());
+    }
+    match app_handle.get_webview_window(&label) {
+        Some(window) => window
+            .close()
+            .map_err(|e| format!("Failed to close window {}: {}", label, e)),
+        None => Err(format!("No window {} is open", label)),
+    }
+}
+
+#[tauri::command]
+pub fn list_windows(registry: tauri::State<'_, WindowRegistry>) -> Vec<WindowInfo> {
+    registry.windows.lock().unwrap().values().cloned().collect()
+}
+
+/// Delivers `event` with `payload` as `window://message` to the window
+/// `label`, or to every window of `role`. Returns how many got it.
+#[tauri::command]
+pub fn send_to_window(
+    app_handle: AppHandle,
+    window: tauri::WebviewWindow,
+    registry: tauri::State<'_, WindowRegistry>,
+    label: Option<String>,
+    role: Option<WindowRole>,
+    event: String,
+    payload: Option<serde_json::Value>,
+) -> Result<usize, String> {
+    if label.is_none() && role.is_none() {
+        return Err("Give a window label or role to send to".to_string());
+    }
+    let targets: Vec<String> = registry
+        .windows
+        .lock()
+        .unwrap()
+        .values()
+        .filter(|info| label.as_ref().map_or(true, |label| *label == info.label))
+        .filter(|info| role.map_or(true, |role| role == info.role))
+        .filter(|info| info.label != window.label())
+        .map(|info| info.label.clone())
+        .collect();
+    let message = MessagePayload {
+        from: window.label().to_string(),
+        from_role: WindowRole::of_label(window.label()).map(|(role, _)| role),
+        event,
+        payload: payload.unwrap_or_default(),
+    };
+    for target in &targets {
+        app_handle
+            .emit_to(target.as_str(), "window://message", message.clone())
+            .map_err(|e| format!("Failed to message window {}: {}", target, e))?;
+    }
+    Ok(targets.len())
+}
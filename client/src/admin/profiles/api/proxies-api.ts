@@ -1,4 +1,5 @@
 import axios from 'axios';
+import { getShellToken, SHELL_TOKEN_HEADER } from '@/utils/shell-token';
 import { createClient } from '@supabase/supabase-js';
 
 // Inline API client to avoid import issues during build
@@ -26,6 +27,8 @@ const api = axios.create({
 });
 
 api.interceptors.request.use(async (config) => {
+  const shellToken = await getShellToken();
+  if (shellToken) config.headers[SHELL_TOKEN_HEADER] = shellToken;
   const { data: { session } } = await supabase.auth.getSession();
   if (session?.access_token) {
     config.headers.Authorization = `Bearer ${session.access_token}`;
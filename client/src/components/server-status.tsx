@@ -4,7 +4,7 @@
  */
 
 import { useState, useEffect } from 'react';
-import { invoke } from '@tauri-apps/api/core';
+import { invoke, isTauri } from '@tauri-apps/api/core';
 import { Card, CardContent, CardDescription, CardHeader, CardTitle } from '@/components/ui/card';
 import { Button } from '@/components/ui/button';
 import { Badge } from '@/components/ui/badge';
@@ -21,9 +21,7 @@ import {
 import { toast } from '@/hooks/use-toast';
 
 // Inline server health and config functions to avoid import issues during build
-const isTauriApp = (): boolean => {
-  return typeof window !== 'undefined' && '__TAURI__' in window;
-};
+const isTauriApp = (): boolean => isTauri();
 
 const getApiUrl = (): string => {
   const runtimeApiUrl = window.localStorage.getItem('RUNTIME_API_URL');
@@ -0,0 +1,33 @@
+import { invoke, isTauri } from '@tauri-apps/api/core'
+import { listen } from '@tauri-apps/api/event'
+
+/** What the shell reports while the server boots; see `boot.rs`. */
+interface BootProgress {
+  phase: string
+  message: string | null
+}
+
+const PHASES: Record<string, [string, number]> = {
+  resolvingBinary: ['Locating server…', 10],
+  spawning: ['Starting server…', 30],
+  waitingForHealth: ['Waiting for server…', 60],
+  migratingDatabase: ['Preparing database…', 85],
+  ready: ['Ready', 100],
+  failed: ['Server failed to start', 100],
+}
+
+function render({ phase, message }: BootProgress) {
+  const [label, percent] = PHASES[phase] || [phase, 50]
+  document.getElementById('status')!.textContent = label
+  document.getElementById('detail')!.textContent = message || ''
+  document.getElementById('fill')!.style.width = percent + '%'
+  document.body.classList.toggle('failed', phase === 'failed')
+}
+
+if (isTauri()) {
+  listen<BootProgress>('boot://progress', (event) => render(event.payload))
+  invoke<BootProgress>('get_boot_progress').then(render)
+  document.getElementById('continue')!.addEventListener('click', () => {
+    invoke('dismiss_splash')
+  })
+}
@@ -0,0 +1,19 @@
+import { invoke, isTauri } from '@tauri-apps/api/core'
+
+/** Header the embedded server expects the shell's shared secret in. */
+export const SHELL_TOKEN_HEADER = 'X-Nyx-Token'
+
+let tokenPromise: Promise<string | null> | null = null
+
+/**
+ * The per-launch secret the desktop shell handed to the embedded server.
+ * Resolves to null in a plain browser, where there is no shell to ask.
+ */
+export function getShellToken(): Promise<string | null> {
+  if (!tokenPromise) {
+    tokenPromise = isTauri()
+      ? invoke<string>('get_api_token').catch(() => null)
+      : Promise.resolve(null)
+  }
+  return tokenPromise
+}
@@ -18,15 +18,13 @@ export default defineConfig({
     // produce sourcemaps for debug builds
     sourcemap: !!process.env.TAURI_DEBUG,
     rollupOptions: {
+      // The splash window is a page of its own; see `boot.rs`
+      input: {
+        main: path.resolve(__dirname, 'index.html'),
+        splash: path.resolve(__dirname, 'splash.html'),
+      },
+      // `@tauri-apps/api` is bundled, as nothing provides it globally
       external: [
-        '@tauri-apps/api/app',
-        '@tauri-apps/api/core',
-        '@tauri-apps/api/event',
-        '@tauri-apps/api/path',
-        '@tauri-apps/api/process',
-        '@tauri-apps/api/shell',
-        '@tauri-apps/api/updater',
-        '@tauri-apps/api/window',
         '@tauri-apps/plugin-shell',
         '@tauri-apps/plugin-updater',
       ],
@@ -57,10 +57,17 @@ class ClientBuilder:
 
             self.logger.info(">> Copying server executable for embedding...")
             server_exe = client_path.parent / 'server' / 'dist' / 'nyx-server.exe'
-            tauri_server_path = client_path / 'src-tauri' / 'nyx-server.exe'
+            # Tauri sidecars are looked up with the target triple appended
+            host_triple = next(
+                line.split(':', 1)[1].strip()
+                for line in subprocess.check_output(['rustc', '-vV'], text=True).splitlines()
+                if line.startswith('host:')
+            )
+            tauri_server_path = client_path / 'src-tauri' / 'binaries' / f'nyx-server-{host_triple}.exe'
             
             if server_exe.exists():
                 import shutil
+                tauri_server_path.parent.mkdir(parents=True, exist_ok=True)
                 shutil.copy2(server_exe, tauri_server_path)
                 size_mb = server_exe.stat().st_size / (1024 * 1024)
                 self.logger.info(f">> Copied server executable: {size_mb:.1f} MB")
@@ -1,4 +1,4 @@
-from fastapi import FastAPI, Request, Depends, HTTPException
+from fastapi import FastAPI, Request, Depends, HTTPException, WebSocket, WebSocketDisconnect
 from fastapi.middleware.cors import CORSMiddleware
 from fastapi.responses import JSONResponse
 from fastapi.exceptions import RequestValidationError
@@ -13,6 +13,8 @@ from api.middleware.enhanced_auth import EnhancedAuthMiddleware
 from api.middleware.rate_limiter import RateLimiterMiddleware, RateLimitConfig
 # After adding CORS middleware
 from api.middleware.options_middleware import OptionsMiddleware
+from api.middleware.shell_token import ShellTokenMiddleware, require_websocket_token
+from core import events
 
 # Configure logging
 logging.basicConfig(
@@ -36,8 +38,6 @@ async def lifespan(app: FastAPI):
         except Exception as e:
             logger.error(f"Error initializing Redis: {str(e)}")
 
-    # WebSocket functionality removed - not needed for core features
-
     # Initialize profile manager
     try:
         # Use the profile manager
@@ -70,6 +70,9 @@ async def lifespan(app: FastAPI):
     # Shutdown logic
     logger.info("Shutting down FastAPI server")
 
+# Version of the HTTP API; bump the major version on breaking changes
+API_VERSION = "1.0.0"
+
 # Create FastAPI app
 app = FastAPI(
     title="Camoufox API",
@@ -97,6 +100,9 @@ app.add_middleware(
     max_age=86400,  # Cache preflight requests for 24 hours
 )
 
+# Only the desktop shell that launched us may call the API
+app.add_middleware(ShellTokenMiddleware, allowed_origins=origins)
+
 # Add options middleware to handle OPTIONS requests explicitly
 app.add_middleware(OptionsMiddleware)
 
@@ -189,6 +195,45 @@ async def health_check():
             "error": str(e)
         }
 
+# Version endpoint, checked by the desktop shell for compatibility
+@app.get("/version")
+async def version():
+    return {"version": app.version, "apiVersion": API_VERSION}
+
+# Graceful shutdown, asked for by the desktop shell before it falls back to
+# signals. Only servers the shell started, and so have its token, accept it.
+@app.post("/shutdown")
+async def shutdown(request: Request):
+    import hmac
+    from api.middleware.shell_token import TOKEN_ENV, TOKEN_HEADER
+
+    token = os.environ.get(TOKEN_ENV)
+    provided = request.headers.get(TOKEN_HEADER, "")
+    if not token or not hmac.compare_digest(provided.encode(), token.encode()):
+        raise HTTPException(status_code=403, detail="Shutdown is only accepted from the desktop shell")
+
+    # main.py hands us the uvicorn server it runs
+    server = getattr(request.app.state, "uvicorn_server", None)
+    if server is None:
+        raise HTTPException(status_code=503, detail="Not running under a shutdown-capable server")
+    logger.info("Shutdown requested by the desktop shell")
+    server.should_exit = True
+    return {"status": "shutting down"}
+
+# Push events for the desktop shell, which relays them to its webview. The
+# token middleware never sees websockets, so the dependency checks it here.
+@app.websocket("/ws")
+async def events_socket(websocket: WebSocket, _: None = Depends(require_websocket_token)):
+    await websocket.accept()
+    queue = events.subscribe()
+    try:
+        while True:
+            await websocket.send_json(await queue.get())
+    except (WebSocketDisconnect, RuntimeError):
+        pass
+    finally:
+        events.unsubscribe(queue)
+
 # Root endpoint
 @app.get("/")
 async def root():
@@ -0,0 +1,55 @@
+import hmac
+import os
+
+from fastapi import Request, WebSocket, WebSocketException, status
+from starlette.middleware.base import BaseHTTPMiddleware
+from starlette.responses import JSONResponse
+
+TOKEN_ENV = "NYX_API_TOKEN"
+TOKEN_HEADER = "X-Nyx-Token"
+
+
+class ShellTokenMiddleware(BaseHTTPMiddleware):
+    """Rejects requests that don't carry the secret the desktop shell
+    generated for this launch, so other local processes can't use the API.
+
+    Inactive when the server was not started by the shell. The rejection
+    only carries CORS headers for `allowed_origins`, the same ones the CORS
+    middleware lets in, so other pages can't read it.
+    """
+
+    def __init__(self, app, allowed_origins=()):
+        super().__init__(app)
+        self.token = os.environ.get(TOKEN_ENV)
+        self.allowed_origins = set(allowed_origins)
+
+    async def dispatch(self, request: Request, call_next):
+        if not self.token or request.method == "OPTIONS":
+            return await call_next(request)
+
+        # WebSocket clients can't always set headers, so accept a query param too
+        provided = request.headers.get(TOKEN_HEADER) or request.query_params.get("token", "")
+        if not hmac.compare_digest(provided.encode(), self.token.encode()):
+            headers = {"Vary": "Origin"}
+            origin = request.headers.get("origin")
+            if origin in self.allowed_origins:
+                headers["Access-Control-Allow-Origin"] = origin
+                headers["Access-Control-Allow-Credentials"] = "true"
+            return JSONResponse(
+                status_code=401,
+                content={"detail": "Missing or invalid shell token"},
+                headers=headers,
+            )
+        return await call_next(request)
+
+
+async def require_websocket_token(websocket: WebSocket):
+    """Dependency doing for websockets what ShellTokenMiddleware does for
+    HTTP, which it never sees: BaseHTTPMiddleware passes websocket scopes
+    straight through."""
+    token = os.environ.get(TOKEN_ENV)
+    if not token:
+        return
+    provided = websocket.headers.get(TOKEN_HEADER, "")
+    if not hmac.compare_digest(provided.encode(), token.encode()):
+        raise WebSocketException(code=status.WS_1008_POLICY_VIOLATION, reason="Missing or invalid shell token")
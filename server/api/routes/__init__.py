@@ -10,7 +10,7 @@ from .settings import router as settings_router
 from .updates import router as updates_router
 # from .numbers import router as numbers_router
 # source_router removed - functionality moved to api/utils/device.py
-# websockets_router removed - WebSocket functionality not needed for core features
+# Push events are served at /ws by api/fastapi.py, outside the /api prefix
 
 # from .detection_risk import router as detection_risk_router
 # from .scrapers import router as scrapers_router
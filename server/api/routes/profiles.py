@@ -9,7 +9,8 @@ import uuid
 import json
 import logging
 import random
-from core.profile_manager import ProfileManager, ProfileData
+from core.profile_manager import ProfileManager, ProfileData, ProfileStatus
+from core import events
 from security.auth import get_current_user, User
 
 router = APIRouter(
@@ -92,6 +93,10 @@ class ProfileResponse(BaseModel):
             metadata=profile_data.metadata
         )
 
+class ProfileStatusUpdate(BaseModel):
+    status: ProfileStatus = Field(..., description="Status of the profile's browser")
+    pid: Optional[int] = Field(None, description="Process ID of the browser, if it is running")
+
 class BatchProfileCreate(BaseModel):
     count: int = Field(5, description="Number of profiles to create", ge=1, le=20)
     base_config: Optional[ProfileConfigCreate] = Field(None, description="Base configuration for all profiles")
@@ -170,6 +175,7 @@ async def create_profile(profile: ProfileCreate, current_user: User = Depends(ge
             name=profile.name,
             config=config
         )
+        events.publish("profile-created", profileId=new_profile_data.id)
         return ProfileResponse.from_profile_data(new_profile_data)
     except Exception as e:
         logging.error(f"Error creating profile: {str(e)}")
@@ -262,6 +268,7 @@ async def update_profile(
         if not updated_profile_data:
             raise HTTPException(status_code=500, detail="Failed to update profile")
 
+        events.publish("profile-updated", profileId=profile_id)
         return ProfileResponse.from_profile_data(updated_profile_data)
     except HTTPException:
         raise
@@ -285,6 +292,7 @@ async def delete_profile(profile_id: str, current_user: User = Depends(get_curre
         if not success:
             raise HTTPException(status_code=500, detail="Failed to delete profile")
 
+        events.publish("profile-deleted", profileId=profile_id)
         return None
     except HTTPException:
         raise
@@ -467,6 +475,33 @@ async def set_browser_config(
         logging.error(f"Error setting browser config for profile {profile_id}: {str(e)}")
         raise HTTPException(status_code=500, detail=f"Failed to set browser config: {str(e)}")
 
+@router.post("/{profile_id}/status", response_model=Dict[str, Any])
+async def set_profile_status(
+    profile_id: str,
+    update: ProfileStatusUpdate,
+    current_user: User = Depends(get_current_user)
+):
+    """
+    Record the status of a browser launched outside the backend
+
+    The desktop shell starts profile browsers itself and reports their
+    lifecycle here so the profile's status stays accurate.
+    """
+    try:
+        if not await profile_manager.set_profile_status(profile_id, update.status, update.pid):
+            raise HTTPException(status_code=404, detail=f"Profile with ID {profile_id} not found")
+
+        return {
+            'success': True,
+            'profile_id': profile_id,
+            'status': update.status
+        }
+    except HTTPException:
+        raise
+    except Exception as e:
+        logging.error(f"Error setting status for profile {profile_id}: {str(e)}")
+        raise HTTPException(status_code=500, detail=f"Failed to set profile status: {str(e)}")
+
 @router.post("/{profile_id}/close", response_model=Dict[str, Any])
 async def close_profile_browser(
     profile_id: str,
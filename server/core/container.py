@@ -1,4 +1,3 @@
-from pathlib import Path
 from typing import Dict, Any
 from security.security_manager import SecurityManager
 from db.supabase import SupabaseClient
@@ -7,6 +6,7 @@ from core.monitoring.analytics import Analytics
 from core.scaling.cluster_manager import ClusterManager
 from core.scaling.load_balancer import LoadBalancer
 from core.profile_manager import ProfileManager
+from core.storage import PROFILES_DIR
 from core.proxy_manager import ProxyManager
 from core.cache_manager import CacheManager
 import os
@@ -81,11 +81,9 @@ class Container:
         if 'profile_manager' not in self._instances:
             # Create profile manager with security manager
             security_manager = self.get_security_manager()
-            data_dir = Path(self.config.get('data_dir', './sessions/storage'))
-            profiles_dir = data_dir / 'profiles'
-
+            # Wherever the desktop shell's data directory puts them
             self._instances['profile_manager'] = ProfileManager(
-                base_dir=profiles_dir,
+                base_dir=PROFILES_DIR,
                 security_manager=security_manager
             )
         return self._instances['profile_manager']
@@ -0,0 +1,33 @@
+"""Events pushed to the desktop shell over the `/ws` websocket, which it
+relays to its webview as `backend://<type>`."""
+
+import asyncio
+import logging
+from typing import Any, Dict, Set
+
+logger = logging.getLogger(__name__)
+
+# Events a client has not read yet; past this it misses the oldest ones
+QUEUE_SIZE = 100
+
+_subscribers: Set[asyncio.Queue] = set()
+
+
+def subscribe() -> asyncio.Queue:
+    queue: asyncio.Queue = asyncio.Queue(maxsize=QUEUE_SIZE)
+    _subscribers.add(queue)
+    return queue
+
+
+def unsubscribe(queue: asyncio.Queue) -> None:
+    _subscribers.discard(queue)
+
+
+def publish(event_type: str, **data: Any) -> None:
+    """Sends `{"type": event_type, ...data}` to every connected client."""
+    message: Dict[str, Any] = {"type": event_type, **data}
+    for queue in list(_subscribers):
+        if queue.full():
+            queue.get_nowait()
+            logger.debug("Dropped an event a websocket client was too slow for")
+        queue.put_nowait(message)
@@ -0,0 +1,72 @@
+"""
+Migrations of the local storage layout, run by the desktop shell with
+`main.py --migrate` before it starts the server.
+
+Progress goes to stdout as `NYX-MIGRATE <json>` lines for the shell to
+follow: `{"total": n}` first, `{"step": i, "name": ...}` before each
+migration, and `{"done": true}` or `{"error": ...}` last.
+"""
+
+import json
+import logging
+
+from core.storage import STORAGE_DIR, ensure_storage_directories
+
+logger = logging.getLogger(__name__)
+
+SCHEMA_FILE = STORAGE_DIR / "schema_version.json"
+PROGRESS_PREFIX = "NYX-MIGRATE "
+
+
+def _initial_layout():
+    ensure_storage_directories()
+
+
+# Applied in order, each once; the schema version is how many have run
+MIGRATIONS = [
+    ("initial-layout", _initial_layout),
+]
+
+
+def current_version():
+    try:
+        return int(json.loads(SCHEMA_FILE.read_text()).get("version", 0))
+    except FileNotFoundError:
+        return 0
+
+
+def _save_version(version):
+    SCHEMA_FILE.parent.mkdir(parents=True, exist_ok=True)
+    tmp = SCHEMA_FILE.with_suffix(".tmp")
+    tmp.write_text(json.dumps({"version": version}))
+    tmp.replace(SCHEMA_FILE)
+
+
+def _report(**event):
+    print(PROGRESS_PREFIX + json.dumps(event), flush=True)
+
+
+def run():
+    """Applies pending migrations and returns the process exit code."""
+    try:
+        version = current_version()
+    except (ValueError, OSError) as e:
+        _report(error=f"Unreadable {SCHEMA_FILE.name}: {e}")
+        return 1
+
+    pending = MIGRATIONS[version:]
+    _report(total=len(pending))
+    for step, (name, migrate) in enumerate(pending, start=1):
+        _report(step=step, name=name)
+        logger.info(f"Running migration {name}")
+        try:
+            migrate()
+        except Exception as e:
+            logger.exception(f"Migration {name} failed")
+            _report(error=f"{name}: {e}")
+            return 1
+        version += 1
+        _save_version(version)
+
+    _report(done=True)
+    return 0
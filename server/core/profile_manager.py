@@ -947,6 +947,37 @@ class ProfileManager:
                 'message': f'Attempted to close browser for profile {profile_id}'
             }
 
+    async def set_profile_status(self, profile_id: str, status: ProfileStatus, pid: Optional[int] = None) -> bool:
+        """
+        Record the status of a browser launched outside the backend, e.g. by the desktop shell
+
+        Args:
+            profile_id: Profile ID
+            status: New status of the profile's browser
+            pid: Process ID of the browser, if it is running
+
+        Returns:
+            True if the profile was found and saved
+        """
+        profile = await self.get_profile(profile_id)
+        if not profile:
+            return False
+
+        if profile.metadata is None:
+            profile.metadata = {}
+        now = datetime.utcnow().isoformat()
+        if status == ProfileStatus.ACTIVE:
+            profile.metadata['last_launch'] = now
+            profile.metadata['launch_count'] = profile.metadata.get('launch_count', 0) + 1
+        profile.metadata['last_used'] = now
+        profile.metadata['status'] = status
+        if pid is not None and status == ProfileStatus.ACTIVE:
+            profile.metadata['pid'] = pid
+        else:
+            profile.metadata.pop('pid', None)
+
+        return await self._save_profile(profile)
+
     async def get_actual_fingerprint(self, profile_id: str) -> Optional[Dict[str, Any]]:
         """
         Get the actual fingerprint for a profile by launching a browser instance
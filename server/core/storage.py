@@ -3,6 +3,7 @@ Storage utilities for the application.
 """
 
 import os
+import shutil
 from pathlib import Path
 import logging
 
@@ -11,21 +12,51 @@ logger = logging.getLogger(__name__)
 # Define common storage paths
 SESSIONS_DIR = Path("./sessions")
 STORAGE_DIR = SESSIONS_DIR / "storage"
-PROFILES_DIR = STORAGE_DIR / "profiles"
+# The desktop shell may keep the bulky data elsewhere, e.g. on another drive.
+# Profile records then live there too, in a folder of their own: the shell's
+# "profiles" folder holds the browsers' profile directories.
+DATA_DIR = Path(os.environ["NYX_DATA_DIR"]) if os.environ.get("NYX_DATA_DIR") else None
+LEGACY_PROFILES_DIR = STORAGE_DIR / "profiles"
+PROFILES_DIR = DATA_DIR / "records" if DATA_DIR else LEGACY_PROFILES_DIR
 LOGS_DIR = SESSIONS_DIR / "./logs"
 TEMP_DIR = SESSIONS_DIR / "./temp"
+# Shared with the desktop shell, which serves it to the UI
+EXPORTS_DIR = Path(os.environ.get("NYX_EXPORTS_DIR", SESSIONS_DIR / "exports"))
+
+def migrate_profile_records():
+    """
+    Move the profile records kept in the storage directory into the data
+    directory, the first time the server runs with one.
+    """
+    if PROFILES_DIR == LEGACY_PROFILES_DIR or not LEGACY_PROFILES_DIR.is_dir():
+        return
+    if PROFILES_DIR.is_dir() and any(PROFILES_DIR.iterdir()):
+        logger.warning(
+            f"Not moving profile records from {LEGACY_PROFILES_DIR}: {PROFILES_DIR} has records already"
+        )
+        return
+    try:
+        PROFILES_DIR.parent.mkdir(parents=True, exist_ok=True)
+        if PROFILES_DIR.is_dir():
+            PROFILES_DIR.rmdir()
+        shutil.move(str(LEGACY_PROFILES_DIR), str(PROFILES_DIR))
+        logger.info(f"Moved profile records from {LEGACY_PROFILES_DIR} to {PROFILES_DIR}")
+    except Exception as e:
+        logger.error(f"Failed to move profile records to {PROFILES_DIR}: {e}")
 
 def ensure_storage_directories():
     """
     Ensure all required storage directories exist.
     This should be called at application startup.
     """
+    migrate_profile_records()
     directories = [
         SESSIONS_DIR,
         STORAGE_DIR,
         PROFILES_DIR,
         LOGS_DIR,
-        TEMP_DIR
+        TEMP_DIR,
+        EXPORTS_DIR
     ]
     
     for directory in directories:
@@ -17,11 +17,14 @@ from api.fastapi import app as fastapi_app
 from core.container import Container
 
 # Import storage utilities
-from core.storage import ensure_storage_directories
+from core.storage import PROFILES_DIR, ensure_storage_directories
+
+# The desktop shell passes the configured log level down
+LOG_LEVEL = os.environ.get("NYX_LOG_LEVEL", "info").lower()
 
 # Configure logging
 logging.basicConfig(
-    level=logging.INFO,
+    level=getattr(logging, LOG_LEVEL.upper(), logging.INFO),
     format="%(asctime)s - %(name)s - %(levelname)s - %(message)s",
 )
 logger = logging.getLogger("api")
@@ -42,20 +45,23 @@ async def run_fastapi_server():
         with open("sessions/logs/fastapi.log", "w") as fastapi_log:
             logger.info("Created FastAPI log file")
 
-        # Start FastAPI server on port 8080
-        logger.info("Starting FastAPI server on port 8080...")
+        # The desktop shell picks a free port and passes it down
+        port = int(os.environ.get("NYX_SERVER_PORT", "8080"))
+        logger.info(f"Starting FastAPI server on port {port}...")
 
         # Configure the server
         config = uvicorn.Config(
             app=fastapi_app,
             host="0.0.0.0",
-            port=8080,
-            log_level="info",
+            port=port,
+            log_level=LOG_LEVEL,
             access_log=False,
         )
 
         # Create the server
         fastapi_server = uvicorn.Server(config)
+        # Lets /shutdown stop it gracefully
+        fastapi_app.state.uvicorn_server = fastapi_server
 
         # Run the server
         await fastapi_server.serve()
@@ -94,7 +100,7 @@ async def configure_browser_settings():
         os.environ['BROWSER_WS_PATH'] = 'browser'
         os.environ['BROWSER_HOST'] = '0.0.0.0'
         os.environ['BROWSER_HEADLESS'] = 'false'  # Show browser window on Windows
-        os.environ['BROWSER_PROFILES_DIR'] = './sessions/storage/profiles'
+        os.environ['BROWSER_PROFILES_DIR'] = str(PROFILES_DIR)
 
         # Ensure profiles directory exists
         Path(os.environ['BROWSER_PROFILES_DIR']).mkdir(parents=True, exist_ok=True)
@@ -167,6 +173,11 @@ def signal_handler(sig, _):
     sys.exit(0)
 
 if __name__ == "__main__":
+    # The desktop shell migrates storage in a separate run before starting us
+    if "--migrate" in sys.argv:
+        from core.migrations import run as run_migrations
+        sys.exit(run_migrations())
+
     # Register signal handlers
     signal.signal(signal.SIGINT, signal_handler)
     signal.signal(signal.SIGTERM, signal_handler)